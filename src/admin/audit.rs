@@ -0,0 +1,278 @@
+//! Audit trail of Admin API configuration changes.
+//!
+//! Every Admin request that changes configuration or runtime state is recorded with the
+//! caller, the outcome and a field-level diff: `PUT`/`PATCH`/`DELETE` of a resource diff
+//! the stored document, while imports, rollbacks, node drains and cache purges report an
+//! [`AuditedChanges`] with one entry per key they changed. Entries are kept in memory for
+//! `GET /apisix/admin/audit` and optionally appended to a file or etcd history prefix.
+
+use std::{
+    collections::{BTreeSet, VecDeque},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Mutex,
+    },
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use serde::Serialize;
+use serde_json::Value as JsonValue;
+use sha2::{Digest, Sha256};
+use tokio::io::AsyncWriteExt;
+
+use crate::config::{
+    etcd::{canonicalize_prefix, EtcdClientWrapper},
+    AdminAudit,
+};
+
+/// One change to a resource document, addressed by JSON pointer.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct AuditChange {
+    pub path: String,
+    /// `add`, `remove` or `replace`.
+    pub op: &'static str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub old: Option<JsonValue>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub new: Option<JsonValue>,
+}
+
+/// What the Admin API did, before an id and timestamp are assigned.
+#[derive(Debug, Clone, Serialize)]
+pub struct AuditRecord {
+    pub method: String,
+    /// Logical resource key, e.g. `routes/1`.
+    pub key: String,
+    /// Fingerprint of the API key used; the key itself is never recorded.
+    pub identity: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub client: Option<String>,
+    /// HTTP status returned to the caller.
+    pub status: u16,
+    /// Configuration version a rollback restored.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub version: Option<i64>,
+    pub changes: Vec<AuditChange>,
+}
+
+/// Keys a request changed that are only known once it ran, attached by the handler to
+/// its response as an extension.
+#[derive(Debug, Clone, Default)]
+pub struct AuditedChanges {
+    /// Configuration version a rollback restored.
+    pub version: Option<i64>,
+    pub keys: Vec<KeyChange>,
+}
+
+/// One changed key with its value before and after the change.
+#[derive(Debug, Clone)]
+pub struct KeyChange {
+    /// Logical key, e.g. `routes/1`; its first segment selects the secrets to redact.
+    pub key: String,
+    pub before: Option<JsonValue>,
+    pub after: Option<JsonValue>,
+}
+
+impl KeyChange {
+    /// A change between two stored documents; values that are not JSON are left out.
+    pub fn stored(key: String, before: Option<&[u8]>, after: Option<&[u8]>) -> Self {
+        let parse = |value: Option<&[u8]>| value.and_then(|v| serde_json::from_slice(v).ok());
+        Self {
+            key,
+            before: parse(before),
+            after: parse(after),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct AuditEntry {
+    pub id: u64,
+    /// Milliseconds since the UNIX epoch.
+    pub timestamp: u64,
+    #[serde(flatten)]
+    pub record: AuditRecord,
+}
+
+pub struct AuditLog {
+    file: Option<String>,
+    etcd_prefix: Option<String>,
+    capacity: usize,
+    entries: Mutex<VecDeque<AuditEntry>>,
+    next_id: AtomicU64,
+}
+
+impl AuditLog {
+    pub fn new(config: Option<&AdminAudit>) -> Self {
+        Self {
+            file: config.and_then(|c| c.file.clone()),
+            etcd_prefix: config
+                .and_then(|c| c.etcd_prefix.as_deref())
+                .map(canonicalize_prefix),
+            capacity: config.map_or(1000, |c| c.max_entries.max(1)),
+            entries: Mutex::new(VecDeque::new()),
+            next_id: AtomicU64::new(1),
+        }
+    }
+
+    /// Keep the entry in memory and write it to the configured sinks. Sink failures
+    /// are logged; they never fail the Admin request that was already applied.
+    pub async fn record(&self, etcd: &EtcdClientWrapper, record: AuditRecord) {
+        let entry = self.remember(record);
+        let Ok(line) = serde_json::to_vec(&entry) else {
+            return;
+        };
+        if let Some(path) = &self.file {
+            if let Err(e) = append_line(path, &line).await {
+                log::error!("Failed to write admin audit entry to '{path}': {e}");
+            }
+        }
+        if let Some(prefix) = &self.etcd_prefix {
+            let key = format!("{prefix}{:013}-{}", entry.timestamp, entry.id);
+            if let Err(e) = etcd.put_absolute(&key, line).await {
+                log::error!("Failed to store admin audit entry in etcd: {e}");
+            }
+        }
+    }
+
+    /// Assign an id and timestamp and add the entry to the in-memory history.
+    fn remember(&self, record: AuditRecord) -> AuditEntry {
+        let entry = AuditEntry {
+            id: self.next_id.fetch_add(1, Ordering::Relaxed),
+            timestamp: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_millis() as u64,
+            record,
+        };
+        {
+            let mut entries = self.entries.lock().unwrap_or_else(|e| e.into_inner());
+            while entries.len() >= self.capacity {
+                entries.pop_front();
+            }
+            entries.push_back(entry.clone());
+        }
+        entry
+    }
+
+    /// Up to `limit` entries, newest first.
+    pub fn recent(&self, limit: usize) -> Vec<AuditEntry> {
+        let entries = self.entries.lock().unwrap_or_else(|e| e.into_inner());
+        entries.iter().rev().take(limit).cloned().collect()
+    }
+}
+
+async fn append_line(path: &str, line: &[u8]) -> std::io::Result<()> {
+    let mut file = tokio::fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .await?;
+    let mut buf = Vec::with_capacity(line.len() + 1);
+    buf.extend_from_slice(line);
+    buf.push(b'\n');
+    file.write_all(&buf).await
+}
+
+/// Stable, non-reversible name for an API key.
+pub fn api_key_identity(api_key: &str) -> String {
+    let digest = Sha256::digest(api_key.as_bytes());
+    format!("key:{}", &hex::encode(digest)[..12])
+}
+
+/// Field-level differences between two versions of a document. Objects are compared
+/// key by key; any other differing value (arrays included) is one `replace`.
+pub fn diff(before: Option<&JsonValue>, after: Option<&JsonValue>) -> Vec<AuditChange> {
+    let mut changes = Vec::new();
+    diff_into(String::new(), before, after, &mut changes);
+    changes
+}
+
+fn diff_into(
+    path: String,
+    before: Option<&JsonValue>,
+    after: Option<&JsonValue>,
+    out: &mut Vec<AuditChange>,
+) {
+    match (before, after) {
+        (Some(JsonValue::Object(old)), Some(JsonValue::Object(new))) => {
+            let keys: BTreeSet<&String> = old.keys().chain(new.keys()).collect();
+            for key in keys {
+                let escaped = key.replace('~', "~0").replace('/', "~1");
+                diff_into(format!("{path}/{escaped}"), old.get(key), new.get(key), out);
+            }
+        }
+        (Some(old), Some(new)) if old == new => {}
+        (None, None) => {}
+        (old, new) => out.push(AuditChange {
+            path,
+            op: match (old, new) {
+                (None, _) => "add",
+                (_, None) => "remove",
+                _ => "replace",
+            },
+            old: old.cloned(),
+            new: new.cloned(),
+        }),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn diff_reports_field_changes() {
+        let before = json!({"uri": "/a", "priority": 1, "plugins": {"cors": {}, "a/b": 1}});
+        let after = json!({"uri": "/b", "plugins": {"cors": {}, "gzip": {"comp_level": 5}}});
+        let changes = diff(Some(&before), Some(&after));
+        let summary: Vec<(&str, &str)> = changes.iter().map(|c| (c.path.as_str(), c.op)).collect();
+        assert_eq!(
+            summary,
+            [
+                ("/plugins/a~1b", "remove"),
+                ("/plugins/gzip", "add"),
+                ("/priority", "remove"),
+                ("/uri", "replace"),
+            ]
+        );
+        assert_eq!(changes[3].old, Some(json!("/a")));
+
+        let created = diff(None, Some(&after));
+        assert_eq!(created.len(), 1);
+        assert_eq!((created[0].path.as_str(), created[0].op), ("", "add"));
+        assert!(diff(Some(&after), Some(&after)).is_empty());
+    }
+
+    #[test]
+    fn recent_entries_are_bounded_and_newest_first() {
+        let unvalidated = AuditLog::new(Some(&AdminAudit {
+            file: None,
+            etcd_prefix: None,
+            max_entries: 0,
+        }));
+        let log = AuditLog::new(Some(&AdminAudit {
+            file: None,
+            etcd_prefix: None,
+            max_entries: 2,
+        }));
+        for key in ["routes/1", "routes/2", "routes/3"] {
+            let record = AuditRecord {
+                method: "PUT".into(),
+                key: key.into(),
+                identity: api_key_identity("secret"),
+                client: None,
+                status: 200,
+                version: None,
+                changes: Vec::new(),
+            };
+            unvalidated.remember(record.clone());
+            log.remember(record);
+        }
+        assert_eq!(unvalidated.recent(10).len(), 1);
+        let keys: Vec<String> = log.recent(10).into_iter().map(|e| e.record.key).collect();
+        assert_eq!(keys, ["routes/3", "routes/2"]);
+        assert!(!api_key_identity("secret").contains("secret"));
+    }
+}
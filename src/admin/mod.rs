@@ -1,8 +1,14 @@
+mod audit;
+mod history;
+mod openapi;
+mod route_match;
+
 use std::{
-    collections::{BTreeMap, HashMap},
+    collections::{BTreeMap, HashMap, HashSet},
     error::Error,
     fmt,
     marker::PhantomData,
+    sync::Arc,
 };
 
 use async_trait::async_trait;
@@ -14,18 +20,37 @@ use pingora::{
 use serde::{de::DeserializeOwned, Deserialize, Serialize};
 use validator::Validate;
 
+use self::{
+    audit::{
+        api_key_identity, diff, AuditChange, AuditLog, AuditRecord, AuditedChanges, KeyChange,
+    },
+    history::ConfigHistory,
+    openapi::{schema_ref, value_of, OperationDoc, OPENAPI_PATH},
+    route_match::MatchRequest,
+};
 use crate::{
     config::{
         self,
         etcd::{json_to_resource, EtcdClientWrapper},
         Admin, Identifiable, Pingsix,
     },
-    core::{constant_time_eq, ProxyError},
-    plugins::{build_plugin, traffic_split},
+    core::{
+        constant_time_eq,
+        maintenance::{self, MAINTENANCE_KEY},
+        metrics, ProxyError,
+    },
+    plugins::{
+        ai_proxy, build_plugin, cache::PurgeTarget, fallback, plugin_schema, traffic_split,
+        validate_plugin_config, workflow,
+    },
     proxy::{
-        graph_mutation::{self, GraphMutationError},
+        control_plane::parse_key,
+        graph_mutation::{self, DeleteMode, GraphMutationError, ImportOutcome},
+        runtime::RUNTIME,
         ssl::ProxySSL,
+        upstream::{drain, ProxyUpstream},
     },
+    service::http::purge_cache,
     utils::response::{CommonErrors, ResponseBuilder},
 };
 
@@ -157,6 +182,8 @@ type RequestParams = BTreeMap<String, String>;
 
 // Maximum request body size for admin API (1 MB)
 const MAX_BODY_SIZE: usize = 1_048_576;
+// Maximum body size for a whole-configuration import (16 MB)
+const MAX_IMPORT_BODY_SIZE: usize = 16 * 1_048_576;
 
 /// Resource handling trait for simplified validation logic across admin APIs.
 ///
@@ -165,6 +192,11 @@ const MAX_BODY_SIZE: usize = 1_048_576;
 /// JSON deserialization, field validation, and plugin-specific validation in a single step.
 trait AdminResource: DeserializeOwned + Validate + Identifiable + Send + Sync + 'static {
     const RESOURCE_TYPE: &'static str;
+    /// Name of the resource schema in the OpenAPI document.
+    const SCHEMA_NAME: &'static str;
+
+    /// JSON Schema of the resource body, for the OpenAPI document.
+    fn schema() -> serde_json::Value;
 
     fn validate_resource(data: &[u8]) -> ApiResult<Self> {
         let resource = json_to_resource::<Self>(data)?;
@@ -191,9 +223,35 @@ fn validate_plugins(plugins: &HashMap<String, serde_json::Value>) -> ApiResult<(
     for (name, value) in plugins {
         if name == "traffic-split" {
             // Do not resolve named upstreams against the live runtime; Candidate publish owns that.
-            traffic_split::validate_traffic_split_config(value).map_err(|e| {
-                ApiError::ValidationError(format!("Failed to validate plugin '{name}': {e}"))
-            })?;
+            validate_plugin_config(name, value)
+                .and_then(|_| traffic_split::validate_traffic_split_config(value))
+                .map_err(|e| {
+                    ApiError::ValidationError(format!("Failed to validate plugin '{name}': {e}"))
+                })?;
+            continue;
+        }
+        if name == workflow::PLUGIN_NAME {
+            validate_plugin_config(name, value)
+                .and_then(|_| workflow::validate_workflow_config(value))
+                .map_err(|e| {
+                    ApiError::ValidationError(format!("Failed to validate plugin '{name}': {e}"))
+                })?;
+            continue;
+        }
+        if name == ai_proxy::PLUGIN_NAME {
+            validate_plugin_config(name, value)
+                .and_then(|_| ai_proxy::validate_ai_proxy_config(value))
+                .map_err(|e| {
+                    ApiError::ValidationError(format!("Failed to validate plugin '{name}': {e}"))
+                })?;
+            continue;
+        }
+        if name == fallback::PLUGIN_NAME {
+            validate_plugin_config(name, value)
+                .and_then(|_| fallback::validate_fallback_config(value))
+                .map_err(|e| {
+                    ApiError::ValidationError(format!("Failed to validate plugin '{name}': {e}"))
+                })?;
             continue;
         }
         build_plugin(name, value.clone()).map_err(|e| {
@@ -205,6 +263,11 @@ fn validate_plugins(plugins: &HashMap<String, serde_json::Value>) -> ApiResult<(
 
 impl AdminResource for config::Route {
     const RESOURCE_TYPE: &'static str = "routes";
+    const SCHEMA_NAME: &'static str = "Route";
+
+    fn schema() -> serde_json::Value {
+        openapi::route_schema()
+    }
 
     fn validate_plugins_if_supported(resource: &Self) -> ApiResult<()> {
         validate_plugins(&resource.plugins)
@@ -213,10 +276,20 @@ impl AdminResource for config::Route {
 
 impl AdminResource for config::Upstream {
     const RESOURCE_TYPE: &'static str = "upstreams";
+    const SCHEMA_NAME: &'static str = "Upstream";
+
+    fn schema() -> serde_json::Value {
+        openapi::upstream_schema()
+    }
 }
 
 impl AdminResource for config::Service {
     const RESOURCE_TYPE: &'static str = "services";
+    const SCHEMA_NAME: &'static str = "Service";
+
+    fn schema() -> serde_json::Value {
+        openapi::service_schema()
+    }
 
     fn validate_plugins_if_supported(resource: &Self) -> ApiResult<()> {
         validate_plugins(&resource.plugins)
@@ -225,6 +298,11 @@ impl AdminResource for config::Service {
 
 impl AdminResource for config::GlobalRule {
     const RESOURCE_TYPE: &'static str = "global_rules";
+    const SCHEMA_NAME: &'static str = "GlobalRule";
+
+    fn schema() -> serde_json::Value {
+        openapi::global_rule_schema()
+    }
 
     fn validate_plugins_if_supported(resource: &Self) -> ApiResult<()> {
         validate_plugins(&resource.plugins)
@@ -233,6 +311,11 @@ impl AdminResource for config::GlobalRule {
 
 impl AdminResource for config::SSL {
     const RESOURCE_TYPE: &'static str = "ssls";
+    const SCHEMA_NAME: &'static str = "SSL";
+
+    fn schema() -> serde_json::Value {
+        openapi::ssl_schema()
+    }
 
     fn validate_plugins_if_supported(resource: &Self) -> ApiResult<()> {
         ProxySSL::try_from(resource.clone())
@@ -241,6 +324,28 @@ impl AdminResource for config::SSL {
     }
 }
 
+impl AdminResource for config::IpList {
+    const RESOURCE_TYPE: &'static str = "ip_lists";
+    const SCHEMA_NAME: &'static str = "IpList";
+
+    fn schema() -> serde_json::Value {
+        openapi::ip_list_schema()
+    }
+}
+
+impl AdminResource for config::PluginConfig {
+    const RESOURCE_TYPE: &'static str = "plugin_configs";
+    const SCHEMA_NAME: &'static str = "PluginConfig";
+
+    fn schema() -> serde_json::Value {
+        openapi::plugin_config_schema()
+    }
+
+    fn validate_plugins_if_supported(resource: &Self) -> ApiResult<()> {
+        validate_plugins(&resource.plugins)
+    }
+}
+
 macro_rules! admin_handler {
     ($name:ident) => {
         struct $name<T: AdminResource> {
@@ -256,143 +361,1260 @@ macro_rules! admin_handler {
     };
 }
 
-admin_handler!(ResourceHandler);
-admin_handler!(GetHandler);
-admin_handler!(DeleteHandler);
-admin_handler!(ListHandler);
+admin_handler!(ResourceHandler);
+admin_handler!(GetHandler);
+admin_handler!(DeleteHandler);
+admin_handler!(ListHandler);
+
+impl<T: AdminResource> ResourceHandler<T> {
+    fn extract_key(params: &RequestParams) -> ApiResult<String> {
+        let id = params
+            .get("id")
+            .ok_or_else(|| ApiError::MissingParameter("id".into()))?;
+
+        Ok(format!("{}/{}", T::RESOURCE_TYPE, id))
+    }
+}
+
+#[async_trait]
+trait Handler {
+    async fn handle(
+        &self,
+        etcd: &EtcdClientWrapper,
+        session: &mut ServerSession,
+        params: RequestParams,
+    ) -> ApiResult<ApiResponse>;
+
+    /// `(resource_type, key)` of the resource this request changes, for the audit log.
+    fn audited_resource(&self, _params: &RequestParams) -> Option<(&'static str, String)> {
+        None
+    }
+
+    /// Whether the request is audited with the [`AuditedChanges`] the handler attaches to
+    /// its response, for changes to keys only known once it ran.
+    fn audits_changes(&self) -> bool {
+        false
+    }
+
+    /// Whether a successful request changes the resource graph (and so adds a version).
+    fn changes_config(&self) -> bool {
+        false
+    }
+
+    /// How the operation is described in the OpenAPI document.
+    fn doc(&self) -> OperationDoc;
+}
+
+// PUT handler
+#[async_trait]
+impl<T: AdminResource> Handler for ResourceHandler<T> {
+    async fn handle(
+        &self,
+        etcd: &EtcdClientWrapper,
+        http_session: &mut ServerSession,
+        params: RequestParams,
+    ) -> ApiResult<ApiResponse> {
+        http_session.validate_content_type()?;
+
+        let body_data = read_request_body(http_session)
+            .await
+            .map_err(|e| ApiError::RequestBodyReadError(e.to_string()))?;
+
+        let key = Self::extract_key(&params)?;
+
+        // Use generic resource validation
+        T::validate_resource(&body_data)?;
+
+        let committed = graph_mutation::put_resource(etcd, &key, body_data).await?;
+
+        let body = serde_json::json!({ "revision": committed });
+        Ok(ResponseBuilder::success_json(&body))
+    }
+
+    fn audited_resource(&self, params: &RequestParams) -> Option<(&'static str, String)> {
+        Some((T::RESOURCE_TYPE, Self::extract_key(params).ok()?))
+    }
+
+    fn changes_config(&self) -> bool {
+        true
+    }
+
+    fn doc(&self) -> OperationDoc {
+        OperationDoc::new(
+            T::RESOURCE_TYPE,
+            format!("Create or replace a {} by id", T::SCHEMA_NAME),
+        )
+        .request(schema_ref(T::SCHEMA_NAME))
+        .response(serde_json::json!({
+            "type": "object",
+            "properties": {"revision": {"type": "integer"}}
+        }))
+    }
+}
+
+// GET handler - separate type needed to distinguish operation types
+#[async_trait]
+impl<T: AdminResource> Handler for GetHandler<T> {
+    async fn handle(
+        &self,
+        etcd: &EtcdClientWrapper,
+        _http_session: &mut ServerSession,
+        params: RequestParams,
+    ) -> ApiResult<ApiResponse> {
+        let key = ResourceHandler::<T>::extract_key(&params)?;
+
+        match etcd.get(&key).await {
+            Err(e) => Err(ApiError::EtcdGetError(e.to_string())),
+            Ok(Some(value)) => {
+                let json_value: serde_json::Value =
+                    serde_json::from_slice(&value).map_err(|e| {
+                        ApiError::ProxyError(ProxyError::serialization_error(
+                            "Failed to parse JSON",
+                            e,
+                        ))
+                    })?;
+                let wrapper = ValueWrapper {
+                    value: redact(T::RESOURCE_TYPE, json_value),
+                };
+                Ok(ResponseBuilder::success_json(&wrapper))
+            }
+            Ok(None) => Err(ApiError::NotFound("Resource not found".into())),
+        }
+    }
+
+    fn doc(&self) -> OperationDoc {
+        OperationDoc::new(T::RESOURCE_TYPE, format!("Get a {} by id", T::SCHEMA_NAME))
+            .response(value_of(schema_ref(T::SCHEMA_NAME)))
+    }
+}
+
+// DELETE handler
+#[async_trait]
+impl<T: AdminResource> Handler for DeleteHandler<T> {
+    async fn handle(
+        &self,
+        etcd: &EtcdClientWrapper,
+        http_session: &mut ServerSession,
+        params: RequestParams,
+    ) -> ApiResult<ApiResponse> {
+        let key = ResourceHandler::<T>::extract_key(&params)?;
+        let flag = |name| query_param(http_session, name).is_some_and(|v| v == "true");
+        let mode = if flag("cascade") {
+            DeleteMode::Cascade
+        } else if flag("force") {
+            DeleteMode::Force
+        } else {
+            DeleteMode::Checked
+        };
+
+        let deleted = graph_mutation::delete_resource(etcd, &key, mode).await?;
+
+        if mode == DeleteMode::Cascade {
+            Ok(ResponseBuilder::success_json(
+                &serde_json::json!({ "deleted": deleted }),
+            ))
+        } else {
+            Ok(ResponseBuilder::success_http(Vec::new(), None))
+        }
+    }
+
+    fn audited_resource(&self, params: &RequestParams) -> Option<(&'static str, String)> {
+        Some((
+            T::RESOURCE_TYPE,
+            ResourceHandler::<T>::extract_key(params).ok()?,
+        ))
+    }
+
+    fn changes_config(&self) -> bool {
+        true
+    }
+
+    fn doc(&self) -> OperationDoc {
+        OperationDoc::new(
+            T::RESOURCE_TYPE,
+            format!("Delete a {} by id", T::SCHEMA_NAME),
+        )
+        .query(
+            "force",
+            "`true` deletes the resource even if others still reference it",
+        )
+        .query(
+            "cascade",
+            "`true` also deletes resources referencing this one",
+        )
+    }
+}
+
+// LIST handler
+#[async_trait]
+impl<T: AdminResource> Handler for ListHandler<T> {
+    async fn handle(
+        &self,
+        etcd: &EtcdClientWrapper,
+        http_session: &mut ServerSession,
+        _params: RequestParams,
+    ) -> ApiResult<ApiResponse> {
+        let selectors = label_selectors(http_session);
+        let response = etcd.list(T::RESOURCE_TYPE).await?;
+
+        let mut list_items = Vec::new();
+        for kv in response.kvs() {
+            let key = String::from_utf8_lossy(kv.key()).to_string();
+            let value: serde_json::Value = serde_json::from_slice(kv.value()).map_err(|e| {
+                ApiError::ProxyError(ProxyError::serialization_error(
+                    "Failed to parse resource JSON",
+                    e,
+                ))
+            })?;
+            if !matches_label_selectors(&value, &selectors) {
+                continue;
+            }
+
+            let item = serde_json::json!({
+                "key": key,
+                "value": redact(T::RESOURCE_TYPE, value),
+                "createdIndex": kv.create_revision(),
+                "modifiedIndex": kv.mod_revision(),
+            });
+
+            list_items.push(item);
+        }
+
+        let result = serde_json::json!({
+            "total": list_items.len(),
+            "list": list_items,
+        });
+
+        Ok(ResponseBuilder::success_json(&result))
+    }
+
+    fn doc(&self) -> OperationDoc {
+        OperationDoc::new(
+            T::RESOURCE_TYPE,
+            format!("List {} resources", T::SCHEMA_NAME),
+        )
+        .query(
+            "label",
+            "Label selector, `key` or `key:value`; repeat to require several",
+        )
+        .response(serde_json::json!({
+            "type": "object",
+            "properties": {
+                "total": {"type": "integer"},
+                "list": {"type": "array", "items": {
+                    "type": "object",
+                    "properties": {
+                        "key": {"type": "string"},
+                        "value": schema_ref(T::SCHEMA_NAME),
+                        "createdIndex": {"type": "integer"},
+                        "modifiedIndex": {"type": "integer"}
+                    }
+                }}
+            }
+        }))
+    }
+}
+
+/// Whole-configuration document used by export and import. Mirrors the resource
+/// sections of the static YAML config; every item carries its `id`.
+#[derive(Default, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ConfigDocument {
+    #[serde(default)]
+    routes: Vec<serde_json::Value>,
+    #[serde(default)]
+    upstreams: Vec<serde_json::Value>,
+    #[serde(default)]
+    services: Vec<serde_json::Value>,
+    #[serde(default)]
+    ssls: Vec<serde_json::Value>,
+    #[serde(default)]
+    global_rules: Vec<serde_json::Value>,
+    #[serde(default)]
+    ip_lists: Vec<serde_json::Value>,
+    #[serde(default)]
+    plugin_configs: Vec<serde_json::Value>,
+}
+
+impl ConfigDocument {
+    /// Build the document from a full-graph read (physical keys), sorted by key.
+    fn from_graph(
+        kvs: HashMap<String, Vec<u8>>,
+        prefix: &str,
+        include_secrets: bool,
+    ) -> ApiResult<Self> {
+        let mut entries: Vec<_> = kvs.into_iter().collect();
+        entries.sort_by(|a, b| a.0.cmp(&b.0));
+
+        let mut document = ConfigDocument::default();
+        for (key, value) in entries {
+            let Ok((id, resource_type)) = parse_key(key.as_bytes(), Some(prefix)) else {
+                continue;
+            };
+            let Some(section) = document.section_mut(&resource_type) else {
+                continue;
+            };
+            let mut item: serde_json::Value = serde_json::from_slice(&value).map_err(|e| {
+                ApiError::ProxyError(ProxyError::serialization_error(
+                    "Failed to parse resource JSON",
+                    e,
+                ))
+            })?;
+            if let Some(obj) = item.as_object_mut() {
+                obj.insert("id".into(), serde_json::Value::String(id));
+            }
+            section.push(if include_secrets {
+                item
+            } else {
+                redact(&resource_type, item)
+            });
+        }
+        Ok(document)
+    }
+
+    fn section_mut(&mut self, resource_type: &str) -> Option<&mut Vec<serde_json::Value>> {
+        match resource_type {
+            "routes" => Some(&mut self.routes),
+            "upstreams" => Some(&mut self.upstreams),
+            "services" => Some(&mut self.services),
+            "ssls" => Some(&mut self.ssls),
+            "global_rules" => Some(&mut self.global_rules),
+            "ip_lists" => Some(&mut self.ip_lists),
+            "plugin_configs" => Some(&mut self.plugin_configs),
+            _ => None,
+        }
+    }
+
+    /// Validate every resource and return `(logical_key, body)` pairs ready to store.
+    fn into_resources(self) -> ApiResult<Vec<(String, Vec<u8>)>> {
+        let mut resources = Vec::new();
+        import_section::<config::Route>(self.routes, &mut resources)?;
+        import_section::<config::Upstream>(self.upstreams, &mut resources)?;
+        import_section::<config::Service>(self.services, &mut resources)?;
+        import_section::<config::SSL>(self.ssls, &mut resources)?;
+        import_section::<config::GlobalRule>(self.global_rules, &mut resources)?;
+        import_section::<config::IpList>(self.ip_lists, &mut resources)?;
+        import_section::<config::PluginConfig>(self.plugin_configs, &mut resources)?;
+        Ok(resources)
+    }
+}
+
+fn import_section<T: AdminResource>(
+    items: Vec<serde_json::Value>,
+    out: &mut Vec<(String, Vec<u8>)>,
+) -> ApiResult<()> {
+    let mut seen = HashSet::new();
+    for item in items {
+        let id = item
+            .get("id")
+            .and_then(|id| id.as_str())
+            .filter(|id| !id.is_empty() && !id.contains('/'))
+            .map(str::to_string)
+            .ok_or_else(|| {
+                ApiError::ValidationError(format!(
+                    "Every item in '{}' needs a non-empty 'id' without '/'",
+                    T::RESOURCE_TYPE
+                ))
+            })?;
+        if !seen.insert(id.clone()) {
+            return Err(ApiError::ValidationError(format!(
+                "Duplicate id '{id}' in '{}'",
+                T::RESOURCE_TYPE
+            )));
+        }
+        if has_redacted_placeholder(&item, &redact(T::RESOURCE_TYPE, item.clone())) {
+            return Err(ApiError::ValidationError(format!(
+                "{} '{id}' contains redacted secrets; export with include_secrets=true",
+                T::RESOURCE_TYPE
+            )));
+        }
+        let body = serde_json::to_vec(&item).map_err(|e| {
+            ApiError::ProxyError(ProxyError::serialization_error(
+                "Failed to encode resource",
+                e,
+            ))
+        })?;
+        T::validate_resource(&body)
+            .map_err(|e| ApiError::ValidationError(format!("{} '{id}': {e}", T::RESOURCE_TYPE)))?;
+        out.push((format!("{}/{id}", T::RESOURCE_TYPE), body));
+    }
+    Ok(())
+}
+
+/// Whether a secret field of `original` still holds the redaction placeholder, i.e. the
+/// document came from a redacted export.
+fn has_redacted_placeholder(original: &serde_json::Value, redacted: &serde_json::Value) -> bool {
+    match (original, redacted) {
+        (serde_json::Value::Object(a), serde_json::Value::Object(b)) => a
+            .iter()
+            .any(|(k, v)| b.get(k).is_some_and(|r| has_redacted_placeholder(v, r))),
+        (serde_json::Value::Array(a), serde_json::Value::Array(b)) => {
+            a.iter().zip(b).any(|(v, r)| has_redacted_placeholder(v, r))
+        }
+        (serde_json::Value::String(a), serde_json::Value::String(b)) => {
+            a == REDACTED && b == REDACTED
+        }
+        _ => false,
+    }
+}
+
+/// Look up a query string parameter of the admin request.
+fn query_param(http_session: &ServerSession, name: &str) -> Option<String> {
+    let query = http_session.req_header().uri.query()?;
+    url::form_urlencoded::parse(query.as_bytes())
+        .find(|(key, _)| key == name)
+        .map(|(_, value)| value.into_owned())
+}
+
+/// `label` query parameters of a list request: `key` requires the label, `key:value`
+/// also its value. Every selector must match.
+fn label_selectors(http_session: &ServerSession) -> Vec<(String, Option<String>)> {
+    let Some(query) = http_session.req_header().uri.query() else {
+        return Vec::new();
+    };
+    url::form_urlencoded::parse(query.as_bytes())
+        .filter(|(key, _)| key == "label")
+        .map(|(_, selector)| match selector.split_once(':') {
+            Some((key, value)) => (key.to_string(), Some(value.to_string())),
+            None => (selector.into_owned(), None),
+        })
+        .collect()
+}
+
+fn matches_label_selectors(
+    resource: &serde_json::Value,
+    selectors: &[(String, Option<String>)],
+) -> bool {
+    selectors.iter().all(|(key, value)| {
+        let label = resource
+            .get("labels")
+            .and_then(|labels| labels.get(key))
+            .and_then(serde_json::Value::as_str);
+        match value {
+            Some(value) => label == Some(value.as_str()),
+            None => label.is_some(),
+        }
+    })
+}
+
+// EXPORT handler: GET /apisix/admin/export[?format=yaml][&include_secrets=true]
+struct ExportHandler;
+
+#[async_trait]
+impl Handler for ExportHandler {
+    async fn handle(
+        &self,
+        etcd: &EtcdClientWrapper,
+        http_session: &mut ServerSession,
+        _params: RequestParams,
+    ) -> ApiResult<ApiResponse> {
+        let yaml = query_param(http_session, "format").is_some_and(|f| f == "yaml");
+        let include_secrets =
+            query_param(http_session, "include_secrets").is_some_and(|v| v == "true");
+
+        let graph = etcd.read_full_graph().await?;
+        let document = ConfigDocument::from_graph(graph.kvs, etcd.prefix(), include_secrets)?;
+
+        if yaml {
+            let body = serde_yml::to_string(&document).map_err(|e| {
+                ApiError::ProxyError(ProxyError::serialization_error("Failed to encode YAML", e))
+            })?;
+            Ok(ResponseBuilder::success_http(
+                body.into_bytes(),
+                Some("application/yaml"),
+            ))
+        } else {
+            Ok(ResponseBuilder::success_json(&document))
+        }
+    }
+
+    fn doc(&self) -> OperationDoc {
+        OperationDoc::new("config", "Export the whole configuration")
+            .query("format", "`yaml` for a YAML document instead of JSON")
+            .query(
+                "include_secrets",
+                "`true` keeps secrets instead of redacting them",
+            )
+    }
+}
+
+// IMPORT handler: POST /apisix/admin/import[?mode=replace]
+struct ImportHandler;
+
+#[async_trait]
+impl Handler for ImportHandler {
+    async fn handle(
+        &self,
+        etcd: &EtcdClientWrapper,
+        http_session: &mut ServerSession,
+        _params: RequestParams,
+    ) -> ApiResult<ApiResponse> {
+        let content_type = http_session
+            .get_header(header::CONTENT_TYPE)
+            .and_then(|v| v.to_str().ok())
+            .unwrap_or("")
+            .to_string();
+        let yaml = is_yaml_content_type(&content_type);
+        if !yaml && !is_json_content_type(&content_type) {
+            return Err(ApiError::InvalidRequest(
+                "Content-Type must be application/json or application/yaml".into(),
+            ));
+        }
+        let replace = match query_param(http_session, "mode").as_deref() {
+            None | Some("merge") => false,
+            Some("replace") => true,
+            Some(other) => {
+                return Err(ApiError::InvalidRequest(format!(
+                    "Unknown import mode '{other}', expected 'merge' or 'replace'"
+                )))
+            }
+        };
+
+        let body_data = read_request_body_limited(http_session, MAX_IMPORT_BODY_SIZE).await?;
+        let document: ConfigDocument = if yaml {
+            serde_yml::from_slice(&body_data)
+                .map_err(|e| ApiError::ValidationError(format!("Invalid YAML document: {e}")))?
+        } else {
+            serde_json::from_slice(&body_data)
+                .map_err(|e| ApiError::ValidationError(format!("Invalid JSON document: {e}")))?
+        };
+
+        let outcome =
+            graph_mutation::import_resources(etcd, document.into_resources()?, replace).await?;
+
+        let body = serde_json::json!({
+            "revision": outcome.revision,
+            "written": outcome.written,
+            "unchanged": outcome.unchanged,
+            "deleted": outcome.deleted,
+        });
+        Ok(with_audited(
+            ResponseBuilder::success_json(&body),
+            imported_changes(&outcome, None),
+        ))
+    }
+
+    fn audits_changes(&self) -> bool {
+        true
+    }
+
+    fn changes_config(&self) -> bool {
+        true
+    }
+
+    fn doc(&self) -> OperationDoc {
+        OperationDoc::new("config", "Import a whole-configuration document")
+            .query(
+                "mode",
+                "`merge` (default) or `replace` to delete resources not in the document",
+            )
+            .request(serde_json::json!({"type": "object"}))
+    }
+}
+
+/// One resource of a batch write.
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct BatchItem {
+    #[serde(rename = "type")]
+    resource_type: String,
+    id: String,
+    value: serde_json::Value,
+}
+
+/// Body of `PUT /apisix/admin/batch`.
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct BatchRequest {
+    resources: Vec<BatchItem>,
+}
+
+impl BatchRequest {
+    /// Sort the items into a document so they are validated like an import.
+    fn into_document(self) -> ApiResult<ConfigDocument> {
+        if self.resources.is_empty() {
+            return Err(ApiError::ValidationError(
+                "'resources' must not be empty".into(),
+            ));
+        }
+        let mut document = ConfigDocument::default();
+        for item in self.resources {
+            let serde_json::Value::Object(mut value) = item.value else {
+                return Err(ApiError::ValidationError(format!(
+                    "{} '{}': 'value' must be an object",
+                    item.resource_type, item.id
+                )));
+            };
+            if value
+                .get("id")
+                .is_some_and(|id| id.as_str() != Some(item.id.as_str()))
+            {
+                return Err(ApiError::ValidationError(format!(
+                    "{} '{}': 'value.id' differs from 'id'",
+                    item.resource_type, item.id
+                )));
+            }
+            value.insert("id".into(), serde_json::Value::String(item.id));
+            document
+                .section_mut(&item.resource_type)
+                .ok_or_else(|| {
+                    ApiError::ValidationError(format!(
+                        "Unknown resource type '{}'",
+                        item.resource_type
+                    ))
+                })?
+                .push(serde_json::Value::Object(value));
+        }
+        Ok(document)
+    }
+}
+
+/// Response of a batch write, carrying its changes for the audit log.
+fn batch_response(outcome: &ImportOutcome, keys: Vec<String>) -> ApiResponse {
+    let body = serde_json::json!({
+        "revision": outcome.revision,
+        "written": outcome.written,
+        "unchanged": outcome.unchanged,
+        "keys": keys,
+    });
+    with_audited(
+        ResponseBuilder::success_json(&body),
+        imported_changes(outcome, None),
+    )
+}
+
+// BATCH handler: PUT /apisix/admin/batch
+struct BatchHandler;
+
+#[async_trait]
+impl Handler for BatchHandler {
+    async fn handle(
+        &self,
+        etcd: &EtcdClientWrapper,
+        http_session: &mut ServerSession,
+        _params: RequestParams,
+    ) -> ApiResult<ApiResponse> {
+        http_session.validate_content_type()?;
+
+        let body_data = read_request_body_limited(http_session, MAX_IMPORT_BODY_SIZE).await?;
+        let request: BatchRequest = serde_json::from_slice(&body_data)
+            .map_err(|e| ApiError::ValidationError(format!("Invalid batch request: {e}")))?;
+        let resources = request.into_document()?.into_resources()?;
+        let keys: Vec<String> = resources.iter().map(|(key, _)| key.clone()).collect();
+
+        let outcome = graph_mutation::import_resources(etcd, resources, false).await?;
+        Ok(batch_response(&outcome, keys))
+    }
+
+    fn audits_changes(&self) -> bool {
+        true
+    }
+
+    fn changes_config(&self) -> bool {
+        true
+    }
+
+    fn doc(&self) -> OperationDoc {
+        OperationDoc::new(
+            "config",
+            "Create or replace several resources in one transaction",
+        )
+        .request(serde_json::json!({
+            "type": "object",
+            "required": ["resources"],
+            "properties": {"resources": {"type": "array", "items": {
+                "type": "object",
+                "required": ["type", "id", "value"],
+                "properties": {
+                    "type": {"type": "string"},
+                    "id": {"type": "string"},
+                    "value": {"type": "object"}
+                }
+            }}}
+        }))
+        .response(serde_json::json!({
+            "type": "object",
+            "properties": {
+                "revision": {"type": ["integer", "null"]},
+                "written": {"type": "integer"},
+                "unchanged": {"type": "integer"},
+                "keys": {"type": "array", "items": {"type": "string"}}
+            }
+        }))
+    }
+}
+
+// VERSIONS handler: GET /apisix/admin/versions
+struct VersionsHandler {
+    history: Arc<ConfigHistory>,
+}
+
+#[async_trait]
+impl Handler for VersionsHandler {
+    async fn handle(
+        &self,
+        etcd: &EtcdClientWrapper,
+        _http_session: &mut ServerSession,
+        _params: RequestParams,
+    ) -> ApiResult<ApiResponse> {
+        let mut versions = self.history.versions(etcd).await?;
+        versions.reverse();
+        Ok(ResponseBuilder::success_json(&serde_json::json!({
+            "total": versions.len(),
+            "versions": versions,
+        })))
+    }
+
+    fn doc(&self) -> OperationDoc {
+        OperationDoc::new("config", "List stored configuration versions")
+    }
+}
+
+// ROLLBACK handler: POST /apisix/admin/rollback/{version}
+struct RollbackHandler {
+    history: Arc<ConfigHistory>,
+}
+
+#[async_trait]
+impl Handler for RollbackHandler {
+    async fn handle(
+        &self,
+        etcd: &EtcdClientWrapper,
+        _http_session: &mut ServerSession,
+        params: RequestParams,
+    ) -> ApiResult<ApiResponse> {
+        let version = params
+            .get("version")
+            .ok_or_else(|| ApiError::MissingParameter("version".into()))?;
+        let version = version
+            .parse::<i64>()
+            .map_err(|_| ApiError::InvalidRequest(format!("Invalid version '{version}'")))?;
+
+        let snapshot = self.history.load(etcd, version).await?;
+        let outcome =
+            graph_mutation::import_resources(etcd, snapshot.document.into_resources()?, true)
+                .await?;
+
+        let body = serde_json::json!({
+            "version": snapshot.version,
+            "revision": outcome.revision,
+            "written": outcome.written,
+            "unchanged": outcome.unchanged,
+            "deleted": outcome.deleted,
+        });
+        Ok(with_audited(
+            ResponseBuilder::success_json(&body),
+            imported_changes(&outcome, Some(snapshot.version)),
+        ))
+    }
+
+    fn audits_changes(&self) -> bool {
+        true
+    }
+
+    fn changes_config(&self) -> bool {
+        true
+    }
+
+    fn doc(&self) -> OperationDoc {
+        OperationDoc::new("config", "Roll the configuration back to a stored version")
+    }
+}
+
+/// Body of `POST /apisix/admin/cache/purge`; exactly one field must be set.
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct PurgeRequest {
+    key: Option<String>,
+    prefix: Option<String>,
+    route_id: Option<String>,
+}
+
+impl PurgeRequest {
+    fn into_target(self) -> ApiResult<PurgeTarget> {
+        match (self.key, self.prefix, self.route_id) {
+            (Some(key), None, None) => Ok(PurgeTarget::Key(key)),
+            (None, Some(prefix), None) if !prefix.is_empty() => Ok(PurgeTarget::Prefix(prefix)),
+            (None, None, Some(route_id)) => Ok(PurgeTarget::Route(route_id)),
+            _ => Err(ApiError::ValidationError(
+                "Purge requires exactly one of 'key', a non-empty 'prefix' or 'route_id'".into(),
+            )),
+        }
+    }
+}
+
+// CACHE PURGE handler: POST /apisix/admin/cache/purge
+struct CachePurgeHandler;
+
+#[async_trait]
+impl Handler for CachePurgeHandler {
+    async fn handle(
+        &self,
+        _etcd: &EtcdClientWrapper,
+        http_session: &mut ServerSession,
+        _params: RequestParams,
+    ) -> ApiResult<ApiResponse> {
+        http_session.validate_content_type()?;
+
+        let body_data = read_request_body(http_session)
+            .await
+            .map_err(|e| ApiError::RequestBodyReadError(e.to_string()))?;
+        let request: PurgeRequest = serde_json::from_slice(&body_data)
+            .map_err(|e| ApiError::ValidationError(format!("Invalid purge request: {e}")))?;
+
+        let target = request.into_target()?;
+        let purged = match &target {
+            PurgeTarget::Key(key) => serde_json::json!({ "key": key }),
+            PurgeTarget::Prefix(prefix) => serde_json::json!({ "prefix": prefix }),
+            PurgeTarget::Route(route_id) => serde_json::json!({ "route_id": route_id }),
+        };
+        let purged_entries = purge_cache(&target).await;
+        Ok(with_audited(
+            // The cache is per process, so is the purge.
+            ResponseBuilder::success_json(&serde_json::json!({
+                "purged": purged_entries,
+                "scope": "local",
+            })),
+            AuditedChanges {
+                version: None,
+                keys: vec![KeyChange {
+                    key: "cache/purge".into(),
+                    before: None,
+                    after: Some(purged),
+                }],
+            },
+        ))
+    }
+
+    fn audits_changes(&self) -> bool {
+        true
+    }
+
+    fn doc(&self) -> OperationDoc {
+        OperationDoc::new("cache", "Purge cached responses by key, prefix or route").request(
+            serde_json::json!({
+                "type": "object",
+                "properties": {
+                    "key": {"type": "string"},
+                    "prefix": {"type": "string", "minLength": 1},
+                    "route_id": {"type": "string"}
+                }
+            }),
+        )
+    }
+}
+
+/// The published named upstream `params["id"]` and the node key `params["node"]`, which
+/// may be percent-encoded (e.g. `%5B%3A%3A1%5D%3A80` for `[::1]:80`).
+fn runtime_upstream_node(params: &RequestParams) -> ApiResult<(Arc<ProxyUpstream>, String)> {
+    let id = params
+        .get("id")
+        .ok_or_else(|| ApiError::MissingParameter("id".into()))?;
+    let node = params
+        .get("node")
+        .ok_or_else(|| ApiError::MissingParameter("node".into()))?;
+    let node = percent_encoding::percent_decode_str(node)
+        .decode_utf8()
+        .map_err(|_| ApiError::InvalidRequest(format!("Node key '{node}' is not valid UTF-8")))?
+        .into_owned();
+
+    let upstream = RUNTIME
+        .load()
+        .upstreams
+        .get(id)
+        .cloned()
+        .ok_or_else(|| ApiError::NotFound(format!("Upstream '{id}'")))?;
+    if !upstream.inner.nodes.contains_key(&node) {
+        return Err(ApiError::NotFound(format!(
+            "Node '{node}' of upstream '{id}'"
+        )));
+    }
+    Ok((upstream, node))
+}
+
+// NODE DRAIN handler: POST /apisix/admin/upstreams/{id}/nodes/{node}/{drain,enable}
+struct NodeDrainHandler {
+    drained: bool,
+}
+
+#[async_trait]
+impl Handler for NodeDrainHandler {
+    async fn handle(
+        &self,
+        _etcd: &EtcdClientWrapper,
+        _http_session: &mut ServerSession,
+        params: RequestParams,
+    ) -> ApiResult<ApiResponse> {
+        let (upstream, node) = runtime_upstream_node(&params)?;
+        let changed = drain::set_drained(&upstream.inner.id, &node, self.drained);
+        upstream.apply_drains();
+        let audited = KeyChange {
+            key: format!("upstreams/{}/nodes/{node}", upstream.inner.id),
+            before: Some(serde_json::json!({ "drained": self.drained != changed })),
+            after: Some(serde_json::json!({ "drained": self.drained })),
+        };
+        log::info!(
+            "Node '{node}' of upstream '{}' {} via Admin API",
+            upstream.inner.id,
+            if self.drained {
+                "drained"
+            } else {
+                "re-enabled"
+            }
+        );
+        Ok(with_audited(
+            ResponseBuilder::success_json(&serde_json::json!({
+                "upstream_id": upstream.inner.id,
+                "node": node,
+                "drained": self.drained,
+                "changed": changed,
+                "scope": "local",
+            })),
+            AuditedChanges {
+                version: None,
+                keys: vec![audited],
+            },
+        ))
+    }
+
+    fn audits_changes(&self) -> bool {
+        true
+    }
+
+    fn doc(&self) -> OperationDoc {
+        let summary = if self.drained {
+            "Drain a node of a published upstream"
+        } else {
+            "Re-enable a drained node of a published upstream"
+        };
+        OperationDoc::new("upstreams", summary)
+    }
+}
+
+// NODES handler: GET /apisix/admin/upstreams/{id}/nodes
+struct NodesHandler;
+
+#[async_trait]
+impl Handler for NodesHandler {
+    async fn handle(
+        &self,
+        _etcd: &EtcdClientWrapper,
+        _http_session: &mut ServerSession,
+        params: RequestParams,
+    ) -> ApiResult<ApiResponse> {
+        let id = params
+            .get("id")
+            .ok_or_else(|| ApiError::MissingParameter("id".into()))?;
+        let runtime = RUNTIME.load();
+        let upstream = runtime
+            .upstreams
+            .get(id)
+            .ok_or_else(|| ApiError::NotFound(format!("Upstream '{id}'")))?;
+        let nodes: BTreeMap<&String, serde_json::Value> = upstream
+            .inner
+            .nodes
+            .iter()
+            .map(|(node, weight)| {
+                let drained = drain::is_drained(id, node);
+                (
+                    node,
+                    serde_json::json!({ "weight": weight, "drained": drained }),
+                )
+            })
+            .collect();
+        Ok(ResponseBuilder::success_json(&serde_json::json!({
+            "upstream_id": id,
+            "nodes": nodes,
+        })))
+    }
+
+    fn doc(&self) -> OperationDoc {
+        OperationDoc::new("upstreams", "List the nodes of a published upstream")
+    }
+}
+
+// NODES PATCH handler: PATCH /apisix/admin/upstreams/{id}/nodes
+struct NodesPatchHandler;
+
+impl NodesPatchHandler {
+    fn extract_key(params: &RequestParams) -> ApiResult<String> {
+        ResourceHandler::<config::Upstream>::extract_key(params)
+    }
+}
+
+/// Apply a nodes patch to a stored upstream body: a weight adds or reweights a node,
+/// `null` removes it together with its priority and zone.
+fn patch_nodes(
+    stored: &[u8],
+    patch: &BTreeMap<String, Option<u32>>,
+) -> Result<Vec<u8>, GraphMutationError> {
+    let invalid = |msg: String| GraphMutationError::InvalidCandidate(msg);
+    let mut upstream: serde_json::Value = serde_json::from_slice(stored)
+        .map_err(|e| invalid(format!("Stored upstream is not valid JSON: {e}")))?;
+    let nodes = upstream
+        .get_mut("nodes")
+        .and_then(serde_json::Value::as_object_mut)
+        .ok_or_else(|| invalid("Stored upstream has no nodes".into()))?;
+    for (node, weight) in patch {
+        match weight {
+            Some(weight) => {
+                nodes.insert(node.clone(), (*weight).into());
+            }
+            None if nodes.remove(node).is_none() => {
+                return Err(GraphMutationError::NotFound(format!("Node '{node}'")));
+            }
+            None => {}
+        }
+    }
+    if nodes.is_empty() {
+        return Err(invalid("An upstream needs at least one node".into()));
+    }
+    for pointer in ["/priorities", "/zone_aware/zones"] {
+        if let Some(labels) = upstream
+            .pointer_mut(pointer)
+            .and_then(serde_json::Value::as_object_mut)
+        {
+            labels.retain(|node, _| !matches!(patch.get(node), Some(None)));
+        }
+    }
+
+    let body = serde_json::to_vec(&upstream)
+        .map_err(|e| invalid(format!("Failed to serialize upstream: {e}")))?;
+    config::Upstream::validate_resource(&body).map_err(|e| invalid(e.to_string()))?;
+    Ok(body)
+}
+
+#[async_trait]
+impl Handler for NodesPatchHandler {
+    async fn handle(
+        &self,
+        etcd: &EtcdClientWrapper,
+        http_session: &mut ServerSession,
+        params: RequestParams,
+    ) -> ApiResult<ApiResponse> {
+        http_session.validate_content_type()?;
+
+        let body_data = read_request_body(http_session)
+            .await
+            .map_err(|e| ApiError::RequestBodyReadError(e.to_string()))?;
+        let patch: BTreeMap<String, Option<u32>> =
+            serde_json::from_slice(&body_data).map_err(|e| {
+                ApiError::InvalidRequest(format!("Expected an object of node weights or null: {e}"))
+            })?;
+        if patch.is_empty() {
+            return Err(ApiError::InvalidRequest("No nodes to update".into()));
+        }
+
+        let key = Self::extract_key(&params)?;
+        let committed =
+            graph_mutation::update_resource(etcd, &key, |stored| patch_nodes(stored, &patch))
+                .await?;
+
+        let body = serde_json::json!({ "revision": committed });
+        Ok(ResponseBuilder::success_json(&body))
+    }
 
-impl<T: AdminResource> ResourceHandler<T> {
-    fn extract_key(params: &RequestParams) -> ApiResult<String> {
-        let id = params
-            .get("id")
-            .ok_or_else(|| ApiError::MissingParameter("id".into()))?;
+    fn audited_resource(&self, params: &RequestParams) -> Option<(&'static str, String)> {
+        Some(("upstreams", Self::extract_key(params).ok()?))
+    }
 
-        Ok(format!("{}/{}", T::RESOURCE_TYPE, id))
+    fn changes_config(&self) -> bool {
+        true
+    }
+
+    fn doc(&self) -> OperationDoc {
+        OperationDoc::new(
+            "upstreams",
+            "Add, reweight or remove nodes of an upstream in place",
+        )
+        .request(serde_json::json!({
+            "type": "object",
+            "additionalProperties": {"type": ["integer", "null"], "minimum": 1}
+        }))
+        .response(serde_json::json!({
+            "type": "object",
+            "properties": {"revision": {"type": "integer"}}
+        }))
     }
 }
 
+// PLUGIN SCHEMA handler: GET /apisix/admin/plugins/{name}/schema
+struct PluginSchemaHandler;
+
 #[async_trait]
-trait Handler {
+impl Handler for PluginSchemaHandler {
     async fn handle(
         &self,
-        etcd: &EtcdClientWrapper,
-        session: &mut ServerSession,
+        _etcd: &EtcdClientWrapper,
+        _http_session: &mut ServerSession,
         params: RequestParams,
-    ) -> ApiResult<ApiResponse>;
+    ) -> ApiResult<ApiResponse> {
+        let name = params
+            .get("name")
+            .ok_or_else(|| ApiError::MissingParameter("name".into()))?;
+        let schema =
+            plugin_schema(name).ok_or_else(|| ApiError::NotFound(format!("Plugin '{name}'")))?;
+        Ok(ResponseBuilder::success_json(&schema))
+    }
+
+    fn doc(&self) -> OperationDoc {
+        OperationDoc::new("plugins", "Get the configuration schema of a plugin")
+            .response(serde_json::json!({"type": "object"}))
+    }
 }
 
-// PUT handler
+// ROUTE MATCH handler: POST /apisix/admin/routes/match
+struct RouteMatchHandler;
+
 #[async_trait]
-impl<T: AdminResource> Handler for ResourceHandler<T> {
+impl Handler for RouteMatchHandler {
     async fn handle(
         &self,
-        etcd: &EtcdClientWrapper,
+        _etcd: &EtcdClientWrapper,
         http_session: &mut ServerSession,
-        params: RequestParams,
+        _params: RequestParams,
     ) -> ApiResult<ApiResponse> {
         http_session.validate_content_type()?;
 
         let body_data = read_request_body(http_session)
             .await
             .map_err(|e| ApiError::RequestBodyReadError(e.to_string()))?;
+        let request: MatchRequest = serde_json::from_slice(&body_data)
+            .map_err(|e| ApiError::ValidationError(format!("Invalid match request: {e}")))?;
 
-        let key = Self::extract_key(&params)?;
-
-        // Use generic resource validation
-        T::validate_resource(&body_data)?;
-
-        let committed = graph_mutation::put_resource(etcd, &key, body_data).await?;
+        Ok(ResponseBuilder::success_json(&route_match::explain(
+            &request,
+        )?))
+    }
 
-        let body = serde_json::json!({ "revision": committed });
-        Ok(ResponseBuilder::success_json(&body))
+    fn doc(&self) -> OperationDoc {
+        OperationDoc::new("routes", "Explain which route a request would match")
     }
 }
 
-// GET handler - separate type needed to distinguish operation types
+// AUDIT handler: GET /apisix/admin/audit[?limit=N]
+struct AuditHandler {
+    log: Arc<AuditLog>,
+}
+
 #[async_trait]
-impl<T: AdminResource> Handler for GetHandler<T> {
+impl Handler for AuditHandler {
     async fn handle(
         &self,
-        etcd: &EtcdClientWrapper,
-        _http_session: &mut ServerSession,
-        params: RequestParams,
+        _etcd: &EtcdClientWrapper,
+        http_session: &mut ServerSession,
+        _params: RequestParams,
     ) -> ApiResult<ApiResponse> {
-        let key = ResourceHandler::<T>::extract_key(&params)?;
+        let limit = match query_param(http_session, "limit") {
+            Some(limit) => limit
+                .parse::<usize>()
+                .map_err(|_| ApiError::InvalidRequest(format!("Invalid limit '{limit}'")))?,
+            None => 100,
+        };
+        let entries = self.log.recent(limit);
+        Ok(ResponseBuilder::success_json(&serde_json::json!({
+            "total": entries.len(),
+            "list": entries,
+        })))
+    }
 
-        match etcd.get(&key).await {
-            Err(e) => Err(ApiError::EtcdGetError(e.to_string())),
-            Ok(Some(value)) => {
-                let json_value: serde_json::Value =
-                    serde_json::from_slice(&value).map_err(|e| {
-                        ApiError::ProxyError(ProxyError::serialization_error(
-                            "Failed to parse JSON",
-                            e,
-                        ))
-                    })?;
-                let wrapper = ValueWrapper {
-                    value: redact(T::RESOURCE_TYPE, json_value),
-                };
-                Ok(ResponseBuilder::success_json(&wrapper))
-            }
-            Ok(None) => Err(ApiError::NotFound("Resource not found".into())),
-        }
+    fn doc(&self) -> OperationDoc {
+        OperationDoc::new("audit", "List recent Admin API changes")
+            .query("limit", "Number of entries, 100 by default")
     }
 }
 
-// DELETE handler
+// MAINTENANCE handler: GET /apisix/admin/maintenance
+struct MaintenanceHandler;
+
 #[async_trait]
-impl<T: AdminResource> Handler for DeleteHandler<T> {
+impl Handler for MaintenanceHandler {
     async fn handle(
         &self,
-        etcd: &EtcdClientWrapper,
+        _etcd: &EtcdClientWrapper,
         _http_session: &mut ServerSession,
-        params: RequestParams,
+        _params: RequestParams,
     ) -> ApiResult<ApiResponse> {
-        let key = ResourceHandler::<T>::extract_key(&params)?;
-
-        graph_mutation::delete_resource(etcd, &key).await?;
+        // The switch in effect, which may come from the static configuration.
+        let current = maintenance::current();
+        Ok(ResponseBuilder::success_json(&serde_json::json!({
+            "value": current.as_deref(),
+            "active": current.is_some_and(|m| m.enabled),
+        })))
+    }
 
-        Ok(ResponseBuilder::success_http(Vec::new(), None))
+    fn doc(&self) -> OperationDoc {
+        OperationDoc::new("maintenance", "Get the maintenance switch in effect")
     }
 }
 
-// LIST handler
+// MAINTENANCE UPDATE handler: PUT/DELETE /apisix/admin/maintenance
+struct MaintenanceUpdateHandler;
+
 #[async_trait]
-impl<T: AdminResource> Handler for ListHandler<T> {
+impl Handler for MaintenanceUpdateHandler {
     async fn handle(
         &self,
         etcd: &EtcdClientWrapper,
-        _http_session: &mut ServerSession,
+        http_session: &mut ServerSession,
         _params: RequestParams,
     ) -> ApiResult<ApiResponse> {
-        let response = etcd.list(T::RESOURCE_TYPE).await?;
+        if http_session.req_header().method == Method::DELETE {
+            etcd.delete(MAINTENANCE_KEY)
+                .await
+                .map_err(ApiError::ProxyError)?;
+            log::info!("Maintenance switch removed via Admin API");
+            return Ok(ResponseBuilder::success_http(Vec::new(), None));
+        }
 
-        let mut list_items = Vec::new();
-        for kv in response.kvs() {
-            let key = String::from_utf8_lossy(kv.key()).to_string();
-            let value: serde_json::Value = serde_json::from_slice(kv.value()).map_err(|e| {
-                ApiError::ProxyError(ProxyError::serialization_error(
-                    "Failed to parse resource JSON",
-                    e,
-                ))
-            })?;
+        http_session.validate_content_type()?;
+        let body_data = read_request_body(http_session)
+            .await
+            .map_err(|e| ApiError::RequestBodyReadError(e.to_string()))?;
+        let switch: config::Maintenance = serde_json::from_slice(&body_data)
+            .map_err(|e| ApiError::ValidationError(format!("Invalid maintenance: {e}")))?;
+        switch
+            .validate()
+            .map_err(|e| ApiError::ValidationError(format!("Invalid maintenance: {e}")))?;
+        etcd.put(MAINTENANCE_KEY, body_data)
+            .await
+            .map_err(ApiError::ProxyError)?;
+        log::info!("Maintenance switch updated via Admin API");
+        Ok(ResponseBuilder::success_json(&ValueWrapper {
+            value: switch,
+        }))
+    }
 
-            let item = serde_json::json!({
-                "key": key,
-                "value": redact(T::RESOURCE_TYPE, value),
-                "createdIndex": kv.create_revision(),
-                "modifiedIndex": kv.mod_revision(),
-            });
+    fn audited_resource(&self, _params: &RequestParams) -> Option<(&'static str, String)> {
+        Some(("maintenance", MAINTENANCE_KEY.to_string()))
+    }
 
-            list_items.push(item);
-        }
+    fn doc(&self) -> OperationDoc {
+        OperationDoc::new(
+            "maintenance",
+            "Set (PUT) or remove (DELETE) the maintenance switch",
+        )
+    }
+}
 
-        let result = serde_json::json!({
-            "total": list_items.len(),
-            "list": list_items,
-        });
+// OPENAPI handler: GET /apisix/admin/openapi.json
+struct OpenApiHandler {
+    document: Arc<serde_json::Value>,
+}
 
-        Ok(ResponseBuilder::success_json(&result))
+#[async_trait]
+impl Handler for OpenApiHandler {
+    async fn handle(
+        &self,
+        _etcd: &EtcdClientWrapper,
+        _http_session: &mut ServerSession,
+        _params: RequestParams,
+    ) -> ApiResult<ApiResponse> {
+        Ok(ResponseBuilder::success_json(self.document.as_ref()))
+    }
+
+    fn doc(&self) -> OperationDoc {
+        openapi::self_doc()
     }
 }
 
@@ -407,14 +1629,29 @@ pub struct AdminHttpApp {
     config: Admin,
     etcd: EtcdClientWrapper,
     router: Router<HashMap<Method, HttpHandler>>,
+    /// Registered `(path, method)` pairs in order, for the OpenAPI document.
+    operations: Vec<(String, Method)>,
+    /// Resource schemas by name, for the OpenAPI document.
+    schemas: BTreeMap<&'static str, serde_json::Value>,
+    audit: Arc<AuditLog>,
+    history: Option<Arc<ConfigHistory>>,
 }
 
 impl AdminHttpApp {
     pub fn new(admin: Admin, etcd_cfg: crate::config::Etcd) -> Self {
+        let audit = Arc::new(AuditLog::new(admin.audit.as_ref()));
+        let history = admin
+            .history
+            .as_ref()
+            .map(|history| Arc::new(ConfigHistory::new(history, &etcd_cfg.prefix)));
         let mut this = Self {
             config: admin,
             etcd: EtcdClientWrapper::new(etcd_cfg),
             router: Router::new(),
+            operations: Vec::new(),
+            schemas: BTreeMap::new(),
+            audit: audit.clone(),
+            history: history.clone(),
         };
 
         // Register routes with type safety and reduced boilerplate
@@ -422,14 +1659,113 @@ impl AdminHttpApp {
             .register_resource_routes::<config::Upstream>()
             .register_resource_routes::<config::Service>()
             .register_resource_routes::<config::GlobalRule>()
-            .register_resource_routes::<config::SSL>();
+            .register_resource_routes::<config::SSL>()
+            .register_resource_routes::<config::IpList>()
+            .register_resource_routes::<config::PluginConfig>();
+        this.route("/apisix/admin/export", Method::GET, Box::new(ExportHandler))
+            .route(
+                "/apisix/admin/import",
+                Method::POST,
+                Box::new(ImportHandler),
+            )
+            .route("/apisix/admin/batch", Method::PUT, Box::new(BatchHandler))
+            .route(
+                "/apisix/admin/cache/purge",
+                Method::POST,
+                Box::new(CachePurgeHandler),
+            )
+            .route(
+                "/apisix/admin/routes/match",
+                Method::POST,
+                Box::new(RouteMatchHandler),
+            )
+            .route(
+                "/apisix/admin/plugins/{name}/schema",
+                Method::GET,
+                Box::new(PluginSchemaHandler),
+            )
+            .route(
+                "/apisix/admin/upstreams/{id}/nodes",
+                Method::GET,
+                Box::new(NodesHandler),
+            )
+            .route(
+                "/apisix/admin/upstreams/{id}/nodes",
+                Method::PATCH,
+                Box::new(NodesPatchHandler),
+            )
+            .route(
+                "/apisix/admin/upstreams/{id}/nodes/{node}/drain",
+                Method::POST,
+                Box::new(NodeDrainHandler { drained: true }),
+            )
+            .route(
+                "/apisix/admin/upstreams/{id}/nodes/{node}/enable",
+                Method::POST,
+                Box::new(NodeDrainHandler { drained: false }),
+            )
+            .route(
+                "/apisix/admin/maintenance",
+                Method::GET,
+                Box::new(MaintenanceHandler),
+            )
+            .route(
+                "/apisix/admin/maintenance",
+                Method::PUT,
+                Box::new(MaintenanceUpdateHandler),
+            )
+            .route(
+                "/apisix/admin/maintenance",
+                Method::DELETE,
+                Box::new(MaintenanceUpdateHandler),
+            )
+            .route(
+                "/apisix/admin/audit",
+                Method::GET,
+                Box::new(AuditHandler { log: audit }),
+            );
+        if let Some(history) = history {
+            this.route(
+                "/apisix/admin/versions",
+                Method::GET,
+                Box::new(VersionsHandler {
+                    history: history.clone(),
+                }),
+            )
+            .route(
+                "/apisix/admin/rollback/{version}",
+                Method::POST,
+                Box::new(RollbackHandler { history }),
+            );
+        }
+
+        let document = Arc::new(this.openapi_document());
+        this.route(
+            OPENAPI_PATH,
+            Method::GET,
+            Box::new(OpenApiHandler { document }),
+        );
 
         this
     }
 
+    /// OpenAPI document of every operation registered so far.
+    fn openapi_document(&self) -> serde_json::Value {
+        let operations = self
+            .operations
+            .iter()
+            .filter_map(|(path, method)| {
+                let handler = self.router.at(path).ok()?.value.get(method)?;
+                Some((path.clone(), method.clone(), handler.doc()))
+            })
+            .collect();
+        openapi::document(operations, &self.schemas)
+    }
+
     fn register_resource_routes<T: AdminResource>(&mut self) -> &mut Self {
         let path = format!("/apisix/admin/{}/{{id}}", T::RESOURCE_TYPE);
         let list_path = format!("/apisix/admin/{}", T::RESOURCE_TYPE);
+        self.schemas.insert(T::SCHEMA_NAME, T::schema());
 
         self.route(&path, Method::PUT, Box::new(ResourceHandler::<T>::new()))
             .route(&path, Method::GET, Box::new(GetHandler::<T>::new()))
@@ -440,7 +1776,16 @@ impl AdminHttpApp {
     }
 
     fn route(&mut self, path: &str, method: Method, handler: HttpHandler) -> &mut Self {
-        if self.router.at(path).is_err() {
+        self.operations.push((path.to_string(), method.clone()));
+        // A pattern only finds itself with each parameter bound to its own name; a static
+        // path such as `routes/match` merely matching `routes/{id}` is a new route.
+        let registered = self.router.at(path).is_ok_and(|matched| {
+            matched
+                .params
+                .iter()
+                .all(|(name, value)| value == format!("{{{name}}}"))
+        });
+        if !registered {
             let mut handlers = HashMap::new();
             handlers.insert(method, handler);
             if let Err(e) = self.router.insert(path, handlers) {
@@ -463,13 +1808,9 @@ impl AdminHttpApp {
         service.add_tcp(addr);
         Some(service)
     }
-}
-
-#[async_trait]
-impl ServeHttp for AdminHttpApp {
-    async fn response(&self, http_session: &mut ServerSession) -> ApiResponse {
-        http_session.set_keepalive(None);
 
+    /// Authenticate and route a request to its resource handler.
+    async fn dispatch(&self, http_session: &mut ServerSession) -> ApiResponse {
         if http_session.validate_api_key(&self.config.api_key).is_err() {
             return CommonErrors::forbidden("Invalid API key");
         }
@@ -486,10 +1827,25 @@ impl ServeHttp for AdminHttpApp {
                         .iter()
                         .map(|(k, v)| (k.to_string(), v.to_string()))
                         .collect();
-                    match handler.handle(&self.etcd, http_session, params).await {
+                    let audited = handler.audited_resource(&params);
+                    let before = match &audited {
+                        Some((_, key)) => self.etcd.get(key).await.ok().flatten(),
+                        None => None,
+                    };
+                    let mut resp = match handler.handle(&self.etcd, http_session, params).await {
                         Ok(resp) => resp,
                         Err(e) => e.into_response(),
+                    };
+                    if let Some((resource_type, key)) = audited {
+                        self.audit_change(http_session, resource_type, key, before, &resp)
+                            .await;
+                    } else if handler.audits_changes() {
+                        self.audit_changes(http_session, &path, &mut resp).await;
+                    }
+                    if handler.changes_config() && resp.status().is_success() {
+                        self.snapshot_version().await;
                     }
+                    resp
                 }
                 None => ResponseBuilder::error_http(
                     StatusCode::METHOD_NOT_ALLOWED,
@@ -501,6 +1857,158 @@ impl ServeHttp for AdminHttpApp {
     }
 }
 
+impl AdminHttpApp {
+    /// Store the graph as a new version after a change. The change itself already
+    /// committed, so a failure here is only logged.
+    async fn snapshot_version(&self) {
+        if let Some(history) = &self.history {
+            if let Err(e) = history.snapshot(&self.etcd).await {
+                log::error!("Failed to store configuration version: {e}");
+            }
+        }
+    }
+
+    /// Record a resource mutation with a diff between the stored documents before and
+    /// after it. Secrets are redacted before diffing.
+    async fn audit_change(
+        &self,
+        http_session: &ServerSession,
+        resource_type: &str,
+        key: String,
+        before: Option<Vec<u8>>,
+        resp: &ApiResponse,
+    ) {
+        let method = http_session.req_header().method.clone();
+        let changes = if resp.status().is_success() {
+            // Every method but DELETE leaves the key in place, e.g. a PATCH of its nodes.
+            let after = if method != Method::DELETE {
+                self.etcd.get(&key).await.ok().flatten()
+            } else {
+                None
+            };
+            stored_diff(resource_type, before, after)
+        } else {
+            Vec::new()
+        };
+        let record = AuditRecord {
+            method: method.to_string(),
+            key,
+            identity: api_key_identity(&self.config.api_key),
+            client: http_session.client_addr().map(|addr| addr.to_string()),
+            status: resp.status().as_u16(),
+            version: None,
+            changes,
+        };
+        self.audit.record(&self.etcd, record).await;
+    }
+
+    /// Record the [`AuditedChanges`] a handler attached to `resp`, one entry per key.
+    /// A request that failed or changed nothing is recorded once, under its path.
+    async fn audit_changes(
+        &self,
+        http_session: &ServerSession,
+        path: &str,
+        resp: &mut ApiResponse,
+    ) {
+        let request = AuditRecord {
+            method: http_session.req_header().method.to_string(),
+            key: path.trim_start_matches("/apisix/admin/").to_string(),
+            identity: api_key_identity(&self.config.api_key),
+            client: http_session.client_addr().map(|addr| addr.to_string()),
+            status: resp.status().as_u16(),
+            version: None,
+            changes: Vec::new(),
+        };
+        for record in reported_records(request, resp) {
+            self.audit.record(&self.etcd, record).await;
+        }
+    }
+}
+
+/// The audit records of a request whose response may carry [`AuditedChanges`]: one per
+/// reported key, or `request` itself when none were reported.
+fn reported_records(request: AuditRecord, resp: &mut ApiResponse) -> Vec<AuditRecord> {
+    let reported = resp
+        .extensions_mut()
+        .remove::<AuditedChanges>()
+        .unwrap_or_default();
+    if reported.keys.is_empty() {
+        return vec![request];
+    }
+    reported
+        .keys
+        .iter()
+        .map(|change| AuditRecord {
+            key: change.key.clone(),
+            version: reported.version,
+            changes: key_diff(change),
+            ..request.clone()
+        })
+        .collect()
+}
+
+/// Diff of a reported key change, with the secrets of its resource type redacted.
+fn key_diff(change: &KeyChange) -> Vec<AuditChange> {
+    let resource_type = change.key.split('/').next().unwrap_or_default();
+    let redacted =
+        |value: &Option<serde_json::Value>| value.clone().map(|value| redact(resource_type, value));
+    diff(
+        redacted(&change.before).as_ref(),
+        redacted(&change.after).as_ref(),
+    )
+}
+
+/// Attach the changes a request made to its response for the audit log.
+fn with_audited(mut resp: ApiResponse, changes: AuditedChanges) -> ApiResponse {
+    resp.extensions_mut().insert(changes);
+    resp
+}
+
+/// Audited changes of a committed import.
+fn imported_changes(outcome: &ImportOutcome, version: Option<i64>) -> AuditedChanges {
+    AuditedChanges {
+        version,
+        keys: outcome
+            .changes
+            .iter()
+            .map(|change| {
+                KeyChange::stored(
+                    change.key.clone(),
+                    change.before.as_deref(),
+                    change.after.as_deref(),
+                )
+            })
+            .collect(),
+    }
+}
+
+/// Diff of two stored versions of a resource, with secrets redacted.
+fn stored_diff(
+    resource_type: &str,
+    before: Option<Vec<u8>>,
+    after: Option<Vec<u8>>,
+) -> Vec<AuditChange> {
+    let parse = |value: Option<Vec<u8>>| {
+        value
+            .and_then(|v| serde_json::from_slice::<serde_json::Value>(&v).ok())
+            .map(|v| redact(resource_type, v))
+    };
+    diff(parse(before).as_ref(), parse(after).as_ref())
+}
+
+#[async_trait]
+impl ServeHttp for AdminHttpApp {
+    async fn response(&self, http_session: &mut ServerSession) -> ApiResponse {
+        http_session.set_keepalive(None);
+        let method = http_session.req_header().method.clone();
+        let resp = self.dispatch(http_session).await;
+        metrics::ADMIN_REQUESTS
+            .with_label_values(&[method.as_str(), resp.status().as_str()])
+            .inc();
+        resp
+    }
+}
+
 trait AdminSessionExt {
     fn validate_api_key(&self, api_key: &str) -> ApiResult<()>;
     fn validate_content_type(&self) -> ApiResult<()>;
@@ -555,7 +2063,26 @@ fn is_json_content_type(ct_str: &str) -> bool {
         .is_some_and(|media_type| media_type.eq_ignore_ascii_case("application/json"))
 }
 
+fn is_yaml_content_type(ct_str: &str) -> bool {
+    ct_str
+        .split(';')
+        .next()
+        .map(str::trim)
+        .is_some_and(|media_type| {
+            ["application/yaml", "application/x-yaml", "text/yaml"]
+                .iter()
+                .any(|yaml| media_type.eq_ignore_ascii_case(yaml))
+        })
+}
+
 async fn read_request_body(http_session: &mut ServerSession) -> Result<Vec<u8>, ApiError> {
+    read_request_body_limited(http_session, MAX_BODY_SIZE).await
+}
+
+async fn read_request_body_limited(
+    http_session: &mut ServerSession,
+    limit: usize,
+) -> Result<Vec<u8>, ApiError> {
     let mut body_data = Vec::with_capacity(1024); // Initial capacity
     while let Some(bytes) = http_session
         .read_request_body()
@@ -563,7 +2090,7 @@ async fn read_request_body(http_session: &mut ServerSession) -> Result<Vec<u8>,
         .map_err(|e| ApiError::RequestBodyReadError(e.to_string()))?
     {
         // Check if the cumulative size exceeds the limit
-        if body_data.len() + bytes.len() > MAX_BODY_SIZE {
+        if body_data.len() + bytes.len() > limit {
             return Err(ApiError::InvalidRequest("Request body too large".into()));
         }
         body_data.extend_from_slice(&bytes);
@@ -578,7 +2105,7 @@ async fn read_request_body(http_session: &mut ServerSession) -> Result<Vec<u8>,
 /// `hash_on` `key`, whose value is something like `"uri"`). Sensitivity is
 /// decided by `resource_type` plus the structural position of the field:
 ///
-/// - `ssls`: top-level `key` (the private key) is redacted.
+/// - `ssls`: top-level `key` (the private key), `pkcs12` and `passphrase` are redacted.
 /// - `upstreams`: the `tls.client_key` field is redacted; the top-level `key`
 ///   (a `hash_on` selector) is left intact.
 /// - `routes`/`services`/`global_rules`: inside a `plugins` object, the
@@ -609,11 +2136,15 @@ fn redact_value(
                         ("jwt-auth", "secret")
                         | ("basic-auth", "password")
                         | ("csrf", "key")
+                        | ("debug-headers", "secret")
+                        | ("debug-routing", "secret")
                         | ("key-auth", "key") => redact_string(v),
                         ("key-auth", "keys") => redact_keys_array(v),
                         _ => redact_value(v, resource_type, false, None),
                     }
-                } else if resource_type == "ssls" && name == "key" {
+                } else if resource_type == "ssls"
+                    && matches!(name.as_str(), "key" | "pkcs12" | "passphrase")
+                {
                     redact_string(v)
                 } else if name == "tls" {
                     redact_value(v, resource_type, true, None)
@@ -652,18 +2183,32 @@ fn redact_plugins(value: serde_json::Value, resource_type: &str) -> serde_json::
     }
 }
 
+/// Placeholder replacing secret values in admin responses.
+const REDACTED: &str = "***";
+
 fn redact_string(v: serde_json::Value) -> serde_json::Value {
     match v {
-        serde_json::Value::String(_) => serde_json::Value::String("***".into()),
+        serde_json::Value::String(_) => serde_json::Value::String(REDACTED.into()),
         other => other,
     }
 }
 
+/// Redact `key-auth.keys[]`, whose entries are plain keys or `{key, ...}` credentials.
 fn redact_keys_array(v: serde_json::Value) -> serde_json::Value {
     match v {
-        serde_json::Value::Array(arr) => {
-            serde_json::Value::Array(arr.into_iter().map(redact_string).collect())
-        }
+        serde_json::Value::Array(arr) => serde_json::Value::Array(
+            arr.into_iter()
+                .map(|entry| match entry {
+                    serde_json::Value::Object(mut cred) => {
+                        if let Some(key) = cred.remove("key") {
+                            cred.insert("key".into(), redact_string(key));
+                        }
+                        serde_json::Value::Object(cred)
+                    }
+                    other => redact_string(other),
+                })
+                .collect(),
+        ),
         other => other,
     }
 }
@@ -672,6 +2217,50 @@ fn redact_keys_array(v: serde_json::Value) -> serde_json::Value {
 mod tests {
     use super::*;
 
+    #[test]
+    fn purge_request_requires_exactly_one_target() {
+        let parse = |body: &str| {
+            serde_json::from_str::<PurgeRequest>(body)
+                .unwrap()
+                .into_target()
+        };
+        assert_eq!(
+            parse(r#"{"route_id": "r1"}"#).unwrap(),
+            PurgeTarget::Route("r1".into())
+        );
+        assert_eq!(
+            parse(r#"{"prefix": "example.com/static/"}"#).unwrap(),
+            PurgeTarget::Prefix("example.com/static/".into())
+        );
+        assert!(parse("{}").is_err());
+        assert!(parse(r#"{"prefix": ""}"#).is_err());
+        assert!(parse(r#"{"key": "example.com/a", "route_id": "r1"}"#).is_err());
+    }
+
+    #[test]
+    fn list_label_selectors_require_every_label() {
+        let resource = serde_json::json!({"labels": {"env": "prod", "team": "payments"}});
+        let selectors = |pairs: &[(&str, Option<&str>)]| -> Vec<(String, Option<String>)> {
+            pairs
+                .iter()
+                .map(|(k, v)| (k.to_string(), v.map(str::to_string)))
+                .collect()
+        };
+        assert!(matches_label_selectors(&resource, &[]));
+        assert!(matches_label_selectors(
+            &resource,
+            &selectors(&[("env", Some("prod")), ("team", None)])
+        ));
+        assert!(!matches_label_selectors(
+            &resource,
+            &selectors(&[("env", Some("staging"))])
+        ));
+        assert!(!matches_label_selectors(
+            &serde_json::json!({"id": "1"}),
+            &selectors(&[("env", None)])
+        ));
+    }
+
     #[test]
     fn content_type_accepts_json_with_charset() {
         assert!(is_json_content_type("application/json"));
@@ -686,6 +2275,199 @@ mod tests {
         assert!(!is_json_content_type(""));
     }
 
+    #[test]
+    fn import_document_validates_ids_and_resources() {
+        let document: ConfigDocument = serde_yml::from_str(
+            "upstreams:\n  - id: u1\n    nodes: {\"127.0.0.1:80\": 1}\nroutes:\n  - id: r1\n    uri: /\n    upstream_id: u1\n",
+        )
+        .unwrap();
+        let keys: Vec<String> = document
+            .into_resources()
+            .unwrap()
+            .into_iter()
+            .map(|(key, _)| key)
+            .collect();
+        assert_eq!(keys, vec!["routes/r1", "upstreams/u1"]);
+
+        let duplicate: ConfigDocument = serde_json::from_value(serde_json::json!({
+            "upstreams": [
+                {"id": "u1", "nodes": {"127.0.0.1:80": 1}},
+                {"id": "u1", "nodes": {"127.0.0.1:81": 1}}
+            ]
+        }))
+        .unwrap();
+        assert!(duplicate.into_resources().is_err());
+
+        let redacted: ConfigDocument = serde_json::from_value(serde_json::json!({
+            "global_rules": [{"id": "g1", "plugins": {"csrf": {"key": "***"}}}]
+        }))
+        .unwrap();
+        assert!(redacted.into_resources().is_err());
+
+        let missing_id: ConfigDocument = serde_json::from_value(serde_json::json!({
+            "upstreams": [{"nodes": {"127.0.0.1:80": 1}}]
+        }))
+        .unwrap();
+        assert!(missing_id.into_resources().is_err());
+    }
+
+    #[test]
+    fn imports_are_audited_per_key_with_secrets_redacted() {
+        let outcome = ImportOutcome {
+            revision: Some(9),
+            written: 1,
+            unchanged: 0,
+            deleted: 1,
+            changes: vec![
+                graph_mutation::ImportedKey {
+                    key: "ssls/1".into(),
+                    before: Some(br#"{"cert": "a", "key": "old"}"#.to_vec()),
+                    after: Some(br#"{"cert": "b", "key": "new"}"#.to_vec()),
+                },
+                graph_mutation::ImportedKey {
+                    key: "routes/2".into(),
+                    before: Some(br#"{"uri": "/"}"#.to_vec()),
+                    after: None,
+                },
+            ],
+        };
+        let audited = imported_changes(&outcome, Some(7));
+        assert_eq!(audited.version, Some(7));
+        let diffs: Vec<Vec<AuditChange>> = audited.keys.iter().map(key_diff).collect();
+        assert_eq!(audited.keys[0].key, "ssls/1");
+        assert_eq!(diffs[0].len(), 1, "the redacted key is unchanged");
+        assert_eq!(diffs[0][0].path, "/cert");
+        assert_eq!((diffs[1][0].path.as_str(), diffs[1][0].op), ("", "remove"));
+    }
+
+    #[test]
+    fn batch_writes_are_audited_per_key() {
+        assert!(BatchHandler.audits_changes());
+        let outcome = ImportOutcome {
+            revision: Some(3),
+            written: 1,
+            unchanged: 0,
+            deleted: 0,
+            changes: vec![graph_mutation::ImportedKey {
+                key: "routes/r1".into(),
+                before: None,
+                after: Some(br#"{"uri": "/"}"#.to_vec()),
+            }],
+        };
+        let mut resp = batch_response(&outcome, vec!["routes/r1".into()]);
+        let request = AuditRecord {
+            method: "PUT".into(),
+            key: "batch".into(),
+            identity: "key:test".into(),
+            client: None,
+            status: resp.status().as_u16(),
+            version: None,
+            changes: Vec::new(),
+        };
+        let records = reported_records(request, &mut resp);
+        assert_eq!(records.len(), 1);
+        assert_eq!(records[0].key, "routes/r1");
+        assert_eq!(
+            (
+                records[0].changes[0].path.as_str(),
+                records[0].changes[0].op
+            ),
+            ("", "add")
+        );
+    }
+
+    #[test]
+    fn batch_items_are_validated_by_type() {
+        let batch: BatchRequest = serde_json::from_value(serde_json::json!({
+            "resources": [
+                {"type": "routes", "id": "r1", "value": {"uri": "/", "upstream_id": "u1"}},
+                {"type": "upstreams", "id": "u1", "value": {"nodes": {"127.0.0.1:80": 1}}}
+            ]
+        }))
+        .unwrap();
+        let keys: Vec<String> = batch
+            .into_document()
+            .and_then(ConfigDocument::into_resources)
+            .unwrap()
+            .into_iter()
+            .map(|(key, _)| key)
+            .collect();
+        assert_eq!(keys, vec!["routes/r1", "upstreams/u1"]);
+
+        for item in [
+            serde_json::json!({"type": "consumers", "id": "c1", "value": {}}),
+            serde_json::json!({"type": "routes", "id": "r1", "value": {"id": "r2", "uri": "/"}}),
+            serde_json::json!({"type": "routes", "id": "r1", "value": {"uri": 1}}),
+        ] {
+            let batch: BatchRequest =
+                serde_json::from_value(serde_json::json!({ "resources": [item] })).unwrap();
+            assert!(batch
+                .into_document()
+                .and_then(ConfigDocument::into_resources)
+                .is_err());
+        }
+    }
+
+    #[test]
+    fn nodes_patch_adds_reweights_and_removes_nodes() {
+        let stored = serde_json::to_vec(&serde_json::json!({
+            "nodes": {"10.0.0.1:80": 1, "10.0.0.2:80": 1},
+            "priorities": {"10.0.0.2:80": -1},
+            "retries": 2
+        }))
+        .unwrap();
+        let patch = BTreeMap::from([
+            ("10.0.0.1:80".to_string(), Some(5)),
+            ("10.0.0.2:80".to_string(), None),
+            ("10.0.0.3:80".to_string(), Some(1)),
+        ]);
+        let patched: serde_json::Value =
+            serde_json::from_slice(&patch_nodes(&stored, &patch).unwrap()).unwrap();
+        assert_eq!(
+            patched,
+            serde_json::json!({
+                "nodes": {"10.0.0.1:80": 5, "10.0.0.3:80": 1},
+                "priorities": {},
+                "retries": 2
+            })
+        );
+
+        let unknown = BTreeMap::from([("10.0.0.9:80".to_string(), None)]);
+        assert!(matches!(
+            patch_nodes(&stored, &unknown),
+            Err(GraphMutationError::NotFound(_))
+        ));
+        let zero = BTreeMap::from([("10.0.0.1:80".to_string(), Some(0))]);
+        assert!(patch_nodes(&stored, &zero).is_err());
+        let all = BTreeMap::from([
+            ("10.0.0.1:80".to_string(), None),
+            ("10.0.0.2:80".to_string(), None),
+        ]);
+        assert!(patch_nodes(&stored, &all).is_err());
+    }
+
+    #[test]
+    fn nodes_patch_is_audited_per_node() {
+        let stored = serde_json::to_vec(&serde_json::json!({
+            "nodes": {"10.0.0.1:80": 1, "10.0.0.2:80": 1}
+        }))
+        .unwrap();
+        let patch = BTreeMap::from([
+            ("10.0.0.1:80".to_string(), Some(5)),
+            ("10.0.0.2:80".to_string(), None),
+        ]);
+        let patched = patch_nodes(&stored, &patch).unwrap();
+        let changes = stored_diff("upstreams", Some(stored), Some(patched));
+        let summary: Vec<(&str, &str)> = changes.iter().map(|c| (c.path.as_str(), c.op)).collect();
+        assert_eq!(
+            summary,
+            [
+                ("/nodes/10.0.0.1:80", "replace"),
+                ("/nodes/10.0.0.2:80", "remove")
+            ]
+        );
+    }
+
     #[test]
     fn empty_api_key_config_is_rejected_by_validator() {
         use validator::Validate;
@@ -693,6 +2475,8 @@ mod tests {
             address: "127.0.0.1:9181".parse().unwrap(),
             api_key: "   ".into(),
             allow_insecure_remote: false,
+            audit: None,
+            history: None,
         };
         assert!(admin.validate().is_err());
     }
@@ -709,6 +2493,16 @@ mod tests {
             "-----BEGIN CERTIFICATE-----\ncert\n-----END CERTIFICATE-----"
         );
         assert_eq!(out["key"], "***");
+
+        let out = redact(
+            "ssls",
+            serde_json::json!({"pkcs12": "MIIK", "passphrase": "pass", "snis": ["a.com"]}),
+        );
+        assert_eq!(
+            (&out["pkcs12"], &out["passphrase"]),
+            (&"***".into(), &"***".into())
+        );
+        assert_eq!(out["snis"][0], "a.com");
     }
 
     #[test]
@@ -743,6 +2537,18 @@ mod tests {
         );
     }
 
+    #[test]
+    fn redact_key_auth_credential_keys() {
+        let input = serde_json::json!({
+            "plugins": { "key-auth": { "keys": ["k1", {"key": "k2", "consumer": "app"}] } },
+        });
+        let out = redact("routes", input);
+        assert_eq!(
+            out["plugins"]["key-auth"]["keys"],
+            serde_json::json!(["***", {"key": "***", "consumer": "app"}])
+        );
+    }
+
     #[test]
     fn redact_csrf_key() {
         let input = serde_json::json!({
@@ -851,8 +2657,46 @@ mod tests {
                 upstream_id: Some("missing".into()),
                 service_id: None,
                 timeout: None,
+                streaming: false,
+                fallback: false,
+                shed_priority: Default::default(),
+                enabled: true,
+                labels: Default::default(),
+                plugin_config_id: Default::default(),
+                strip_path_prefix: None,
+                api_group: None,
             },
         );
         assert!(CandidateSnapshot::build(set).is_err());
     }
+
+    #[test]
+    fn upstream_node_keys_are_percent_decoded() {
+        use crate::proxy::control_plane::{CandidateSnapshot, ResourceConfigSet};
+        use crate::proxy::runtime::{RuntimeSnapshot, RUNTIME_TEST_LOCK};
+
+        let _guard = RUNTIME_TEST_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+        let mut set = ResourceConfigSet::default();
+        set.upstreams.insert(
+            "u-node-key".into(),
+            serde_json::from_value(
+                serde_json::json!({"id": "u-node-key", "nodes": {"[::1]:80": 1}}),
+            )
+            .unwrap(),
+        );
+        RUNTIME
+            .publish(RuntimeSnapshot::compile(CandidateSnapshot::build(set).unwrap(), 1).unwrap())
+            .unwrap();
+
+        let node = |node: &str| {
+            let params = RequestParams::from([
+                ("id".to_string(), "u-node-key".to_string()),
+                ("node".to_string(), node.to_string()),
+            ]);
+            runtime_upstream_node(&params).map(|(_, node)| node)
+        };
+        assert_eq!(node("%5B%3A%3A1%5D%3A80").unwrap(), "[::1]:80");
+        assert_eq!(node("[::1]:80").unwrap(), "[::1]:80");
+        assert!(node("%FF").is_err());
+    }
 }
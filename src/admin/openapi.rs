@@ -0,0 +1,376 @@
+//! OpenAPI document behind `GET /apisix/admin/openapi.json`.
+//!
+//! The document is assembled once from the handlers registered on the Admin API: each
+//! handler describes its operation, resource handlers reference the schema of their
+//! resource type, and the `plugins` object lists every registered plugin with its
+//! configuration schema.
+
+use std::collections::BTreeMap;
+
+use http::Method;
+use serde_json::{json, Map, Value as JsonValue};
+
+use crate::plugins::{plugin_schema, registered_plugins};
+
+pub(super) const OPENAPI_PATH: &str = "/apisix/admin/openapi.json";
+
+/// How one Admin API operation is described in the document.
+#[derive(Default)]
+pub(super) struct OperationDoc {
+    summary: String,
+    tag: &'static str,
+    request: Option<JsonValue>,
+    response: Option<JsonValue>,
+    query: Vec<(&'static str, &'static str)>,
+}
+
+impl OperationDoc {
+    pub(super) fn new(tag: &'static str, summary: impl Into<String>) -> Self {
+        Self {
+            summary: summary.into(),
+            tag,
+            ..Default::default()
+        }
+    }
+
+    /// Schema of the JSON request body.
+    pub(super) fn request(mut self, schema: JsonValue) -> Self {
+        self.request = Some(schema);
+        self
+    }
+
+    /// Schema of the successful JSON response.
+    pub(super) fn response(mut self, schema: JsonValue) -> Self {
+        self.response = Some(schema);
+        self
+    }
+
+    /// A query parameter with its description.
+    pub(super) fn query(mut self, name: &'static str, description: &'static str) -> Self {
+        self.query.push((name, description));
+        self
+    }
+}
+
+/// Description of `GET /apisix/admin/openapi.json` itself, which is registered after
+/// the document is built.
+pub(super) fn self_doc() -> OperationDoc {
+    OperationDoc::new("meta", "This OpenAPI document").response(json!({"type": "object"}))
+}
+
+/// Reference to a schema under `components/schemas`.
+pub(super) fn schema_ref(name: &str) -> JsonValue {
+    json!({"$ref": format!("#/components/schemas/{name}")})
+}
+
+/// Schema of a stored resource wrapped as `{"value": ...}`.
+pub(super) fn value_of(schema: JsonValue) -> JsonValue {
+    json!({"type": "object", "properties": {"value": schema}})
+}
+
+/// Builds the document from `(path, method, description)` of every registered operation
+/// and the resource schemas by name.
+pub(super) fn document(
+    operations: Vec<(String, Method, OperationDoc)>,
+    schemas: &BTreeMap<&'static str, JsonValue>,
+) -> JsonValue {
+    let mut paths: BTreeMap<String, Map<String, JsonValue>> = BTreeMap::new();
+    for (path, method, doc) in operations {
+        let operation = operation(&path, &method, doc);
+        paths
+            .entry(path)
+            .or_default()
+            .insert(method.as_str().to_ascii_lowercase(), operation);
+    }
+    paths.entry(OPENAPI_PATH.to_string()).or_default().insert(
+        "get".to_string(),
+        operation(OPENAPI_PATH, &Method::GET, self_doc()),
+    );
+
+    let mut components: Map<String, JsonValue> = schemas
+        .iter()
+        .map(|(name, schema)| (name.to_string(), schema.clone()))
+        .collect();
+    components.insert("Plugins".to_string(), plugins_schema());
+    components.insert("Timeout".to_string(), timeout_schema());
+
+    json!({
+        "openapi": "3.0.3",
+        "info": {
+            "title": "PingSIX Admin API",
+            "version": env!("CARGO_PKG_VERSION")
+        },
+        "security": [{"ApiKey": []}],
+        "paths": paths,
+        "components": {
+            "securitySchemes": {
+                "ApiKey": {"type": "apiKey", "in": "header", "name": "X-API-KEY"}
+            },
+            "schemas": components
+        }
+    })
+}
+
+fn operation(path: &str, method: &Method, doc: OperationDoc) -> JsonValue {
+    let mut parameters: Vec<JsonValue> = path
+        .split('/')
+        .filter_map(|segment| segment.strip_prefix('{')?.strip_suffix('}'))
+        .map(|name| {
+            json!({"name": name, "in": "path", "required": true, "schema": {"type": "string"}})
+        })
+        .collect();
+    parameters.extend(doc.query.iter().map(|(name, description)| {
+        json!({
+            "name": name,
+            "in": "query",
+            "required": false,
+            "description": description,
+            "schema": {"type": "string"}
+        })
+    }));
+
+    let success = match doc.response {
+        Some(schema) => json!({
+            "description": "Success",
+            "content": {"application/json": {"schema": schema}}
+        }),
+        None => json!({"description": "Success"}),
+    };
+    let mut operation = json!({
+        "operationId": operation_id(path, method),
+        "summary": doc.summary,
+        "tags": [doc.tag],
+        "parameters": parameters,
+        "responses": {
+            "200": success,
+            "400": {"description": "Invalid request, body or API key"},
+            "404": {"description": "Not found"},
+            "409": {"description": "Conflict with the stored configuration"}
+        }
+    });
+    if let Some(schema) = doc.request {
+        operation["requestBody"] = json!({
+            "required": true,
+            "content": {"application/json": {"schema": schema}}
+        });
+    }
+    operation
+}
+
+/// `get_routes_id`-style identifier unique per path and method.
+fn operation_id(path: &str, method: &Method) -> String {
+    let path = path
+        .trim_start_matches("/apisix/admin/")
+        .replace(['/', '.', '-'], "_")
+        .replace(['{', '}'], "");
+    format!("{}_{path}", method.as_str().to_ascii_lowercase())
+}
+
+/// Every registered plugin keyed by name with its configuration schema.
+fn plugins_schema() -> JsonValue {
+    let properties: Map<String, JsonValue> = registered_plugins()
+        .into_iter()
+        .filter_map(|(name, _)| Some((name.to_string(), plugin_schema(name)?)))
+        .collect();
+    json!({"type": "object", "properties": properties, "additionalProperties": false})
+}
+
+fn timeout_schema() -> JsonValue {
+    let seconds = json!({"type": "integer", "minimum": 1, "maximum": 86400});
+    json!({
+        "type": "object",
+        "properties": {"connect": seconds, "send": seconds, "read": seconds, "total": seconds},
+        "required": ["connect", "send", "read"]
+    })
+}
+
+fn labels_schema() -> JsonValue {
+    json!({"type": "object", "additionalProperties": {"type": "string"}})
+}
+
+fn strings_schema() -> JsonValue {
+    json!({"type": "array", "items": {"type": "string"}})
+}
+
+pub(super) fn route_schema() -> JsonValue {
+    json!({
+        "type": "object",
+        "properties": {
+            "id": {"type": "string"},
+            "uri": {"type": "string"},
+            "uris": strings_schema(),
+            "methods": strings_schema(),
+            "host": {"type": "string"},
+            "hosts": strings_schema(),
+            "priority": {"type": "integer", "minimum": 0},
+            "plugins": schema_ref("Plugins"),
+            "plugin_config_id": {"type": "string"},
+            "upstream": schema_ref("Upstream"),
+            "upstream_id": {"type": "string"},
+            "service_id": {"type": "string"},
+            "timeout": schema_ref("Timeout"),
+            "streaming": {"type": "boolean"},
+            "fallback": {"type": "boolean"},
+            "shed_priority": {"type": "string", "enum": ["low", "normal", "high", "critical"]},
+            "enabled": {"type": "boolean"},
+            "labels": labels_schema()
+        }
+    })
+}
+
+pub(super) fn upstream_schema() -> JsonValue {
+    json!({
+        "type": "object",
+        "properties": {
+            "id": {"type": "string"},
+            "nodes": {"type": "object", "additionalProperties": {"type": "integer", "minimum": 0}},
+            "type": {
+                "type": "string",
+                "enum": ["roundrobin", "random", "fnv", "ketama", "least_conn", "latency"]
+            },
+            "retries": {"type": "integer", "minimum": 0},
+            "retry_timeout": {"type": "integer", "minimum": 0},
+            "timeout": schema_ref("Timeout"),
+            "checks": {"type": "object"},
+            "hash_on": {"type": "string", "enum": ["vars", "head", "cookie"]},
+            "key": {"type": "string"},
+            "scheme": {
+                "type": "string",
+                "enum": ["http", "https", "http2", "https2", "grpc", "grpcs"]
+            },
+            "pass_host": {"type": "string", "enum": ["pass", "rewrite", "node"]},
+            "upstream_host": {"type": "string"},
+            "tls": {
+                "type": "object",
+                "properties": {
+                    "client_cert": {"type": "string"},
+                    "client_key": {"type": "string"},
+                    "sni": {"type": "string"},
+                    "verify": {"type": "boolean"},
+                    "server_name": {"type": "string"}
+                }
+            },
+            "zone_aware": {"type": "object"},
+            "discovery_type": {"type": "string", "enum": ["dns", "dns_srv"]},
+            "priorities": {"type": "object", "additionalProperties": {"type": "integer"}},
+            "sticky": {"type": "object"},
+            "labels": labels_schema()
+        },
+        "required": ["nodes"]
+    })
+}
+
+pub(super) fn service_schema() -> JsonValue {
+    json!({
+        "type": "object",
+        "properties": {
+            "id": {"type": "string"},
+            "plugins": schema_ref("Plugins"),
+            "upstream": schema_ref("Upstream"),
+            "upstream_id": {"type": "string"},
+            "hosts": strings_schema(),
+            "virtual_host": {"type": "boolean"},
+            "enabled": {"type": "boolean"},
+            "labels": labels_schema(),
+            "blue_green": {
+                "type": "object",
+                "required": ["blue", "green"],
+                "properties": {
+                    "blue": {"type": "string", "description": "upstream id"},
+                    "green": {"type": "string", "description": "upstream id"},
+                    "active": {"type": "string", "enum": ["blue", "green"], "default": "blue"},
+                    "weight": {"type": "integer", "minimum": 0, "maximum": 100, "default": 0},
+                    "header": {"type": "string"},
+                    "cookie": {"type": "string"}
+                }
+            }
+        }
+    })
+}
+
+pub(super) fn global_rule_schema() -> JsonValue {
+    json!({
+        "type": "object",
+        "properties": {
+            "id": {"type": "string"},
+            "plugins": schema_ref("Plugins"),
+            "enabled": {"type": "boolean"},
+            "labels": labels_schema(),
+            "hosts": strings_schema(),
+            "uri_prefixes": strings_schema(),
+            "route_labels": labels_schema()
+        }
+    })
+}
+
+pub(super) fn ssl_schema() -> JsonValue {
+    json!({
+        "type": "object",
+        "properties": {
+            "id": {"type": "string"},
+            "cert": {"type": "string", "description": "PEM certificate chain"},
+            "key": {"type": "string", "description": "PEM private key"},
+            "pkcs12": {"type": "string", "description": "Base64 PKCS#12 bundle, instead of cert and key"},
+            "passphrase": {"type": "string", "description": "Passphrase of key or pkcs12; $env://NAME reads an environment variable"},
+            "snis": {"type": "array", "items": {"type": "string"}, "minItems": 1},
+            "labels": labels_schema()
+        },
+        "required": ["snis"]
+    })
+}
+
+pub(super) fn ip_list_schema() -> JsonValue {
+    json!({
+        "type": "object",
+        "properties": {
+            "id": {"type": "string"},
+            "desc": {"type": "string"},
+            "cidrs": strings_schema()
+        }
+    })
+}
+
+pub(super) fn plugin_config_schema() -> JsonValue {
+    json!({
+        "type": "object",
+        "properties": {
+            "id": {"type": "string"},
+            "desc": {"type": "string"},
+            "plugins": schema_ref("Plugins"),
+            "labels": labels_schema()
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn operations_carry_path_parameters_and_schemas() {
+        let schemas = BTreeMap::from([("Route", route_schema())]);
+        let doc = document(
+            vec![(
+                "/apisix/admin/routes/{id}".to_string(),
+                Method::PUT,
+                OperationDoc::new("routes", "Create or replace a route")
+                    .request(schema_ref("Route"))
+                    .query("force", "Delete dependents"),
+            )],
+            &schemas,
+        );
+
+        let put = &doc["paths"]["/apisix/admin/routes/{id}"]["put"];
+        assert_eq!(put["operationId"], "put_routes_id");
+        assert_eq!(put["parameters"][0]["name"], "id");
+        assert_eq!(put["parameters"][0]["in"], "path");
+        assert_eq!(put["parameters"][1]["in"], "query");
+        assert_eq!(
+            put["requestBody"]["content"]["application/json"]["schema"]["$ref"],
+            "#/components/schemas/Route"
+        );
+        assert!(doc["paths"][OPENAPI_PATH]["get"].is_object());
+        assert!(doc["components"]["schemas"]["Route"].is_object());
+        assert!(doc["components"]["schemas"]["Plugins"]["properties"]["limit-count"].is_object());
+    }
+}
@@ -0,0 +1,191 @@
+//! Route explainer behind `POST /apisix/admin/routes/match`.
+//!
+//! A synthetic request is matched against the live runtime snapshot with the same
+//! matcher the proxy uses, and the selected route is described together with the
+//! plugins in execution order and the upstream it would proxy to.
+
+use std::collections::BTreeMap;
+
+use http::header::HOST;
+use pingora_http::RequestHeader;
+use serde::Deserialize;
+use serde_json::{json, Value as JsonValue};
+
+use super::{ApiError, ApiResult};
+use crate::{
+    config,
+    core::RouteContext,
+    proxy::{
+        route::ProxyRoute,
+        runtime::{RuntimeSnapshot, RUNTIME},
+    },
+};
+
+/// Body of `POST /apisix/admin/routes/match`.
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub(super) struct MatchRequest {
+    #[serde(default = "MatchRequest::default_method")]
+    method: String,
+    /// Sent as the `Host` header; a `Host` entry in `headers` is used otherwise.
+    #[serde(default)]
+    host: Option<String>,
+    /// Listener port, for routes whose hosts name a port.
+    #[serde(default)]
+    port: Option<u16>,
+    /// Path and optional query string.
+    path: String,
+    #[serde(default)]
+    headers: BTreeMap<String, String>,
+}
+
+impl MatchRequest {
+    fn default_method() -> String {
+        "GET".to_string()
+    }
+
+    fn to_request_header(&self) -> ApiResult<RequestHeader> {
+        let invalid = |e: &dyn std::fmt::Display| ApiError::ValidationError(e.to_string());
+        let method = self
+            .method
+            .to_ascii_uppercase()
+            .parse::<http::Method>()
+            .map_err(|e| invalid(&e))?;
+        if !self.path.starts_with('/') {
+            return Err(ApiError::ValidationError(
+                "'path' must start with '/'".into(),
+            ));
+        }
+        let mut request =
+            RequestHeader::build(method, self.path.as_bytes(), None).map_err(|e| invalid(&e))?;
+        for (name, value) in &self.headers {
+            request
+                .append_header(name.clone(), value.as_str())
+                .map_err(|e| invalid(&e))?;
+        }
+        if let Some(host) = &self.host {
+            request
+                .insert_header(HOST, host.as_str())
+                .map_err(|e| invalid(&e))?;
+        }
+        Ok(request)
+    }
+}
+
+/// Describe what the live configuration does with `request`.
+pub(super) fn explain(request: &MatchRequest) -> ApiResult<JsonValue> {
+    let header = request.to_request_header()?;
+    let runtime = RUNTIME.load();
+    let global_has_cors = runtime.global_plugins.has_plugin("cors");
+    let Some((kind, params, route)) =
+        runtime
+            .route_matcher
+            .match_header(&header, request.port, global_has_cors)
+    else {
+        return Ok(json!({ "matched": false, "revision": runtime.revision }));
+    };
+
+    let params: BTreeMap<String, String> = params.into_iter().collect();
+    Ok(json!({
+        "matched": true,
+        "revision": runtime.revision,
+        "match_type": kind.as_str(),
+        "route": {
+            "id": route.inner.id,
+            "service_id": route.inner.service_id,
+            "priority": route.inner.priority,
+            "uris": route.inner.get_uris(),
+            "hosts": route.effective_hosts(),
+            "methods": route.inner.methods.iter().map(|m| m.as_str()).collect::<Vec<_>>(),
+        },
+        "params": params,
+        "plugins": plugin_chain(&runtime, &route, &header),
+        "upstream": upstream_of(&runtime, &route),
+    }))
+}
+
+/// Plugins in the order they run: global rules first, then the merged route and
+/// service plugins.
+fn plugin_chain(
+    runtime: &RuntimeSnapshot,
+    route: &ProxyRoute,
+    header: &RequestHeader,
+) -> Vec<JsonValue> {
+    let global = runtime.global_plugins_for(Some(route), header);
+    let global = global.plugins.iter().map(
+        |plugin| json!({ "name": plugin.name(), "priority": plugin.priority(), "scope": "global" }),
+    );
+    let merged = route.build_plugin_executor();
+    let scoped = merged.plugins.iter().map(|plugin| {
+        let scope = if route.inner.plugins.contains_key(plugin.name()) {
+            "route"
+        } else {
+            "service"
+        };
+        json!({ "name": plugin.name(), "priority": plugin.priority(), "scope": scope })
+    });
+    global.chain(scoped).collect()
+}
+
+/// The configured upstream: the route's own, else its service's. Plugins such as
+/// `traffic-split` may still pick another one per request.
+fn upstream_of(runtime: &RuntimeSnapshot, route: &ProxyRoute) -> JsonValue {
+    let service = route
+        .inner
+        .service_id
+        .as_deref()
+        .and_then(|id| runtime.services.get(id));
+    let (source, inline, upstream_id) = if route.inner.upstream.is_some() {
+        ("route", route.inner.upstream.as_ref(), None)
+    } else if route.inner.upstream_id.is_some() {
+        ("route", None, route.inner.upstream_id.as_deref())
+    } else if let Some(service) = service {
+        (
+            "service",
+            service.inner.upstream.as_ref(),
+            service.inner.upstream_id.as_deref(),
+        )
+    } else {
+        return JsonValue::Null;
+    };
+
+    let upstream: Option<&config::Upstream> = match upstream_id {
+        Some(id) => runtime.upstreams.get(id).map(|upstream| &upstream.inner),
+        None => inline,
+    };
+    let Some(upstream) = upstream else {
+        return JsonValue::Null;
+    };
+    json!({
+        "source": source,
+        "id": upstream_id,
+        "type": upstream.r#type,
+        "scheme": upstream.scheme,
+        "nodes": upstream.nodes,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn synthetic_request_sets_host_and_headers() {
+        let request: MatchRequest = serde_json::from_value(json!({
+            "method": "options",
+            "host": "api.example.com",
+            "path": "/users/1?verbose=1",
+            "headers": {"Origin": "https://app.example.com", "Host": "ignored.example.com"},
+        }))
+        .unwrap();
+        let header = request.to_request_header().unwrap();
+        assert_eq!(header.method, http::Method::OPTIONS);
+        assert_eq!(header.uri.path(), "/users/1");
+        assert_eq!(header.headers.get(HOST).unwrap(), "api.example.com");
+        assert_eq!(header.headers.get_all(HOST).iter().count(), 1);
+        assert!(header.headers.contains_key("origin"));
+
+        let relative: MatchRequest = serde_json::from_value(json!({"path": "users"})).unwrap();
+        assert!(relative.to_request_header().is_err());
+    }
+}
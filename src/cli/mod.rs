@@ -0,0 +1,239 @@
+//! Command-line interface. Without a subcommand `pingsix` runs the gateway with the
+//! Pingora server options; subcommands are one-shot operator tools.
+
+use std::fmt::Write as _;
+
+use clap::{Args, Parser, Subcommand};
+use pingora_core::server::configuration::Opt;
+
+use crate::{
+    config::Config,
+    core::{ProxyError, ProxyResult},
+    migration::{self, MigrateArgs},
+    plugins,
+    proxy::control_plane::compile_static_configurations,
+};
+
+#[derive(Parser)]
+#[command(name = "pingsix", version, about = "PingSIX API gateway", long_about = None)]
+pub struct Cli {
+    #[command(subcommand)]
+    pub command: Option<Command>,
+
+    #[command(flatten)]
+    pub server: Opt,
+}
+
+#[derive(Subcommand)]
+pub enum Command {
+    /// Validate a configuration file and its static resources, then exit.
+    Check(CheckArgs),
+    /// Inspect routing.
+    #[command(subcommand)]
+    Routes(RoutesCommand),
+    /// Inspect the plugin registry.
+    #[command(subcommand)]
+    Plugins(PluginsCommand),
+    /// Convert another gateway's etcd configuration into the pingsix prefix.
+    Migrate(MigrateArgs),
+}
+
+/// Arguments of `pingsix check`.
+#[derive(Debug, Args)]
+pub struct CheckArgs {
+    /// Configuration file to validate.
+    #[arg(short, long)]
+    pub conf: String,
+}
+
+#[derive(Subcommand)]
+pub enum RoutesCommand {
+    /// Print the route a request would match.
+    Test(RouteTestArgs),
+}
+
+/// Arguments of `pingsix routes test`.
+#[derive(Debug, Args)]
+pub struct RouteTestArgs {
+    /// Configuration file whose routes are matched.
+    #[arg(short, long)]
+    pub conf: String,
+
+    /// Request `Host` (without port).
+    #[arg(long)]
+    pub host: Option<String>,
+
+    /// Port the request is addressed to, for routes whose hosts name a port.
+    #[arg(long)]
+    pub port: Option<u16>,
+
+    /// Request path.
+    #[arg(long)]
+    pub path: String,
+
+    /// Request method.
+    #[arg(long, default_value = "GET")]
+    pub method: String,
+}
+
+#[derive(Subcommand)]
+pub enum PluginsCommand {
+    /// List registered plugins in execution order.
+    List,
+}
+
+impl Command {
+    /// Run the subcommand and return the process exit code.
+    pub fn run(self) -> i32 {
+        env_logger::init();
+        let (name, result) = match self {
+            Command::Check(args) => ("check", check(&args)),
+            Command::Routes(RoutesCommand::Test(args)) => ("routes test", test_route(&args)),
+            Command::Plugins(PluginsCommand::List) => ("plugins list", Ok(list_plugins())),
+            Command::Migrate(args) => (
+                "migrate",
+                migration::run(&args).map(|report| report.to_string()),
+            ),
+        };
+        match result {
+            Ok(output) => {
+                print!("{output}");
+                0
+            }
+            Err(e) => {
+                eprintln!("pingsix {name} failed: {e}");
+                1
+            }
+        }
+    }
+}
+
+fn load_config(path: &str) -> ProxyResult<Config> {
+    Config::load_from_yaml(path)
+        .map_err(|e| ProxyError::Configuration(format!("Failed to load '{path}': {e}")))
+}
+
+/// Validate the file and compile its static resources the way startup would.
+fn check(args: &CheckArgs) -> ProxyResult<String> {
+    let config = load_config(&args.conf)?;
+    if config.pingsix.has_dynamic_store() {
+        return Ok(format!(
+            "{}: configuration is valid (resources are loaded from the config store)\n",
+            args.conf
+        ));
+    }
+    let snapshot = compile_static_configurations(&config)?;
+    Ok(format!(
+        "{}: configuration is valid ({} routes, {} services, {} upstreams, {} global rules, {} ssls)\n",
+        args.conf,
+        snapshot.routes.len(),
+        snapshot.services.len(),
+        snapshot.upstreams.len(),
+        snapshot.global_rules.len(),
+        snapshot.ssls.len()
+    ))
+}
+
+/// Match a synthetic request against the static routes of the file.
+fn test_route(args: &RouteTestArgs) -> ProxyResult<String> {
+    let config = load_config(&args.conf)?;
+    let snapshot = compile_static_configurations(&config)?;
+    let method = args.method.to_ascii_uppercase();
+    let matcher = &snapshot.route_matcher;
+    let host = args.host.as_deref();
+    let Some((params, route)) = matcher
+        .match_host_port_uri_method(host, args.port, &args.path, &method)
+        .or_else(|| matcher.match_fallback_host(host, args.port, &method))
+    else {
+        return Err(ProxyError::Configuration(format!(
+            "No route matches {method} {}{}",
+            args.host.as_deref().unwrap_or(""),
+            args.path
+        )));
+    };
+
+    let route = &route.inner;
+    let mut output = format!("route: {}\npriority: {}\n", route.id, route.priority);
+    if route.fallback {
+        let _ = writeln!(output, "fallback: true");
+    }
+    if let Some(service_id) = &route.service_id {
+        let _ = writeln!(output, "service_id: {service_id}");
+    }
+    if let Some(upstream_id) = &route.upstream_id {
+        let _ = writeln!(output, "upstream_id: {upstream_id}");
+    } else if route.upstream.is_some() {
+        let _ = writeln!(output, "upstream: inline");
+    }
+    for (name, value) in params {
+        let _ = writeln!(output, "param {name}: {value}");
+    }
+    Ok(output)
+}
+
+fn list_plugins() -> String {
+    plugins::registered_plugins()
+        .into_iter()
+        .fold(String::new(), |mut output, (name, priority)| {
+            let _ = writeln!(output, "{priority:>6}  {name}");
+            output
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_list_plugins_in_execution_order() {
+        let output = list_plugins();
+        let priorities: Vec<i32> = output
+            .lines()
+            .map(|line| line.split_whitespace().next().unwrap().parse().unwrap())
+            .collect();
+        assert!(priorities.windows(2).all(|w| w[0] >= w[1]));
+        assert!(output.lines().next().unwrap().ends_with("client-control"));
+    }
+
+    #[test]
+    fn test_route_test_prints_matched_route() {
+        let path = std::env::temp_dir().join(format!("pingsix-cli-{}.yaml", std::process::id()));
+        std::fs::write(
+            &path,
+            r#"
+pingsix:
+  listeners:
+    - address: "[::1]:8080"
+
+routes:
+  - id: "api"
+    uri: /api/{*path}
+    host: api.example.com
+    priority: 10
+    upstream_id: "1"
+
+upstreams:
+  - id: "1"
+    nodes:
+      "127.0.0.1:1980": 1
+"#,
+        )
+        .unwrap();
+        let conf = path.to_string_lossy().into_owned();
+
+        let mut args = RouteTestArgs {
+            conf,
+            host: Some("api.example.com".into()),
+            port: None,
+            path: "/api/users".into(),
+            method: "get".into(),
+        };
+        let output = test_route(&args).unwrap();
+        assert!(output.contains("route: api\npriority: 10\nupstream_id: 1\n"));
+        assert!(output.contains("param path: users"));
+
+        args.host = Some("other.example.com".into());
+        assert!(test_route(&args).is_err());
+        std::fs::remove_file(path).unwrap();
+    }
+}
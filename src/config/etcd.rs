@@ -1,29 +1,22 @@
-use std::time::Duration;
+use std::{collections::HashMap, time::Duration};
 
 use url::Url;
 
 use async_trait::async_trait;
 use etcd_client::{
-    Client, Compare, CompareOp, ConnectOptions, Event, GetOptions, GetResponse, Txn, TxnOp,
-    WatchOptions,
-};
-use pingora::server::ListenFds;
-use pingora_core::{server::ShutdownWatch, services::Service};
-use tokio::{
-    sync::{Mutex, OnceCell},
-    time::sleep,
+    Client, Compare, CompareOp, ConnectOptions, EventType, GetOptions, Txn, TxnOp, WatchOptions,
 };
+use tokio::sync::{Mutex, OnceCell};
 
-use super::{Etcd, EtcdTls};
+use super::{
+    provider::{ConfigChange, ConfigEventHandler, ConfigListing, ConfigProvider},
+    Etcd, EtcdTls,
+};
 use crate::{
     core::{status, ProxyError, ProxyResult},
     proxy::control_plane::CONTROL_PLANE,
 };
 
-// Retry delay constants
-const LIST_RETRY_DELAY: Duration = Duration::from_secs(3);
-const WATCH_RETRY_DELAY: Duration = Duration::from_secs(1);
-
 /// Normalize an etcd namespace so range queries cannot leak across sibling prefixes.
 ///
 /// `/apisix` and `/apisix/` both become `/apisix/`, which excludes `/apisix-other/...`.
@@ -36,26 +29,33 @@ pub fn canonicalize_prefix(prefix: &str) -> String {
     }
 }
 
-/// Service responsible for syncing and watching etcd configuration changes.
-pub struct EtcdConfigSync {
+/// [`ConfigProvider`] backed by an etcd prefix range and a revisioned watch.
+pub struct EtcdProvider {
     config: Etcd,
     /// Trailing-slash form used for list/watch range queries.
     canonical_prefix: String,
     client: Option<Client>,
+    /// Last revision delivered to the handler; watches resume right after it.
     revision: i64,
-    handler: Box<dyn EtcdEventHandler + Send + Sync>,
+    /// Why the last watch cannot be resumed, if it cannot.
+    resync: Option<&'static str>,
+    /// Revision a compaction cut the watch at; the next listing must not be older.
+    compacted_revision: Option<i64>,
+    /// `mod_revision` of every key seen, to log what a resync changed.
+    known: HashMap<String, i64>,
 }
 
-impl EtcdConfigSync {
-    pub fn new(config: Etcd, handler: Box<dyn EtcdEventHandler + Send + Sync>) -> Self {
+impl EtcdProvider {
+    pub fn new(config: Etcd) -> Self {
         let canonical_prefix = canonicalize_prefix(&config.prefix);
-        CONTROL_PLANE.set_etcd_prefix(canonical_prefix.clone());
         Self {
             config,
             canonical_prefix,
             client: None,
             revision: 0,
-            handler,
+            resync: None,
+            compacted_revision: None,
+            known: HashMap::new(),
         }
     }
 
@@ -70,9 +70,20 @@ impl EtcdConfigSync {
             .as_mut()
             .ok_or_else(|| ProxyError::etcd_error("Etcd client is not initialized"))
     }
+}
+
+#[async_trait]
+impl ConfigProvider for EtcdProvider {
+    fn source(&self) -> status::ConfigSource {
+        status::ConfigSource::Etcd
+    }
+
+    fn prefix(&self) -> &str {
+        &self.canonical_prefix
+    }
 
     /// Synchronize etcd data on initialization.
-    async fn list(&mut self) -> ProxyResult<()> {
+    async fn list(&mut self) -> ProxyResult<ConfigListing> {
         let prefix = self.canonical_prefix.clone();
         let client = self.get_client().await?;
 
@@ -84,21 +95,60 @@ impl EtcdConfigSync {
                 ProxyError::etcd_error_with_cause(format!("Failed to list key '{prefix}'"), e)
             })?;
 
-        if let Some(header) = response.header() {
-            self.revision = header.revision();
-        } else {
+        let Some(header) = response.header() else {
             return Err(ProxyError::etcd_error(
                 "Failed to get header from list response",
             ));
+        };
+        let revision = header.revision();
+        if let Some(compacted) = self.compacted_revision {
+            // A lagging member may still serve the compacted history; wait for one
+            // that has caught up rather than going back in time.
+            if revision < compacted {
+                return Err(ProxyError::etcd_error(format!(
+                    "Resync listing at revision {revision} predates compaction at {compacted}"
+                )));
+            }
         }
 
-        self.handler.handle_list_response(&response).await?;
-        status::record_sync_success(self.revision);
-        Ok(())
+        let listed: HashMap<String, i64> = response
+            .kvs()
+            .iter()
+            .map(|kv| {
+                (
+                    String::from_utf8_lossy(kv.key()).into_owned(),
+                    kv.mod_revision(),
+                )
+            })
+            .collect();
+        if self.revision > 0 {
+            log_resync_diff(&self.known, &listed, revision);
+        }
+        self.known = listed;
+        self.compacted_revision = None;
+        self.revision = revision;
+
+        Ok(ConfigListing {
+            revision,
+            kvs: response
+                .kvs()
+                .iter()
+                .map(|kv| {
+                    (
+                        String::from_utf8_lossy(kv.key()).into_owned(),
+                        kv.value().to_vec(),
+                    )
+                })
+                .collect(),
+        })
     }
 
     /// Watch for etcd data changes.
-    async fn watch(&mut self) -> ProxyResult<()> {
+    ///
+    /// Connection failures leave `resync` unset so the next watch resumes after the last
+    /// delivered revision; compaction and rejected batches require a relist.
+    async fn watch(&mut self, handler: &(dyn ConfigEventHandler + Send + Sync)) -> ProxyResult<()> {
+        self.resync = None;
         let prefix = self.canonical_prefix.clone();
         let start_revision = self.revision + 1;
         let options = WatchOptions::new()
@@ -117,7 +167,7 @@ impl EtcdConfigSync {
                 ProxyError::etcd_error_with_cause(format!("Failed to watch key '{prefix}'"), e)
             })?;
 
-        status::mark_etcd_connected(true);
+        status::mark_connected(true);
 
         // Periodically request progress so last_success advances even when the server
         // is quiet and its own progress interval is longer than config_stale_after.
@@ -136,15 +186,67 @@ impl EtcdConfigSync {
                         break;
                     };
 
+                    if response.compact_revision() > 0 {
+                        self.resync = Some("compacted");
+                        self.compacted_revision = Some(response.compact_revision());
+                        return Err(ProxyError::etcd_error(format!(
+                            "Watch from revision {start_revision} was compacted at {}",
+                            response.compact_revision()
+                        )));
+                    }
+
                     if response.canceled() {
                         log::debug!("Watch stream for prefix '{prefix}' was canceled");
+                        self.resync = Some("canceled");
                         break;
                     }
 
+                    // Use the highest revision observed in the batch when available.
+                    let revision = response
+                        .events()
+                        .iter()
+                        .filter_map(|event| event.kv().map(|kv| kv.mod_revision()))
+                        .max()
+                        .unwrap_or(0);
+                    let changes = response
+                        .events()
+                        .iter()
+                        .map(|event| {
+                            let kv = event.kv().ok_or_else(|| {
+                                ProxyError::Configuration("Etcd event missing key-value pair".into())
+                            })?;
+                            let key = String::from_utf8_lossy(kv.key()).into_owned();
+                            Ok(match event.event_type() {
+                                EventType::Put => ConfigChange::Put {
+                                    key,
+                                    value: kv.value().to_vec(),
+                                },
+                                EventType::Delete => ConfigChange::Delete { key },
+                            })
+                        })
+                        .collect::<ProxyResult<Vec<_>>>();
+
                     // Propagate handler failures so the sync loop relists instead of
                     // silently advancing past a rejected revision.
-                    // Progress responses have no events; handle_events is a no-op for them.
-                    self.handler.handle_events(response.events()).await?;
+                    // Progress responses have no events; handle_changes is a no-op for them.
+                    let applied = match changes {
+                        Ok(changes) => handler.handle_changes(&changes, revision).await,
+                        Err(e) => Err(e),
+                    };
+                    if let Err(e) = applied {
+                        self.resync = Some("rejected");
+                        return Err(e);
+                    }
+
+                    for event in response.events() {
+                        if let Some(kv) = event.kv() {
+                            let key = String::from_utf8_lossy(kv.key()).into_owned();
+                            match event.event_type() {
+                                EventType::Put => self.known.insert(key, kv.mod_revision()),
+                                EventType::Delete => self.known.remove(&key),
+                            };
+                        }
+                    }
 
                     if let Some(header) = response.header() {
                         self.revision = header.revision();
@@ -164,109 +266,54 @@ impl EtcdConfigSync {
         Ok(())
     }
 
-    /// Reset the client on failure.
-    fn reset_client(&mut self) {
-        log::debug!("Resetting etcd client for prefix '{}'", self.config.prefix);
+    /// Reset the client on failure. The revision is kept so the watch can resume.
+    fn reset(&mut self) {
         self.client = None;
-        status::mark_etcd_connected(false);
     }
 
-    /// Main task loop for synchronization.
-    async fn run_sync_loop(&mut self, mut shutdown: ShutdownWatch) {
-        loop {
-            tokio::select! {
-                biased;
-                _ = shutdown.changed() => {
-                    if *shutdown.borrow() {
-                        log::debug!("Shutdown signal received, stopping etcd config sync for prefix '{}'", self.config.prefix);
-                        CONTROL_PLANE.stop_preparation_worker().await;
-                        return;
-                    }
-                },
-
-                result = self.list() => {
-                    if let Err(err) = result {
-                        log::error!("List operation failed for prefix '{}': {:?}", self.config.prefix, err);
-                        status::record_sync_error(err.to_string());
-                        self.reset_client();
-                        if sleep_or_shutdown(LIST_RETRY_DELAY, &shutdown).await {
-                            CONTROL_PLANE.stop_preparation_worker().await;
-                            return;
-                        }
-                        continue;
-                    }
-                }
-            }
-
-            tokio::select! {
-                biased;
-                _ = shutdown.changed() => {
-                    if *shutdown.borrow() {
-                        log::debug!("Shutdown signal received, stopping etcd config sync for prefix '{}'", self.config.prefix);
-                        CONTROL_PLANE.stop_preparation_worker().await;
-                        return;
-                    }
-                },
-
-                result = self.watch() => {
-                    if let Err(err) = result {
-                        log::error!("Watch operation failed for prefix '{}': {:?}", self.config.prefix, err);
-                        status::record_sync_error(err.to_string());
-                        self.reset_client();
-                        if sleep_or_shutdown(WATCH_RETRY_DELAY, &shutdown).await {
-                            CONTROL_PLANE.stop_preparation_worker().await;
-                            return;
-                        }
-                        // Loop continues to list() — full resync after watch failure.
-                    }
-                }
-            }
-        }
+    fn resync_reason(&self) -> Option<&'static str> {
+        self.resync
     }
 }
 
-#[async_trait]
-impl Service for EtcdConfigSync {
-    async fn start_service(
-        &mut self,
-        _fds: Option<ListenFds>,
-        shutdown: ShutdownWatch,
-        _listeners_per_fd: usize,
-    ) {
-        status::begin_etcd_sync();
-        self.run_sync_loop(shutdown).await
-    }
-
-    fn name(&self) -> &'static str {
-        "Etcd config SYNC"
-    }
-
-    fn threads(&self) -> Option<usize> {
-        Some(1)
+/// Keys a resync listing added, changed or removed compared to what was known.
+fn resync_diff<'a>(
+    known: &'a HashMap<String, i64>,
+    listed: &'a HashMap<String, i64>,
+) -> (Vec<&'a str>, Vec<&'a str>, Vec<&'a str>) {
+    let mut added = Vec::new();
+    let mut changed = Vec::new();
+    for (key, revision) in listed {
+        match known.get(key) {
+            None => added.push(key.as_str()),
+            Some(known) if known != revision => changed.push(key.as_str()),
+            Some(_) => {}
+        }
     }
+    let mut removed: Vec<&str> = known
+        .keys()
+        .filter(|key| !listed.contains_key(*key))
+        .map(String::as_str)
+        .collect();
+    added.sort_unstable();
+    changed.sort_unstable();
+    removed.sort_unstable();
+    (added, changed, removed)
 }
 
-#[async_trait]
-pub trait EtcdEventHandler {
-    /// Submit all events from one etcd watch response. Acceptance is fast; DNS
-    /// preparation and publishing are owned by the control-plane worker.
-    async fn handle_events(&self, events: &[Event]) -> ProxyResult<()>;
-
-    async fn handle_list_response(&self, response: &GetResponse) -> ProxyResult<()>;
-}
-
-/// Sleep for `delay`, but return `true` immediately if shutdown is requested.
-async fn sleep_or_shutdown(delay: Duration, shutdown: &ShutdownWatch) -> bool {
-    let mut shutdown = shutdown.clone();
-    tokio::select! {
-        _ = sleep(delay) => false,
-        result = shutdown.changed() => {
-            match result {
-                Ok(()) => *shutdown.borrow(),
-                Err(_) => true,
-            }
-        }
-    }
+fn log_resync_diff(known: &HashMap<String, i64>, listed: &HashMap<String, i64>, revision: i64) {
+    let (added, changed, removed) = resync_diff(known, listed);
+    if added.is_empty() && changed.is_empty() && removed.is_empty() {
+        log::info!("Etcd resync at revision {revision}: no changes missed");
+        return;
+    }
+    log::warn!(
+        "Etcd resync at revision {revision}: {} added, {} changed, {} removed",
+        added.len(),
+        changed.len(),
+        removed.len()
+    );
+    log::debug!("Etcd resync added {added:?}, changed {changed:?}, removed {removed:?}");
 }
 
 async fn create_client(cfg: &Etcd) -> ProxyResult<Client> {
@@ -384,6 +431,9 @@ pub struct FullGraph {
 }
 
 /// Wrapper for etcd client used by Admin API, ensuring local mutability.
+/// etcd's default `--max-txn-ops`.
+pub const DEFAULT_MAX_TXN_OPS: usize = 128;
+
 pub struct EtcdClientWrapper {
     config: Etcd,
     canonical_prefix: String,
@@ -393,7 +443,7 @@ pub struct EtcdClientWrapper {
 impl EtcdClientWrapper {
     pub fn new(cfg: Etcd) -> Self {
         let canonical_prefix = canonicalize_prefix(&cfg.prefix);
-        CONTROL_PLANE.set_etcd_prefix(canonical_prefix.clone());
+        CONTROL_PLANE.set_config_prefix(canonical_prefix.clone());
         Self {
             config: cfg,
             canonical_prefix,
@@ -406,6 +456,11 @@ impl EtcdClientWrapper {
         &self.canonical_prefix
     }
 
+    /// Most operations allowed in one transaction.
+    pub fn max_txn_ops(&self) -> usize {
+        self.config.max_txn_ops.unwrap_or(DEFAULT_MAX_TXN_OPS)
+    }
+
     async fn ensure_connected(&self) -> ProxyResult<&Mutex<Client>> {
         self.client
             .get_or_try_init(|| async {
@@ -448,6 +503,62 @@ impl EtcdClientWrapper {
         Ok(())
     }
 
+    /// Get a key outside the configuration namespace.
+    pub async fn get_absolute(&self, key: &str) -> ProxyResult<Option<Vec<u8>>> {
+        let client_mutex = self.ensure_connected().await?;
+        let mut client = client_mutex.lock().await;
+
+        client
+            .get(key.as_bytes(), None)
+            .await
+            .map_err(|e| ProxyError::etcd_error_with_cause(format!("Failed to get key '{key}'"), e))
+            .map(|resp| resp.kvs().first().map(|kv| kv.value().to_vec()))
+    }
+
+    /// List the keys (without values) under a prefix outside the configuration namespace.
+    pub async fn list_keys_absolute(&self, prefix: &str) -> ProxyResult<Vec<String>> {
+        let client_mutex = self.ensure_connected().await?;
+        let mut client = client_mutex.lock().await;
+
+        let options = GetOptions::new().with_prefix().with_keys_only();
+        let response = client
+            .get(prefix.as_bytes(), Some(options))
+            .await
+            .map_err(|e| {
+                ProxyError::etcd_error_with_cause(
+                    format!("List operation for prefix '{prefix}' failed"),
+                    e,
+                )
+            })?;
+        Ok(response
+            .kvs()
+            .iter()
+            .map(|kv| String::from_utf8_lossy(kv.key()).into_owned())
+            .collect())
+    }
+
+    /// Delete a key outside the configuration namespace.
+    pub async fn delete_absolute(&self, key: &str) -> ProxyResult<()> {
+        let client_mutex = self.ensure_connected().await?;
+        let mut client = client_mutex.lock().await;
+
+        client.delete(key.as_bytes(), None).await.map_err(|e| {
+            ProxyError::etcd_error_with_cause(format!("Delete operation for key '{key}' failed"), e)
+        })?;
+        Ok(())
+    }
+
+    /// Put a key outside the configuration namespace (e.g. the admin audit history).
+    pub async fn put_absolute(&self, key: &str, value: Vec<u8>) -> ProxyResult<()> {
+        let client_mutex = self.ensure_connected().await?;
+        let mut client = client_mutex.lock().await;
+
+        client.put(key.as_bytes(), value, None).await.map_err(|e| {
+            ProxyError::etcd_error_with_cause(format!("Put operation for key '{key}' failed"), e)
+        })?;
+        Ok(())
+    }
+
     pub async fn delete(&self, key: &str) -> ProxyResult<()> {
         let client_mutex = self.ensure_connected().await?;
         let mut client = client_mutex.lock().await;
@@ -600,6 +711,59 @@ impl EtcdClientWrapper {
         self.graph_txn(key, None, Some(expected), guard).await
     }
 
+    /// Atomically apply a batch of puts and deletes (physical keys) and advance the graph
+    /// generation guard. Only the guard is compared: any concurrent supported mutation
+    /// advances it, so the batch applies to exactly the graph it was validated against.
+    ///
+    /// etcd limits operations per transaction (`--max-txn-ops`, 128 by default); callers
+    /// check [`Self::max_txn_ops`] first.
+    pub async fn graph_txn_batch(
+        &self,
+        puts: Vec<(String, Vec<u8>)>,
+        deletes: Vec<String>,
+        guard_mod_revision: Option<i64>,
+    ) -> ProxyResult<i64> {
+        let client_mutex = self.ensure_connected().await?;
+        let mut client = client_mutex.lock().await;
+        let guard_key = self.prefixed_key(GRAPH_REVISION_KEY);
+        let guard = match guard_mod_revision {
+            None => Compare::create_revision(guard_key.as_bytes(), CompareOp::Equal, 0),
+            Some(revision) => {
+                Compare::mod_revision(guard_key.as_bytes(), CompareOp::Equal, revision)
+            }
+        };
+        let mut ops: Vec<TxnOp> = puts
+            .into_iter()
+            .map(|(key, value)| TxnOp::put(key.as_bytes(), value, None))
+            .collect();
+        ops.extend(
+            deletes
+                .iter()
+                .map(|key| TxnOp::delete(key.as_bytes(), None)),
+        );
+        ops.push(TxnOp::put(
+            guard_key.as_bytes(),
+            GRAPH_PROTOCOL_VERSION.to_vec(),
+            None,
+        ));
+        let txn = Txn::new().when(vec![guard]).and_then(ops);
+        let response = client
+            .txn(txn)
+            .await
+            .map_err(|e| ProxyError::etcd_error_with_cause("graph batch transaction failed", e))?;
+        if !response.succeeded() {
+            return Err(ProxyError::CasConflict(
+                "configuration graph changed concurrently".into(),
+            ));
+        }
+        response
+            .header()
+            .map(|header| header.revision())
+            .ok_or_else(|| {
+                ProxyError::etcd_error("graph batch transaction: missing response header")
+            })
+    }
+
     /// Returns the full physical etcd key (prefix + logical key). Exposed so the
     /// Admin write path can correlate `read_full_graph` keys (which are physical)
     /// with CAS operations.
@@ -664,9 +828,28 @@ fake-key
             user: None,
             password: None,
             tls: Some(tls),
+            max_txn_ops: None,
         }
     }
 
+    #[test]
+    fn resync_diff_reports_missed_changes() {
+        let known = HashMap::from([
+            ("/p/routes/1".to_string(), 5),
+            ("/p/routes/2".to_string(), 6),
+            ("/p/routes/3".to_string(), 7),
+        ]);
+        let listed = HashMap::from([
+            ("/p/routes/1".to_string(), 5),
+            ("/p/routes/2".to_string(), 9),
+            ("/p/routes/4".to_string(), 8),
+        ]);
+        let (added, changed, removed) = resync_diff(&known, &listed);
+        assert_eq!(added, vec!["/p/routes/4"]);
+        assert_eq!(changed, vec!["/p/routes/2"]);
+        assert_eq!(removed, vec!["/p/routes/3"]);
+    }
+
     #[test]
     fn canonicalize_prefix_adds_trailing_slash_and_isolates_siblings() {
         assert_eq!(canonicalize_prefix("/apisix"), "/apisix/");
@@ -769,6 +952,7 @@ fake-key
             user: Some("root".to_string()),
             password: Some("pw".to_string()),
             tls: None,
+            max_txn_ops: None,
         };
         // No TLS configured: options must build without invoking any file reads.
         assert!(build_connect_options(&cfg).is_ok());
@@ -1,9 +1,12 @@
 pub mod etcd;
+pub mod provider;
+pub mod redis;
+pub mod xds;
 
 use std::{
     collections::{HashMap, HashSet},
     fs,
-    net::SocketAddr,
+    net::{IpAddr, SocketAddr},
 };
 
 use http::Method;
@@ -14,12 +17,15 @@ use regex::Regex;
 use serde::{Deserialize, Serialize};
 use serde_json::Value as JsonValue;
 use serde_with::{serde_as, DisplayFromStr};
+use sha2::{Digest, Sha256};
 use validator::{Validate, ValidationError};
 
+use crate::core::status::SkippedResource;
+
 // Pre-compiled regex for upstream node validation to avoid per-request compilation overhead
 static NODE_KEY_REGEX: Lazy<Regex> = Lazy::new(|| {
     Regex::new(
-        r"(?i)^(?:(?:\d{1,3}\.){3}\d{1,3}|\[[0-9a-f:]+\]|[a-z0-9](?:[a-z0-9-]*[a-z0-9])?(?:\.[a-z0-9](?:[a-z0-9-]*[a-z0-9])?)*)(?::\d+)?$"
+        r"(?i)^(?:(?:\d{1,3}\.){3}\d{1,3}|\[[0-9a-f:]+\]|_?[a-z0-9](?:[a-z0-9-]*[a-z0-9])?(?:\._?[a-z0-9](?:[a-z0-9-]*[a-z0-9])?)*)(?::\d+)?$"
     ).expect("Invalid regex pattern for node key validation")
 });
 
@@ -48,6 +54,8 @@ impl_identifiable!(Upstream);
 impl_identifiable!(Service);
 impl_identifiable!(GlobalRule);
 impl_identifiable!(SSL);
+impl_identifiable!(IpList);
+impl_identifiable!(PluginConfig);
 
 /// Root configuration structure combining Pingora framework config with Pingsix-specific settings.
 #[serde_as]
@@ -79,6 +87,20 @@ pub struct Config {
     #[validate(nested)]
     #[serde(default)]
     pub ssls: Vec<SSL>,
+    #[validate(nested)]
+    #[serde(default)]
+    pub ip_lists: Vec<IpList>,
+    #[validate(nested)]
+    #[serde(default)]
+    pub plugin_configs: Vec<PluginConfig>,
+
+    /// Resources dropped while parsing under [`StaticLoadPolicy::Tolerant`].
+    #[serde(skip)]
+    pub skipped_resources: Vec<SkippedResource>,
+
+    /// Hex SHA-256 of the YAML the configuration was parsed from.
+    #[serde(skip)]
+    pub digest: String,
 }
 
 // Configuration loading and validation methods
@@ -111,18 +133,24 @@ impl Config {
 
     /// Parses YAML configuration string with comprehensive validation.
     pub fn from_yaml(conf_str: &str) -> Result<Self> {
-        let conf: Config = serde_yml::from_str(conf_str)
+        let mut conf: Config = serde_yml::from_str(conf_str)
             .or_err_with(ReadError, || "Unable to parse yaml configuration")?;
+        conf.digest = hex::encode(Sha256::digest(conf_str.as_bytes()));
 
         log::debug!(
-            "Loaded configuration with {} routes, {} upstreams, {} services, {} global rules, and {} SSL entries",
+            "Loaded configuration with {} routes, {} upstreams, {} services, {} global rules, {} SSL entries, and {} IP lists",
             conf.routes.len(),
             conf.upstreams.len(),
             conf.services.len(),
             conf.global_rules.len(),
             conf.ssls.len(),
+            conf.ip_lists.len(),
         );
 
+        if conf.pingsix.static_load_policy == StaticLoadPolicy::Tolerant {
+            conf.skipped_resources = conf.drop_invalid_resources();
+        }
+
         // Validate configuration structure and constraints
         conf.validate()
             .or_err_with(FileReadError, || "Conf file validation failed")?;
@@ -138,10 +166,99 @@ impl Config {
             .or_err_with(FileReadError, || "Global rule ID validation failed")?;
         Self::validate_unique_ids(&conf.ssls, "ssl")
             .or_err_with(FileReadError, || "SSL ID validation failed")?;
+        Self::validate_unique_ids(&conf.ip_lists, "ip_list")
+            .or_err_with(FileReadError, || "IP list ID validation failed")?;
+        Self::validate_unique_ids(&conf.plugin_configs, "plugin_config")
+            .or_err_with(FileReadError, || "Plugin config ID validation failed")?;
+
+        // Ensure upstream_id/service_id references resolve within the file
+        conf.validate_references()
+            .or_err_with(FileReadError, || "Resource reference validation failed")?;
 
+        conf.apply_shutdown();
         Ok(conf)
     }
 
+    /// Remove the resources that would fail validation, in dependency order so a
+    /// resource referencing a removed one is removed too.
+    fn drop_invalid_resources(&mut self) -> Vec<SkippedResource> {
+        let mut skipped = Vec::new();
+        retain_valid(&mut self.upstreams, "upstreams", &mut skipped);
+        retain_valid(&mut self.ssls, "ssls", &mut skipped);
+        retain_valid(&mut self.ip_lists, "ip_lists", &mut skipped);
+        retain_valid(&mut self.plugin_configs, "plugin_configs", &mut skipped);
+        retain_valid(&mut self.global_rules, "global_rules", &mut skipped);
+        retain_valid(&mut self.services, "services", &mut skipped);
+        retain_valid(&mut self.routes, "routes", &mut skipped);
+
+        let upstreams: HashSet<String> = self.upstreams.iter().map(|u| u.id.clone()).collect();
+        self.services.retain(|service| {
+            let missing = service
+                .upstream
+                .is_none()
+                .then(|| service.upstream_ids().find(|id| !upstreams.contains(*id)))
+                .flatten();
+            if let Some(id) = missing {
+                skipped.push(SkippedResource::new(
+                    "services",
+                    &service.id,
+                    format!("references missing upstream '{id}'"),
+                ));
+            }
+            missing.is_none()
+        });
+        let services: HashSet<&str> = self.services.iter().map(|s| s.id.as_str()).collect();
+        let plugin_configs: HashSet<&str> =
+            self.plugin_configs.iter().map(|p| p.id.as_str()).collect();
+        self.routes.retain(|route| {
+            let missing = if let Some(id) = route
+                .plugin_config_id
+                .as_ref()
+                .filter(|id| !plugin_configs.contains(id.as_str()))
+            {
+                format!("references missing plugin_config '{id}'")
+            } else if let Some(id) = route
+                .service_id
+                .as_ref()
+                .filter(|id| !services.contains(id.as_str()))
+            {
+                format!("references missing service '{id}'")
+            } else if let (None, Some(id)) = (&route.upstream, &route.upstream_id) {
+                if upstreams.contains(id) {
+                    return true;
+                }
+                format!("references missing upstream '{id}'")
+            } else {
+                return true;
+            };
+            skipped.push(SkippedResource::new("routes", &route.id, missing));
+            false
+        });
+
+        for resource in &skipped {
+            log::warn!(
+                "Skipping {}/{}: {}",
+                resource.resource_type,
+                resource.id,
+                resource.error
+            );
+        }
+        skipped
+    }
+
+    /// Fold `pingsix.shutdown` into the Pingora server settings that drive it.
+    fn apply_shutdown(&mut self) {
+        let Some(shutdown) = &self.pingsix.shutdown else {
+            return;
+        };
+        if let Some(grace_period) = shutdown.grace_period {
+            self.pingora.grace_period_seconds = Some(grace_period);
+        }
+        if let Some(drain_timeout) = shutdown.drain_timeout {
+            self.pingora.graceful_shutdown_timeout_seconds = Some(drain_timeout);
+        }
+    }
+
     /// Serializes configuration back to YAML format for debugging or export.
     #[cfg(test)]
     pub fn to_yaml(&self) -> String {
@@ -164,6 +281,8 @@ impl Config {
         Self::validate_non_empty_ids(&self.services, "service")?;
         Self::validate_non_empty_ids(&self.global_rules, "global_rule")?;
         Self::validate_non_empty_ids(&self.ssls, "ssl")?;
+        Self::validate_non_empty_ids(&self.ip_lists, "ip_list")?;
+        Self::validate_non_empty_ids(&self.plugin_configs, "plugin_config")?;
         Ok(())
     }
 
@@ -194,9 +313,85 @@ impl Config {
         }
         Ok(())
     }
+
+    /// Checks that every `upstream_id`/`service_id` names a resource defined in the file,
+    /// reporting all dangling references at once.
+    fn validate_references(&self) -> Result<()> {
+        let upstreams: HashSet<&str> = self.upstreams.iter().map(|u| u.id.as_str()).collect();
+        let services: HashSet<&str> = self.services.iter().map(|s| s.id.as_str()).collect();
+        let plugin_configs: HashSet<&str> =
+            self.plugin_configs.iter().map(|p| p.id.as_str()).collect();
+        let mut missing = Vec::new();
+
+        for route in &self.routes {
+            if let Some(id) = &route.plugin_config_id {
+                if !plugin_configs.contains(id.as_str()) {
+                    missing.push(format!(
+                        "route '{}' references missing plugin_config '{id}'",
+                        route.id
+                    ));
+                }
+            }
+            if let Some(id) = &route.service_id {
+                if !services.contains(id.as_str()) {
+                    missing.push(format!(
+                        "route '{}' references missing service '{id}'",
+                        route.id
+                    ));
+                }
+            }
+            if let (None, Some(id)) = (&route.upstream, &route.upstream_id) {
+                if !upstreams.contains(id.as_str()) {
+                    missing.push(format!(
+                        "route '{}' references missing upstream '{id}'",
+                        route.id
+                    ));
+                }
+            }
+        }
+        for service in self.services.iter().filter(|s| s.upstream.is_none()) {
+            for id in service.upstream_ids() {
+                if !upstreams.contains(id) {
+                    missing.push(format!(
+                        "service '{}' references missing upstream '{id}'",
+                        service.id
+                    ));
+                }
+            }
+        }
+
+        if missing.is_empty() {
+            Ok(())
+        } else {
+            Error::e_explain(FileReadError, missing.join("; "))
+        }
+    }
+}
+
+/// Keep the resources with a non-empty, unique id that pass validation; record the rest.
+fn retain_valid<T: Identifiable + Validate>(
+    items: &mut Vec<T>,
+    resource_type: &str,
+    skipped: &mut Vec<SkippedResource>,
+) {
+    let mut ids = HashSet::new();
+    items.retain(|item| {
+        let error = if item.id().is_empty() {
+            "id is required".to_string()
+        } else if !ids.insert(item.id().to_string()) {
+            "duplicate id".to_string()
+        } else if let Err(e) = item.validate() {
+            e.to_string()
+        } else {
+            return true;
+        };
+        skipped.push(SkippedResource::new(resource_type, item.id(), error));
+        false
+    });
 }
 
 #[derive(Clone, Default, Debug, Serialize, Deserialize, Validate)]
+#[validate(schema(function = "Pingsix::validate_config_store"))]
 #[serde(deny_unknown_fields)]
 pub struct Pingsix {
     #[validate(length(min = 1))]
@@ -206,6 +401,14 @@ pub struct Pingsix {
     #[validate(nested)]
     pub etcd: Option<Etcd>,
 
+    /// Redis config store, an alternative to `etcd`.
+    #[validate(nested)]
+    pub redis: Option<Redis>,
+
+    /// xDS control plane, an alternative to `etcd` and `redis`.
+    #[validate(nested)]
+    pub xds: Option<Xds>,
+
     #[validate(nested)]
     pub admin: Option<Admin>,
 
@@ -223,6 +426,111 @@ pub struct Pingsix {
 
     #[validate(nested)]
     pub defaults: Option<Defaults>,
+
+    /// Graceful shutdown sequencing.
+    #[validate(nested)]
+    pub shutdown: Option<Shutdown>,
+
+    /// Resolver settings for upstream DNS discovery.
+    #[validate(nested)]
+    pub dns: Option<Dns>,
+
+    /// Availability zone of this gateway instance, used by zone-aware upstreams.
+    /// Falls back to the `PINGSIX_ZONE` environment variable when unset.
+    pub zone: Option<String>,
+
+    /// Load shedding when the gateway itself is overloaded.
+    #[validate(nested)]
+    pub overload: Option<Overload>,
+
+    /// Slow request logging.
+    #[validate(nested)]
+    pub slow_log: Option<SlowLog>,
+
+    /// Timeouts and connection limits applied to client connections.
+    #[validate(nested)]
+    pub downstream: Option<Downstream>,
+
+    /// Header sanitization of requests and responses.
+    #[validate(nested)]
+    pub security: Option<Security>,
+
+    /// What to do with static resources that fail to load.
+    #[serde(default)]
+    pub static_load_policy: StaticLoadPolicy,
+
+    /// Worker threads and CPU placement of the gateway services.
+    #[validate(nested)]
+    pub runtime: Option<Runtime>,
+
+    /// Maintenance mode at startup; the config store's switch takes precedence.
+    #[validate(nested)]
+    pub maintenance: Option<Maintenance>,
+
+    /// Endpoint notified whenever the route table changes.
+    #[validate(nested)]
+    pub config_webhook: Option<ConfigWebhook>,
+
+    /// Soft limit in bytes on the memory held by the response cache, the DNS cache and
+    /// rate-limit state together.
+    #[validate(range(min = 1048576))]
+    pub memory_limit: Option<usize>,
+
+    /// Certificate expiry checks; they run with the defaults when unset.
+    #[validate(nested)]
+    pub cert_expiry: Option<CertExpiry>,
+}
+
+/// Handling of invalid resources in the static YAML file, at startup and on SIGHUP.
+#[derive(Clone, Copy, Default, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum StaticLoadPolicy {
+    /// Any invalid resource fails the load.
+    #[default]
+    Strict,
+    /// Invalid resources, and the resources depending on them, are left out and
+    /// reported by the status API; the rest are served.
+    Tolerant,
+}
+
+impl Pingsix {
+    fn validate_config_store(&self) -> Result<(), ValidationError> {
+        let stores = [
+            self.etcd.is_some(),
+            self.redis.is_some(),
+            self.xds.is_some(),
+        ];
+        if stores.into_iter().filter(|configured| *configured).count() > 1 {
+            return Err(ValidationError::new("config_stores_are_exclusive"));
+        }
+        if let (Some(admin), Some(etcd)) = (&self.admin, &self.etcd) {
+            let config_prefix = etcd::canonicalize_prefix(&etcd.prefix);
+            let overlaps = |prefix: &str| {
+                prefix.starts_with(&config_prefix) || config_prefix.starts_with(prefix)
+            };
+            let audit_prefix = admin
+                .audit
+                .as_ref()
+                .and_then(|audit| audit.etcd_prefix.as_deref())
+                .map(etcd::canonicalize_prefix);
+            if audit_prefix.is_some_and(|prefix| overlaps(&prefix)) {
+                return Err(ValidationError::new("audit_prefix_overlaps_etcd_prefix"));
+            }
+            let history_prefix = admin
+                .history
+                .as_ref()
+                .map(|history| history.prefix(&etcd.prefix));
+            if history_prefix.is_some_and(|prefix| overlaps(&prefix)) {
+                return Err(ValidationError::new("history_prefix_overlaps_etcd_prefix"));
+            }
+        }
+        Ok(())
+    }
+
+    /// Whether resources come from a watched store (etcd, Redis or xDS) instead of the YAML file.
+    pub fn has_dynamic_store(&self) -> bool {
+        self.etcd.is_some() || self.redis.is_some() || self.xds.is_some()
+    }
 }
 
 /// Global default settings applied when a route/upstream does not override them.
@@ -283,6 +591,8 @@ static DEFAULT_UPSTREAM_TIMEOUT: once_cell::sync::OnceCell<Option<Timeout>> =
 static DNS_RESOLUTION_TIMEOUT: once_cell::sync::OnceCell<u64> = once_cell::sync::OnceCell::new();
 static DNS_REFRESH_INTERVAL: once_cell::sync::OnceCell<Option<u64>> =
     once_cell::sync::OnceCell::new();
+static GATEWAY_ZONE: once_cell::sync::OnceCell<Option<String>> = once_cell::sync::OnceCell::new();
+static DNS_SETTINGS: once_cell::sync::OnceCell<Option<Dns>> = once_cell::sync::OnceCell::new();
 
 /// Populate the global default upstream timeout from configuration. Called once
 /// at startup. Subsequent calls are no-ops (first value wins), which keeps
@@ -316,12 +626,34 @@ pub fn dns_refresh_interval() -> Option<u64> {
     DNS_REFRESH_INTERVAL.get().cloned().flatten()
 }
 
+/// Populate the resolver settings from `pingsix.dns`. Must run before the first
+/// DNS upstream is built; the resolver is created once.
+pub fn init_dns_settings(dns: Option<Dns>) {
+    let _ = DNS_SETTINGS.set(dns);
+}
+
+/// Configured resolver settings, if any.
+pub fn dns_settings() -> Option<&'static Dns> {
+    DNS_SETTINGS.get().and_then(Option::as_ref)
+}
+
+/// Populate the gateway's own availability zone from configuration.
+pub fn init_gateway_zone(zone: Option<String>) {
+    let _ = GATEWAY_ZONE.set(zone.filter(|z| !z.is_empty()));
+}
+
+/// Zone of this gateway instance, if configured.
+pub fn gateway_zone() -> Option<&'static str> {
+    GATEWAY_ZONE.get().and_then(|zone| zone.as_deref())
+}
+
 /// Finite peer-I/O fallback used whenever neither a route nor upstream nor
 /// global defaults provide a timeout.
 pub const BUILTIN_UPSTREAM_TIMEOUT: Timeout = Timeout {
     connect: 5,
     send: 30,
     read: 30,
+    total: None,
 };
 
 /// Resolve the effective timeout for a route/upstream: explicit > configured
@@ -363,6 +695,48 @@ pub struct Listener {
     pub offer_h2: bool,
     #[serde(default)]
     pub offer_h2c: bool,
+    /// Socket options of the listening socket and its accepted connections.
+    #[validate(nested)]
+    pub socket: Option<ListenerSocket>,
+}
+
+/// TCP options for one listener. Unset fields keep the Pingora/kernel defaults.
+#[derive(Clone, Debug, Serialize, Deserialize, Validate)]
+#[serde(deny_unknown_fields)]
+pub struct ListenerSocket {
+    /// TCP keepalive probes on accepted connections.
+    #[validate(nested)]
+    pub tcp_keepalive: Option<TcpKeepalive>,
+    /// Disable Nagle's algorithm on accepted connections. Pingora enables it by
+    /// default; `false` turns it back off for HTTP/1 connections.
+    #[serde(default = "ListenerSocket::default_tcp_nodelay")]
+    pub tcp_nodelay: bool,
+    /// Enable TCP Fast Open with this pending-connection backlog (Linux only).
+    #[validate(range(min = 1))]
+    pub tcp_fastopen: Option<usize>,
+    /// Set `SO_REUSEPORT` so every worker thread gets its own accept queue.
+    pub reuseport: Option<bool>,
+}
+
+impl ListenerSocket {
+    fn default_tcp_nodelay() -> bool {
+        true
+    }
+}
+
+/// TCP keepalive timers, all in seconds except `count`.
+#[derive(Clone, Debug, Serialize, Deserialize, Validate)]
+#[serde(deny_unknown_fields)]
+pub struct TcpKeepalive {
+    /// Idle seconds before the first probe is sent.
+    #[validate(range(min = 1))]
+    pub idle: u64,
+    /// Seconds between two probes.
+    #[validate(range(min = 1))]
+    pub interval: u64,
+    /// Unanswered probes before the connection is dropped.
+    #[validate(range(min = 1))]
+    pub count: usize,
 }
 
 impl Listener {
@@ -389,6 +763,10 @@ pub struct Etcd {
     pub password: Option<String>,
     #[validate(nested)]
     pub tls: Option<EtcdTls>,
+    /// Most operations etcd accepts in one transaction, its `--max-txn-ops` (default 128).
+    #[serde(default)]
+    #[validate(range(min = 2))]
+    pub max_txn_ops: Option<usize>,
 }
 
 impl Etcd {
@@ -409,6 +787,57 @@ impl Etcd {
     }
 }
 
+/// Redis config store watched through keyspace notifications.
+#[derive(Clone, Debug, Serialize, Deserialize, Validate)]
+#[validate(schema(function = "Redis::validate_auth"))]
+#[serde(deny_unknown_fields)]
+pub struct Redis {
+    /// `host:port` of the Redis server.
+    #[validate(length(min = 1))]
+    pub address: String,
+    #[serde(default)]
+    pub db: u32,
+    #[validate(length(min = 1))]
+    pub prefix: String,
+    pub username: Option<String>,
+    pub password: Option<String>,
+    /// Connect and command timeout in seconds (default: 5).
+    #[validate(range(min = 1))]
+    pub timeout: Option<u32>,
+}
+
+impl Redis {
+    fn validate_auth(&self) -> Result<(), ValidationError> {
+        if self.username.is_some() && self.password.is_none() {
+            return Err(ValidationError::new("redis_username_requires_password"));
+        }
+        Ok(())
+    }
+}
+
+/// xDS control plane streamed over ADS (Aggregated Discovery Service).
+#[derive(Clone, Debug, Serialize, Deserialize, Validate)]
+#[serde(deny_unknown_fields)]
+pub struct Xds {
+    /// gRPC URL of the control plane, e.g. `http://istiod:15010`.
+    #[validate(url)]
+    pub address: String,
+    /// Node id presented to the control plane.
+    #[validate(length(min = 1))]
+    pub node_id: String,
+    /// Node cluster presented to the control plane.
+    pub cluster: Option<String>,
+    /// RDS route configuration names to subscribe to.
+    #[validate(length(min = 1))]
+    pub route_configs: Vec<String>,
+    /// Connect and keepalive timeout in seconds (default: 5).
+    #[validate(range(min = 1))]
+    pub timeout: Option<u32>,
+    /// Same shape as `etcd.tls`; requires an `https://` address.
+    #[validate(nested)]
+    pub tls: Option<EtcdTls>,
+}
+
 #[derive(Clone, Debug, Serialize, Deserialize, Validate)]
 #[serde(deny_unknown_fields)]
 pub struct Admin {
@@ -418,6 +847,65 @@ pub struct Admin {
     /// Allow binding Admin to a non-loopback address without TLS (default: false).
     #[serde(default)]
     pub allow_insecure_remote: bool,
+    /// Audit trail of configuration changes made through the Admin API.
+    #[validate(nested)]
+    #[serde(default)]
+    pub audit: Option<AdminAudit>,
+    /// Versioned snapshots of the resource graph for `POST /apisix/admin/rollback/{version}`.
+    #[validate(nested)]
+    #[serde(default)]
+    pub history: Option<AdminHistory>,
+}
+
+/// Where and how many configuration versions are kept.
+#[derive(Clone, Debug, Serialize, Deserialize, Validate)]
+#[serde(deny_unknown_fields)]
+pub struct AdminHistory {
+    /// etcd prefix of the snapshots (default: `etcd.prefix` + `_history`); it must lie
+    /// outside `etcd.prefix`.
+    pub etcd_prefix: Option<String>,
+    /// Versions kept; older ones are pruned (default: 50).
+    #[serde(default = "AdminHistory::default_max_versions")]
+    #[validate(range(min = 1, max = 1000))]
+    pub max_versions: usize,
+}
+
+impl AdminHistory {
+    fn default_max_versions() -> usize {
+        50
+    }
+
+    /// Canonical snapshot prefix for the configuration stored under `config_prefix`.
+    pub fn prefix(&self, config_prefix: &str) -> String {
+        match &self.etcd_prefix {
+            Some(prefix) => etcd::canonicalize_prefix(prefix),
+            None => etcd::canonicalize_prefix(&format!(
+                "{}_history",
+                config_prefix.trim_end_matches('/')
+            )),
+        }
+    }
+}
+
+/// Where Admin API mutations are recorded besides the in-memory history served by
+/// `GET /apisix/admin/audit`.
+#[derive(Clone, Debug, Serialize, Deserialize, Validate)]
+#[serde(deny_unknown_fields)]
+pub struct AdminAudit {
+    /// Append each entry as a JSON line to this file.
+    pub file: Option<String>,
+    /// Store each entry in etcd under this prefix; it must lie outside `etcd.prefix`.
+    pub etcd_prefix: Option<String>,
+    /// Entries kept in memory for `GET /apisix/admin/audit` (default: 1000).
+    #[serde(default = "AdminAudit::default_max_entries")]
+    #[validate(range(min = 1, max = 100000))]
+    pub max_entries: usize,
+}
+
+impl AdminAudit {
+    fn default_max_entries() -> usize {
+        1000
+    }
 }
 
 impl Admin {
@@ -466,6 +954,10 @@ pub struct Status {
     /// Explicitly allow protected diagnostics on a non-loopback plaintext listener.
     #[serde(default)]
     pub allow_insecure_remote: bool,
+    /// Serve `/metrics` without the diagnostics checks, e.g. for a Prometheus scraper on
+    /// a private network.
+    #[serde(default)]
+    pub public_metrics: bool,
 }
 
 impl Status {
@@ -499,6 +991,9 @@ impl Status {
 #[serde(deny_unknown_fields)]
 pub struct Prometheus {
     pub address: SocketAddr,
+    /// Record the latency and errors of every plugin call, by plugin, phase and route.
+    #[serde(default)]
+    pub plugin_metrics: bool,
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize, Validate)]
@@ -507,42 +1002,462 @@ pub struct Sentry {
     pub dsn: String,
 }
 
+/// Pingora's grace period when `grace_period_seconds` is unset.
+pub const DEFAULT_GRACE_PERIOD_SECS: u64 = 300;
+
+/// Graceful shutdown on `SIGTERM`: readiness fails first, then listeners close, in-flight
+/// requests finish within the grace period and service runtimes stop within the drain timeout.
+#[derive(Clone, Debug, Default, Serialize, Deserialize, Validate)]
+#[serde(deny_unknown_fields)]
+pub struct Shutdown {
+    /// Seconds `/status/ready` fails while listeners keep accepting, so load balancers
+    /// stop routing here first (default: 0).
+    #[serde(default)]
+    pub readiness_delay: u64,
+    /// Seconds in-flight requests get once listeners are closed; overrides
+    /// `pingora.grace_period_seconds`.
+    pub grace_period: Option<u64>,
+    /// Seconds service runtimes get to stop after the grace period; overrides
+    /// `pingora.graceful_shutdown_timeout_seconds`.
+    pub drain_timeout: Option<u64>,
+}
+
+/// Resolver used by DNS-based upstream nodes. Unset fields keep the system
+/// (`/etc/resolv.conf`) behaviour.
+/// Overload protection limits. The load is the larger of the in-flight and event-loop
+/// lag ratios to these limits; routes are shed by `shed_priority` as it grows past 1.
+#[derive(Clone, Debug, Serialize, Deserialize, Validate)]
+#[serde(deny_unknown_fields)]
+#[validate(schema(function = "Overload::validate_limits"))]
+pub struct Overload {
+    /// Proxied requests in flight at which `low` priority routes start being shed.
+    #[validate(range(min = 1))]
+    pub max_in_flight: Option<usize>,
+    /// Event-loop lag in milliseconds at which `low` priority routes start being shed.
+    #[validate(range(min = 1))]
+    pub max_loop_lag_ms: Option<u64>,
+    /// `Retry-After` seconds sent with shed responses.
+    #[serde(default = "Overload::default_retry_after")]
+    pub retry_after: u64,
+}
+
+impl Overload {
+    fn default_retry_after() -> u64 {
+        1
+    }
+
+    fn validate_limits(&self) -> Result<(), ValidationError> {
+        if self.max_in_flight.is_none() && self.max_loop_lag_ms.is_none() {
+            return Err(ValidationError::new("overload_requires_a_limit"));
+        }
+        Ok(())
+    }
+}
+
+/// Maintenance mode: matching requests are answered with a fixed response instead of
+/// being proxied. Set statically in `pingsix.maintenance` or at runtime through the
+/// admin API.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize, Validate)]
+#[serde(deny_unknown_fields)]
+pub struct Maintenance {
+    #[serde(default = "Maintenance::default_enabled")]
+    pub enabled: bool,
+    /// Route ids in maintenance; empty puts the whole gateway in maintenance.
+    #[serde(default)]
+    pub routes: Vec<String>,
+    #[serde(default = "Maintenance::default_status_code")]
+    #[validate(range(min = 400, max = 599))]
+    pub status_code: u16,
+    /// `Retry-After` seconds sent with the response.
+    pub retry_after: Option<u64>,
+    /// Response body; `$route_id`, `$host` and `$retry_after` are expanded.
+    #[serde(default = "Maintenance::default_body")]
+    pub body: String,
+    #[serde(default = "Maintenance::default_content_type")]
+    #[validate(length(min = 1))]
+    pub content_type: String,
+}
+
+impl Maintenance {
+    fn default_enabled() -> bool {
+        true
+    }
+
+    fn default_status_code() -> u16 {
+        503
+    }
+
+    fn default_body() -> String {
+        "Service temporarily unavailable for maintenance".to_string()
+    }
+
+    fn default_content_type() -> String {
+        "text/plain; charset=utf-8".to_string()
+    }
+}
+
+/// Requests slower than `threshold_ms` in total are logged with their per-phase
+/// latency breakdown.
+#[derive(Clone, Debug, Serialize, Deserialize, Validate)]
+#[serde(deny_unknown_fields)]
+pub struct SlowLog {
+    #[validate(range(min = 1))]
+    pub threshold_ms: u64,
+}
+
+/// Periodic check of the expiry of SSL resources and listener certificates.
+#[derive(Clone, Debug, Serialize, Deserialize, Validate)]
+#[serde(deny_unknown_fields)]
+pub struct CertExpiry {
+    /// Certificates expiring within this many days are logged as warnings.
+    #[serde(default = "CertExpiry::default_warn_days")]
+    #[validate(range(min = 1, max = 3650))]
+    pub warn_days: u64,
+    /// Seconds between checks.
+    #[serde(default = "CertExpiry::default_interval")]
+    #[validate(range(min = 10, max = 86400))]
+    pub interval: u64,
+}
+
+impl CertExpiry {
+    fn default_warn_days() -> u64 {
+        30
+    }
+
+    fn default_interval() -> u64 {
+        300
+    }
+}
+
+impl Default for CertExpiry {
+    fn default() -> Self {
+        Self {
+            warn_days: Self::default_warn_days(),
+            interval: Self::default_interval(),
+        }
+    }
+}
+
+/// Every published change of the route table is POSTed to `url` as JSON.
+#[derive(Clone, Debug, Serialize, Deserialize, Validate)]
+#[serde(deny_unknown_fields)]
+pub struct ConfigWebhook {
+    /// `http://` or `https://` URL of the receiver.
+    #[validate(custom(function = "ConfigWebhook::validate_url"))]
+    pub url: String,
+    /// Seconds allowed for delivering one notification.
+    #[serde(default = "ConfigWebhook::default_timeout")]
+    #[validate(range(min = 1, max = 300))]
+    pub timeout: u64,
+    /// Extra request headers, e.g. an authorization token.
+    #[serde(default)]
+    pub headers: HashMap<String, String>,
+}
+
+impl ConfigWebhook {
+    fn default_timeout() -> u64 {
+        5
+    }
+
+    fn validate_url(url: &str) -> Result<(), ValidationError> {
+        match url::Url::parse(url) {
+            Ok(url) if matches!(url.scheme(), "http" | "https") && url.host_str().is_some() => {
+                Ok(())
+            }
+            _ => Err(ValidationError::new("invalid_webhook_url")),
+        }
+    }
+}
+
+/// Client connection settings applied to every listener. Unset fields keep the
+/// Pingora defaults.
+#[derive(Clone, Debug, Default, Serialize, Deserialize, Validate)]
+#[serde(deny_unknown_fields)]
+pub struct Downstream {
+    /// Seconds allowed for the request headers of a reused HTTP/1 connection to arrive.
+    #[validate(range(min = 1))]
+    pub header_timeout: Option<u64>,
+    /// Seconds allowed between two reads of an HTTP/1 request body.
+    #[validate(range(min = 1))]
+    pub body_timeout: Option<u64>,
+    /// Seconds an idle HTTP/1 connection is kept open for the next request; 0 disables
+    /// keepalive.
+    pub keepalive_timeout: Option<u64>,
+    /// Requests served on one HTTP/1 connection before it is closed.
+    #[validate(range(min = 1))]
+    pub keepalive_requests: Option<u32>,
+    /// Concurrent streams a client may open on one HTTP/2 connection.
+    #[validate(range(min = 1))]
+    pub h2_max_concurrent_streams: Option<u32>,
+}
+
+/// Thread and CPU allocation of the Pingora runtimes. Unset fields fall back to
+/// `pingora.threads` and the kernel scheduler.
+#[derive(Clone, Debug, Default, Serialize, Deserialize, Validate)]
+#[serde(deny_unknown_fields)]
+pub struct Runtime {
+    /// Worker threads per service.
+    #[serde(default)]
+    #[validate(nested)]
+    pub threads: ServiceThreads,
+    /// CPUs the gateway threads may run on (Linux only).
+    #[validate(length(min = 1), custom(function = "Runtime::validate_cpu_affinity"))]
+    pub cpu_affinity: Option<Vec<usize>>,
+    /// Threads of the log writer, which performs the file IO of the access/error logs
+    /// and the file-logger plugin. Defaults to 1.
+    #[validate(range(min = 1))]
+    pub log_threads: Option<usize>,
+}
+
+impl Runtime {
+    fn validate_cpu_affinity(cpus: &[usize]) -> Result<(), ValidationError> {
+        // CPU sets are fixed-size bitmaps of 1024 CPUs.
+        if cpus.iter().any(|&cpu| cpu >= 1024) {
+            return Err(ValidationError::new("cpu_affinity_out_of_range"));
+        }
+        Ok(())
+    }
+}
+
+/// Worker threads of each service; unset services use `pingora.threads`.
+#[derive(Clone, Debug, Default, Serialize, Deserialize, Validate)]
+#[serde(deny_unknown_fields)]
+pub struct ServiceThreads {
+    /// The proxy serving `listeners`.
+    #[validate(range(min = 1))]
+    pub proxy: Option<usize>,
+    /// The admin API.
+    #[validate(range(min = 1))]
+    pub admin: Option<usize>,
+    /// The status endpoint.
+    #[validate(range(min = 1))]
+    pub status: Option<usize>,
+    /// The Prometheus endpoint.
+    #[validate(range(min = 1))]
+    pub prometheus: Option<usize>,
+}
+
+/// Header hygiene applied to every request and response.
+#[derive(Clone, Debug, Serialize, Deserialize, Validate)]
+#[serde(deny_unknown_fields)]
+pub struct Security {
+    /// Remove hop-by-hop headers before requests and responses are forwarded.
+    #[serde(default = "Security::default_strip_hop_by_hop")]
+    pub strip_hop_by_hop: bool,
+    /// Remove `Server` and `X-Powered-By` from upstream responses.
+    #[serde(default)]
+    pub hide_server_headers: bool,
+    /// Request headers only trusted clients may send; a trailing `*` matches a prefix.
+    #[serde(default)]
+    #[validate(custom(function = "Security::validate_internal_headers"))]
+    pub internal_headers: Vec<String>,
+    /// Client networks whose internal headers are kept.
+    #[serde(default)]
+    #[validate(custom(function = "Security::validate_trusted_addresses"))]
+    pub trusted_addresses: Vec<String>,
+}
+
+impl Default for Security {
+    fn default() -> Self {
+        Self {
+            strip_hop_by_hop: Self::default_strip_hop_by_hop(),
+            hide_server_headers: false,
+            internal_headers: Vec::new(),
+            trusted_addresses: Vec::new(),
+        }
+    }
+}
+
+impl Security {
+    fn default_strip_hop_by_hop() -> bool {
+        true
+    }
+
+    fn validate_internal_headers(headers: &[String]) -> Result<(), ValidationError> {
+        for name in headers {
+            let name = name.strip_suffix('*').unwrap_or(name);
+            if name.is_empty() || http::HeaderName::from_bytes(name.as_bytes()).is_err() {
+                return Err(ValidationError::new("invalid_internal_header"));
+            }
+        }
+        Ok(())
+    }
+
+    fn validate_trusted_addresses(addresses: &[String]) -> Result<(), ValidationError> {
+        crate::utils::ip_set::parse_ip_set("trusted", addresses)
+            .map(|_| ())
+            .map_err(|_| ValidationError::new("invalid_trusted_address"))
+    }
+}
+
+/// How early a route's requests are shed under overload.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ShedPriority {
+    /// Shed once the load reaches the configured limits.
+    Low,
+    /// Shed at 125% of the limits.
+    #[default]
+    Normal,
+    /// Shed at 150% of the limits.
+    High,
+    /// Never shed.
+    Critical,
+}
+
+impl ShedPriority {
+    fn is_normal(&self) -> bool {
+        *self == ShedPriority::Normal
+    }
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize, Validate)]
+#[serde(deny_unknown_fields)]
+#[validate(schema(function = "Dns::validate_ttl_range"))]
+pub struct Dns {
+    /// Nameservers as `ip` or `ip:port` (port 53 by default), replacing the system ones.
+    #[serde(default)]
+    #[validate(custom(function = "Dns::validate_nameservers"))]
+    pub nameservers: Vec<String>,
+    /// Per-query timeout in seconds.
+    #[validate(range(min = 1, max = 60))]
+    pub timeout: Option<u64>,
+    /// Minimum seconds an answer is cached, raising shorter record TTLs.
+    pub min_ttl: Option<u64>,
+    /// Maximum seconds an answer is cached, capping longer record TTLs.
+    pub max_ttl: Option<u64>,
+    /// Address families to query and their order.
+    pub ip_preference: Option<DnsIpPreference>,
+    /// Seconds between re-resolutions of published DNS upstream nodes; overrides
+    /// `defaults.dns_refresh_interval`.
+    #[validate(range(min = 1, max = 3600))]
+    pub refresh_interval: Option<u64>,
+    /// Seconds a failed resolution is cached before the name is queried again; 5 by
+    /// default, 0 disables negative caching.
+    #[validate(range(max = 3600))]
+    pub negative_ttl: Option<u64>,
+    /// Seconds an expired answer is still served while it is refreshed in the
+    /// background; 30 by default, 0 disables.
+    #[validate(range(max = 86400))]
+    pub stale_ttl: Option<u64>,
+}
+
+impl Dns {
+    /// Parsed nameserver addresses.
+    pub fn nameserver_addrs(&self) -> Result<Vec<SocketAddr>, String> {
+        self.nameservers
+            .iter()
+            .map(|ns| Self::parse_nameserver(ns))
+            .collect()
+    }
+
+    fn parse_nameserver(nameserver: &str) -> Result<SocketAddr, String> {
+        nameserver
+            .parse::<SocketAddr>()
+            .or_else(|_| {
+                nameserver
+                    .parse::<IpAddr>()
+                    .map(|ip| SocketAddr::new(ip, 53))
+            })
+            .map_err(|_| format!("invalid DNS nameserver '{nameserver}'"))
+    }
+
+    fn validate_nameservers(nameservers: &[String]) -> Result<(), ValidationError> {
+        if nameservers
+            .iter()
+            .any(|ns| Self::parse_nameserver(ns).is_err())
+        {
+            return Err(ValidationError::new("invalid_dns_nameserver"));
+        }
+        Ok(())
+    }
+
+    fn validate_ttl_range(&self) -> Result<(), ValidationError> {
+        match (self.min_ttl, self.max_ttl) {
+            (Some(min), Some(max)) if min > max => {
+                Err(ValidationError::new("dns_min_ttl_exceeds_max_ttl"))
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+/// Which address families DNS discovery queries.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DnsIpPreference {
+    /// `A` records only.
+    Ipv4Only,
+    /// `AAAA` records only.
+    Ipv6Only,
+    /// `A` and `AAAA` in parallel.
+    Ipv4AndIpv6,
+    /// `A`, falling back to `AAAA` (resolver default).
+    Ipv4ThenIpv6,
+    /// `AAAA`, falling back to `A`.
+    Ipv6ThenIpv4,
+}
+
 #[derive(Clone, Debug, Serialize, Deserialize, Validate)]
+#[validate(schema(function = "Log::validate_rotation"))]
 #[serde(deny_unknown_fields)]
 pub struct Log {
     #[validate(length(min = 1), custom(function = "Log::validate_path"))]
     pub path: String,
-    #[serde(default = "Log::default_max_size_bytes")]
+    /// Size that triggers internal rotation (default 100 MiB).
+    #[serde(default)]
     #[validate(range(min = 1))]
-    pub max_size_bytes: u64,
+    pub max_size_bytes: Option<u64>,
     #[serde(default = "Log::default_max_backups")]
     pub max_backups: u32,
     /// Internal size-based rotation is the bounded production default. External
     /// and disabled modes must be selected explicitly.
     #[serde(default)]
     pub rotation: LogRotation,
+    /// Optional time-based trigger for internal rotation, aligned to UTC
+    /// boundaries. Size-based rotation via `max_size_bytes` still applies.
+    #[serde(default)]
+    pub rotate: Option<LogRotatePeriod>,
+}
+
+/// Wall-clock period for internal log rotation.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LogRotatePeriod {
+    Hourly,
+    Daily,
+}
+
+impl LogRotatePeriod {
+    /// Length of one rotation period in seconds.
+    pub fn as_secs(self) -> u64 {
+        match self {
+            LogRotatePeriod::Hourly => 60 * 60,
+            LogRotatePeriod::Daily => 24 * 60 * 60,
+        }
+    }
 }
 
 /// Log file rotation strategy.
 ///
-/// Only `Internal` rotates in-process. `External` and `Disabled` never reopen
-/// the file: the writer keeps its descriptor open, so an external rotator must
-/// use copytruncate semantics (or the process must be restarted) after a
-/// rename-based rotation.
+/// Only `Internal` rotates in-process. With `External` the writer keeps its
+/// descriptor open until `SIGUSR1` asks it to reopen the configured path, so a
+/// rename-based rotator must signal the process (or use copytruncate).
 #[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
 #[serde(rename_all = "lowercase")]
 pub enum LogRotation {
     #[default]
     Internal,
-    /// No in-process rotation; rely on an external rotator (copytruncate).
+    /// No in-process rotation; rely on an external rotator (SIGUSR1 or copytruncate).
     External,
     /// No rotation at all; the file grows unbounded.
     Disabled,
 }
 
 impl Log {
-    fn default_max_size_bytes() -> u64 {
-        100 * 1024 * 1024
+    /// Size that triggers internal rotation.
+    pub fn max_size_bytes(&self) -> u64 {
+        self.max_size_bytes.unwrap_or(100 * 1024 * 1024)
     }
 
     fn default_max_backups() -> u32 {
@@ -555,6 +1470,18 @@ impl Log {
         }
         Ok(())
     }
+
+    /// `rotate` and `max_size_bytes` only drive internal rotation.
+    fn validate_rotation(&self) -> Result<(), ValidationError> {
+        if self.rotation != LogRotation::Internal
+            && (self.rotate.is_some() || self.max_size_bytes.is_some())
+        {
+            return Err(ValidationError::new(
+                "log rotate and max_size_bytes require rotation: internal",
+            ));
+        }
+        Ok(())
+    }
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
@@ -564,12 +1491,41 @@ pub struct Tls {
     pub key_path: String,
 }
 
+/// TLS settings for connecting to an HTTPS upstream.
 #[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize, Validate)]
+#[validate(schema(function = "UpstreamTls::validate_mtls_pair"))]
 pub struct UpstreamTls {
+    /// PEM client certificate chain for mTLS; requires `client_key`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[validate(length(min = 1))]
+    pub client_cert: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
     #[validate(length(min = 1))]
-    pub client_cert: String,
+    pub client_key: Option<String>,
+    /// SNI sent in the handshake instead of the node host, e.g. for IP-based nodes.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[validate(length(min = 1))]
+    pub sni: Option<String>,
+    /// Verify the upstream certificate chain and host name (default `true`).
+    #[serde(default = "UpstreamTls::default_verify")]
+    pub verify: bool,
+    /// Additional name the upstream certificate may be issued for.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
     #[validate(length(min = 1))]
-    pub client_key: String,
+    pub server_name: Option<String>,
+}
+
+impl UpstreamTls {
+    fn default_verify() -> bool {
+        true
+    }
+
+    fn validate_mtls_pair(&self) -> Result<(), ValidationError> {
+        match (&self.client_cert, &self.client_key) {
+            (Some(_), Some(_)) | (None, None) => Ok(()),
+            _ => Err(ValidationError::new("mtls_cert_and_key_required_together")),
+        }
+    }
 }
 
 #[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize, Validate)]
@@ -580,6 +1536,11 @@ pub struct Timeout {
     pub send: u64,
     #[validate(range(min = 1, max = 86400))]
     pub read: u64,
+    /// Deadline in seconds for the whole exchange, from the request's arrival to the
+    /// last response body byte. Only honored on routes.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[validate(range(min = 1, max = 86400))]
+    pub total: Option<u64>,
 }
 
 #[serde_as]
@@ -603,20 +1564,66 @@ pub struct Route {
 
     #[serde(default)]
     pub plugins: HashMap<String, JsonValue>,
+    /// Plugins of this `plugin_configs` entry run on the route unless the route
+    /// configures a plugin of the same name.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub plugin_config_id: Option<String>,
     #[validate(nested)]
     pub upstream: Option<Upstream>,
     pub upstream_id: Option<String>,
     pub service_id: Option<String>,
     #[validate(nested)]
     pub timeout: Option<Timeout>,
+    /// Treat responses as long-lived streams (SSE, chunked LLM output): skip response
+    /// compression, body-buffering plugins and the response cache.
+    #[serde(default)]
+    pub streaming: bool,
+    /// Serve requests no other route matches: for its `hosts` when set, otherwise for
+    /// any host. Fallback routes match by host only and must not set `uri`/`uris`.
+    #[serde(default)]
+    pub fallback: bool,
+    /// Order in which this route's requests are shed when `pingsix.overload` trips.
+    #[serde(default, skip_serializing_if = "ShedPriority::is_normal")]
+    pub shed_priority: ShedPriority,
+    /// Set to `false` to keep the route configured but stop matching it.
+    #[serde(default = "default_enabled", skip_serializing_if = "is_enabled")]
+    pub enabled: bool,
+    /// Free-form `key: value` metadata used to filter Admin API lists and to select
+    /// routes for global rules.
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    #[validate(custom(function = "validate_labels"))]
+    pub labels: HashMap<String, String>,
+    /// Path prefix removed before proxying: `/shop` turns `/shop/items` into `/items`
+    /// and `/shop` into `/`. Requests outside the prefix are proxied unchanged.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub strip_path_prefix: Option<String>,
+    /// API product the route belongs to. Grouped routes share the `api_group` metrics
+    /// label and the `api_group` variable, e.g. as a `limit-count` key.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[validate(length(min = 1, max = 64))]
+    pub api_group: Option<String>,
 }
 
 impl Route {
     fn validate(&self) -> Result<(), ValidationError> {
-        if self.uri.is_none() && self.uris.is_empty() {
+        let has_uri = self.uri.is_some() || !self.uris.is_empty();
+        if self.fallback && has_uri {
+            return Err(ValidationError::new("fallback_route_must_not_set_uri"));
+        }
+        if !self.fallback && !has_uri {
             return Err(ValidationError::new("uri_or_uris_required"));
         }
 
+        if let Some(prefix) = &self.strip_path_prefix {
+            if !prefix.starts_with('/')
+                || prefix.len() < 2
+                || prefix.ends_with('/')
+                || prefix.contains('?')
+            {
+                return Err(ValidationError::new("invalid_strip_path_prefix"));
+            }
+        }
+
         if self.upstream_id.is_none() && self.service_id.is_none() && self.upstream.is_none() {
             return Err(ValidationError::new("upstream_or_service_required"));
         }
@@ -645,8 +1652,42 @@ impl Route {
     }
 }
 
+fn default_enabled() -> bool {
+    true
+}
+
+fn is_enabled(enabled: &bool) -> bool {
+    *enabled
+}
+
+/// Label keys and values must be non-empty and free of whitespace.
+fn validate_labels(labels: &HashMap<String, String>) -> Result<(), ValidationError> {
+    let valid = |s: &str, max: usize| {
+        !s.is_empty() && s.len() <= max && !s.chars().any(char::is_whitespace)
+    };
+    if labels
+        .iter()
+        .all(|(key, value)| valid(key, 64) && valid(value, 256))
+    {
+        Ok(())
+    } else {
+        Err(ValidationError::new("invalid_label"))
+    }
+}
+
+/// Whether `labels` carries every `key: value` pair of `selector`.
+pub fn labels_match(labels: &HashMap<String, String>, selector: &HashMap<String, String>) -> bool {
+    selector
+        .iter()
+        .all(|(key, value)| labels.get(key) == Some(value))
+}
+
 #[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize, Validate)]
 #[validate(schema(function = "Upstream::validate_upstream_host"))]
+#[validate(schema(function = "Upstream::validate_zone_nodes"))]
+#[validate(schema(function = "Upstream::validate_discovery_nodes"))]
+#[validate(schema(function = "Upstream::validate_priority_nodes"))]
+#[validate(schema(function = "Upstream::validate_timeout"))]
 pub struct Upstream {
     #[serde(default)]
     pub id: String,
@@ -671,6 +1712,22 @@ pub struct Upstream {
     pub upstream_host: Option<String>,
     #[validate(nested)]
     pub tls: Option<UpstreamTls>,
+    #[validate(nested)]
+    pub zone_aware: Option<UpstreamZoneAware>,
+    /// How node keys are resolved. Unset resolves domain names through `A`/`AAAA`.
+    pub discovery_type: Option<UpstreamDiscoveryType>,
+    /// Failover priority per node key (default `0`). Only the highest priority with a
+    /// healthy node receives traffic.
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub priorities: HashMap<String, i32>,
+    /// Session affinity; unset selects every request independently.
+    #[validate(nested)]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sticky: Option<UpstreamSticky>,
+    /// Free-form `key: value` metadata used to filter Admin API lists.
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    #[validate(custom(function = "validate_labels"))]
+    pub labels: HashMap<String, String>,
 }
 
 impl Upstream {
@@ -689,6 +1746,62 @@ impl Upstream {
         }
     }
 
+    fn validate_zone_nodes(&self) -> Result<(), ValidationError> {
+        let Some(zone_aware) = &self.zone_aware else {
+            return Ok(());
+        };
+        for node in zone_aware.zones.keys() {
+            if !self.nodes.contains_key(node) {
+                let mut err = ValidationError::new("zone_for_unknown_node");
+                err.add_param("key".into(), node);
+                return Err(err);
+            }
+        }
+        Ok(())
+    }
+
+    /// A deadline covers the whole exchange, which only a route sees.
+    fn validate_timeout(&self) -> Result<(), ValidationError> {
+        if self.timeout.as_ref().is_some_and(|t| t.total.is_some()) {
+            return Err(ValidationError::new("upstream_timeout_total_not_supported"));
+        }
+        Ok(())
+    }
+
+    fn validate_priority_nodes(&self) -> Result<(), ValidationError> {
+        if let Some(node) = self
+            .priorities
+            .keys()
+            .find(|node| !self.nodes.contains_key(*node))
+        {
+            let mut err = ValidationError::new("priority_for_unknown_node");
+            err.add_param("key".into(), node);
+            return Err(err);
+        }
+        Ok(())
+    }
+
+    /// SRV names (`_service._proto.name`) only make sense for `dns_srv`, where node keys
+    /// are names without a port: the port comes from the SRV records.
+    fn validate_discovery_nodes(&self) -> Result<(), ValidationError> {
+        let srv = self.discovery_type == Some(UpstreamDiscoveryType::DnsSrv);
+        for key in self.nodes.keys() {
+            let valid = if srv {
+                Self::extract_port(key).is_none()
+                    && !key.starts_with('[')
+                    && key.parse::<IpAddr>().is_err()
+            } else {
+                !key.contains('_')
+            };
+            if !valid {
+                let mut err = ValidationError::new("invalid_node_for_discovery_type");
+                err.add_param("key".into(), key);
+                return Err(err);
+            }
+        }
+        Ok(())
+    }
+
     // Custom validation function for `nodes` keys
     fn validate_nodes_keys(nodes: &HashMap<String, u32>) -> Result<(), ValidationError> {
         for (key, weight) in nodes {
@@ -739,6 +1852,71 @@ impl Upstream {
     }
 }
 
+/// Zone-aware selection: nodes in the gateway's own zone are preferred and traffic
+/// spills over to other zones only when local health degrades.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize, Validate)]
+pub struct UpstreamZoneAware {
+    /// Zone label per node, keyed by the same address strings as `nodes`.
+    #[serde(default)]
+    pub zones: HashMap<String, String>,
+    /// Spill over once fewer than this percentage of local nodes are healthy.
+    /// `0` (default) spills over only when no local node is healthy.
+    #[serde(default)]
+    #[validate(range(max = 100))]
+    pub min_healthy_percent: u8,
+}
+
+/// Session affinity: a cookie pins a client to the backend that served it for as long
+/// as that backend stays healthy.  A pinned request skips node priorities and zone preference.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize, Validate)]
+pub struct UpstreamSticky {
+    #[serde(default)]
+    pub r#type: StickyType,
+    /// Name of the affinity cookie.
+    #[serde(default = "UpstreamSticky::default_cookie")]
+    #[validate(custom(function = "UpstreamSticky::validate_cookie"))]
+    pub cookie: String,
+    /// Cookie lifetime in seconds; `0` (default) issues a session cookie.
+    #[serde(default)]
+    pub ttl: u64,
+}
+
+impl UpstreamSticky {
+    fn default_cookie() -> String {
+        "pingsix_affinity".to_string()
+    }
+
+    fn validate_cookie(cookie: &str) -> Result<(), ValidationError> {
+        let valid = !cookie.is_empty()
+            && cookie
+                .bytes()
+                .all(|b| b.is_ascii_alphanumeric() || b"!#$%&'*+-.^_`|~".contains(&b));
+        if valid {
+            Ok(())
+        } else {
+            Err(ValidationError::new("invalid_cookie_name"))
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum StickyType {
+    #[default]
+    Cookie,
+}
+
+/// Node resolution strategy of an upstream.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum UpstreamDiscoveryType {
+    /// Domain names resolve through `A`/`AAAA` records (the default).
+    Dns,
+    /// Node keys are SRV names; each record's target, port and weight become a backend
+    /// and the configured node weight is ignored.
+    DnsSrv,
+}
+
 #[derive(Clone, Default, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
 #[serde(rename_all = "lowercase")]
 pub enum SelectionType {
@@ -747,6 +1925,11 @@ pub enum SelectionType {
     Random,
     Fnv,
     Ketama,
+    /// Fewest in-flight requests relative to node weight.
+    #[serde(rename = "least_conn")]
+    LeastConn,
+    /// Lowest peak-EWMA response time, scaled by in-flight requests and node weight.
+    Latency,
 }
 
 #[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize, Validate)]
@@ -772,6 +1955,9 @@ pub struct ActiveCheck {
     pub https_verify_certificate: bool,
     #[serde(default)]
     pub req_headers: Vec<String>,
+    /// Service asked about by `grpc` checks; empty asks about the whole server.
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub grpc_service: String,
     #[validate(nested)]
     pub healthy: Option<Health>,
     #[validate(nested)]
@@ -786,6 +1972,10 @@ pub enum ActiveCheckType {
     #[default]
     HTTP,
     HTTPS,
+    /// `grpc.health.v1.Health/Check` over h2c.
+    GRPC,
+    /// `grpc.health.v1.Health/Check` over TLS.
+    GRPCS,
 }
 
 impl ActiveCheck {
@@ -865,60 +2055,333 @@ pub enum UpstreamScheme {
     #[default]
     HTTP,
     HTTPS,
+    /// HTTP/2 over cleartext with prior knowledge (h2c).
+    HTTP2,
+    /// HTTP/2 over TLS, negotiated by ALPN with a fallback to HTTP/1.1.
+    HTTPS2,
     GRPC,
     GRPCS,
 }
 
-#[derive(Clone, Default, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
-#[serde(rename_all = "lowercase")]
-#[allow(clippy::upper_case_acronyms)]
-pub enum UpstreamPassHost {
-    #[default]
-    PASS,
-    REWRITE,
-    NODE,
+impl UpstreamScheme {
+    /// Whether backends of this scheme are reached over TLS.
+    pub fn is_tls(self) -> bool {
+        matches!(self, Self::HTTPS | Self::HTTPS2 | Self::GRPCS)
+    }
+}
+
+#[derive(Clone, Default, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+#[allow(clippy::upper_case_acronyms)]
+pub enum UpstreamPassHost {
+    #[default]
+    PASS,
+    REWRITE,
+    NODE,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize, Validate)]
+#[validate(schema(function = "Service::validate_upstream"))]
+#[validate(schema(function = "Service::validate_virtual_host"))]
+pub struct Service {
+    #[serde(default)]
+    pub id: String,
+    #[serde(default)]
+    pub plugins: HashMap<String, JsonValue>,
+    pub upstream: Option<Upstream>,
+    pub upstream_id: Option<String>,
+    #[serde(default)]
+    pub hosts: Vec<String>,
+    /// Proxy every request for `hosts` that no route matches through the service,
+    /// like a fallback route bound to it.
+    #[serde(default)]
+    pub virtual_host: bool,
+    /// Set to `false` to stop matching every route bound to the service.
+    #[serde(default = "default_enabled", skip_serializing_if = "is_enabled")]
+    pub enabled: bool,
+    /// Free-form `key: value` metadata used to filter Admin API lists.
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    #[validate(custom(function = "validate_labels"))]
+    pub labels: HashMap<String, String>,
+    /// Switch the service between two upstreams instead of using `upstream`/`upstream_id`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[validate(nested)]
+    pub blue_green: Option<BlueGreen>,
+}
+
+impl Default for Service {
+    fn default() -> Self {
+        Self {
+            id: String::new(),
+            plugins: HashMap::new(),
+            upstream: None,
+            upstream_id: None,
+            hosts: Vec::new(),
+            virtual_host: false,
+            enabled: true,
+            labels: HashMap::new(),
+            blue_green: None,
+        }
+    }
+}
+
+impl Service {
+    fn validate_upstream(&self) -> Result<(), ValidationError> {
+        let has_upstream = self.upstream_id.is_some() || self.upstream.is_some();
+        match (&self.blue_green, has_upstream) {
+            (Some(_), true) => Err(ValidationError::new("blue_green_with_upstream")),
+            (None, false) => Err(ValidationError::new("upstream_required")),
+            _ => Ok(()),
+        }
+    }
+
+    /// Every upstream id the service references, including both blue/green sides.
+    pub fn upstream_ids(&self) -> impl Iterator<Item = &str> {
+        self.upstream_id.as_deref().into_iter().chain(
+            self.blue_green
+                .iter()
+                .flat_map(|bg| [bg.blue.as_str(), bg.green.as_str()]),
+        )
+    }
+
+    fn validate_virtual_host(&self) -> Result<(), ValidationError> {
+        if self.virtual_host && self.hosts.is_empty() {
+            return Err(ValidationError::new("virtual_host_requires_hosts"));
+        }
+        Ok(())
+    }
+}
+
+/// One side of a [`BlueGreen`] switch.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum BlueGreenColor {
+    #[default]
+    Blue,
+    Green,
+}
+
+impl BlueGreenColor {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Blue => "blue",
+            Self::Green => "green",
+        }
+    }
+
+    pub fn other(self) -> Self {
+        match self {
+            Self::Blue => Self::Green,
+            Self::Green => Self::Blue,
+        }
+    }
+
+    /// Parses a header or cookie value, ignoring case and surrounding whitespace.
+    pub fn parse(value: &str) -> Option<Self> {
+        let value = value.trim();
+        if value.eq_ignore_ascii_case("blue") {
+            Some(Self::Blue)
+        } else if value.eq_ignore_ascii_case("green") {
+            Some(Self::Green)
+        } else {
+            None
+        }
+    }
+}
+
+/// Blue/green switch of a service between two upstreams.
+///
+/// Requests go to `active`, except those naming a side through `header` or
+/// `cookie` and `weight` percent of the rest, which go to the other side.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize, Validate)]
+#[serde(deny_unknown_fields)]
+#[validate(schema(function = "BlueGreen::validate_sides"))]
+pub struct BlueGreen {
+    /// Upstream id of the blue side.
+    #[validate(length(min = 1))]
+    pub blue: String,
+    /// Upstream id of the green side.
+    #[validate(length(min = 1))]
+    pub green: String,
+    #[serde(default)]
+    pub active: BlueGreenColor,
+    /// Percentage of the remaining requests sent to the inactive side.
+    #[serde(default)]
+    #[validate(range(max = 100))]
+    pub weight: u32,
+    /// Request header whose value (`blue` or `green`) picks the side.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[validate(length(min = 1))]
+    pub header: Option<String>,
+    /// Cookie whose value (`blue` or `green`) picks the side.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[validate(length(min = 1))]
+    pub cookie: Option<String>,
+}
+
+impl BlueGreen {
+    /// Upstream id of the given side.
+    pub fn upstream_id(&self, color: BlueGreenColor) -> &str {
+        match color {
+            BlueGreenColor::Blue => &self.blue,
+            BlueGreenColor::Green => &self.green,
+        }
+    }
+
+    fn validate_sides(&self) -> Result<(), ValidationError> {
+        if self.blue == self.green {
+            return Err(ValidationError::new("blue_green_same_upstream"));
+        }
+        Ok(())
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize, Validate)]
+#[validate(schema(function = "GlobalRule::validate_scope"))]
+pub struct GlobalRule {
+    #[serde(default)]
+    pub id: String,
+    #[serde(default)]
+    pub plugins: HashMap<String, JsonValue>,
+    /// Set to `false` to keep the rule configured but stop running its plugins.
+    #[serde(default = "default_enabled", skip_serializing_if = "is_enabled")]
+    pub enabled: bool,
+    /// Free-form `key: value` metadata used to filter Admin API lists.
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    #[validate(custom(function = "validate_labels"))]
+    pub labels: HashMap<String, String>,
+    /// Run the rule's plugins only for requests to one of these hosts; `*.example.com`
+    /// matches its subdomains.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub hosts: Vec<String>,
+    /// Run the rule's plugins only for request paths starting with one of these.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub uri_prefixes: Vec<String>,
+    /// Run the rule's plugins only for matched routes carrying all of these labels.
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub route_labels: HashMap<String, String>,
+}
+
+impl GlobalRule {
+    /// Whether the rule is limited by `hosts`, `uri_prefixes` or `route_labels`; a
+    /// rule without them applies to every request.
+    pub fn is_scoped(&self) -> bool {
+        !self.hosts.is_empty() || !self.uri_prefixes.is_empty() || !self.route_labels.is_empty()
+    }
+
+    fn validate_scope(&self) -> Result<(), ValidationError> {
+        if self.hosts.iter().any(|host| host.is_empty() || host == "*") {
+            return Err(ValidationError::new("invalid_global_rule_host"));
+        }
+        if self
+            .uri_prefixes
+            .iter()
+            .any(|prefix| !prefix.starts_with('/'))
+        {
+            return Err(ValidationError::new("uri_prefix_must_start_with_slash"));
+        }
+        Ok(())
+    }
+}
+
+impl Default for GlobalRule {
+    fn default() -> Self {
+        Self {
+            id: String::new(),
+            plugins: HashMap::new(),
+            enabled: true,
+            labels: HashMap::new(),
+            hosts: Vec::new(),
+            uri_prefixes: Vec::new(),
+            route_labels: HashMap::new(),
+        }
+    }
 }
 
 #[derive(Clone, Default, Debug, PartialEq, Eq, Serialize, Deserialize, Validate)]
-#[validate(schema(function = "Service::validate_upstream"))]
-pub struct Service {
+#[allow(clippy::upper_case_acronyms)]
+#[validate(schema(function = "SSL::validate_material"))]
+pub struct SSL {
     #[serde(default)]
     pub id: String,
+    /// PEM certificate chain, leaf first.
     #[serde(default)]
-    pub plugins: HashMap<String, JsonValue>,
-    pub upstream: Option<Upstream>,
-    pub upstream_id: Option<String>,
+    pub cert: String,
+    /// PEM private key, encrypted when `passphrase` is set.
     #[serde(default)]
-    pub hosts: Vec<String>,
+    pub key: String,
+    /// Base64 PKCS#12 bundle with the certificate, its chain and the key, instead of
+    /// `cert` and `key`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pkcs12: Option<String>,
+    /// Passphrase of `key` or `pkcs12`; `$env://NAME` reads it from the environment.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub passphrase: Option<String>,
+    #[validate(length(min = 1))]
+    pub snis: Vec<String>,
+    /// Free-form `key: value` metadata used to filter Admin API lists.
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    #[validate(custom(function = "validate_labels"))]
+    pub labels: HashMap<String, String>,
 }
 
-impl Service {
-    fn validate_upstream(&self) -> Result<(), ValidationError> {
-        if self.upstream_id.is_none() && self.upstream.is_none() {
-            Err(ValidationError::new("upstream_required"))
-        } else {
-            Ok(())
+impl SSL {
+    /// Certificate and key come either from `cert`/`key` or from `pkcs12`.
+    fn validate_material(&self) -> Result<(), ValidationError> {
+        let pem = !self.cert.is_empty() || !self.key.is_empty();
+        match (&self.pkcs12, pem) {
+            (Some(_), true) => Err(ValidationError::new("pkcs12_excludes_cert_and_key")),
+            (Some(bundle), false) if bundle.is_empty() => {
+                Err(ValidationError::new("pkcs12_must_not_be_empty"))
+            }
+            (None, _) if self.cert.is_empty() || self.key.is_empty() => {
+                Err(ValidationError::new("cert_and_key_or_pkcs12_required"))
+            }
+            _ => Ok(()),
         }
     }
 }
 
+/// A named set of networks shared by `ip-restriction` instances through
+/// `whitelist_lists`/`blacklist_lists`.
 #[derive(Clone, Default, Debug, PartialEq, Eq, Serialize, Deserialize, Validate)]
-pub struct GlobalRule {
+pub struct IpList {
     #[serde(default)]
     pub id: String,
     #[serde(default)]
-    pub plugins: HashMap<String, JsonValue>,
+    pub desc: Option<String>,
+    /// Networks in CIDR notation or single addresses, IPv4 or IPv6.
+    #[serde(default)]
+    #[validate(custom(function = "IpList::validate_cidrs"))]
+    pub cidrs: Vec<String>,
+}
+
+impl IpList {
+    fn validate_cidrs(cidrs: &[String]) -> Result<(), ValidationError> {
+        if cidrs
+            .iter()
+            .all(|cidr| cidr.trim().parse::<ipnetwork::IpNetwork>().is_ok())
+        {
+            Ok(())
+        } else {
+            Err(ValidationError::new("invalid_cidr"))
+        }
+    }
 }
 
+/// A named bundle of plugins shared by routes through `plugin_config_id`.
 #[derive(Clone, Default, Debug, PartialEq, Eq, Serialize, Deserialize, Validate)]
-#[allow(clippy::upper_case_acronyms)]
-pub struct SSL {
+pub struct PluginConfig {
     #[serde(default)]
     pub id: String,
-    pub cert: String,
-    pub key: String,
-    #[validate(length(min = 1))]
-    pub snis: Vec<String>,
+    #[serde(default)]
+    pub desc: Option<String>,
+    #[serde(default)]
+    pub plugins: HashMap<String, JsonValue>,
+    /// Free-form `key: value` metadata used to filter Admin API lists.
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    #[validate(custom(function = "validate_labels"))]
+    pub labels: HashMap<String, String>,
 }
 
 #[cfg(test)]
@@ -930,6 +2393,21 @@ mod tests {
         let _ = env_logger::builder().is_test(true).try_init();
     }
 
+    #[test]
+    fn test_ssl_takes_pem_pair_or_pkcs12() {
+        let ssl = |value: JsonValue| serde_json::from_value::<SSL>(value).unwrap().validate();
+        assert!(ssl(serde_json::json!({"cert": "c", "key": "k", "snis": ["a.com"]})).is_ok());
+        assert!(
+            ssl(serde_json::json!({"pkcs12": "MIIK", "passphrase": "p", "snis": ["a.com"]}))
+                .is_ok()
+        );
+        assert!(ssl(serde_json::json!({"cert": "c", "snis": ["a.com"]})).is_err());
+        assert!(ssl(
+            serde_json::json!({"cert": "c", "key": "k", "pkcs12": "MIIK", "snis": ["a.com"]})
+        )
+        .is_err());
+    }
+
     #[test]
     fn test_print_default_yaml() {
         init_log();
@@ -1053,6 +2531,53 @@ services:
         print!("{}", conf.to_yaml());
     }
 
+    #[test]
+    fn tolerant_policy_skips_invalid_resources_and_their_dependents() {
+        init_log();
+        let conf_str = r#"
+---
+pingsix:
+  static_load_policy: tolerant
+  listeners:
+    - address: 0.0.0.0:8080
+
+routes:
+  - id: "ok"
+    uri: /ok
+    upstream_id: "1"
+  - id: "orphan"
+    uri: /orphan
+    service_id: "broken"
+
+upstreams:
+  - id: "1"
+    nodes:
+      "127.0.0.1:1980": 1
+
+services:
+  - id: "broken"
+    hosts: ["example.com"]
+        "#;
+        let conf = Config::from_yaml(conf_str).unwrap();
+        assert_eq!(
+            conf.routes
+                .iter()
+                .map(|r| r.id.as_str())
+                .collect::<Vec<_>>(),
+            vec!["ok"]
+        );
+        assert!(conf.services.is_empty());
+        let skipped: Vec<(&str, &str)> = conf
+            .skipped_resources
+            .iter()
+            .map(|s| (s.resource_type.as_str(), s.id.as_str()))
+            .collect();
+        assert_eq!(skipped, vec![("services", "broken"), ("routes", "orphan")]);
+
+        let strict = conf_str.replace("static_load_policy: tolerant", "static_load_policy: strict");
+        assert!(Config::from_yaml(&strict).is_err());
+    }
+
     #[test]
     fn test_valid_listeners_length() {
         init_log();
@@ -1193,6 +2718,235 @@ upstreams:
         }
     }
 
+    #[test]
+    fn test_dangling_references_are_rejected() {
+        init_log();
+        let conf_str = r#"
+---
+pingsix:
+  listeners:
+    - address: "[::1]:8080"
+
+routes:
+  - id: "1"
+    uri: /
+    service_id: "missing-svc"
+  - id: "2"
+    uri: /two
+    upstream_id: "1"
+
+upstreams:
+  - id: "1"
+    nodes:
+      "127.0.0.1:1980": 1
+
+services:
+  - id: "s1"
+    upstream_id: "missing-up"
+        "#;
+        let err = Config::from_yaml(conf_str).unwrap_err().to_string();
+        assert!(err.contains("route '1' references missing service 'missing-svc'"));
+        assert!(err.contains("service 's1' references missing upstream 'missing-up'"));
+        assert!(!err.contains("route '2'"));
+    }
+
+    #[test]
+    fn test_shutdown_overrides_pingora_timeouts() {
+        init_log();
+        let conf_str = r#"
+---
+pingora:
+  version: 1
+  grace_period_seconds: 60
+
+pingsix:
+  listeners:
+    - address: "[::1]:8080"
+  shutdown:
+    readiness_delay: 5
+    grace_period: 30
+    drain_timeout: 10
+        "#;
+        let conf = Config::from_yaml(conf_str).unwrap();
+        assert_eq!(conf.pingsix.shutdown.unwrap().readiness_delay, 5);
+        assert_eq!(conf.pingora.grace_period_seconds, Some(30));
+        assert_eq!(conf.pingora.graceful_shutdown_timeout_seconds, Some(10));
+    }
+
+    #[test]
+    fn test_dns_settings() {
+        init_log();
+        let conf_str = r#"
+---
+pingsix:
+  listeners:
+    - address: "[::1]:8080"
+  dns:
+    nameservers: ["10.0.0.2", "[fd00::53]:5353"]
+    timeout: 2
+    min_ttl: 5
+    max_ttl: 30
+    ip_preference: ipv4_only
+    refresh_interval: 10
+        "#;
+        let dns = Config::from_yaml(conf_str).unwrap().pingsix.dns.unwrap();
+        assert_eq!(
+            dns.nameserver_addrs().unwrap(),
+            vec![
+                "10.0.0.2:53".parse::<SocketAddr>().unwrap(),
+                "[fd00::53]:5353".parse().unwrap()
+            ]
+        );
+        assert_eq!(dns.ip_preference, Some(DnsIpPreference::Ipv4Only));
+
+        let invalid_nameserver = conf_str.replace("\"10.0.0.2\"", "\"ns.example.com\"");
+        assert!(Config::from_yaml(&invalid_nameserver).is_err());
+        let inverted_ttl = conf_str.replace("max_ttl: 30", "max_ttl: 1");
+        assert!(Config::from_yaml(&inverted_ttl).is_err());
+    }
+
+    #[test]
+    fn test_dns_srv_upstream_nodes() {
+        init_log();
+        let conf_str = r#"
+---
+pingsix:
+  listeners:
+    - address: "[::1]:8080"
+
+upstreams:
+  - id: "1"
+    discovery_type: dns_srv
+    nodes:
+      "_http._tcp.web.service.consul": 1
+        "#;
+        let conf = Config::from_yaml(conf_str).unwrap();
+        assert_eq!(
+            conf.upstreams[0].discovery_type,
+            Some(UpstreamDiscoveryType::DnsSrv)
+        );
+
+        let with_port = conf_str.replace("web.service.consul", "web.service.consul:80");
+        assert!(Config::from_yaml(&with_port).is_err());
+        let without_srv = conf_str.replace("discovery_type: dns_srv", "discovery_type: dns");
+        assert!(Config::from_yaml(&without_srv).is_err());
+    }
+
+    #[test]
+    fn test_upstream_priorities_reference_nodes() {
+        init_log();
+        let conf_str = r#"
+---
+pingsix:
+  listeners:
+    - address: "[::1]:8080"
+
+upstreams:
+  - id: "1"
+    nodes:
+      "10.0.0.1:80": 1
+      "10.1.0.1:80": 1
+    priorities:
+      "10.0.0.1:80": 10
+        "#;
+        let conf = Config::from_yaml(conf_str).unwrap();
+        assert_eq!(conf.upstreams[0].priorities.get("10.0.0.1:80"), Some(&10));
+
+        let unknown = conf_str.replace("\"10.0.0.1:80\": 10", "\"10.2.0.1:80\": 10");
+        assert!(Config::from_yaml(&unknown).is_err());
+    }
+
+    #[test]
+    fn test_total_timeout_only_on_routes() {
+        init_log();
+        let conf_str = r#"
+---
+pingsix:
+  listeners:
+    - address: "[::1]:8080"
+
+routes:
+  - id: "1"
+    uri: /
+    upstream_id: "1"
+    timeout: {connect: 5, send: 10, read: 30, total: 60}
+
+upstreams:
+  - id: "1"
+    nodes:
+      "127.0.0.1:1980": 1
+        "#;
+        let conf = Config::from_yaml(conf_str).unwrap();
+        assert_eq!(conf.routes[0].timeout.as_ref().unwrap().total, Some(60));
+
+        let on_upstream = conf_str.replace(
+            "      \"127.0.0.1:1980\": 1",
+            "      \"127.0.0.1:1980\": 1\n    timeout: {connect: 5, send: 10, read: 30, total: 60}",
+        );
+        assert!(Config::from_yaml(&on_upstream).is_err());
+    }
+
+    #[test]
+    fn test_fallback_route_matches_by_host_only() {
+        init_log();
+        let conf_str = r#"
+---
+pingsix:
+  listeners:
+    - address: "[::1]:8080"
+
+routes:
+  - id: "fallback"
+    fallback: true
+    hosts: ["api.example.com:8443"]
+    upstream_id: "1"
+
+upstreams:
+  - id: "1"
+    nodes:
+      "127.0.0.1:1980": 1
+        "#;
+        let conf = Config::from_yaml(conf_str).unwrap();
+        assert!(conf.routes[0].fallback);
+
+        let with_uri = conf_str.replace("fallback: true", "fallback: true\n    uri: /");
+        assert!(Config::from_yaml(&with_uri).is_err());
+    }
+
+    #[test]
+    fn test_strip_path_prefix_must_be_a_path() {
+        init_log();
+        let conf_str = r#"
+---
+pingsix:
+  listeners:
+    - address: "[::1]:8080"
+
+routes:
+  - id: "orders"
+    uri: /shop/orders/*
+    strip_path_prefix: /shop
+    api_group: shop
+    upstream_id: "1"
+
+upstreams:
+  - id: "1"
+    nodes:
+      "127.0.0.1:1980": 1
+        "#;
+        let conf = Config::from_yaml(conf_str).unwrap();
+        assert_eq!(conf.routes[0].strip_path_prefix.as_deref(), Some("/shop"));
+        assert_eq!(conf.routes[0].api_group.as_deref(), Some("shop"));
+
+        for prefix in ["shop", "/", "/shop/", "/shop?x=1"] {
+            let invalid = conf_str.replace(
+                "strip_path_prefix: /shop",
+                &format!("strip_path_prefix: \"{prefix}\""),
+            );
+            assert!(Config::from_yaml(&invalid).is_err(), "{prefix}");
+        }
+    }
+
     #[test]
     fn test_valid_route_upstream() {
         init_log();
@@ -1249,6 +3003,35 @@ services:
         }
     }
 
+    #[test]
+    fn test_service_blue_green_excludes_upstream() {
+        let service = |extra: JsonValue| {
+            let mut value = serde_json::json!({
+                "id": "s1",
+                "blue_green": {"blue": "u1", "green": "u2", "weight": 10}
+            });
+            value
+                .as_object_mut()
+                .unwrap()
+                .extend(extra.as_object().unwrap().clone());
+            serde_json::from_value::<Service>(value).unwrap()
+        };
+        assert!(service(serde_json::json!({})).validate().is_ok());
+        assert!(service(serde_json::json!({"upstream_id": "u1"}))
+            .validate()
+            .is_err());
+        assert!(
+            service(serde_json::json!({"blue_green": {"blue": "u1", "green": "u1"}}))
+                .validate()
+                .is_err()
+        );
+        assert!(service(
+            serde_json::json!({"blue_green": {"blue": "u1", "green": "u2", "weight": 101}})
+        )
+        .validate()
+        .is_err());
+    }
+
     #[test]
     fn test_admin_api_key_must_not_be_empty() {
         init_log();
@@ -1418,15 +3201,51 @@ upstreams:
         let route_upstream = conf.routes[0].upstream.as_ref().unwrap();
         assert!(route_upstream.tls.is_some());
         let route_tls = route_upstream.tls.as_ref().unwrap();
-        assert!(route_tls.client_cert.contains("BEGIN CERTIFICATE"));
-        assert!(route_tls.client_key.contains("BEGIN EC PRIVATE KEY"));
+        assert!(route_tls
+            .client_cert
+            .as_deref()
+            .unwrap()
+            .contains("BEGIN CERTIFICATE"));
+        assert!(route_tls
+            .client_key
+            .as_deref()
+            .unwrap()
+            .contains("BEGIN EC PRIVATE KEY"));
+        assert!(route_tls.verify);
 
         // Check upstream TLS config
         let upstream = &conf.upstreams[0];
         assert!(upstream.tls.is_some());
         let upstream_tls = upstream.tls.as_ref().unwrap();
-        assert!(upstream_tls.client_cert.contains("BEGIN CERTIFICATE"));
-        assert!(upstream_tls.client_key.contains("BEGIN EC PRIVATE KEY"));
+        assert!(upstream_tls
+            .client_cert
+            .as_deref()
+            .unwrap()
+            .contains("BEGIN CERTIFICATE"));
+        assert!(upstream_tls
+            .client_key
+            .as_deref()
+            .unwrap()
+            .contains("BEGIN EC PRIVATE KEY"));
+    }
+
+    #[test]
+    fn upstream_tls_overrides_without_client_cert() {
+        let tls: UpstreamTls = serde_yml::from_str(
+            r#"
+sni: api.example.com
+verify: false
+server_name: internal.example.com
+"#,
+        )
+        .unwrap();
+        assert!(tls.validate().is_ok());
+        assert_eq!(tls.sni.as_deref(), Some("api.example.com"));
+        assert!(!tls.verify);
+        assert!(tls.client_cert.is_none());
+
+        let tls: UpstreamTls = serde_yml::from_str("client_cert: cert").unwrap();
+        assert!(tls.validate().is_err());
     }
 
     #[test]
@@ -1435,6 +3254,8 @@ upstreams:
             address: "0.0.0.0:9181".parse().unwrap(),
             api_key: "secret".into(),
             allow_insecure_remote: false,
+            audit: None,
+            history: None,
         };
         assert!(admin.validate_bind_safety().is_err());
     }
@@ -1445,6 +3266,8 @@ upstreams:
             address: "127.0.0.1:9181".parse().unwrap(),
             api_key: "secret".into(),
             allow_insecure_remote: false,
+            audit: None,
+            history: None,
         };
         assert!(loopback.validate_bind_safety().is_ok());
 
@@ -1452,6 +3275,8 @@ upstreams:
             address: "0.0.0.0:9181".parse().unwrap(),
             api_key: "secret".into(),
             allow_insecure_remote: true,
+            audit: None,
+            history: None,
         };
         assert!(remote.validate_bind_safety().is_ok());
     }
@@ -1486,6 +3311,37 @@ routes:
         );
     }
 
+    #[test]
+    fn test_resource_labels() {
+        init_log();
+        let route: Route = serde_yml::from_str(
+            r#"
+uri: /
+upstream_id: "1"
+labels:
+  env: prod
+"#,
+        )
+        .unwrap();
+        assert_eq!(route.labels.get("env").map(String::as_str), Some("prod"));
+        assert!(Validate::validate(&route).is_ok());
+        assert!(labels_match(&route.labels, &HashMap::new()));
+        assert!(labels_match(
+            &route.labels,
+            &HashMap::from([("env".to_string(), "prod".to_string())])
+        ));
+        assert!(!labels_match(
+            &route.labels,
+            &HashMap::from([("env".to_string(), "dev".to_string())])
+        ));
+
+        let invalid = Route {
+            labels: HashMap::from([("env".to_string(), "two words".to_string())]),
+            ..route
+        };
+        assert!(Validate::validate(&invalid).is_err());
+    }
+
     #[test]
     fn resolve_upstream_timeout_prefers_explicit() {
         init_log();
@@ -1493,11 +3349,13 @@ routes:
             connect: 1,
             send: 2,
             read: 3,
+            total: None,
         };
         let global = Timeout {
             connect: 9,
             send: 9,
             read: 9,
+            total: None,
         };
         let t = resolve_upstream_timeout(Some(explicit), Some(global));
         assert_eq!(t.connect, 1);
@@ -1511,6 +3369,7 @@ routes:
             connect: 9,
             send: 9,
             read: 9,
+            total: None,
         };
         let t = resolve_upstream_timeout(None, Some(global));
         assert_eq!(t.connect, 9);
@@ -1537,6 +3396,22 @@ routes:
         assert_eq!(disabled.rotation, LogRotation::Disabled);
     }
 
+    #[test]
+    fn log_rotation_triggers_require_internal_rotation() {
+        for extra in ["rotate: daily", "max_size_bytes: 1048576"] {
+            let internal: Log =
+                serde_yml::from_str(&format!("path: /tmp/pingsix.log\n{extra}")).unwrap();
+            assert!(internal.validate().is_ok(), "{extra}");
+            for rotation in ["external", "disabled"] {
+                let log: Log = serde_yml::from_str(&format!(
+                    "path: /tmp/pingsix.log\nrotation: {rotation}\n{extra}"
+                ))
+                .unwrap();
+                assert!(log.validate().is_err(), "{rotation} with {extra}");
+            }
+        }
+    }
+
     #[test]
     fn status_defaults_to_fail_closed_and_protects_remote_diagnostics() {
         let status: Status = serde_yml::from_str("address: 127.0.0.1:9000").unwrap();
@@ -1861,4 +3736,33 @@ routes:
         };
         assert!(tls.validate().is_ok());
     }
+
+    #[test]
+    fn runtime_rejects_cpus_beyond_the_cpu_set() {
+        use validator::Validate;
+        let runtime = Runtime {
+            cpu_affinity: Some(vec![0, 1024]),
+            ..Default::default()
+        };
+        assert!(runtime.validate().is_err());
+
+        let runtime = Runtime {
+            threads: ServiceThreads {
+                proxy: Some(0),
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        assert!(runtime.validate().is_err());
+
+        let runtime = Runtime {
+            threads: ServiceThreads {
+                proxy: Some(8),
+                ..Default::default()
+            },
+            cpu_affinity: Some(vec![0, 1, 2, 3]),
+            log_threads: Some(2),
+        };
+        assert!(runtime.validate().is_ok());
+    }
 }
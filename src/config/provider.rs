@@ -0,0 +1,308 @@
+//! Backend-neutral configuration sync: a [`ConfigProvider`] lists and watches a key
+//! namespace, and [`ConfigSync`] drives it with list/watch/relist semantics.
+//!
+//! Keys are physical (`<prefix><resource_type>/<id>`) and values are resource JSON,
+//! whichever store they come from.
+
+use std::time::Duration;
+
+use async_trait::async_trait;
+use pingora::server::ListenFds;
+use pingora_core::{
+    server::ShutdownWatch,
+    services::{ServiceReadyNotifier, ServiceWithDependents},
+};
+use rand::Rng;
+use tokio::time::{sleep, Instant};
+
+use crate::{
+    core::{metrics, status, ProxyResult},
+    proxy::control_plane::CONTROL_PLANE,
+};
+
+// Retry delays: exponential from the first to the max, with jitter
+const FIRST_RETRY_DELAY: Duration = Duration::from_secs(1);
+const MAX_RETRY_DELAY: Duration = Duration::from_secs(30);
+// Upper bound on holding dependent listeners back for the first publish
+const INITIAL_PUBLISH_WAIT: Duration = Duration::from_secs(10);
+const INITIAL_PUBLISH_POLL: Duration = Duration::from_millis(50);
+
+/// A single change observed by a provider watch.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ConfigChange {
+    Put { key: String, value: Vec<u8> },
+    Delete { key: String },
+}
+
+impl ConfigChange {
+    pub fn key(&self) -> &str {
+        match self {
+            Self::Put { key, .. } | Self::Delete { key } => key,
+        }
+    }
+}
+
+/// Every `(key, value)` under the provider namespace at one revision.
+#[derive(Debug, Default)]
+pub struct ConfigListing {
+    pub revision: i64,
+    pub kvs: Vec<(String, Vec<u8>)>,
+}
+
+/// Receives listings and change batches from a provider.
+#[async_trait]
+pub trait ConfigEventHandler {
+    /// Submit one batch of watch changes. Acceptance is fast; DNS preparation and
+    /// publishing are owned by the control-plane worker. An empty batch is a
+    /// progress notification.
+    async fn handle_changes(&self, changes: &[ConfigChange], revision: i64) -> ProxyResult<()>;
+
+    /// Replace the whole graph with a fresh listing.
+    async fn handle_listing(&self, listing: ConfigListing) -> ProxyResult<()>;
+}
+
+/// A configuration store offering list and watch over a key namespace.
+///
+/// `watch` must deliver every change made after the preceding `list`; a provider
+/// whose notifications are not replayable (such as Redis pub/sub) subscribes
+/// during `list` before reading keys.
+#[async_trait]
+pub trait ConfigProvider {
+    /// Source reported in logs and `/status`.
+    fn source(&self) -> status::ConfigSource;
+
+    /// Canonical namespace (`/prefix/`) every key lives under.
+    fn prefix(&self) -> &str;
+
+    /// Read the whole namespace.
+    async fn list(&mut self) -> ProxyResult<ConfigListing>;
+
+    /// Stream changes after the last listing into `handler` until the stream ends
+    /// (`Ok`) or fails (`Err`); either way the sync loop relists.
+    async fn watch(&mut self, handler: &(dyn ConfigEventHandler + Send + Sync)) -> ProxyResult<()>;
+
+    /// Drop connections after a failure so the next list reconnects.
+    fn reset(&mut self);
+
+    /// Why the next cycle must relist after `watch` returned, or `None` when the watch
+    /// can resume from the last delivered revision instead.
+    fn resync_reason(&self) -> Option<&'static str> {
+        Some("watch_ended")
+    }
+}
+
+/// Exponential retry delay with jitter, so replicas do not reconnect in lockstep.
+#[derive(Debug)]
+struct Backoff {
+    failures: u32,
+}
+
+impl Backoff {
+    fn new() -> Self {
+        Self { failures: 0 }
+    }
+
+    /// Delay before the next attempt: between half and all of `FIRST_RETRY_DELAY`
+    /// doubled per consecutive failure, capped at `MAX_RETRY_DELAY`.
+    fn next_delay(&mut self) -> Duration {
+        let ceiling = FIRST_RETRY_DELAY
+            .saturating_mul(1 << self.failures.min(16))
+            .min(MAX_RETRY_DELAY);
+        self.failures = self.failures.saturating_add(1);
+        ceiling.mul_f64(rand::thread_rng().gen_range(0.5..=1.0))
+    }
+
+    fn reset(&mut self) {
+        self.failures = 0;
+    }
+}
+
+/// Service keeping the control plane in sync with a [`ConfigProvider`].
+///
+/// It signals readiness once the first listing is published (or the first list
+/// fails), so services depending on it — the proxy listeners — do not accept
+/// traffic with an empty route table, notably right after a binary upgrade.
+pub struct ConfigSync {
+    provider: Box<dyn ConfigProvider + Send + Sync>,
+    handler: Box<dyn ConfigEventHandler + Send + Sync>,
+}
+
+impl ConfigSync {
+    pub fn new(
+        provider: Box<dyn ConfigProvider + Send + Sync>,
+        handler: Box<dyn ConfigEventHandler + Send + Sync>,
+    ) -> Self {
+        CONTROL_PLANE.set_config_prefix(provider.prefix().to_string());
+        Self { provider, handler }
+    }
+
+    async fn list(&mut self) -> ProxyResult<()> {
+        let listing = self.provider.list().await?;
+        let revision = listing.revision;
+        self.handler.handle_listing(listing).await?;
+        status::record_sync_success(revision);
+        Ok(())
+    }
+
+    /// Reset the provider on failure.
+    fn reset_provider(&mut self) {
+        log::debug!(
+            "Resetting {} provider for prefix '{}'",
+            self.provider.source().as_str(),
+            self.provider.prefix()
+        );
+        self.provider.reset();
+        status::mark_connected(false);
+    }
+
+    /// Main task loop for synchronization.
+    ///
+    /// Lists once, then watches. A watch that can resume continues from the last
+    /// delivered revision; otherwise the store is listed again (a full resync).
+    async fn run_sync_loop(
+        &mut self,
+        mut shutdown: ShutdownWatch,
+        mut ready_notifier: Option<ServiceReadyNotifier>,
+    ) {
+        let source = self.provider.source().as_str();
+        let prefix = self.provider.prefix().to_string();
+        let mut backoff = Backoff::new();
+        let mut needs_list = true;
+        loop {
+            if needs_list {
+                tokio::select! {
+                    biased;
+                    _ = shutdown.changed() => {
+                        if *shutdown.borrow() {
+                            log::debug!("Shutdown signal received, stopping {source} config sync for prefix '{prefix}'");
+                            CONTROL_PLANE.stop_preparation_worker().await;
+                            return;
+                        }
+                        continue;
+                    },
+
+                    result = self.list() => {
+                        if let Err(err) = result {
+                            log::error!("List operation failed for {source} prefix '{prefix}': {err:?}");
+                            status::record_sync_error(err.to_string());
+                            if let Some(notifier) = ready_notifier.take() {
+                                log::warn!("Initial {source} sync failed, starting listeners without configuration");
+                                notifier.notify_ready();
+                            }
+                            self.reset_provider();
+                            if sleep_or_shutdown(backoff.next_delay(), &shutdown).await {
+                                CONTROL_PLANE.stop_preparation_worker().await;
+                                return;
+                            }
+                            continue;
+                        }
+                        needs_list = false;
+                        backoff.reset();
+                        if let Some(notifier) = ready_notifier.take() {
+                            tokio::spawn(notify_when_published(notifier));
+                        }
+                    }
+                }
+            }
+
+            let started = Instant::now();
+            tokio::select! {
+                biased;
+                _ = shutdown.changed() => {
+                    if *shutdown.borrow() {
+                        log::debug!("Shutdown signal received, stopping {source} config sync for prefix '{prefix}'");
+                        CONTROL_PLANE.stop_preparation_worker().await;
+                        return;
+                    }
+                },
+
+                result = self.provider.watch(self.handler.as_ref()) => {
+                    if let Some(reason) = self.provider.resync_reason() {
+                        log::info!("Relisting {source} prefix '{prefix}' ({reason})");
+                        metrics::CONFIG_RESYNCS.with_label_values(&[reason]).inc();
+                        needs_list = true;
+                    }
+                    if let Err(err) = result {
+                        log::error!("Watch operation failed for {source} prefix '{prefix}': {err:?}");
+                        status::record_sync_error(err.to_string());
+                        metrics::ETCD_WATCH_RECONNECTS.inc();
+                        self.reset_provider();
+                        // A watch that stayed up for a while was healthy; start over.
+                        if started.elapsed() >= MAX_RETRY_DELAY {
+                            backoff.reset();
+                        }
+                        if sleep_or_shutdown(backoff.next_delay(), &shutdown).await {
+                            CONTROL_PLANE.stop_preparation_worker().await;
+                            return;
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[async_trait]
+impl ServiceWithDependents for ConfigSync {
+    async fn start_service(
+        &mut self,
+        _fds: Option<ListenFds>,
+        shutdown: ShutdownWatch,
+        _listeners_per_fd: usize,
+        ready_notifier: ServiceReadyNotifier,
+    ) {
+        status::begin_sync(self.provider.source());
+        self.run_sync_loop(shutdown, Some(ready_notifier)).await
+    }
+
+    fn name(&self) -> &'static str {
+        "Config SYNC"
+    }
+
+    fn threads(&self) -> Option<usize> {
+        Some(1)
+    }
+}
+
+/// Signal readiness once the listed graph is published, or after
+/// [`INITIAL_PUBLISH_WAIT`] when preparation is slow or the graph is rejected.
+async fn notify_when_published(notifier: ServiceReadyNotifier) {
+    let deadline = Instant::now() + INITIAL_PUBLISH_WAIT;
+    while !status::is_ready() && Instant::now() < deadline {
+        sleep(INITIAL_PUBLISH_POLL).await;
+    }
+    notifier.notify_ready();
+}
+
+/// Sleep for `delay`, but return `true` immediately if shutdown is requested.
+async fn sleep_or_shutdown(delay: Duration, shutdown: &ShutdownWatch) -> bool {
+    let mut shutdown = shutdown.clone();
+    tokio::select! {
+        _ = sleep(delay) => false,
+        result = shutdown.changed() => {
+            match result {
+                Ok(()) => *shutdown.borrow(),
+                Err(_) => true,
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn backoff_grows_with_jitter_up_to_the_cap() {
+        let mut backoff = Backoff::new();
+        let first = backoff.next_delay();
+        assert!(first >= FIRST_RETRY_DELAY / 2 && first <= FIRST_RETRY_DELAY);
+        let second = backoff.next_delay();
+        assert!(second >= FIRST_RETRY_DELAY && second <= FIRST_RETRY_DELAY * 2);
+        for _ in 0..40 {
+            assert!(backoff.next_delay() <= MAX_RETRY_DELAY);
+        }
+        backoff.reset();
+        assert!(backoff.next_delay() <= FIRST_RETRY_DELAY);
+    }
+}
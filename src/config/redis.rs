@@ -0,0 +1,688 @@
+//! Redis [`ConfigProvider`]: resources are string keys under a prefix and changes
+//! arrive as keyspace notifications.
+//!
+//! Redis must publish keyspace events for strings and generic commands, e.g.
+//! `notify-keyspace-events K$g` (or `KA`). Redis has no global revision, so the
+//! provider reports a local sequence that increases with every listing and change.
+//!
+//! Listings skip non-string keys with `SCAN ... TYPE string` (Redis 6.0+). Older servers
+//! reject the filter; the provider then scans without it and `MGET` returns nil for them.
+
+use std::{io, time::Duration};
+
+use async_trait::async_trait;
+use bytes::{Buf, BytesMut};
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::{
+        tcp::{OwnedReadHalf, OwnedWriteHalf},
+        TcpStream,
+    },
+    time::timeout,
+};
+
+use super::{
+    etcd::canonicalize_prefix,
+    provider::{ConfigChange, ConfigEventHandler, ConfigListing, ConfigProvider},
+    Redis,
+};
+use crate::core::{status, ProxyError, ProxyResult};
+
+const DEFAULT_TIMEOUT_SECS: u32 = 5;
+/// Keys fetched per `SCAN`/`MGET` round trip.
+const BATCH_SIZE: usize = 500;
+/// The subscription is pinged this often; a missing pong fails the watch.
+const PING_INTERVAL: Duration = Duration::from_secs(30);
+
+fn redis_error(message: impl Into<String>) -> ProxyError {
+    ProxyError::Network(io::Error::other(message.into()))
+}
+
+/// A RESP2 reply.
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum RespValue {
+    Simple(String),
+    Error(String),
+    /// `-WRONGTYPE` error: the key holds a value of another type.
+    WrongType(String),
+    Integer(i64),
+    Bulk(Option<Vec<u8>>),
+    Array(Option<Vec<RespValue>>),
+}
+
+impl RespValue {
+    fn into_bulk(self) -> Option<Vec<u8>> {
+        match self {
+            RespValue::Bulk(data) => data,
+            RespValue::Simple(s) => Some(s.into_bytes()),
+            RespValue::Integer(n) => Some(n.to_string().into_bytes()),
+            _ => None,
+        }
+    }
+
+    fn into_array(self) -> ProxyResult<Vec<RespValue>> {
+        match self {
+            RespValue::Array(Some(items)) => Ok(items),
+            other => Err(redis_error(format!("expected array reply, got {other:?}"))),
+        }
+    }
+}
+
+/// Encode a command as a RESP array of bulk strings.
+fn encode_command(args: &[&[u8]]) -> Vec<u8> {
+    let mut out = format!("*{}\r\n", args.len()).into_bytes();
+    for arg in args {
+        out.extend_from_slice(format!("${}\r\n", arg.len()).as_bytes());
+        out.extend_from_slice(arg);
+        out.extend_from_slice(b"\r\n");
+    }
+    out
+}
+
+/// Parse one reply from the front of `buf`, returning it and the bytes consumed,
+/// or `None` when more data is needed.
+fn parse_value(buf: &[u8]) -> ProxyResult<Option<(RespValue, usize)>> {
+    let Some(line_end) = buf.windows(2).position(|w| w == b"\r\n") else {
+        return Ok(None);
+    };
+    if line_end == 0 {
+        return Err(redis_error("empty RESP line"));
+    }
+    let line =
+        std::str::from_utf8(&buf[1..line_end]).map_err(|_| redis_error("non-UTF-8 RESP header"))?;
+    let header_len = line_end + 2;
+    let parse_len = |s: &str| {
+        s.parse::<i64>()
+            .map_err(|_| redis_error(format!("invalid RESP length '{s}'")))
+    };
+
+    match buf[0] {
+        b'+' => Ok(Some((RespValue::Simple(line.to_string()), header_len))),
+        b'-' if line.starts_with("WRONGTYPE ") => {
+            Ok(Some((RespValue::WrongType(line.to_string()), header_len)))
+        }
+        b'-' => Ok(Some((RespValue::Error(line.to_string()), header_len))),
+        b':' => Ok(Some((RespValue::Integer(parse_len(line)?), header_len))),
+        b'$' => {
+            let len = parse_len(line)?;
+            if len < 0 {
+                return Ok(Some((RespValue::Bulk(None), header_len)));
+            }
+            let end = header_len + len as usize;
+            if buf.len() < end + 2 {
+                return Ok(None);
+            }
+            Ok(Some((
+                RespValue::Bulk(Some(buf[header_len..end].to_vec())),
+                end + 2,
+            )))
+        }
+        b'*' => {
+            let len = parse_len(line)?;
+            if len < 0 {
+                return Ok(Some((RespValue::Array(None), header_len)));
+            }
+            let mut consumed = header_len;
+            let mut items = Vec::with_capacity(len.min(1024) as usize);
+            for _ in 0..len {
+                let Some((item, used)) = parse_value(&buf[consumed..])? else {
+                    return Ok(None);
+                };
+                items.push(item);
+                consumed += used;
+            }
+            Ok(Some((RespValue::Array(Some(items)), consumed)))
+        }
+        other => Err(redis_error(format!(
+            "unexpected RESP type byte 0x{other:02x}"
+        ))),
+    }
+}
+
+/// Escape glob metacharacters so a prefix matches literally in `SCAN`/`PSUBSCRIBE`.
+fn escape_glob(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    for c in s.chars() {
+        if matches!(c, '*' | '?' | '[' | ']' | '\\') {
+            out.push('\\');
+        }
+        out.push(c);
+    }
+    out
+}
+
+/// Whether a `notify-keyspace-events` setting publishes the events the provider needs.
+fn notifications_enabled(flags: &str) -> bool {
+    flags.contains('K') && (flags.contains('A') || (flags.contains('$') && flags.contains('g')))
+}
+
+/// Read side of a connection with its parse buffer; reads are cancel-safe.
+struct RespReader {
+    half: OwnedReadHalf,
+    buf: BytesMut,
+}
+
+impl RespReader {
+    async fn read_value(&mut self) -> ProxyResult<RespValue> {
+        loop {
+            if let Some((value, used)) = parse_value(&self.buf)? {
+                self.buf.advance(used);
+                return Ok(value);
+            }
+            if self.half.read_buf(&mut self.buf).await? == 0 {
+                return Err(redis_error("connection closed by server"));
+            }
+        }
+    }
+}
+
+struct RespConnection {
+    reader: RespReader,
+    writer: OwnedWriteHalf,
+    timeout: Duration,
+}
+
+impl RespConnection {
+    async fn connect(cfg: &Redis) -> ProxyResult<Self> {
+        let timeout_duration =
+            Duration::from_secs(cfg.timeout.unwrap_or(DEFAULT_TIMEOUT_SECS) as u64);
+        let stream = timeout(timeout_duration, TcpStream::connect(&cfg.address))
+            .await
+            .map_err(|_| redis_error(format!("connect to '{}' timed out", cfg.address)))?
+            .map_err(|e| {
+                ProxyError::with_cause(format!("Failed to connect to redis '{}'", cfg.address), e)
+            })?;
+        stream.set_nodelay(true)?;
+        let (read_half, writer) = stream.into_split();
+        let mut conn = Self {
+            reader: RespReader {
+                half: read_half,
+                buf: BytesMut::with_capacity(16 * 1024),
+            },
+            writer,
+            timeout: timeout_duration,
+        };
+
+        if let Some(password) = &cfg.password {
+            match &cfg.username {
+                Some(user) => {
+                    conn.command(&[b"AUTH", user.as_bytes(), password.as_bytes()])
+                        .await?
+                }
+                None => conn.command(&[b"AUTH", password.as_bytes()]).await?,
+            };
+        }
+        if cfg.db != 0 {
+            conn.command(&[b"SELECT", cfg.db.to_string().as_bytes()])
+                .await?;
+        }
+        Ok(conn)
+    }
+
+    async fn send(&mut self, args: &[&[u8]]) -> ProxyResult<()> {
+        timeout(self.timeout, self.writer.write_all(&encode_command(args)))
+            .await
+            .map_err(|_| redis_error("write timed out"))??;
+        Ok(())
+    }
+
+    /// Send a command and wait for its reply, server errors included.
+    async fn request(&mut self, args: &[&[u8]]) -> ProxyResult<RespValue> {
+        self.send(args).await?;
+        timeout(self.timeout, self.reader.read_value())
+            .await
+            .map_err(|_| redis_error("command timed out"))?
+    }
+
+    /// Send a command and wait for its reply; server errors become `Err`.
+    async fn command(&mut self, args: &[&[u8]]) -> ProxyResult<RespValue> {
+        match self.request(args).await? {
+            RespValue::Error(message) | RespValue::WrongType(message) => {
+                Err(command_error(args, &message))
+            }
+            reply => Ok(reply),
+        }
+    }
+}
+
+fn command_error(args: &[&[u8]], message: &str) -> ProxyError {
+    let name = String::from_utf8_lossy(args[0]);
+    redis_error(format!("{name} failed: {message}"))
+}
+
+/// [`ConfigProvider`] reading string keys under a Redis prefix.
+pub struct RedisProvider {
+    config: Redis,
+    canonical_prefix: String,
+    /// Connection for SCAN/MGET/GET.
+    commands: Option<RespConnection>,
+    /// Connection in subscribe mode, opened by `list` before any key is read.
+    subscriber: Option<RespConnection>,
+    /// Local sequence standing in for a store revision.
+    revision: i64,
+    /// Whether `SCAN` takes a `TYPE` filter; cleared once a pre-6.0 server rejects it.
+    scan_by_type: bool,
+}
+
+impl RedisProvider {
+    pub fn new(config: Redis) -> Self {
+        let canonical_prefix = canonicalize_prefix(&config.prefix);
+        Self {
+            config,
+            canonical_prefix,
+            commands: None,
+            subscriber: None,
+            revision: 0,
+            scan_by_type: true,
+        }
+    }
+
+    fn channel_prefix(&self) -> String {
+        format!("__keyspace@{}__:", self.config.db)
+    }
+
+    async fn commands(&mut self) -> ProxyResult<&mut RespConnection> {
+        if self.commands.is_none() {
+            log::debug!("Connecting to redis at '{}'", self.config.address);
+            self.commands = Some(RespConnection::connect(&self.config).await?);
+        }
+        self.commands
+            .as_mut()
+            .ok_or_else(|| redis_error("command connection is not initialized"))
+    }
+
+    async fn subscribe(&mut self) -> ProxyResult<()> {
+        let pattern = format!(
+            "{}{}*",
+            self.channel_prefix(),
+            escape_glob(&self.canonical_prefix)
+        );
+        let mut conn = RespConnection::connect(&self.config).await?;
+        let confirmation = conn
+            .command(&[b"PSUBSCRIBE", pattern.as_bytes()])
+            .await?
+            .into_array()?;
+        if confirmation.first() != Some(&RespValue::Bulk(Some(b"psubscribe".to_vec()))) {
+            return Err(redis_error("unexpected PSUBSCRIBE confirmation"));
+        }
+        self.subscriber = Some(conn);
+        Ok(())
+    }
+
+    /// Warn when the server is not configured to publish the events the watch relies on.
+    async fn check_notifications(&mut self) {
+        let reply = match self.commands().await {
+            Ok(conn) => {
+                conn.command(&[b"CONFIG", b"GET", b"notify-keyspace-events"])
+                    .await
+            }
+            Err(e) => Err(e),
+        };
+        // Managed Redis often disables CONFIG; only a readable setting is checked.
+        let flags = reply
+            .and_then(RespValue::into_array)
+            .ok()
+            .and_then(|items| items.into_iter().nth(1))
+            .and_then(RespValue::into_bulk)
+            .map(|v| String::from_utf8_lossy(&v).into_owned());
+        if let Some(flags) = flags {
+            if !notifications_enabled(&flags) {
+                log::warn!(
+                    "Redis notify-keyspace-events is '{flags}'; set it to 'K$g' or 'KA' or config changes will not be observed"
+                );
+            }
+        }
+    }
+
+    async fn scan_keys(&mut self) -> ProxyResult<Vec<String>> {
+        let pattern = format!("{}*", escape_glob(&self.canonical_prefix));
+        let count = BATCH_SIZE.to_string();
+        let mut scan_by_type = self.scan_by_type;
+        let conn = self.commands().await?;
+        let mut cursor = b"0".to_vec();
+        let mut keys = Vec::new();
+        loop {
+            let mut args: Vec<&[u8]> = vec![
+                b"SCAN",
+                cursor.as_slice(),
+                b"MATCH",
+                pattern.as_bytes(),
+                b"COUNT",
+                count.as_bytes(),
+            ];
+            if scan_by_type {
+                args.extend([b"TYPE".as_slice(), b"string"]);
+            }
+            let reply = match conn.request(&args).await? {
+                RespValue::Error(message) if scan_by_type && cursor == b"0" => {
+                    log::warn!(
+                        "Redis rejected SCAN TYPE ({message}), scanning without it (Redis < 6.0)"
+                    );
+                    scan_by_type = false;
+                    continue;
+                }
+                RespValue::Error(message) | RespValue::WrongType(message) => {
+                    return Err(command_error(&args, &message))
+                }
+                reply => reply,
+            };
+            let mut reply = reply.into_array()?.into_iter();
+            let (Some(next), Some(batch)) = (reply.next(), reply.next()) else {
+                return Err(redis_error("malformed SCAN reply"));
+            };
+            for key in batch.into_array()? {
+                if let Some(key) = key.into_bulk() {
+                    keys.push(
+                        String::from_utf8(key)
+                            .map_err(|_| redis_error("non-UTF-8 key under configuration prefix"))?,
+                    );
+                }
+            }
+            cursor = next
+                .into_bulk()
+                .ok_or_else(|| redis_error("malformed SCAN cursor"))?;
+            if cursor == b"0" {
+                break;
+            }
+        }
+        self.scan_by_type = scan_by_type;
+        // SCAN may return a key more than once.
+        keys.sort();
+        keys.dedup();
+        Ok(keys)
+    }
+
+    /// Current value of `key` as a change: a vanished key is a delete.
+    async fn fetch_change(&mut self, key: String) -> ProxyResult<ConfigChange> {
+        let conn = self.commands().await?;
+        let args: [&[u8]; 2] = [b"GET", key.as_bytes()];
+        let value = match conn.request(&args).await? {
+            // Non-string values are not resources; treat them as absent.
+            RespValue::WrongType(_) => None,
+            RespValue::Error(message) => return Err(command_error(&args, &message)),
+            reply => reply.into_bulk(),
+        };
+        Ok(match value {
+            Some(value) => ConfigChange::Put { key, value },
+            None => ConfigChange::Delete { key },
+        })
+    }
+}
+
+#[async_trait]
+impl ConfigProvider for RedisProvider {
+    fn source(&self) -> status::ConfigSource {
+        status::ConfigSource::Redis
+    }
+
+    fn prefix(&self) -> &str {
+        &self.canonical_prefix
+    }
+
+    async fn list(&mut self) -> ProxyResult<ConfigListing> {
+        // Pub/sub does not replay: subscribe before reading so no write between the
+        // scan and the watch is lost. Notifications queue on the socket meanwhile.
+        self.subscribe().await?;
+        self.check_notifications().await;
+
+        let keys = self.scan_keys().await?;
+        let conn = self.commands().await?;
+        let mut kvs = Vec::with_capacity(keys.len());
+        for chunk in keys.chunks(BATCH_SIZE) {
+            let mut args: Vec<&[u8]> = Vec::with_capacity(chunk.len() + 1);
+            args.push(b"MGET");
+            args.extend(chunk.iter().map(|k| k.as_bytes()));
+            let values = conn.command(&args).await?.into_array()?;
+            for (key, value) in chunk.iter().zip(values) {
+                // Deleted between SCAN and MGET; the queued notification covers it.
+                if let Some(value) = value.into_bulk() {
+                    kvs.push((key.clone(), value));
+                }
+            }
+        }
+
+        self.revision += 1;
+        Ok(ConfigListing {
+            revision: self.revision,
+            kvs,
+        })
+    }
+
+    async fn watch(&mut self, handler: &(dyn ConfigEventHandler + Send + Sync)) -> ProxyResult<()> {
+        let mut subscriber = self
+            .subscriber
+            .take()
+            .ok_or_else(|| redis_error("watch started without a subscription"))?;
+        let channel_prefix = self.channel_prefix();
+        status::mark_connected(true);
+
+        let mut ping_interval = tokio::time::interval(PING_INTERVAL);
+        ping_interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Skip);
+        ping_interval.tick().await;
+        let mut awaiting_pong = false;
+
+        loop {
+            tokio::select! {
+                message = subscriber.reader.read_value() => {
+                    let mut parts = message?.into_array()?.into_iter();
+                    match parts.next().and_then(RespValue::into_bulk).as_deref() {
+                        Some(b"pmessage") => {
+                            let channel = parts
+                                .nth(1)
+                                .and_then(RespValue::into_bulk)
+                                .ok_or_else(|| redis_error("malformed keyspace notification"))?;
+                            let channel = String::from_utf8_lossy(&channel);
+                            let Some(key) = channel.strip_prefix(&channel_prefix) else {
+                                continue;
+                            };
+                            let change = self.fetch_change(key.to_string()).await?;
+                            self.revision += 1;
+                            handler.handle_changes(&[change], self.revision).await?;
+                            status::record_sync_success(self.revision);
+                        }
+                        Some(b"pong") => {
+                            awaiting_pong = false;
+                            handler.handle_changes(&[], self.revision).await?;
+                            status::record_sync_success(self.revision);
+                        }
+                        _ => {}
+                    }
+                }
+                _ = ping_interval.tick() => {
+                    if awaiting_pong {
+                        return Err(redis_error("subscription did not answer PING"));
+                    }
+                    subscriber.send(&[b"PING"]).await?;
+                    awaiting_pong = true;
+                }
+            }
+        }
+    }
+
+    fn reset(&mut self) {
+        self.commands = None;
+        self.subscriber = None;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_nested_replies_and_waits_for_partial_input() {
+        let reply =
+            b"*4\r\n$8\r\npmessage\r\n$3\r\np:*\r\n$21\r\n__keyspace@0__:/a/b/1\r\n$3\r\nset\r\n";
+        let (value, used) = parse_value(reply).unwrap().unwrap();
+        assert_eq!(used, reply.len());
+        let items = value.into_array().unwrap();
+        assert_eq!(
+            items[2],
+            RespValue::Bulk(Some(b"__keyspace@0__:/a/b/1".to_vec()))
+        );
+
+        assert_eq!(parse_value(&reply[..reply.len() - 3]).unwrap(), None);
+        assert_eq!(
+            parse_value(b"$-1\r\n").unwrap(),
+            Some((RespValue::Bulk(None), 5))
+        );
+        assert_eq!(
+            parse_value(b"-ERR bad\r\n").unwrap(),
+            Some((RespValue::Error("ERR bad".into()), 10))
+        );
+        assert_eq!(
+            parse_value(b"-WRONGTYPE Operation\r\n").unwrap(),
+            Some((RespValue::WrongType("WRONGTYPE Operation".into()), 22))
+        );
+        assert!(parse_value(b"?x\r\n").is_err());
+    }
+
+    #[test]
+    fn parses_replies_split_at_every_byte() {
+        let reply: &[u8] = b"*4\r\n$4\r\na\r\nb\r\n:-7\r\n$-1\r\n-ERR nested\r\n+OK\r\n";
+        let first = reply.len() - 5;
+        for end in 0..first {
+            assert_eq!(parse_value(&reply[..end]).unwrap(), None, "prefix {end}");
+        }
+        let (value, used) = parse_value(reply).unwrap().unwrap();
+        assert_eq!(used, first);
+        assert_eq!(
+            value,
+            RespValue::Array(Some(vec![
+                RespValue::Bulk(Some(b"a\r\nb".to_vec())),
+                RespValue::Integer(-7),
+                RespValue::Bulk(None),
+                RespValue::Error("ERR nested".into()),
+            ]))
+        );
+        assert_eq!(
+            parse_value(&reply[used..]).unwrap(),
+            Some((RespValue::Simple("OK".into()), 5))
+        );
+        assert!(parse_value(b"$x\r\n").is_err());
+        assert!(parse_value(b"\r\n").is_err());
+    }
+
+    /// A server answering the commands on each accepted connection with the scripted
+    /// replies, in order, written a few bytes at a time.
+    async fn scripted_server(connections: Vec<Vec<&'static [u8]>>) -> String {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap().to_string();
+        tokio::spawn(async move {
+            for replies in connections {
+                let (mut stream, _) = listener.accept().await.unwrap();
+                tokio::spawn(async move {
+                    let mut buf = BytesMut::new();
+                    for reply in replies {
+                        while parse_value(&buf).unwrap().is_none() {
+                            if stream.read_buf(&mut buf).await.unwrap() == 0 {
+                                return;
+                            }
+                        }
+                        buf.clear();
+                        for chunk in reply.chunks(3) {
+                            stream.write_all(chunk).await.unwrap();
+                            tokio::time::sleep(Duration::from_millis(1)).await;
+                        }
+                    }
+                    let _ = stream.read_buf(&mut buf).await;
+                });
+            }
+        });
+        address
+    }
+
+    #[tokio::test]
+    async fn lists_through_fragmented_and_error_replies_and_reconnects() {
+        let address = scripted_server(vec![
+            vec![b"*3\r\n$10\r\npsubscribe\r\n$5\r\n/t/\\*\r\n:1\r\n"],
+            vec![
+                // Managed Redis may refuse CONFIG; the listing goes on.
+                b"-ERR unknown command 'CONFIG'\r\n",
+                b"*2\r\n$1\r\n0\r\n*2\r\n$11\r\n/t/routes/1\r\n$11\r\n/t/routes/2\r\n",
+                b"*2\r\n$7\r\n{\"a\":1}\r\n$-1\r\n",
+                b"-WRONGTYPE Operation against a key holding the wrong kind of value\r\n",
+                b"-ERR boom\r\n",
+            ],
+            // After a reset the provider lists again on fresh connections.
+            vec![b"*3\r\n$10\r\npsubscribe\r\n$5\r\n/t/\\*\r\n:1\r\n"],
+            vec![
+                b"*2\r\n$22\r\nnotify-keyspace-events\r\n$3\r\nK$g\r\n",
+                b"*2\r\n$1\r\n0\r\n*0\r\n",
+            ],
+        ])
+        .await;
+        let mut provider = RedisProvider::new(Redis {
+            address,
+            db: 0,
+            prefix: "/t".into(),
+            username: None,
+            password: None,
+            timeout: Some(2),
+        });
+
+        let listing = provider.list().await.unwrap();
+        assert_eq!(listing.revision, 1);
+        assert_eq!(
+            listing.kvs,
+            vec![("/t/routes/1".to_string(), b"{\"a\":1}".to_vec())]
+        );
+
+        let key = "/t/routes/3".to_string();
+        assert_eq!(
+            provider.fetch_change(key.clone()).await.unwrap(),
+            ConfigChange::Delete { key: key.clone() }
+        );
+        let err = provider.fetch_change(key).await.unwrap_err();
+        assert!(err.to_string().contains("GET failed: ERR boom"), "{err}");
+
+        provider.reset();
+        let listing = provider.list().await.unwrap();
+        assert_eq!(listing.revision, 2);
+        assert!(listing.kvs.is_empty());
+    }
+
+    #[tokio::test]
+    async fn scans_without_type_filter_when_the_server_rejects_it() {
+        let address = scripted_server(vec![
+            vec![b"*3\r\n$10\r\npsubscribe\r\n$5\r\n/t/\\*\r\n:1\r\n"],
+            vec![
+                b"*2\r\n$22\r\nnotify-keyspace-events\r\n$3\r\nK$g\r\n",
+                // Redis 5 does not know `SCAN ... TYPE`.
+                b"-ERR syntax error\r\n",
+                b"*2\r\n$1\r\n0\r\n*2\r\n$11\r\n/t/routes/1\r\n$9\r\n/t/a-list\r\n",
+                // Sorted keys: the list comes first and reads as nil.
+                b"*2\r\n$-1\r\n$7\r\n{\"a\":1}\r\n",
+            ],
+        ])
+        .await;
+        let mut provider = RedisProvider::new(Redis {
+            address,
+            db: 0,
+            prefix: "/t".into(),
+            username: None,
+            password: None,
+            timeout: Some(2),
+        });
+
+        let listing = provider.list().await.unwrap();
+        assert_eq!(
+            listing.kvs,
+            vec![("/t/routes/1".to_string(), b"{\"a\":1}".to_vec())]
+        );
+        assert!(!provider.scan_by_type);
+    }
+
+    #[test]
+    fn encodes_commands_and_escapes_prefix_globs() {
+        assert_eq!(
+            encode_command(&[b"GET", b"/k"]),
+            b"*2\r\n$3\r\nGET\r\n$2\r\n/k\r\n".to_vec()
+        );
+        assert_eq!(escape_glob("/a*b?[c]/"), "/a\\*b\\?\\[c\\]/");
+        assert!(notifications_enabled("K$g"));
+        assert!(notifications_enabled("AKE"));
+        assert!(!notifications_enabled("Ex"));
+        assert!(!notifications_enabled("K$"));
+    }
+}
@@ -0,0 +1,1083 @@
+//! xDS [`ConfigProvider`]: clusters, endpoints and route configurations streamed from an
+//! Envoy-compatible control plane over ADS (Aggregated Discovery Service).
+//!
+//! Only the subset of the v3 API that maps onto pingsix resources is decoded:
+//! - CDS clusters (static, DNS or EDS) become upstreams `upstreams/<cluster>`.
+//! - EDS endpoints of the lowest populated priority fill the upstream nodes.
+//! - RDS routes with a prefix or exact path become `routes/<config>.<vhost>.<index>`;
+//!   weighted clusters become a `traffic-split` plugin.
+//!
+//! The control plane has no revision that spans resource types, so the provider reports
+//! a local sequence that increases with every listing and change.
+
+use std::{
+    collections::{BTreeMap, BTreeSet, HashMap, HashSet},
+    io,
+    time::Duration,
+};
+
+use async_trait::async_trait;
+use prost::Message;
+use serde_json::{json, Value as JsonValue};
+use tokio::{sync::mpsc, time::timeout};
+use tonic::{
+    client::Grpc,
+    codegen::http::uri::PathAndQuery,
+    transport::{Certificate, ClientTlsConfig, Endpoint, Identity},
+    Streaming,
+};
+use tonic_prost::ProstCodec;
+
+use super::{
+    provider::{ConfigChange, ConfigEventHandler, ConfigListing, ConfigProvider},
+    EtcdTls, Xds,
+};
+use crate::core::{status, ProxyError, ProxyResult};
+
+/// Namespace the translated resources are published under.
+pub const XDS_PREFIX: &str = "/xds/";
+
+const DEFAULT_TIMEOUT_SECS: u32 = 5;
+/// Time allowed for the first CDS/EDS/RDS responses after connecting.
+const INITIAL_FETCH_TIMEOUT: Duration = Duration::from_secs(30);
+const KEEPALIVE_INTERVAL: Duration = Duration::from_secs(30);
+const ADS_PATH: &str =
+    "/envoy.service.discovery.v3.AggregatedDiscoveryService/StreamAggregatedResources";
+
+const CLUSTER_TYPE: &str = "type.googleapis.com/envoy.config.cluster.v3.Cluster";
+const ENDPOINT_TYPE: &str = "type.googleapis.com/envoy.config.endpoint.v3.ClusterLoadAssignment";
+const ROUTE_TYPE: &str = "type.googleapis.com/envoy.config.route.v3.RouteConfiguration";
+
+/// Catch-all parameter name used for translated prefix matches.
+const PREFIX_PARAM: &str = "{*xds_path}";
+
+fn xds_error(message: impl Into<String>) -> ProxyError {
+    ProxyError::Network(io::Error::other(message.into()))
+}
+
+/// Hand-written prost messages for the fields pingsix reads; unknown fields are skipped
+/// by the decoder.
+mod proto {
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct Node {
+        #[prost(string, tag = "1")]
+        pub id: String,
+        #[prost(string, tag = "2")]
+        pub cluster: String,
+        #[prost(string, tag = "6")]
+        pub user_agent_name: String,
+    }
+
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct RpcStatus {
+        #[prost(int32, tag = "1")]
+        pub code: i32,
+        #[prost(string, tag = "2")]
+        pub message: String,
+    }
+
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct DiscoveryRequest {
+        #[prost(string, tag = "1")]
+        pub version_info: String,
+        #[prost(message, optional, tag = "2")]
+        pub node: Option<Node>,
+        #[prost(string, repeated, tag = "3")]
+        pub resource_names: Vec<String>,
+        #[prost(string, tag = "4")]
+        pub type_url: String,
+        #[prost(string, tag = "5")]
+        pub response_nonce: String,
+        #[prost(message, optional, tag = "6")]
+        pub error_detail: Option<RpcStatus>,
+    }
+
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct DiscoveryResponse {
+        #[prost(string, tag = "1")]
+        pub version_info: String,
+        #[prost(message, repeated, tag = "2")]
+        pub resources: Vec<prost_types::Any>,
+        #[prost(string, tag = "4")]
+        pub type_url: String,
+        #[prost(string, tag = "5")]
+        pub nonce: String,
+    }
+
+    pub const DISCOVERY_TYPE_EDS: i32 = 3;
+
+    pub const LB_POLICY_RANDOM: i32 = 3;
+    pub const LB_POLICY_RING_HASH: i32 = 2;
+    pub const LB_POLICY_MAGLEV: i32 = 5;
+
+    pub const HEALTH_STATUS_UNHEALTHY: i32 = 2;
+    pub const HEALTH_STATUS_DRAINING: i32 = 3;
+
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct Cluster {
+        #[prost(string, tag = "1")]
+        pub name: String,
+        #[prost(int32, tag = "2")]
+        pub r#type: i32,
+        #[prost(message, optional, tag = "3")]
+        pub eds_cluster_config: Option<EdsClusterConfig>,
+        #[prost(int32, tag = "6")]
+        pub lb_policy: i32,
+        #[prost(message, optional, tag = "33")]
+        pub load_assignment: Option<ClusterLoadAssignment>,
+    }
+
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct EdsClusterConfig {
+        #[prost(string, tag = "2")]
+        pub service_name: String,
+    }
+
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct ClusterLoadAssignment {
+        #[prost(string, tag = "1")]
+        pub cluster_name: String,
+        #[prost(message, repeated, tag = "2")]
+        pub endpoints: Vec<LocalityLbEndpoints>,
+    }
+
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct LocalityLbEndpoints {
+        #[prost(message, repeated, tag = "2")]
+        pub lb_endpoints: Vec<LbEndpoint>,
+        #[prost(uint32, tag = "5")]
+        pub priority: u32,
+    }
+
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct LbEndpoint {
+        #[prost(message, optional, tag = "1")]
+        pub endpoint: Option<Endpoint>,
+        #[prost(int32, tag = "2")]
+        pub health_status: i32,
+        #[prost(message, optional, tag = "4")]
+        pub load_balancing_weight: Option<u32>,
+    }
+
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct Endpoint {
+        #[prost(message, optional, tag = "1")]
+        pub address: Option<Address>,
+    }
+
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct Address {
+        #[prost(message, optional, tag = "1")]
+        pub socket_address: Option<SocketAddress>,
+    }
+
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct SocketAddress {
+        #[prost(string, tag = "2")]
+        pub address: String,
+        #[prost(uint32, tag = "3")]
+        pub port_value: u32,
+    }
+
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct RouteConfiguration {
+        #[prost(string, tag = "1")]
+        pub name: String,
+        #[prost(message, repeated, tag = "2")]
+        pub virtual_hosts: Vec<VirtualHost>,
+    }
+
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct VirtualHost {
+        #[prost(string, tag = "1")]
+        pub name: String,
+        #[prost(string, repeated, tag = "2")]
+        pub domains: Vec<String>,
+        #[prost(message, repeated, tag = "3")]
+        pub routes: Vec<Route>,
+    }
+
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct Route {
+        #[prost(message, optional, tag = "1")]
+        pub r#match: Option<RouteMatch>,
+        #[prost(message, optional, tag = "2")]
+        pub route: Option<RouteAction>,
+        #[prost(string, tag = "14")]
+        pub name: String,
+    }
+
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct RouteMatch {
+        #[prost(string, optional, tag = "1")]
+        pub prefix: Option<String>,
+        #[prost(string, optional, tag = "2")]
+        pub path: Option<String>,
+        #[prost(message, repeated, tag = "6")]
+        pub headers: Vec<Matcher>,
+        #[prost(message, repeated, tag = "7")]
+        pub query_parameters: Vec<Matcher>,
+    }
+
+    /// Header or query parameter matcher; only its presence matters.
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct Matcher {
+        #[prost(string, tag = "1")]
+        pub name: String,
+    }
+
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct RouteAction {
+        #[prost(string, tag = "1")]
+        pub cluster: String,
+        #[prost(message, optional, tag = "3")]
+        pub weighted_clusters: Option<WeightedCluster>,
+    }
+
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct WeightedCluster {
+        #[prost(message, repeated, tag = "1")]
+        pub clusters: Vec<ClusterWeight>,
+    }
+
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct ClusterWeight {
+        #[prost(string, tag = "1")]
+        pub name: String,
+        #[prost(message, optional, tag = "3")]
+        pub weight: Option<u32>,
+    }
+}
+
+use proto::{
+    Cluster, ClusterLoadAssignment, DiscoveryRequest, DiscoveryResponse, RouteConfiguration,
+};
+
+/// Last accepted resources and per-type protocol state of one ADS stream.
+#[derive(Default)]
+struct XdsState {
+    clusters: BTreeMap<String, Cluster>,
+    endpoints: BTreeMap<String, ClusterLoadAssignment>,
+    route_configs: BTreeMap<String, RouteConfiguration>,
+    /// EDS resource names requested for the current clusters.
+    eds_names: BTreeSet<String>,
+    versions: HashMap<&'static str, String>,
+    nonces: HashMap<&'static str, String>,
+    received: HashSet<&'static str>,
+}
+
+impl XdsState {
+    /// Decode and store one response. CDS is state-of-the-world; EDS and RDS responses
+    /// may carry only the resources that changed, so they merge by name.
+    fn apply(
+        &mut self,
+        type_url: &'static str,
+        resources: &[prost_types::Any],
+        route_configs: &[String],
+    ) -> Result<(), String> {
+        match type_url {
+            CLUSTER_TYPE => {
+                let mut clusters = BTreeMap::new();
+                for cluster in decode_all::<Cluster>(resources)? {
+                    clusters.insert(cluster.name.clone(), cluster);
+                }
+                self.clusters = clusters;
+            }
+            ENDPOINT_TYPE => {
+                for assignment in decode_all::<ClusterLoadAssignment>(resources)? {
+                    if self.eds_names.contains(&assignment.cluster_name) {
+                        self.endpoints
+                            .insert(assignment.cluster_name.clone(), assignment);
+                    }
+                }
+            }
+            _ => {
+                for config in decode_all::<RouteConfiguration>(resources)? {
+                    if route_configs.contains(&config.name) {
+                        self.route_configs.insert(config.name.clone(), config);
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Refresh the EDS subscription from the current clusters. Returns whether it changed.
+    fn refresh_eds_names(&mut self) -> bool {
+        let names: BTreeSet<String> = self
+            .clusters
+            .values()
+            .filter(|c| c.r#type == proto::DISCOVERY_TYPE_EDS)
+            .map(|c| eds_service_name(c).to_string())
+            .collect();
+        if names == self.eds_names {
+            return false;
+        }
+        self.endpoints.retain(|name, _| names.contains(name));
+        self.eds_names = names;
+        true
+    }
+
+    /// Whether every subscribed type has answered at least once.
+    fn is_synced(&self, route_configs: &[String]) -> bool {
+        self.received.contains(CLUSTER_TYPE)
+            && (route_configs.is_empty() || self.received.contains(ROUTE_TYPE))
+            && (self.eds_names.is_empty() || self.received.contains(ENDPOINT_TYPE))
+    }
+}
+
+fn decode_all<M: Message + Default>(resources: &[prost_types::Any]) -> Result<Vec<M>, String> {
+    resources
+        .iter()
+        .map(|any| {
+            M::decode(any.value.as_slice())
+                .map_err(|e| format!("failed to decode '{}': {e}", any.type_url))
+        })
+        .collect()
+}
+
+fn known_type(type_url: &str) -> Option<&'static str> {
+    [CLUSTER_TYPE, ENDPOINT_TYPE, ROUTE_TYPE]
+        .into_iter()
+        .find(|known| *known == type_url)
+}
+
+fn eds_service_name(cluster: &Cluster) -> &str {
+    cluster
+        .eds_cluster_config
+        .as_ref()
+        .map(|c| c.service_name.as_str())
+        .filter(|name| !name.is_empty())
+        .unwrap_or(&cluster.name)
+}
+
+/// Map an xDS name onto a resource id: keys cannot contain `/`, and other characters
+/// outside `[A-Za-z0-9._-]` (such as Istio's `|`) are replaced as well.
+fn resource_id(name: &str) -> String {
+    name.chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || matches!(c, '.' | '_' | '-') {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect()
+}
+
+/// Nodes of the lowest priority that has usable endpoints. Unhealthy and draining
+/// endpoints are skipped; duplicate addresses add up their weights.
+fn endpoint_nodes(assignment: &ClusterLoadAssignment) -> BTreeMap<String, u32> {
+    let mut by_priority: BTreeMap<u32, BTreeMap<String, u32>> = BTreeMap::new();
+    for locality in &assignment.endpoints {
+        for lb_endpoint in &locality.lb_endpoints {
+            if matches!(
+                lb_endpoint.health_status,
+                proto::HEALTH_STATUS_UNHEALTHY | proto::HEALTH_STATUS_DRAINING
+            ) {
+                continue;
+            }
+            let Some(socket) = lb_endpoint
+                .endpoint
+                .as_ref()
+                .and_then(|e| e.address.as_ref())
+                .and_then(|a| a.socket_address.as_ref())
+            else {
+                continue;
+            };
+            if socket.address.is_empty() || socket.port_value == 0 {
+                continue;
+            }
+            let host = if socket.address.contains(':') {
+                format!("[{}]", socket.address)
+            } else {
+                socket.address.clone()
+            };
+            let weight = lb_endpoint.load_balancing_weight.unwrap_or(1).max(1);
+            *by_priority
+                .entry(locality.priority)
+                .or_default()
+                .entry(format!("{host}:{}", socket.port_value))
+                .or_insert(0) += weight;
+        }
+    }
+    by_priority.into_values().next().unwrap_or_default()
+}
+
+fn selection_type(lb_policy: i32) -> &'static str {
+    match lb_policy {
+        proto::LB_POLICY_RANDOM => "random",
+        proto::LB_POLICY_RING_HASH | proto::LB_POLICY_MAGLEV => "ketama",
+        _ => "roundrobin",
+    }
+}
+
+/// Translate a prefix match into matchit patterns. Prefixes match on path-segment
+/// boundaries: `/api` and `/api/` both cover `/api`, `/api/` and `/api/...`.
+fn prefix_uris(prefix: &str) -> Vec<String> {
+    let base = prefix.trim_end_matches('/');
+    let mut uris = vec![format!("{base}/{PREFIX_PARAM}")];
+    if base.is_empty() {
+        uris.push("/".to_string());
+    } else {
+        uris.push(base.to_string());
+        uris.push(format!("{base}/"));
+    }
+    uris
+}
+
+/// Hosts of a virtual host, `None` when no domain can be expressed. `*` matches any host
+/// and suffix wildcards such as `example.*` are unsupported; ports are dropped.
+fn vhost_hosts(domains: &[String]) -> Option<Vec<String>> {
+    if domains.iter().any(|d| d == "*") {
+        return Some(Vec::new());
+    }
+    let hosts: BTreeSet<String> = domains
+        .iter()
+        .filter(|d| !d.ends_with('*'))
+        .map(|d| match d.rsplit_once(':') {
+            Some((host, port))
+                if !port.is_empty()
+                    && port.bytes().all(|b| b.is_ascii_digit())
+                    && (!host.contains(':') || host.ends_with(']')) =>
+            {
+                host.to_string()
+            }
+            _ => d.clone(),
+        })
+        .collect();
+    (!hosts.is_empty()).then(|| hosts.into_iter().collect())
+}
+
+fn translate_route(
+    route: &proto::Route,
+    hosts: &[String],
+    priority: u32,
+    upstreams: &HashSet<&str>,
+) -> Option<JsonValue> {
+    let route_match = route.r#match.as_ref()?;
+    if !route_match.headers.is_empty() || !route_match.query_parameters.is_empty() {
+        return None;
+    }
+    let uris = match (&route_match.prefix, &route_match.path) {
+        (Some(prefix), _) if !prefix.contains(['{', '}']) => prefix_uris(prefix),
+        (None, Some(path)) if !path.contains(['{', '}']) => vec![path.clone()],
+        _ => return None,
+    };
+    let action = route.route.as_ref()?;
+
+    let targets: Vec<(&str, u32)> = if !action.cluster.is_empty() {
+        vec![(action.cluster.as_str(), 1)]
+    } else {
+        action
+            .weighted_clusters
+            .as_ref()?
+            .clusters
+            .iter()
+            .map(|c| (c.name.as_str(), c.weight.unwrap_or(0)))
+            .filter(|(_, weight)| *weight > 0)
+            .collect()
+    };
+    // Dropping a cluster would shift its share onto the others; wait until all resolve.
+    if let Some((missing, _)) = targets.iter().find(|(name, _)| !upstreams.contains(name)) {
+        log::warn!(
+            "Skipping xDS route '{}' until its cluster '{missing}' has usable endpoints",
+            route.name
+        );
+        return None;
+    }
+    let (primary, _) = targets.iter().max_by_key(|(_, weight)| *weight)?;
+
+    let mut value = json!({
+        "uris": uris,
+        "priority": priority,
+        "upstream_id": resource_id(primary),
+    });
+    if !hosts.is_empty() {
+        value["hosts"] = json!(hosts);
+    }
+    if targets.len() > 1 {
+        let weighted: Vec<JsonValue> = targets
+            .iter()
+            .map(|(name, weight)| json!({"upstream_id": resource_id(name), "weight": weight}))
+            .collect();
+        value["plugins"] = json!({
+            "traffic-split": {"rules": [{"weighted_upstreams": weighted}]}
+        });
+    }
+    Some(value)
+}
+
+/// Translate the accepted xDS resources into pingsix resource kvs under `prefix`.
+/// Clusters without endpoints and routes that cannot be expressed (regex or header
+/// matches, redirects, any missing cluster) are skipped.
+fn translate(state: &XdsState, prefix: &str) -> BTreeMap<String, Vec<u8>> {
+    let mut kvs = BTreeMap::new();
+    let mut upstreams = HashSet::new();
+
+    for (name, cluster) in &state.clusters {
+        let assignment = if cluster.r#type == proto::DISCOVERY_TYPE_EDS {
+            state.endpoints.get(eds_service_name(cluster))
+        } else {
+            cluster.load_assignment.as_ref()
+        };
+        let nodes = assignment.map(endpoint_nodes).unwrap_or_default();
+        if nodes.is_empty() {
+            log::debug!("Skipping xDS cluster '{name}' without usable endpoints");
+            continue;
+        }
+        let upstream = json!({"nodes": nodes, "type": selection_type(cluster.lb_policy)});
+        kvs.insert(
+            format!("{prefix}upstreams/{}", resource_id(name)),
+            upstream.to_string().into_bytes(),
+        );
+        upstreams.insert(name.as_str());
+    }
+
+    for (config_name, config) in &state.route_configs {
+        for vhost in &config.virtual_hosts {
+            let Some(hosts) = vhost_hosts(&vhost.domains) else {
+                log::debug!(
+                    "Skipping xDS virtual host '{}' without usable domains",
+                    vhost.name
+                );
+                continue;
+            };
+            let count = vhost.routes.len() as u32;
+            for (index, route) in vhost.routes.iter().enumerate() {
+                // Envoy picks the first matching route; keep that order among routes
+                // sharing a pattern.
+                let priority = count - index as u32;
+                let Some(value) = translate_route(route, &hosts, priority, &upstreams) else {
+                    log::debug!(
+                        "Skipping untranslatable xDS route '{}' in '{config_name}/{}'",
+                        route.name,
+                        vhost.name
+                    );
+                    continue;
+                };
+                let id = resource_id(&format!("{config_name}.{}.{index}", vhost.name));
+                kvs.insert(
+                    format!("{prefix}routes/{id}"),
+                    value.to_string().into_bytes(),
+                );
+            }
+        }
+    }
+    kvs
+}
+
+/// Changes turning `previous` into `current`.
+fn diff(
+    previous: &BTreeMap<String, Vec<u8>>,
+    current: &BTreeMap<String, Vec<u8>>,
+) -> Vec<ConfigChange> {
+    let mut changes: Vec<ConfigChange> = current
+        .iter()
+        .filter(|(key, value)| previous.get(*key) != Some(*value))
+        .map(|(key, value)| ConfigChange::Put {
+            key: key.clone(),
+            value: value.clone(),
+        })
+        .collect();
+    changes.extend(
+        previous
+            .keys()
+            .filter(|key| !current.contains_key(*key))
+            .map(|key| ConfigChange::Delete { key: key.clone() }),
+    );
+    changes
+}
+
+fn build_tls_config(tls: &EtcdTls) -> ProxyResult<ClientTlsConfig> {
+    let read = |path: &str| {
+        std::fs::read(path).map_err(|e| {
+            ProxyError::Configuration(format!("Failed to read xDS TLS file '{path}': {e}"))
+        })
+    };
+    let mut config =
+        ClientTlsConfig::new().ca_certificate(Certificate::from_pem(read(&tls.ca_cert)?));
+    if let (Some(cert), Some(key)) = (&tls.client_cert, &tls.client_key) {
+        config = config.identity(Identity::from_pem(read(cert)?, read(key)?));
+    }
+    if let Some(domain) = &tls.domain {
+        config = config.domain_name(domain);
+    }
+    Ok(config)
+}
+
+/// [`ConfigProvider`] subscribed to a control plane over one ADS stream.
+pub struct XdsProvider {
+    config: Xds,
+    requests: Option<mpsc::UnboundedSender<DiscoveryRequest>>,
+    responses: Option<Streaming<DiscoveryResponse>>,
+    state: XdsState,
+    /// Translated kvs last handed to the control plane.
+    emitted: BTreeMap<String, Vec<u8>>,
+    /// Local sequence standing in for a store revision.
+    revision: i64,
+}
+
+impl XdsProvider {
+    pub fn new(config: Xds) -> Self {
+        Self {
+            config,
+            requests: None,
+            responses: None,
+            state: XdsState::default(),
+            emitted: BTreeMap::new(),
+            revision: 0,
+        }
+    }
+
+    fn node(&self) -> proto::Node {
+        proto::Node {
+            id: self.config.node_id.clone(),
+            cluster: self.config.cluster.clone().unwrap_or_default(),
+            user_agent_name: "pingsix".to_string(),
+        }
+    }
+
+    fn subscription(&self, type_url: &str) -> Vec<String> {
+        match type_url {
+            ENDPOINT_TYPE => self.state.eds_names.iter().cloned().collect(),
+            ROUTE_TYPE => self.config.route_configs.clone(),
+            // CDS is a wildcard subscription.
+            _ => Vec::new(),
+        }
+    }
+
+    /// Send a request for `type_url` carrying the last accepted version and nonce: an
+    /// ACK, a subscription change, or a NACK when `error` is set.
+    fn send(&self, type_url: &'static str, error: Option<String>) -> ProxyResult<()> {
+        let request = DiscoveryRequest {
+            version_info: self
+                .state
+                .versions
+                .get(type_url)
+                .cloned()
+                .unwrap_or_default(),
+            node: Some(self.node()),
+            resource_names: self.subscription(type_url),
+            type_url: type_url.to_string(),
+            response_nonce: self.state.nonces.get(type_url).cloned().unwrap_or_default(),
+            error_detail: error.map(|message| proto::RpcStatus {
+                // google.rpc.Code.INVALID_ARGUMENT
+                code: 3,
+                message,
+            }),
+        };
+        self.requests
+            .as_ref()
+            .ok_or_else(|| xds_error("ADS stream is not connected"))?
+            .send(request)
+            .map_err(|_| xds_error("ADS request stream closed"))
+    }
+
+    async fn connect(&mut self) -> ProxyResult<()> {
+        let timeout_secs = self.config.timeout.unwrap_or(DEFAULT_TIMEOUT_SECS) as u64;
+        let mut endpoint = Endpoint::from_shared(self.config.address.clone())
+            .map_err(|e| {
+                ProxyError::Configuration(format!(
+                    "Invalid xDS address '{}': {e}",
+                    self.config.address
+                ))
+            })?
+            .connect_timeout(Duration::from_secs(timeout_secs))
+            .http2_keep_alive_interval(KEEPALIVE_INTERVAL)
+            .keep_alive_timeout(Duration::from_secs(timeout_secs));
+        if let Some(tls) = &self.config.tls {
+            endpoint = endpoint
+                .tls_config(build_tls_config(tls)?)
+                .map_err(|e| ProxyError::Configuration(format!("Invalid xDS TLS config: {e}")))?;
+        }
+
+        log::debug!("Connecting to xDS server at '{}'", self.config.address);
+        let channel = endpoint.connect().await.map_err(|e| {
+            xds_error(format!(
+                "failed to connect to xDS server '{}': {e}",
+                self.config.address
+            ))
+        })?;
+        let mut grpc = Grpc::new(channel);
+        grpc.ready()
+            .await
+            .map_err(|e| xds_error(format!("xDS channel not ready: {e}")))?;
+
+        let (tx, rx) = mpsc::unbounded_channel();
+        self.requests = Some(tx);
+        // Queue the initial subscriptions; EDS follows once clusters are known.
+        self.send(CLUSTER_TYPE, None)?;
+        if !self.config.route_configs.is_empty() {
+            self.send(ROUTE_TYPE, None)?;
+        }
+
+        let outbound = futures::stream::unfold(rx, |mut rx| async move {
+            rx.recv().await.map(|request| (request, rx))
+        });
+        let response = grpc
+            .streaming(
+                tonic::Request::new(outbound),
+                PathAndQuery::from_static(ADS_PATH),
+                ProstCodec::<DiscoveryRequest, DiscoveryResponse>::default(),
+            )
+            .await
+            .map_err(|status| xds_error(format!("failed to open ADS stream: {status}")))?;
+        self.responses = Some(response.into_inner());
+        Ok(())
+    }
+
+    /// Next response, or `None` once the server ends the stream.
+    async fn next_response(&mut self) -> ProxyResult<Option<DiscoveryResponse>> {
+        self.responses
+            .as_mut()
+            .ok_or_else(|| xds_error("ADS stream is not connected"))?
+            .message()
+            .await
+            .map_err(|status| xds_error(format!("ADS stream failed: {status}")))
+    }
+
+    /// ACK or NACK one response. Returns whether the accepted resources changed.
+    fn handle_response(&mut self, response: DiscoveryResponse) -> ProxyResult<bool> {
+        let Some(type_url) = known_type(&response.type_url) else {
+            log::warn!("Ignoring unsubscribed xDS type '{}'", response.type_url);
+            return Ok(false);
+        };
+        self.state.nonces.insert(type_url, response.nonce);
+
+        if let Err(err) =
+            self.state
+                .apply(type_url, &response.resources, &self.config.route_configs)
+        {
+            log::warn!(
+                "Rejecting xDS response for '{type_url}' version '{}': {err}",
+                response.version_info
+            );
+            self.send(type_url, Some(err))?;
+            return Ok(false);
+        }
+
+        self.state.versions.insert(type_url, response.version_info);
+        self.state.received.insert(type_url);
+        self.send(type_url, None)?;
+        if type_url == CLUSTER_TYPE && self.state.refresh_eds_names() {
+            self.send(ENDPOINT_TYPE, None)?;
+        }
+        Ok(true)
+    }
+}
+
+#[async_trait]
+impl ConfigProvider for XdsProvider {
+    fn source(&self) -> status::ConfigSource {
+        status::ConfigSource::Xds
+    }
+
+    fn prefix(&self) -> &str {
+        XDS_PREFIX
+    }
+
+    async fn list(&mut self) -> ProxyResult<ConfigListing> {
+        // Every listing starts a fresh stream; the server resends the full state.
+        self.reset();
+        self.connect().await?;
+
+        let route_configs = self.config.route_configs.clone();
+        timeout(INITIAL_FETCH_TIMEOUT, async {
+            while !self.state.is_synced(&route_configs) {
+                let response = self
+                    .next_response()
+                    .await?
+                    .ok_or_else(|| xds_error("ADS stream closed during initial fetch"))?;
+                self.handle_response(response)?;
+            }
+            Ok::<_, ProxyError>(())
+        })
+        .await
+        .map_err(|_| xds_error("timed out waiting for the initial xDS resources"))??;
+
+        self.emitted = translate(&self.state, XDS_PREFIX);
+        self.revision += 1;
+        Ok(ConfigListing {
+            revision: self.revision,
+            kvs: self
+                .emitted
+                .iter()
+                .map(|(key, value)| (key.clone(), value.clone()))
+                .collect(),
+        })
+    }
+
+    async fn watch(&mut self, handler: &(dyn ConfigEventHandler + Send + Sync)) -> ProxyResult<()> {
+        status::mark_connected(true);
+        while let Some(response) = self.next_response().await? {
+            if !self.handle_response(response)? {
+                continue;
+            }
+            let current = translate(&self.state, XDS_PREFIX);
+            let changes = diff(&self.emitted, &current);
+            self.emitted = current;
+            if !changes.is_empty() {
+                self.revision += 1;
+            }
+            // An empty batch still reports progress.
+            handler.handle_changes(&changes, self.revision).await?;
+            status::record_sync_success(self.revision);
+        }
+        Ok(())
+    }
+
+    fn reset(&mut self) {
+        self.requests = None;
+        self.responses = None;
+        self.state = XdsState::default();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::proto::*;
+    use super::*;
+
+    fn lb_endpoint(address: &str, port: u32, weight: Option<u32>, health: i32) -> LbEndpoint {
+        LbEndpoint {
+            endpoint: Some(proto::Endpoint {
+                address: Some(Address {
+                    socket_address: Some(SocketAddress {
+                        address: address.to_string(),
+                        port_value: port,
+                    }),
+                }),
+            }),
+            health_status: health,
+            load_balancing_weight: weight,
+        }
+    }
+
+    fn assignment(name: &str, endpoints: Vec<(u32, Vec<LbEndpoint>)>) -> ClusterLoadAssignment {
+        ClusterLoadAssignment {
+            cluster_name: name.to_string(),
+            endpoints: endpoints
+                .into_iter()
+                .map(|(priority, lb_endpoints)| LocalityLbEndpoints {
+                    lb_endpoints,
+                    priority,
+                })
+                .collect(),
+        }
+    }
+
+    fn any<M: Message>(type_url: &str, message: &M) -> prost_types::Any {
+        prost_types::Any {
+            type_url: type_url.to_string(),
+            value: message.encode_to_vec(),
+        }
+    }
+
+    fn json_at(kvs: &BTreeMap<String, Vec<u8>>, key: &str) -> JsonValue {
+        serde_json::from_slice(&kvs[key]).unwrap()
+    }
+
+    #[test]
+    fn translates_clusters_endpoints_and_routes() {
+        let mut state = XdsState::default();
+        let clusters = vec![
+            Cluster {
+                name: "outbound|80||reviews".to_string(),
+                r#type: DISCOVERY_TYPE_EDS,
+                lb_policy: LB_POLICY_RANDOM,
+                ..Default::default()
+            },
+            Cluster {
+                name: "static".to_string(),
+                load_assignment: Some(assignment(
+                    "static",
+                    vec![(0, vec![lb_endpoint("10.0.0.9", 8080, None, 0)])],
+                )),
+                ..Default::default()
+            },
+            Cluster {
+                name: "empty".to_string(),
+                ..Default::default()
+            },
+        ];
+        let resources: Vec<_> = clusters.iter().map(|c| any(CLUSTER_TYPE, c)).collect();
+        state.apply(CLUSTER_TYPE, &resources, &[]).unwrap();
+        assert!(state.refresh_eds_names());
+        assert!(!state.is_synced(&[]));
+
+        let endpoints = assignment(
+            "outbound|80||reviews",
+            vec![
+                (
+                    0,
+                    vec![
+                        lb_endpoint("10.0.0.1", 9080, Some(3), 0),
+                        lb_endpoint("10.0.0.2", 9080, None, HEALTH_STATUS_DRAINING),
+                        lb_endpoint("fd00::1", 9080, None, 0),
+                    ],
+                ),
+                (1, vec![lb_endpoint("10.0.1.1", 9080, None, 0)]),
+            ],
+        );
+        state
+            .apply(ENDPOINT_TYPE, &[any(ENDPOINT_TYPE, &endpoints)], &[])
+            .unwrap();
+        state.received.extend([CLUSTER_TYPE, ENDPOINT_TYPE]);
+        assert!(state.is_synced(&[]));
+
+        let route = |r#match: RouteMatch, action: RouteAction| proto::Route {
+            r#match: Some(r#match),
+            route: Some(action),
+            name: String::new(),
+        };
+        let config = RouteConfiguration {
+            name: "http.80".to_string(),
+            virtual_hosts: vec![VirtualHost {
+                name: "reviews".to_string(),
+                domains: vec!["reviews.local:80".to_string(), "reviews.*".to_string()],
+                routes: vec![
+                    route(
+                        RouteMatch {
+                            path: Some("/health".to_string()),
+                            ..Default::default()
+                        },
+                        RouteAction {
+                            cluster: "static".to_string(),
+                            ..Default::default()
+                        },
+                    ),
+                    route(
+                        RouteMatch {
+                            prefix: Some("/".to_string()),
+                            ..Default::default()
+                        },
+                        RouteAction {
+                            weighted_clusters: Some(WeightedCluster {
+                                clusters: vec![
+                                    ClusterWeight {
+                                        name: "outbound|80||reviews".to_string(),
+                                        weight: Some(90),
+                                    },
+                                    ClusterWeight {
+                                        name: "static".to_string(),
+                                        weight: Some(10),
+                                    },
+                                ],
+                            }),
+                            ..Default::default()
+                        },
+                    ),
+                    route(
+                        RouteMatch {
+                            prefix: Some("/empty".to_string()),
+                            ..Default::default()
+                        },
+                        RouteAction {
+                            cluster: "empty".to_string(),
+                            ..Default::default()
+                        },
+                    ),
+                    route(
+                        RouteMatch {
+                            prefix: Some("/partial".to_string()),
+                            ..Default::default()
+                        },
+                        RouteAction {
+                            weighted_clusters: Some(WeightedCluster {
+                                clusters: vec![
+                                    ClusterWeight {
+                                        name: "static".to_string(),
+                                        weight: Some(50),
+                                    },
+                                    ClusterWeight {
+                                        name: "empty".to_string(),
+                                        weight: Some(50),
+                                    },
+                                ],
+                            }),
+                            ..Default::default()
+                        },
+                    ),
+                ],
+            }],
+        };
+        let routes = ["http.80".to_string()];
+        state
+            .apply(ROUTE_TYPE, &[any(ROUTE_TYPE, &config)], &routes)
+            .unwrap();
+
+        let kvs = translate(&state, XDS_PREFIX);
+        assert_eq!(
+            kvs.keys().collect::<Vec<_>>(),
+            vec![
+                "/xds/routes/http.80.reviews.0",
+                "/xds/routes/http.80.reviews.1",
+                "/xds/upstreams/outbound_80__reviews",
+                "/xds/upstreams/static",
+            ]
+        );
+        assert_eq!(
+            json_at(&kvs, "/xds/upstreams/outbound_80__reviews"),
+            json!({"nodes": {"10.0.0.1:9080": 3, "[fd00::1]:9080": 1}, "type": "random"})
+        );
+        assert_eq!(
+            json_at(&kvs, "/xds/routes/http.80.reviews.0"),
+            json!({"uris": ["/health"], "priority": 4, "upstream_id": "static", "hosts": ["reviews.local"]})
+        );
+        let split = json_at(&kvs, "/xds/routes/http.80.reviews.1");
+        assert_eq!(split["uris"], json!(["/{*xds_path}", "/"]));
+        assert_eq!(split["upstream_id"], "outbound_80__reviews");
+        assert_eq!(
+            split["plugins"]["traffic-split"]["rules"][0]["weighted_upstreams"],
+            json!([
+                {"upstream_id": "outbound_80__reviews", "weight": 90},
+                {"upstream_id": "static", "weight": 10},
+            ])
+        );
+
+        // Dropping the EDS cluster prunes its endpoints and subscription.
+        state
+            .apply(CLUSTER_TYPE, &[any(CLUSTER_TYPE, &clusters[1])], &[])
+            .unwrap();
+        assert!(state.refresh_eds_names());
+        assert!(state.endpoints.is_empty());
+    }
+
+    #[test]
+    fn diff_emits_puts_and_deletes_and_bad_resources_are_rejected() {
+        let previous = BTreeMap::from([
+            ("/xds/upstreams/a".to_string(), b"1".to_vec()),
+            ("/xds/upstreams/b".to_string(), b"1".to_vec()),
+        ]);
+        let current = BTreeMap::from([
+            ("/xds/upstreams/a".to_string(), b"2".to_vec()),
+            ("/xds/upstreams/b".to_string(), b"1".to_vec()),
+            ("/xds/upstreams/c".to_string(), b"1".to_vec()),
+        ]);
+        let changes = diff(&previous, &current);
+        assert_eq!(changes.len(), 2);
+        assert_eq!(changes[0].key(), "/xds/upstreams/a");
+        assert_eq!(changes[1].key(), "/xds/upstreams/c");
+        let changes = diff(&current, &previous);
+        assert_eq!(
+            changes.last(),
+            Some(&ConfigChange::Delete {
+                key: "/xds/upstreams/c".to_string()
+            })
+        );
+
+        let mut state = XdsState::default();
+        let garbage = prost_types::Any {
+            type_url: CLUSTER_TYPE.to_string(),
+            value: vec![0xff, 0xff],
+        };
+        assert!(state.apply(CLUSTER_TYPE, &[garbage], &[]).is_err());
+        assert_eq!(
+            prefix_uris("/api/"),
+            vec!["/api/{*xds_path}", "/api", "/api/"]
+        );
+        assert_eq!(vhost_hosts(&["*".to_string()]), Some(Vec::new()));
+        assert_eq!(vhost_hosts(&["example.*".to_string()]), None);
+    }
+}
@@ -0,0 +1,304 @@
+//! LRU eviction for the response cache with a size limit that can change at runtime,
+//! so the memory tuner can shrink and regrow the cache.
+
+use std::{
+    collections::{BTreeMap, HashMap},
+    hash::{DefaultHasher, Hash, Hasher},
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Mutex,
+    },
+    time::SystemTime,
+};
+
+use async_trait::async_trait;
+use pingora_cache::{eviction::EvictionManager, key::CompactCacheKey};
+use pingora_error::Result;
+
+/// What a cached item was stored for, so purges can find it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ItemTag {
+    pub route_id: String,
+    /// Request `Host` header followed by the path and query.
+    pub key: String,
+}
+
+struct Node {
+    key: CompactCacheKey,
+    size: usize,
+    /// Position in [`Lru::order`]; higher is more recently used.
+    tick: u64,
+    tag: Option<ItemTag>,
+}
+
+#[derive(Default)]
+struct Lru {
+    nodes: HashMap<u64, Node>,
+    order: BTreeMap<u64, u64>,
+    next_tick: u64,
+}
+
+impl Lru {
+    fn touch(&mut self, hash: u64) -> Option<&mut Node> {
+        let tick = self.next_tick;
+        let node = self.nodes.get_mut(&hash)?;
+        self.order.remove(&node.tick);
+        node.tick = tick;
+        self.order.insert(tick, hash);
+        self.next_tick += 1;
+        Some(node)
+    }
+
+    fn pop_oldest(&mut self) -> Option<Node> {
+        let (_, hash) = self.order.pop_first()?;
+        self.nodes.remove(&hash)
+    }
+}
+
+/// Least-recently-used eviction within `limit` bytes.
+pub struct AdaptiveLru {
+    lru: Mutex<Lru>,
+    limit: AtomicUsize,
+    used: AtomicUsize,
+    items: AtomicUsize,
+    evicted_size: AtomicUsize,
+    evicted_items: AtomicUsize,
+}
+
+impl AdaptiveLru {
+    pub fn new(limit: usize) -> Self {
+        Self {
+            lru: Mutex::new(Lru::default()),
+            limit: AtomicUsize::new(limit),
+            used: AtomicUsize::new(0),
+            items: AtomicUsize::new(0),
+            evicted_size: AtomicUsize::new(0),
+            evicted_items: AtomicUsize::new(0),
+        }
+    }
+
+    pub fn limit(&self) -> usize {
+        self.limit.load(Ordering::Relaxed)
+    }
+
+    /// Change the limit and return the items evicted to fit it; the caller removes
+    /// them from storage.
+    pub fn set_limit(&self, limit: usize) -> Vec<CompactCacheKey> {
+        self.limit.store(limit, Ordering::Relaxed);
+        self.evict()
+    }
+
+    fn hash(key: &CompactCacheKey) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        key.hash(&mut hasher);
+        hasher.finish()
+    }
+
+    fn insert(&self, key: CompactCacheKey, size: usize) {
+        let hash = Self::hash(&key);
+        let mut lru = self.lru.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(node) = lru.touch(hash) {
+            let old = std::mem::replace(&mut node.size, size);
+            self.used.fetch_add(size, Ordering::Relaxed);
+            self.used.fetch_sub(old, Ordering::Relaxed);
+            return;
+        }
+        let tick = lru.next_tick;
+        lru.next_tick += 1;
+        lru.order.insert(tick, hash);
+        lru.nodes.insert(
+            hash,
+            Node {
+                key,
+                size,
+                tick,
+                tag: None,
+            },
+        );
+        self.used.fetch_add(size, Ordering::Relaxed);
+        self.items.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Attach `tag` to a cached item; a no-op once the item is gone.
+    pub fn tag(&self, item: &CompactCacheKey, tag: ItemTag) {
+        let hash = Self::hash(item);
+        let mut lru = self.lru.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(node) = lru.nodes.get_mut(&hash) {
+            node.tag = Some(tag);
+        }
+    }
+
+    /// Remove the items whose tag satisfies `covers` and return them; the caller removes
+    /// them from storage.
+    pub fn take_matching(&self, covers: impl Fn(&ItemTag) -> bool) -> Vec<CompactCacheKey> {
+        let mut lru = self.lru.lock().unwrap_or_else(|e| e.into_inner());
+        let hashes: Vec<u64> = lru
+            .nodes
+            .iter()
+            .filter(|(_, node)| node.tag.as_ref().is_some_and(&covers))
+            .map(|(hash, _)| *hash)
+            .collect();
+        let mut taken = Vec::with_capacity(hashes.len());
+        for hash in hashes {
+            if let Some(node) = lru.nodes.remove(&hash) {
+                lru.order.remove(&node.tick);
+                self.used.fetch_sub(node.size, Ordering::Relaxed);
+                self.items.fetch_sub(1, Ordering::Relaxed);
+                taken.push(node.key);
+            }
+        }
+        taken
+    }
+
+    fn evict(&self) -> Vec<CompactCacheKey> {
+        let limit = self.limit();
+        if self.used.load(Ordering::Relaxed) <= limit {
+            return Vec::new();
+        }
+        let mut lru = self.lru.lock().unwrap_or_else(|e| e.into_inner());
+        let mut evicted = Vec::new();
+        while self.used.load(Ordering::Relaxed) > limit {
+            let Some(node) = lru.pop_oldest() else {
+                break;
+            };
+            self.used.fetch_sub(node.size, Ordering::Relaxed);
+            self.items.fetch_sub(1, Ordering::Relaxed);
+            self.evicted_size.fetch_add(node.size, Ordering::Relaxed);
+            self.evicted_items.fetch_add(1, Ordering::Relaxed);
+            evicted.push(node.key);
+        }
+        evicted
+    }
+}
+
+#[async_trait]
+impl EvictionManager for AdaptiveLru {
+    fn total_size(&self) -> usize {
+        self.used.load(Ordering::Relaxed)
+    }
+
+    fn total_items(&self) -> usize {
+        self.items.load(Ordering::Relaxed)
+    }
+
+    fn evicted_size(&self) -> usize {
+        self.evicted_size.load(Ordering::Relaxed)
+    }
+
+    fn evicted_items(&self) -> usize {
+        self.evicted_items.load(Ordering::Relaxed)
+    }
+
+    fn admit(
+        &self,
+        item: CompactCacheKey,
+        size: usize,
+        _fresh_until: SystemTime,
+    ) -> Vec<CompactCacheKey> {
+        self.insert(item, size);
+        self.evict()
+    }
+
+    fn increment_weight(
+        &self,
+        item: &CompactCacheKey,
+        delta: usize,
+        _max_weight: Option<usize>,
+    ) -> Vec<CompactCacheKey> {
+        let hash = Self::hash(item);
+        let mut lru = self.lru.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(node) = lru.nodes.get_mut(&hash) {
+            node.size += delta;
+            self.used.fetch_add(delta, Ordering::Relaxed);
+        }
+        drop(lru);
+        self.evict()
+    }
+
+    fn remove(&self, item: &CompactCacheKey) {
+        let hash = Self::hash(item);
+        let mut lru = self.lru.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(node) = lru.nodes.remove(&hash) {
+            lru.order.remove(&node.tick);
+            self.used.fetch_sub(node.size, Ordering::Relaxed);
+            self.items.fetch_sub(1, Ordering::Relaxed);
+        }
+    }
+
+    fn access(&self, item: &CompactCacheKey, size: usize, _fresh_until: SystemTime) -> bool {
+        let hash = Self::hash(item);
+        let mut lru = self.lru.lock().unwrap_or_else(|e| e.into_inner());
+        if lru.touch(hash).is_some() {
+            return true;
+        }
+        drop(lru);
+        self.insert(item.clone(), size);
+        false
+    }
+
+    fn peek(&self, item: &CompactCacheKey) -> bool {
+        let hash = Self::hash(item);
+        let lru = self.lru.lock().unwrap_or_else(|e| e.into_inner());
+        lru.nodes.contains_key(&hash)
+    }
+
+    // The cache lives in memory, so there is nothing to persist across restarts.
+    async fn save(&self, _dir_path: &str) -> Result<()> {
+        Ok(())
+    }
+
+    async fn load(&self, _dir_path: &str) -> Result<()> {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use pingora_cache::CacheKey;
+
+    use super::*;
+
+    fn key(name: &str) -> CompactCacheKey {
+        CacheKey::new("", name, "").to_compact()
+    }
+
+    #[test]
+    fn evicts_least_recently_used_and_follows_the_limit() {
+        let lru = AdaptiveLru::new(30);
+        let now = SystemTime::now();
+        assert!(lru.admit(key("a"), 10, now).is_empty());
+        assert!(lru.admit(key("b"), 10, now).is_empty());
+        assert!(lru.admit(key("c"), 10, now).is_empty());
+        assert!(lru.access(&key("a"), 10, now));
+
+        assert_eq!(lru.admit(key("d"), 10, now), vec![key("b")]);
+        assert_eq!(lru.set_limit(15), vec![key("c"), key("a")]);
+        assert_eq!((lru.total_size(), lru.total_items()), (10, 1));
+        assert!(lru.peek(&key("d")));
+
+        assert!(lru.set_limit(30).is_empty());
+        assert!(lru.admit(key("e"), 10, now).is_empty());
+        assert_eq!(lru.evicted_items(), 3);
+    }
+
+    #[test]
+    fn takes_items_by_tag() {
+        let lru = AdaptiveLru::new(100);
+        let now = SystemTime::now();
+        let tag = |route_id: &str, key: &str| ItemTag {
+            route_id: route_id.into(),
+            key: key.into(),
+        };
+        lru.admit(key("a"), 10, now);
+        lru.admit(key("b"), 10, now);
+        lru.admit(key("c"), 10, now);
+        lru.tag(&key("a"), tag("r1", "example.com/a"));
+        lru.tag(&key("b"), tag("r2", "example.com/b"));
+        lru.tag(&key("gone"), tag("r1", "example.com/gone"));
+
+        assert_eq!(lru.take_matching(|t| t.route_id == "r1"), vec![key("a")]);
+        assert_eq!((lru.total_size(), lru.total_items()), (20, 2));
+        assert!(lru.take_matching(|t| t.route_id == "r1").is_empty());
+        assert!(lru.peek(&key("b")) && lru.peek(&key("c")));
+    }
+}
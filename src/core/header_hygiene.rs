@@ -0,0 +1,201 @@
+//! Request and response header hygiene.
+//!
+//! Configured by `pingsix.security`: hop-by-hop headers are removed before requests
+//! and responses are forwarded, `Server` and `X-Powered-By` can be hidden, and internal
+//! headers sent by clients outside the trusted networks are dropped before any plugin
+//! sees the request.
+
+use http::{header, HeaderMap, HeaderName};
+use once_cell::sync::OnceCell;
+use pingora_http::{RequestHeader, ResponseHeader};
+use pingora_proxy::Session;
+
+use crate::config::Security;
+use crate::utils::{
+    ip_set::{parse_ip_set, IpSet},
+    request::{get_direct_client_ip, GATEWAY_CLIENT_HEADER},
+};
+
+/// Hop-by-hop headers of RFC 9110 besides `Connection` itself, which Pingora manages
+/// together with `Transfer-Encoding`, `Upgrade` and `TE: trailers`.
+const HOP_BY_HOP: [&str; 4] = [
+    "keep-alive",
+    "proxy-connection",
+    "proxy-authenticate",
+    "proxy-authorization",
+];
+
+/// Headers kept even when `Connection` nominates them, since message framing and
+/// protocol upgrades depend on them.
+const FRAMING: [&str; 5] = [
+    "host",
+    "content-length",
+    "transfer-encoding",
+    "upgrade",
+    "te",
+];
+
+static HYGIENE: OnceCell<Hygiene> = OnceCell::new();
+
+struct Hygiene {
+    strip_hop_by_hop: bool,
+    hide_server_headers: bool,
+    /// Lowercase internal header names, with whether each is a prefix.
+    internal_headers: Vec<(String, bool)>,
+    trusted: IpSet,
+}
+
+impl Hygiene {
+    fn new(config: Security) -> Self {
+        Self {
+            strip_hop_by_hop: config.strip_hop_by_hop,
+            hide_server_headers: config.hide_server_headers,
+            internal_headers: config
+                .internal_headers
+                .iter()
+                .map(|name| match name.strip_suffix('*') {
+                    Some(prefix) => (prefix.to_ascii_lowercase(), true),
+                    None => (name.to_ascii_lowercase(), false),
+                })
+                .collect(),
+            // Checked by the config validation.
+            trusted: parse_ip_set("trusted", &config.trusted_addresses).unwrap_or_default(),
+        }
+    }
+
+    fn is_internal(&self, name: &HeaderName) -> bool {
+        self.internal_headers.iter().any(|(internal, prefix)| {
+            if *prefix {
+                name.as_str().starts_with(internal.as_str())
+            } else {
+                name.as_str() == internal
+            }
+        })
+    }
+}
+
+/// Enable header hygiene. Called once at startup; later calls are no-ops.
+pub fn init(config: Option<Security>) {
+    if let Some(config) = config {
+        let _ = HYGIENE.set(Hygiene::new(config));
+    }
+}
+
+/// Hop-by-hop headers of `headers`, including those nominated by `Connection`.
+fn hop_by_hop_headers(headers: &HeaderMap) -> Vec<HeaderName> {
+    let nominated = headers
+        .get_all(header::CONNECTION)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .filter_map(|token| HeaderName::from_bytes(token.trim().as_bytes()).ok())
+        .filter(|name| !FRAMING.contains(&name.as_str()));
+    let mut names: Vec<HeaderName> = HOP_BY_HOP
+        .iter()
+        .map(|name| HeaderName::from_static(name))
+        .chain(nominated)
+        .filter(|name| headers.contains_key(name))
+        .collect();
+    let te_trailers_only = headers
+        .get(header::TE)
+        .and_then(|value| value.to_str().ok())
+        .is_none_or(|value| value.trim().eq_ignore_ascii_case("trailers"));
+    if !te_trailers_only {
+        names.push(header::TE);
+    }
+    names
+}
+
+/// Whether `name` is one of the configured internal headers.
+pub fn is_internal_header(name: &HeaderName) -> bool {
+    HYGIENE.get().is_some_and(|h| h.is_internal(name))
+}
+
+/// Drop internal headers sent by a client outside the trusted networks.
+pub fn sanitize_client_request(session: &mut Session) {
+    let Some(hygiene) = HYGIENE.get().filter(|h| !h.internal_headers.is_empty()) else {
+        return;
+    };
+    if get_direct_client_ip(session).is_some_and(|ip| hygiene.trusted.contains(ip)) {
+        return;
+    }
+    let spoofed: Vec<HeaderName> = session
+        .req_header()
+        .headers
+        .keys()
+        .filter(|name| hygiene.is_internal(name))
+        .cloned()
+        .collect();
+    for name in spoofed {
+        log::debug!("Dropping internal header {name} sent by an untrusted client");
+        session.req_header_mut().remove_header(&name);
+    }
+}
+
+/// Remove hop-by-hop headers and the gateway's own client address header from a request
+/// about to be sent upstream.
+pub fn sanitize_upstream_request(req: &mut RequestHeader) {
+    req.remove_header(GATEWAY_CLIENT_HEADER);
+    if !HYGIENE.get().is_some_and(|h| h.strip_hop_by_hop) {
+        return;
+    }
+    for name in hop_by_hop_headers(&req.headers) {
+        req.remove_header(&name);
+    }
+}
+
+/// Remove hop-by-hop and, when configured, server identification headers from an
+/// upstream response.
+pub fn sanitize_response(resp: &mut ResponseHeader) {
+    let Some(hygiene) = HYGIENE.get() else {
+        return;
+    };
+    if hygiene.strip_hop_by_hop {
+        for name in hop_by_hop_headers(&resp.headers) {
+            resp.remove_header(&name);
+        }
+    }
+    if hygiene.hide_server_headers {
+        resp.remove_header(&header::SERVER);
+        resp.remove_header("x-powered-by");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn hop_by_hop_headers_keep_framing() {
+        let mut req = RequestHeader::build("GET", b"/", None).unwrap();
+        req.insert_header("Connection", "Upgrade, X-Trace, TE")
+            .unwrap();
+        req.insert_header("Upgrade", "websocket").unwrap();
+        req.insert_header("X-Trace", "1").unwrap();
+        req.insert_header("Keep-Alive", "timeout=5").unwrap();
+        req.insert_header("TE", "trailers").unwrap();
+        let names = hop_by_hop_headers(&req.headers);
+        assert_eq!(
+            names,
+            vec![
+                HeaderName::from_static("keep-alive"),
+                HeaderName::from_static("x-trace")
+            ]
+        );
+
+        req.insert_header("TE", "gzip").unwrap();
+        assert!(hop_by_hop_headers(&req.headers).contains(&header::TE));
+    }
+
+    #[test]
+    fn internal_headers_match_names_and_prefixes() {
+        let hygiene = Hygiene::new(Security {
+            internal_headers: vec!["X-Consumer-*".into(), "X-Real-IP".into()],
+            ..Default::default()
+        });
+        assert!(hygiene.is_internal(&HeaderName::from_static("x-consumer-username")));
+        assert!(hygiene.is_internal(&HeaderName::from_static("x-real-ip")));
+        assert!(!hygiene.is_internal(&HeaderName::from_static("x-real-ip-hint")));
+        assert!(!hygiene.is_internal(&HeaderName::from_static("x-consumer")));
+    }
+}
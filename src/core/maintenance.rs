@@ -0,0 +1,130 @@
+//! Maintenance mode for the whole gateway or selected routes.
+//!
+//! The switch comes from `pingsix.maintenance` at startup and from the
+//! [`MAINTENANCE_KEY`] entry of the config store at runtime; a stored switch replaces
+//! the static one until it is deleted.
+
+use std::sync::Arc;
+
+use arc_swap::ArcSwapOption;
+use once_cell::sync::{Lazy, OnceCell};
+use prometheus::{register_int_counter, IntCounter};
+
+use crate::config::Maintenance;
+
+/// Config store key (relative to the prefix) holding the runtime switch. The leading
+/// dot keeps it out of the resource graph.
+pub const MAINTENANCE_KEY: &str = ".maintenance";
+
+static MAINTENANCE_RESPONSES: Lazy<IntCounter> = Lazy::new(|| {
+    register_int_counter!(
+        "pingsix_maintenance_responses_total",
+        "Requests answered with the maintenance response"
+    )
+    .expect("maintenance metric registration must succeed")
+});
+
+static STATIC_SWITCH: OnceCell<Arc<Maintenance>> = OnceCell::new();
+static STORED_SWITCH: Lazy<ArcSwapOption<Maintenance>> = Lazy::new(ArcSwapOption::empty);
+
+/// Install the `pingsix.maintenance` switch. Called once at startup; later calls are
+/// no-ops.
+pub fn init(config: Option<Maintenance>) {
+    if let Some(config) = config {
+        let _ = STATIC_SWITCH.set(Arc::new(config));
+    }
+}
+
+/// Replace the switch read from the config store; `None` falls back to the static one.
+pub fn set_stored(config: Option<Maintenance>) {
+    match &config {
+        Some(m) if m.enabled => log::warn!(
+            "Maintenance mode enabled for {}",
+            if m.routes.is_empty() {
+                "the whole gateway".to_string()
+            } else {
+                format!("routes {:?}", m.routes)
+            }
+        ),
+        Some(_) => log::info!("Maintenance mode disabled"),
+        None => log::info!("Maintenance switch removed from the config store"),
+    }
+    STORED_SWITCH.store(config.map(Arc::new));
+}
+
+/// The switch in effect: the stored one if present, else the static one.
+pub fn current() -> Option<Arc<Maintenance>> {
+    STORED_SWITCH
+        .load_full()
+        .or_else(|| STATIC_SWITCH.get().cloned())
+}
+
+/// The maintenance settings to answer a request for `route_id` with, if any.
+/// Unmatched requests (`None`) are only affected by gateway-wide maintenance.
+pub fn check(route_id: Option<&str>) -> Option<Arc<Maintenance>> {
+    let maintenance = current()?;
+    let applies = maintenance.enabled
+        && (maintenance.routes.is_empty()
+            || route_id.is_some_and(|id| maintenance.routes.iter().any(|r| r == id)));
+    if applies {
+        MAINTENANCE_RESPONSES.inc();
+        Some(maintenance)
+    } else {
+        None
+    }
+}
+
+/// Expands the body template for one request.
+pub fn render_body(maintenance: &Maintenance, route_id: Option<&str>, host: &str) -> String {
+    let retry_after = maintenance
+        .retry_after
+        .map(|secs| secs.to_string())
+        .unwrap_or_default();
+    maintenance
+        .body
+        .replace("$route_id", route_id.unwrap_or(""))
+        .replace("$host", host)
+        .replace("$retry_after", &retry_after)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn maintenance(routes: &[&str]) -> Maintenance {
+        serde_json::from_value(serde_json::json!({
+            "routes": routes,
+            "retry_after": 120,
+            "body": "$host/$route_id back in $retry_after s"
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn stored_switch_scopes_routes_and_overrides_static() {
+        set_stored(Some(maintenance(&["orders"])));
+        assert!(check(Some("orders")).is_some());
+        assert!(check(Some("users")).is_none());
+        assert!(check(None).is_none());
+
+        set_stored(Some(maintenance(&[])));
+        assert!(check(None).is_some());
+
+        let mut disabled = maintenance(&[]);
+        disabled.enabled = false;
+        set_stored(Some(disabled));
+        assert!(check(Some("orders")).is_none());
+
+        set_stored(None);
+        assert!(check(Some("orders")).is_none());
+    }
+
+    #[test]
+    fn body_template_is_expanded() {
+        let m = maintenance(&[]);
+        assert_eq!(
+            render_body(&m, Some("orders"), "shop.example.com"),
+            "shop.example.com/orders back in 120 s"
+        );
+    }
+}
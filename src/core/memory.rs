@@ -0,0 +1,237 @@
+//! Soft memory limit shared by the gateway's caches.
+//!
+//! With `pingsix.memory_limit` set, the response cache, the DNS cache, rate-limit state
+//! and shared plugin dictionaries report their (estimated) usage here. A background
+//! tuner checks the total every second: when the others hold more than half of the
+//! limit they are shrunk proportionally, and the response cache gets whatever the
+//! limit leaves, up to `pingsix.defaults.cache.max_memory_bytes`. Usage is exported as
+//! Prometheus gauges.
+
+use std::{
+    sync::{Arc, Mutex, Weak},
+    time::Duration,
+};
+
+use async_trait::async_trait;
+use once_cell::sync::{Lazy, OnceCell};
+use pingora_core::{
+    server::ShutdownWatch,
+    services::background::{background_service, BackgroundService, GenBackgroundService},
+};
+use prometheus::{
+    register_int_counter_vec, register_int_gauge, register_int_gauge_vec, IntCounterVec, IntGauge,
+    IntGaugeVec,
+};
+
+/// Consumer kind of the response cache.
+pub const CACHE: &str = "cache";
+/// Consumer kind of the DNS answer cache.
+pub const DNS: &str = "dns";
+/// Consumer kind of per-key rate-limit state.
+pub const RATE_LIMIT: &str = "rate_limit";
+/// Consumer kind of plugin state kept in shared dictionaries.
+pub const PLUGIN_STATE: &str = "plugin_state";
+
+/// Approximate bytes of a map entry besides its key and value data, for estimates.
+pub const ENTRY_OVERHEAD: usize = 64;
+
+const TUNE_INTERVAL: Duration = Duration::from_secs(1);
+
+static MEMORY_LIMIT: Lazy<IntGauge> = Lazy::new(|| {
+    register_int_gauge!(
+        "pingsix_memory_limit_bytes",
+        "Configured soft memory limit of caches and rate-limit state"
+    )
+    .expect("memory limit metric registration must succeed")
+});
+
+static MEMORY_USAGE: Lazy<IntGaugeVec> = Lazy::new(|| {
+    register_int_gauge_vec!(
+        "pingsix_memory_usage_bytes",
+        "Estimated memory held, by consumer",
+        &["consumer"]
+    )
+    .expect("memory usage metric registration must succeed")
+});
+
+static CACHE_BUDGET: Lazy<IntGauge> = Lazy::new(|| {
+    register_int_gauge!(
+        "pingsix_cache_memory_budget_bytes",
+        "Memory the response cache may currently use"
+    )
+    .expect("cache budget metric registration must succeed")
+});
+
+static MEMORY_SHRINKS: Lazy<IntCounterVec> = Lazy::new(|| {
+    register_int_counter_vec!(
+        "pingsix_memory_shrinks_total",
+        "Times a consumer was asked to release memory",
+        &["consumer"]
+    )
+    .expect("memory shrink metric registration must succeed")
+});
+
+/// Something holding memory that the limit accounts for.
+#[async_trait]
+pub trait MemoryConsumer: Send + Sync {
+    /// Estimated bytes currently held.
+    fn usage(&self) -> usize;
+
+    /// Release memory down to about `target` bytes. Consumers with a size limit keep
+    /// `target` as their limit until the next call, which may raise it again.
+    async fn shrink(&self, target: usize);
+}
+
+type Registered = (&'static str, Weak<dyn MemoryConsumer>);
+
+static LIMIT: OnceCell<usize> = OnceCell::new();
+static CONSUMERS: Lazy<Mutex<Vec<Registered>>> = Lazy::new(|| Mutex::new(Vec::new()));
+
+/// Enable the memory limit. Returns the tuner service to add to the server; `None`
+/// without a limit or on later calls.
+pub fn init(
+    limit: Option<usize>,
+    cache: Arc<dyn MemoryConsumer>,
+) -> Option<GenBackgroundService<MemoryTuner>> {
+    let limit = limit?;
+    LIMIT.set(limit).ok()?;
+    MEMORY_LIMIT.set(limit as i64);
+    Some(background_service(
+        "memory tuner",
+        MemoryTuner { limit, cache },
+    ))
+}
+
+/// Account for `consumer` under the limit, for as long as it is alive. A no-op when
+/// no limit is configured.
+pub fn register(kind: &'static str, consumer: Weak<dyn MemoryConsumer>) {
+    if LIMIT.get().is_none() {
+        return;
+    }
+    let mut consumers = CONSUMERS.lock().unwrap_or_else(|e| e.into_inner());
+    consumers.retain(|(_, consumer)| consumer.strong_count() > 0);
+    consumers.push((kind, consumer));
+}
+
+fn registered() -> Vec<(&'static str, Arc<dyn MemoryConsumer>)> {
+    let mut consumers = CONSUMERS.lock().unwrap_or_else(|e| e.into_inner());
+    consumers.retain(|(_, consumer)| consumer.strong_count() > 0);
+    consumers
+        .iter()
+        .filter_map(|(kind, consumer)| Some((*kind, consumer.upgrade()?)))
+        .collect()
+}
+
+/// Periodically fits the registered consumers under the limit.
+pub struct MemoryTuner {
+    limit: usize,
+    cache: Arc<dyn MemoryConsumer>,
+}
+
+#[async_trait]
+impl BackgroundService for MemoryTuner {
+    async fn start(&self, mut shutdown: ShutdownWatch) {
+        let mut interval = tokio::time::interval(TUNE_INTERVAL);
+        loop {
+            tokio::select! {
+                _ = shutdown.changed() => return,
+                _ = interval.tick() => {
+                    tune(self.limit, self.cache.as_ref(), &registered()).await;
+                }
+            }
+        }
+    }
+}
+
+/// One tuning pass: shrink the other consumers if together above half the limit, then
+/// give the cache the rest.
+async fn tune(
+    limit: usize,
+    cache: &dyn MemoryConsumer,
+    consumers: &[(&'static str, Arc<dyn MemoryConsumer>)],
+) -> usize {
+    let cap = limit / 2;
+    let held: usize = consumers.iter().map(|(_, c)| c.usage()).sum();
+    if held > cap {
+        for (kind, consumer) in consumers {
+            let usage = consumer.usage();
+            let target = (usage as u128 * cap as u128 / held as u128) as usize;
+            if usage > target {
+                MEMORY_SHRINKS.with_label_values(&[kind]).inc();
+                consumer.shrink(target).await;
+            }
+        }
+    }
+
+    let mut usage = [(DNS, 0), (RATE_LIMIT, 0), (PLUGIN_STATE, 0)];
+    for (kind, consumer) in consumers {
+        if let Some((_, total)) = usage.iter_mut().find(|(k, _)| k == kind) {
+            *total += consumer.usage();
+        }
+    }
+    let others: usize = usage.iter().map(|(_, total)| total).sum();
+    let budget = limit.saturating_sub(others);
+    if cache.usage() > budget {
+        MEMORY_SHRINKS.with_label_values(&[CACHE]).inc();
+    }
+    cache.shrink(budget).await;
+
+    CACHE_BUDGET.set(budget as i64);
+    MEMORY_USAGE
+        .with_label_values(&[CACHE])
+        .set(cache.usage() as i64);
+    for (kind, total) in usage {
+        MEMORY_USAGE.with_label_values(&[kind]).set(total as i64);
+    }
+    budget
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use super::*;
+
+    #[derive(Default)]
+    struct Fixed {
+        held: AtomicUsize,
+        limit: AtomicUsize,
+    }
+
+    #[async_trait]
+    impl MemoryConsumer for Fixed {
+        fn usage(&self) -> usize {
+            self.held.load(Ordering::Relaxed)
+        }
+
+        async fn shrink(&self, target: usize) {
+            self.limit.store(target, Ordering::Relaxed);
+            self.held.fetch_min(target, Ordering::Relaxed);
+        }
+    }
+
+    fn fixed(held: usize) -> Arc<Fixed> {
+        let consumer = Arc::new(Fixed::default());
+        consumer.held.store(held, Ordering::Relaxed);
+        consumer
+    }
+
+    #[tokio::test]
+    async fn cache_gets_what_other_consumers_leave() {
+        let cache = fixed(900);
+        let dns = fixed(100);
+        let consumers: Vec<(&'static str, Arc<dyn MemoryConsumer>)> = vec![(DNS, dns.clone())];
+        assert_eq!(tune(1000, cache.as_ref(), &consumers).await, 900);
+        assert_eq!(cache.limit.load(Ordering::Relaxed), 900);
+
+        // Above half the limit, the others are cut to their share of it first.
+        dns.held.store(800, Ordering::Relaxed);
+        let rate = fixed(200);
+        let consumers: Vec<(&'static str, Arc<dyn MemoryConsumer>)> =
+            vec![(DNS, dns.clone()), (RATE_LIMIT, rate.clone())];
+        assert_eq!(tune(1000, cache.as_ref(), &consumers).await, 500);
+        assert_eq!(dns.usage(), 400);
+        assert_eq!(rate.usage(), 100);
+        assert_eq!(cache.usage(), 500);
+    }
+}
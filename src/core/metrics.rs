@@ -0,0 +1,138 @@
+//! Gateway-internal operational metrics.
+//!
+//! Request-path metrics live next to the code that records them (plugins, cache,
+//! logging). This module collects the control-plane and admin signals that do not
+//! belong to a single request so they are registered in one place and exposed by
+//! both the Prometheus service and the status server `/metrics` endpoint.
+
+use once_cell::sync::Lazy;
+use prometheus::{
+    register_histogram, register_int_counter, register_int_counter_vec, register_int_gauge,
+    Encoder, Histogram, IntCounter, IntCounterVec, IntGauge, TextEncoder,
+};
+
+/// Etcd watch streams re-established after a failure. Resumable failures continue from
+/// the last revision; the rest also count in [`CONFIG_RESYNCS`].
+pub static ETCD_WATCH_RECONNECTS: Lazy<IntCounter> = Lazy::new(|| {
+    register_int_counter!(
+        "pingsix_etcd_watch_reconnects_total",
+        "Etcd watch streams restarted after a failure"
+    )
+    .expect("etcd watch reconnect metric registration must succeed")
+});
+
+/// Full relists of the config store after the initial one, by reason (`compacted`,
+/// `canceled`, `rejected`, or `watch_ended` for stores whose watches cannot resume).
+pub static CONFIG_RESYNCS: Lazy<IntCounterVec> = Lazy::new(|| {
+    register_int_counter_vec!(
+        "pingsix_config_resyncs_total",
+        "Full config store relists after the initial listing",
+        &["reason"]
+    )
+    .expect("config resync metric registration must succeed")
+});
+
+/// Time spent building, compiling, and publishing a runtime snapshot.
+pub static CONFIG_RELOAD_DURATION: Lazy<Histogram> = Lazy::new(|| {
+    register_histogram!(
+        "pingsix_config_reload_duration_seconds",
+        "Duration of runtime snapshot build and publish",
+        vec![0.001, 0.005, 0.01, 0.05, 0.1, 0.5, 1.0, 5.0]
+    )
+    .expect("config reload metric registration must succeed")
+});
+
+/// Time spent rebuilding the route matcher for a new snapshot.
+pub static ROUTE_MATCHER_REBUILD_DURATION: Lazy<Histogram> = Lazy::new(|| {
+    register_histogram!(
+        "pingsix_route_matcher_rebuild_duration_seconds",
+        "Duration of route matcher rebuilds",
+        vec![0.0001, 0.0005, 0.001, 0.005, 0.01, 0.05, 0.1, 0.5]
+    )
+    .expect("route matcher metric registration must succeed")
+});
+
+/// Route matcher shards (one per host pattern, plus the host-less one) rebuilt or
+/// carried over unchanged when a new snapshot's matcher is built.
+pub static ROUTE_MATCHER_SHARDS: Lazy<IntCounterVec> = Lazy::new(|| {
+    register_int_counter_vec!(
+        "pingsix_route_matcher_shards_total",
+        "Route matcher shards rebuilt or reused by matcher rebuilds",
+        &["outcome"]
+    )
+    .expect("route matcher shard metric registration must succeed")
+});
+
+/// Plugin constructions rejected by their builder, by plugin name.
+pub static PLUGIN_BUILD_FAILURES: Lazy<IntCounterVec> = Lazy::new(|| {
+    register_int_counter_vec!(
+        "pingsix_plugin_build_failures_total",
+        "Plugin instances that failed to build",
+        &["plugin"]
+    )
+    .expect("plugin build failure metric registration must succeed")
+});
+
+/// Routes, services and global rules compiled again for a new snapshot, by resource
+/// type and reason (`created`, `changed`, or `dependency` when a referenced service or
+/// upstream was rebuilt).
+pub static EXECUTOR_REBUILDS: Lazy<IntCounterVec> = Lazy::new(|| {
+    register_int_counter_vec!(
+        "pingsix_plugin_executor_rebuilds_total",
+        "Resources whose plugin executor was rebuilt for a new snapshot",
+        &["resource", "reason"]
+    )
+    .expect("executor rebuild metric registration must succeed")
+});
+
+/// Routes, services and global rules carried over unchanged into a new snapshot.
+pub static EXECUTOR_REUSES: Lazy<IntCounterVec> = Lazy::new(|| {
+    register_int_counter_vec!(
+        "pingsix_plugin_executor_reuses_total",
+        "Resources whose compiled plugin executor was reused by a new snapshot",
+        &["resource"]
+    )
+    .expect("executor reuse metric registration must succeed")
+});
+
+/// Static resources the latest load left out under the tolerant load policy.
+pub static SKIPPED_RESOURCES: Lazy<IntGauge> = Lazy::new(|| {
+    register_int_gauge!(
+        "pingsix_static_resources_skipped",
+        "Static resources left out of the runtime by the latest load"
+    )
+    .expect("skipped resource metric registration must succeed")
+});
+
+/// Admin API requests by method and response status code.
+pub static ADMIN_REQUESTS: Lazy<IntCounterVec> = Lazy::new(|| {
+    register_int_counter_vec!(
+        "pingsix_admin_requests_total",
+        "Admin API requests",
+        &["method", "code"]
+    )
+    .expect("admin request metric registration must succeed")
+});
+
+/// Render every metric in the default registry in the Prometheus text format.
+pub fn encode_text() -> Vec<u8> {
+    let mut buffer = Vec::new();
+    if let Err(e) = TextEncoder::new().encode(&prometheus::gather(), &mut buffer) {
+        log::error!("Failed to encode Prometheus metrics: {e}");
+    }
+    buffer
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn encoded_text_includes_registered_internal_metrics() {
+        PLUGIN_BUILD_FAILURES
+            .with_label_values(&["unit-test"])
+            .inc();
+        let text = String::from_utf8(encode_text()).unwrap();
+        assert!(text.contains("pingsix_plugin_build_failures_total"));
+    }
+}
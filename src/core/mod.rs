@@ -4,17 +4,34 @@
 //! - Error handling and result types
 //! - Plugin system infrastructure
 //! - Request context management
-//! - Plugin execution framework
+//! - Plugin execution framework and per-plugin metrics
 //! - Service readiness tracking
+//! - Operational metrics
+//! - Overload protection
+//! - Maintenance mode
+//! - Slow request logging
+//! - Header hygiene
+//! - Soft memory limit and adaptive cache eviction
+//! - Shared plugin state with TTL
 
 pub mod error;
+pub mod eviction;
+pub mod header_hygiene;
+pub mod maintenance;
+pub mod memory;
+pub mod metrics;
+pub mod overload;
 pub mod plugin;
+pub mod plugin_metrics;
+pub mod shared_dict;
+pub mod slow_log;
 pub mod status;
 
 // Re-export all public items so external modules can use `crate::core::*`
 pub use error::{ErrorContext, ProxyError, ProxyResult};
 pub use plugin::{
-    apply_regex_uri_template, constant_time_digest_eq, constant_time_eq, secret_digest,
-    sort_plugins_by_priority_desc, HealthCheckFingerprint, HealthCheckSpec, PluginCreateFn,
-    ProxyContext, ProxyPlugin, ProxyPluginExecutor, RouteContext, UpstreamSelector,
+    apply_regex_uri_template, constant_time_digest_eq, constant_time_eq, expand_route_params,
+    secret_digest, sort_plugins_by_priority_desc, HealthCheckFingerprint, HealthCheckSpec,
+    PhaseTimings, PluginCreateFn, ProxyContext, ProxyPlugin, ProxyPluginExecutor, RouteContext,
+    UpstreamInfo, UpstreamSelector,
 };
@@ -0,0 +1,186 @@
+//! Adaptive overload protection for the proxy listeners.
+//!
+//! In-flight proxied requests and event-loop lag are compared with the limits in
+//! `pingsix.overload`; the larger ratio is the current load. Once it reaches 1, requests
+//! are shed with `503` and `Retry-After`, lowest route priority first: `low` routes at
+//! the limits, `normal` at 125%, `high` at 150%. `critical` routes are never shed.
+
+use std::{
+    sync::{
+        atomic::{AtomicU64, AtomicUsize, Ordering},
+        Once,
+    },
+    time::{Duration, Instant},
+};
+
+use once_cell::sync::{Lazy, OnceCell};
+use prometheus::{register_int_counter_vec, IntCounterVec};
+
+use crate::config::{Overload, ShedPriority};
+
+/// How often the event-loop lag is sampled.
+const LAG_SAMPLE_INTERVAL: Duration = Duration::from_millis(100);
+
+static SHED_REQUESTS: Lazy<IntCounterVec> = Lazy::new(|| {
+    register_int_counter_vec!(
+        "pingsix_overload_shed_total",
+        "Requests rejected by overload protection",
+        &["priority"]
+    )
+    .expect("overload metric registration must succeed")
+});
+
+static OVERLOAD: OnceCell<OverloadState> = OnceCell::new();
+
+/// Enable overload protection. Called once at startup; later calls are no-ops.
+pub fn init(config: Option<Overload>) {
+    if let Some(config) = config {
+        let _ = OVERLOAD.set(OverloadState::new(config));
+    }
+}
+
+/// Admit a request of a route with `priority`, or return the `Retry-After` seconds
+/// of a shed response. Admitted requests count as in flight until the slot drops.
+pub fn admit(priority: ShedPriority) -> Result<Option<InFlightSlot>, u64> {
+    match OVERLOAD.get() {
+        Some(state) => state.admit(priority).map(Some),
+        None => Ok(None),
+    }
+}
+
+/// A request counted as in flight, released on drop.
+pub struct InFlightSlot {
+    state: &'static OverloadState,
+}
+
+impl Drop for InFlightSlot {
+    fn drop(&mut self) {
+        self.state.in_flight.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+pub struct OverloadState {
+    config: Overload,
+    in_flight: AtomicUsize,
+    loop_lag_ms: AtomicU64,
+    sampler: Once,
+}
+
+impl OverloadState {
+    fn new(config: Overload) -> Self {
+        Self {
+            config,
+            in_flight: AtomicUsize::new(0),
+            loop_lag_ms: AtomicU64::new(0),
+            sampler: Once::new(),
+        }
+    }
+
+    fn admit(&'static self, priority: ShedPriority) -> Result<InFlightSlot, u64> {
+        self.start_lag_sampler();
+        if self.load() >= shed_threshold(priority) {
+            SHED_REQUESTS
+                .with_label_values(&[priority_label(priority)])
+                .inc();
+            return Err(self.config.retry_after);
+        }
+        self.in_flight.fetch_add(1, Ordering::Relaxed);
+        Ok(InFlightSlot { state: self })
+    }
+
+    /// The larger of the in-flight and event-loop lag ratios to their limits.
+    fn load(&self) -> f64 {
+        let in_flight = self.config.max_in_flight.map_or(0.0, |max| {
+            self.in_flight.load(Ordering::Relaxed) as f64 / max as f64
+        });
+        let lag = self.config.max_loop_lag_ms.map_or(0.0, |max| {
+            self.loop_lag_ms.load(Ordering::Relaxed) as f64 / max as f64
+        });
+        in_flight.max(lag)
+    }
+
+    /// Sample lag on the proxy runtime itself, so it is spawned from the first request.
+    fn start_lag_sampler(&'static self) {
+        if self.config.max_loop_lag_ms.is_none() {
+            return;
+        }
+        self.sampler.call_once(|| {
+            tokio::spawn(async move {
+                loop {
+                    let start = Instant::now();
+                    tokio::time::sleep(LAG_SAMPLE_INTERVAL).await;
+                    let lag = start.elapsed().saturating_sub(LAG_SAMPLE_INTERVAL);
+                    self.record_lag(lag);
+                }
+            });
+        });
+    }
+
+    /// Lag rises at once and decays by half per sample, so one quiet tick does not
+    /// end shedding in the middle of a spike.
+    fn record_lag(&self, lag: Duration) {
+        let sample = lag.as_millis() as u64;
+        let previous = self.loop_lag_ms.load(Ordering::Relaxed);
+        self.loop_lag_ms
+            .store(sample.max(previous / 2), Ordering::Relaxed);
+    }
+}
+
+fn shed_threshold(priority: ShedPriority) -> f64 {
+    match priority {
+        ShedPriority::Low => 1.0,
+        ShedPriority::Normal => 1.25,
+        ShedPriority::High => 1.5,
+        ShedPriority::Critical => f64::INFINITY,
+    }
+}
+
+fn priority_label(priority: ShedPriority) -> &'static str {
+    match priority {
+        ShedPriority::Low => "low",
+        ShedPriority::Normal => "normal",
+        ShedPriority::High => "high",
+        ShedPriority::Critical => "critical",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lower_priorities_are_shed_first() {
+        let state: &'static OverloadState = Box::leak(Box::new(OverloadState::new(Overload {
+            max_in_flight: Some(4),
+            max_loop_lag_ms: None,
+            retry_after: 2,
+        })));
+        let mut slots: Vec<InFlightSlot> = (0..4)
+            .map(|_| state.admit(ShedPriority::Low).unwrap())
+            .collect();
+        assert_eq!(state.admit(ShedPriority::Low).err(), Some(2));
+
+        slots.push(state.admit(ShedPriority::Normal).unwrap());
+        assert!(state.admit(ShedPriority::Normal).is_err());
+        slots.push(state.admit(ShedPriority::High).unwrap());
+        assert!(state.admit(ShedPriority::High).is_err());
+        slots.push(state.admit(ShedPriority::Critical).unwrap());
+
+        slots.clear();
+        assert_eq!(state.in_flight.load(Ordering::Relaxed), 0);
+        assert!(state.admit(ShedPriority::Low).is_ok());
+    }
+
+    #[test]
+    fn loop_lag_decays_gradually() {
+        let state = OverloadState::new(Overload {
+            max_in_flight: None,
+            max_loop_lag_ms: Some(100),
+            retry_after: 1,
+        });
+        state.record_lag(Duration::from_millis(200));
+        assert_eq!(state.load(), 2.0);
+        state.record_lag(Duration::ZERO);
+        assert_eq!(state.load(), 1.0);
+    }
+}
@@ -2,10 +2,17 @@
 //!
 //! Provides the plugin trait, executor, context, and URI rewriting utilities.
 
-use std::{any::Any, borrow::Cow, collections::HashMap, sync::Arc, time::Instant};
+use std::{
+    any::Any,
+    borrow::Cow,
+    collections::HashMap,
+    sync::Arc,
+    time::{Duration, Instant},
+};
 
 use async_trait::async_trait;
 use bytes::Bytes;
+use http::HeaderMap;
 use once_cell::sync::Lazy;
 use pingora_core::upstreams::peer::HttpPeer;
 use pingora_error::{Error, Result};
@@ -15,7 +22,11 @@ use regex::Regex;
 use serde_json::Value as JsonValue;
 
 use crate::config;
-use crate::core::error::ProxyResult;
+use crate::core::{
+    error::ProxyResult,
+    plugin_metrics,
+    shared_dict::{self, SharedDict},
+};
 use pingora_load_balancing::Backend;
 
 // =============================================================================
@@ -31,6 +42,23 @@ pub trait UpstreamSelector: Send + Sync {
     /// Select a backend for the given session
     fn select_backend(&self, session: &mut Session) -> Option<Backend>;
 
+    /// Id of the upstream resource; empty for inline upstreams.
+    fn upstream_id(&self) -> &str {
+        ""
+    }
+
+    /// The backend at address `node` (`ip:port`), bypassing balancing and health
+    /// state. Used by debug routing; `None` when the upstream has no such node.
+    fn select_node(&self, _node: &str) -> Option<Backend> {
+        None
+    }
+
+    /// Called once a backend returned by `select_backend` or `select_node` stops
+    /// serving the request (before a retry selects again, or in the logging phase),
+    /// with the upstream response time when a response arrived. Load-tracking
+    /// selectors release the backend here.
+    fn release_backend(&self, _peer: &HttpPeer, _response_time: Option<Duration>) {}
+
     /// Get the number of retries configured for this upstream
     fn get_retries(&self) -> Option<usize>;
 
@@ -41,12 +69,24 @@ pub trait UpstreamSelector: Send + Sync {
     fn get_pass_host(&self) -> &config::UpstreamPassHost;
 
     /// Rewrite the upstream host in the request header if needed
-    fn upstream_host_rewrite(&self, upstream_request: &mut RequestHeader);
+    ///
+    /// `route_params` expand `$param_<name>` placeholders in the configured host.
+    fn upstream_host_rewrite(
+        &self,
+        upstream_request: &mut RequestHeader,
+        route_params: Option<&[(String, String)]>,
+    );
 
     /// Stable cache-namespace fragment that changes when upstream identity or
     /// origin-selection configuration changes, so process-local cache cannot
     /// reuse stale entries after a dynamic config switch.
     fn cache_isolation_key(&self) -> String;
+
+    /// `Set-Cookie` value pinning the client to `peer`, when the upstream uses
+    /// session affinity and the request is not already pinned to it.
+    fn affinity_cookie(&self, _req: &RequestHeader, _peer: &HttpPeer) -> Option<String> {
+        None
+    }
 }
 
 /// Trait for route behavior that can be used in proxy context
@@ -84,8 +124,34 @@ pub trait RouteContext: Send + Sync {
     /// Resolve upstream for this route
     fn resolve_upstream(&self) -> Option<Arc<dyn UpstreamSelector>>;
 
+    /// Upstream chosen for this particular request, e.g. by a service's blue/green
+    /// switch. `None` keeps [`Self::resolve_upstream`].
+    fn request_upstream(&self, _req: &RequestHeader) -> Option<Arc<dyn UpstreamSelector>> {
+        None
+    }
+
     /// Route-level timeout, applied after an upstream override is selected.
     fn timeout(&self) -> Option<&crate::config::Timeout>;
+
+    /// Whether responses on this route are long-lived streams.
+    fn streaming(&self) -> bool {
+        false
+    }
+
+    /// How early this route's requests are shed under overload.
+    fn shed_priority(&self) -> crate::config::ShedPriority {
+        crate::config::ShedPriority::Normal
+    }
+
+    /// Path prefix removed from the upstream request.
+    fn strip_path_prefix(&self) -> Option<&str> {
+        None
+    }
+
+    /// API product this route is grouped under.
+    fn api_group(&self) -> Option<&str> {
+        None
+    }
 }
 
 // =============================================================================
@@ -107,6 +173,80 @@ pub struct HealthCheckSpec {
 // PROXY CONTEXT (in plugin to avoid context<->plugin circular dependency)
 // =============================================================================
 
+/// Upstream exchange details recorded by the core HTTP service for access logging.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct UpstreamInfo {
+    /// When the most recent upstream peer was selected.
+    pub peer_selected_at: Option<Instant>,
+    /// When the upstream connection was ready to carry the request.
+    pub connected_at: Option<Instant>,
+    /// When the upstream response header was received.
+    pub responded_at: Option<Instant>,
+    /// Status code of the upstream response.
+    pub status: Option<u16>,
+}
+
+impl UpstreamInfo {
+    /// Time from peer selection until the connection was usable.
+    pub fn connect_time(&self) -> Option<Duration> {
+        Some(
+            self.connected_at?
+                .saturating_duration_since(self.peer_selected_at?),
+        )
+    }
+
+    /// Time from peer selection until the upstream response header arrived.
+    pub fn response_time(&self) -> Option<Duration> {
+        Some(
+            self.responded_at?
+                .saturating_duration_since(self.peer_selected_at?),
+        )
+    }
+
+    /// Time from the connection being usable until the upstream response header arrived.
+    pub fn ttfb(&self) -> Option<Duration> {
+        Some(
+            self.responded_at?
+                .saturating_duration_since(self.connected_at?),
+        )
+    }
+}
+
+/// Time spent matching the route and running plugins, per phase. Phases that run more
+/// than once (body filters, upstream request filter on retries) accumulate.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct PhaseTimings {
+    pub route_match: Duration,
+    pub early_request_filter: Duration,
+    pub request_filter: Duration,
+    pub request_body_filter: Duration,
+    pub upstream_request_filter: Duration,
+    pub response_filter: Duration,
+    pub response_body_filter: Duration,
+}
+
+impl PhaseTimings {
+    /// Plugin phases by name, in execution order.
+    pub fn plugin_phases(&self) -> [(&'static str, Duration); 6] {
+        [
+            ("early_request_filter", self.early_request_filter),
+            ("request_filter", self.request_filter),
+            ("request_body_filter", self.request_body_filter),
+            ("upstream_request_filter", self.upstream_request_filter),
+            ("response_filter", self.response_filter),
+            ("response_body_filter", self.response_body_filter),
+        ]
+    }
+
+    /// Time spent in plugins across all phases.
+    pub fn plugins(&self) -> Duration {
+        self.plugin_phases()
+            .iter()
+            .map(|(_, elapsed)| *elapsed)
+            .sum()
+    }
+}
+
 /// Request-scoped context shared across all plugin phases.
 ///
 /// Contains routing information, retry state, and plugin-specific data.
@@ -132,6 +272,8 @@ pub struct ProxyContext {
     pub global_plugin: Arc<ProxyPluginExecutor>,
     /// Request start timestamp for performance metrics and timeouts.
     pub request_start: Instant,
+    /// End of the whole exchange from the route's `timeout.total`.
+    pub deadline: Option<Instant>,
     /// Unique request identifier, set by request-id plugin if enabled.
     pub request_id: Option<String>,
     /// Whether the original downstream request contained authentication/session credentials.
@@ -141,6 +283,19 @@ pub struct ProxyContext {
     pub request_has_credentials: bool,
     /// Optional authenticated identity established by an auth plugin.
     pub authenticated_identity: Option<String>,
+    /// Upstream timing and status for the current attempt.
+    pub upstream_info: UpstreamInfo,
+    /// Route match and per-phase plugin latency.
+    pub timings: PhaseTimings,
+    /// Set for streaming responses (route `streaming: true` or an SSE/NDJSON content type).
+    /// Buffering plugins and the response cache stand aside when it is set.
+    pub streaming: bool,
+    /// Pause before the current response body chunk is sent, requested by pacing
+    /// plugins such as limit-bandwidth. Taken after each chunk.
+    pub response_body_delay: Option<Duration>,
+    /// `grpc-status` of a gRPC response, from its trailers or, for trailers-only
+    /// responses, its headers.
+    pub grpc_status: Option<u32>,
     /// Custom variables available to plugins (type-erased, thread-safe).
     /// Lazily allocated because many requests never store plugin variables.
     pub vars: Option<HashMap<String, Box<dyn Any + Send + Sync>>>,
@@ -158,10 +313,16 @@ impl Default for ProxyContext {
             plugin: ProxyPluginExecutor::default_shared(),
             global_plugin: ProxyPluginExecutor::default_shared(),
             request_start: Instant::now(),
+            deadline: None,
             request_id: None,
             original_request_had_credentials: false,
             request_has_credentials: false,
             authenticated_identity: None,
+            upstream_info: UpstreamInfo::default(),
+            timings: PhaseTimings::default(),
+            streaming: false,
+            response_body_delay: None,
+            grpc_status: None,
             vars: None,
         }
     }
@@ -189,16 +350,45 @@ impl ProxyContext {
             .and_then(|v| v.downcast_ref::<T>())
     }
 
+    /// Get a typed mutable reference, for per-request state updated across phases.
+    pub fn get_mut<T: Any>(&mut self, key: &str) -> Option<&mut T> {
+        self.vars
+            .as_mut()
+            .and_then(|vars| vars.get_mut(key))
+            .and_then(|v| v.downcast_mut::<T>())
+    }
+
     /// Convenience method for string values to avoid repeated type annotation.
     pub fn get_str(&self, key: &str) -> Option<&str> {
         self.get::<String>(key).map(|s| s.as_str())
     }
 
+    /// The shared dictionary of `namespace`, for state shared between plugin instances
+    /// and requests; see [`shared_dict::shared_dict`].
+    pub fn shared_dict<V: Send + Sync + 'static>(
+        &self,
+        namespace: &'static str,
+        max_entries: usize,
+    ) -> ProxyResult<Arc<SharedDict<V>>> {
+        shared_dict::shared_dict(namespace, max_entries)
+    }
+
     /// Get the elapsed time since request start in milliseconds.
     pub fn elapsed_ms(&self) -> u128 {
         self.request_start.elapsed().as_millis()
     }
 
+    /// Time left before the route's `timeout.total` deadline, if it has one.
+    pub fn remaining(&self) -> Option<Duration> {
+        self.deadline
+            .map(|deadline| deadline.saturating_duration_since(Instant::now()))
+    }
+
+    /// Whether the route's `timeout.total` deadline has passed.
+    pub fn deadline_passed(&self) -> bool {
+        self.remaining().is_some_and(|left| left.is_zero())
+    }
+
     /// Get the elapsed time since request start as f64 milliseconds (for metrics).
     pub fn elapsed_ms_f64(&self) -> f64 {
         self.elapsed_ms() as f64
@@ -301,6 +491,28 @@ pub trait ProxyPlugin: Send + Sync {
         Ok(())
     }
 
+    /// Whether this plugin implements request body filtering.
+    ///
+    /// Executors use this capability to skip body-filter traversal when no
+    /// configured plugin needs it.
+    fn has_request_body_filter(&self) -> bool {
+        false
+    }
+
+    /// Handle the request body chunks before they are sent upstream
+    ///
+    /// Use this for: request body inspection and transformation. Plugins that change
+    /// the body length must drop `Content-Length` in `upstream_request_filter`.
+    async fn request_body_filter(
+        &self,
+        _session: &mut Session,
+        _body: &mut Option<Bytes>,
+        _end_of_stream: bool,
+        _ctx: &mut ProxyContext,
+    ) -> Result<()> {
+        Ok(())
+    }
+
     /// Whether this plugin implements response body filtering.
     ///
     /// Executors use this capability to skip body-filter traversal when no
@@ -323,6 +535,18 @@ pub trait ProxyPlugin: Send + Sync {
         Ok(())
     }
 
+    /// Handle the response trailers, e.g. `grpc-status`, before they are sent downstream.
+    ///
+    /// Only called when the upstream sends trailers (typically HTTP/2 gRPC upstreams).
+    async fn response_trailer_filter(
+        &self,
+        _session: &mut Session,
+        _trailers: &mut HeaderMap,
+        _ctx: &mut ProxyContext,
+    ) -> Result<()> {
+        Ok(())
+    }
+
     /// Called after the complete response is sent or on fatal error.
     ///
     /// Use this for: metrics collection, access logging, cleanup operations.
@@ -415,6 +639,28 @@ pub fn apply_regex_uri_template<'a>(
     Cow::Borrowed(uri)
 }
 
+/// Route parameter placeholders such as `$param_id`.
+static ROUTE_PARAM_RE: Lazy<Regex> =
+    Lazy::new(|| Regex::new(r"\$param_(\w+)").expect("Invalid route parameter regex"));
+
+/// Replaces `$param_<name>` in `template` with the route parameter `name` captured
+/// by the route's URI pattern (e.g. `/users/{id}`). Unknown parameters expand to an
+/// empty string.
+pub fn expand_route_params<'a>(
+    template: &'a str,
+    params: Option<&[(String, String)]>,
+) -> Cow<'a, str> {
+    if !template.contains("$param_") {
+        return Cow::Borrowed(template);
+    }
+    ROUTE_PARAM_RE.replace_all(template, |caps: &regex::Captures| {
+        params
+            .and_then(|params| params.iter().find(|(name, _)| *name == caps[1]))
+            .map(|(_, value)| value.clone())
+            .unwrap_or_default()
+    })
+}
+
 // =============================================================================
 // PLUGIN EXECUTOR
 // =============================================================================
@@ -431,44 +677,59 @@ static DEFAULT_PLUGIN_EXECUTOR: Lazy<Arc<ProxyPluginExecutor>> =
 #[derive(Default)]
 pub struct ProxyPluginExecutor {
     pub plugins: Vec<Arc<dyn ProxyPlugin>>,
+    /// Whether at least one configured plugin processes request body chunks.
+    pub has_request_body_filter: bool,
     /// Whether at least one configured plugin processes response body chunks.
     pub has_response_body_filter: bool,
 }
 
 /// Invokes a plugin method on each plugin in sequence (async, propagates Result).
+/// Each call is recorded under `$phase` by [`plugin_metrics`].
 macro_rules! for_each_plugin_async {
-    ($self:expr, $method:ident, $($arg:expr),*) => {
+    ($self:expr, $phase:literal, $method:ident, $($arg:expr),*; $ctx:ident) => {
         for plugin in $self.plugins.iter() {
-            plugin.$method($($arg),*).await?;
+            let started = plugin_metrics::start();
+            let result = plugin.$method($($arg,)* $ctx).await;
+            plugin_metrics::record(started, plugin.name(), $phase, $ctx, result.is_err());
+            result?;
         }
     };
 }
 
 /// Invokes a plugin method on each plugin in sequence (sync, propagates Result).
 macro_rules! for_each_plugin_sync {
-    ($self:expr, $method:ident, $($arg:expr),*) => {
+    ($self:expr, $phase:literal, $method:ident, $($arg:expr),*; $ctx:ident) => {
         for plugin in $self.plugins.iter() {
-            plugin.$method($($arg),*)?;
+            let started = plugin_metrics::start();
+            let result = plugin.$method($($arg,)* $ctx);
+            plugin_metrics::record(started, plugin.name(), $phase, $ctx, result.is_err());
+            result?;
         }
     };
 }
 
 /// Invokes a plugin method on each plugin in sequence (async, no return value).
 macro_rules! for_each_plugin_async_unit {
-    ($self:expr, $method:ident, $($arg:expr),*) => {
+    ($self:expr, $phase:literal, $method:ident, $($arg:expr),*; $ctx:ident) => {
         for plugin in $self.plugins.iter() {
-            plugin.$method($($arg),*).await;
+            let started = plugin_metrics::start();
+            plugin.$method($($arg,)* $ctx).await;
+            plugin_metrics::record(started, plugin.name(), $phase, $ctx, false);
         }
     };
 }
 
 impl ProxyPluginExecutor {
     pub fn new(plugins: Vec<Arc<dyn ProxyPlugin>>) -> Self {
+        let has_request_body_filter = plugins
+            .iter()
+            .any(|plugin| plugin.has_request_body_filter());
         let has_response_body_filter = plugins
             .iter()
             .any(|plugin| plugin.has_response_body_filter());
         Self {
             plugins,
+            has_request_body_filter,
             has_response_body_filter,
         }
     }
@@ -495,7 +756,16 @@ impl ProxyPlugin for ProxyPluginExecutor {
 
     async fn request_filter(&self, session: &mut Session, ctx: &mut ProxyContext) -> Result<bool> {
         for plugin in self.plugins.iter() {
-            if plugin.request_filter(session, ctx).await? {
+            let started = plugin_metrics::start();
+            let result = plugin.request_filter(session, ctx).await;
+            plugin_metrics::record(
+                started,
+                plugin.name(),
+                "request_filter",
+                ctx,
+                result.is_err(),
+            );
+            if result? {
                 return Ok(true);
             }
         }
@@ -507,7 +777,7 @@ impl ProxyPlugin for ProxyPluginExecutor {
         session: &mut Session,
         ctx: &mut ProxyContext,
     ) -> Result<()> {
-        for_each_plugin_async!(self, early_request_filter, session, ctx);
+        for_each_plugin_async!(self, "early_request_filter", early_request_filter, session; ctx);
         Ok(())
     }
 
@@ -519,9 +789,10 @@ impl ProxyPlugin for ProxyPluginExecutor {
     ) -> Result<()> {
         for_each_plugin_async!(
             self,
+            "upstream_request_filter",
             upstream_request_filter,
             session,
-            upstream_request,
+            upstream_request;
             ctx
         );
         Ok(())
@@ -533,7 +804,20 @@ impl ProxyPlugin for ProxyPluginExecutor {
         upstream_response: &mut ResponseHeader,
         ctx: &mut ProxyContext,
     ) -> Result<()> {
-        for_each_plugin_async!(self, response_filter, session, upstream_response, ctx);
+        for_each_plugin_async!(self, "response_filter", response_filter, session, upstream_response; ctx);
+        Ok(())
+    }
+
+    async fn request_body_filter(
+        &self,
+        session: &mut Session,
+        body: &mut Option<Bytes>,
+        end_of_stream: bool,
+        ctx: &mut ProxyContext,
+    ) -> Result<()> {
+        if self.has_request_body_filter {
+            for_each_plugin_async!(self, "request_body_filter", request_body_filter, session, body, end_of_stream; ctx);
+        }
         Ok(())
     }
 
@@ -547,18 +831,29 @@ impl ProxyPlugin for ProxyPluginExecutor {
         if self.has_response_body_filter {
             for_each_plugin_sync!(
                 self,
+                "response_body_filter",
                 response_body_filter,
                 session,
                 body,
-                end_of_stream,
+                end_of_stream;
                 ctx
             );
         }
         Ok(())
     }
 
+    async fn response_trailer_filter(
+        &self,
+        session: &mut Session,
+        trailers: &mut HeaderMap,
+        ctx: &mut ProxyContext,
+    ) -> Result<()> {
+        for_each_plugin_async!(self, "response_trailer_filter", response_trailer_filter, session, trailers; ctx);
+        Ok(())
+    }
+
     async fn logging(&self, session: &mut Session, e: Option<&Error>, ctx: &mut ProxyContext) {
-        for_each_plugin_async_unit!(self, logging, session, e, ctx);
+        for_each_plugin_async_unit!(self, "logging", logging, session, e; ctx);
     }
 }
 
@@ -566,6 +861,29 @@ impl ProxyPlugin for ProxyPluginExecutor {
 mod tests {
     use super::*;
 
+    #[test]
+    fn upstream_info_times_are_relative_to_peer_selection() {
+        let selected = Instant::now();
+        let info = UpstreamInfo {
+            peer_selected_at: Some(selected),
+            connected_at: Some(selected + Duration::from_millis(5)),
+            responded_at: Some(selected + Duration::from_millis(20)),
+            status: Some(200),
+        };
+        assert_eq!(info.connect_time(), Some(Duration::from_millis(5)));
+        assert_eq!(info.response_time(), Some(Duration::from_millis(20)));
+        assert_eq!(UpstreamInfo::default().connect_time(), None);
+    }
+
+    #[test]
+    fn requests_reach_the_same_shared_dict() {
+        let (first, second) = (ProxyContext::default(), ProxyContext::default());
+        let dict = first.shared_dict::<u32>("context-dict-test", 16).unwrap();
+        dict.set("seen".into(), 1, None);
+        let dict = second.shared_dict::<u32>("context-dict-test", 16).unwrap();
+        assert_eq!(dict.get("seen", |v| *v), Some(1));
+    }
+
     struct BodyFilterPlugin;
 
     #[async_trait]
@@ -735,4 +1053,21 @@ mod tests {
 
         assert_eq!(result, "/123-9");
     }
+
+    #[test]
+    fn route_params_expand_in_templates() {
+        let params = vec![
+            ("id".to_string(), "42".to_string()),
+            ("tenant".to_string(), "acme".to_string()),
+        ];
+        assert_eq!(
+            expand_route_params("/v2/users/$param_id/$param_missing", Some(&params)),
+            "/v2/users/42/"
+        );
+        assert_eq!(
+            expand_route_params("$param_tenant.internal", Some(&params)),
+            "acme.internal"
+        );
+        assert_eq!(expand_route_params("/static", None), "/static");
+    }
 }
@@ -0,0 +1,288 @@
+//! Shared plugin state.
+//!
+//! A [`SharedDict`] is a concurrent string-keyed map whose entries may expire, bounded
+//! in entry count and accounted for under `pingsix.memory_limit`. Plugins keeping
+//! per-key state (token buckets, usage windows) create one per instance with
+//! [`SharedDict::tracked`], or get the one of a namespace from [`shared_dict`] to share
+//! state between instances and across route reloads; keys of a namespaced dictionary
+//! should include what scopes them, such as the route id.
+
+use std::{
+    any::Any,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use async_trait::async_trait;
+use dashmap::{mapref::entry::Entry as MapEntry, DashMap};
+use once_cell::sync::Lazy;
+
+use super::{
+    memory::{self, MemoryConsumer, ENTRY_OVERHEAD},
+    ProxyError, ProxyResult,
+};
+
+static DICTS: Lazy<DashMap<&'static str, Arc<dyn Any + Send + Sync>>> = Lazy::new(DashMap::new);
+
+/// The dictionary of `namespace`, created with room for `max_entries` on first use.
+/// Later calls return the same dictionary; asking for it with another value type is an
+/// error.
+pub fn shared_dict<V: Send + Sync + 'static>(
+    namespace: &'static str,
+    max_entries: usize,
+) -> ProxyResult<Arc<SharedDict<V>>> {
+    let dict = DICTS
+        .entry(namespace)
+        .or_insert_with(|| SharedDict::<V>::tracked(namespace, max_entries))
+        .clone();
+    dict.downcast().map_err(|_| {
+        ProxyError::Internal(format!(
+            "Shared dict '{namespace}' already holds another value type"
+        ))
+    })
+}
+
+struct Slot<V> {
+    value: V,
+    expires: Option<Instant>,
+}
+
+impl<V> Slot<V> {
+    fn new(value: V, ttl: Option<Duration>, now: Instant) -> Self {
+        Self {
+            value,
+            expires: ttl.map(|ttl| now + ttl),
+        }
+    }
+
+    fn expired(&self, now: Instant) -> bool {
+        self.expires.is_some_and(|expires| expires <= now)
+    }
+}
+
+/// Concurrent map with per-entry TTL and a bound on the number of entries.
+///
+/// When full, expired entries are dropped first, then those closest to expiry (entries
+/// without TTL last) until a tenth of the room is free again.
+pub struct SharedDict<V> {
+    namespace: String,
+    entries: DashMap<String, Slot<V>>,
+    max_entries: usize,
+}
+
+impl<V> SharedDict<V> {
+    pub fn new(namespace: &str, max_entries: usize) -> Self {
+        Self {
+            namespace: namespace.to_string(),
+            entries: DashMap::new(),
+            max_entries: max_entries.max(1),
+        }
+    }
+
+    /// A dictionary accounted for under `pingsix.memory_limit`, for state private to
+    /// one plugin instance.
+    pub fn tracked(namespace: &str, max_entries: usize) -> Arc<Self>
+    where
+        V: Send + Sync + 'static,
+    {
+        let dict = Arc::new(Self::new(namespace, max_entries));
+        memory::register(memory::PLUGIN_STATE, Arc::downgrade(&dict) as _);
+        dict
+    }
+
+    pub fn namespace(&self) -> &str {
+        &self.namespace
+    }
+
+    /// Number of entries, including expired ones not dropped yet.
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Read the live value of `key`.
+    pub fn get<R>(&self, key: &str, read: impl FnOnce(&V) -> R) -> Option<R> {
+        let now = Instant::now();
+        self.entries
+            .get(key)
+            .filter(|entry| !entry.expired(now))
+            .map(|entry| read(&entry.value))
+    }
+
+    /// Store `value` under `key`, replacing any previous one.
+    pub fn set(&self, key: String, value: V, ttl: Option<Duration>) {
+        let now = Instant::now();
+        self.make_room(now);
+        self.entries.insert(key, Slot::new(value, ttl, now));
+    }
+
+    /// Store `value` unless `key` already holds a live value; returns whether it was
+    /// stored.
+    pub fn add(&self, key: String, value: V, ttl: Option<Duration>) -> bool {
+        let now = Instant::now();
+        self.make_room(now);
+        match self.entries.entry(key) {
+            MapEntry::Occupied(mut occupied) => {
+                if !occupied.get().expired(now) {
+                    return false;
+                }
+                occupied.insert(Slot::new(value, ttl, now));
+            }
+            MapEntry::Vacant(vacant) => {
+                vacant.insert(Slot::new(value, ttl, now));
+            }
+        }
+        true
+    }
+
+    /// Update the value of `key` in place, starting from `init()` when there is no live
+    /// one, and restart its TTL. `update` runs under the entry's lock and must not use
+    /// the dictionary.
+    pub fn update<R>(
+        &self,
+        key: String,
+        ttl: Option<Duration>,
+        init: impl FnOnce() -> V,
+        update: impl FnOnce(&mut V) -> R,
+    ) -> R {
+        let now = Instant::now();
+        self.make_room(now);
+        let mut slot = match self.entries.entry(key) {
+            MapEntry::Occupied(occupied) => {
+                let mut slot = occupied.into_ref();
+                if slot.expired(now) {
+                    slot.value = init();
+                }
+                slot
+            }
+            MapEntry::Vacant(vacant) => vacant.insert(Slot::new(init(), ttl, now)),
+        };
+        slot.expires = ttl.map(|ttl| now + ttl);
+        update(&mut slot.value)
+    }
+
+    pub fn remove(&self, key: &str) -> Option<V> {
+        self.entries.remove(key).map(|(_, entry)| entry.value)
+    }
+
+    /// Drop expired entries.
+    pub fn purge_expired(&self) {
+        let now = Instant::now();
+        self.entries.retain(|_, entry| !entry.expired(now));
+    }
+
+    fn make_room(&self, now: Instant) {
+        if self.entries.len() < self.max_entries {
+            return;
+        }
+        self.entries.retain(|_, entry| !entry.expired(now));
+        let keep = self.max_entries - self.max_entries / 10 - 1;
+        self.evict_to(keep);
+    }
+
+    /// Drop the entries closest to expiry until at most `keep` remain.
+    fn evict_to(&self, keep: usize) {
+        let excess = self.entries.len().saturating_sub(keep);
+        if excess == 0 {
+            return;
+        }
+        let mut order: Vec<(Option<Instant>, String)> = self
+            .entries
+            .iter()
+            .map(|entry| (entry.expires, entry.key().clone()))
+            .collect();
+        // `None` sorts first, but entries without TTL should go last.
+        order.sort_by_key(|(expires, _)| (expires.is_none(), *expires));
+        for (_, key) in order.into_iter().take(excess) {
+            self.entries.remove(&key);
+        }
+    }
+
+    fn entry_size(key: &str) -> usize {
+        key.len() + std::mem::size_of::<Slot<V>>() + ENTRY_OVERHEAD
+    }
+}
+
+#[async_trait]
+impl<V: Send + Sync> MemoryConsumer for SharedDict<V> {
+    fn usage(&self) -> usize {
+        self.entries
+            .iter()
+            .map(|entry| Self::entry_size(entry.key()))
+            .sum()
+    }
+
+    async fn shrink(&self, target: usize) {
+        self.purge_expired();
+        let per_entry = self.usage() / self.entries.len().max(1);
+        self.evict_to(target / per_entry.max(1));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn entries_expire_and_update_in_place() {
+        let dict = SharedDict::new("test", 10);
+        dict.set("gone".into(), 1, Some(Duration::ZERO));
+        assert_eq!(dict.get("gone", |v| *v), None);
+        assert!(
+            dict.add("gone".into(), 2, None),
+            "expired entries can be replaced"
+        );
+        assert!(!dict.add("gone".into(), 3, None));
+        assert_eq!(dict.get("gone", |v| *v), Some(2));
+
+        let count = |dict: &SharedDict<u32>| {
+            dict.update(
+                "n".into(),
+                None,
+                || 0,
+                |n| {
+                    *n += 1;
+                    *n
+                },
+            )
+        };
+        assert_eq!((count(&dict), count(&dict)), (1, 2));
+        assert_eq!(dict.remove("n"), Some(2));
+        dict.purge_expired();
+        assert_eq!(dict.len(), 1);
+    }
+
+    #[test]
+    fn full_dict_drops_entries_closest_to_expiry() {
+        let dict = SharedDict::new("test", 10);
+        dict.set("forever".into(), 0, None);
+        for i in 1..10 {
+            dict.set(format!("k{i}"), i, Some(Duration::from_secs(60 + i as u64)));
+        }
+        dict.set("new".into(), 10, Some(Duration::from_secs(600)));
+        assert_eq!(dict.len(), 9);
+        // Room for a tenth is made: the two soonest to expire go.
+        assert!(dict.get("k2", |_| ()).is_none());
+        assert!(dict.get("k3", |_| ()).is_some());
+        assert!(dict.get("forever", |_| ()).is_some());
+        assert!(dict.get("new", |_| ()).is_some());
+    }
+
+    #[tokio::test]
+    async fn namespaces_are_shared_and_shrinkable() {
+        let a = shared_dict::<u64>("shared-dict-test", 100).unwrap();
+        let b = shared_dict::<u64>("shared-dict-test", 100).unwrap();
+        assert!(Arc::ptr_eq(&a, &b));
+        assert!(shared_dict::<String>("shared-dict-test", 100).is_err());
+
+        for i in 0..10 {
+            a.set(format!("k{i}"), i, None);
+        }
+        let per_entry = a.usage() / 10;
+        a.shrink(per_entry * 4).await;
+        assert_eq!(b.len(), 4);
+    }
+}
@@ -0,0 +1,115 @@
+//! Slow request log.
+//!
+//! Requests whose total latency exceeds `pingsix.slow_log.threshold_ms` are logged at
+//! `warn` level under the `pingsix::slow_log` target, as one JSON object with the
+//! route match, per-phase plugin, upstream connect and TTFB timings of the request.
+
+use std::time::Duration;
+
+use once_cell::sync::OnceCell;
+use pingora_proxy::Session;
+use serde_json::{json, Map, Value as JsonValue};
+
+use super::ProxyContext;
+use crate::config::SlowLog;
+
+static THRESHOLD: OnceCell<Duration> = OnceCell::new();
+
+/// Enable the slow request log. Called once at startup; later calls are no-ops.
+pub fn init(config: Option<SlowLog>) {
+    if let Some(config) = config {
+        let _ = THRESHOLD.set(Duration::from_millis(config.threshold_ms));
+    }
+}
+
+/// Log the request if it took longer than the configured threshold.
+pub fn record(session: &Session, ctx: &ProxyContext) {
+    let Some(threshold) = THRESHOLD.get() else {
+        return;
+    };
+    let total = ctx.request_start.elapsed();
+    if total <= *threshold {
+        return;
+    }
+    let req = session.req_header();
+    let status = session.response_written().map(|resp| resp.status.as_u16());
+    let upstream = ctx.peer.as_ref().map(|peer| peer._address.to_string());
+    log::warn!(
+        target: "pingsix::slow_log",
+        "{}",
+        entry(
+            ctx,
+            total,
+            req.method.as_str(),
+            req.uri.path(),
+            status,
+            upstream
+        )
+    );
+}
+
+fn entry(
+    ctx: &ProxyContext,
+    total: Duration,
+    method: &str,
+    path: &str,
+    status: Option<u16>,
+    upstream: Option<String>,
+) -> JsonValue {
+    let plugins: Map<String, JsonValue> = ctx
+        .timings
+        .plugin_phases()
+        .iter()
+        .filter(|(_, elapsed)| !elapsed.is_zero())
+        .map(|(phase, elapsed)| (phase.to_string(), millis(*elapsed).into()))
+        .collect();
+    json!({
+        "request_id": ctx.request_id,
+        "route_id": ctx.route.as_ref().map(|route| route.id()),
+        "method": method,
+        "path": path,
+        "status": status,
+        "upstream_addr": upstream,
+        "total_ms": millis(total),
+        "route_match_ms": millis(ctx.timings.route_match),
+        "plugins_ms": millis(ctx.timings.plugins()),
+        "plugin_phases_ms": plugins,
+        "upstream_connect_ms": ctx.upstream_info.connect_time().map(millis),
+        "upstream_ttfb_ms": ctx.upstream_info.ttfb().map(millis),
+    })
+}
+
+/// Milliseconds with microsecond precision.
+pub fn millis(elapsed: Duration) -> f64 {
+    elapsed.as_micros() as f64 / 1000.0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn entry_breaks_down_latency() {
+        let mut ctx = ProxyContext::default();
+        ctx.timings.route_match = Duration::from_micros(250);
+        ctx.timings.request_filter = Duration::from_millis(3);
+        ctx.timings.response_filter = Duration::from_millis(1);
+
+        let entry = entry(
+            &ctx,
+            Duration::from_millis(1500),
+            "GET",
+            "/slow",
+            Some(200),
+            None,
+        );
+        assert_eq!(entry["total_ms"], 1500.0);
+        assert_eq!(entry["route_match_ms"], 0.25);
+        assert_eq!(entry["plugins_ms"], 4.0);
+        assert_eq!(
+            entry["plugin_phases_ms"],
+            json!({"request_filter": 3.0, "response_filter": 1.0})
+        );
+        assert!(entry["upstream_ttfb_ms"].is_null());
+    }
+}
@@ -6,12 +6,16 @@ use std::time::{Duration, Instant};
 use once_cell::sync::Lazy;
 use serde::Serialize;
 
+use super::metrics;
+
 /// Configuration source type for better error reporting
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
 #[serde(rename_all = "lowercase")]
 pub enum ConfigSource {
     Yaml,
     Etcd,
+    Redis,
+    Xds,
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
@@ -26,6 +30,32 @@ impl ConfigSource {
         match self {
             ConfigSource::Yaml => "yaml",
             ConfigSource::Etcd => "etcd",
+            ConfigSource::Redis => "redis",
+            ConfigSource::Xds => "xds",
+        }
+    }
+
+    /// Whether the source is continuously synced (and can therefore go stale).
+    pub fn is_dynamic(&self) -> bool {
+        !matches!(self, ConfigSource::Yaml)
+    }
+}
+
+/// A static resource left out of the runtime under the tolerant load policy.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct SkippedResource {
+    /// Resource type as used by the Admin API, e.g. `routes`.
+    pub resource_type: String,
+    pub id: String,
+    pub error: String,
+}
+
+impl SkippedResource {
+    pub fn new(resource_type: &str, id: &str, error: impl ToString) -> Self {
+        Self {
+            resource_type: resource_type.to_string(),
+            id: id.to_string(),
+            error: error.to_string(),
         }
     }
 }
@@ -41,12 +71,20 @@ pub struct RuntimeStatusView {
     pub published_revision: Option<i64>,
     /// Alias of `observed_revision` for backward-compatible clients.
     pub revision: Option<i64>,
+    /// Version of the served configuration: the published store revision, or a
+    /// digest of the file for static YAML.
+    pub config_revision: Option<String>,
     pub connected: bool,
     pub degraded: bool,
     pub degraded_reason: Option<String>,
     pub last_success_age_secs: Option<u64>,
     pub error_kind: Option<ConfigErrorKind>,
     pub last_error: Option<String>,
+    /// The process is handing its listeners over to a successor (or shutting down).
+    pub draining: bool,
+    /// Static resources the last load left out.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub skipped_resources: Vec<SkippedResource>,
 }
 
 struct RuntimeStatusInner {
@@ -62,6 +100,9 @@ struct RuntimeStatusInner {
     awaiting_publish_after_reconnect: bool,
     config_stale_after: Duration,
     fail_readiness_when_stale: bool,
+    draining: bool,
+    skipped_resources: Vec<SkippedResource>,
+    config_digest: Option<String>,
 }
 
 impl Default for RuntimeStatusInner {
@@ -79,6 +120,9 @@ impl Default for RuntimeStatusInner {
             awaiting_publish_after_reconnect: false,
             config_stale_after: Duration::from_secs(300),
             fail_readiness_when_stale: true,
+            draining: false,
+            skipped_resources: Vec::new(),
+            config_digest: None,
         }
     }
 }
@@ -110,7 +154,14 @@ pub fn mark_ready(source: ConfigSource) {
     );
 }
 
-pub fn mark_etcd_connected(connected: bool) {
+/// Replace the static resources reported as skipped by the latest load.
+pub fn set_skipped_resources(skipped: Vec<SkippedResource>) {
+    metrics::SKIPPED_RESOURCES.set(skipped.len() as i64);
+    let mut status = STATUS.lock().unwrap_or_else(|e| e.into_inner());
+    status.skipped_resources = skipped;
+}
+
+pub fn mark_connected(connected: bool) {
     let mut status = STATUS.lock().unwrap_or_else(|e| e.into_inner());
     if connected {
         status.disconnected_since = None;
@@ -120,11 +171,11 @@ pub fn mark_etcd_connected(connected: bool) {
     status.connected = connected;
 }
 
-/// Identify etcd as the active configuration source without claiming a valid
+/// Identify the active dynamic configuration source without claiming a valid
 /// snapshot has been published yet.
-pub fn begin_etcd_sync() {
+pub fn begin_sync(source: ConfigSource) {
     let mut status = STATUS.lock().unwrap_or_else(|e| e.into_inner());
-    status.config_source = Some(ConfigSource::Etcd);
+    status.config_source = Some(source);
 }
 
 pub fn set_revision(revision: Option<i64>) {
@@ -132,10 +183,33 @@ pub fn set_revision(revision: Option<i64>) {
     status.revision = revision;
 }
 
+/// Record the digest of the static YAML being published; returns the previous one.
+pub fn set_config_digest(digest: Option<String>) -> Option<String> {
+    let mut status = STATUS.lock().unwrap_or_else(|e| e.into_inner());
+    std::mem::replace(&mut status.config_digest, digest)
+}
+
+/// Version of the served configuration, see [`RuntimeStatusView::config_revision`].
+pub fn config_revision() -> Option<String> {
+    let status = STATUS.lock().unwrap_or_else(|e| e.into_inner());
+    config_revision_of(&status)
+}
+
+fn config_revision_of(status: &RuntimeStatusInner) -> Option<String> {
+    match &status.config_digest {
+        Some(digest) if !status.config_source.is_some_and(|s| s.is_dynamic()) => {
+            Some(format!("sha256:{}", &digest[..digest.len().min(16)]))
+        }
+        _ => status
+            .published_revision
+            .map(|revision| revision.to_string()),
+    }
+}
+
 pub fn set_published_revision(revision: i64) {
     let mut status = STATUS.lock().unwrap_or_else(|e| e.into_inner());
     status.published_revision = Some(revision);
-    if status.config_source == Some(ConfigSource::Etcd) {
+    if status.config_source.is_some_and(|s| s.is_dynamic()) {
         status.initialized = true;
         status.last_success = Some(Instant::now());
         status.error_kind = None;
@@ -185,6 +259,16 @@ pub fn record_preparation_error(error: String) {
     status.last_error = Some(error);
 }
 
+/// Fail readiness for the rest of the process lifetime: listeners are being handed to
+/// an upgraded process or the server is shutting down.
+pub fn mark_draining() {
+    let mut status = STATUS.lock().unwrap_or_else(|e| e.into_inner());
+    if !status.draining {
+        status.draining = true;
+        log::info!("Service is draining, readiness disabled");
+    }
+}
+
 /// Check if the service is ready to handle traffic.
 pub fn is_ready() -> bool {
     let status = STATUS.lock().unwrap_or_else(|e| e.into_inner());
@@ -201,6 +285,8 @@ pub fn readiness() -> (bool, Option<&'static str>) {
     let ready = compute_ready(&status);
     let reason = if ready {
         None
+    } else if status.draining {
+        Some("draining")
     } else if !status.initialized {
         Some("not_initialized")
     } else if is_stale(&status) {
@@ -215,16 +301,27 @@ pub fn status_view() -> RuntimeStatusView {
     let status = STATUS.lock().unwrap_or_else(|e| e.into_inner());
     let stale = is_stale(&status);
     let ready = compute_ready(&status);
-    let degraded =
-        status.initialized && (!status.connected || stale || status.error_kind.is_some());
+    let degraded = status.initialized
+        && (!status.connected
+            || stale
+            || status.error_kind.is_some()
+            || !status.skipped_resources.is_empty());
     let degraded_reason = if !status.initialized {
         None
     } else if !status.connected {
-        Some("etcd disconnected".into())
+        Some(format!(
+            "{} disconnected",
+            status.config_source.map_or("etcd", |s| s.as_str())
+        ))
     } else if stale {
         Some("configuration sync is stale".into())
     } else if status.error_kind == Some(ConfigErrorKind::CandidateInvalid) {
         Some("latest configuration candidate is invalid".into())
+    } else if !status.skipped_resources.is_empty() {
+        Some(format!(
+            "{} static resources skipped",
+            status.skipped_resources.len()
+        ))
     } else {
         None
     };
@@ -236,17 +333,20 @@ pub fn status_view() -> RuntimeStatusView {
         observed_revision: status.revision,
         published_revision: status.published_revision,
         revision: status.revision,
+        config_revision: config_revision_of(&status),
         connected: status.connected,
         degraded,
         degraded_reason,
         last_success_age_secs: status.last_success.map(|t| t.elapsed().as_secs()),
         error_kind: status.error_kind,
         last_error: status.last_error.clone(),
+        draining: status.draining,
+        skipped_resources: status.skipped_resources.clone(),
     }
 }
 
 fn compute_ready(status: &RuntimeStatusInner) -> bool {
-    if !status.initialized || status.awaiting_publish_after_reconnect {
+    if !status.initialized || status.awaiting_publish_after_reconnect || status.draining {
         return false;
     }
     if status.fail_readiness_when_stale && is_stale(status) {
@@ -256,8 +356,8 @@ fn compute_ready(status: &RuntimeStatusInner) -> bool {
 }
 
 fn is_stale(status: &RuntimeStatusInner) -> bool {
-    // Static YAML has no continuous sync; only etcd sync can become stale.
-    if status.config_source != Some(ConfigSource::Etcd) {
+    // Static YAML has no continuous sync; only etcd/redis sync can become stale.
+    if !status.config_source.is_some_and(|s| s.is_dynamic()) {
         return false;
     }
     // A healthy idle watch (connected, possibly no config events) is not stale.
@@ -300,6 +400,24 @@ mod tests {
         assert!(is_ready());
     }
 
+    #[test]
+    fn test_config_revision_follows_source() {
+        let _guard = TEST_LOCK.lock().unwrap();
+        reset();
+        set_config_digest(Some("0123456789abcdef0123".into()));
+        mark_ready(ConfigSource::Yaml);
+        set_published_revision(0);
+        assert_eq!(
+            config_revision().as_deref(),
+            Some("sha256:0123456789abcdef")
+        );
+
+        reset();
+        mark_ready(ConfigSource::Etcd);
+        set_published_revision(42);
+        assert_eq!(status_view().config_revision.as_deref(), Some("42"));
+    }
+
     #[test]
     fn test_mark_ready_etcd() {
         let _guard = TEST_LOCK.lock().unwrap();
@@ -319,13 +437,24 @@ mod tests {
         assert!(is_ready());
     }
 
+    #[test]
+    fn draining_fails_readiness() {
+        let _guard = TEST_LOCK.lock().unwrap();
+        reset();
+        mark_ready(ConfigSource::Yaml);
+        mark_draining();
+        assert!(!is_ready());
+        assert_eq!(readiness(), (false, Some("draining")));
+        assert!(status_view().draining);
+    }
+
     #[test]
     fn stale_fails_readiness_by_default() {
         let _guard = TEST_LOCK.lock().unwrap();
         reset();
         configure_status_policy(0, true);
         mark_ready(ConfigSource::Etcd);
-        mark_etcd_connected(false);
+        mark_connected(false);
         // Disconnected etcd with zero threshold: any elapsed time is stale.
         std::thread::sleep(Duration::from_millis(5));
         assert!(!is_ready());
@@ -337,9 +466,9 @@ mod tests {
         let _guard = TEST_LOCK.lock().unwrap();
         reset();
         configure_status_policy(0, true);
-        begin_etcd_sync();
+        begin_sync(ConfigSource::Etcd);
         record_sync_success(10);
-        mark_etcd_connected(false);
+        mark_connected(false);
         std::thread::sleep(Duration::from_millis(5));
         assert!(!is_ready());
         record_sync_success(11);
@@ -355,7 +484,7 @@ mod tests {
         reset();
         configure_status_policy(0, true);
         mark_ready(ConfigSource::Etcd);
-        mark_etcd_connected(true);
+        mark_connected(true);
         std::thread::sleep(Duration::from_millis(5));
         assert!(is_ready());
         assert!(!status_view().degraded);
@@ -368,8 +497,8 @@ mod tests {
         reset();
         configure_status_policy(60, true);
         mark_ready(ConfigSource::Etcd);
-        mark_etcd_connected(true);
-        mark_etcd_connected(false);
+        mark_connected(true);
+        mark_connected(false);
         assert!(is_ready());
         configure_status_policy(300, true);
     }
@@ -380,9 +509,9 @@ mod tests {
         reset();
         configure_status_policy(0, true);
         mark_ready(ConfigSource::Etcd);
-        mark_etcd_connected(true);
+        mark_connected(true);
         assert!(is_ready());
-        mark_etcd_connected(false);
+        mark_connected(false);
         std::thread::sleep(Duration::from_millis(5));
         assert!(!is_ready());
         configure_status_policy(300, false);
@@ -392,9 +521,9 @@ mod tests {
     fn preparation_error_has_stable_diagnostic_category() {
         let _guard = TEST_LOCK.lock().unwrap();
         reset();
-        begin_etcd_sync();
+        begin_sync(ConfigSource::Etcd);
         mark_ready(ConfigSource::Etcd);
-        mark_etcd_connected(true);
+        mark_connected(true);
         record_preparation_error("resolver leaked internal.example".into());
         let view = status_view();
         assert_eq!(view.error_kind, Some(ConfigErrorKind::CandidateInvalid));
@@ -3,9 +3,11 @@
 //! It defines the main modules for configuration, proxying, and service management.
 
 pub mod admin;
+pub mod cli;
 pub mod config;
 pub mod core;
 pub mod logging;
+pub mod migration;
 pub mod plugins;
 pub mod proxy;
 pub mod service;
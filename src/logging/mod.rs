@@ -13,8 +13,11 @@ use prometheus::{register_int_counter, register_int_counter_vec, IntCounter, Int
 use tokio::{
     fs::{create_dir_all, metadata, OpenOptions},
     io::{AsyncWriteExt, BufWriter},
-    sync::mpsc::{channel, Receiver, Sender},
-    time::{interval, Duration},
+    sync::{
+        mpsc::{channel, Receiver, Sender},
+        Notify,
+    },
+    time::{interval, sleep_until, Duration, Instant},
 };
 
 use crate::config;
@@ -69,6 +72,40 @@ static LOG_ROTATIONS: Lazy<IntCounter> = Lazy::new(|| {
 });
 static DROP_SUMMARY: AtomicU64 = AtomicU64::new(0);
 
+/// Upper bound on draining accepted lines once the writer stops.
+const SHUTDOWN_DRAIN: Duration = Duration::from_secs(2);
+
+/// Pending request to reopen the log file, e.g. after an external logrotate rename.
+static REOPEN: Lazy<Notify> = Lazy::new(Notify::new);
+
+/// Ask the logging service to flush and reopen its file at the configured path.
+pub fn request_reopen() {
+    REOPEN.notify_one();
+}
+
+/// Point stderr at a freshly opened `path`: Pingora's `error_log`, which a daemonized
+/// server writes stderr to, after an external logrotate rename.
+#[cfg(unix)]
+pub fn reopen_error_log(path: &str) -> io::Result<()> {
+    reopen_onto(path, libc::STDERR_FILENO)
+}
+
+/// Open `path` for appending and make `target` refer to it.
+#[cfg(unix)]
+fn reopen_onto(path: &str, target: std::os::fd::RawFd) -> io::Result<()> {
+    use std::os::fd::AsRawFd;
+
+    let file = std::fs::OpenOptions::new()
+        .append(true)
+        .create(true)
+        .open(path)?;
+    // SAFETY: both descriptors are valid for the duration of the call.
+    if unsafe { libc::dup2(file.as_raw_fd(), target) } < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(())
+}
+
 pub struct AsyncWriter {
     sender: Sender<Vec<u8>>,
     /// Shared flag set once the Logger service has given up on the file and
@@ -118,6 +155,10 @@ pub struct Logger {
     receiver: Receiver<Vec<u8>>,
     config: config::Log,
     stopped: Arc<AtomicBool>,
+    /// How long to keep writing after the shutdown signal before draining.
+    shutdown_linger: Duration,
+    /// Threads of the runtime doing the file IO.
+    threads: usize,
 }
 
 async fn rotate_log_file(path: &str, max_backups: u32) -> io::Result<()> {
@@ -166,6 +207,16 @@ async fn rotate_log_file(path: &str, max_backups: u32) -> io::Result<()> {
     tokio::fs::rename(&path, format!("{path}.1")).await
 }
 
+/// Delay until the next UTC-aligned boundary of `period`.
+fn until_next_period(period: config::LogRotatePeriod) -> Duration {
+    let period = period.as_secs();
+    let now = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs();
+    Duration::from_secs(period - now % period)
+}
+
 impl Logger {
     pub fn new(config: config::Log) -> Self {
         // Bounded channel with configurable buffer size (default: 1024)
@@ -175,9 +226,24 @@ impl Logger {
             receiver,
             config,
             stopped: Arc::new(AtomicBool::new(false)),
+            shutdown_linger: Duration::ZERO,
+            threads: 1,
         }
     }
 
+    /// Run the writer on `threads` threads instead of one.
+    pub fn with_threads(mut self, threads: usize) -> Self {
+        self.threads = threads.max(1);
+        self
+    }
+
+    /// Keep logging through the server's shutdown grace period so lines from requests
+    /// finishing during it still reach the file, then drain before runtimes stop.
+    pub fn with_shutdown_grace_period(mut self, grace_period: Duration) -> Self {
+        self.shutdown_linger = grace_period.saturating_sub(SHUTDOWN_DRAIN);
+        self
+    }
+
     fn create_async_writer(&self) -> AsyncWriter {
         AsyncWriter {
             sender: self.sender.clone(),
@@ -254,15 +320,49 @@ impl Service for Logger {
         // Use configurable flush interval (default: 5 seconds)
         let mut flush_interval = interval(Duration::from_secs(5));
         let mut fail_count: u64 = 0;
+        let next_rotation_at = |config: &config::Log| {
+            config
+                .rotate
+                .filter(|_| config.rotation == config::LogRotation::Internal)
+                .map(|period| tokio::time::Instant::now() + until_next_period(period))
+        };
+        let mut next_rotation = next_rotation_at(&self.config);
+        let mut linger_until: Option<Instant> = None;
 
         loop {
             tokio::select! {
                 biased;
                 // Shutdown signal handling
-                _ = shutdown.changed() => {
+                _ = shutdown.changed(), if linger_until.is_none() => {
                     if *shutdown.borrow() {
-                        log::debug!("Shutdown signal received, draining log writer");
-                        break;
+                        if self.shutdown_linger.is_zero() {
+                            log::debug!("Shutdown signal received, draining log writer");
+                            break;
+                        }
+                        log::debug!("Shutdown signal received, logging until the grace period ends");
+                        linger_until = Some(Instant::now() + self.shutdown_linger);
+                    }
+                },
+                _ = sleep_until(linger_until.unwrap_or_else(Instant::now)), if linger_until.is_some() => {
+                    log::debug!("Shutdown grace period over, draining log writer");
+                    break;
+                },
+                _ = REOPEN.notified() => {
+                    if let Some(mut file) = file_writer.take() {
+                        if let Err(e) = file.flush().await {
+                            Self::report_write_failure(&mut fail_count, log_file_path, e).await;
+                        }
+                    }
+                    match open_log_file(log_file_path).await {
+                        Ok(file) => {
+                            file_writer = Some(BufWriter::with_capacity(4096, file));
+                            self.stopped.store(false, Ordering::Relaxed);
+                            fail_count = 0;
+                        }
+                        Err(e) => {
+                            Self::report_write_failure(&mut fail_count, log_file_path, e).await;
+                            self.stopped.store(true, Ordering::Relaxed);
+                        }
                     }
                 },
                 _ = flush_interval.tick() => {
@@ -323,15 +423,17 @@ impl Service for Logger {
 
                                 // Rotate between messages, preserving the active writer's
                                 // ordering and bounding retained disk use.
+                                let period_elapsed = next_rotation
+                                    .is_some_and(|at| tokio::time::Instant::now() >= at);
                                 let should_rotate = self.config.rotation == config::LogRotation::Internal
-                                    && match file_writer.as_ref() {
+                                    && (period_elapsed || match file_writer.as_ref() {
                                     Some(file) => file
                                         .get_ref()
                                         .metadata()
                                         .await
-                                        .is_ok_and(|metadata| metadata.len() >= self.config.max_size_bytes),
+                                        .is_ok_and(|metadata| metadata.len() >= self.config.max_size_bytes()),
                                     None => false,
-                                };
+                                });
                                 if should_rotate {
                                     if let Some(file) = file_writer.as_mut() {
                                         if let Err(e) = file.flush().await {
@@ -349,6 +451,7 @@ impl Service for Logger {
                                         match open_log_file(log_file_path).await {
                                             Ok(file) => {
                                                 file_writer = Some(BufWriter::with_capacity(4096, file));
+                                                next_rotation = next_rotation_at(&self.config);
                                                 LOG_ROTATIONS.inc();
                                             }
                                             Err(e) => {
@@ -428,7 +531,6 @@ impl Service for Logger {
 
         // Drain already-accepted lines within a bounded deadline so shutdown
         // does not lose the final request/error/control-plane logs.
-        const SHUTDOWN_DRAIN: Duration = Duration::from_secs(2);
         let deadline = tokio::time::Instant::now() + SHUTDOWN_DRAIN;
         let mut drained = 0u64;
         let mut dropped = 0u64;
@@ -479,7 +581,7 @@ impl Service for Logger {
     }
 
     fn threads(&self) -> Option<usize> {
-        Some(1)
+        Some(self.threads)
     }
 }
 
@@ -491,12 +593,21 @@ mod tests {
     fn config_for(path: impl Into<String>) -> config::Log {
         config::Log {
             path: path.into(),
-            max_size_bytes: 100 * 1024 * 1024,
+            max_size_bytes: None,
             max_backups: 5,
             rotation: config::LogRotation::Internal,
+            rotate: None,
         }
     }
 
+    #[test]
+    fn period_boundaries_are_within_one_period() {
+        let hourly = until_next_period(config::LogRotatePeriod::Hourly);
+        assert!(hourly > Duration::ZERO && hourly <= Duration::from_secs(3600));
+        let daily = until_next_period(config::LogRotatePeriod::Daily);
+        assert!(daily > Duration::ZERO && daily <= Duration::from_secs(86400));
+    }
+
     #[tokio::test]
     async fn rotation_retains_configured_backups() {
         let path = std::env::temp_dir().join(format!("pingsix-log-{}", std::process::id()));
@@ -512,6 +623,30 @@ mod tests {
         let _ = std::fs::remove_file(format!("{}.2", path.display()));
     }
 
+    /// After a logrotate rename, writes through the reopened descriptor land in a
+    /// fresh file at the configured path, not in the renamed one.
+    #[cfg(unix)]
+    #[test]
+    fn reopen_moves_writes_to_the_new_file() {
+        use std::os::fd::AsRawFd;
+
+        let path = std::env::temp_dir().join(format!("pingsix-error-{}.log", std::process::id()));
+        let rotated = path.with_extension("log.1");
+        let path_str = path.to_str().unwrap();
+        // Stand-in for stderr, so the test harness output is left alone.
+        let mut target = std::fs::File::create(&path).unwrap();
+        target.write_all(b"before\n").unwrap();
+        std::fs::rename(&path, &rotated).unwrap();
+
+        reopen_onto(path_str, target.as_raw_fd()).unwrap();
+        target.write_all(b"after\n").unwrap();
+
+        assert_eq!(std::fs::read_to_string(&rotated).unwrap(), "before\n");
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "after\n");
+        let _ = std::fs::remove_file(&path);
+        let _ = std::fs::remove_file(&rotated);
+    }
+
     /// `AsyncWriter::write` must never panic and must report `Ok` even when the
     /// channel is closed or full, and must not re-enter the logging channel
     /// (it writes to stderr instead).
@@ -535,6 +670,36 @@ mod tests {
         }
     }
 
+    /// Lines logged during the shutdown grace period still reach the file.
+    #[tokio::test]
+    async fn shutdown_linger_keeps_writing_until_deadline() {
+        let path =
+            std::env::temp_dir().join(format!("pingsix-log-linger-{}.log", std::process::id()));
+        let mut logger = Logger::new(config_for(path.to_string_lossy()))
+            .with_shutdown_grace_period(SHUTDOWN_DRAIN + Duration::from_millis(300));
+        let (shutdown_tx, shutdown_rx) = watch::channel(false);
+        let sender = logger.sender.clone();
+        let handle = tokio::spawn(async move {
+            logger.start_service(None, shutdown_rx, 0).await;
+        });
+
+        shutdown_tx.send(true).unwrap();
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        assert!(
+            !handle.is_finished(),
+            "writer stopped before the linger deadline"
+        );
+        sender.try_send(b"late line\n".to_vec()).unwrap();
+
+        tokio::time::timeout(Duration::from_secs(3), handle)
+            .await
+            .expect("writer did not stop after the linger deadline")
+            .unwrap();
+        let written = std::fs::read_to_string(&path).unwrap();
+        assert!(written.contains("late line"));
+        let _ = std::fs::remove_file(&path);
+    }
+
     /// When the log file cannot be opened, the service must not silently
     /// `return` and drop every subsequent log line. It stays alive, drains the
     /// channel to stderr, and shuts down cleanly within a timeout.
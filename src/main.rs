@@ -1,30 +1,43 @@
-use std::ops::DerefMut;
+use std::{ops::DerefMut, time::Duration};
 
+use clap::Parser;
 use pingora::services::listening::Service;
 use pingora_core::{
     apps::HttpServerOptions,
-    listeners::tls::TlsSettings,
-    server::{configuration::Opt, Server},
+    listeners::{tls::TlsSettings, TcpSocketOptions},
+    protocols::{http::v2::server::default_h2_options, TcpKeepalive},
+    server::{RunArgs, Server},
 };
 use pingora_proxy::{http_proxy_service_with_name, HttpProxy};
 use sentry::IntoDsn;
 
 use pingsix::admin::AdminHttpApp;
-use pingsix::config::{self, etcd::EtcdConfigSync, Config};
+use pingsix::cli::Cli;
+use pingsix::config::{
+    self, etcd::EtcdProvider, provider::ConfigSync, redis::RedisProvider, xds::XdsProvider, Config,
+};
 use pingsix::core;
 use pingsix::logging::Logger;
 use pingsix::proxy::{
     control_plane::load_static_configurations, event::ProxyEventHandler, ssl::DynamicCert,
     upstream::SHARED_HEALTH_CHECK_SERVICE,
 };
-use pingsix::service::{http::HttpService, status::StatusHttpApp};
+use pingsix::service::{
+    http::HttpService,
+    signal::{DrainingShutdownSignal, SignalService},
+    status::StatusHttpApp,
+};
 
 // Service name constants
 const PINGSIX_SERVICE: &str = "pingsix";
 
 fn main() {
     // Parse CLI args and load config - exit early on failure to prevent silent misconfiguration
-    let cli_options = Opt::parse_args();
+    let cli = Cli::parse();
+    if let Some(command) = cli.command {
+        std::process::exit(command.run());
+    }
+    let cli_options = cli.server;
     let config = match Config::load_yaml_with_opt_override(&cli_options) {
         Ok(cfg) => cfg,
         Err(e) => {
@@ -33,9 +46,19 @@ fn main() {
         }
     };
 
+    let runtime_cfg = config.pingsix.runtime.clone().unwrap_or_default();
+
     // Setup logging early to capture all subsequent initialization events
     let logger = if let Some(log_cfg) = &config.pingsix.log {
-        let logger = Logger::new(log_cfg.clone());
+        // Keep logging while in-flight requests finish so the writer drains last.
+        let grace_period = config
+            .pingora
+            .grace_period_seconds
+            .unwrap_or(config::DEFAULT_GRACE_PERIOD_SECS);
+        let log_threads = runtime_cfg.log_threads.unwrap_or(1);
+        let logger = Logger::new(log_cfg.clone())
+            .with_shutdown_grace_period(Duration::from_secs(grace_period))
+            .with_threads(log_threads);
         logger.init_env_logger();
         Some(logger)
     } else {
@@ -43,19 +66,56 @@ fn main() {
         None
     };
 
+    // Threads inherit the CPU mask of the thread creating them, so pin before any runtime starts.
+    if let Some(cpus) = &runtime_cfg.cpu_affinity {
+        if let Err(e) = pin_to_cpus(cpus) {
+            log::error!("Failed to apply runtime.cpu_affinity {cpus:?}: {e}");
+            std::process::exit(1);
+        }
+        log::info!("Gateway threads pinned to CPUs {cpus:?}");
+    }
+
     // Defaults must be initialized before any plugin/upstream build so static YAML
     // snapshots bake in `pingsix.defaults` (cache object size, upstream timeout).
     init_pingsix_defaults(&config.pingsix);
+    // Set up before the first publish so the initial route table is reported too.
+    let config_webhook =
+        pingsix::proxy::config_webhook::init(config.pingsix.config_webhook.clone());
+    // Before any cache consumer is built, so each one registers under the limit.
+    let memory_tuner = pingsix::core::memory::init(
+        config.pingsix.memory_limit,
+        pingsix::service::http::cache_memory(),
+    );
 
-    // Choose config source: etcd for dynamic updates in distributed env, or static file for simple setups
-    let etcd_sync = if let Some(etcd_cfg) = &config.pingsix.etcd {
+    // Choose config source: etcd, Redis or xDS for dynamic updates in distributed env, or static file for simple setups
+    let config_sync = if let Some(etcd_cfg) = &config.pingsix.etcd {
         log::debug!(
             "Initializing etcd config sync with prefix: {}",
             etcd_cfg.prefix
         );
         let event_handler = ProxyEventHandler::new(etcd_cfg.prefix.clone());
-        Some(EtcdConfigSync::new(
-            etcd_cfg.clone(),
+        Some(ConfigSync::new(
+            Box::new(EtcdProvider::new(etcd_cfg.clone())),
+            Box::new(event_handler),
+        ))
+    } else if let Some(redis_cfg) = &config.pingsix.redis {
+        log::debug!(
+            "Initializing redis config sync with prefix: {}",
+            redis_cfg.prefix
+        );
+        let event_handler = ProxyEventHandler::new(redis_cfg.prefix.clone());
+        Some(ConfigSync::new(
+            Box::new(RedisProvider::new(redis_cfg.clone())),
+            Box::new(event_handler),
+        ))
+    } else if let Some(xds_cfg) = &config.pingsix.xds {
+        log::debug!(
+            "Initializing xDS config sync from control plane: {}",
+            xds_cfg.address
+        );
+        let event_handler = ProxyEventHandler::new(config::xds::XDS_PREFIX);
+        Some(ConfigSync::new(
+            Box::new(XdsProvider::new(xds_cfg.clone())),
             Box::new(event_handler),
         ))
     } else {
@@ -67,6 +127,12 @@ fn main() {
         None
     };
 
+    // SIGHUP only reloads static YAML; etcd/Redis/xDS stay authoritative when configured.
+    let reload_path = if config.pingsix.has_dynamic_store() {
+        None
+    } else {
+        cli_options.conf.clone()
+    };
     let mut pingsix_server = Server::new_with_opt_and_conf(Some(cli_options), config.pingora);
 
     // Register logger service to enable centralized log handling across all workers
@@ -75,11 +141,11 @@ fn main() {
         pingsix_server.add_service(log_service);
     }
 
-    // Register etcd service for real-time config synchronization in cluster deployments
-    if let Some(etcd_service) = etcd_sync {
-        log::debug!("Initializing etcd config sync service");
-        pingsix_server.add_service(etcd_service);
-    }
+    // Register config sync service for real-time config synchronization in cluster deployments
+    let config_sync_handle = config_sync.map(|sync_service| {
+        log::debug!("Initializing config sync service");
+        pingsix_server.add_service(sync_service)
+    });
 
     // Create main HTTP proxy service - core request handling logic
     let mut http_service = http_proxy_service_with_name(
@@ -88,25 +154,63 @@ fn main() {
         PINGSIX_SERVICE,
     );
 
+    http_service.threads = runtime_cfg.threads.proxy;
+
     log::debug!("Configuring listeners");
     if let Err(e) = add_listeners(&mut http_service, &config.pingsix) {
         log::error!("Failed to add listeners: {e}");
         std::process::exit(1);
     }
 
+    log::debug!("Initializing signal handler service");
+    let execution_phase = pingsix_server.watch_execution_phase();
+    // Pingora only sends stderr to `error_log` when it daemonizes.
+    let error_log = pingsix_server
+        .configuration
+        .error_log
+        .clone()
+        .filter(|_| pingsix_server.configuration.daemon);
+    pingsix_server.add_service(
+        SignalService::new(reload_path)
+            .with_execution_phase(execution_phase)
+            .with_error_log(error_log),
+    );
+
     // Shared health check service reduces overhead by consolidating upstream health monitoring
     log::debug!("Initializing shared health check service");
     pingsix_server.add_service(SHARED_HEALTH_CHECK_SERVICE.clone());
 
+    if let Some(webhook) = config_webhook {
+        pingsix_server.add_service(webhook);
+    }
+    if let Some(tuner) = memory_tuner {
+        pingsix_server.add_service(tuner);
+    }
+
     add_optional_services(&mut pingsix_server, &config.pingsix);
 
     log::info!("Starting pingsix server");
     pingsix_server.bootstrap();
     log::debug!("Server bootstrapped, adding services");
-    pingsix_server.add_service(http_service);
+    let http_handle = pingsix_server.add_service(http_service);
+    // Hold the proxy listeners (inherited during an upgrade) until the first dynamic
+    // configuration is published, so a new process never serves an empty route table.
+    if let Some(config_sync_handle) = &config_sync_handle {
+        http_handle.add_dependency(config_sync_handle);
+    }
 
     log::info!("Pingsix server running");
-    pingsix_server.run_forever();
+    let readiness_delay = config
+        .pingsix
+        .shutdown
+        .as_ref()
+        .map_or(0, |shutdown| shutdown.readiness_delay);
+    pingsix_server.run(RunArgs {
+        shutdown_signal: Box::new(DrainingShutdownSignal::new(Duration::from_secs(
+            readiness_delay,
+        ))),
+    });
+    std::process::exit(0);
 }
 
 /// Configures HTTP/HTTPS listeners with TLS settings.
@@ -117,7 +221,27 @@ fn add_listeners(
     http_service: &mut Service<HttpProxy<HttpService>>,
     cfg: &config::Pingsix,
 ) -> Result<(), Box<dyn std::error::Error>> {
+    // Server options are shared by every listener of the service.
+    let downstream = cfg.downstream.clone().unwrap_or_default();
+    let http_logic = http_service
+        .app_logic_mut()
+        .ok_or("Failed to get app logic")?;
+    let mut http_server_options = HttpServerOptions::default();
+    // Enable H2C (HTTP/2 over cleartext) for better performance without TLS overhead
+    http_server_options.h2c = cfg
+        .listeners
+        .iter()
+        .any(|list_cfg| list_cfg.tls.is_none() && list_cfg.offer_h2c);
+    http_server_options.keepalive_request_limit = downstream.keepalive_requests;
+    http_logic.server_options = Some(http_server_options);
+    if let Some(streams) = downstream.h2_max_concurrent_streams {
+        let mut h2_options = default_h2_options();
+        h2_options.max_concurrent_streams(streams);
+        http_logic.h2_options = Some(h2_options);
+    }
+
     for list_cfg in cfg.listeners.iter() {
+        let sock_opt = list_cfg.socket.as_ref().map(listener_socket_options);
         if let Some(tls) = &list_cfg.tls {
             let dynamic_cert = DynamicCert::new(tls).map_err(|e| {
                 std::io::Error::new(
@@ -139,28 +263,46 @@ fn add_listeners(
             if list_cfg.offer_h2 {
                 tls_settings.enable_h2();
             }
-            http_service.add_tls_with_settings(&list_cfg.address.to_string(), None, tls_settings);
+            http_service.add_tls_with_settings(
+                &list_cfg.address.to_string(),
+                sock_opt,
+                tls_settings,
+            );
+        } else if let Some(sock_opt) = sock_opt {
+            http_service.add_tcp_with_settings(&list_cfg.address.to_string(), sock_opt);
         } else {
-            // Enable H2C (HTTP/2 over cleartext) for better performance without TLS overhead
-            if list_cfg.offer_h2c {
-                let http_logic = http_service
-                    .app_logic_mut()
-                    .ok_or("Failed to get app logic")?;
-                let mut http_server_options = HttpServerOptions::default();
-                http_server_options.h2c = true;
-                http_logic.server_options = Some(http_server_options);
-            }
             http_service.add_tcp(&list_cfg.address.to_string());
         }
     }
     Ok(())
 }
 
+/// Translates `listeners[].socket` into Pingora socket options. `tcp_nodelay` is not
+/// part of them; it is applied per connection by the HTTP service.
+fn listener_socket_options(socket: &config::ListenerSocket) -> TcpSocketOptions {
+    let mut sock_opt = TcpSocketOptions::default();
+    sock_opt.tcp_keepalive = socket.tcp_keepalive.as_ref().map(|ka| TcpKeepalive {
+        idle: Duration::from_secs(ka.idle),
+        interval: Duration::from_secs(ka.interval),
+        count: ka.count,
+        #[cfg(target_os = "linux")]
+        user_timeout: Duration::ZERO,
+    });
+    sock_opt.tcp_fastopen = socket.tcp_fastopen;
+    sock_opt.so_reuseport = socket.reuseport;
+    sock_opt
+}
+
 /// Conditionally enables monitoring and admin services based on configuration.
 ///
 /// Invalid Sentry configuration only disables Sentry; Admin/Status/Prometheus still start.
 /// Admin interface is only available when etcd is enabled.
 fn add_optional_services(server: &mut Server, cfg: &config::Pingsix) {
+    let threads = cfg
+        .runtime
+        .as_ref()
+        .map(|runtime| runtime.threads.clone())
+        .unwrap_or_default();
     if let Some(sentry_cfg) = &cfg.sentry {
         if is_example_sentry_dsn(&sentry_cfg.dsn) {
             log::warn!("Ignoring example Sentry DSN, Sentry disabled");
@@ -184,6 +326,14 @@ fn add_optional_services(server: &mut Server, cfg: &config::Pingsix) {
         }
     }
 
+    if cfg.redis.is_some() && cfg.admin.is_some() {
+        log::warn!("Admin API writes to etcd only; it is disabled with the Redis config store");
+    }
+
+    if cfg.xds.is_some() && cfg.admin.is_some() {
+        log::warn!("Admin API writes to etcd only; it is disabled with the xDS control plane");
+    }
+
     if cfg.etcd.is_some() && cfg.admin.is_some() {
         if let Some(admin_cfg) = &cfg.admin {
             if let Err(e) = validate_admin_bind(admin_cfg) {
@@ -191,7 +341,8 @@ fn add_optional_services(server: &mut Server, cfg: &config::Pingsix) {
                 std::process::exit(1);
             }
             log::debug!("Configuring admin HTTP interface");
-            if let Some(admin_service_http) = AdminHttpApp::admin_http_service(cfg) {
+            if let Some(mut admin_service_http) = AdminHttpApp::admin_http_service(cfg) {
+                admin_service_http.threads = threads.admin;
                 server.add_service(admin_service_http);
                 log::info!("Admin HTTP interface enabled");
             } else {
@@ -200,6 +351,12 @@ fn add_optional_services(server: &mut Server, cfg: &config::Pingsix) {
         }
     }
 
+    log::debug!("Initializing certificate expiry monitor");
+    server.add_service(pingsix::proxy::cert_expiry::service(
+        cfg.cert_expiry.clone().unwrap_or_default(),
+        &cfg.listeners,
+    ));
+
     if let Some(status_cfg) = &cfg.status {
         status_cfg.log_bind_safety();
         core::status::configure_status_policy(
@@ -207,7 +364,8 @@ fn add_optional_services(server: &mut Server, cfg: &config::Pingsix) {
             status_cfg.fail_readiness_when_stale,
         );
         log::debug!("Configuring status HTTP endpoint on {}", status_cfg.address);
-        let status_service_http = StatusHttpApp::status_http_service(status_cfg);
+        let mut status_service_http = StatusHttpApp::status_http_service(status_cfg);
+        status_service_http.threads = threads.status;
         server.add_service(status_service_http);
         log::info!("Status HTTP endpoint enabled on {}", status_cfg.address);
     }
@@ -218,6 +376,7 @@ fn add_optional_services(server: &mut Server, cfg: &config::Pingsix) {
             prometheus_cfg.address
         );
         let mut prometheus_service_http = Service::prometheus_http_service();
+        prometheus_service_http.threads = threads.prometheus;
         prometheus_service_http.add_tcp(&prometheus_cfg.address.to_string());
         server.add_service(prometheus_service_http);
         log::info!(
@@ -238,13 +397,62 @@ fn init_pingsix_defaults(cfg: &config::Pingsix) {
     }
     if let Some(defaults) = &cfg.defaults {
         pingsix::config::init_dns_resolution_timeout(defaults.dns_resolution_timeout);
-        pingsix::config::init_dns_refresh_interval(defaults.dns_refresh_interval);
     }
+    pingsix::config::init_dns_refresh_interval(
+        cfg.dns
+            .as_ref()
+            .and_then(|dns| dns.refresh_interval)
+            .or_else(|| cfg.defaults.as_ref().and_then(|d| d.dns_refresh_interval)),
+    );
+    pingsix::config::init_dns_settings(cfg.dns.clone());
     pingsix::config::init_default_upstream_timeout(
         cfg.defaults
             .as_ref()
             .and_then(|d| d.upstream_timeout.clone()),
     );
+    pingsix::core::overload::init(cfg.overload.clone());
+    pingsix::core::maintenance::init(cfg.maintenance.clone());
+    pingsix::core::slow_log::init(cfg.slow_log.clone());
+    pingsix::core::plugin_metrics::init(
+        cfg.prometheus
+            .as_ref()
+            .is_some_and(|prometheus| prometheus.plugin_metrics),
+    );
+    pingsix::service::http::init_downstream(cfg.downstream.clone());
+    pingsix::service::http::init_listener_sockets(&cfg.listeners);
+    pingsix::core::header_hygiene::init(cfg.security.clone());
+    pingsix::config::init_gateway_zone(
+        cfg.zone
+            .clone()
+            .or_else(|| std::env::var("PINGSIX_ZONE").ok()),
+    );
+}
+
+/// Restricts the calling thread, and every thread it spawns afterwards, to `cpus`.
+#[cfg(target_os = "linux")]
+fn pin_to_cpus(cpus: &[usize]) -> std::io::Result<()> {
+    // SAFETY: `cpu_set_t` is a plain bitmap and config validation keeps every CPU
+    // index below `CPU_SETSIZE`.
+    let rc = unsafe {
+        let mut set: libc::cpu_set_t = std::mem::zeroed();
+        for &cpu in cpus {
+            libc::CPU_SET(cpu, &mut set);
+        }
+        libc::sched_setaffinity(0, std::mem::size_of::<libc::cpu_set_t>(), &set)
+    };
+    if rc == 0 {
+        Ok(())
+    } else {
+        Err(std::io::Error::last_os_error())
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+fn pin_to_cpus(_cpus: &[usize]) -> std::io::Result<()> {
+    Err(std::io::Error::new(
+        std::io::ErrorKind::Unsupported,
+        "CPU affinity is only supported on Linux",
+    ))
 }
 
 fn validate_admin_bind(admin_cfg: &config::Admin) -> Result<(), String> {
@@ -0,0 +1,799 @@
+//! Conversion of an APISIX etcd snapshot into pingsix resources.
+//!
+//! Routes, services, upstreams, global rules and SSLs map onto their pingsix
+//! counterparts. Consumers have no pingsix resource: their `key-auth` and `basic-auth`
+//! credentials are folded into the auth plugins of every route and service using them.
+
+use std::collections::{BTreeMap, HashSet};
+
+use serde::de::DeserializeOwned;
+use serde_json::{json, Map, Value as JsonValue};
+use validator::Validate;
+
+use super::MigrationReport;
+use crate::{
+    config::{GlobalRule, Route, Service, Upstream, SSL},
+    plugins::{self, ai_proxy, basic_auth, key_auth},
+};
+
+/// Route fields that narrow matching or attach plugins indirectly. Dropping them would
+/// widen the route, so routes using them are not converted.
+const UNSUPPORTED_ROUTE_FIELDS: &[&str] = &[
+    "vars",
+    "remote_addr",
+    "remote_addrs",
+    "filter_func",
+    "script",
+    "plugin_config_id",
+];
+
+/// APISIX `key-auth` reads the `apikey` query parameter unless told otherwise.
+const APISIX_KEY_AUTH_QUERY: &str = "apikey";
+
+#[derive(Debug, Default, Clone, Copy)]
+pub struct ConvertOptions {
+    /// Drop plugins that cannot be converted instead of skipping their resource.
+    pub drop_unsupported_plugins: bool,
+}
+
+/// Consumer credentials, as `(credential, consumer)` pairs.
+#[derive(Default)]
+struct Consumers {
+    key_auth: Vec<(String, String)>,
+    /// `((username, password), consumer)`.
+    basic_auth: Vec<((String, String), String)>,
+}
+
+struct Entry {
+    id: String,
+    /// Key relative to the APISIX prefix, used in the report.
+    source_key: String,
+    value: Map<String, JsonValue>,
+}
+
+/// Convert `(key relative to the APISIX prefix, value)` pairs into pingsix logical keys
+/// and JSON bodies. Everything skipped or approximated is recorded in `report`.
+pub fn convert(
+    kvs: &[(String, Vec<u8>)],
+    options: ConvertOptions,
+    report: &mut MigrationReport,
+) -> Vec<(String, Vec<u8>)> {
+    let mut entries: BTreeMap<&'static str, Vec<Entry>> = BTreeMap::new();
+    for (key, value) in kvs {
+        // Directory markers (`init_dir`) and other non-object values carry no resource.
+        let Ok(JsonValue::Object(value)) = serde_json::from_slice::<JsonValue>(value) else {
+            continue;
+        };
+        let Some((kind, id)) = key.split_once('/').filter(|(_, id)| !id.is_empty()) else {
+            continue;
+        };
+        let kind = match kind {
+            "routes" => "routes",
+            "services" => "services",
+            "upstreams" => "upstreams",
+            "global_rules" => "global_rules",
+            "ssls" | "ssl" => "ssls",
+            "consumers" => "consumers",
+            other => {
+                report.skip(key, format!("resource type '{other}' is not supported"));
+                continue;
+            }
+        };
+        entries.entry(kind).or_default().push(Entry {
+            id: id.to_string(),
+            source_key: key.clone(),
+            value,
+        });
+    }
+
+    let mut converter = Converter {
+        options,
+        consumers: Consumers::default(),
+        report,
+    };
+    converter.collect_consumers(entries.remove("consumers").unwrap_or_default());
+
+    let mut output = Vec::new();
+    let mut kept_upstreams = HashSet::new();
+    for entry in entries.remove("upstreams").unwrap_or_default() {
+        let result = converter
+            .convert_upstream(&entry.source_key, &entry.value)
+            .and_then(|value| check::<Upstream>(&value).map(|_| value));
+        if converter.emit("upstreams", &entry, result, &mut output) {
+            kept_upstreams.insert(entry.id);
+        }
+    }
+
+    let mut kept_services = HashSet::new();
+    for entry in entries.remove("services").unwrap_or_default() {
+        let result = converter
+            .convert_service(&entry.source_key, &entry.value, &kept_upstreams)
+            .and_then(|value| check::<Service>(&value).map(|_| value));
+        if converter.emit("services", &entry, result, &mut output) {
+            kept_services.insert(entry.id);
+        }
+    }
+
+    for entry in entries.remove("routes").unwrap_or_default() {
+        let result = converter
+            .convert_route(
+                &entry.source_key,
+                &entry.value,
+                &kept_upstreams,
+                &kept_services,
+            )
+            .and_then(|value| check::<Route>(&value).map(|_| value));
+        converter.emit("routes", &entry, result, &mut output);
+    }
+
+    for entry in entries.remove("global_rules").unwrap_or_default() {
+        let result = converter
+            .convert_plugins(&entry.source_key, entry.value.get("plugins"))
+            .map(|plugins| json!({ "plugins": plugins }))
+            .and_then(|value| check::<GlobalRule>(&value).map(|_| value));
+        converter.emit("global_rules", &entry, result, &mut output);
+    }
+
+    for entry in entries.remove("ssls").unwrap_or_default() {
+        let result = converter
+            .convert_ssl(&entry.source_key, &entry.value)
+            .and_then(|value| check::<SSL>(&value).map(|_| value));
+        converter.emit("ssls", &entry, result, &mut output);
+    }
+
+    output
+}
+
+/// Deserialize and validate `value` as the pingsix resource `T`.
+fn check<T: DeserializeOwned + Validate>(value: &JsonValue) -> Result<(), String> {
+    let resource: T = serde_json::from_value(value.clone()).map_err(|e| e.to_string())?;
+    resource.validate().map_err(|e| e.to_string())
+}
+
+/// APISIX ids may be strings or integers.
+fn as_id(value: &JsonValue) -> Option<String> {
+    match value {
+        JsonValue::String(s) => Some(s.clone()),
+        JsonValue::Number(n) => Some(n.to_string()),
+        _ => None,
+    }
+}
+
+/// APISIX timeouts are (possibly fractional) seconds; pingsix takes whole seconds.
+fn convert_timeout(timeout: &JsonValue) -> Result<JsonValue, String> {
+    let seconds = |field: &str| {
+        timeout
+            .get(field)
+            .and_then(JsonValue::as_f64)
+            .map(|secs| secs.ceil().max(1.0) as u64)
+            .ok_or_else(|| format!("timeout.{field} is missing"))
+    };
+    Ok(json!({
+        "connect": seconds("connect")?,
+        "send": seconds("send")?,
+        "read": seconds("read")?,
+    }))
+}
+
+/// Translate an APISIX radixtree path into matchit patterns.
+///
+/// `:name` becomes `{name}` and a trailing `*` or `*name` becomes a catch-all. A
+/// catch-all in matchit requires at least one character, so the bare prefix is added
+/// too. Returns whether the translation is approximate: `/api*` matches within a
+/// segment in APISIX but only on segment boundaries here.
+fn convert_uri(uri: &str) -> (Vec<String>, bool) {
+    let parts: Vec<&str> = uri.split('/').collect();
+    let last = parts.len() - 1;
+    let mut segments = Vec::with_capacity(parts.len());
+    let mut catch_all = None;
+    let mut approximate = false;
+    for (index, part) in parts.iter().enumerate() {
+        if let Some(name) = part.strip_prefix(':') {
+            segments.push(format!("{{{name}}}"));
+        } else if let Some(name) = part.strip_prefix('*').filter(|_| index == last) {
+            catch_all = Some(if name.is_empty() { "path" } else { name });
+        } else if index == last && part.ends_with('*') {
+            segments.push(part.trim_end_matches('*').to_string());
+            catch_all = Some("path");
+            approximate = true;
+        } else {
+            segments.push(part.to_string());
+        }
+    }
+
+    let base = segments.join("/");
+    let Some(name) = catch_all else {
+        return (vec![base], false);
+    };
+    let mut uris = Vec::new();
+    if approximate {
+        uris.push(base.clone());
+    }
+    uris.push(format!("{base}/"));
+    uris.push(format!("{base}/{{*{name}}}"));
+    (uris, approximate)
+}
+
+/// Upstream `nodes` and their non-zero `priorities`, keyed by address.
+type ConvertedNodes = (Map<String, JsonValue>, Map<String, JsonValue>);
+
+/// Convert nodes into `(nodes, priorities)`; only non-zero priorities are kept.
+fn convert_nodes(nodes: Option<&JsonValue>, scheme: &str) -> Result<ConvertedNodes, String> {
+    let mut converted = Map::new();
+    let mut priorities = Map::new();
+    match nodes {
+        Some(JsonValue::Object(map)) => {
+            for (address, weight) in map {
+                converted.insert(address.clone(), json!(weight.as_u64().unwrap_or(1)));
+            }
+        }
+        Some(JsonValue::Array(list)) => {
+            for node in list {
+                let host = node
+                    .get("host")
+                    .and_then(JsonValue::as_str)
+                    .ok_or("node without host")?;
+                let default_port = if matches!(scheme, "https" | "grpcs") {
+                    443
+                } else {
+                    80
+                };
+                let port = node
+                    .get("port")
+                    .and_then(JsonValue::as_u64)
+                    .unwrap_or(default_port);
+                let host = if host.contains(':') && !host.starts_with('[') {
+                    format!("[{host}]")
+                } else {
+                    host.to_string()
+                };
+                let weight = node.get("weight").and_then(JsonValue::as_u64).unwrap_or(1);
+                let address = format!("{host}:{port}");
+                if let Some(priority) = node
+                    .get("priority")
+                    .and_then(JsonValue::as_i64)
+                    .filter(|p| *p != 0)
+                {
+                    priorities.insert(address.clone(), json!(priority));
+                }
+                converted.insert(address, json!(weight));
+            }
+        }
+        _ => {}
+    }
+    if converted.is_empty() {
+        return Err("upstream has no nodes".to_string());
+    }
+    Ok((converted, priorities))
+}
+
+struct Converter<'a> {
+    options: ConvertOptions,
+    consumers: Consumers,
+    report: &'a mut MigrationReport,
+}
+
+impl Converter<'_> {
+    /// Record the outcome for `entry`; returns whether it was converted.
+    fn emit(
+        &mut self,
+        kind: &str,
+        entry: &Entry,
+        result: Result<JsonValue, String>,
+        output: &mut Vec<(String, Vec<u8>)>,
+    ) -> bool {
+        match result {
+            Ok(value) => {
+                output.push((
+                    format!("{kind}/{}", entry.id),
+                    value.to_string().into_bytes(),
+                ));
+                *self.report.converted.entry(kind.to_string()).or_default() += 1;
+                true
+            }
+            Err(reason) => {
+                self.report.skip(&entry.source_key, reason);
+                false
+            }
+        }
+    }
+
+    fn warn(&mut self, source_key: &str, message: impl std::fmt::Display) {
+        self.report
+            .warnings
+            .push(format!("{source_key}: {message}"));
+    }
+
+    /// Gather credentials from consumers (`consumers/<name>`) and, on APISIX 3,
+    /// their credential entries (`consumers/<name>/credentials/<id>`).
+    fn collect_consumers(&mut self, entries: Vec<Entry>) {
+        for entry in entries {
+            let consumer = match entry.id.split_once("/credentials/") {
+                Some((name, _)) => name.to_string(),
+                None => entry
+                    .value
+                    .get("username")
+                    .and_then(JsonValue::as_str)
+                    .unwrap_or(&entry.id)
+                    .to_string(),
+            };
+            let Some(plugins) = entry.value.get("plugins").and_then(JsonValue::as_object) else {
+                continue;
+            };
+            for (name, cfg) in plugins {
+                let field =
+                    |field: &str| cfg.get(field).and_then(JsonValue::as_str).map(String::from);
+                match name.as_str() {
+                    key_auth::PLUGIN_NAME => match field("key") {
+                        Some(key) => self.consumers.key_auth.push((key, consumer.clone())),
+                        None => self.warn(&entry.source_key, "key-auth credential without a key"),
+                    },
+                    basic_auth::PLUGIN_NAME => match (field("username"), field("password")) {
+                        (Some(username), Some(password)) => self
+                            .consumers
+                            .basic_auth
+                            .push(((username, password), consumer.clone())),
+                        _ => self.warn(&entry.source_key, "incomplete basic-auth credential"),
+                    },
+                    other => {
+                        self.report
+                            .unsupported_plugins
+                            .entry(other.to_string())
+                            .or_default()
+                            .insert(entry.source_key.clone());
+                        self.warn(
+                            &entry.source_key,
+                            format!("consumer plugin '{other}' is not migrated"),
+                        );
+                    }
+                }
+            }
+            *self.report.converted.entry("consumers".into()).or_default() += 1;
+        }
+    }
+
+    /// Convert one plugin config. `Ok(None)` means the plugin is disabled through
+    /// `_meta.disable` and is dropped.
+    fn convert_plugin(&self, name: &str, cfg: &JsonValue) -> Result<Option<JsonValue>, String> {
+        let mut cfg = cfg.clone();
+        if let Some(meta) = cfg.as_object_mut().and_then(|obj| obj.remove("_meta")) {
+            if meta.get("disable").and_then(JsonValue::as_bool) == Some(true) {
+                return Ok(None);
+            }
+        }
+
+        match name {
+            key_auth::PLUGIN_NAME => {
+                if self.consumers.key_auth.is_empty() {
+                    return Err("no consumer has key-auth credentials".into());
+                }
+                let keys: Vec<JsonValue> = self
+                    .consumers
+                    .key_auth
+                    .iter()
+                    .map(|(key, consumer)| json!({ "key": key, "consumer": consumer }))
+                    .collect();
+                cfg = json!({
+                    "header": cfg.get("header").cloned().unwrap_or(json!("apikey")),
+                    "query": cfg.get("query").cloned().unwrap_or(json!(APISIX_KEY_AUTH_QUERY)),
+                    "hide_credentials": cfg.get("hide_credentials").cloned().unwrap_or(json!(false)),
+                    "keys": keys,
+                });
+            }
+            basic_auth::PLUGIN_NAME => match self.consumers.basic_auth.as_slice() {
+                [((username, password), _)] => {
+                    cfg = json!({
+                        "username": username,
+                        "password": password,
+                        "hide_credentials": cfg.get("hide_credentials").cloned().unwrap_or(json!(false)),
+                    });
+                }
+                [] => return Err("no consumer has basic-auth credentials".into()),
+                many => {
+                    return Err(format!(
+                        "pingsix basic-auth takes one credential but {} consumers have one",
+                        many.len()
+                    ))
+                }
+            },
+            _ => {}
+        }
+
+        if !plugins::is_registered(name) {
+            return Err("not available in pingsix".into());
+        }
+        if name == ai_proxy::PLUGIN_NAME {
+            // The provider endpoint is only resolved when the config is loaded.
+            plugins::validate_plugin_config(name, &cfg)
+                .and_then(|_| ai_proxy::validate_ai_proxy_config(&cfg))
+                .map_err(|e| e.to_string())?;
+        } else {
+            plugins::build_plugin(name, cfg.clone()).map_err(|e| e.to_string())?;
+        }
+        Ok(Some(cfg))
+    }
+
+    fn convert_plugins(
+        &mut self,
+        source_key: &str,
+        plugins: Option<&JsonValue>,
+    ) -> Result<Map<String, JsonValue>, String> {
+        let mut converted = Map::new();
+        let Some(plugins) = plugins.and_then(JsonValue::as_object) else {
+            return Ok(converted);
+        };
+        let mut unsupported = Vec::new();
+        for (name, cfg) in plugins {
+            match self.convert_plugin(name, cfg) {
+                Ok(Some(cfg)) => {
+                    converted.insert(name.clone(), cfg);
+                }
+                Ok(None) => self.warn(source_key, format!("disabled plugin '{name}' dropped")),
+                Err(reason) => {
+                    self.report
+                        .unsupported_plugins
+                        .entry(name.clone())
+                        .or_default()
+                        .insert(source_key.to_string());
+                    unsupported.push(format!("{name} ({reason})"));
+                }
+            }
+        }
+        if unsupported.is_empty() {
+            return Ok(converted);
+        }
+        let unsupported = unsupported.join(", ");
+        if self.options.drop_unsupported_plugins {
+            self.warn(source_key, format!("dropped plugins {unsupported}"));
+            Ok(converted)
+        } else {
+            Err(format!("unsupported plugins: {unsupported}"))
+        }
+    }
+
+    fn convert_upstream(
+        &mut self,
+        source_key: &str,
+        upstream: &Map<String, JsonValue>,
+    ) -> Result<JsonValue, String> {
+        if let Some(discovery) = upstream.get("discovery_type").and_then(JsonValue::as_str) {
+            return Err(format!("service discovery '{discovery}' is not supported"));
+        }
+        let scheme = upstream
+            .get("scheme")
+            .and_then(JsonValue::as_str)
+            .unwrap_or("http");
+        if !matches!(scheme, "http" | "https" | "grpc" | "grpcs") {
+            return Err(format!("scheme '{scheme}' is not supported"));
+        }
+        let (nodes, priorities) = convert_nodes(upstream.get("nodes"), scheme)?;
+
+        let mut converted = json!({ "nodes": nodes, "scheme": scheme });
+        if !priorities.is_empty() {
+            converted["priorities"] = JsonValue::Object(priorities);
+        }
+        match upstream
+            .get("type")
+            .and_then(JsonValue::as_str)
+            .unwrap_or("roundrobin")
+        {
+            "roundrobin" => converted["type"] = json!("roundrobin"),
+            "chash" => {
+                converted["type"] = json!("ketama");
+                converted["hash_on"] = match upstream
+                    .get("hash_on")
+                    .and_then(JsonValue::as_str)
+                    .unwrap_or("vars")
+                {
+                    "vars" => json!("vars"),
+                    "header" => json!("head"),
+                    "cookie" => json!("cookie"),
+                    other => return Err(format!("hash_on '{other}' is not supported")),
+                };
+                if let Some(key) = upstream.get("key") {
+                    converted["key"] = key.clone();
+                }
+            }
+            "least_conn" => converted["type"] = json!("least_conn"),
+            "ewma" => converted["type"] = json!("latency"),
+            other => return Err(format!("balancer type '{other}' is not supported")),
+        }
+
+        if let Some(retries) = upstream.get("retries").and_then(JsonValue::as_u64) {
+            converted["retries"] = json!(retries);
+        }
+        if let Some(retry_timeout) = upstream.get("retry_timeout").and_then(JsonValue::as_f64) {
+            converted["retry_timeout"] = json!(retry_timeout.ceil() as u64);
+        }
+        if let Some(timeout) = upstream.get("timeout") {
+            converted["timeout"] = convert_timeout(timeout)?;
+        }
+        for field in ["pass_host", "upstream_host"] {
+            if let Some(value) = upstream.get(field) {
+                converted[field] = value.clone();
+            }
+        }
+        if let Some(checks) = upstream.get("checks") {
+            if checks.get("passive").is_some() {
+                self.warn(source_key, "passive health checks are not migrated");
+            }
+            if let Some(active) = checks.get("active") {
+                converted["checks"] = json!({ "active": active });
+                if check::<Upstream>(&converted).is_err() {
+                    if let Some(obj) = converted.as_object_mut() {
+                        obj.remove("checks");
+                    }
+                    self.warn(source_key, "active health check could not be converted");
+                }
+            }
+        }
+        for field in ["tls", "keepalive_pool"] {
+            if upstream.contains_key(field) {
+                self.warn(source_key, format!("'{field}' is not migrated"));
+            }
+        }
+        Ok(converted)
+    }
+
+    /// Copy `upstream_id` (which must have been migrated) or convert an inline upstream.
+    fn convert_upstream_ref(
+        &mut self,
+        source_key: &str,
+        resource: &Map<String, JsonValue>,
+        kept_upstreams: &HashSet<String>,
+        converted: &mut JsonValue,
+    ) -> Result<(), String> {
+        if let Some(upstream_id) = resource.get("upstream_id").and_then(as_id) {
+            if !kept_upstreams.contains(&upstream_id) {
+                return Err(format!("upstream '{upstream_id}' was not migrated"));
+            }
+            converted["upstream_id"] = json!(upstream_id);
+        }
+        if let Some(JsonValue::Object(upstream)) = resource.get("upstream") {
+            converted["upstream"] = self.convert_upstream(source_key, upstream)?;
+        }
+        Ok(())
+    }
+
+    fn convert_service(
+        &mut self,
+        source_key: &str,
+        service: &Map<String, JsonValue>,
+        kept_upstreams: &HashSet<String>,
+    ) -> Result<JsonValue, String> {
+        let mut converted = json!({});
+        self.convert_upstream_ref(source_key, service, kept_upstreams, &mut converted)?;
+        if let Some(hosts) = service.get("hosts") {
+            converted["hosts"] = hosts.clone();
+        }
+        converted["plugins"] = json!(self.convert_plugins(source_key, service.get("plugins"))?);
+        Ok(converted)
+    }
+
+    fn convert_route(
+        &mut self,
+        source_key: &str,
+        route: &Map<String, JsonValue>,
+        kept_upstreams: &HashSet<String>,
+        kept_services: &HashSet<String>,
+    ) -> Result<JsonValue, String> {
+        if route.get("status").and_then(JsonValue::as_i64) == Some(0) {
+            return Err("route is disabled (status 0)".into());
+        }
+        if let Some(field) = UNSUPPORTED_ROUTE_FIELDS
+            .iter()
+            .find(|field| route.contains_key(**field))
+        {
+            return Err(format!("'{field}' is not supported"));
+        }
+
+        let mut sources: Vec<&str> = Vec::new();
+        if let Some(uri) = route.get("uri").and_then(JsonValue::as_str) {
+            sources.push(uri);
+        }
+        if let Some(JsonValue::Array(uris)) = route.get("uris") {
+            sources.extend(uris.iter().filter_map(JsonValue::as_str));
+        }
+        let mut uris: Vec<String> = Vec::new();
+        for source in sources {
+            let (converted, approximate) = convert_uri(source);
+            if approximate {
+                self.warn(
+                    source_key,
+                    format!("uri '{source}' now matches on path segment boundaries"),
+                );
+            }
+            for uri in converted {
+                if !uris.contains(&uri) {
+                    uris.push(uri);
+                }
+            }
+        }
+        if uris.is_empty() {
+            return Err("route has no uri".into());
+        }
+
+        let mut converted = json!({ "uris": uris });
+        for field in ["methods", "host", "hosts", "priority"] {
+            if let Some(value) = route.get(field) {
+                converted[field] = value.clone();
+            }
+        }
+        if let Some(service_id) = route.get("service_id").and_then(as_id) {
+            if !kept_services.contains(&service_id) {
+                return Err(format!("service '{service_id}' was not migrated"));
+            }
+            converted["service_id"] = json!(service_id);
+        }
+        self.convert_upstream_ref(source_key, route, kept_upstreams, &mut converted)?;
+        if let Some(timeout) = route.get("timeout") {
+            converted["timeout"] = convert_timeout(timeout)?;
+        }
+        converted["plugins"] = json!(self.convert_plugins(source_key, route.get("plugins"))?);
+        Ok(converted)
+    }
+
+    fn convert_ssl(
+        &mut self,
+        source_key: &str,
+        ssl: &Map<String, JsonValue>,
+    ) -> Result<JsonValue, String> {
+        if ssl.get("type").and_then(JsonValue::as_str) == Some("client") {
+            return Err("client certificates are not supported".into());
+        }
+        if ssl.get("status").and_then(JsonValue::as_i64) == Some(0) {
+            return Err("certificate is disabled (status 0)".into());
+        }
+        let snis = match (ssl.get("snis"), ssl.get("sni")) {
+            (Some(snis), _) => snis.clone(),
+            (None, Some(sni)) => json!([sni]),
+            (None, None) => return Err("certificate has no SNI".into()),
+        };
+        if ssl
+            .get("certs")
+            .is_some_and(|certs| certs.as_array().is_some_and(|c| !c.is_empty()))
+        {
+            self.warn(
+                source_key,
+                "additional certificates in 'certs' are not migrated",
+            );
+        }
+        Ok(json!({
+            "cert": ssl.get("cert").cloned().unwrap_or_default(),
+            "key": ssl.get("key").cloned().unwrap_or_default(),
+            "snis": snis,
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn kv(key: &str, value: JsonValue) -> (String, Vec<u8>) {
+        (key.to_string(), value.to_string().into_bytes())
+    }
+
+    fn body(output: &[(String, Vec<u8>)], key: &str) -> Option<JsonValue> {
+        output
+            .iter()
+            .find(|(k, _)| k == key)
+            .map(|(_, v)| serde_json::from_slice(v).unwrap())
+    }
+
+    #[test]
+    fn converts_uris() {
+        assert_eq!(
+            convert_uri("/users/:id"),
+            (vec!["/users/{id}".to_string()], false)
+        );
+        assert_eq!(
+            convert_uri("/*"),
+            (vec!["/".to_string(), "/{*path}".to_string()], false)
+        );
+        assert_eq!(
+            convert_uri("/static/*file"),
+            (
+                vec!["/static/".to_string(), "/static/{*file}".to_string()],
+                false
+            )
+        );
+        assert_eq!(
+            convert_uri("/api*"),
+            (
+                vec![
+                    "/api".to_string(),
+                    "/api/".to_string(),
+                    "/api/{*path}".to_string()
+                ],
+                true
+            )
+        );
+    }
+
+    #[test]
+    fn converts_snapshot_and_reports_unsupported_resources() {
+        let kvs = vec![
+            kv("routes", json!("init_dir")),
+            kv(
+                "upstreams/1",
+                json!({
+                    "type": "chash", "hash_on": "header", "key": "x-user",
+                    "nodes": [{"host": "10.0.0.1", "port": 8080, "weight": 2}, {"host": "::1"}],
+                    "timeout": {"connect": 0.5, "send": 3, "read": 3},
+                }),
+            ),
+            kv(
+                "upstreams/2",
+                json!({"discovery_type": "consul", "service_name": "web"}),
+            ),
+            kv(
+                "consumers/jack",
+                json!({"username": "jack", "plugins": {"key-auth": {"key": "jack-key"}}}),
+            ),
+            kv(
+                "routes/1",
+                json!({
+                    "uri": "/api/*", "methods": ["GET"], "upstream_id": "1",
+                    "plugins": {"key-auth": {"header": "x-api-key"}},
+                }),
+            ),
+            kv("routes/2", json!({"uri": "/legacy", "upstream_id": "2"})),
+            kv(
+                "routes/3",
+                json!({"uri": "/lua", "upstream_id": "1", "plugins": {"serverless-pre-function": {}}}),
+            ),
+            kv(
+                "routes/4",
+                json!({"uri": "/v", "upstream_id": "1", "vars": [["arg_a", "==", "1"]]}),
+            ),
+            kv("stream_routes/1", json!({"server_port": 9100})),
+        ];
+
+        let mut report = MigrationReport::default();
+        let output = convert(&kvs, ConvertOptions::default(), &mut report);
+
+        let upstream = body(&output, "upstreams/1").unwrap();
+        assert_eq!(upstream["type"], "ketama");
+        assert_eq!(upstream["hash_on"], "head");
+        assert_eq!(
+            upstream["nodes"],
+            json!({"10.0.0.1:8080": 2, "[::1]:80": 1})
+        );
+        assert_eq!(upstream["timeout"]["connect"], 1);
+
+        let route = body(&output, "routes/1").unwrap();
+        assert_eq!(route["uris"], json!(["/api/", "/api/{*path}"]));
+        let key_auth = &route["plugins"]["key-auth"];
+        assert_eq!(key_auth["header"], "x-api-key");
+        assert_eq!(key_auth["query"], "apikey");
+        assert_eq!(
+            key_auth["keys"],
+            json!([{"key": "jack-key", "consumer": "jack"}])
+        );
+
+        assert_eq!(output.len(), 2);
+        let skipped: Vec<&str> = report.skipped.iter().map(|(k, _)| k.as_str()).collect();
+        assert_eq!(
+            skipped,
+            vec![
+                "stream_routes/1",
+                "upstreams/2",
+                "routes/2",
+                "routes/3",
+                "routes/4"
+            ]
+        );
+        assert!(report.unsupported_plugins["serverless-pre-function"].contains("routes/3"));
+        assert_eq!(report.converted["consumers"], 1);
+
+        // Dropping unsupported plugins keeps the route.
+        let mut report = MigrationReport::default();
+        let options = ConvertOptions {
+            drop_unsupported_plugins: true,
+        };
+        let output = convert(&kvs, options, &mut report);
+        assert_eq!(body(&output, "routes/3").unwrap()["plugins"], json!({}));
+        assert!(report.warnings.iter().any(|w| w.starts_with("routes/3:")));
+    }
+}
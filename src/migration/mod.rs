@@ -0,0 +1,183 @@
+//! `pingsix migrate`: convert another gateway's etcd configuration into the pingsix
+//! layout and import it under the configured pingsix prefix.
+
+pub mod apisix;
+
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fmt,
+};
+
+use crate::{
+    config::{etcd::EtcdClientWrapper, Config},
+    core::{ProxyError, ProxyResult},
+    proxy::graph_mutation::{import_resources, plan_import},
+};
+
+/// Gateways `pingsix migrate` can read from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum MigrationSource {
+    Apisix,
+}
+
+/// Arguments of `pingsix migrate`.
+#[derive(Debug, clap::Args)]
+pub struct MigrateArgs {
+    /// Gateway whose configuration is converted.
+    #[arg(long, value_enum)]
+    pub from: MigrationSource,
+
+    /// pingsix configuration file; `pingsix.etcd` is the migration target.
+    #[arg(short, long)]
+    pub conf: String,
+
+    /// etcd prefix holding the source configuration.
+    #[arg(long, default_value = "/apisix")]
+    pub source_prefix: String,
+
+    /// etcd endpoints of the source cluster (default: the target endpoints).
+    #[arg(long)]
+    pub source_host: Vec<String>,
+
+    /// Convert and validate without writing to etcd.
+    #[arg(long)]
+    pub dry_run: bool,
+
+    /// Drop plugins that cannot be converted instead of skipping the resources using them.
+    #[arg(long)]
+    pub drop_unsupported_plugins: bool,
+}
+
+/// What a migration converted, skipped and wrote.
+#[derive(Debug, Default)]
+pub struct MigrationReport {
+    /// Converted resources per type.
+    pub converted: BTreeMap<String, usize>,
+    /// `(source key, reason)` for resources that were not converted.
+    pub skipped: Vec<(String, String)>,
+    /// Lossy conversions that were applied.
+    pub warnings: Vec<String>,
+    /// Unsupported plugin name to the source keys using it.
+    pub unsupported_plugins: BTreeMap<String, BTreeSet<String>>,
+    /// `(written, unchanged)` resource counts; `None` for a dry run.
+    pub imported: Option<(usize, usize)>,
+}
+
+impl MigrationReport {
+    pub(crate) fn skip(&mut self, source_key: &str, reason: impl Into<String>) {
+        self.skipped.push((source_key.to_string(), reason.into()));
+    }
+}
+
+impl fmt::Display for MigrationReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Converted:")?;
+        for (kind, count) in &self.converted {
+            writeln!(f, "  {kind}: {count}")?;
+        }
+        if !self.skipped.is_empty() {
+            writeln!(f, "Skipped ({}):", self.skipped.len())?;
+            for (key, reason) in &self.skipped {
+                writeln!(f, "  {key}: {reason}")?;
+            }
+        }
+        if !self.unsupported_plugins.is_empty() {
+            writeln!(f, "Unsupported plugins:")?;
+            for (name, keys) in &self.unsupported_plugins {
+                let keys: Vec<&str> = keys.iter().map(String::as_str).collect();
+                writeln!(f, "  {name}: {}", keys.join(", "))?;
+            }
+        }
+        if !self.warnings.is_empty() {
+            writeln!(f, "Warnings:")?;
+            for warning in &self.warnings {
+                writeln!(f, "  {warning}")?;
+            }
+        }
+        match self.imported {
+            Some((written, unchanged)) => {
+                writeln!(f, "Imported: {written} written, {unchanged} unchanged")
+            }
+            None => writeln!(f, "Dry run: nothing was written"),
+        }
+    }
+}
+
+/// Run a migration to completion on a private runtime.
+pub fn run(args: &MigrateArgs) -> ProxyResult<MigrationReport> {
+    let runtime = tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()
+        .map_err(ProxyError::Network)?;
+    runtime.block_on(migrate(args))
+}
+
+async fn migrate(args: &MigrateArgs) -> ProxyResult<MigrationReport> {
+    let config = Config::load_from_yaml(&args.conf)
+        .map_err(|e| ProxyError::Configuration(format!("Failed to load '{}': {e}", args.conf)))?;
+    let Some(target_cfg) = config.pingsix.etcd else {
+        return Err(ProxyError::Configuration(
+            "Migration requires `pingsix.etcd` as the target".into(),
+        ));
+    };
+
+    let mut source_cfg = target_cfg.clone();
+    source_cfg.prefix = args.source_prefix.clone();
+    if !args.source_host.is_empty() {
+        source_cfg.host = args.source_host.clone();
+    }
+    let source = EtcdClientWrapper::new(source_cfg);
+    // Created last so the process-wide config prefix is the target's.
+    let target = EtcdClientWrapper::new(target_cfg);
+    if source.prefix() == target.prefix() && args.source_host.is_empty() {
+        return Err(ProxyError::Configuration(
+            "Source and target etcd prefixes must differ".into(),
+        ));
+    }
+
+    let listing = source.list("").await?;
+    let kvs: Vec<(String, Vec<u8>)> = listing
+        .kvs()
+        .iter()
+        .filter_map(|kv| {
+            let key = std::str::from_utf8(kv.key()).ok()?;
+            let key = key.strip_prefix(source.prefix())?;
+            Some((key.to_string(), kv.value().to_vec()))
+        })
+        .collect();
+    log::debug!(
+        "Read {} keys under source prefix '{}'",
+        kvs.len(),
+        source.prefix()
+    );
+
+    let mut report = MigrationReport::default();
+    let resources = match args.from {
+        MigrationSource::Apisix => apisix::convert(
+            &kvs,
+            apisix::ConvertOptions {
+                drop_unsupported_plugins: args.drop_unsupported_plugins,
+            },
+            &mut report,
+        ),
+    };
+
+    if args.dry_run {
+        // Validate against the current target graph without committing.
+        let graph = target.read_full_graph().await?;
+        let physical = resources
+            .into_iter()
+            .map(|(key, body)| (target.prefixed_key(&key), body))
+            .collect();
+        plan_import(&graph, physical, false, target.prefix())
+            .and_then(|plan| plan.check_txn_ops(target.max_txn_ops()))
+            .map_err(|e| ProxyError::Validation(e.to_string()))?;
+        return Ok(report);
+    }
+
+    let outcome = import_resources(&target, resources, false)
+        .await
+        .map_err(|e| ProxyError::Validation(e.to_string()))?;
+    report.imported = Some((outcome.written, outcome.unchanged));
+    Ok(report)
+}
@@ -0,0 +1,621 @@
+use std::{
+    collections::HashMap,
+    sync::Arc,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use async_trait::async_trait;
+use bytes::{Bytes, BytesMut};
+use http::{header, HeaderName, HeaderValue, StatusCode, Uri};
+use once_cell::sync::Lazy;
+use pingora_error::{Error, ErrorType, Result};
+use pingora_http::{RequestHeader, ResponseHeader};
+use pingora_proxy::Session;
+use prometheus::{register_int_counter_vec, IntCounterVec};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Map, Value as JsonValue};
+use url::Url;
+use validator::{Validate, ValidationError};
+
+use crate::config::Upstream;
+use crate::core::{
+    shared_dict::SharedDict, ProxyContext, ProxyError, ProxyPlugin, ProxyResult, UpstreamSelector,
+};
+use crate::proxy::upstream::{ai_proxy_key, PreparedUpstreams, ProxyUpstream};
+use crate::utils::{request::get_direct_client_ip, response::ResponseBuilder, secret};
+
+pub const PLUGIN_NAME: &str = "ai-proxy";
+pub const PRIORITY: i32 = 1040;
+
+const STATE_CTX_KEY: &str = "ai_proxy_state";
+
+const OPENAI_ENDPOINT: &str = "https://api.openai.com/v1/chat/completions";
+
+/// Largest request body rewritten, and largest non-streaming response body scanned for
+/// `usage`.
+const MAX_BODY_BYTES: usize = 1024 * 1024;
+
+/// Most token windows kept; each expires once its minute is over.
+const MAX_WINDOWS: usize = 10_000;
+
+const WINDOW_TTL: Duration = Duration::from_secs(60);
+
+static AI_TOKENS: Lazy<IntCounterVec> = Lazy::new(|| {
+    register_int_counter_vec!(
+        "pingsix_ai_tokens_total",
+        "LLM tokens used through ai-proxy, by type (prompt or completion)",
+        &["route", "type"]
+    )
+    .expect("ai-proxy metric registration must succeed")
+});
+
+/// Creates an ai-proxy plugin. The provider endpoint is an upstream prepared by the
+/// control plane, see [`create_ai_proxy_plugin_with_upstreams`].
+pub fn create_ai_proxy_plugin(cfg: JsonValue) -> ProxyResult<Arc<dyn ProxyPlugin>> {
+    create_ai_proxy_plugin_with_upstreams(cfg, &HashMap::new(), "admin")
+}
+
+/// JSON Schema of the ai-proxy plugin configuration.
+pub fn schema() -> JsonValue {
+    let strings = json!({"type": "object", "additionalProperties": {"type": "string"}});
+    json!({
+        "type": "object",
+        "properties": {
+            "provider": {"type": "string", "enum": ["openai", "openai-compatible"], "default": "openai"},
+            "auth": {
+                "type": "object",
+                "properties": {"header": strings, "query": strings}
+            },
+            "options": {"type": "object"},
+            "override": {
+                "type": "object",
+                "required": ["endpoint"],
+                "properties": {"endpoint": {"type": "string", "minLength": 1}}
+            },
+            "tokens_per_minute": {"type": "integer", "minimum": 1}
+        }
+    })
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+enum Provider {
+    #[default]
+    Openai,
+    OpenaiCompatible,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct AuthConfig {
+    /// Request headers set on the upstream request, replacing the client's.
+    #[serde(default)]
+    header: HashMap<String, String>,
+    /// Query parameters added to the endpoint.
+    #[serde(default)]
+    query: HashMap<String, String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct OverrideConfig {
+    endpoint: String,
+}
+
+#[derive(Debug, Serialize, Deserialize, Validate)]
+#[validate(schema(function = "PluginConfig::validate_endpoint"))]
+pub(super) struct PluginConfig {
+    #[serde(default)]
+    provider: Provider,
+    #[serde(default)]
+    auth: AuthConfig,
+    /// Fields set on every request body, such as `model`.
+    #[serde(default)]
+    options: Map<String, JsonValue>,
+    #[serde(default, rename = "override")]
+    endpoint_override: Option<OverrideConfig>,
+    /// Prompt plus completion tokens allowed per consumer and minute.
+    #[serde(default)]
+    #[validate(range(min = 1))]
+    tokens_per_minute: Option<u64>,
+}
+
+impl PluginConfig {
+    fn endpoint(&self) -> &str {
+        self.endpoint_override
+            .as_ref()
+            .map_or(OPENAI_ENDPOINT, |o| o.endpoint.as_str())
+    }
+
+    fn endpoint_url(&self) -> Result<Url, String> {
+        let url = Url::parse(self.endpoint()).map_err(|e| format!("invalid endpoint: {e}"))?;
+        if !matches!(url.scheme(), "http" | "https") || url.host_str().is_none() {
+            return Err("endpoint must be an http or https URL".to_string());
+        }
+        Ok(url)
+    }
+
+    fn validate_endpoint(&self) -> Result<(), ValidationError> {
+        if self.provider == Provider::OpenaiCompatible && self.endpoint_override.is_none() {
+            return Err(ValidationError::new(
+                "openai-compatible providers need override.endpoint",
+            ));
+        }
+        self.endpoint_url()
+            .map_err(|_| ValidationError::new("endpoint must be an http or https URL"))?;
+        if self
+            .auth
+            .header
+            .keys()
+            .any(|name| HeaderName::try_from(name.as_str()).is_err())
+        {
+            return Err(ValidationError::new("invalid auth header name"));
+        }
+        Ok(())
+    }
+}
+
+impl TryFrom<JsonValue> for PluginConfig {
+    type Error = ProxyError;
+
+    fn try_from(value: JsonValue) -> Result<Self, Self::Error> {
+        let config: PluginConfig = serde_json::from_value(value)
+            .map_err(|e| ProxyError::serialization_error("Invalid ai-proxy plugin config", e))?;
+
+        config.validate()?;
+
+        Ok(config)
+    }
+}
+
+/// Validate ai-proxy JSON without building its endpoint upstream (Admin pre-check).
+pub fn validate_ai_proxy_config(cfg: &JsonValue) -> ProxyResult<()> {
+    PluginConfig::try_from(cfg.clone()).map(|_| ())
+}
+
+/// The upstream serving the provider endpoint of an ai-proxy config value.
+pub fn endpoint_upstream(cfg: &JsonValue) -> ProxyResult<Upstream> {
+    let config = PluginConfig::try_from(cfg.clone())?;
+    let url = config.endpoint_url().map_err(ProxyError::Configuration)?;
+    let host = url.host_str().unwrap_or_default();
+    let port = url.port_or_known_default().unwrap_or(443);
+    serde_json::from_value(json!({
+        "nodes": {format!("{host}:{port}"): 1},
+        "type": "roundrobin",
+        "scheme": url.scheme(),
+        "pass_host": "rewrite",
+        "upstream_host": host,
+    }))
+    .map_err(|e| ProxyError::serialization_error("Invalid ai-proxy endpoint upstream", e))
+}
+
+/// Replace `$env://NAME` with the value of the environment variable `NAME`.
+fn resolve_secret(value: &str) -> ProxyResult<String> {
+    secret::resolve(value).map_err(|e| ProxyError::Configuration(format!("ai-proxy {e}")))
+}
+
+pub(crate) fn create_ai_proxy_plugin_with_upstreams(
+    cfg: JsonValue,
+    prepared: &PreparedUpstreams,
+    owner: &str,
+) -> ProxyResult<Arc<dyn ProxyPlugin>> {
+    let upstream = endpoint_upstream(&cfg)?;
+    let config = PluginConfig::try_from(cfg)?;
+
+    let mut url = config.endpoint_url().map_err(ProxyError::Configuration)?;
+    for (name, value) in &config.auth.query {
+        url.query_pairs_mut()
+            .append_pair(name, &resolve_secret(value)?);
+    }
+    let path = match url.query() {
+        Some(query) => format!("{}?{query}", url.path()),
+        None => url.path().to_string(),
+    };
+    let path = path.parse::<Uri>().map_err(|e| {
+        ProxyError::Configuration(format!("Invalid ai-proxy endpoint path '{path}': {e}"))
+    })?;
+    let mut headers = Vec::with_capacity(config.auth.header.len());
+    for (name, value) in &config.auth.header {
+        let value = HeaderValue::try_from(resolve_secret(value)?).map_err(|_| {
+            ProxyError::Configuration(format!("Invalid ai-proxy auth header '{name}'"))
+        })?;
+        headers.push((HeaderName::try_from(name.as_str()).unwrap(), value));
+    }
+
+    let prepared = prepared.get(&ai_proxy_key(owner)).cloned().ok_or_else(|| {
+        ProxyError::Configuration(format!("ai-proxy endpoint of {owner} was not prepared"))
+    })?;
+    let upstream = Arc::new(ProxyUpstream::build(upstream, prepared)?);
+
+    Ok(Arc::new(PluginAiProxy {
+        config,
+        upstream,
+        path,
+        headers,
+        windows: SharedDict::tracked(PLUGIN_NAME, MAX_WINDOWS),
+    }))
+}
+
+/// Token counts of one response, from its `usage` object when the provider sends one.
+#[derive(Debug, Default)]
+struct Usage {
+    prompt: Option<u64>,
+    completion: Option<u64>,
+    /// Streamed content deltas, counted as completion tokens when `usage` is missing.
+    deltas: u64,
+}
+
+impl Usage {
+    fn scan(&mut self, value: &JsonValue) {
+        if let Some(usage) = value.get("usage").filter(|usage| usage.is_object()) {
+            self.prompt = usage.get("prompt_tokens").and_then(JsonValue::as_u64);
+            self.completion = usage.get("completion_tokens").and_then(JsonValue::as_u64);
+        }
+        let deltas = value
+            .get("choices")
+            .and_then(JsonValue::as_array)
+            .into_iter()
+            .flatten()
+            .filter(|choice| {
+                choice
+                    .pointer("/delta/content")
+                    .and_then(JsonValue::as_str)
+                    .is_some_and(|content| !content.is_empty())
+            })
+            .count();
+        self.deltas += deltas as u64;
+    }
+
+    /// Scan one server-sent event line such as `data: {...}`.
+    fn scan_event_line(&mut self, line: &[u8]) {
+        let Some(data) = line.strip_prefix(b"data:") else {
+            return;
+        };
+        if let Ok(value) = serde_json::from_slice::<JsonValue>(data.trim_ascii()) {
+            self.scan(&value);
+        }
+    }
+}
+
+/// Per-request progress through the request and response bodies.
+#[derive(Default)]
+struct AiProxyState {
+    request_body: BytesMut,
+    /// Prompt size guessed from the request, used when the provider reports no usage.
+    prompt_estimate: u64,
+    event_stream: bool,
+    /// Unscanned response bytes: a partial event line, or the whole JSON body.
+    response_body: BytesMut,
+    usage: Usage,
+}
+
+/// Tokens used by one key in the minute `minute`.
+struct TokenWindow {
+    minute: u64,
+    used: u64,
+}
+
+pub struct PluginAiProxy {
+    config: PluginConfig,
+    upstream: Arc<ProxyUpstream>,
+    /// Path and query of the endpoint, including the `auth.query` parameters.
+    path: Uri,
+    headers: Vec<(HeaderName, HeaderValue)>,
+    windows: Arc<SharedDict<TokenWindow>>,
+}
+
+fn current_minute() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs() / 60)
+        .unwrap_or_default()
+}
+
+/// Apply `options` to a chat request and ask streamed responses to end with `usage`.
+/// Returns the rewritten body and a rough prompt token count of its messages.
+fn rewrite_request(
+    body: &[u8],
+    options: &Map<String, JsonValue>,
+) -> Result<(Vec<u8>, u64), String> {
+    let mut request: JsonValue =
+        serde_json::from_slice(body).map_err(|e| format!("invalid JSON body: {e}"))?;
+    let object = request
+        .as_object_mut()
+        .ok_or("request body must be a JSON object")?;
+    for (name, value) in options {
+        object.insert(name.clone(), value.clone());
+    }
+    if object.get("stream").and_then(JsonValue::as_bool) == Some(true) {
+        let stream_options = object.entry("stream_options").or_insert_with(|| json!({}));
+        if let Some(stream_options) = stream_options.as_object_mut() {
+            stream_options
+                .entry("include_usage")
+                .or_insert(JsonValue::Bool(true));
+        }
+    }
+    // About four characters per token for English text.
+    let chars: usize = object
+        .get("messages")
+        .and_then(JsonValue::as_array)
+        .into_iter()
+        .flatten()
+        .filter_map(|message| message.get("content").and_then(JsonValue::as_str))
+        .map(str::len)
+        .sum();
+    let body = serde_json::to_vec(&request).map_err(|e| e.to_string())?;
+    Ok((body, chars.div_ceil(4) as u64))
+}
+
+impl PluginAiProxy {
+    fn key(&self, session: &Session, ctx: &ProxyContext) -> String {
+        let route = ctx.route.as_ref().map_or("", |route| route.id());
+        match &ctx.authenticated_identity {
+            Some(consumer) => format!("{route}/consumer/{consumer}"),
+            None => format!(
+                "{route}/ip/{}",
+                get_direct_client_ip(session).map_or_else(String::new, |ip| ip.to_string())
+            ),
+        }
+    }
+
+    /// Tokens `key` may still use this minute, or `None` without a limit.
+    fn remaining(&self, key: &str, minute: u64) -> Option<u64> {
+        let limit = self.config.tokens_per_minute?;
+        let used = self
+            .windows
+            .get(key, |window| {
+                (window.minute == minute).then_some(window.used)
+            })
+            .flatten()
+            .unwrap_or(0);
+        Some(limit.saturating_sub(used))
+    }
+
+    fn consume(&self, key: String, tokens: u64, minute: u64) {
+        if self.config.tokens_per_minute.is_none() {
+            return;
+        }
+        self.windows.update(
+            key,
+            Some(WINDOW_TTL),
+            || TokenWindow { minute, used: 0 },
+            |window| {
+                if window.minute != minute {
+                    *window = TokenWindow { minute, used: 0 };
+                }
+                window.used += tokens;
+            },
+        );
+    }
+
+    fn finish(&self, session: &Session, ctx: &mut ProxyContext) {
+        let Some(state) = ctx.get_mut::<AiProxyState>(STATE_CTX_KEY) else {
+            return;
+        };
+        if !state.event_stream {
+            let body = std::mem::take(&mut state.response_body);
+            if let Ok(value) = serde_json::from_slice::<JsonValue>(&body) {
+                state.usage.scan(&value);
+            }
+        }
+        let prompt = state.usage.prompt.unwrap_or(state.prompt_estimate);
+        let completion = state.usage.completion.unwrap_or(state.usage.deltas);
+
+        let route = ctx.route.as_ref().map_or("", |route| route.id());
+        AI_TOKENS
+            .with_label_values(&[route, "prompt"])
+            .inc_by(prompt);
+        AI_TOKENS
+            .with_label_values(&[route, "completion"])
+            .inc_by(completion);
+        self.consume(
+            self.key(session, ctx),
+            prompt + completion,
+            current_minute(),
+        );
+    }
+}
+
+#[async_trait]
+impl ProxyPlugin for PluginAiProxy {
+    fn name(&self) -> &str {
+        PLUGIN_NAME
+    }
+
+    fn priority(&self) -> i32 {
+        PRIORITY
+    }
+
+    async fn request_filter(&self, session: &mut Session, ctx: &mut ProxyContext) -> Result<bool> {
+        if self.remaining(&self.key(session, ctx), current_minute()) == Some(0) {
+            let retry_after = (60
+                - SystemTime::now()
+                    .duration_since(UNIX_EPOCH)
+                    .map(|d| d.as_secs() % 60)
+                    .unwrap_or_default())
+            .to_string();
+            ResponseBuilder::send_proxy_error(
+                session,
+                StatusCode::TOO_MANY_REQUESTS,
+                Some("Token rate limit exceeded"),
+                Some(&[("Retry-After", retry_after.as_str())]),
+            )
+            .await?;
+            return Ok(true);
+        }
+        ctx.set(STATE_CTX_KEY, AiProxyState::default());
+        ctx.upstream_override = Some(self.upstream.clone() as Arc<dyn UpstreamSelector>);
+        Ok(false)
+    }
+
+    async fn upstream_request_filter(
+        &self,
+        _session: &mut Session,
+        upstream_request: &mut RequestHeader,
+        _ctx: &mut ProxyContext,
+    ) -> Result<()> {
+        upstream_request.set_uri(self.path.clone());
+        for (name, value) in &self.headers {
+            upstream_request.insert_header(name.clone(), value.clone())?;
+        }
+        // Usage is read from the response body, so it must arrive uncompressed.
+        upstream_request.remove_header(&header::ACCEPT_ENCODING);
+        upstream_request.remove_header(&header::CONTENT_LENGTH);
+        upstream_request.insert_header(header::TRANSFER_ENCODING, "chunked")?;
+        upstream_request.insert_header(header::CONTENT_TYPE, "application/json")?;
+        Ok(())
+    }
+
+    fn has_request_body_filter(&self) -> bool {
+        true
+    }
+
+    async fn request_body_filter(
+        &self,
+        _session: &mut Session,
+        body: &mut Option<Bytes>,
+        end_of_stream: bool,
+        ctx: &mut ProxyContext,
+    ) -> Result<()> {
+        let Some(state) = ctx.get_mut::<AiProxyState>(STATE_CTX_KEY) else {
+            return Ok(());
+        };
+        if let Some(chunk) = body.take() {
+            state.request_body.extend_from_slice(&chunk);
+        }
+        if state.request_body.len() > MAX_BODY_BYTES {
+            return Error::e_explain(
+                ErrorType::HTTPStatus(413),
+                "request body exceeds ai-proxy limit",
+            );
+        }
+        if !end_of_stream {
+            return Ok(());
+        }
+        let input = std::mem::take(&mut state.request_body);
+        match rewrite_request(&input, &self.config.options) {
+            Ok((output, prompt_estimate)) => {
+                state.prompt_estimate = prompt_estimate;
+                *body = Some(Bytes::from(output));
+                Ok(())
+            }
+            Err(e) => Error::e_explain(
+                ErrorType::HTTPStatus(400),
+                format!("ai-proxy failed to read the request: {e}"),
+            ),
+        }
+    }
+
+    async fn response_filter(
+        &self,
+        session: &mut Session,
+        upstream_response: &mut ResponseHeader,
+        ctx: &mut ProxyContext,
+    ) -> Result<()> {
+        if let Some(remaining) = self.remaining(&self.key(session, ctx), current_minute()) {
+            let limit = self.config.tokens_per_minute.unwrap_or_default();
+            upstream_response.insert_header("X-AI-RateLimit-Limit-Tokens", limit.to_string())?;
+            upstream_response
+                .insert_header("X-AI-RateLimit-Remaining-Tokens", remaining.to_string())?;
+        }
+        if let Some(state) = ctx.get_mut::<AiProxyState>(STATE_CTX_KEY) {
+            state.event_stream = upstream_response
+                .headers
+                .get(header::CONTENT_TYPE)
+                .and_then(|v| v.to_str().ok())
+                .is_some_and(|v| v.starts_with("text/event-stream"));
+        }
+        Ok(())
+    }
+
+    fn has_response_body_filter(&self) -> bool {
+        true
+    }
+
+    fn response_body_filter(
+        &self,
+        session: &mut Session,
+        body: &mut Option<Bytes>,
+        end_of_stream: bool,
+        ctx: &mut ProxyContext,
+    ) -> Result<()> {
+        let Some(state) = ctx.get_mut::<AiProxyState>(STATE_CTX_KEY) else {
+            return Ok(());
+        };
+        if let Some(chunk) = body.as_ref() {
+            if state.event_stream {
+                state.response_body.extend_from_slice(chunk);
+                while let Some(end) = state.response_body.iter().position(|b| *b == b'\n') {
+                    let line = state.response_body.split_to(end + 1);
+                    state.usage.scan_event_line(&line);
+                }
+            } else if state.response_body.len() + chunk.len() <= MAX_BODY_BYTES {
+                state.response_body.extend_from_slice(chunk);
+            }
+        }
+        if end_of_stream {
+            self.finish(session, ctx);
+            if let Some(vars) = ctx.vars.as_mut() {
+                vars.remove(STATE_CTX_KEY);
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn requests_get_options_and_streamed_usage() {
+        let options = json!({"model": "gpt-4o-mini"});
+        let (body, prompt) = rewrite_request(
+            br#"{"model": "gpt-4", "stream": true, "messages": [{"role": "user", "content": "Hello there!"}]}"#,
+            options.as_object().unwrap(),
+        )
+        .unwrap();
+        let body: JsonValue = serde_json::from_slice(&body).unwrap();
+        assert_eq!(body["model"], "gpt-4o-mini");
+        assert_eq!(body["stream_options"], json!({"include_usage": true}));
+        assert_eq!(prompt, 3);
+        assert!(rewrite_request(b"[1]", &Map::new()).is_err());
+    }
+
+    #[test]
+    fn usage_is_read_from_event_streams() {
+        let mut usage = Usage::default();
+        for line in [
+            &br#"data: {"choices": [{"delta": {"role": "assistant"}}]}"#[..],
+            br#"data: {"choices": [{"delta": {"content": "Hi"}}]}"#,
+            br#"data: {"choices": [{"delta": {"content": "!"}}]}"#,
+            b"",
+            b"data: [DONE]",
+        ] {
+            usage.scan_event_line(line);
+        }
+        assert_eq!(
+            (usage.prompt, usage.completion, usage.deltas),
+            (None, None, 2)
+        );
+
+        usage.scan_event_line(
+            br#"data: {"choices": [], "usage": {"prompt_tokens": 9, "completion_tokens": 12}}"#,
+        );
+        assert_eq!((usage.prompt, usage.completion), (Some(9), Some(12)));
+    }
+
+    #[test]
+    fn endpoints_become_upstreams() {
+        let upstream = endpoint_upstream(&json!({
+            "provider": "openai-compatible",
+            "override": {"endpoint": "http://llm.internal:8000/v1/chat/completions"}
+        }))
+        .unwrap();
+        assert!(upstream.nodes.contains_key("llm.internal:8000"));
+        assert_eq!(upstream.upstream_host.as_deref(), Some("llm.internal"));
+
+        let upstream = endpoint_upstream(&json!({})).unwrap();
+        assert!(upstream.nodes.contains_key("api.openai.com:443"));
+        assert!(PluginConfig::try_from(json!({"provider": "openai-compatible"})).is_err());
+        assert!(PluginConfig::try_from(json!({"override": {"endpoint": "ftp://x"}})).is_err());
+    }
+}
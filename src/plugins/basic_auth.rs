@@ -6,41 +6,122 @@ use http::{header, StatusCode};
 use pingora_error::Result;
 use pingora_proxy::Session;
 use serde::{Deserialize, Serialize};
-use serde_json::Value as JsonValue;
-use validator::Validate;
+use serde_json::{json, Value as JsonValue};
+use validator::{Validate, ValidationError};
 
 use crate::{
     core::{
         constant_time_digest_eq, secret_digest, ProxyContext, ProxyError, ProxyPlugin, ProxyResult,
     },
-    utils::{request, response::ResponseBuilder},
+    utils::{
+        htpasswd::{HtpasswdFile, VerifierBusy},
+        request,
+        response::ResponseBuilder,
+    },
 };
 
 pub const PLUGIN_NAME: &str = "basic-auth";
-const PRIORITY: i32 = 2520;
+pub const PRIORITY: i32 = 2520;
 
 /// Creates a Basic Auth plugin instance.
 pub fn create_basic_auth_plugin(cfg: JsonValue) -> ProxyResult<Arc<dyn ProxyPlugin>> {
     let config = PluginConfig::try_from(cfg)?;
-    let username_digest = secret_digest(&config.username);
-    let password_digest = secret_digest(&config.password);
+
+    let mut credentials = Vec::with_capacity(config.credentials.len() + 1);
+    if let (Some(username), Some(password)) = (&config.username, &config.password) {
+        credentials.push(StoredCredential::new(username, password, None));
+    }
+    for cred in &config.credentials {
+        credentials.push(StoredCredential::new(
+            &cred.username,
+            &cred.password,
+            cred.consumer.as_deref(),
+        ));
+    }
+    let htpasswd = config
+        .htpasswd_file
+        .as_deref()
+        .map(HtpasswdFile::load)
+        .transpose()?;
+
     Ok(Arc::new(PluginBasicAuth {
         config,
-        username_digest,
-        password_digest,
+        credentials,
+        htpasswd,
     }))
 }
 
+/// JSON Schema of the basic-auth plugin configuration.
+pub fn schema() -> JsonValue {
+    json!({
+        "type": "object",
+        "properties": {
+            "username": {"type": "string", "minLength": 1},
+            "password": {"type": "string", "minLength": 1},
+            "credentials": {
+                "type": "array",
+                "items": {
+                    "type": "object",
+                    "required": ["username", "password"],
+                    "properties": {
+                        "username": {"type": "string", "minLength": 1},
+                        "password": {"type": "string", "minLength": 1},
+                        "consumer": {"type": "string"}
+                    }
+                }
+            },
+            "htpasswd_file": {"type": "string", "minLength": 1},
+            "hide_credentials": {"type": "boolean", "default": false}
+        }
+    })
+}
+
+/// One user of the `credentials` list.
 #[derive(Debug, Serialize, Deserialize, Validate)]
-struct PluginConfig {
+#[serde(deny_unknown_fields)]
+struct Credential {
     #[validate(length(min = 1))]
     username: String,
     #[validate(length(min = 1))]
     password: String,
+    /// Consumer identity of this user; defaults to the username.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    consumer: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Validate)]
+#[validate(schema(function = "PluginConfig::validate_sources"))]
+pub(super) struct PluginConfig {
+    /// Single inline user, kept for existing configurations.
+    #[validate(length(min = 1))]
+    username: Option<String>,
+    #[validate(length(min = 1))]
+    password: Option<String>,
+    /// Users with their own consumer identity.
+    #[serde(default)]
+    #[validate(nested)]
+    credentials: Vec<Credential>,
+    /// htpasswd file with bcrypt or MD5-crypt entries, reloaded when it changes.
+    #[validate(length(min = 1))]
+    htpasswd_file: Option<String>,
     #[serde(default)]
     hide_credentials: bool,
 }
 
+impl PluginConfig {
+    fn validate_sources(&self) -> Result<(), ValidationError> {
+        if self.username.is_some() != self.password.is_some() {
+            return Err(ValidationError::new(
+                "username_and_password_required_together",
+            ));
+        }
+        if self.username.is_none() && self.credentials.is_empty() && self.htpasswd_file.is_none() {
+            return Err(ValidationError::new("no_basic_auth_credentials"));
+        }
+        Ok(())
+    }
+}
+
 impl TryFrom<JsonValue> for PluginConfig {
     type Error = ProxyError;
 
@@ -53,45 +134,92 @@ impl TryFrom<JsonValue> for PluginConfig {
     }
 }
 
-pub struct PluginBasicAuth {
-    config: PluginConfig,
+/// An inline credential reduced to digests for constant-time comparison.
+struct StoredCredential {
     username_digest: [u8; 32],
     password_digest: [u8; 32],
+    identity: String,
+}
+
+impl StoredCredential {
+    fn new(username: &str, password: &str, consumer: Option<&str>) -> Self {
+        Self {
+            username_digest: secret_digest(username),
+            password_digest: secret_digest(password),
+            identity: consumer.unwrap_or(username).to_string(),
+        }
+    }
+}
+
+pub struct PluginBasicAuth {
+    config: PluginConfig,
+    credentials: Vec<StoredCredential>,
+    htpasswd: Option<HtpasswdFile>,
 }
 
 impl PluginBasicAuth {
-    /// Validates Basic Authentication credentials using constant-time comparison.
+    /// Validates Basic Authentication credentials and returns the authenticated identity.
     ///
     /// This method:
     /// 1. Checks for "Basic " prefix (case-insensitive)
     /// 2. Decodes the Base64-encoded credentials
     /// 3. Splits username and password at the first colon
-    /// 4. Uses constant-time comparison to prevent timing attacks
-    fn validate_credentials(&self, auth_value: &str) -> bool {
+    /// 4. Compares against every inline credential in constant time, then the htpasswd file
+    ///    on a blocking thread, failing with [`VerifierBusy`] when too many logins are
+    ///    being checked
+    async fn validate_credentials(
+        &self,
+        auth_value: &str,
+    ) -> std::result::Result<Option<String>, VerifierBusy> {
         // 1. Check prefix without allocating a lowercased copy.
         if auth_value.len() < 6 || !auth_value[..6].eq_ignore_ascii_case("basic ") {
-            return false;
+            return Ok(None);
         }
 
         // 2. Decode Base64
         let credential_part = &auth_value[6..];
-        let Ok(decoded_bytes) = general_purpose::STANDARD.decode(credential_part) else {
-            return false;
-        };
-
-        let Ok(decoded_str) = String::from_utf8(decoded_bytes) else {
-            return false;
+        let Some(decoded_str) = general_purpose::STANDARD
+            .decode(credential_part)
+            .ok()
+            .and_then(|bytes| String::from_utf8(bytes).ok())
+        else {
+            return Ok(None);
         };
 
         // 3. Separate username:password
         let Some((user, pass)) = decoded_str.split_once(':') else {
-            return false;
+            return Ok(None);
         };
+        if let Some(identity) = self.inline_identity(user, pass) {
+            return Ok(Some(identity));
+        }
 
-        // 4. Hash each supplied value once and compare against configuration
-        // digests in constant time.
-        constant_time_digest_eq(&secret_digest(user), &self.username_digest)
-            & constant_time_digest_eq(&secret_digest(pass), &self.password_digest)
+        let Some(htpasswd) = self.htpasswd.as_ref() else {
+            return Ok(None);
+        };
+        let verified = htpasswd
+            .entries()
+            .await
+            .verify_blocking(user.to_string(), pass.to_string())
+            .await?;
+        Ok(verified.then(|| user.to_string()))
+    }
+
+    /// Identity of the inline credential matching `user` and `pass`.
+    fn inline_identity(&self, user: &str, pass: &str) -> Option<String> {
+        // Hash each supplied value once and compare against every configured
+        // digest, so the response time does not reveal which user matched.
+        let user_digest = secret_digest(user);
+        let pass_digest = secret_digest(pass);
+        let mut matched = None;
+        for cred in &self.credentials {
+            let ok = constant_time_digest_eq(&user_digest, &cred.username_digest)
+                & constant_time_digest_eq(&pass_digest, &cred.password_digest);
+            if ok && matched.is_none() {
+                matched = Some(cred.identity.clone());
+            }
+        }
+        matched
     }
 }
 
@@ -113,12 +241,24 @@ impl ProxyPlugin for PluginBasicAuth {
             ctx.mark_request_has_credentials();
         }
 
-        let is_valid = match auth_header {
-            Some(val) => self.validate_credentials(val),
-            None => false,
+        let identity = match auth_header {
+            Some(val) => match self.validate_credentials(val).await {
+                Ok(identity) => identity,
+                Err(VerifierBusy) => {
+                    ResponseBuilder::send_proxy_error(
+                        session,
+                        StatusCode::SERVICE_UNAVAILABLE,
+                        Some("Too many logins in progress"),
+                        Some(&[("Retry-After", "1")]),
+                    )
+                    .await?;
+                    return Ok(true);
+                }
+            },
+            None => None,
         };
 
-        if !is_valid {
+        let Some(identity) = identity else {
             // Return 401 and include the standard Basic challenge header
             ResponseBuilder::send_proxy_error(
                 session,
@@ -128,7 +268,9 @@ impl ProxyPlugin for PluginBasicAuth {
             )
             .await?;
             return Ok(true);
-        }
+        };
+
+        ctx.authenticated_identity = Some(identity);
 
         // Hide credentials by removing the Authorization header before forwarding upstream
         if self.config.hide_credentials {
@@ -147,32 +289,101 @@ mod tests {
 
     fn build_plugin(username: &str, password: &str) -> PluginBasicAuth {
         PluginBasicAuth {
-            config: PluginConfig {
-                username: username.to_string(),
-                password: password.to_string(),
-                hide_credentials: false,
-            },
-            username_digest: secret_digest(username),
-            password_digest: secret_digest(password),
+            config: PluginConfig::try_from(json!({"username": username, "password": password}))
+                .unwrap(),
+            credentials: vec![StoredCredential::new(username, password, None)],
+            htpasswd: None,
         }
     }
 
-    #[test]
-    fn validate_credentials_accepts_valid_pairs() {
+    fn basic(user_pass: &str) -> String {
+        format!("Basic {}", general_purpose::STANDARD.encode(user_pass))
+    }
+
+    #[tokio::test]
+    async fn validate_credentials_accepts_valid_pairs() {
         let plugin = build_plugin("demo", "s3cret");
-        let header = format!("Basic {}", general_purpose::STANDARD.encode("demo:s3cret"));
-        assert!(plugin.validate_credentials(&header));
+        let header = basic("demo:s3cret");
+        assert_eq!(
+            plugin
+                .validate_credentials(&header)
+                .await
+                .unwrap()
+                .as_deref(),
+            Some("demo")
+        );
     }
 
-    #[test]
-    fn validate_credentials_rejects_invalid_pairs() {
+    #[tokio::test]
+    async fn validate_credentials_rejects_invalid_pairs() {
         let plugin = build_plugin("demo", "s3cret");
 
         // Wrong prefix
-        assert!(!plugin.validate_credentials("Bearer something"));
+        assert!(plugin
+            .validate_credentials("Bearer something")
+            .await
+            .unwrap()
+            .is_none());
 
         // Wrong password
-        let header = format!("Basic {}", general_purpose::STANDARD.encode("demo:badpass"));
-        assert!(!plugin.validate_credentials(&header));
+        let header = basic("demo:badpass");
+        assert!(plugin
+            .validate_credentials(&header)
+            .await
+            .unwrap()
+            .is_none());
+    }
+
+    #[tokio::test]
+    async fn consumers_and_htpasswd_file_authenticate_with_their_identity() {
+        let path = std::env::temp_dir().join(format!("pingsix-htpasswd-{}", uuid::Uuid::new_v4()));
+        std::fs::write(&path, "carol:$apr1$saltsalt$yAAkm4libquA.ZWLHbSBq/\n").unwrap();
+
+        let plugin = PluginBasicAuth {
+            config: PluginConfig::try_from(json!({"htpasswd_file": path.to_str().unwrap()}))
+                .unwrap(),
+            credentials: vec![
+                StoredCredential::new("alice", "a-pass", Some("team-a")),
+                StoredCredential::new("bob", "b-pass", None),
+            ],
+            htpasswd: Some(HtpasswdFile::load(path.to_str().unwrap()).unwrap()),
+        };
+        let _ = std::fs::remove_file(&path);
+
+        assert_eq!(
+            plugin
+                .validate_credentials(&basic("alice:a-pass"))
+                .await
+                .unwrap()
+                .as_deref(),
+            Some("team-a")
+        );
+        assert_eq!(
+            plugin
+                .validate_credentials(&basic("bob:b-pass"))
+                .await
+                .unwrap()
+                .as_deref(),
+            Some("bob")
+        );
+        assert_eq!(
+            plugin
+                .validate_credentials(&basic("carol:password"))
+                .await
+                .unwrap()
+                .as_deref(),
+            Some("carol")
+        );
+        assert!(plugin
+            .validate_credentials(&basic("alice:b-pass"))
+            .await
+            .unwrap()
+            .is_none());
+    }
+
+    #[test]
+    fn config_requires_a_credential_source() {
+        assert!(PluginConfig::try_from(json!({})).is_err());
+        assert!(PluginConfig::try_from(json!({"username": "demo"})).is_err());
     }
 }
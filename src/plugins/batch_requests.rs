@@ -0,0 +1,587 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    net::{IpAddr, SocketAddr},
+    sync::Arc,
+    time::Duration,
+};
+
+use async_trait::async_trait;
+use base64::{engine::general_purpose, Engine as _};
+use bytes::{Bytes, BytesMut};
+use http::{header, HeaderName, Method, StatusCode};
+use once_cell::sync::Lazy;
+use pingora_core::{connectors::http::Connector as HttpConnector, upstreams::peer::HttpPeer};
+use pingora_error::Result;
+use pingora_http::{RequestHeader, ResponseHeader};
+use pingora_proxy::Session;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value as JsonValue};
+use tokio::time::Instant;
+use url::form_urlencoded;
+use validator::{Validate, ValidationError};
+
+use crate::{
+    core::{header_hygiene, ProxyContext, ProxyError, ProxyPlugin, ProxyResult},
+    utils::{
+        request::{
+            gateway_client_value, get_direct_client_ip, get_request_host, GATEWAY_CLIENT_HEADER,
+        },
+        response::{content_type, ResponseBuilder},
+    },
+};
+
+pub const PLUGIN_NAME: &str = "batch-requests";
+pub const PRIORITY: i32 = 4010;
+
+/// Marks sub-requests so a pipeline cannot call the batch endpoint again.
+const BATCH_MARKER_HEADER: &str = "x-pingsix-batch";
+
+/// Headers of the batch request that describe its own body or connection and so are
+/// not copied to sub-requests.
+const NOT_INHERITED: &[&str] = &[
+    "content-length",
+    "content-type",
+    "content-encoding",
+    "transfer-encoding",
+    "connection",
+    "keep-alive",
+    "expect",
+    "te",
+    "upgrade",
+];
+
+/// Idle time of pooled connections to the gateway's own listener.
+const POOL_IDLE_TIMEOUT: Duration = Duration::from_secs(60);
+
+static CONNECTOR: Lazy<HttpConnector> = Lazy::new(|| HttpConnector::new(None));
+
+/// Creates a batch-requests plugin that runs a pipeline of sub-requests through the
+/// gateway.
+pub fn create_batch_requests_plugin(cfg: JsonValue) -> ProxyResult<Arc<dyn ProxyPlugin>> {
+    let config = PluginConfig::try_from(cfg)?;
+    Ok(Arc::new(PluginBatchRequests { config }))
+}
+
+/// JSON Schema of the batch-requests plugin configuration.
+pub fn schema() -> JsonValue {
+    json!({
+        "type": "object",
+        "properties": {
+            "max_body_size": {"type": "integer", "minimum": 1, "default": 1048576},
+            "max_response_body_size": {"type": "integer", "minimum": 1, "default": 1048576},
+            "max_requests": {"type": "integer", "minimum": 1, "maximum": 100, "default": 20},
+            "batch_timeout": {"type": "integer", "minimum": 1, "default": 60000},
+            "address": {"type": "string"}
+        }
+    })
+}
+
+#[derive(Debug, Serialize, Deserialize, Validate)]
+pub(super) struct PluginConfig {
+    /// Largest batch request body accepted.
+    #[serde(default = "PluginConfig::default_body_size")]
+    #[validate(range(min = 1))]
+    max_body_size: usize,
+    /// Largest body kept per sub-response; larger ones are reported as `502`.
+    #[serde(default = "PluginConfig::default_body_size")]
+    #[validate(range(min = 1))]
+    max_response_body_size: usize,
+    /// Most sub-requests in one pipeline.
+    #[serde(default = "PluginConfig::default_max_requests")]
+    #[validate(range(min = 1, max = 100))]
+    max_requests: usize,
+    /// Deadline of a whole pipeline in milliseconds; sub-requests still running or not
+    /// yet started when it passes are reported as `504`.
+    #[serde(default = "PluginConfig::default_batch_timeout")]
+    #[validate(range(min = 1))]
+    batch_timeout: u64,
+    /// Plaintext listener (`ip:port`) sub-requests are sent to; defaults to the
+    /// listener the batch request arrived on.
+    #[serde(default)]
+    #[validate(custom(function = "PluginConfig::validate_address"))]
+    address: Option<String>,
+}
+
+impl PluginConfig {
+    fn default_body_size() -> usize {
+        1024 * 1024
+    }
+
+    fn default_max_requests() -> usize {
+        20
+    }
+
+    fn default_batch_timeout() -> u64 {
+        60_000
+    }
+
+    fn validate_address(address: &str) -> Result<(), ValidationError> {
+        address
+            .parse::<SocketAddr>()
+            .map(|_| ())
+            .map_err(|_| ValidationError::new("address must be an ip:port socket address"))
+    }
+}
+
+impl TryFrom<JsonValue> for PluginConfig {
+    type Error = ProxyError;
+
+    fn try_from(value: JsonValue) -> Result<Self, Self::Error> {
+        let config: PluginConfig = serde_json::from_value(value).map_err(|e| {
+            ProxyError::serialization_error("Invalid batch requests plugin config", e)
+        })?;
+
+        config.validate()?;
+
+        Ok(config)
+    }
+}
+
+/// The batch request body.
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Batch {
+    /// Headers added to every sub-request.
+    #[serde(default)]
+    headers: HashMap<String, String>,
+    /// Query arguments added to every sub-request.
+    #[serde(default)]
+    query: HashMap<String, String>,
+    /// Timeout of each sub-request in milliseconds.
+    #[serde(default = "Batch::default_timeout")]
+    timeout: u64,
+    pipeline: Vec<SubRequest>,
+}
+
+impl Batch {
+    fn default_timeout() -> u64 {
+        30_000
+    }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct SubRequest {
+    #[serde(default = "SubRequest::default_method")]
+    method: String,
+    path: String,
+    #[serde(default)]
+    headers: HashMap<String, String>,
+    #[serde(default)]
+    query: HashMap<String, String>,
+    #[serde(default)]
+    body: Option<String>,
+}
+
+impl SubRequest {
+    fn default_method() -> String {
+        "GET".to_string()
+    }
+}
+
+#[derive(Debug, Serialize)]
+struct SubResponse {
+    status: u16,
+    reason: String,
+    headers: BTreeMap<String, String>,
+    body: String,
+    /// `base64` when the body is not valid UTF-8.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    body_encoding: Option<&'static str>,
+}
+
+impl SubResponse {
+    fn error(status: StatusCode, reason: impl Into<String>) -> Self {
+        Self {
+            status: status.as_u16(),
+            reason: reason.into(),
+            headers: BTreeMap::new(),
+            body: String::new(),
+            body_encoding: None,
+        }
+    }
+}
+
+/// A sub-response body as text, base64-encoded when it is not valid UTF-8.
+fn encode_body(body: Vec<u8>) -> (String, Option<&'static str>) {
+    match String::from_utf8(body) {
+        Ok(text) => (text, None),
+        Err(e) => (
+            general_purpose::STANDARD.encode(e.into_bytes()),
+            Some("base64"),
+        ),
+    }
+}
+
+/// Parses and checks a batch body.
+fn parse_batch(body: &[u8], max_requests: usize) -> Result<Batch, String> {
+    let batch: Batch =
+        serde_json::from_slice(body).map_err(|e| format!("invalid batch request: {e}"))?;
+    if batch.pipeline.is_empty() {
+        return Err("pipeline must not be empty".to_string());
+    }
+    if batch.pipeline.len() > max_requests {
+        return Err(format!("pipeline exceeds {max_requests} requests"));
+    }
+    if batch.timeout == 0 {
+        return Err("timeout must be positive".to_string());
+    }
+    for (i, sub) in batch.pipeline.iter().enumerate() {
+        if !sub.path.starts_with('/') {
+            return Err(format!("pipeline[{i}].path must start with '/'"));
+        }
+        Method::from_bytes(sub.method.as_bytes())
+            .map_err(|_| format!("pipeline[{i}].method '{}' is invalid", sub.method))?;
+    }
+    Ok(batch)
+}
+
+/// Builds the header of one sub-request: the batch request's headers, then the batch
+/// `headers`, then the sub-request's own. Internal headers are never taken from the
+/// batch body, since sub-requests arrive from the gateway itself; the client address is
+/// passed on in [`GATEWAY_CLIENT_HEADER`] so address-based plugins see the real client.
+fn build_sub_request(
+    original: &RequestHeader,
+    batch: &Batch,
+    sub: &SubRequest,
+    client_ip: Option<IpAddr>,
+) -> Result<RequestHeader> {
+    let mut path = sub.path.clone();
+    if !batch.query.is_empty() || !sub.query.is_empty() {
+        let mut query = form_urlencoded::Serializer::new(String::new());
+        for (name, value) in batch
+            .query
+            .iter()
+            .filter(|(n, _)| !sub.query.contains_key(*n))
+        {
+            query.append_pair(name, value);
+        }
+        for (name, value) in &sub.query {
+            query.append_pair(name, value);
+        }
+        path.push(if path.contains('?') { '&' } else { '?' });
+        path.push_str(&query.finish());
+    }
+
+    let mut req = RequestHeader::build(sub.method.as_str(), path.as_bytes(), None)?;
+    for (name, value) in &original.headers {
+        if !NOT_INHERITED.contains(&name.as_str()) {
+            req.append_header(name.clone(), value.clone())?;
+        }
+    }
+    // HTTP/2 clients send the host as the `:authority` pseudo-header.
+    if !req.headers.contains_key(header::HOST) {
+        if let Some(authority) = original.uri.authority() {
+            req.insert_header(header::HOST, authority.as_str())?;
+        }
+    }
+    for (name, value) in batch.headers.iter().chain(&sub.headers) {
+        let name = HeaderName::from_bytes(name.as_bytes()).map_err(|e| {
+            pingora_error::Error::because(pingora_error::ErrorType::InvalidHTTPHeader, "", e)
+        })?;
+        if NOT_INHERITED.contains(&name.as_str())
+            || name.as_str() == GATEWAY_CLIENT_HEADER
+            || header_hygiene::is_internal_header(&name)
+        {
+            continue;
+        }
+        req.insert_header(name, value.as_str())?;
+    }
+
+    if let Some(ip) = client_ip {
+        let forwarded = match req
+            .headers
+            .get("x-forwarded-for")
+            .and_then(|v| v.to_str().ok())
+        {
+            Some(existing) => format!("{existing}, {ip}"),
+            None => ip.to_string(),
+        };
+        req.insert_header("x-forwarded-for", forwarded)?;
+        req.insert_header(GATEWAY_CLIENT_HEADER, gateway_client_value(ip))?;
+    } else {
+        req.remove_header(GATEWAY_CLIENT_HEADER);
+    }
+    req.insert_header(BATCH_MARKER_HEADER, "1")?;
+    let body_len = sub.body.as_ref().map_or(0, String::len);
+    if body_len > 0 || sub.method != "GET" {
+        req.insert_header(header::CONTENT_LENGTH, body_len.to_string())?;
+    }
+    Ok(req)
+}
+
+pub struct PluginBatchRequests {
+    config: PluginConfig,
+}
+
+impl PluginBatchRequests {
+    /// Reads the whole batch body, or `None` once it exceeds `max_body_size`.
+    async fn read_body(&self, session: &mut Session) -> Result<Option<Bytes>> {
+        let mut body = BytesMut::new();
+        while let Some(chunk) = session.read_request_body().await? {
+            body.extend_from_slice(&chunk);
+            if body.len() > self.config.max_body_size {
+                return Ok(None);
+            }
+        }
+        Ok(Some(body.freeze()))
+    }
+
+    /// The gateway listener sub-requests are sent to.
+    fn gateway_peer(&self, session: &Session) -> Option<HttpPeer> {
+        if let Some(address) = &self.config.address {
+            return Some(HttpPeer::new(address.as_str(), false, String::new()));
+        }
+        let local = session.server_addr()?.as_inet()?;
+        let tls = session
+            .digest()
+            .is_some_and(|digest| digest.ssl_digest.is_some());
+        let sni = get_request_host(session.req_header())
+            .unwrap_or_default()
+            .to_string();
+        let mut peer = HttpPeer::new(*local, tls, sni);
+        // The listener is the gateway itself; its certificate is for client hostnames.
+        peer.options.verify_cert = false;
+        peer.options.verify_hostname = false;
+        Some(peer)
+    }
+
+    async fn execute(
+        &self,
+        peer: &HttpPeer,
+        req: RequestHeader,
+        body: Option<String>,
+        timeout: Duration,
+    ) -> SubResponse {
+        match tokio::time::timeout(timeout, self.send(peer, req, body)).await {
+            Ok(Ok(resp)) => resp,
+            Ok(Err(e)) => {
+                log::warn!("Batch sub-request failed: {e}");
+                SubResponse::error(StatusCode::BAD_GATEWAY, e.to_string())
+            }
+            Err(_) => SubResponse::error(StatusCode::GATEWAY_TIMEOUT, "upstream timeout"),
+        }
+    }
+
+    async fn send(
+        &self,
+        peer: &HttpPeer,
+        req: RequestHeader,
+        body: Option<String>,
+    ) -> Result<SubResponse> {
+        let (mut client, _) = CONNECTOR.get_http_session(peer).await?;
+        client.write_request_header(Box::new(req)).await?;
+        if let Some(body) = body.filter(|body| !body.is_empty()) {
+            client.write_request_body(Bytes::from(body), true).await?;
+        }
+        client.finish_request_body().await?;
+        client.read_response_header().await?;
+
+        let mut body = BytesMut::new();
+        while let Some(chunk) = client.read_response_body().await? {
+            body.extend_from_slice(&chunk);
+            if body.len() > self.config.max_response_body_size {
+                return Ok(SubResponse::error(
+                    StatusCode::BAD_GATEWAY,
+                    "response body too large",
+                ));
+            }
+        }
+
+        let resp: &ResponseHeader = client.response_header().expect("response header was read");
+        let mut headers = BTreeMap::new();
+        for (name, value) in &resp.headers {
+            if let Ok(value) = value.to_str() {
+                headers.insert(name.to_string(), value.to_string());
+            }
+        }
+        let (body, body_encoding) = encode_body(body.to_vec());
+        let sub = SubResponse {
+            status: resp.status.as_u16(),
+            reason: resp
+                .status
+                .canonical_reason()
+                .unwrap_or_default()
+                .to_string(),
+            headers,
+            body,
+            body_encoding,
+        };
+        CONNECTOR
+            .release_http_session(client, peer, Some(POOL_IDLE_TIMEOUT))
+            .await;
+        Ok(sub)
+    }
+}
+
+#[async_trait]
+impl ProxyPlugin for PluginBatchRequests {
+    fn name(&self) -> &str {
+        PLUGIN_NAME
+    }
+
+    fn priority(&self) -> i32 {
+        PRIORITY
+    }
+
+    async fn request_filter(&self, session: &mut Session, _ctx: &mut ProxyContext) -> Result<bool> {
+        let outcome = if session.req_header().method != Method::POST {
+            Err((
+                StatusCode::METHOD_NOT_ALLOWED,
+                "batch requests must use POST".to_string(),
+            ))
+        } else if session
+            .req_header()
+            .headers
+            .contains_key(BATCH_MARKER_HEADER)
+        {
+            Err((
+                StatusCode::BAD_REQUEST,
+                "nested batch requests are not allowed".to_string(),
+            ))
+        } else {
+            match self.read_body(session).await? {
+                None => Err((
+                    StatusCode::PAYLOAD_TOO_LARGE,
+                    format!("batch request exceeds {} bytes", self.config.max_body_size),
+                )),
+                Some(body) => parse_batch(&body, self.config.max_requests)
+                    .map_err(|e| (StatusCode::BAD_REQUEST, e)),
+            }
+        };
+        let batch = match outcome {
+            Ok(batch) => batch,
+            Err((status, message)) => {
+                ResponseBuilder::send_proxy_error(session, status, Some(&message), None).await?;
+                return Ok(true);
+            }
+        };
+
+        let Some(peer) = self.gateway_peer(session) else {
+            ResponseBuilder::send_proxy_error(
+                session,
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Some("batch requests need an inet listener or a configured address"),
+                None,
+            )
+            .await?;
+            return Ok(true);
+        };
+
+        let client_ip = get_direct_client_ip(session);
+        let timeout = Duration::from_millis(batch.timeout);
+        let deadline = Instant::now() + Duration::from_millis(self.config.batch_timeout);
+        let mut responses = Vec::with_capacity(batch.pipeline.len());
+        for sub in &batch.pipeline {
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            let resp = if remaining.is_zero() {
+                SubResponse::error(StatusCode::GATEWAY_TIMEOUT, "batch timeout")
+            } else {
+                match build_sub_request(session.req_header(), &batch, sub, client_ip) {
+                    Ok(req) => {
+                        self.execute(&peer, req, sub.body.clone(), timeout.min(remaining))
+                            .await
+                    }
+                    Err(e) => SubResponse::error(StatusCode::BAD_REQUEST, e.to_string()),
+                }
+            };
+            responses.push(resp);
+        }
+
+        let body = serde_json::to_vec(&responses).map_err(|e| {
+            pingora_error::Error::because(
+                pingora_error::ErrorType::InternalError,
+                "failed to encode batch responses",
+                e,
+            )
+        })?;
+        let mut resp = ResponseHeader::build(StatusCode::OK, None)?;
+        resp.insert_header(header::CONTENT_TYPE, content_type::APPLICATION_JSON)?;
+        resp.insert_header(header::CONTENT_LENGTH, body.len().to_string())?;
+        session.write_response_header(Box::new(resp), false).await?;
+        session
+            .write_response_body(Some(Bytes::from(body)), true)
+            .await?;
+        Ok(true)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn header<'a>(req: &'a RequestHeader, name: &str) -> Option<&'a str> {
+        req.headers.get(name).and_then(|v| v.to_str().ok())
+    }
+
+    #[test]
+    fn pipelines_are_checked() {
+        let body = br#"{"pipeline": [{"path": "/a"}, {"method": "POST", "path": "/b"}]}"#;
+        let batch = parse_batch(body, 2).unwrap();
+        assert_eq!(batch.pipeline[0].method, "GET");
+        assert_eq!(batch.timeout, 30_000);
+
+        assert!(parse_batch(body, 1).is_err());
+        assert!(parse_batch(br#"{"pipeline": []}"#, 2).is_err());
+        assert!(parse_batch(br#"{"pipeline": [{"path": "a"}]}"#, 2).is_err());
+        assert!(parse_batch(br#"{"pipeline": [{"path": "/a", "method": "G T"}]}"#, 2).is_err());
+    }
+
+    #[test]
+    fn sub_requests_inherit_and_override_headers_and_query() {
+        let mut original = RequestHeader::build("POST", b"/batch", None).unwrap();
+        original.insert_header("Host", "api.example.com").unwrap();
+        original.insert_header("Authorization", "Bearer t").unwrap();
+        original.insert_header("Content-Length", "120").unwrap();
+        original.insert_header("X-Env", "outer").unwrap();
+        let batch = parse_batch(
+            br#"{
+                "headers": {"X-Env": "batch", "X-Common": "1", "X-Pingsix-Client-Addr": "10.0.0.1"},
+                "query": {"lang": "en", "v": "1"},
+                "pipeline": [{
+                    "method": "POST",
+                    "path": "/users?id=7",
+                    "headers": {"X-Env": "sub"},
+                    "query": {"v": "2"},
+                    "body": "{}"
+                }]
+            }"#,
+            20,
+        )
+        .unwrap();
+
+        let req = build_sub_request(
+            &original,
+            &batch,
+            &batch.pipeline[0],
+            Some("203.0.113.9".parse().unwrap()),
+        )
+        .unwrap();
+        let uri = req.uri.to_string();
+        assert!(uri.starts_with("/users?id=7&"));
+        assert!(uri.contains("lang=en") && uri.contains("v=2") && !uri.contains("v=1"));
+        assert_eq!(header(&req, "host"), Some("api.example.com"));
+        assert_eq!(header(&req, "authorization"), Some("Bearer t"));
+        assert_eq!(header(&req, "x-env"), Some("sub"));
+        assert_eq!(header(&req, "x-common"), Some("1"));
+        assert_eq!(header(&req, "content-length"), Some("2"));
+        assert_eq!(header(&req, "x-forwarded-for"), Some("203.0.113.9"));
+        assert_eq!(
+            header(&req, GATEWAY_CLIENT_HEADER),
+            Some(gateway_client_value("203.0.113.9".parse().unwrap()).as_str())
+        );
+        assert_eq!(header(&req, BATCH_MARKER_HEADER), Some("1"));
+    }
+
+    #[test]
+    fn binary_bodies_are_base64_encoded() {
+        assert_eq!(
+            encode_body(b"{\"ok\":true}".to_vec()),
+            ("{\"ok\":true}".to_string(), None)
+        );
+        assert_eq!(
+            encode_body(vec![0x89, b'P', b'N', b'G']),
+            ("iVBORw==".to_string(), Some("base64"))
+        );
+    }
+}
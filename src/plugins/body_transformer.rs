@@ -0,0 +1,731 @@
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use bytes::{Bytes, BytesMut};
+use http::{header, HeaderMap, Method};
+use once_cell::sync::Lazy;
+use pingora_error::{Error, ErrorType, Result};
+use pingora_http::{RequestHeader, ResponseHeader};
+use pingora_proxy::Session;
+use quick_xml::{
+    escape::{escape, resolve_predefined_entity},
+    events::{BytesStart, Event},
+    Reader,
+};
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Map, Value as JsonValue};
+use validator::Validate;
+
+use crate::{
+    core::{ProxyContext, ProxyError, ProxyPlugin, ProxyResult},
+    utils::request::{request_has_body, set_chunked_body},
+};
+
+pub const PLUGIN_NAME: &str = "body-transformer";
+pub const PRIORITY: i32 = 1080;
+
+/// Context keys for the per-request body buffers.
+const REQUEST_STATE_KEY: &str = "body-transformer-request";
+const RESPONSE_STATE_KEY: &str = "body-transformer-response";
+
+/// `{{ path.to.field }}` placeholders in templates.
+static PLACEHOLDER_RE: Lazy<Regex> =
+    Lazy::new(|| Regex::new(r"\{\{\s*([^{}\s]+)\s*\}\}").expect("placeholder regex must compile"));
+
+pub fn create_body_transformer_plugin(cfg: JsonValue) -> ProxyResult<Arc<dyn ProxyPlugin>> {
+    let config = PluginConfig::try_from(cfg)?;
+    Ok(Arc::new(PluginBodyTransformer { config }))
+}
+
+/// JSON Schema of the body-transformer plugin configuration.
+pub fn schema() -> JsonValue {
+    let format = json!({"type": "string", "enum": ["json", "xml"]});
+    let transform = json!({
+        "type": "object",
+        "required": ["output_format"],
+        "properties": {
+            "input_format": format,
+            "output_format": format,
+            "template": {"type": "string"}
+        }
+    });
+    json!({
+        "type": "object",
+        "properties": {
+            "request": transform,
+            "response": transform,
+            "max_body_bytes": {"type": "integer", "minimum": 1, "default": 1024 * 1024}
+        }
+    })
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum BodyFormat {
+    Json,
+    Xml,
+}
+
+impl BodyFormat {
+    /// Detect the body format from a `Content-Type` value.
+    fn from_content_type(value: &str) -> Option<Self> {
+        let value = value.to_ascii_lowercase();
+        if value.contains("json") {
+            Some(Self::Json)
+        } else if value.contains("xml") {
+            Some(Self::Xml)
+        } else {
+            None
+        }
+    }
+
+    fn content_type(self) -> &'static str {
+        match self {
+            Self::Json => "application/json",
+            Self::Xml => "text/xml; charset=utf-8",
+        }
+    }
+}
+
+/// One direction of the transformation.
+#[derive(Debug, Serialize, Deserialize, Validate)]
+struct TransformConfig {
+    /// Format of the incoming body; detected from `Content-Type` when unset.
+    input_format: Option<BodyFormat>,
+    output_format: BodyFormat,
+    /// Output template with `{{ path }}` placeholders resolved against the parsed body.
+    /// Without a template the parsed body is converted to `output_format` as a whole.
+    template: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Validate)]
+pub(super) struct PluginConfig {
+    #[validate(nested)]
+    request: Option<TransformConfig>,
+    #[validate(nested)]
+    response: Option<TransformConfig>,
+    /// Largest body buffered for transformation.
+    #[serde(default = "PluginConfig::default_max_body_bytes")]
+    #[validate(range(min = 1))]
+    max_body_bytes: usize,
+}
+
+impl PluginConfig {
+    fn default_max_body_bytes() -> usize {
+        1024 * 1024
+    }
+}
+
+impl TryFrom<JsonValue> for PluginConfig {
+    type Error = ProxyError;
+
+    fn try_from(value: JsonValue) -> Result<Self, Self::Error> {
+        let config: PluginConfig = serde_json::from_value(value).map_err(|e| {
+            ProxyError::serialization_error("Failed to parse body-transformer plugin config", e)
+        })?;
+        config.validate()?;
+        if config.request.is_none() && config.response.is_none() {
+            return Err(ProxyError::validation_error(
+                "body-transformer requires 'request' and/or 'response'",
+            ));
+        }
+        Ok(config)
+    }
+}
+
+/// Buffered body for one direction of the current request.
+struct BodyBuffer {
+    input_format: BodyFormat,
+    data: BytesMut,
+}
+
+pub struct PluginBodyTransformer {
+    config: PluginConfig,
+}
+
+/// Length announced in `Content-Length`.
+fn content_length(headers: &HeaderMap) -> Option<usize> {
+    headers
+        .get(header::CONTENT_LENGTH)?
+        .to_str()
+        .ok()?
+        .trim()
+        .parse()
+        .ok()
+}
+
+/// Whether the response to a `method` request carries a body.
+fn response_has_body(method: &Method, resp: &ResponseHeader) -> bool {
+    *method != Method::HEAD
+        && !resp.status.is_informational()
+        && !matches!(resp.status.as_u16(), 204 | 304)
+        && content_length(&resp.headers) != Some(0)
+}
+
+impl PluginBodyTransformer {
+    /// Input format of a body, if it should be transformed.
+    fn input_format(spec: &TransformConfig, content_type: Option<&str>) -> Option<BodyFormat> {
+        spec.input_format
+            .or_else(|| content_type.and_then(BodyFormat::from_content_type))
+    }
+
+    /// Append a chunk to the buffer; returns true once it exceeds `max_body_bytes`.
+    fn buffer_chunk(&self, state: &mut BodyBuffer, body: &mut Option<Bytes>) -> bool {
+        if let Some(chunk) = body.take() {
+            state.data.extend_from_slice(&chunk);
+        }
+        state.data.len() > self.config.max_body_bytes
+    }
+}
+
+#[async_trait]
+impl ProxyPlugin for PluginBodyTransformer {
+    fn name(&self) -> &str {
+        PLUGIN_NAME
+    }
+
+    fn priority(&self) -> i32 {
+        PRIORITY
+    }
+
+    async fn request_filter(&self, session: &mut Session, ctx: &mut ProxyContext) -> Result<bool> {
+        let Some(spec) = &self.config.request else {
+            return Ok(false);
+        };
+        if !request_has_body(session.req_header()) {
+            return Ok(false);
+        }
+        let content_type = session
+            .req_header()
+            .headers
+            .get(header::CONTENT_TYPE)
+            .and_then(|v| v.to_str().ok());
+        if let Some(input_format) = Self::input_format(spec, content_type) {
+            ctx.set(
+                REQUEST_STATE_KEY,
+                BodyBuffer {
+                    input_format,
+                    data: BytesMut::new(),
+                },
+            );
+        }
+        Ok(false)
+    }
+
+    async fn upstream_request_filter(
+        &self,
+        _session: &mut Session,
+        upstream_request: &mut RequestHeader,
+        ctx: &mut ProxyContext,
+    ) -> Result<()> {
+        let (Some(spec), Some(_)) = (
+            &self.config.request,
+            ctx.get::<BodyBuffer>(REQUEST_STATE_KEY),
+        ) else {
+            return Ok(());
+        };
+        set_chunked_body(upstream_request)?;
+        upstream_request.insert_header(header::CONTENT_TYPE, spec.output_format.content_type())?;
+        Ok(())
+    }
+
+    fn has_request_body_filter(&self) -> bool {
+        self.config.request.is_some()
+    }
+
+    async fn request_body_filter(
+        &self,
+        _session: &mut Session,
+        body: &mut Option<Bytes>,
+        end_of_stream: bool,
+        ctx: &mut ProxyContext,
+    ) -> Result<()> {
+        let Some(spec) = &self.config.request else {
+            return Ok(());
+        };
+        let Some(state) = ctx.get_mut::<BodyBuffer>(REQUEST_STATE_KEY) else {
+            return Ok(());
+        };
+
+        if self.buffer_chunk(state, body) {
+            return Error::e_explain(
+                ErrorType::HTTPStatus(413),
+                "request body exceeds body-transformer max_body_bytes",
+            );
+        }
+        if !end_of_stream {
+            return Ok(());
+        }
+
+        let input = std::mem::take(&mut state.data).freeze();
+        match transform(&input, state.input_format, spec) {
+            Ok(output) => *body = Some(Bytes::from(output)),
+            Err(e) => {
+                return Error::e_explain(
+                    ErrorType::HTTPStatus(400),
+                    format!("body-transformer failed to transform request body: {e}"),
+                )
+            }
+        }
+        Ok(())
+    }
+
+    async fn response_filter(
+        &self,
+        session: &mut Session,
+        upstream_response: &mut ResponseHeader,
+        ctx: &mut ProxyContext,
+    ) -> Result<()> {
+        let Some(spec) = &self.config.response else {
+            return Ok(());
+        };
+        // Streams never end in time to be buffered and converted.
+        if ctx.streaming {
+            return Ok(());
+        }
+        let content_type = upstream_response
+            .headers
+            .get(header::CONTENT_TYPE)
+            .and_then(|v| v.to_str().ok());
+        let encoded = upstream_response
+            .headers
+            .get(header::CONTENT_ENCODING)
+            .is_some_and(|v| !v.as_bytes().eq_ignore_ascii_case(b"identity"));
+        let Some(input_format) = Self::input_format(spec, content_type).filter(|_| !encoded) else {
+            return Ok(());
+        };
+        // The headers go out before the body is seen, so bodies known to be too large keep
+        // their headers and pass through.
+        if !response_has_body(&session.req_header().method, upstream_response)
+            || content_length(&upstream_response.headers)
+                .is_some_and(|len| len > self.config.max_body_bytes)
+        {
+            return Ok(());
+        }
+
+        ctx.set(
+            RESPONSE_STATE_KEY,
+            BodyBuffer {
+                input_format,
+                data: BytesMut::new(),
+            },
+        );
+        upstream_response.remove_header(&header::CONTENT_LENGTH);
+        if !session.is_http2() {
+            upstream_response.insert_header(header::TRANSFER_ENCODING, "chunked")?;
+        }
+        upstream_response.insert_header(header::CONTENT_TYPE, spec.output_format.content_type())?;
+        Ok(())
+    }
+
+    fn has_response_body_filter(&self) -> bool {
+        self.config.response.is_some()
+    }
+
+    fn response_body_filter(
+        &self,
+        _session: &mut Session,
+        body: &mut Option<Bytes>,
+        end_of_stream: bool,
+        ctx: &mut ProxyContext,
+    ) -> Result<()> {
+        let Some(spec) = &self.config.response else {
+            return Ok(());
+        };
+        let Some(state) = ctx.get_mut::<BodyBuffer>(RESPONSE_STATE_KEY) else {
+            return Ok(());
+        };
+
+        // The output `Content-Type` was already sent, so a body that cannot be converted
+        // is not passed through under it; the response is aborted instead.
+        if self.buffer_chunk(state, body) {
+            return Error::e_explain(
+                ErrorType::InternalError,
+                "body-transformer: response body without Content-Length exceeds max_body_bytes",
+            );
+        }
+        if !end_of_stream {
+            return Ok(());
+        }
+
+        let input = std::mem::take(&mut state.data).freeze();
+        match transform(&input, state.input_format, spec) {
+            Ok(output) => *body = Some(Bytes::from(output)),
+            Err(e) => {
+                return Error::e_explain(
+                    ErrorType::InternalError,
+                    format!("body-transformer failed to transform response body: {e}"),
+                )
+            }
+        }
+        Ok(())
+    }
+}
+
+// =============================================================================
+// TRANSFORMATION
+// =============================================================================
+
+fn transform(
+    input: &[u8],
+    input_format: BodyFormat,
+    spec: &TransformConfig,
+) -> std::result::Result<String, String> {
+    let text = std::str::from_utf8(input).map_err(|e| format!("body is not UTF-8: {e}"))?;
+    let document = match input_format {
+        BodyFormat::Json => serde_json::from_str(text).map_err(|e| e.to_string())?,
+        BodyFormat::Xml => xml_to_json(text)?,
+    };
+
+    Ok(match &spec.template {
+        Some(template) => render_template(template, &document, spec.output_format),
+        None => match spec.output_format {
+            BodyFormat::Json => document.to_string(),
+            BodyFormat::Xml => json_to_xml(&document)?,
+        },
+    })
+}
+
+/// Replace `{{ path }}` placeholders with values from `document`.
+///
+/// Values are JSON-encoded for JSON output (missing values render as `null`) and
+/// XML-escaped text for XML output (missing values render empty).
+fn render_template(template: &str, document: &JsonValue, format: BodyFormat) -> String {
+    PLACEHOLDER_RE
+        .replace_all(template, |caps: &regex::Captures| {
+            let value = lookup(document, &caps[1]);
+            match format {
+                BodyFormat::Json => value.map_or_else(|| "null".to_string(), |v| v.to_string()),
+                BodyFormat::Xml => match value {
+                    None | Some(JsonValue::Null) => String::new(),
+                    Some(JsonValue::String(s)) => escape(s.as_str()).into_owned(),
+                    Some(other) => escape(other.to_string()).into_owned(),
+                },
+            }
+        })
+        .into_owned()
+}
+
+/// Resolve a dotted path; numeric segments index arrays.
+fn lookup<'a>(document: &'a JsonValue, path: &str) -> Option<&'a JsonValue> {
+    path.split('.')
+        .try_fold(document, |value, segment| match value {
+            JsonValue::Object(map) => map.get(segment),
+            JsonValue::Array(items) => segment.parse::<usize>().ok().and_then(|i| items.get(i)),
+            _ => None,
+        })
+}
+
+/// Element being assembled while parsing XML.
+struct XmlFrame {
+    name: String,
+    fields: Map<String, JsonValue>,
+    text: String,
+}
+
+impl XmlFrame {
+    fn open(start: &BytesStart) -> std::result::Result<Self, String> {
+        let mut fields = Map::new();
+        for attr in start.attributes() {
+            let attr = attr.map_err(|e| e.to_string())?;
+            let key = attr.key.as_ref();
+            if key == b"xmlns" || key.starts_with(b"xmlns:") {
+                continue;
+            }
+            let value = attr.unescape_value().map_err(|e| e.to_string())?;
+            fields.insert(
+                format!(
+                    "@{}",
+                    String::from_utf8_lossy(attr.key.local_name().as_ref())
+                ),
+                JsonValue::String(value.into_owned()),
+            );
+        }
+        Ok(Self {
+            name: String::from_utf8_lossy(start.local_name().as_ref()).into_owned(),
+            fields,
+            text: String::new(),
+        })
+    }
+
+    fn close(self) -> (String, JsonValue) {
+        let text = self.text.trim();
+        if self.fields.is_empty() {
+            return (self.name, JsonValue::String(text.to_string()));
+        }
+        let mut fields = self.fields;
+        if !text.is_empty() {
+            fields.insert("#text".to_string(), JsonValue::String(text.to_string()));
+        }
+        (self.name, JsonValue::Object(fields))
+    }
+}
+
+/// Add a child element, turning repeated names into arrays.
+fn insert_child(fields: &mut Map<String, JsonValue>, name: String, value: JsonValue) {
+    match fields.get_mut(&name) {
+        None => {
+            fields.insert(name, value);
+        }
+        Some(JsonValue::Array(items)) => items.push(value),
+        Some(existing) => {
+            let first = existing.take();
+            *existing = JsonValue::Array(vec![first, value]);
+        }
+    }
+}
+
+/// Convert XML into JSON: elements become keys (namespace prefixes dropped), attributes
+/// become `@name` keys, mixed text becomes `#text`, and repeated elements become arrays.
+fn xml_to_json(input: &str) -> std::result::Result<JsonValue, String> {
+    let mut reader = Reader::from_str(input);
+    let mut stack = vec![XmlFrame {
+        name: String::new(),
+        fields: Map::new(),
+        text: String::new(),
+    }];
+
+    loop {
+        let event = reader.read_event().map_err(|e| e.to_string())?;
+        match event {
+            Event::Start(start) => stack.push(XmlFrame::open(&start)?),
+            Event::Empty(start) => {
+                let (name, value) = XmlFrame::open(&start)?.close();
+                let parent = stack.last_mut().ok_or("unbalanced XML")?;
+                insert_child(&mut parent.fields, name, value);
+            }
+            Event::End(_) => {
+                let frame = stack.pop().ok_or("unbalanced XML")?;
+                let (name, value) = frame.close();
+                let parent = stack.last_mut().ok_or("unbalanced XML")?;
+                insert_child(&mut parent.fields, name, value);
+            }
+            Event::Text(text) => {
+                let text = text.decode().map_err(|e| e.to_string())?;
+                if let Some(frame) = stack.last_mut() {
+                    frame.text.push_str(&text);
+                }
+            }
+            Event::CData(data) => {
+                let data = data.decode().map_err(|e| e.to_string())?;
+                if let Some(frame) = stack.last_mut() {
+                    frame.text.push_str(&data);
+                }
+            }
+            Event::GeneralRef(reference) => {
+                let resolved = match reference.resolve_char_ref().map_err(|e| e.to_string())? {
+                    Some(ch) => ch.to_string(),
+                    None => {
+                        let name = reference.decode().map_err(|e| e.to_string())?;
+                        resolve_predefined_entity(&name)
+                            .ok_or_else(|| format!("unknown XML entity &{name};"))?
+                            .to_string()
+                    }
+                };
+                if let Some(frame) = stack.last_mut() {
+                    frame.text.push_str(&resolved);
+                }
+            }
+            Event::Eof => break,
+            _ => {}
+        }
+    }
+
+    match stack.pop() {
+        Some(root) if stack.is_empty() => Ok(JsonValue::Object(root.fields)),
+        _ => Err("unclosed XML element".to_string()),
+    }
+}
+
+/// Convert JSON into XML, inverting the conventions of [`xml_to_json`]. A single-key
+/// object becomes the document element; anything else is wrapped in `<root>`. Keys that
+/// are not valid XML names fail the conversion.
+fn json_to_xml(document: &JsonValue) -> std::result::Result<String, String> {
+    let mut out = String::from(r#"<?xml version="1.0" encoding="UTF-8"?>"#);
+    match document {
+        JsonValue::Object(map) if map.len() == 1 => {
+            for (name, value) in map {
+                write_element(&mut out, name, value)?;
+            }
+        }
+        other => write_element(&mut out, "root", other)?,
+    }
+    Ok(out)
+}
+
+/// Whether `name` matches the XML 1.0 `Name` production.
+fn is_xml_name(name: &str) -> bool {
+    let start = |c: char| {
+        matches!(c,
+            ':' | 'A'..='Z' | '_' | 'a'..='z'
+            | '\u{C0}'..='\u{D6}' | '\u{D8}'..='\u{F6}' | '\u{F8}'..='\u{2FF}'
+            | '\u{370}'..='\u{37D}' | '\u{37F}'..='\u{1FFF}' | '\u{200C}'..='\u{200D}'
+            | '\u{2070}'..='\u{218F}' | '\u{2C00}'..='\u{2FEF}' | '\u{3001}'..='\u{D7FF}'
+            | '\u{F900}'..='\u{FDCF}' | '\u{FDF0}'..='\u{FFFD}' | '\u{10000}'..='\u{EFFFF}')
+    };
+    let rest = |c: char| {
+        start(c)
+            || matches!(c,
+                '-' | '.' | '0'..='9' | '\u{B7}' | '\u{300}'..='\u{36F}' | '\u{203F}'..='\u{2040}')
+    };
+    let mut chars = name.chars();
+    chars.next().is_some_and(start) && chars.all(rest)
+}
+
+fn check_xml_name(name: &str) -> std::result::Result<&str, String> {
+    if is_xml_name(name) {
+        Ok(name)
+    } else {
+        Err(format!("'{name}' is not a valid XML name"))
+    }
+}
+
+fn write_element(
+    out: &mut String,
+    name: &str,
+    value: &JsonValue,
+) -> std::result::Result<(), String> {
+    let name = check_xml_name(name)?;
+    match value {
+        JsonValue::Array(items) => {
+            for item in items {
+                write_element(out, name, item)?;
+            }
+        }
+        JsonValue::Object(map) => {
+            out.push('<');
+            out.push_str(name);
+            for (key, value) in map {
+                if let Some(attr) = key.strip_prefix('@') {
+                    let attr = check_xml_name(attr)?;
+                    let value = value
+                        .as_str()
+                        .map_or_else(|| value.to_string(), str::to_string);
+                    out.push_str(&format!(" {attr}=\"{}\"", escape(value)));
+                }
+            }
+            out.push('>');
+            for (key, value) in map {
+                if key == "#text" {
+                    out.push_str(&scalar_text(value));
+                } else if !key.starts_with('@') {
+                    write_element(out, key, value)?;
+                }
+            }
+            out.push_str(&format!("</{name}>"));
+        }
+        JsonValue::Null => out.push_str(&format!("<{name}/>")),
+        scalar => out.push_str(&format!("<{name}>{}</{name}>", scalar_text(scalar))),
+    }
+    Ok(())
+}
+
+fn scalar_text(value: &JsonValue) -> String {
+    match value {
+        JsonValue::String(s) => escape(s.as_str()).into_owned(),
+        other => escape(other.to_string()).into_owned(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn only_bodies_switch_to_chunked_encoding() {
+        let mut req = RequestHeader::build("POST", b"/", None).unwrap();
+        assert!(!request_has_body(&req));
+        req.insert_header("Content-Length", "0").unwrap();
+        assert!(!request_has_body(&req));
+        req.insert_header("Content-Length", "12").unwrap();
+        assert!(request_has_body(&req));
+
+        let mut resp = ResponseHeader::build(200, None).unwrap();
+        assert!(response_has_body(&Method::GET, &resp));
+        assert!(!response_has_body(&Method::HEAD, &resp));
+        resp.insert_header("Content-Length", "0").unwrap();
+        assert!(!response_has_body(&Method::GET, &resp));
+        let not_modified = ResponseHeader::build(304, None).unwrap();
+        assert!(!response_has_body(&Method::GET, &not_modified));
+    }
+
+    #[test]
+    fn xml_to_json_strips_namespaces_and_groups_repeats() {
+        let xml = r#"<?xml version="1.0"?>
+            <soap:Envelope xmlns:soap="http://schemas.xmlsoap.org/soap/envelope/">
+              <soap:Body>
+                <GetUserResponse id="7">
+                  <name>Tom &amp; Jerry</name>
+                  <role>admin</role>
+                  <role>dev</role>
+                </GetUserResponse>
+              </soap:Body>
+            </soap:Envelope>"#;
+        let doc = xml_to_json(xml).unwrap();
+        assert_eq!(
+            doc,
+            json!({"Envelope": {"Body": {"GetUserResponse": {
+                "@id": "7",
+                "name": "Tom & Jerry",
+                "role": ["admin", "dev"],
+            }}}})
+        );
+    }
+
+    #[test]
+    fn templates_escape_values_for_the_output_format() {
+        let doc = json!({"user": {"name": "a<b", "tags": ["x", "y"]}});
+        assert_eq!(
+            render_template(
+                "<n>{{ user.name }}</n><t>{{user.tags.1}}</t>",
+                &doc,
+                BodyFormat::Xml
+            ),
+            "<n>a&lt;b</n><t>y</t>"
+        );
+        assert_eq!(
+            render_template(
+                r#"{"n": {{ user.name }}, "m": {{ missing }}}"#,
+                &doc,
+                BodyFormat::Json
+            ),
+            r#"{"n": "a<b", "m": null}"#
+        );
+    }
+
+    #[test]
+    fn json_round_trips_through_xml() {
+        let doc = json!({"order": {"@id": "1", "item": ["a", "b"], "note": "x&y"}});
+        let xml = json_to_xml(&doc).unwrap();
+        assert_eq!(
+            xml,
+            r#"<?xml version="1.0" encoding="UTF-8"?><order id="1"><item>a</item><item>b</item><note>x&amp;y</note></order>"#
+        );
+        assert_eq!(xml_to_json(&xml).unwrap(), doc);
+    }
+
+    #[test]
+    fn json_keys_must_be_xml_names() {
+        for doc in [
+            json!({"a><injected/": "x"}),
+            json!({"order": {"ok": 1, "a b": 2}}),
+            json!({"order": {"@x=\"1\" y": "v"}}),
+            json!({"order": {"1st": "v"}}),
+            json!({"order": {"": "v"}}),
+        ] {
+            assert!(json_to_xml(&doc).is_err(), "{doc}");
+        }
+
+        let spec = TransformConfig {
+            input_format: None,
+            output_format: BodyFormat::Xml,
+            template: None,
+        };
+        let err = transform(br#"{"a><injected/": 1}"#, BodyFormat::Json, &spec).unwrap_err();
+        assert!(err.contains("not a valid XML name"), "{err}");
+
+        assert!(json_to_xml(&json!({"ns:item": {"@xml:lang": "en", "_v-1.2": "é"}})).is_ok());
+    }
+}
@@ -5,15 +5,19 @@ use pingora::{
     modules::http::compression::ResponseCompression, protocols::http::compression::Algorithm,
 };
 use pingora_error::Result;
+use pingora_http::ResponseHeader;
 use pingora_proxy::Session;
 use serde::{Deserialize, Serialize};
-use serde_json::Value as JsonValue;
+use serde_json::{json, Value as JsonValue};
 use validator::Validate;
 
-use crate::core::{ProxyContext, ProxyError, ProxyPlugin, ProxyResult};
+use crate::{
+    core::{ProxyContext, ProxyError, ProxyPlugin, ProxyResult},
+    utils::compression::{self, CompressionRules},
+};
 
 pub const PLUGIN_NAME: &str = "brotli";
-const PRIORITY: i32 = 996;
+pub const PRIORITY: i32 = 996;
 
 /// Creates a Brotli plugin instance with the given configuration.
 pub fn create_brotli_plugin(cfg: JsonValue) -> ProxyResult<Arc<dyn ProxyPlugin>> {
@@ -21,9 +25,23 @@ pub fn create_brotli_plugin(cfg: JsonValue) -> ProxyResult<Arc<dyn ProxyPlugin>>
     Ok(Arc::new(PluginBrotli { config }))
 }
 
+/// JSON Schema of the brotli plugin configuration.
+pub fn schema() -> JsonValue {
+    let mut properties = CompressionRules::schema_properties();
+    properties.insert(
+        "comp_level".into(),
+        json!({"type": "integer", "minimum": 0, "maximum": 11, "default": 1}),
+    );
+    properties.insert(
+        "decompression".into(),
+        json!({"type": "boolean", "default": false}),
+    );
+    json!({"type": "object", "properties": properties})
+}
+
 /// Configuration for the Brotli plugin.
 #[derive(Default, Debug, Serialize, Deserialize, Validate)]
-struct PluginConfig {
+pub(super) struct PluginConfig {
     /// Compression level (0-11) for Brotli.
     #[serde(default = "PluginConfig::default_comp_level")]
     #[validate(range(min = 0, max = 11))]
@@ -32,6 +50,11 @@ struct PluginConfig {
     /// Whether to enable decompression for Brotli.
     #[serde(default)]
     decompression: bool,
+
+    /// Which responses are compressed (`min_length`, `types`, `vary`).
+    #[serde(flatten)]
+    #[validate(nested)]
+    rules: CompressionRules,
 }
 
 impl PluginConfig {
@@ -71,19 +94,32 @@ impl ProxyPlugin for PluginBrotli {
     async fn early_request_filter(
         &self,
         session: &mut Session,
-        _ctx: &mut ProxyContext,
+        ctx: &mut ProxyContext,
     ) -> Result<()> {
-        let Some(resp_compression) = session
+        compression::enable_codec(session, ctx, Algorithm::Brotli, self.config.comp_level);
+
+        if let Some(resp_compression) = session
             .downstream_modules_ctx
             .get_mut::<ResponseCompression>()
-        else {
-            return Ok(());
-        };
-
-        resp_compression.adjust_algorithm_level(Algorithm::Brotli, self.config.comp_level);
-
-        resp_compression.adjust_decompression(self.config.decompression);
+        {
+            resp_compression.adjust_decompression(self.config.decompression);
+        }
 
         Ok(())
     }
+
+    async fn response_filter(
+        &self,
+        session: &mut Session,
+        upstream_response: &mut ResponseHeader,
+        ctx: &mut ProxyContext,
+    ) -> Result<()> {
+        // Streaming responses already had compression turned off.
+        if ctx.streaming {
+            return Ok(());
+        }
+        self.config
+            .rules
+            .apply(session, upstream_response, Algorithm::Brotli)
+    }
 }
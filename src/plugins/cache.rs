@@ -4,18 +4,61 @@ use std::time::Duration;
 
 use async_trait::async_trait;
 use http::{HeaderName, Method};
-use once_cell::sync::OnceCell;
+use once_cell::sync::{Lazy, OnceCell};
 use pingora_error::Result;
 use pingora_proxy::Session;
+use prometheus::{register_gauge_vec, register_int_counter_vec, GaugeVec, IntCounterVec};
 use regex::Regex;
 use serde::{Deserialize, Serialize};
-use serde_json::Value as JsonValue;
+use serde_json::{json, Value as JsonValue};
 use validator::{Validate, ValidationError};
 
 use crate::core::{ProxyContext, ProxyError, ProxyPlugin, ProxyResult};
 
 pub const PLUGIN_NAME: &str = "cache";
-const PRIORITY: i32 = 1085;
+pub const PRIORITY: i32 = 1085;
+
+/// Per-route cache outcomes (`hit`, `miss`, `stale`, `bypass`). Bypasses include
+/// requests the plugin declined before the cache was enabled, so routes that never
+/// benefit from caching stand out even when no lookup happened.
+static CACHE_ROUTE_REQUESTS: Lazy<IntCounterVec> = Lazy::new(|| {
+    register_int_counter_vec!(
+        "pingsix_cache_route_requests_total",
+        "Response-cache outcomes per route",
+        &["route", "outcome"]
+    )
+    .expect("cache route metric registration must succeed")
+});
+
+/// Operator-declared hit-rate target per route, exported so alerting rules can compare
+/// it against the observed ratio from `pingsix_cache_route_requests_total`.
+static CACHE_ROUTE_HIT_RATE_TARGET: Lazy<GaugeVec> = Lazy::new(|| {
+    register_gauge_vec!(
+        "pingsix_cache_route_hit_rate_target",
+        "Configured cache hit-rate target per route (0.0-1.0)",
+        &["route"]
+    )
+    .expect("cache hit-rate target metric registration must succeed")
+});
+
+/// Folds a Pingora cache phase into the coarse per-route outcome label.
+pub(crate) fn route_outcome(phase: &pingora_cache::CachePhase) -> &'static str {
+    use pingora_cache::CachePhase;
+    match phase {
+        CachePhase::Hit | CachePhase::Revalidated | CachePhase::RevalidatedNoCache(_) => "hit",
+        CachePhase::Miss | CachePhase::Expired => "miss",
+        CachePhase::Stale | CachePhase::StaleUpdating => "stale",
+        _ => "bypass",
+    }
+}
+
+/// Records a per-route cache outcome for the matched route of `ctx`.
+pub(crate) fn record_route_outcome(ctx: &ProxyContext, outcome: &str) {
+    let route = ctx.route.as_ref().map(|r| r.id()).unwrap_or("");
+    CACHE_ROUTE_REQUESTS
+        .with_label_values(&[route, outcome])
+        .inc();
+}
 
 /// Global default max object size, populated once at startup from
 /// `pingsix.defaults.cache.default_max_object_bytes`. Falls back to 1MB when unset
@@ -59,6 +102,26 @@ fn resolve_max_file_size(configured: Option<usize>, global_default: usize) -> us
     configured.unwrap_or(global_default)
 }
 
+/// What a cache purge covers. Keys are `host` (as sent in the `Host` header) followed
+/// by the path and query, e.g. `example.com/static/app.js?v=2`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PurgeTarget {
+    Key(String),
+    Prefix(String),
+    Route(String),
+}
+
+impl PurgeTarget {
+    /// Whether the purge covers an entry stored for `key` of `route_id`.
+    pub fn covers(&self, route_id: &str, key: &str) -> bool {
+        match self {
+            PurgeTarget::Key(target) => key == target,
+            PurgeTarget::Prefix(prefix) => key.starts_with(prefix.as_str()),
+            PurgeTarget::Route(target) => route_id == target,
+        }
+    }
+}
+
 // Context key for sharing cache settings between plugin and HttpService
 pub const CTX_KEY_CACHE_SETTINGS: &str = "pingsix_cache_settings";
 
@@ -76,6 +139,11 @@ pub struct CacheSettings {
     pub max_file_size_bytes: usize,
     /// Enable Stale-While-Revalidate: serve stale content while fetching fresh content in background
     pub stale_while_revalidate: Option<Duration>,
+    /// Stale-If-Error window: serve stale content for this long past expiry when the
+    /// upstream fails. Origin `stale-if-error` values are capped at it.
+    pub stale_if_error: Option<Duration>,
+    /// Treat upstream 5xx responses like upstream failures for `stale_if_error`.
+    pub serve_stale_on_5xx: bool,
     /// Enable s-maxage support: respect Cache-Control s-maxage directive for shared caches
     pub respect_s_maxage: bool,
     /// Cache authenticated or cookie-bearing requests. Disabled by default because a shared
@@ -129,6 +197,18 @@ pub struct PluginConfig {
     #[serde(default)]
     pub stale_while_revalidate_secs: Option<u64>,
 
+    /// Stale-If-Error window in seconds: the maximum staleness of a cached response
+    /// served when the upstream cannot be reached. Origin `stale-if-error` directives are
+    /// capped at this value.
+    #[serde(default)]
+    #[validate(range(min = 1))]
+    pub stale_if_error_secs: Option<u64>,
+
+    /// Also serve stale content (within `stale_if_error_secs`) when the upstream answers
+    /// with a 5xx status. Requires `stale_if_error_secs`.
+    #[serde(default)]
+    pub serve_stale_on_5xx: bool,
+
     /// Respect Cache-Control s-maxage directive for shared caches.
     /// When enabled, s-maxage overrides the configured TTL for shared cache scenarios.
     /// Default: true (recommended for CDN/proxy scenarios)
@@ -144,6 +224,12 @@ pub struct PluginConfig {
     /// This is a separate, high-risk opt-in and defaults to false.
     #[serde(default)]
     pub cache_set_cookie_responses: bool,
+
+    /// Expected hit ratio for this route (0.0-1.0). Purely an annotation: it is exported as
+    /// `pingsix_cache_route_hit_rate_target` for alerting and never changes caching behavior.
+    #[serde(default)]
+    #[validate(range(min = 0.0, max = 1.0))]
+    pub hit_rate_target: Option<f64>,
 }
 
 #[derive(Debug, Clone, Copy, Deserialize, Serialize, PartialEq, Eq, Default)]
@@ -212,6 +298,11 @@ impl TryFrom<JsonValue> for PluginConfig {
         })?;
 
         config.validate()?;
+        if config.serve_stale_on_5xx && config.stale_if_error_secs.is_none() {
+            return Err(ProxyError::validation_error(
+                "cache serve_stale_on_5xx requires stale_if_error_secs",
+            ));
+        }
         if config.scope == Scope::Cluster {
             return Err(ProxyError::validation_error(
                 "cache scope 'cluster' requires a distributed backend",
@@ -227,6 +318,7 @@ pub struct PluginCache {
     no_cache_regex: Vec<Regex>,
     // Pre-compiled shared settings to avoid recreation on each request
     cache_settings: Arc<CacheSettings>,
+    hit_rate_target: Option<f64>,
 }
 
 pub fn create_cache_plugin(cfg: JsonValue) -> ProxyResult<Arc<dyn ProxyPlugin>> {
@@ -275,6 +367,7 @@ pub fn create_cache_plugin(cfg: JsonValue) -> ProxyResult<Arc<dyn ProxyPlugin>>
         config.cache_authenticated_requests.hash(&mut hasher);
         config.cache_set_cookie_responses.hash(&mut hasher);
         config.stale_while_revalidate_secs.hash(&mut hasher);
+        config.stale_if_error_secs.hash(&mut hasher);
         let mut statuses: Vec<_> = config.cache_http_statuses.clone();
         statuses.sort_unstable();
         statuses.dedup();
@@ -295,6 +388,8 @@ pub fn create_cache_plugin(cfg: JsonValue) -> ProxyResult<Arc<dyn ProxyPlugin>>
         hide_cache_headers: config.hide_cache_headers,
         max_file_size_bytes,
         stale_while_revalidate: config.stale_while_revalidate_secs.map(Duration::from_secs),
+        stale_if_error: config.stale_if_error_secs.map(Duration::from_secs),
+        serve_stale_on_5xx: config.serve_stale_on_5xx,
         respect_s_maxage: config.respect_s_maxage,
         cache_authenticated_requests: config.cache_authenticated_requests,
         cache_set_cookie_responses: config.cache_set_cookie_responses,
@@ -305,9 +400,43 @@ pub fn create_cache_plugin(cfg: JsonValue) -> ProxyResult<Arc<dyn ProxyPlugin>>
         methods,
         no_cache_regex,
         cache_settings,
+        hit_rate_target: config.hit_rate_target,
     }))
 }
 
+/// JSON Schema of the cache plugin configuration.
+pub fn schema() -> JsonValue {
+    json!({
+        "type": "object",
+        "required": ["ttl"],
+        "properties": {
+            "ttl": {"type": "integer", "minimum": 1},
+            "cache_http_methods": {
+                "type": "array",
+                "items": {"type": "string"},
+                "default": ["GET", "HEAD"]
+            },
+            "cache_http_statuses": {
+                "type": "array",
+                "items": {"type": "integer", "minimum": 100, "maximum": 599},
+                "default": [200]
+            },
+            "no_cache_str": {"type": "array", "items": {"type": "string"}},
+            "vary": {"type": "array", "items": {"type": "string"}},
+            "hide_cache_headers": {"type": "boolean", "default": false},
+            "scope": {"type": "string", "enum": ["local", "cluster"], "default": "local"},
+            "max_file_size_bytes": {"type": "integer", "minimum": 0},
+            "stale_while_revalidate_secs": {"type": "integer", "minimum": 0},
+            "stale_if_error_secs": {"type": "integer", "minimum": 1},
+            "serve_stale_on_5xx": {"type": "boolean", "default": false},
+            "respect_s_maxage": {"type": "boolean", "default": true},
+            "cache_authenticated_requests": {"type": "boolean", "default": false},
+            "cache_set_cookie_responses": {"type": "boolean", "default": false},
+            "hit_rate_target": {"type": "number", "minimum": 0, "maximum": 1}
+        }
+    })
+}
+
 pub(crate) fn should_bypass_authenticated_request(
     settings: &CacheSettings,
     ctx: &ProxyContext,
@@ -334,15 +463,23 @@ impl ProxyPlugin for PluginCache {
         let method = &session.req_header().method;
         let path = session.req_header().uri.path();
 
+        if let (Some(target), Some(route)) = (self.hit_rate_target, ctx.route.as_ref()) {
+            CACHE_ROUTE_HIT_RATE_TARGET
+                .with_label_values(&[route.id()])
+                .set(target);
+        }
+
         // 1. Check if method is cacheable
         if !self.methods.contains(method) {
             log::trace!("Method {method} not cacheable, skipping cache");
+            record_route_outcome(ctx, "bypass");
             return Ok(false);
         }
 
         // 2. Shared caching of authenticated or cookie-bearing requests is opt-in.
         if should_bypass_authenticated_request(&self.cache_settings, ctx) {
             log::trace!("Request contains credentials, skipping shared cache");
+            record_route_outcome(ctx, "bypass");
             return Ok(false);
         }
 
@@ -350,6 +487,7 @@ impl ProxyPlugin for PluginCache {
         for re in &self.no_cache_regex {
             if re.is_match(path) {
                 log::trace!("Path {path} matches no-cache pattern, skipping cache");
+                record_route_outcome(ctx, "bypass");
                 return Ok(false);
             }
         }
@@ -378,6 +516,8 @@ mod tests {
             hide_cache_headers: config.hide_cache_headers,
             max_file_size_bytes: resolve_max_file_size(config.max_file_size_bytes, global_default),
             stale_while_revalidate: config.stale_while_revalidate_secs.map(Duration::from_secs),
+            stale_if_error: config.stale_if_error_secs.map(Duration::from_secs),
+            serve_stale_on_5xx: config.serve_stale_on_5xx,
             respect_s_maxage: config.respect_s_maxage,
             cache_authenticated_requests: config.cache_authenticated_requests,
             cache_set_cookie_responses: config.cache_set_cookie_responses,
@@ -389,6 +529,38 @@ mod tests {
         settings_from_json_with_default(value, default_max_object_bytes())
     }
 
+    #[test]
+    fn purge_targets_cover_matching_entries() {
+        let route = PurgeTarget::Route("r1".into());
+        assert!(route.covers("r1", "example.com/a"));
+        assert!(!route.covers("r2", "example.com/a"));
+
+        let prefix = PurgeTarget::Prefix("example.com/static/".into());
+        assert!(prefix.covers("r2", "example.com/static/app.js"));
+        assert!(!prefix.covers("r2", "example.com/a"));
+
+        let key = PurgeTarget::Key("example.com/a".into());
+        assert!(key.covers("r2", "example.com/a"));
+        assert!(!key.covers("r2", "example.com/a?v=2"));
+    }
+
+    #[test]
+    fn serve_stale_on_5xx_requires_stale_if_error_window() {
+        assert!(PluginConfig::try_from(serde_json::json!({
+            "ttl": 60,
+            "serve_stale_on_5xx": true
+        }))
+        .is_err());
+
+        let settings = settings_from_json(serde_json::json!({
+            "ttl": 60,
+            "stale_if_error_secs": 300,
+            "serve_stale_on_5xx": true
+        }));
+        assert_eq!(settings.stale_if_error, Some(Duration::from_secs(300)));
+        assert!(settings.serve_stale_on_5xx);
+    }
+
     #[test]
     fn vary_rejects_wildcard_and_invalid_header_names() {
         assert!(PluginConfig::try_from(serde_json::json!({ "ttl": 1, "vary": ["*"] })).is_err());
@@ -476,6 +648,31 @@ mod tests {
         assert!(!should_bypass_set_cookie_response(&opt_in, true));
     }
 
+    #[test]
+    fn hit_rate_target_must_be_a_ratio() {
+        let config =
+            PluginConfig::try_from(serde_json::json!({ "ttl": 60, "hit_rate_target": 0.8 }))
+                .unwrap();
+        assert_eq!(config.hit_rate_target, Some(0.8));
+        assert!(
+            PluginConfig::try_from(serde_json::json!({ "ttl": 60, "hit_rate_target": 1.5 }))
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn cache_phases_fold_into_route_outcomes() {
+        use pingora_cache::{CachePhase, NoCacheReason};
+        assert_eq!(route_outcome(&CachePhase::Hit), "hit");
+        assert_eq!(route_outcome(&CachePhase::Revalidated), "hit");
+        assert_eq!(route_outcome(&CachePhase::Expired), "miss");
+        assert_eq!(route_outcome(&CachePhase::StaleUpdating), "stale");
+        assert_eq!(
+            route_outcome(&CachePhase::Disabled(NoCacheReason::NeverEnabled)),
+            "bypass"
+        );
+    }
+
     #[test]
     fn max_file_size_none_uses_global_default() {
         // None (unconfigured) inherits the global default, whatever it is.
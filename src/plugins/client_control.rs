@@ -0,0 +1,193 @@
+use std::{sync::Arc, time::Duration};
+
+use async_trait::async_trait;
+use bytes::Bytes;
+use http::header;
+use pingora_error::{Error, ErrorType, Result};
+use pingora_http::RequestHeader;
+use pingora_proxy::Session;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value as JsonValue};
+use validator::Validate;
+
+use crate::core::{ProxyContext, ProxyError, ProxyPlugin, ProxyResult};
+
+pub const PLUGIN_NAME: &str = "client-control";
+pub const PRIORITY: i32 = 22000;
+
+/// Context key counting the request body bytes received so far.
+const BODY_BYTES_CTX_KEY: &str = "client_control_body_bytes";
+
+/// Creates a client-control plugin that applies per-route limits to the client
+/// connection.
+pub fn create_client_control_plugin(cfg: JsonValue) -> ProxyResult<Arc<dyn ProxyPlugin>> {
+    let config = PluginConfig::try_from(cfg)?;
+    Ok(Arc::new(PluginClientControl { config }))
+}
+
+/// JSON Schema of the client-control plugin configuration.
+pub fn schema() -> JsonValue {
+    json!({
+        "type": "object",
+        "properties": {
+            "max_body_size": {"type": "integer", "minimum": 1},
+            "read_timeout": {"type": "integer", "minimum": 1},
+            "allow_expect_continue": {"type": "boolean", "default": true}
+        }
+    })
+}
+
+#[derive(Debug, Serialize, Deserialize, Validate)]
+pub(super) struct PluginConfig {
+    /// Largest request body in bytes; larger bodies are rejected with `413`.
+    #[serde(default)]
+    #[validate(range(min = 1))]
+    max_body_size: Option<u64>,
+    /// Seconds to wait for each read of the request body.
+    #[serde(default)]
+    #[validate(range(min = 1))]
+    read_timeout: Option<u64>,
+    /// Accept requests with `Expect: 100-continue`; otherwise they get `417`.
+    #[serde(default = "PluginConfig::default_allow_expect_continue")]
+    allow_expect_continue: bool,
+}
+
+impl PluginConfig {
+    fn default_allow_expect_continue() -> bool {
+        true
+    }
+}
+
+impl TryFrom<JsonValue> for PluginConfig {
+    type Error = ProxyError;
+
+    fn try_from(value: JsonValue) -> Result<Self, Self::Error> {
+        let config: PluginConfig = serde_json::from_value(value).map_err(|e| {
+            ProxyError::serialization_error("Invalid client control plugin config", e)
+        })?;
+
+        config.validate()?;
+
+        Ok(config)
+    }
+}
+
+pub struct PluginClientControl {
+    config: PluginConfig,
+}
+
+impl PluginClientControl {
+    /// The status a request is rejected with based on its headers alone.
+    fn check_head(&self, req: &RequestHeader) -> Option<u16> {
+        if !self.config.allow_expect_continue
+            && req
+                .headers
+                .get(header::EXPECT)
+                .and_then(|v| v.to_str().ok())
+                .is_some_and(|v| v.trim().eq_ignore_ascii_case("100-continue"))
+        {
+            return Some(417);
+        }
+        let declared = req
+            .headers
+            .get(header::CONTENT_LENGTH)
+            .and_then(|v| v.to_str().ok())
+            .and_then(|v| v.trim().parse::<u64>().ok());
+        match (self.config.max_body_size, declared) {
+            (Some(max), Some(length)) if length > max => Some(413),
+            _ => None,
+        }
+    }
+}
+
+#[async_trait]
+impl ProxyPlugin for PluginClientControl {
+    fn name(&self) -> &str {
+        PLUGIN_NAME
+    }
+
+    fn priority(&self) -> i32 {
+        PRIORITY
+    }
+
+    async fn early_request_filter(
+        &self,
+        session: &mut Session,
+        _ctx: &mut ProxyContext,
+    ) -> Result<()> {
+        if let Some(read_timeout) = self.config.read_timeout {
+            session.set_read_timeout(Some(Duration::from_secs(read_timeout)));
+        }
+        match self.check_head(session.req_header()) {
+            Some(status) => Error::e_explain(
+                ErrorType::HTTPStatus(status),
+                "request rejected by client-control",
+            ),
+            None => Ok(()),
+        }
+    }
+
+    fn has_request_body_filter(&self) -> bool {
+        self.config.max_body_size.is_some()
+    }
+
+    /// Enforces `max_body_size` on bodies without a `Content-Length`, such as chunked
+    /// uploads.
+    async fn request_body_filter(
+        &self,
+        _session: &mut Session,
+        body: &mut Option<Bytes>,
+        _end_of_stream: bool,
+        ctx: &mut ProxyContext,
+    ) -> Result<()> {
+        let (Some(max), Some(chunk)) = (self.config.max_body_size, body.as_ref()) else {
+            return Ok(());
+        };
+        let received = match ctx.get_mut::<u64>(BODY_BYTES_CTX_KEY) {
+            Some(received) => {
+                *received += chunk.len() as u64;
+                *received
+            }
+            None => {
+                ctx.set(BODY_BYTES_CTX_KEY, chunk.len() as u64);
+                chunk.len() as u64
+            }
+        };
+        if received > max {
+            return Error::e_explain(
+                ErrorType::HTTPStatus(413),
+                "request body exceeds client-control max_body_size",
+            );
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn plugin(cfg: JsonValue) -> PluginClientControl {
+        PluginClientControl {
+            config: PluginConfig::try_from(cfg).unwrap(),
+        }
+    }
+
+    #[test]
+    fn requests_are_checked_against_the_route_limits() {
+        let mut req = RequestHeader::build("POST", b"/upload", None).unwrap();
+        req.insert_header("Content-Length", "2048").unwrap();
+        req.insert_header("Expect", "100-Continue").unwrap();
+
+        assert_eq!(plugin(json!({})).check_head(&req), None);
+        assert_eq!(
+            plugin(json!({"max_body_size": 1024})).check_head(&req),
+            Some(413)
+        );
+        assert_eq!(
+            plugin(json!({"max_body_size": 4096, "allow_expect_continue": false})).check_head(&req),
+            Some(417)
+        );
+        assert!(PluginConfig::try_from(json!({"read_timeout": 0})).is_err());
+    }
+}
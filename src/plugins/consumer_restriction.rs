@@ -0,0 +1,198 @@
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use http::StatusCode;
+use pingora_error::Result;
+use pingora_proxy::Session;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value as JsonValue};
+use validator::{Validate, ValidationError};
+
+use crate::{
+    core::{ProxyContext, ProxyError, ProxyPlugin, ProxyResult},
+    utils::response::ResponseBuilder,
+};
+
+pub const PLUGIN_NAME: &str = "consumer-restriction";
+/// Runs after the auth plugins (basic-auth 2520, jwt-auth 2510, key-auth 2500).
+pub const PRIORITY: i32 = 2400;
+
+/// Creates a consumer-restriction plugin that authorizes the identity set by auth plugins.
+pub fn create_consumer_restriction_plugin(cfg: JsonValue) -> ProxyResult<Arc<dyn ProxyPlugin>> {
+    let config = PluginConfig::try_from(cfg)?;
+    Ok(Arc::new(PluginConsumerRestriction { config }))
+}
+
+/// JSON Schema of the consumer-restriction plugin configuration.
+pub fn schema() -> JsonValue {
+    json!({
+        "type": "object",
+        "properties": {
+            "whitelist": {"type": "array", "items": {"type": "string"}},
+            "blacklist": {"type": "array", "items": {"type": "string"}},
+            "allowed_by_methods": {
+                "type": "array",
+                "items": {
+                    "type": "object",
+                    "required": ["user", "methods"],
+                    "properties": {
+                        "user": {"type": "string", "minLength": 1},
+                        "methods": {"type": "array", "minItems": 1, "items": {"type": "string"}}
+                    }
+                }
+            },
+            "rejected_code": {"type": "integer", "minimum": 200, "maximum": 599, "default": 403},
+            "rejected_msg": {"type": "string"}
+        }
+    })
+}
+
+/// Methods a consumer may use.
+#[derive(Debug, Serialize, Deserialize, Validate)]
+struct AllowedMethods {
+    #[validate(length(min = 1))]
+    user: String,
+    #[validate(length(min = 1))]
+    methods: Vec<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Validate)]
+#[validate(schema(function = "PluginConfig::validate_has_rules"))]
+pub(super) struct PluginConfig {
+    /// Consumers allowed through; empty allows every authenticated consumer.
+    #[serde(default)]
+    whitelist: Vec<String>,
+    /// Consumers always rejected.
+    #[serde(default)]
+    blacklist: Vec<String>,
+    /// Per-consumer method restrictions; consumers not listed are not restricted by method.
+    #[serde(default)]
+    #[validate(nested)]
+    allowed_by_methods: Vec<AllowedMethods>,
+    #[serde(default = "PluginConfig::default_rejected_code")]
+    #[validate(range(min = 200, max = 599))]
+    rejected_code: u16,
+    rejected_msg: Option<String>,
+}
+
+impl PluginConfig {
+    fn default_rejected_code() -> u16 {
+        403
+    }
+
+    fn validate_has_rules(&self) -> Result<(), ValidationError> {
+        if self.whitelist.is_empty()
+            && self.blacklist.is_empty()
+            && self.allowed_by_methods.is_empty()
+        {
+            return Err(ValidationError::new("no_consumer_rules"));
+        }
+        Ok(())
+    }
+}
+
+impl TryFrom<JsonValue> for PluginConfig {
+    type Error = ProxyError;
+
+    fn try_from(value: JsonValue) -> Result<Self, Self::Error> {
+        let config: PluginConfig = serde_json::from_value(value).map_err(|e| {
+            ProxyError::serialization_error("Failed to parse consumer-restriction plugin config", e)
+        })?;
+        config.validate()?;
+        Ok(config)
+    }
+}
+
+pub struct PluginConsumerRestriction {
+    config: PluginConfig,
+}
+
+impl PluginConsumerRestriction {
+    /// Whether `consumer` may send a request with `method`.
+    fn is_allowed(&self, consumer: &str, method: &str) -> bool {
+        if self.config.blacklist.iter().any(|c| c == consumer) {
+            return false;
+        }
+        if !self.config.whitelist.is_empty() && !self.config.whitelist.iter().any(|c| c == consumer)
+        {
+            return false;
+        }
+        self.config
+            .allowed_by_methods
+            .iter()
+            .filter(|entry| entry.user == consumer)
+            .all(|entry| entry.methods.iter().any(|m| m.eq_ignore_ascii_case(method)))
+    }
+}
+
+#[async_trait]
+impl ProxyPlugin for PluginConsumerRestriction {
+    fn name(&self) -> &str {
+        PLUGIN_NAME
+    }
+
+    fn priority(&self) -> i32 {
+        PRIORITY
+    }
+
+    async fn request_filter(&self, session: &mut Session, ctx: &mut ProxyContext) -> Result<bool> {
+        let Some(consumer) = ctx.authenticated_identity.as_deref() else {
+            ResponseBuilder::send_proxy_error(
+                session,
+                StatusCode::UNAUTHORIZED,
+                Some("Missing authentication or identity verification"),
+                None,
+            )
+            .await?;
+            return Ok(true);
+        };
+
+        if self.is_allowed(consumer, session.req_header().method.as_str()) {
+            return Ok(false);
+        }
+
+        let status =
+            StatusCode::from_u16(self.config.rejected_code).unwrap_or(StatusCode::FORBIDDEN);
+        ResponseBuilder::send_proxy_error(
+            session,
+            status,
+            self.config.rejected_msg.as_deref(),
+            None,
+        )
+        .await?;
+        Ok(true)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn plugin(cfg: JsonValue) -> PluginConsumerRestriction {
+        PluginConsumerRestriction {
+            config: PluginConfig::try_from(cfg).unwrap(),
+        }
+    }
+
+    #[test]
+    fn whitelist_and_blacklist_are_enforced() {
+        let p = plugin(json!({"whitelist": ["alice", "bob"], "blacklist": ["bob"]}));
+        assert!(p.is_allowed("alice", "GET"));
+        assert!(!p.is_allowed("bob", "GET"));
+        assert!(!p.is_allowed("carol", "GET"));
+    }
+
+    #[test]
+    fn method_restrictions_apply_only_to_listed_consumers() {
+        let p = plugin(json!({"allowed_by_methods": [{"user": "alice", "methods": ["get"]}]}));
+        assert!(p.is_allowed("alice", "GET"));
+        assert!(!p.is_allowed("alice", "DELETE"));
+        assert!(p.is_allowed("bob", "DELETE"));
+    }
+
+    #[test]
+    fn config_requires_a_rule() {
+        assert!(PluginConfig::try_from(json!({})).is_err());
+    }
+}
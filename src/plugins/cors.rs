@@ -7,7 +7,7 @@ use pingora_http::ResponseHeader;
 use pingora_proxy::Session;
 use regex::Regex;
 use serde::{Deserialize, Serialize};
-use serde_json::Value as JsonValue;
+use serde_json::{json, Value as JsonValue};
 use validator::{Validate, ValidationError};
 
 use crate::{
@@ -16,7 +16,7 @@ use crate::{
 };
 
 pub const PLUGIN_NAME: &str = "cors";
-const PRIORITY: i32 = 4000;
+pub const PRIORITY: i32 = 4000;
 
 /// Creates an CORS plugin instance with the given configuration.
 pub fn create_cors_plugin(cfg: JsonValue) -> ProxyResult<Arc<dyn ProxyPlugin>> {
@@ -30,6 +30,22 @@ pub fn create_cors_plugin(cfg: JsonValue) -> ProxyResult<Arc<dyn ProxyPlugin>> {
     }))
 }
 
+/// JSON Schema of the cors plugin configuration.
+pub fn schema() -> JsonValue {
+    json!({
+        "type": "object",
+        "properties": {
+            "allow_origins": {"type": "string", "minLength": 1, "default": "*"},
+            "allow_methods": {"type": "string", "default": "*"},
+            "allow_headers": {"type": "string", "default": "*"},
+            "expose_headers": {"type": "string"},
+            "max_age": {"type": "integer", "default": 5},
+            "allow_credential": {"type": "boolean", "default": false},
+            "allow_origins_by_regex": {"type": "array", "items": {"type": "string"}}
+        }
+    })
+}
+
 #[derive(Debug, Serialize, Deserialize, Default, Validate)]
 #[validate(schema(function = "PluginConfig::validate"))]
 pub struct PluginConfig {
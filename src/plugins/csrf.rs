@@ -10,15 +10,15 @@ use pingora_http::ResponseHeader;
 use pingora_proxy::Session;
 use rand::Rng;
 use serde::{Deserialize, Serialize};
-use serde_json::Value as JsonValue;
+use serde_json::{json, Value as JsonValue};
 use sha2::Sha256;
-use validator::Validate;
+use validator::{Validate, ValidationError};
 
 use crate::core::{constant_time_eq, ProxyContext, ProxyError, ProxyPlugin, ProxyResult};
 use crate::utils::{request, response::ResponseBuilder};
 
 pub const PLUGIN_NAME: &str = "csrf";
-const PRIORITY: i32 = 2980;
+pub const PRIORITY: i32 = 2980;
 
 /// Safe HTTP methods that do not require CSRF validation
 const SAFE_METHODS: &[Method] = &[Method::GET, Method::HEAD, Method::OPTIONS];
@@ -28,14 +28,68 @@ pub fn create_csrf_plugin(cfg: JsonValue) -> ProxyResult<Arc<dyn ProxyPlugin>> {
     Ok(Arc::new(PluginCsrf { config }))
 }
 
+/// JSON Schema of the csrf plugin configuration.
+pub fn schema() -> JsonValue {
+    json!({
+        "type": "object",
+        "required": ["key"],
+        "properties": {
+            "key": {"type": "string", "minLength": 1},
+            "expires": {"type": "integer", "minimum": 0, "default": 7200},
+            "name": {"type": "string", "default": "pingsix-csrf-token"},
+            "exclude_paths": {"type": "array", "items": {"type": "string"}},
+            "exclude_methods": {"type": "array", "items": {"type": "string"}},
+            "same_site": {"type": "string", "enum": ["Strict", "Lax", "None"], "default": "Lax"},
+            "secure": {"type": "boolean", "default": true},
+            "rotate_interval": {"type": "integer", "minimum": 0, "default": 0}
+        }
+    })
+}
+
+/// `SameSite` attribute of the CSRF cookie.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+enum SameSite {
+    Strict,
+    #[default]
+    Lax,
+    None,
+}
+
+impl SameSite {
+    fn as_str(self) -> &'static str {
+        match self {
+            SameSite::Strict => "Strict",
+            SameSite::Lax => "Lax",
+            SameSite::None => "None",
+        }
+    }
+}
+
 #[derive(Debug, Serialize, Deserialize, Validate)]
-struct PluginConfig {
+#[validate(schema(function = "PluginConfig::validate_cookie_attributes"))]
+pub(super) struct PluginConfig {
     #[validate(length(min = 1))]
     key: String,
     #[serde(default = "PluginConfig::default_expires")]
     expires: u64,
     #[serde(default = "PluginConfig::default_name")]
     name: String,
+    /// Paths exempt from CSRF checks, e.g. webhook receivers. Entries ending in `*` match
+    /// by prefix; other entries must match the path exactly.
+    #[serde(default)]
+    exclude_paths: Vec<String>,
+    /// Methods exempt from CSRF checks in addition to GET, HEAD and OPTIONS.
+    #[serde(default)]
+    exclude_methods: Vec<String>,
+    #[serde(default)]
+    same_site: SameSite,
+    /// Add the `Secure` attribute; required when `same_site` is `None`.
+    #[serde(default = "PluginConfig::default_secure")]
+    secure: bool,
+    /// Seconds before a still-valid token is replaced. `0` issues a new token on every
+    /// response.
+    #[serde(default)]
+    rotate_interval: u64,
 }
 
 impl PluginConfig {
@@ -45,6 +99,16 @@ impl PluginConfig {
     fn default_name() -> String {
         "pingsix-csrf-token".to_string()
     }
+    fn default_secure() -> bool {
+        true
+    }
+
+    fn validate_cookie_attributes(&self) -> Result<(), ValidationError> {
+        if self.same_site == SameSite::None && !self.secure {
+            return Err(ValidationError::new("same_site_none_requires_secure"));
+        }
+        Ok(())
+    }
 }
 
 impl TryFrom<JsonValue> for PluginConfig {
@@ -69,11 +133,19 @@ struct CsrfToken {
 
 /// Build the CSRF cookie value string with security attributes.
 ///
-/// `Secure` is included so the cookie is only sent over HTTPS in production.
+/// `Secure` is included by default so the cookie is only sent over HTTPS in production.
 /// `HttpOnly` is intentionally omitted because the double-submit pattern requires
 /// JavaScript to read the token and resend it in a header.
-fn build_cookie_value(name: &str, token: &str, expires: u64) -> String {
-    format!("{name}={token}; Path=/; SameSite=Lax; Max-Age={expires}; Secure")
+fn build_cookie_value(
+    name: &str,
+    token: &str,
+    expires: u64,
+    same_site: SameSite,
+    secure: bool,
+) -> String {
+    let same_site = same_site.as_str();
+    let secure = if secure { "; Secure" } else { "" };
+    format!("{name}={token}; Path=/; SameSite={same_site}; Max-Age={expires}{secure}")
 }
 
 pub struct PluginCsrf {
@@ -134,7 +206,13 @@ impl PluginCsrf {
         // Set the CSRF cookie with security attributes
         // Note: HttpOnly is intentionally NOT set because JavaScript needs to read this
         // for the double-submit pattern (sending it in both cookie and header)
-        let cookie_val = build_cookie_value(&self.config.name, &csrf_token, self.config.expires);
+        let cookie_val = build_cookie_value(
+            &self.config.name,
+            &csrf_token,
+            self.config.expires,
+            self.config.same_site,
+            self.config.secure,
+        );
 
         upstream_response.append_header(header::SET_COOKIE, cookie_val)?;
         Ok(())
@@ -144,16 +222,22 @@ impl PluginCsrf {
     ///
     /// Returns false if the token is invalid, expired, or has an incorrect signature
     fn check_token(&self, token_b64: &str) -> bool {
+        self.token_issued_at(token_b64).is_some()
+    }
+
+    /// Returns the issue time of a valid token, or None if the token is invalid, expired,
+    /// or has an incorrect signature
+    fn token_issued_at(&self, token_b64: &str) -> Option<u64> {
         // Decode base64
         let Ok(decoded) = general_purpose::STANDARD.decode(token_b64) else {
             log::debug!("CSRF token base64 decode error");
-            return false;
+            return None;
         };
 
         // Parse JSON
         let Ok(token_table) = serde_json::from_slice::<CsrfToken>(&decoded) else {
             log::debug!("CSRF token json decode error");
-            return false;
+            return None;
         };
 
         // Get current timestamp, handle system time errors gracefully
@@ -161,7 +245,7 @@ impl PluginCsrf {
             Ok(duration) => duration.as_secs(),
             Err(e) => {
                 log::error!("System time error during CSRF validation: {e}");
-                return false;
+                return None;
             }
         };
 
@@ -173,21 +257,67 @@ impl PluginCsrf {
             let expiry_time = token_table.expires.saturating_add(self.config.expires);
             if now > expiry_time {
                 log::debug!("CSRF token expired (now: {now}, expiry: {expiry_time})");
-                return false;
+                return None;
             }
         }
 
         // Validate signature using constant-time comparison
         let Some(expected_sign) = self.gen_sign(&token_table.random, token_table.expires) else {
             log::debug!("CSRF token signature could not be computed");
-            return false;
+            return None;
         };
         if !constant_time_eq(&token_table.sign, &expected_sign) {
             log::debug!("CSRF token invalid signature");
-            return false;
+            return None;
         }
 
-        true
+        Some(token_table.expires)
+    }
+
+    /// Whether the request is exempt from CSRF checks by method or path.
+    fn is_excluded(&self, method: &Method, path: &str) -> bool {
+        if SAFE_METHODS.contains(method)
+            || self
+                .config
+                .exclude_methods
+                .iter()
+                .any(|m| m.eq_ignore_ascii_case(method.as_str()))
+        {
+            return true;
+        }
+        // Dot segments are resolved first, so `/public/../admin` is not under `/public/`.
+        let path = normalize_path(path);
+        self.config.exclude_paths.iter().any(|pattern| {
+            match pattern.strip_suffix('*') {
+                // A prefix only matches whole segments: `/public*` covers `/public/x`,
+                // not `/publicX`.
+                Some(prefix) => {
+                    let prefix = normalize_path(prefix);
+                    prefix == "/"
+                        || path
+                            .strip_prefix(prefix.as_str())
+                            .is_some_and(|rest| rest.is_empty() || rest.starts_with('/'))
+                }
+                None => path == normalize_path(pattern),
+            }
+        })
+    }
+
+    /// Whether the request already carries a valid token younger than `rotate_interval`.
+    fn has_fresh_token(&self, session: &Session) -> bool {
+        if self.config.rotate_interval == 0 {
+            return false;
+        }
+        let Some(issued_at) = request::get_cookie_value(session.req_header(), &self.config.name)
+            .and_then(|token| self.token_issued_at(token))
+        else {
+            return false;
+        };
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or_default();
+        now.saturating_sub(issued_at) < self.config.rotate_interval
     }
 }
 
@@ -201,10 +331,10 @@ impl ProxyPlugin for PluginCsrf {
     }
 
     async fn request_filter(&self, session: &mut Session, _ctx: &mut ProxyContext) -> Result<bool> {
-        let method = &session.req_header().method;
+        let req = session.req_header();
 
-        // 1. Allow safe methods to bypass CSRF validation
-        if SAFE_METHODS.contains(method) {
+        // 1. Allow safe methods and excluded methods/paths to bypass CSRF validation
+        if self.is_excluded(&req.method, req.uri.path()) {
             return Ok(false);
         }
 
@@ -263,14 +393,33 @@ impl ProxyPlugin for PluginCsrf {
 
     async fn response_filter(
         &self,
-        _session: &mut Session,
+        session: &mut Session,
         upstream_response: &mut ResponseHeader,
         _ctx: &mut ProxyContext,
     ) -> Result<()> {
+        if self.has_fresh_token(session) {
+            return Ok(());
+        }
         self.set_csrf_cookie(upstream_response)
     }
 }
 
+/// `path` with empty and dot segments resolved (including percent-encoded dots) and
+/// without a trailing slash.
+fn normalize_path(path: &str) -> String {
+    let mut segments: Vec<&str> = Vec::new();
+    for segment in path.split('/') {
+        match segment.to_ascii_lowercase().replace("%2e", ".").as_str() {
+            "" | "." => {}
+            ".." => {
+                segments.pop();
+            }
+            _ => segments.push(segment),
+        }
+    }
+    format!("/{}", segments.join("/"))
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -281,6 +430,11 @@ mod tests {
                 key: "unit-test-key".to_string(),
                 expires,
                 name: "csrf-token".to_string(),
+                exclude_paths: vec![],
+                exclude_methods: vec![],
+                same_site: SameSite::Lax,
+                secure: true,
+                rotate_interval: 0,
             },
         }
     }
@@ -349,13 +503,55 @@ mod tests {
 
     #[test]
     fn csrf_cookie_contains_secure_attribute() {
-        let cookie = build_cookie_value("csrf-token", "dummy-token", 7200);
+        let cookie = build_cookie_value("csrf-token", "dummy-token", 7200, SameSite::Lax, true);
         assert!(
             cookie.contains("Secure"),
             "CSRF cookie should contain the Secure attribute, got: {cookie}"
         );
     }
 
+    #[test]
+    fn cookie_attributes_are_configurable() {
+        let cookie = build_cookie_value("csrf-token", "t", 60, SameSite::Strict, false);
+        assert_eq!(cookie, "csrf-token=t; Path=/; SameSite=Strict; Max-Age=60");
+        assert!(PluginConfig::try_from(
+            serde_json::json!({"key": "k", "same_site": "None", "secure": false})
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn excluded_paths_and_methods_skip_validation() {
+        let mut plugin = build_plugin(7200);
+        plugin.config.exclude_paths = vec!["/webhooks/*".to_string(), "/callback".to_string()];
+        plugin.config.exclude_methods = vec!["put".to_string()];
+
+        assert!(plugin.is_excluded(&Method::POST, "/webhooks/github"));
+        assert!(plugin.is_excluded(&Method::POST, "/callback"));
+        assert!(!plugin.is_excluded(&Method::POST, "/callback/other"));
+        assert!(plugin.is_excluded(&Method::POST, "/webhooks"));
+        assert!(plugin.is_excluded(&Method::POST, "//webhooks/./github"));
+        assert!(!plugin.is_excluded(&Method::POST, "/webhooks/../admin"));
+        assert!(!plugin.is_excluded(&Method::POST, "/webhooks/%2E%2e/admin"));
+        assert!(!plugin.is_excluded(&Method::POST, "/webhooksX"));
+        assert!(plugin.is_excluded(&Method::PUT, "/api"));
+        assert!(!plugin.is_excluded(&Method::POST, "/api"));
+        assert!(plugin.is_excluded(&Method::GET, "/api"));
+    }
+
+    #[test]
+    fn prefixes_match_whole_normalized_segments() {
+        let mut plugin = build_plugin(7200);
+        plugin.config.exclude_paths = vec!["/public/*".to_string(), "/static*".to_string()];
+
+        assert!(plugin.is_excluded(&Method::POST, "/public/form"));
+        assert!(!plugin.is_excluded(&Method::POST, "/public/../admin"));
+        assert!(!plugin.is_excluded(&Method::POST, "/publicX"));
+        assert!(plugin.is_excluded(&Method::POST, "/static/app.js"));
+        assert!(!plugin.is_excluded(&Method::POST, "/staticX"));
+        assert!(plugin.is_excluded(&Method::POST, "/admin/../static/x"));
+    }
+
     #[test]
     fn csrf_uses_append_not_insert() {
         let plugin = build_plugin(7200);
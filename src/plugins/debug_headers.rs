@@ -0,0 +1,276 @@
+use std::{
+    sync::Arc,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use async_trait::async_trait;
+use pingora_error::Result;
+use pingora_http::{RequestHeader, ResponseHeader};
+use pingora_proxy::Session;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value as JsonValue};
+use validator::Validate;
+
+use crate::{
+    core::{status, ProxyContext, ProxyError, ProxyPlugin, ProxyResult},
+    utils::signed_token,
+};
+
+pub const PLUGIN_NAME: &str = "debug-headers";
+pub const PRIORITY: i32 = 12011;
+
+/// Context key set when the response should be annotated.
+const DEBUG_CTX_KEY: &str = "debug_headers_enabled";
+
+const DEFAULT_DEBUG_HEADER: &str = "X-Pingsix-Debug";
+
+/// Creates a debug-headers plugin that annotates responses with routing details.
+pub fn create_debug_headers_plugin(cfg: JsonValue) -> ProxyResult<Arc<dyn ProxyPlugin>> {
+    let config = PluginConfig::try_from(cfg)?;
+    Ok(Arc::new(PluginDebugHeaders { config }))
+}
+
+/// JSON Schema of the debug-headers plugin configuration.
+pub fn schema() -> JsonValue {
+    json!({
+        "type": "object",
+        "properties": {
+            "secret": {"type": "string", "minLength": 16},
+            "header": {"type": "string", "minLength": 1, "default": DEFAULT_DEBUG_HEADER}
+        }
+    })
+}
+
+#[derive(Debug, Serialize, Deserialize, Validate)]
+pub(super) struct PluginConfig {
+    /// When set, only requests carrying a valid signed token in `header` are annotated;
+    /// otherwise every response is.
+    #[serde(default)]
+    #[validate(length(min = 16))]
+    secret: Option<String>,
+    /// Request header carrying the `<expires>.<signature>` token.
+    #[serde(default = "PluginConfig::default_header")]
+    #[validate(length(min = 1))]
+    header: String,
+}
+
+impl PluginConfig {
+    fn default_header() -> String {
+        DEFAULT_DEBUG_HEADER.to_string()
+    }
+}
+
+impl TryFrom<JsonValue> for PluginConfig {
+    type Error = ProxyError;
+
+    fn try_from(value: JsonValue) -> Result<Self, Self::Error> {
+        let config: PluginConfig = serde_json::from_value(value).map_err(|e| {
+            ProxyError::serialization_error("Invalid debug headers plugin config", e)
+        })?;
+
+        config.validate()?;
+
+        Ok(config)
+    }
+}
+
+pub struct PluginDebugHeaders {
+    config: PluginConfig,
+}
+
+impl PluginDebugHeaders {
+    fn annotate(
+        &self,
+        session: &Session,
+        resp: &mut ResponseHeader,
+        ctx: &ProxyContext,
+    ) -> Result<()> {
+        let route = ctx.route.as_ref();
+        resp.insert_header("X-Pingsix-Route", route.map_or("-", |r| r.id()))?;
+        if let Some(service_id) = route.and_then(|r| r.service_id()) {
+            resp.insert_header("X-Pingsix-Service", service_id)?;
+        }
+
+        let plugins: Vec<&str> = ctx
+            .global_plugin
+            .plugins
+            .iter()
+            .chain(ctx.plugin.plugins.iter())
+            .map(|plugin| plugin.name())
+            .collect();
+        resp.insert_header("X-Pingsix-Plugins", plugins.join(","))?;
+
+        let upstream = ctx
+            .peer
+            .as_ref()
+            .map_or_else(|| "-".to_string(), |peer| peer._address.to_string());
+        resp.insert_header("X-Pingsix-Upstream", upstream)?;
+        resp.insert_header("X-Pingsix-Retries", ctx.tries.to_string())?;
+        resp.insert_header("X-Pingsix-Cache", session.cache.phase().as_str())?;
+        if let Some(revision) = status::config_revision() {
+            resp.insert_header("X-Pingsix-Config-Revision", revision)?;
+        }
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl ProxyPlugin for PluginDebugHeaders {
+    fn name(&self) -> &str {
+        PLUGIN_NAME
+    }
+
+    fn priority(&self) -> i32 {
+        PRIORITY
+    }
+
+    async fn early_request_filter(
+        &self,
+        session: &mut Session,
+        ctx: &mut ProxyContext,
+    ) -> Result<()> {
+        let enabled = match &self.config.secret {
+            None => true,
+            Some(secret) => {
+                let now = SystemTime::now()
+                    .duration_since(UNIX_EPOCH)
+                    .map(|d| d.as_secs())
+                    .unwrap_or_default();
+                session
+                    .req_header()
+                    .headers
+                    .get(self.config.header.as_str())
+                    .and_then(|v| v.to_str().ok())
+                    .is_some_and(|token| signed_token::verify(secret, None, token, now))
+            }
+        };
+        if enabled {
+            ctx.set(DEBUG_CTX_KEY, true);
+        }
+        Ok(())
+    }
+
+    async fn upstream_request_filter(
+        &self,
+        _session: &mut Session,
+        upstream_request: &mut RequestHeader,
+        _ctx: &mut ProxyContext,
+    ) -> Result<()> {
+        // The token is for the gateway; keep it from upstream logs.
+        upstream_request.remove_header(self.config.header.as_str());
+        Ok(())
+    }
+
+    async fn response_filter(
+        &self,
+        session: &mut Session,
+        upstream_response: &mut ResponseHeader,
+        ctx: &mut ProxyContext,
+    ) -> Result<()> {
+        if ctx.get::<bool>(DEBUG_CTX_KEY).copied().unwrap_or(false) {
+            self.annotate(session, upstream_response, ctx)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pingora_core::upstreams::peer::HttpPeer;
+    use serde_json::json;
+
+    const SECRET: &str = "0123456789abcdef";
+
+    fn now() -> u64 {
+        SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_secs()
+    }
+
+    /// Runs a request carrying `token` through the plugin and returns the response headers.
+    async fn respond(cfg: JsonValue, token: Option<&str>) -> ResponseHeader {
+        let plugin = Arc::new(PluginDebugHeaders {
+            config: PluginConfig::try_from(cfg).unwrap(),
+        });
+        let request = match token {
+            Some(token) => format!("GET / HTTP/1.1\r\nX-Pingsix-Debug: {token}\r\n\r\n"),
+            None => "GET / HTTP/1.1\r\n\r\n".to_string(),
+        };
+        let stream = tokio_test::io::Builder::new()
+            .read(request.as_bytes())
+            .build();
+        let mut session = Session::new_h1(Box::new(stream));
+        assert!(session.read_request().await.unwrap());
+
+        let mut ctx = ProxyContext {
+            plugin: Arc::new(crate::core::ProxyPluginExecutor::new(vec![plugin.clone()])),
+            peer: Some(Box::new(HttpPeer::new(
+                "10.0.0.1:8080",
+                false,
+                String::new(),
+            ))),
+            tries: 1,
+            ..Default::default()
+        };
+        plugin
+            .early_request_filter(&mut session, &mut ctx)
+            .await
+            .unwrap();
+        let mut resp = ResponseHeader::build(200, None).unwrap();
+        plugin
+            .response_filter(&mut session, &mut resp, &mut ctx)
+            .await
+            .unwrap();
+        resp
+    }
+
+    fn header<'a>(resp: &'a ResponseHeader, name: &str) -> Option<&'a str> {
+        resp.headers.get(name).and_then(|v| v.to_str().ok())
+    }
+
+    #[tokio::test]
+    async fn responses_are_annotated_with_routing_details() {
+        let resp = respond(json!({}), None).await;
+        assert_eq!(header(&resp, "X-Pingsix-Route"), Some("-"));
+        assert_eq!(header(&resp, "X-Pingsix-Plugins"), Some(PLUGIN_NAME));
+        assert_eq!(header(&resp, "X-Pingsix-Upstream"), Some("10.0.0.1:8080"));
+        assert_eq!(header(&resp, "X-Pingsix-Retries"), Some("1"));
+        assert_eq!(header(&resp, "X-Pingsix-Cache"), Some("disabled"));
+        assert!(header(&resp, "X-Pingsix-Service").is_none());
+    }
+
+    #[tokio::test]
+    async fn only_valid_signed_tokens_unlock_annotations() {
+        let cfg = json!({"secret": SECRET});
+        let valid = signed_token::sign(SECRET, None, now() + 60);
+        let resp = respond(cfg.clone(), Some(&valid)).await;
+        assert_eq!(header(&resp, "X-Pingsix-Upstream"), Some("10.0.0.1:8080"));
+
+        let expired = signed_token::sign(SECRET, None, now() - 1);
+        let forged = signed_token::sign("fedcba9876543210", None, now() + 60);
+        for token in [
+            None,
+            Some(expired.as_str()),
+            Some(forged.as_str()),
+            Some("x"),
+        ] {
+            let resp = respond(cfg.clone(), token).await;
+            let annotations = resp
+                .headers
+                .keys()
+                .filter(|name| name.as_str().starts_with("x-pingsix-"))
+                .count();
+            assert_eq!(annotations, 0, "token {token:?} was accepted");
+        }
+    }
+
+    #[test]
+    fn config_defaults_and_validation() {
+        let config = PluginConfig::try_from(json!({})).unwrap();
+        assert!(config.secret.is_none());
+        assert_eq!(config.header, DEFAULT_DEBUG_HEADER);
+        assert!(PluginConfig::try_from(json!({"secret": "short"})).is_err());
+    }
+}
@@ -0,0 +1,195 @@
+use std::{
+    sync::Arc,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use async_trait::async_trait;
+use pingora_error::Result;
+use pingora_http::RequestHeader;
+use pingora_proxy::Session;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value as JsonValue};
+use validator::Validate;
+
+use crate::{
+    core::{ProxyContext, ProxyError, ProxyPlugin, ProxyResult},
+    utils::signed_token,
+};
+
+pub const PLUGIN_NAME: &str = "debug-routing";
+pub const PRIORITY: i32 = 965;
+
+/// Context key holding the node (`ip:port`) a verified request is pinned to.
+pub const CTX_KEY_DEBUG_NODE: &str = "debug_routing_node";
+
+const DEFAULT_NODE_HEADER: &str = "X-Debug-Node";
+const DEFAULT_SIGNATURE_HEADER: &str = "X-Debug-Signature";
+
+/// Creates a debug-routing plugin that pins signed requests to one upstream node.
+pub fn create_debug_routing_plugin(cfg: JsonValue) -> ProxyResult<Arc<dyn ProxyPlugin>> {
+    let config = PluginConfig::try_from(cfg)?;
+    Ok(Arc::new(PluginDebugRouting { config }))
+}
+
+/// JSON Schema of the debug-routing plugin configuration.
+pub fn schema() -> JsonValue {
+    json!({
+        "type": "object",
+        "properties": {
+            "secret": {"type": "string", "minLength": 16},
+            "allowed_nodes": {"type": "array", "items": {"type": "string", "minLength": 1}},
+            "node_header": {"type": "string", "minLength": 1, "default": DEFAULT_NODE_HEADER},
+            "signature_header": {
+                "type": "string",
+                "minLength": 1,
+                "default": DEFAULT_SIGNATURE_HEADER
+            }
+        },
+        "required": ["secret"]
+    })
+}
+
+#[derive(Debug, Serialize, Deserialize, Validate)]
+pub(super) struct PluginConfig {
+    /// Key of the HMAC-SHA256 signature over the node and expiry.
+    #[validate(length(min = 16))]
+    secret: String,
+    /// Nodes requests may be pinned to; empty allows any node of the upstream.
+    #[serde(default)]
+    allowed_nodes: Vec<String>,
+    /// Request header naming the node.
+    #[serde(default = "PluginConfig::default_node_header")]
+    #[validate(length(min = 1))]
+    node_header: String,
+    /// Request header carrying the `<expires>.<signature>` token.
+    #[serde(default = "PluginConfig::default_signature_header")]
+    #[validate(length(min = 1))]
+    signature_header: String,
+}
+
+impl PluginConfig {
+    fn default_node_header() -> String {
+        DEFAULT_NODE_HEADER.to_string()
+    }
+
+    fn default_signature_header() -> String {
+        DEFAULT_SIGNATURE_HEADER.to_string()
+    }
+}
+
+impl TryFrom<JsonValue> for PluginConfig {
+    type Error = ProxyError;
+
+    fn try_from(value: JsonValue) -> Result<Self, Self::Error> {
+        let config: PluginConfig = serde_json::from_value(value).map_err(|e| {
+            ProxyError::serialization_error("Invalid debug routing plugin config", e)
+        })?;
+
+        config.validate()?;
+
+        Ok(config)
+    }
+}
+
+pub struct PluginDebugRouting {
+    config: PluginConfig,
+}
+
+impl PluginDebugRouting {
+    /// The node the request asks for, when its signature verifies and the node is
+    /// allowed.
+    fn requested_node(&self, req: &RequestHeader, now: u64) -> Option<String> {
+        let header = |name: &str| {
+            req.headers
+                .get(name)
+                .and_then(|v| v.to_str().ok())
+                .map(str::trim)
+        };
+        let node = header(&self.config.node_header)?;
+        let token = header(&self.config.signature_header)?;
+        if !signed_token::verify(&self.config.secret, Some(node), token, now) {
+            log::warn!(
+                "Debug routing: ignoring request for node '{node}' with an invalid signature"
+            );
+            return None;
+        }
+        if !self.config.allowed_nodes.is_empty()
+            && !self.config.allowed_nodes.iter().any(|n| n == node)
+        {
+            log::warn!("Debug routing: node '{node}' is not in allowed_nodes");
+            return None;
+        }
+        Some(node.to_string())
+    }
+}
+
+#[async_trait]
+impl ProxyPlugin for PluginDebugRouting {
+    fn name(&self) -> &str {
+        PLUGIN_NAME
+    }
+
+    fn priority(&self) -> i32 {
+        PRIORITY
+    }
+
+    async fn request_filter(&self, session: &mut Session, ctx: &mut ProxyContext) -> Result<bool> {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or_default();
+        if let Some(node) = self.requested_node(session.req_header(), now) {
+            log::info!("Debug routing: pinning request to node '{node}'");
+            ctx.set(CTX_KEY_DEBUG_NODE, node);
+        }
+        Ok(false)
+    }
+
+    async fn upstream_request_filter(
+        &self,
+        _session: &mut Session,
+        upstream_request: &mut RequestHeader,
+        _ctx: &mut ProxyContext,
+    ) -> Result<()> {
+        // The headers are for the gateway; keep the token from upstream logs.
+        upstream_request.remove_header(self.config.node_header.as_str());
+        upstream_request.remove_header(self.config.signature_header.as_str());
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SECRET: &str = "0123456789abcdef";
+
+    fn sign(node: &str, expires: u64) -> String {
+        signed_token::sign(SECRET, Some(node), expires)
+    }
+
+    fn request(node: &str, token: &str) -> RequestHeader {
+        let mut req = RequestHeader::build("GET", b"/", None).unwrap();
+        req.insert_header("X-Debug-Node", node).unwrap();
+        req.insert_header("X-Debug-Signature", token).unwrap();
+        req
+    }
+
+    #[test]
+    fn only_allowed_nodes_are_pinned() {
+        let plugin = PluginDebugRouting {
+            config: PluginConfig::try_from(json!({
+                "secret": SECRET,
+                "allowed_nodes": ["10.0.0.5:8080"]
+            }))
+            .unwrap(),
+        };
+        let req = request("10.0.0.5:8080", &sign("10.0.0.5:8080", 2000));
+        assert_eq!(
+            plugin.requested_node(&req, 1000).as_deref(),
+            Some("10.0.0.5:8080")
+        );
+        let req = request("10.0.0.6:8080", &sign("10.0.0.6:8080", 2000));
+        assert!(plugin.requested_node(&req, 1000).is_none());
+    }
+}
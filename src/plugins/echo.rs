@@ -6,12 +6,12 @@ use pingora_error::Result;
 use pingora_http::ResponseHeader;
 use pingora_proxy::Session;
 use serde::{Deserialize, Serialize};
-use serde_json::Value as JsonValue;
+use serde_json::{json, Value as JsonValue};
 
 use crate::core::{ProxyContext, ProxyError, ProxyPlugin, ProxyResult};
 
 pub const PLUGIN_NAME: &str = "echo";
-const PRIORITY: i32 = 412;
+pub const PRIORITY: i32 = 412;
 
 /// Creates an Echo plugin instance with the given configuration.
 pub fn create_echo_plugin(cfg: JsonValue) -> ProxyResult<Arc<dyn ProxyPlugin>> {
@@ -19,9 +19,21 @@ pub fn create_echo_plugin(cfg: JsonValue) -> ProxyResult<Arc<dyn ProxyPlugin>> {
     Ok(Arc::new(PluginEcho { config }))
 }
 
+/// JSON Schema of the echo plugin configuration.
+pub fn schema() -> JsonValue {
+    json!({
+        "type": "object",
+        "required": ["body"],
+        "properties": {
+            "body": {"type": "string"},
+            "headers": {"type": "object", "additionalProperties": {"type": "string"}}
+        }
+    })
+}
+
 /// Configuration for the Echo plugin.
 #[derive(Default, Debug, Serialize, Deserialize)]
-struct PluginConfig {
+pub(super) struct PluginConfig {
     /// The response body to be sent back in the HTTP response.
     body: String,
 
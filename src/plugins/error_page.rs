@@ -0,0 +1,243 @@
+use std::{collections::HashMap, sync::Arc};
+
+use async_trait::async_trait;
+use bytes::Bytes;
+use http::{header, StatusCode};
+use pingora_error::Result;
+use pingora_http::ResponseHeader;
+use pingora_proxy::Session;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value as JsonValue};
+
+use crate::core::{ProxyContext, ProxyError, ProxyPlugin, ProxyResult};
+
+pub const PLUGIN_NAME: &str = "error-page";
+pub const PRIORITY: i32 = 12005;
+
+/// Context key holding the error pages that apply to the current request.
+const PAGES_CTX_KEY: &str = "error_pages";
+
+/// Creates an Error Page plugin instance with the given configuration.
+/// This plugin replaces the empty bodies of gateway-generated errors (no matching route,
+/// upstream connect/response failures) with configured pages. A route-level instance
+/// takes precedence over one in a global rule.
+pub fn create_error_page_plugin(cfg: JsonValue) -> ProxyResult<Arc<dyn ProxyPlugin>> {
+    let config = PluginConfig::try_from(cfg)?;
+    Ok(Arc::new(PluginErrorPage {
+        pages: Arc::new(config),
+    }))
+}
+
+/// JSON Schema of the error-page plugin configuration.
+pub fn schema() -> JsonValue {
+    json!({
+        "type": "object",
+        "required": ["pages"],
+        "properties": {
+            "pages": {
+                "type": "object",
+                "description": "Pages by status code (400-599)",
+                "additionalProperties": {
+                    "type": "object",
+                    "required": ["body"],
+                    "properties": {
+                        "body": {"type": "string"},
+                        "content_type": {"type": "string", "default": "text/html; charset=utf-8"}
+                    }
+                }
+            }
+        }
+    })
+}
+
+/// A page rendered for one status code.
+#[derive(Debug, Serialize, Deserialize)]
+struct ErrorPage {
+    /// Body template; `$status`, `$reason` and `$request_id` are expanded.
+    body: String,
+
+    /// Content type of the body (default: `text/html; charset=utf-8`).
+    #[serde(default = "ErrorPage::default_content_type")]
+    content_type: String,
+}
+
+impl ErrorPage {
+    fn default_content_type() -> String {
+        "text/html; charset=utf-8".to_string()
+    }
+
+    /// Expand the template variables, escaped for the page's content type.
+    fn render(&self, status: StatusCode, request_id: &str) -> String {
+        if !self.body.contains('$') {
+            return self.body.clone();
+        }
+        let escape: fn(&str) -> String = if self.content_type.contains("json") {
+            escape_json
+        } else if self.content_type.contains("html") || self.content_type.contains("xml") {
+            escape_html
+        } else {
+            |value| value.to_string()
+        };
+        self.body
+            .replace("$status", status.as_str())
+            .replace("$reason", status.canonical_reason().unwrap_or(""))
+            .replace("$request_id", &escape(request_id))
+    }
+}
+
+/// Configuration for the Error Page plugin.
+#[derive(Default, Debug, Serialize, Deserialize)]
+pub(super) struct PluginConfig {
+    /// Pages by status code (`400`-`599`).
+    pages: HashMap<u16, ErrorPage>,
+}
+
+impl TryFrom<JsonValue> for PluginConfig {
+    type Error = ProxyError;
+
+    fn try_from(value: JsonValue) -> Result<Self, Self::Error> {
+        let config: PluginConfig = serde_json::from_value(value)
+            .map_err(|e| ProxyError::serialization_error("Invalid error page plugin config", e))?;
+
+        if let Some(status) = config.pages.keys().find(|s| !(400..=599).contains(*s)) {
+            return Err(ProxyError::Configuration(format!(
+                "error-page status {status} is not an error status (400-599)"
+            )));
+        }
+        for page in config.pages.values() {
+            http::HeaderValue::from_str(&page.content_type).map_err(|_| {
+                ProxyError::Configuration(format!(
+                    "error-page content_type '{}' is not a valid header value",
+                    page.content_type
+                ))
+            })?;
+        }
+
+        Ok(config)
+    }
+}
+
+impl PluginConfig {
+    /// Response header and body for `status`, if a page is configured for it.
+    fn render(&self, status: u16, request_id: &str) -> Option<(ResponseHeader, Bytes)> {
+        let page = self.pages.get(&status)?;
+        let status = StatusCode::from_u16(status).ok()?;
+        let body = page.render(status, request_id);
+
+        let mut resp = ResponseHeader::build(status, Some(3)).ok()?;
+        resp.insert_header(header::CONTENT_TYPE, page.content_type.as_str())
+            .ok()?;
+        resp.insert_header(header::CONTENT_LENGTH, body.len().to_string())
+            .ok()?;
+        resp.insert_header(header::CACHE_CONTROL, "no-store").ok()?;
+        Some((resp, Bytes::from(body)))
+    }
+}
+
+fn escape_html(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for ch in value.chars() {
+        match ch {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            _ => escaped.push(ch),
+        }
+    }
+    escaped
+}
+
+fn escape_json(value: &str) -> String {
+    let quoted = JsonValue::from(value).to_string();
+    quoted[1..quoted.len() - 1].to_string()
+}
+
+/// Error Page plugin implementation.
+///
+/// The plugin only records its pages on the request; the proxy service renders them
+/// when it generates an error response itself.
+pub struct PluginErrorPage {
+    pages: Arc<PluginConfig>,
+}
+
+#[async_trait]
+impl ProxyPlugin for PluginErrorPage {
+    fn name(&self) -> &str {
+        PLUGIN_NAME
+    }
+
+    fn priority(&self) -> i32 {
+        PRIORITY
+    }
+
+    async fn early_request_filter(
+        &self,
+        _session: &mut Session,
+        ctx: &mut ProxyContext,
+    ) -> Result<()> {
+        // Global rules run first, so a route-level instance replaces their pages.
+        ctx.set(PAGES_CTX_KEY, self.pages.clone());
+        Ok(())
+    }
+}
+
+/// Send the gateway error `status`, rendered from the request's `error-page`
+/// configuration when it has a page for it, otherwise as Pingora's empty response.
+pub async fn respond_error(session: &mut Session, ctx: &ProxyContext, status: u16) -> Result<()> {
+    // Once a response header went out only Pingora's own handling applies.
+    let page = ctx
+        .get::<Arc<PluginConfig>>(PAGES_CTX_KEY)
+        .filter(|_| session.response_written().is_none())
+        .and_then(|pages| pages.render(status, ctx.request_id().unwrap_or_default()));
+    match page {
+        Some((resp, body)) => {
+            session.write_response_header(Box::new(resp), false).await?;
+            session.write_response_body(Some(body), true).await
+        }
+        None => session.respond_error(status).await,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::response::content_type;
+    use serde_json::json;
+
+    #[test]
+    fn config_rejects_non_error_status() {
+        assert!(PluginConfig::try_from(json!({"pages": {"200": {"body": "ok"}}})).is_err());
+        assert!(PluginConfig::try_from(json!({"pages": {"404": {"body": "missing"}}})).is_ok());
+    }
+
+    #[test]
+    fn renders_page_with_escaped_variables() {
+        let config = PluginConfig::try_from(json!({
+            "pages": {
+                "502": {
+                    "body": "{\"status\": $status, \"reason\": \"$reason\", \"id\": \"$request_id\"}",
+                    "content_type": "application/json"
+                },
+                "404": {"body": "<p>$status $request_id</p>"}
+            }
+        }))
+        .unwrap();
+
+        let (resp, body) = config.render(502, "a\"b").unwrap();
+        assert_eq!(resp.status, StatusCode::BAD_GATEWAY);
+        assert_eq!(
+            resp.headers[header::CONTENT_TYPE],
+            content_type::APPLICATION_JSON
+        );
+        assert_eq!(
+            body,
+            r#"{"status": 502, "reason": "Bad Gateway", "id": "a\"b"}"#
+        );
+
+        let (_, body) = config.render(404, "<x>").unwrap();
+        assert_eq!(body, "<p>404 &lt;x&gt;</p>");
+        assert!(config.render(504, "").is_none());
+    }
+}
@@ -0,0 +1,346 @@
+use std::{collections::HashMap, sync::Arc};
+
+use async_trait::async_trait;
+use bytes::Bytes;
+use http::{header, HeaderName, HeaderValue, StatusCode};
+use once_cell::sync::Lazy;
+use pingora_error::Result;
+use pingora_http::ResponseHeader;
+use pingora_proxy::Session;
+use prometheus::{register_int_counter_vec, IntCounterVec};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value as JsonValue};
+use validator::{Validate, ValidationError};
+
+use crate::core::{ProxyContext, ProxyError, ProxyPlugin, ProxyResult, UpstreamSelector};
+use crate::proxy::upstream::ProxyUpstream;
+
+pub const PLUGIN_NAME: &str = "fallback";
+pub const PRIORITY: i32 = 1007;
+
+/// Context key holding the request's [`FallbackState`].
+pub const CTX_KEY_FALLBACK: &str = "pingsix_fallback";
+
+/// Fallbacks taken per route, by `action` (`upstream` or `response`).
+static FALLBACKS: Lazy<IntCounterVec> = Lazy::new(|| {
+    register_int_counter_vec!(
+        "pingsix_fallback_total",
+        "Requests answered by a fallback upstream or static response",
+        &["route", "action"]
+    )
+    .expect("fallback metric registration must succeed")
+});
+
+/// Creates a fallback plugin. An `upstream_id` needs the named upstreams of the
+/// runtime, see [`create_fallback_plugin_with_upstreams`].
+pub fn create_fallback_plugin(cfg: JsonValue) -> ProxyResult<Arc<dyn ProxyPlugin>> {
+    create_fallback_plugin_with_upstreams(cfg, &HashMap::new())
+}
+
+/// JSON Schema of the fallback plugin configuration.
+pub fn schema() -> JsonValue {
+    json!({
+        "type": "object",
+        "anyOf": [{"required": ["upstream_id"]}, {"required": ["response"]}],
+        "properties": {
+            "upstream_id": {"type": "string", "minLength": 1},
+            "response": {
+                "type": "object",
+                "properties": {
+                    "status": {"type": "integer", "minimum": 200, "maximum": 599, "default": 503},
+                    "content_type": {"type": "string", "default": "text/plain; charset=utf-8"},
+                    "headers": {"type": "object", "additionalProperties": {"type": "string"}},
+                    "body": {"type": "string", "default": ""}
+                }
+            },
+            "statuses": {
+                "type": "array",
+                "items": {"type": "integer", "minimum": 500, "maximum": 599},
+                "default": [500, 502, 503, 504]
+            }
+        }
+    })
+}
+
+#[derive(Debug, Serialize, Deserialize, Validate)]
+#[validate(schema(function = "PluginConfig::validate_target"))]
+pub(super) struct PluginConfig {
+    /// Named upstream the request is retried against once.
+    #[serde(default)]
+    upstream_id: Option<String>,
+    /// Static response sent when there is no fallback upstream or it failed too.
+    #[serde(default)]
+    #[validate(nested)]
+    response: Option<StaticResponse>,
+    /// Upstream statuses handled like a failed connection.
+    #[serde(default = "PluginConfig::default_statuses")]
+    #[validate(custom(function = "validate_statuses"))]
+    statuses: Vec<u16>,
+}
+
+impl PluginConfig {
+    fn default_statuses() -> Vec<u16> {
+        vec![500, 502, 503, 504]
+    }
+
+    fn validate_target(&self) -> Result<(), ValidationError> {
+        if self.upstream_id.is_none() && self.response.is_none() {
+            return Err(ValidationError::new("set upstream_id or response"));
+        }
+        Ok(())
+    }
+}
+
+fn validate_statuses(statuses: &[u16]) -> Result<(), ValidationError> {
+    if statuses.iter().any(|status| !(500..=599).contains(status)) {
+        return Err(ValidationError::new("fallback statuses must be 5xx"));
+    }
+    Ok(())
+}
+
+#[derive(Debug, Serialize, Deserialize, Validate)]
+#[validate(schema(function = "StaticResponse::validate_headers"))]
+struct StaticResponse {
+    #[serde(default = "StaticResponse::default_status")]
+    #[validate(range(min = 200, max = 599))]
+    status: u16,
+    #[serde(default = "StaticResponse::default_content_type")]
+    content_type: String,
+    #[serde(default)]
+    headers: HashMap<String, String>,
+    #[serde(default)]
+    body: String,
+}
+
+impl StaticResponse {
+    fn default_status() -> u16 {
+        503
+    }
+
+    fn default_content_type() -> String {
+        "text/plain; charset=utf-8".to_string()
+    }
+
+    fn validate_headers(&self) -> Result<(), ValidationError> {
+        if HeaderValue::try_from(self.content_type.as_str()).is_err()
+            || self.headers.iter().any(|(name, value)| {
+                HeaderName::try_from(name.as_str()).is_err()
+                    || HeaderValue::try_from(value.as_str()).is_err()
+            })
+        {
+            return Err(ValidationError::new("invalid response header"));
+        }
+        Ok(())
+    }
+}
+
+impl TryFrom<JsonValue> for PluginConfig {
+    type Error = ProxyError;
+
+    fn try_from(value: JsonValue) -> Result<Self, Self::Error> {
+        let config: PluginConfig = serde_json::from_value(value)
+            .map_err(|e| ProxyError::serialization_error("Invalid fallback plugin config", e))?;
+
+        config.validate()?;
+
+        Ok(config)
+    }
+}
+
+/// Validate fallback JSON without resolving the named upstream (Admin pre-check).
+pub fn validate_fallback_config(cfg: &JsonValue) -> ProxyResult<()> {
+    PluginConfig::try_from(cfg.clone()).map(|_| ())
+}
+
+/// The `upstream_id` of a fallback config value, if any.
+pub fn named_upstream_ids(cfg: &JsonValue) -> ProxyResult<Vec<String>> {
+    let config = PluginConfig::try_from(cfg.clone())?;
+    Ok(config.upstream_id.into_iter().collect())
+}
+
+pub(crate) fn create_fallback_plugin_with_upstreams(
+    cfg: JsonValue,
+    upstreams: &HashMap<String, Arc<ProxyUpstream>>,
+) -> ProxyResult<Arc<dyn ProxyPlugin>> {
+    let config = PluginConfig::try_from(cfg)?;
+    let upstream = match &config.upstream_id {
+        Some(id) => Some(upstreams.get(id).cloned().ok_or_else(|| {
+            ProxyError::Configuration(format!("Fallback references missing upstream '{id}'"))
+        })? as Arc<dyn UpstreamSelector>),
+        None => None,
+    };
+
+    Ok(Arc::new(PluginFallback {
+        settings: Arc::new(FallbackSettings { config, upstream }),
+    }))
+}
+
+/// What the HTTP service needs to degrade a failing request.
+pub struct FallbackSettings {
+    config: PluginConfig,
+    /// The `upstream_id` upstream, resolved at build time.
+    upstream: Option<Arc<dyn UpstreamSelector>>,
+}
+
+/// Per-request fallback progress, stored under [`CTX_KEY_FALLBACK`].
+pub struct FallbackState {
+    settings: Arc<FallbackSettings>,
+    /// Whether the request has been sent to the fallback upstream already.
+    rerouted: bool,
+}
+
+impl FallbackState {
+    fn can_reroute(&self) -> bool {
+        !self.rerouted && self.settings.upstream.is_some()
+    }
+
+    /// Whether an upstream response with `status` should be failed to take the
+    /// fallback rather than be passed to the client.
+    fn intercepts(&self, status: u16) -> bool {
+        self.settings.config.statuses.contains(&status)
+            && (self.can_reroute() || self.settings.config.response.is_some())
+    }
+}
+
+fn record(ctx: &ProxyContext, action: &str) {
+    let route = ctx.route.as_ref().map_or("", |route| route.id());
+    FALLBACKS.with_label_values(&[route, action]).inc();
+}
+
+/// Whether the upstream response status of this request should be turned into an
+/// error so the fallback handles it.
+pub(crate) fn intercepts(ctx: &ProxyContext, status: u16) -> bool {
+    ctx.get::<FallbackState>(CTX_KEY_FALLBACK)
+        .is_some_and(|state| state.intercepts(status))
+}
+
+/// Point the request at the fallback upstream once. Returns whether the caller should
+/// retry; requests whose body no longer fits the retry buffer stay where they are.
+pub(crate) fn reroute(session: &Session, ctx: &mut ProxyContext) -> bool {
+    let Some(state) = ctx.get_mut::<FallbackState>(CTX_KEY_FALLBACK) else {
+        return false;
+    };
+    if !state.can_reroute() || session.retry_buffer_truncated() {
+        return false;
+    }
+    state.rerouted = true;
+    let upstream = state.settings.upstream.clone();
+    ctx.upstream_override = upstream;
+    record(ctx, "upstream");
+    true
+}
+
+/// Send the configured static response. Returns its status, or `None` when the
+/// request has no static fallback.
+pub(crate) async fn respond(session: &mut Session, ctx: &ProxyContext) -> Result<Option<u16>> {
+    let Some(state) = ctx.get::<FallbackState>(CTX_KEY_FALLBACK) else {
+        return Ok(None);
+    };
+    let Some(response) = &state.settings.config.response else {
+        return Ok(None);
+    };
+    let status = StatusCode::from_u16(response.status).unwrap_or(StatusCode::SERVICE_UNAVAILABLE);
+    let mut resp = ResponseHeader::build(status, None)?;
+    resp.insert_header(header::CONTENT_TYPE, response.content_type.as_str())?;
+    resp.insert_header(header::CONTENT_LENGTH, response.body.len().to_string())?;
+    for (name, value) in &response.headers {
+        resp.insert_header(name.clone(), value.as_str())?;
+    }
+    session.set_keepalive(None);
+    session
+        .write_response_header(Box::new(resp), response.body.is_empty())
+        .await?;
+    if !response.body.is_empty() {
+        session
+            .write_response_body(Some(Bytes::from(response.body.clone())), true)
+            .await?;
+    }
+    record(ctx, "response");
+    Ok(Some(response.status))
+}
+
+pub struct PluginFallback {
+    settings: Arc<FallbackSettings>,
+}
+
+#[async_trait]
+impl ProxyPlugin for PluginFallback {
+    fn name(&self) -> &str {
+        PLUGIN_NAME
+    }
+
+    fn priority(&self) -> i32 {
+        PRIORITY
+    }
+
+    async fn request_filter(&self, session: &mut Session, ctx: &mut ProxyContext) -> Result<bool> {
+        if self.settings.upstream.is_some() {
+            // Keep the request body so it can be replayed to the fallback upstream.
+            session.enable_retry_buffering();
+        }
+        ctx.set(
+            CTX_KEY_FALLBACK,
+            FallbackState {
+                settings: self.settings.clone(),
+                rerouted: false,
+            },
+        );
+        Ok(false)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fallback_state(cfg: JsonValue, with_upstream: bool) -> FallbackState {
+        let config = PluginConfig::try_from(cfg).unwrap();
+        let upstream = with_upstream.then(|| {
+            let upstream = serde_json::from_value(json!({
+                "id": "backup",
+                "nodes": {"127.0.0.1:8080": 1},
+                "type": "roundrobin"
+            }))
+            .unwrap();
+            Arc::new(ProxyUpstream::build_static(upstream).unwrap()) as Arc<dyn UpstreamSelector>
+        });
+        FallbackState {
+            settings: Arc::new(FallbackSettings { config, upstream }),
+            rerouted: false,
+        }
+    }
+
+    #[test]
+    fn statuses_are_intercepted_while_a_fallback_remains() {
+        let mut state = fallback_state(json!({"upstream_id": "backup"}), true);
+        assert!(state.intercepts(502));
+        assert!(!state.intercepts(501));
+        assert!(!state.intercepts(404));
+        state.rerouted = true;
+        assert!(
+            !state.intercepts(502),
+            "the fallback upstream's answer is final"
+        );
+
+        let mut state = fallback_state(
+            json!({"upstream_id": "backup", "response": {"body": "down"}, "statuses": [503]}),
+            true,
+        );
+        state.rerouted = true;
+        assert!(state.intercepts(503));
+    }
+
+    #[test]
+    fn config_needs_a_fallback_target() {
+        assert!(PluginConfig::try_from(json!({})).is_err());
+        assert!(PluginConfig::try_from(json!({"response": {}, "statuses": [404]})).is_err());
+        assert!(
+            PluginConfig::try_from(json!({"response": {"headers": {"bad header": "x"}}})).is_err()
+        );
+        let config = PluginConfig::try_from(json!({"response": {}})).unwrap();
+        assert_eq!(config.statuses, vec![500, 502, 503, 504]);
+        assert_eq!(config.response.unwrap().status, 503);
+
+        assert!(create_fallback_plugin(json!({"upstream_id": "missing"})).is_err());
+    }
+}
@@ -8,13 +8,14 @@ use pingora_http::ResponseHeader;
 use pingora_proxy::Session;
 use rand::Rng;
 use serde::{Deserialize, Serialize};
-use serde_json::Value as JsonValue;
-use validator::Validate;
+use serde_json::{json, Value as JsonValue};
+use sha2::{Digest, Sha256};
+use validator::{Validate, ValidationError};
 
 use crate::core::{ProxyContext, ProxyError, ProxyPlugin, ProxyResult};
 
 pub const PLUGIN_NAME: &str = "fault-injection";
-const PRIORITY: i32 = 11000;
+pub const PRIORITY: i32 = 11000;
 
 /// Creates a Fault Injection plugin instance with the given configuration.
 /// This plugin allows you to inject faults (delays and aborts) into requests for testing purposes.
@@ -23,19 +24,81 @@ pub fn create_fault_injection_plugin(cfg: JsonValue) -> ProxyResult<Arc<dyn Prox
     Ok(Arc::new(PluginFaultInjection { config }))
 }
 
+/// JSON Schema of the fault-injection plugin configuration.
+pub fn schema() -> JsonValue {
+    let percentage = json!({"type": "integer", "minimum": 0, "maximum": 100});
+    json!({
+        "type": "object",
+        "properties": {
+            "delay": {
+                "type": "object",
+                "required": ["duration"],
+                "properties": {
+                    "duration": {"type": "number", "minimum": 0},
+                    "max_duration": {"type": "number", "minimum": 0},
+                    "percentage": percentage
+                }
+            },
+            "abort": {
+                "type": "object",
+                "required": ["http_status"],
+                "properties": {
+                    "http_status": {"type": "integer", "minimum": 200},
+                    "body": {"type": "string"},
+                    "headers": {"type": "object"},
+                    "percentage": percentage
+                }
+            },
+            "match": {
+                "type": "object",
+                "properties": {
+                    "consumers": {"type": "array", "items": {"type": "string"}},
+                    "headers": {"type": "object", "additionalProperties": {"type": "string"}}
+                }
+            },
+            "sample_by": {"type": "string", "enum": ["random", "consumer", "header"], "default": "random"},
+            "sample_header": {"type": "string", "minLength": 1}
+        }
+    })
+}
+
 /// Configuration for injecting delays into requests
 #[derive(Debug, Clone, Serialize, Deserialize, Validate)]
+#[validate(schema(function = "DelayConfig::validate_range"))]
 struct DelayConfig {
     /// Duration to delay the request in seconds (supports decimals)
     #[validate(range(min = 0.0))]
     duration: f64,
 
+    /// When set, each delay is drawn uniformly between `duration` and this many seconds.
+    #[serde(default)]
+    max_duration: Option<f64>,
+
     /// Percentage of requests to apply delay to (0-100). If not set, applies to all requests.
     #[serde(default)]
     #[validate(range(min = 0, max = 100))]
     percentage: Option<u32>,
 }
 
+impl DelayConfig {
+    fn validate_range(&self) -> Result<(), ValidationError> {
+        match self.max_duration {
+            Some(max) if max < self.duration => Err(ValidationError::new(
+                "max_duration must not be below duration",
+            )),
+            _ => Ok(()),
+        }
+    }
+
+    fn pick(&self) -> Duration {
+        let seconds = match self.max_duration {
+            Some(max) if max > self.duration => rand::thread_rng().gen_range(self.duration..=max),
+            _ => self.duration,
+        };
+        Duration::from_secs_f64(seconds)
+    }
+}
+
 /// Configuration for aborting requests with a specific status code
 #[derive(Debug, Clone, Serialize, Deserialize, Validate)]
 struct AbortConfig {
@@ -57,9 +120,34 @@ struct AbortConfig {
     percentage: Option<u32>,
 }
 
+/// Requests faults are limited to; every listed condition must hold.
+#[derive(Debug, Default, Clone, Serialize, Deserialize, Validate)]
+struct MatchConfig {
+    /// Authenticated consumers, any of which matches.
+    #[serde(default)]
+    consumers: Vec<String>,
+
+    /// Request headers that must carry exactly these values.
+    #[serde(default)]
+    headers: HashMap<String, String>,
+}
+
+/// What decides whether a request falls within a fault's `percentage`.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum SampleBy {
+    /// An independent draw per request.
+    #[default]
+    Random,
+    /// The authenticated consumer, so a consumer is always in or out.
+    Consumer,
+    /// The value of `sample_header`.
+    Header,
+}
+
 /// Main plugin configuration
 #[derive(Debug, Serialize, Deserialize, Validate)]
-struct PluginConfig {
+pub(super) struct PluginConfig {
     /// Optional delay configuration
     #[serde(default)]
     #[validate(nested)]
@@ -69,6 +157,19 @@ struct PluginConfig {
     #[serde(default)]
     #[validate(nested)]
     abort: Option<AbortConfig>,
+
+    /// Only inject faults into matching requests.
+    #[serde(default, rename = "match")]
+    #[validate(nested)]
+    matches: Option<MatchConfig>,
+
+    #[serde(default)]
+    sample_by: SampleBy,
+
+    /// Header whose value keys sampling when `sample_by` is `header`.
+    #[serde(default)]
+    #[validate(length(min = 1))]
+    sample_header: Option<String>,
 }
 
 impl TryFrom<JsonValue> for PluginConfig {
@@ -87,6 +188,11 @@ impl TryFrom<JsonValue> for PluginConfig {
                 "At least one of 'delay' or 'abort' must be configured".to_string(),
             ));
         }
+        if config.sample_by == SampleBy::Header && config.sample_header.is_none() {
+            return Err(ProxyError::Plugin(
+                "'sample_header' is required when 'sample_by' is 'header'".to_string(),
+            ));
+        }
 
         Ok(config)
     }
@@ -97,32 +203,71 @@ pub struct PluginFaultInjection {
     config: PluginConfig,
 }
 
+/// Stable bucket in `1..=100` for a sampling key.
+fn key_bucket(key: &str) -> u32 {
+    let digest = Sha256::digest(key.as_bytes());
+    u32::from_be_bytes([digest[0], digest[1], digest[2], digest[3]]) % 100 + 1
+}
+
 impl PluginFaultInjection {
-    /// Check if a fault should be applied based on the configured percentage
-    fn sample_hit(percentage: Option<u32>) -> bool {
+    /// Check if a fault should be applied based on the configured percentage. `bucket`
+    /// is the request's stable sampling bucket; without one the request is drawn at random.
+    fn sample_hit(percentage: Option<u32>, bucket: Option<u32>) -> bool {
         match percentage {
             None => true, // If no percentage is set, always apply
-            Some(pct) => {
-                let mut rng = rand::thread_rng();
-                rng.gen_range(1..=100) <= pct
-            }
+            Some(pct) => bucket.unwrap_or_else(|| rand::thread_rng().gen_range(1..=100)) <= pct,
         }
     }
 
+    /// Whether the request is one faults may be injected into.
+    fn matches(&self, session: &Session, ctx: &ProxyContext) -> bool {
+        let Some(matches) = &self.config.matches else {
+            return true;
+        };
+        let consumer_ok = matches.consumers.is_empty()
+            || ctx
+                .authenticated_identity
+                .as_ref()
+                .is_some_and(|consumer| matches.consumers.contains(consumer));
+        let headers = &session.req_header().headers;
+        consumer_ok
+            && matches.headers.iter().all(|(name, value)| {
+                headers
+                    .get(name.as_str())
+                    .is_some_and(|actual| actual.as_bytes() == value.as_bytes())
+            })
+    }
+
+    /// Stable sampling bucket of the request; `None` when sampling is random or the
+    /// request has no key.
+    fn bucket(&self, session: &Session, ctx: &ProxyContext) -> Option<u32> {
+        let key = match self.config.sample_by {
+            SampleBy::Random => return None,
+            SampleBy::Consumer => ctx.authenticated_identity.clone()?,
+            SampleBy::Header => session
+                .req_header()
+                .headers
+                .get(self.config.sample_header.as_deref()?)?
+                .to_str()
+                .ok()?
+                .to_string(),
+        };
+        Some(key_bucket(&key))
+    }
+
     /// Apply delay if configured and sampled
-    async fn apply_delay(&self) {
+    async fn apply_delay(&self, bucket: Option<u32>) {
         if let Some(ref delay_config) = self.config.delay {
-            if Self::sample_hit(delay_config.percentage) {
-                let duration = Duration::from_secs_f64(delay_config.duration);
-                tokio::time::sleep(duration).await;
+            if Self::sample_hit(delay_config.percentage, bucket) {
+                tokio::time::sleep(delay_config.pick()).await;
             }
         }
     }
 
     /// Check if request should be aborted and send abort response if needed
-    async fn check_and_abort(&self, session: &mut Session) -> Result<bool> {
+    async fn check_and_abort(&self, session: &mut Session, bucket: Option<u32>) -> Result<bool> {
         if let Some(ref abort_config) = self.config.abort {
-            if Self::sample_hit(abort_config.percentage) {
+            if Self::sample_hit(abort_config.percentage, bucket) {
                 return self.send_abort_response(session, abort_config).await;
             }
         }
@@ -184,12 +329,17 @@ impl ProxyPlugin for PluginFaultInjection {
         PRIORITY
     }
 
-    async fn request_filter(&self, session: &mut Session, _ctx: &mut ProxyContext) -> Result<bool> {
+    async fn request_filter(&self, session: &mut Session, ctx: &mut ProxyContext) -> Result<bool> {
+        if !self.matches(session, ctx) {
+            return Ok(false);
+        }
+        let bucket = self.bucket(session, ctx);
+
         // Apply delay first (if configured)
-        self.apply_delay().await;
+        self.apply_delay(bucket).await;
 
         // Then check if request should be aborted (if configured)
-        self.check_and_abort(session).await
+        self.check_and_abort(session, bucket).await
     }
 }
 
@@ -299,18 +449,60 @@ mod tests {
 
     #[test]
     fn test_sample_hit_always_true_when_none() {
-        assert!(PluginFaultInjection::sample_hit(None));
+        assert!(PluginFaultInjection::sample_hit(None, None));
     }
 
     #[test]
     fn test_sample_hit_never_at_zero() {
-        assert!(!PluginFaultInjection::sample_hit(Some(0)));
+        assert!(!PluginFaultInjection::sample_hit(Some(0), None));
     }
 
     #[test]
     fn test_sample_hit_always_at_hundred() {
         for _ in 0..100 {
-            assert!(PluginFaultInjection::sample_hit(Some(100)));
+            assert!(PluginFaultInjection::sample_hit(Some(100), None));
+        }
+    }
+
+    #[test]
+    fn keyed_sampling_is_stable_per_key() {
+        let bucket = key_bucket("alice");
+        assert!((1..=100).contains(&bucket));
+        assert_eq!(bucket, key_bucket("alice"));
+        for _ in 0..20 {
+            assert!(PluginFaultInjection::sample_hit(Some(bucket), Some(bucket)));
+            assert!(!PluginFaultInjection::sample_hit(
+                Some(bucket - 1),
+                Some(bucket)
+            ));
+        }
+        let hits = (0..1000)
+            .filter(|i| {
+                PluginFaultInjection::sample_hit(Some(30), Some(key_bucket(&i.to_string())))
+            })
+            .count();
+        assert!((200..400).contains(&hits), "{hits} of 1000 keys hit 30%");
+    }
+
+    #[test]
+    fn delay_jitter_stays_within_range() {
+        let config = PluginConfig::try_from(json!({
+            "delay": {"duration": 0.1, "max_duration": 0.2}
+        }))
+        .unwrap();
+        let delay = config.delay.unwrap();
+        for _ in 0..50 {
+            let picked = delay.pick();
+            assert!(picked >= Duration::from_millis(100) && picked <= Duration::from_millis(200));
         }
+        assert!(PluginConfig::try_from(json!({
+            "delay": {"duration": 2.0, "max_duration": 1.0}
+        }))
+        .is_err());
+        assert!(PluginConfig::try_from(json!({
+            "abort": {"http_status": 503},
+            "sample_by": "header"
+        }))
+        .is_err());
     }
 }
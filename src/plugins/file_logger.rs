@@ -1,4 +1,4 @@
-use std::sync::Arc;
+use std::{sync::Arc, time::Duration};
 
 use async_trait::async_trait;
 use log::info;
@@ -7,15 +7,15 @@ use pingora_error::Result;
 use pingora_proxy::Session;
 use regex::Regex;
 use serde::{Deserialize, Serialize};
-use serde_json::Value as JsonValue;
+use serde_json::{json, Value as JsonValue};
 
 use crate::{
-    core::{ProxyContext, ProxyError, ProxyPlugin, ProxyResult},
+    core::{slow_log, ProxyContext, ProxyError, ProxyPlugin, ProxyResult},
     utils::request,
 };
 
 pub const PLUGIN_NAME: &str = "file-logger";
-const PRIORITY: i32 = 399;
+pub const PRIORITY: i32 = 399;
 
 fn push_escaped(output: &mut String, value: &str) {
     for character in value.chars() {
@@ -46,6 +46,24 @@ fn redact_query(query: &str, names: &[String]) -> String {
         .join("&")
 }
 
+/// Milliseconds with microsecond precision, for the latency breakdown variables.
+fn write_millis(output: &mut String, elapsed: Duration) {
+    use std::fmt::Write;
+    let _ = write!(output, "{:.3}", slow_log::millis(elapsed));
+}
+
+/// Approximate wire size of the request line and headers, as counted by `$request_length`.
+fn request_header_length(req: &pingora_http::RequestHeader) -> usize {
+    // "METHOD URI HTTP/x.y\r\n" plus "name: value\r\n" per header and the final "\r\n".
+    let request_line = req.method.as_str().len() + req.uri.to_string().len() + 12;
+    let headers: usize = req
+        .headers
+        .iter()
+        .map(|(name, value)| name.as_str().len() + value.len() + 4)
+        .sum();
+    request_line + headers + 2
+}
+
 /// Creates a file logger plugin instance with the given configuration.
 pub fn create_file_logger_plugin(cfg: JsonValue) -> ProxyResult<Arc<dyn ProxyPlugin>> {
     let config = PluginConfig::try_from(cfg)?;
@@ -57,13 +75,28 @@ pub fn create_file_logger_plugin(cfg: JsonValue) -> ProxyResult<Arc<dyn ProxyPlu
     }))
 }
 
+/// JSON Schema of the file-logger plugin configuration.
+pub fn schema() -> JsonValue {
+    json!({
+        "type": "object",
+        "properties": {
+            "log_format": {"type": "string", "default": PluginConfig::default_log_format()},
+            "redact_query_params": {"type": "array", "items": {"type": "string"}}
+        }
+    })
+}
+
 /// Configuration for the file logger plugin.
 #[derive(Default, Debug, Serialize, Deserialize)]
-struct PluginConfig {
+pub(super) struct PluginConfig {
     /// The log format string, containing static text and variables (e.g., `$remote_addr "$request_method $uri" $status`).
     /// Supported variables include: `request_method`, `uri`, `query_string`, `http_host`, `request_time`,
     /// `http_user_agent`, `http_referer`, `remote_addr`, `remote_port`, `server_addr`, `status`,
-    /// `server_protocol`, `request_id`, `body_bytes_sent`, `error`, and custom variables via `var_<name>`.
+    /// `server_protocol`, `request_id`, `body_bytes_sent`, `error`, `upstream_addr`, `upstream_status`,
+    /// `grpc_status`, `upstream_response_time`, `upstream_connect_time`, `request_length`, `ssl_protocol`,
+    /// `ssl_cipher`, `api_group`, the latency breakdown `route_match_time`, `plugin_time`, `upstream_ttfb_time`
+    /// and `<phase>_time` per plugin phase (e.g. `request_filter_time`), and custom variables
+    /// via `var_<name>`.
     #[serde(default = "PluginConfig::default_log_format")]
     log_format: String,
 
@@ -179,6 +212,16 @@ impl LogFormat {
             "server_protocol" => 8,                  // "http/1.1" or "http/2"
             "body_bytes_sent" => 12,                 // Large numbers
             "error" => 128,                          // Error messages can be long
+            "upstream_addr" => 24,                   // Peer socket address
+            "upstream_status" => 4,                  // 3-digit status
+            "grpc_status" => 2,                      // 0-16
+            "upstream_response_time" => 8,           // Milliseconds as string
+            "upstream_connect_time" => 8,            // Milliseconds as string
+            "request_length" => 8,                   // Header + body bytes
+            "ssl_protocol" => 8,                     // e.g. "TLSv1.3"
+            "ssl_cipher" => 32,                      // Cipher suite name
+            "api_group" => 16,                       // Route's API group
+            _ if var_name.ends_with("_time") => 8,   // Milliseconds as string
             _ if var_name.starts_with("var_") => 32, // Custom variables
             _ => 16,                                 // Default for unknown variables
         }
@@ -293,7 +336,73 @@ impl LogFormat {
                     push_escaped(output, &format!("{error}"));
                 }
             }
-            _ => {}
+            "upstream_addr" => {
+                if let Some(peer) = ctx.peer.as_ref() {
+                    let _ = write!(output, "{}", peer._address);
+                }
+            }
+            "upstream_status" => {
+                if let Some(status) = ctx.upstream_info.status {
+                    let _ = write!(output, "{status}");
+                }
+            }
+            "grpc_status" => {
+                if let Some(status) = ctx.grpc_status {
+                    let _ = write!(output, "{status}");
+                }
+            }
+            "api_group" => push_escaped(
+                output,
+                ctx.route
+                    .as_ref()
+                    .and_then(|route| route.api_group())
+                    .unwrap_or_default(),
+            ),
+            "upstream_response_time" => {
+                if let Some(elapsed) = ctx.upstream_info.response_time() {
+                    let _ = write!(output, "{}", elapsed.as_millis());
+                }
+            }
+            "upstream_connect_time" => {
+                if let Some(elapsed) = ctx.upstream_info.connect_time() {
+                    let _ = write!(output, "{}", elapsed.as_millis());
+                }
+            }
+            "request_length" => {
+                let _ = write!(
+                    output,
+                    "{}",
+                    request_header_length(session.req_header()) + session.body_bytes_read()
+                );
+            }
+            "ssl_protocol" => {
+                if let Some(ssl) = session.digest().and_then(|d| d.ssl_digest.as_ref()) {
+                    push_escaped(output, &ssl.version);
+                }
+            }
+            "ssl_cipher" => {
+                if let Some(ssl) = session.digest().and_then(|d| d.ssl_digest.as_ref()) {
+                    push_escaped(output, &ssl.cipher);
+                }
+            }
+            "route_match_time" => write_millis(output, ctx.timings.route_match),
+            "plugin_time" => write_millis(output, ctx.timings.plugins()),
+            "upstream_ttfb_time" => {
+                if let Some(elapsed) = ctx.upstream_info.ttfb() {
+                    write_millis(output, elapsed);
+                }
+            }
+            _ => {
+                let phase = var.strip_suffix("_time").and_then(|phase| {
+                    ctx.timings
+                        .plugin_phases()
+                        .into_iter()
+                        .find(|(name, _)| *name == phase)
+                });
+                if let Some((_, elapsed)) = phase {
+                    write_millis(output, elapsed);
+                }
+            }
         }
     }
 }
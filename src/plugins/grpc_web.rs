@@ -1,27 +1,35 @@
 use std::sync::Arc;
 
 use async_trait::async_trait;
-use pingora::modules::http::grpc_web::GrpcWebBridge;
 use pingora_error::Result;
 use pingora_proxy::Session;
-use serde_json::Value as JsonValue;
+use serde_json::{json, Value as JsonValue};
 
-use crate::core::{ProxyContext, ProxyPlugin, ProxyResult};
+use crate::{
+    core::{ProxyContext, ProxyPlugin, ProxyResult},
+    utils::grpc_bridge::GrpcBridge,
+};
 
 pub const PLUGIN_NAME: &str = "grpc-web";
-const PRIORITY: i32 = 505;
+pub const PRIORITY: i32 = 505;
 
 /// Creates a gRPC-Web plugin instance.
-/// This plugin enables support for the gRPC-Web protocol by initializing the `GrpcWebBridge` module
-/// for each request. The configuration is currently unused, but the `cfg` parameter is provided for
+/// This plugin enables support for the gRPC-Web and Connect protocols by initializing the
+/// `GrpcBridge` module for each request. The configuration is currently unused, but the `cfg` parameter is provided for
 /// future extensibility.
 pub fn create_grpc_web_plugin(_cfg: JsonValue) -> ProxyResult<Arc<dyn ProxyPlugin>> {
     Ok(Arc::new(PluginGrpcWeb {}))
 }
 
+/// JSON Schema of the grpc-web plugin configuration; the plugin takes no options.
+pub fn schema() -> JsonValue {
+    json!({"type": ["object", "null"]})
+}
+
 /// gRPC-Web plugin implementation.
-/// This plugin integrates the `GrpcWebBridge` module to enable gRPC-Web protocol support
-/// in the proxy, allowing clients to communicate with gRPC services over HTTP/1.1 or HTTP/2.
+/// This plugin integrates the `GrpcBridge` module to enable gRPC-Web (binary and text) and
+/// Connect streaming support in the proxy, allowing browser clients to make unary and
+/// streaming calls to gRPC services over HTTP/1.1 or HTTP/2.
 #[derive(Default)]
 pub struct PluginGrpcWeb;
 
@@ -40,11 +48,11 @@ impl ProxyPlugin for PluginGrpcWeb {
         session: &mut Session,
         _ctx: &mut ProxyContext,
     ) -> Result<()> {
-        let Some(grpc) = session.downstream_modules_ctx.get_mut::<GrpcWebBridge>() else {
+        let Some(grpc) = session.downstream_modules_ctx.get_mut::<GrpcBridge>() else {
             return Ok(());
         };
 
-        // Initialize gRPC module for this request
+        // Initialize the bridge module for this request
         grpc.init();
         Ok(())
     }
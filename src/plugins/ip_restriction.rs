@@ -1,29 +1,46 @@
-use std::{net::IpAddr, sync::Arc};
+use std::{
+    net::IpAddr,
+    sync::Arc,
+    time::{Duration, Instant},
+};
 
 use async_trait::async_trait;
+use dashmap::DashMap;
 use http::StatusCode;
-use ipnetwork::IpNetwork;
+use once_cell::sync::Lazy;
 use pingora_error::Result;
 use pingora_proxy::Session;
 use serde::{Deserialize, Serialize};
-use serde_json::Value as JsonValue;
+use serde_json::{json, Value as JsonValue};
 
 use crate::core::{ProxyContext, ProxyError, ProxyPlugin, ProxyResult};
+use crate::proxy::ip_list::ip_list_fetch;
 use crate::utils::{
+    ip_set::{parse_ip_set, IpSet},
     request::{get_direct_client_ip, get_req_header_value},
     response::ResponseBuilder,
 };
 
 pub const PLUGIN_NAME: &str = "ip-restriction";
-const PRIORITY: i32 = 3000;
+pub const PRIORITY: i32 = 3000;
+
+/// Least time between two warnings about the same missing `ip_list`.
+const MISSING_LIST_WARN_INTERVAL: Duration = Duration::from_secs(60);
+
+/// When each missing `ip_list` was last warned about.
+static MISSING_LIST_WARNINGS: Lazy<DashMap<String, Instant>> = Lazy::new(DashMap::new);
 
 /// Raw configuration for IP restriction plugin (before parsing networks).
-#[derive(Deserialize)]
-struct RawConfig {
+#[derive(Serialize, Deserialize)]
+pub(super) struct RawConfig {
     #[serde(default)]
     whitelist: Vec<String>,
     #[serde(default)]
     blacklist: Vec<String>,
+    #[serde(default)]
+    whitelist_lists: Vec<String>,
+    #[serde(default)]
+    blacklist_lists: Vec<String>,
     message: Option<String>,
     #[serde(default)]
     trusted_proxies: Vec<String>,
@@ -58,47 +75,29 @@ impl TryFrom<JsonValue> for RawConfig {
 ///
 /// Supports CIDR notation for network ranges (e.g., `192.168.1.0/24`, `2001:db8::/32`).
 /// Handles proxy chains by examining X-Forwarded-For and X-Real-IP headers when configured.
-/// Whitelist takes precedence over blacklist for overlapping ranges.
+/// Whitelist takes precedence over blacklist for overlapping ranges. Networks can also
+/// come from named `ip_lists` resources, resolved per request so list updates apply
+/// without touching the plugin configuration.
 pub fn create_ip_restriction_plugin(cfg: JsonValue) -> ProxyResult<Arc<dyn ProxyPlugin>> {
     let raw_config = RawConfig::try_from(cfg)?;
 
-    let whitelist = raw_config
-        .whitelist
-        .into_iter()
-        .map(|s| {
-            s.parse::<IpNetwork>()
-                .map_err(|e| -> Box<pingora_error::Error> {
-                    ProxyError::validation_error(format!("Invalid whitelist IP network '{s}': {e}"))
-                        .into()
-                })
-        })
-        .collect::<Result<Vec<_>>>()?;
-
-    let blacklist = raw_config
-        .blacklist
-        .into_iter()
-        .map(|s| {
-            s.parse::<IpNetwork>()
-                .map_err(|e| -> Box<pingora_error::Error> {
-                    ProxyError::validation_error(format!("Invalid blacklist IP network '{s}': {e}"))
-                        .into()
-                })
-        })
-        .collect::<Result<Vec<_>>>()?;
-
-    let trusted_proxies = raw_config
-        .trusted_proxies
-        .into_iter()
-        .map(|s| {
-            s.parse::<IpNetwork>()
-                .map_err(|e| -> Box<pingora_error::Error> {
-                    ProxyError::validation_error(format!(
-                        "Invalid trusted proxy IP network '{s}': {e}"
-                    ))
-                    .into()
-                })
-        })
-        .collect::<Result<Vec<_>>>()?;
+    let whitelist =
+        parse_ip_set("whitelist", &raw_config.whitelist).map_err(ProxyError::validation_error)?;
+    let blacklist =
+        parse_ip_set("blacklist", &raw_config.blacklist).map_err(ProxyError::validation_error)?;
+    let trusted_proxies = parse_ip_set("trusted proxy", &raw_config.trusted_proxies)
+        .map_err(ProxyError::validation_error)?;
+
+    if let Some(id) = raw_config
+        .whitelist_lists
+        .iter()
+        .chain(&raw_config.blacklist_lists)
+        .find(|id| id.is_empty())
+    {
+        return Err(ProxyError::validation_error(format!(
+            "Invalid ip_list reference '{id}'"
+        )));
+    }
 
     let policy = match raw_config.forwarded_header_error_policy.as_str() {
         "direct" => ForwardedHeaderErrorPolicy::Direct,
@@ -113,6 +112,8 @@ pub fn create_ip_restriction_plugin(cfg: JsonValue) -> ProxyResult<Arc<dyn Proxy
     let config = PluginConfig {
         whitelist,
         blacklist,
+        whitelist_lists: raw_config.whitelist_lists,
+        blacklist_lists: raw_config.blacklist_lists,
         message: raw_config.message,
         trusted_proxies,
         use_forwarded_headers: raw_config.use_forwarded_headers,
@@ -122,6 +123,28 @@ pub fn create_ip_restriction_plugin(cfg: JsonValue) -> ProxyResult<Arc<dyn Proxy
     Ok(Arc::new(PluginIPRestriction { config }))
 }
 
+/// JSON Schema of the ip-restriction plugin configuration.
+pub fn schema() -> JsonValue {
+    let strings = json!({"type": "array", "items": {"type": "string"}});
+    json!({
+        "type": "object",
+        "properties": {
+            "whitelist": strings,
+            "blacklist": strings,
+            "whitelist_lists": strings,
+            "blacklist_lists": strings,
+            "message": {"type": "string"},
+            "trusted_proxies": strings,
+            "use_forwarded_headers": {"type": "boolean", "default": false},
+            "forwarded_header_error_policy": {
+                "type": "string",
+                "enum": ["direct", "deny"],
+                "default": "direct"
+            }
+        }
+    })
+}
+
 #[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
 #[serde(rename_all = "lowercase")]
 enum ForwardedHeaderErrorPolicy {
@@ -131,33 +154,79 @@ enum ForwardedHeaderErrorPolicy {
 }
 
 /// Configuration for IP-based access control.
-#[derive(Default, Debug, Serialize, Deserialize)]
-struct PluginConfig {
-    /// Allowed IP networks in CIDR notation. Empty list allows all IPs.
-    #[serde(default)]
-    whitelist: Vec<IpNetwork>,
+#[derive(Default, Debug)]
+pub(super) struct PluginConfig {
+    /// Allowed IP networks. With no whitelist networks or lists, all IPs are allowed.
+    whitelist: IpSet,
 
-    /// Denied IP networks in CIDR notation. Checked after whitelist.
-    #[serde(default)]
-    blacklist: Vec<IpNetwork>,
+    /// Denied IP networks. Checked after whitelist.
+    blacklist: IpSet,
+
+    /// IDs of `ip_lists` resources whose networks are allowed.
+    whitelist_lists: Vec<String>,
+
+    /// IDs of `ip_lists` resources whose networks are denied.
+    blacklist_lists: Vec<String>,
 
     /// Custom rejection message for blocked requests.
     message: Option<String>,
 
     /// Trusted proxy networks allowed to set forwarded headers.
     /// Used for proxy chain validation when use_forwarded_headers is true.
-    #[serde(default)]
-    trusted_proxies: Vec<IpNetwork>,
+    trusted_proxies: IpSet,
 
     /// Enable parsing of X-Forwarded-For and X-Real-IP headers from trusted proxies.
     /// Prevents IP spoofing by validating proxy chain.
-    #[serde(default)]
     use_forwarded_headers: bool,
 
-    #[serde(default)]
     forwarded_header_error_policy: ForwardedHeaderErrorPolicy,
 }
 
+/// IDs of the `ip_lists` a raw `ip-restriction` configuration references.
+pub fn named_ip_list_ids(value: &JsonValue) -> Vec<String> {
+    ["whitelist_lists", "blacklist_lists"]
+        .iter()
+        .filter_map(|field| value.get(field).and_then(JsonValue::as_array))
+        .flatten()
+        .filter_map(JsonValue::as_str)
+        .map(str::to_string)
+        .collect()
+}
+
+/// Whether `ip` is in `inline` or in any of the named lists.
+///
+/// A list missing from the runtime snapshot matches nothing, so a whitelist that
+/// only names missing lists denies everyone. Configuration validation rejects such
+/// references, so this only happens while a snapshot is being replaced.
+fn matches(inline: &IpSet, lists: &[String], ip: IpAddr) -> bool {
+    inline.contains(ip)
+        || lists.iter().any(|id| match ip_list_fetch(id) {
+            Some(list) => list.set.contains(ip),
+            None => {
+                if missing_list_warning_due(id, Instant::now()) {
+                    log::warn!("ip-restriction references missing ip_list '{id}'");
+                }
+                false
+            }
+        })
+}
+
+/// Whether to warn about missing list `id` at `now`: once per
+/// [`MISSING_LIST_WARN_INTERVAL`], not on every request.
+fn missing_list_warning_due(id: &str, now: Instant) -> bool {
+    let mut due = true;
+    MISSING_LIST_WARNINGS
+        .entry(id.to_string())
+        .and_modify(|last| {
+            due = now.duration_since(*last) >= MISSING_LIST_WARN_INTERVAL;
+            if due {
+                *last = now;
+            }
+        })
+        .or_insert(now);
+    due
+}
+
 /// IP Restriction Plugin implementation.
 pub struct PluginIPRestriction {
     config: PluginConfig,
@@ -182,24 +251,16 @@ impl ProxyPlugin for PluginIPRestriction {
             Err(ClientIpError::Other(err)) => return Err(err),
         };
 
+        let config = &self.config;
+
         // Check whitelist first
-        if !self.config.whitelist.is_empty()
-            && !self
-                .config
-                .whitelist
-                .iter()
-                .any(|network| network.contains(client_ip))
-        {
+        let has_whitelist = !config.whitelist.is_empty() || !config.whitelist_lists.is_empty();
+        if has_whitelist && !matches(&config.whitelist, &config.whitelist_lists, client_ip) {
             return self.reject_request(session).await;
         }
 
         // Check blacklist
-        if self
-            .config
-            .blacklist
-            .iter()
-            .any(|network| network.contains(client_ip))
-        {
+        if matches(&config.blacklist, &config.blacklist_lists, client_ip) {
             return self.reject_request(session).await;
         }
 
@@ -250,10 +311,7 @@ impl PluginIPRestriction {
 
     /// Check if an IP address is from a trusted proxy
     fn is_trusted_proxy(&self, ip: IpAddr) -> bool {
-        self.config
-            .trusted_proxies
-            .iter()
-            .any(|network| network.contains(ip))
+        self.config.trusted_proxies.contains(ip)
     }
 
     /// Extract the client IP by walking X-Forwarded-For from the nearest hop to the farthest.
@@ -312,6 +370,21 @@ impl PluginIPRestriction {
 mod tests {
     use super::*;
 
+    #[test]
+    fn missing_list_warnings_are_rate_limited() {
+        let now = Instant::now();
+        assert!(missing_list_warning_due("warn-test", now));
+        assert!(!missing_list_warning_due(
+            "warn-test",
+            now + Duration::from_secs(1)
+        ));
+        assert!(missing_list_warning_due("warn-other", now));
+        assert!(missing_list_warning_due(
+            "warn-test",
+            now + MISSING_LIST_WARN_INTERVAL
+        ));
+    }
+
     #[test]
     fn illegal_xff_does_not_use_x_real_ip_fallback_logic() {
         // When XFF is present but unparsable, extract_forwarded_ip returns Err(()) and
@@ -335,6 +408,32 @@ mod tests {
         assert_ne!(resolved, x_real_ip);
     }
 
+    #[test]
+    fn list_references_are_collected_and_validated() {
+        let value = serde_json::json!({
+            "whitelist": ["10.0.0.0/8"],
+            "whitelist_lists": ["office"],
+            "blacklist_lists": ["abuse", "tor"]
+        });
+        assert_eq!(named_ip_list_ids(&value), ["office", "abuse", "tor"]);
+        assert!(create_ip_restriction_plugin(value).is_ok());
+        assert!(
+            create_ip_restriction_plugin(serde_json::json!({"whitelist_lists": [""]})).is_err()
+        );
+        assert!(
+            create_ip_restriction_plugin(serde_json::json!({"blacklist": ["10.0.0.0/33"]}))
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn missing_whitelist_list_denies() {
+        let inline: IpSet = ["10.0.0.0/8".parse().unwrap()].into_iter().collect();
+        let lists = ["ip-restriction-test-missing".to_string()];
+        assert!(matches(&inline, &lists, "10.1.1.1".parse().unwrap()));
+        assert!(!matches(&inline, &lists, "192.0.2.1".parse().unwrap()));
+    }
+
     #[test]
     fn all_trusted_chain_returns_leftmost() {
         let hops = [
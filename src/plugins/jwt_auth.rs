@@ -8,7 +8,7 @@ use pingora_error::Result;
 use pingora_http::RequestHeader;
 use pingora_proxy::Session;
 use serde::{Deserialize, Serialize};
-use serde_json::Value as JsonValue;
+use serde_json::{json, Value as JsonValue};
 
 use crate::{
     core::{ProxyContext, ProxyError, ProxyPlugin, ProxyResult},
@@ -16,7 +16,7 @@ use crate::{
 };
 
 pub const PLUGIN_NAME: &str = "jwt-auth";
-const PRIORITY: i32 = 2510;
+pub const PRIORITY: i32 = 2510;
 
 /// Key for storing JWT authentication payload in the proxy context
 const JWT_AUTH_PAYLOAD_KEY: &str = "jwt-auth-payload";
@@ -44,6 +44,36 @@ pub fn create_jwt_auth_plugin(cfg: JsonValue) -> ProxyResult<Arc<dyn ProxyPlugin
     }))
 }
 
+/// JSON Schema of the jwt-auth plugin configuration.
+pub fn schema() -> JsonValue {
+    json!({
+        "type": "object",
+        "properties": {
+            "header": {"type": "string", "default": DEFAULT_AUTH_HEADER},
+            "query": {"type": "string", "default": ""},
+            "cookie": {"type": "string", "default": DEFAULT_JWT_COOKIE},
+            "hide_credentials": {"type": "boolean", "default": false},
+            "store_in_ctx": {"type": "boolean", "default": false},
+            "secret": {"type": "string"},
+            "algorithm": {
+                "type": "string",
+                "enum": [
+                    "HS256", "HS384", "HS512", "ES256", "ES384", "RS256", "RS384", "RS512",
+                    "PS256", "PS384", "PS512", "EdDSA"
+                ],
+                "default": "HS256"
+            },
+            "base64_secret": {"type": "boolean", "default": false},
+            "lifetime_grace_period": {"type": "integer", "minimum": 0, "default": 0},
+            "public_key": {"type": "string"},
+            "iss": {"type": "string"},
+            "aud": {"type": "string"},
+            "required_claims": {"type": "array", "items": {"type": "string"}},
+            "consumer_claim": {"type": "string", "default": "sub"}
+        }
+    })
+}
+
 /// Configuration for the JWT Auth plugin.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct PluginConfig {
@@ -100,6 +130,10 @@ pub struct PluginConfig {
     /// Only the registered claim names listed by `jsonwebtoken` are enforced.
     #[serde(default)]
     pub required_claims: Vec<String>,
+
+    /// String claim used as the consumer identity (default: `sub`).
+    #[serde(default = "PluginConfig::default_consumer_claim")]
+    pub consumer_claim: String,
 }
 
 impl PluginConfig {
@@ -119,6 +153,10 @@ impl PluginConfig {
         Algorithm::HS256
     }
 
+    fn default_consumer_claim() -> String {
+        "sub".to_string()
+    }
+
     fn get_decoding_key(&self) -> Result<DecodingKey, String> {
         match self.algorithm {
             Algorithm::HS256 | Algorithm::HS512 => {
@@ -268,6 +306,15 @@ impl ProxyPlugin for PluginJWTAuth {
             }
         };
 
+        if let Some(identity) = token_data
+            .claims
+            .extra
+            .get(&self.config.consumer_claim)
+            .and_then(|v| v.as_str())
+        {
+            ctx.authenticated_identity = Some(identity.to_string());
+        }
+
         if self.config.store_in_ctx {
             // Store structured payload directly for downstream plugins to use without re-parsing
             ctx.set(JWT_AUTH_PAYLOAD_KEY, token_data.claims.extra.clone());
@@ -404,6 +451,7 @@ mod tests {
             iss: None,
             aud: None,
             required_claims: vec![],
+            consumer_claim: PluginConfig::default_consumer_claim(),
         }
     }
 
@@ -1,11 +1,14 @@
-use std::sync::Arc;
+use std::{
+    sync::Arc,
+    time::{SystemTime, UNIX_EPOCH},
+};
 
 use async_trait::async_trait;
 use http::StatusCode;
 use pingora_error::Result;
 use pingora_proxy::Session;
 use serde::{Deserialize, Serialize};
-use serde_json::Value as JsonValue;
+use serde_json::{json, Value as JsonValue};
 use validator::Validate;
 
 use crate::{
@@ -16,7 +19,7 @@ use crate::{
 };
 
 pub const PLUGIN_NAME: &str = "key-auth";
-const PRIORITY: i32 = 2500;
+pub const PRIORITY: i32 = 2500;
 
 /// Default header name for API key
 const DEFAULT_API_KEY_HEADER: &str = "apikey";
@@ -26,20 +29,62 @@ const DEFAULT_API_KEY_HEADER: &str = "apikey";
 /// against configured keys. If the key is invalid or missing, it returns a `401 Unauthorized` response.
 pub fn create_key_auth_plugin(cfg: JsonValue) -> ProxyResult<Arc<dyn ProxyPlugin>> {
     let config = PluginConfig::try_from(cfg)?;
-    let key_digests = config
-        .get_valid_keys()
-        .into_iter()
-        .map(|key| secret_digest(key))
-        .collect();
-    Ok(Arc::new(PluginKeyAuth {
-        config,
-        key_digests,
-    }))
+    Ok(Arc::new(PluginKeyAuth::new(config)))
+}
+
+/// JSON Schema of the key-auth plugin configuration.
+pub fn schema() -> JsonValue {
+    json!({
+        "type": "object",
+        "properties": {
+            "header": {"type": "string", "default": DEFAULT_API_KEY_HEADER},
+            "query": {"type": "string", "default": ""},
+            "key": {"type": "string"},
+            "keys": {
+                "type": "array",
+                "items": {"anyOf": [
+                    {"type": "string"},
+                    {
+                        "type": "object",
+                        "required": ["key"],
+                        "properties": {
+                            "key": {"type": "string"},
+                            "expires_at": {"type": "integer", "minimum": 0},
+                            "consumer": {"type": "string"}
+                        },
+                        "additionalProperties": false
+                    }
+                ]}
+            },
+            "hide_credentials": {"type": "boolean", "default": false},
+            "consumer_name": {"type": "string"}
+        }
+    })
+}
+
+/// A configured key: either a bare string or a credential with expiry and consumer.
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(untagged)]
+enum KeyEntry {
+    Plain(String),
+    Credential(KeyCredential),
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct KeyCredential {
+    key: String,
+    /// Unix timestamp (seconds) after which the key is rejected.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    expires_at: Option<u64>,
+    /// Consumer identity for requests using this key; overrides `consumer_name`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    consumer: Option<String>,
 }
 
 /// Configuration for the Key Auth plugin.
 #[derive(Default, Debug, Serialize, Deserialize, Validate)]
-struct PluginConfig {
+pub(super) struct PluginConfig {
     /// HTTP header field name containing the API key (default: `apikey`).
     #[serde(default = "PluginConfig::default_header")]
     header: String,
@@ -55,16 +100,22 @@ struct PluginConfig {
     #[serde(skip_serializing_if = "Option::is_none")]
     key: Option<String>,
 
-    /// Multiple API keys to match against. Supports key rotation.
+    /// Multiple API keys to match against. Supports key rotation: entries are either plain
+    /// strings or `{key, expires_at, consumer}` objects, so an old key can be given an expiry
+    /// while its replacement is rolled out.
     /// Takes precedence over single `key` if both are provided.
     /// No `length(min = 1)` here — the struct-level validator ensures
     /// that at least one of `key` or `keys` is non-empty.
     #[serde(default)]
-    keys: Vec<String>,
+    keys: Vec<KeyEntry>,
 
     /// Whether to remove the API key from headers or query parameters after validation (default: false).
     #[serde(default = "PluginConfig::default_hide_credentials")]
     hide_credentials: bool,
+
+    /// Consumer identity recorded for requests authenticated by this plugin instance.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    consumer_name: Option<String>,
 }
 
 impl PluginConfig {
@@ -81,17 +132,29 @@ impl PluginConfig {
     }
 
     /// Get all valid keys (combines single key and multiple keys)
-    fn get_valid_keys(&self) -> Vec<&String> {
+    fn get_valid_keys(&self) -> Vec<KeyEntryRef<'_>> {
         if !self.keys.is_empty() {
-            self.keys.iter().collect()
+            self.keys
+                .iter()
+                .map(|entry| match entry {
+                    KeyEntry::Plain(key) => KeyEntryRef::Plain(key),
+                    KeyEntry::Credential(cred) => KeyEntryRef::Credential(cred),
+                })
+                .collect()
         } else if let Some(ref key) = self.key {
-            vec![key]
+            vec![KeyEntryRef::Plain(key)]
         } else {
             vec![]
         }
     }
 }
 
+/// Borrowed view over `key` and `keys` entries.
+enum KeyEntryRef<'a> {
+    Plain(&'a str),
+    Credential(&'a KeyCredential),
+}
+
 impl TryFrom<JsonValue> for PluginConfig {
     type Error = ProxyError;
 
@@ -103,11 +166,20 @@ impl TryFrom<JsonValue> for PluginConfig {
         config.validate()?;
 
         // Custom validation: at least one of `key` or `keys` must be non-empty.
-        if config.get_valid_keys().is_empty() {
+        let keys = config.get_valid_keys();
+        if keys.is_empty() {
             return Err(ProxyError::validation_error(
                 "key-auth plugin requires at least one of 'key' or 'keys' to be non-empty",
             ));
         }
+        if keys
+            .iter()
+            .any(|entry| matches!(entry, KeyEntryRef::Credential(cred) if cred.key.is_empty()))
+        {
+            return Err(ProxyError::validation_error(
+                "key-auth credential 'key' must be non-empty",
+            ));
+        }
 
         Ok(config)
     }
@@ -128,7 +200,20 @@ enum KeySource {
 /// or integration with a consumer management system instead of fixed key matching.
 pub struct PluginKeyAuth {
     config: PluginConfig,
-    key_digests: Vec<[u8; 32]>,
+    keys: Vec<StoredKey>,
+}
+
+/// Digest of a configured key with its rotation metadata.
+struct StoredKey {
+    digest: [u8; 32],
+    expires_at: Option<u64>,
+    consumer: Option<String>,
+}
+
+impl StoredKey {
+    fn is_expired(&self, now: u64) -> bool {
+        self.expires_at.is_some_and(|expires_at| now >= expires_at)
+    }
 }
 
 #[async_trait]
@@ -159,7 +244,10 @@ impl ProxyPlugin for PluginKeyAuth {
         }
 
         // Validate key using constant-time comparison
-        if value.is_empty() || !self.is_valid_key(value) {
+        let matched = (!value.is_empty())
+            .then(|| self.find_key(value, unix_now()))
+            .flatten();
+        let Some(key) = matched else {
             ResponseBuilder::send_proxy_error(
                 session,
                 StatusCode::UNAUTHORIZED,
@@ -168,6 +256,10 @@ impl ProxyPlugin for PluginKeyAuth {
             )
             .await?;
             return Ok(true);
+        };
+
+        if let Some(name) = key.consumer.as_ref().or(self.config.consumer_name.as_ref()) {
+            ctx.authenticated_identity = Some(name.clone());
         }
 
         // Hide credentials if configured
@@ -193,20 +285,54 @@ impl ProxyPlugin for PluginKeyAuth {
 }
 
 impl PluginKeyAuth {
-    /// Validate the provided key against configured keys using constant-time comparison
-    fn is_valid_key(&self, provided_key: &str) -> bool {
+    fn new(config: PluginConfig) -> Self {
+        let keys = config
+            .get_valid_keys()
+            .into_iter()
+            .map(|entry| match entry {
+                KeyEntryRef::Plain(key) => StoredKey {
+                    digest: secret_digest(key),
+                    expires_at: None,
+                    consumer: None,
+                },
+                KeyEntryRef::Credential(cred) => StoredKey {
+                    digest: secret_digest(&cred.key),
+                    expires_at: cred.expires_at,
+                    consumer: cred.consumer.clone(),
+                },
+            })
+            .collect();
+        Self { config, keys }
+    }
+
+    /// Find the unexpired configured key matching the provided one using constant-time
+    /// comparison. An expired key is treated like an unknown one, so a duplicate entry
+    /// that is still valid is found.
+    fn find_key(&self, provided_key: &str, now: u64) -> Option<&StoredKey> {
         let provided_digest = secret_digest(provided_key);
-        let mut matched = 0u8;
+        let mut matched = None;
 
         // Compare every configured digest to avoid leaking which rotation key matched.
-        for valid_key_digest in &self.key_digests {
-            matched |= u8::from(constant_time_digest_eq(&provided_digest, valid_key_digest));
+        for key in &self.keys {
+            if constant_time_digest_eq(&provided_digest, &key.digest)
+                && !key.is_expired(now)
+                && matched.is_none()
+            {
+                matched = Some(key);
+            }
         }
 
-        matched != 0
+        matched
     }
 }
 
+fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default()
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -224,4 +350,51 @@ mod tests {
                 .unwrap();
         assert_eq!(config.query, "apikey");
     }
+
+    #[test]
+    fn rotated_keys_carry_expiry_and_consumer() {
+        let config = PluginConfig::try_from(serde_json::json!({
+            "consumer_name": "default",
+            "keys": [
+                "plain",
+                {"key": "old", "expires_at": 1_000, "consumer": "legacy"},
+                {"key": "new", "consumer": "app"}
+            ]
+        }))
+        .unwrap();
+        let plugin = PluginKeyAuth::new(config);
+
+        assert!(plugin.find_key("missing", 999).is_none());
+        assert!(plugin.find_key("old", 1_000).is_none());
+        assert_eq!(
+            plugin.find_key("old", 999).unwrap().consumer.as_deref(),
+            Some("legacy")
+        );
+        assert_eq!(
+            plugin.find_key("new", 1_000).unwrap().consumer.as_deref(),
+            Some("app")
+        );
+        assert!(plugin.find_key("plain", 1_000).unwrap().consumer.is_none());
+    }
+
+    #[test]
+    fn expired_duplicates_do_not_hide_a_valid_entry() {
+        let config = PluginConfig::try_from(serde_json::json!({
+            "keys": [
+                {"key": "shared", "expires_at": 1_000, "consumer": "before"},
+                {"key": "shared", "consumer": "after"}
+            ]
+        }))
+        .unwrap();
+        let plugin = PluginKeyAuth::new(config);
+
+        let consumer = |now| plugin.find_key("shared", now).unwrap().consumer.as_deref();
+        assert_eq!(consumer(999), Some("before"));
+        assert_eq!(consumer(1_000), Some("after"));
+    }
+
+    #[test]
+    fn credential_keys_must_be_non_empty() {
+        assert!(PluginConfig::try_from(serde_json::json!({ "keys": [{"key": ""}] })).is_err());
+    }
 }
@@ -0,0 +1,215 @@
+use std::{
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use async_trait::async_trait;
+use bytes::Bytes;
+use pingora_error::Result;
+use pingora_proxy::Session;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value as JsonValue};
+use validator::Validate;
+
+use crate::core::{shared_dict::SharedDict, ProxyContext, ProxyError, ProxyPlugin, ProxyResult};
+use crate::utils::request::get_direct_client_ip;
+
+pub const PLUGIN_NAME: &str = "limit-bandwidth";
+pub const PRIORITY: i32 = 980;
+
+/// Most buckets kept; those idle longest are dropped first.
+const MAX_BUCKETS: usize = 10_000;
+
+/// A bucket idle this long is full again and expires.
+const IDLE_BUCKET: Duration = Duration::from_secs(60);
+
+/// Creates a limit-bandwidth plugin that paces response bodies.
+pub fn create_limit_bandwidth_plugin(cfg: JsonValue) -> ProxyResult<Arc<dyn ProxyPlugin>> {
+    let config = PluginConfig::try_from(cfg)?;
+    Ok(Arc::new(PluginLimitBandwidth {
+        config,
+        buckets: SharedDict::tracked(PLUGIN_NAME, MAX_BUCKETS),
+    }))
+}
+
+/// JSON Schema of the limit-bandwidth plugin configuration.
+pub fn schema() -> JsonValue {
+    json!({
+        "type": "object",
+        "required": ["rate"],
+        "properties": {
+            "rate": {"type": "integer", "minimum": 1},
+            "burst": {"type": "integer", "minimum": 0},
+            "key_type": {"type": "string", "enum": ["route", "consumer"], "default": "route"}
+        }
+    })
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum KeyType {
+    /// One budget shared by every request of the route.
+    #[default]
+    Route,
+    /// One budget per consumer on the route; anonymous requests are keyed by client IP.
+    Consumer,
+}
+
+#[derive(Debug, Serialize, Deserialize, Validate)]
+pub(super) struct PluginConfig {
+    /// Sustained response body rate in bytes per second.
+    #[validate(range(min = 1))]
+    rate: u64,
+    /// Bytes that may be sent at once above `rate`; defaults to one second of `rate`.
+    #[serde(default)]
+    burst: Option<u64>,
+    #[serde(default)]
+    key_type: KeyType,
+}
+
+impl TryFrom<JsonValue> for PluginConfig {
+    type Error = ProxyError;
+
+    fn try_from(value: JsonValue) -> Result<Self, Self::Error> {
+        let config: PluginConfig = serde_json::from_value(value).map_err(|e| {
+            ProxyError::serialization_error("Invalid limit bandwidth plugin config", e)
+        })?;
+
+        config.validate()?;
+
+        Ok(config)
+    }
+}
+
+/// Token bucket that goes into debt: a chunk is always sent, and the debt is the pause
+/// before the next one, so concurrent responses on one key share the rate.
+struct Bucket {
+    tokens: f64,
+    updated: Instant,
+}
+
+impl Bucket {
+    fn new(burst: f64, now: Instant) -> Self {
+        Self {
+            tokens: burst,
+            updated: now,
+        }
+    }
+
+    /// Spend `bytes` and return how long to wait before sending them.
+    fn take(&mut self, bytes: usize, rate: f64, burst: f64, now: Instant) -> Duration {
+        let refill = now.saturating_duration_since(self.updated).as_secs_f64() * rate;
+        self.tokens = (self.tokens + refill).min(burst) - bytes as f64;
+        self.updated = now;
+        if self.tokens >= 0.0 {
+            Duration::ZERO
+        } else {
+            Duration::from_secs_f64(-self.tokens / rate)
+        }
+    }
+}
+
+pub struct PluginLimitBandwidth {
+    config: PluginConfig,
+    buckets: Arc<SharedDict<Bucket>>,
+}
+
+impl PluginLimitBandwidth {
+    fn key(&self, session: &Session, ctx: &ProxyContext) -> String {
+        let route = ctx.route.as_ref().map_or("", |route| route.id());
+        match self.config.key_type {
+            KeyType::Route => route.to_string(),
+            KeyType::Consumer => match &ctx.authenticated_identity {
+                Some(consumer) => format!("{route}/consumer/{consumer}"),
+                None => format!(
+                    "{route}/ip/{}",
+                    get_direct_client_ip(session).map_or_else(String::new, |ip| ip.to_string())
+                ),
+            },
+        }
+    }
+
+    fn delay(&self, key: String, bytes: usize, now: Instant) -> Duration {
+        let rate = self.config.rate as f64;
+        let burst = self.config.burst.unwrap_or(self.config.rate) as f64;
+        self.buckets.update(
+            key,
+            Some(IDLE_BUCKET),
+            || Bucket::new(burst, now),
+            |bucket| bucket.take(bytes, rate, burst, now),
+        )
+    }
+}
+
+#[async_trait]
+impl ProxyPlugin for PluginLimitBandwidth {
+    fn name(&self) -> &str {
+        PLUGIN_NAME
+    }
+
+    fn priority(&self) -> i32 {
+        PRIORITY
+    }
+
+    fn has_response_body_filter(&self) -> bool {
+        true
+    }
+
+    fn response_body_filter(
+        &self,
+        session: &mut Session,
+        body: &mut Option<Bytes>,
+        _end_of_stream: bool,
+        ctx: &mut ProxyContext,
+    ) -> Result<()> {
+        let Some(chunk) = body.as_ref().filter(|chunk| !chunk.is_empty()) else {
+            return Ok(());
+        };
+        let delay = self.delay(self.key(session, ctx), chunk.len(), Instant::now());
+        if !delay.is_zero() {
+            ctx.response_body_delay = ctx.response_body_delay.max(Some(delay));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn chunks_beyond_the_burst_are_paced_at_the_rate() {
+        let plugin = PluginLimitBandwidth {
+            config: PluginConfig::try_from(json!({"rate": 1000, "burst": 500})).unwrap(),
+            buckets: SharedDict::tracked(PLUGIN_NAME, MAX_BUCKETS),
+        };
+        let start = Instant::now();
+        let delay = |bytes, after_ms| {
+            plugin.delay(
+                "r1".to_string(),
+                bytes,
+                start + Duration::from_millis(after_ms),
+            )
+        };
+
+        assert_eq!(delay(500, 0), Duration::ZERO);
+        assert_eq!(delay(250, 0), Duration::from_millis(250));
+        // 250 bytes of debt are repaid after 250 ms; the next 100 wait 100 ms.
+        assert_eq!(delay(100, 250), Duration::from_millis(100));
+        // Idle time refills at most the burst.
+        assert_eq!(delay(500, 10_000), Duration::ZERO);
+        assert_eq!(
+            plugin.delay("r2".to_string(), 500, start),
+            Duration::ZERO,
+            "keys have separate budgets"
+        );
+    }
+
+    #[test]
+    fn config_requires_a_positive_rate() {
+        assert!(PluginConfig::try_from(json!({"rate": 0})).is_err());
+        let config = PluginConfig::try_from(json!({"rate": 10, "key_type": "consumer"})).unwrap();
+        assert_eq!(config.key_type, KeyType::Consumer);
+        assert!(config.burst.is_none());
+    }
+}
@@ -0,0 +1,358 @@
+use std::{borrow::Cow, sync::Arc, time::Duration};
+
+use async_trait::async_trait;
+use dashmap::DashMap;
+use http::StatusCode;
+use once_cell::sync::Lazy;
+use pingora_error::{Error, Result};
+use pingora_proxy::Session;
+use prometheus::{register_int_counter_vec, IntCounterVec};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value as JsonValue};
+use validator::{Validate, ValidationError};
+
+use crate::{
+    config::UpstreamHashOn,
+    core::{
+        memory::{self, MemoryConsumer, ENTRY_OVERHEAD},
+        ProxyContext, ProxyError, ProxyPlugin, ProxyResult,
+    },
+    utils::{request::request_selector_key, response::ResponseBuilder},
+};
+
+pub const PLUGIN_NAME: &str = "limit-conn";
+pub const PRIORITY: i32 = 1003;
+
+/// Context key holding the slots acquired by every `limit-conn` instance of a request.
+const SLOTS_CTX_KEY: &str = "limit_conn_slots";
+
+static LIMIT_CONN_REQUESTS: Lazy<IntCounterVec> = Lazy::new(|| {
+    register_int_counter_vec!(
+        "pingsix_limit_conn_requests_total",
+        "Concurrency-limit decisions by outcome",
+        &["outcome"]
+    )
+    .expect("limit-conn metric registration must succeed")
+});
+
+/// Creates a Limit Conn plugin instance with the given configuration.
+/// This plugin caps the number of concurrent in-flight requests per key. Requests above
+/// `conn` but within `conn + burst` are delayed; the rest are rejected (default: `503`).
+/// Slots are released in the logging phase.
+pub fn create_limit_conn_plugin(cfg: JsonValue) -> ProxyResult<Arc<dyn ProxyPlugin>> {
+    let config = PluginConfig::try_from(cfg)?;
+    let tracker = Arc::new(ConnTracker::default());
+    memory::register(memory::RATE_LIMIT, Arc::downgrade(&tracker) as _);
+    Ok(Arc::new(PluginLimitConn { config, tracker }))
+}
+
+/// JSON Schema of the limit-conn plugin configuration.
+pub fn schema() -> JsonValue {
+    json!({
+        "type": "object",
+        "required": ["conn"],
+        "properties": {
+            "conn": {"type": "integer", "minimum": 1},
+            "burst": {"type": "integer", "minimum": 0, "default": 0},
+            "default_conn_delay": {"type": "number", "minimum": 0, "maximum": 60, "default": 0},
+            "only_use_default_delay": {"type": "boolean", "default": false},
+            "key_type": {"type": "string", "enum": ["vars", "head", "cookie"], "default": "vars"},
+            "key": {"type": "string", "minLength": 1, "default": "remote_addr"},
+            "rejected_code": {"type": "integer", "minimum": 400, "maximum": 599, "default": 503},
+            "rejected_msg": {"type": "string"}
+        }
+    })
+}
+
+/// Configuration for the Limit Conn plugin.
+#[derive(Default, Debug, Serialize, Deserialize, Validate)]
+pub(super) struct PluginConfig {
+    /// Concurrent requests allowed per key without delay.
+    #[validate(range(min = 1))]
+    conn: u32,
+
+    /// Additional concurrent requests admitted with a delay.
+    #[serde(default)]
+    burst: u32,
+
+    /// Delay in seconds applied per `conn` requests above the limit.
+    #[serde(default)]
+    #[validate(range(min = 0.0, max = 60.0))]
+    default_conn_delay: f64,
+
+    /// Delay requests in the burst by exactly `default_conn_delay` instead of scaling it.
+    #[serde(default)]
+    only_use_default_delay: bool,
+
+    /// Type of key to limit on (e.g., `vars`, `head`, `cookie`).
+    #[serde(default)]
+    key_type: UpstreamHashOn,
+
+    /// Key to limit on. Besides request variables, `vars` accepts `consumer_name`,
+    /// `route_id` and `var_<name>` (a context value set by an earlier plugin).
+    #[validate(custom(function = "validate_key"))]
+    #[serde(default = "PluginConfig::default_key")]
+    key: String,
+
+    /// HTTP status code for rejected requests (default: 503).
+    #[serde(default = "PluginConfig::default_rejected_code")]
+    #[validate(range(min = 400, max = 599))]
+    rejected_code: u16,
+
+    /// Optional custom message for rejected requests.
+    #[serde(default)]
+    rejected_msg: Option<String>,
+}
+
+impl PluginConfig {
+    fn default_key() -> String {
+        "remote_addr".to_string()
+    }
+
+    fn default_rejected_code() -> u16 {
+        503
+    }
+
+    /// Requests admitted concurrently per key, delayed ones included.
+    fn capacity(&self) -> usize {
+        self.conn as usize + self.burst as usize
+    }
+
+    /// Delay for the `current`-th concurrent request of a key, `None` within `conn`.
+    fn delay_for(&self, current: usize) -> Option<Duration> {
+        let conn = self.conn as usize;
+        if current <= conn || self.default_conn_delay <= 0.0 {
+            return None;
+        }
+        let units = if self.only_use_default_delay {
+            1
+        } else {
+            (current - 1) / conn
+        };
+        Some(Duration::from_secs_f64(
+            self.default_conn_delay * units as f64,
+        ))
+    }
+}
+
+impl TryFrom<JsonValue> for PluginConfig {
+    type Error = ProxyError;
+
+    fn try_from(value: JsonValue) -> Result<Self, Self::Error> {
+        let config: PluginConfig = serde_json::from_value(value)
+            .map_err(|e| ProxyError::serialization_error("Invalid limit conn plugin config", e))?;
+
+        config.validate()?;
+
+        Ok(config)
+    }
+}
+
+fn validate_key(key: &str) -> Result<(), ValidationError> {
+    if key.is_empty() {
+        return Err(ValidationError::new("key cannot be empty"));
+    }
+    if key.contains(|c: char| !c.is_alphanumeric() && c != '-' && c != '_' && c != '.') {
+        return Err(ValidationError::new(
+            "key contains invalid characters (only alphanumeric, -, _, . allowed)",
+        ));
+    }
+    Ok(())
+}
+
+/// In-flight request counts per key. Keys are dropped when their count reaches zero.
+#[derive(Default)]
+struct ConnTracker {
+    counts: DashMap<String, usize>,
+}
+
+impl ConnTracker {
+    /// Take a slot for `key`; returns the new in-flight count, or `None` at `capacity`.
+    fn acquire(self: &Arc<Self>, key: &str, capacity: usize) -> Option<(usize, ConnSlot)> {
+        let mut count = self.counts.entry(key.to_string()).or_insert(0);
+        if *count >= capacity {
+            return None;
+        }
+        *count += 1;
+        let current = *count;
+        drop(count);
+        Some((
+            current,
+            ConnSlot {
+                tracker: self.clone(),
+                key: key.to_string(),
+            },
+        ))
+    }
+
+    fn release(&self, key: &str) {
+        if let Some(mut count) = self.counts.get_mut(key) {
+            *count = count.saturating_sub(1);
+        }
+        self.counts.remove_if(key, |_, count| *count == 0);
+    }
+
+    #[cfg(test)]
+    fn in_flight(&self, key: &str) -> usize {
+        self.counts.get(key).map_or(0, |count| *count)
+    }
+}
+
+// In-flight counts are bounded by concurrency and cannot be dropped, so they are only
+// accounted for.
+#[async_trait]
+impl MemoryConsumer for ConnTracker {
+    fn usage(&self) -> usize {
+        self.counts
+            .iter()
+            .map(|entry| entry.key().len() + ENTRY_OVERHEAD)
+            .sum()
+    }
+
+    async fn shrink(&self, _target: usize) {}
+}
+
+/// A held concurrency slot, released on drop so aborted requests cannot leak it.
+struct ConnSlot {
+    tracker: Arc<ConnTracker>,
+    key: String,
+}
+
+impl Drop for ConnSlot {
+    fn drop(&mut self) {
+        self.tracker.release(&self.key);
+    }
+}
+
+/// Limit Conn plugin implementation.
+///
+/// Counts are per plugin instance: a route-level plugin limits that route, a global
+/// rule limits every request it applies to. Like `limit-count`, limits are per process.
+pub struct PluginLimitConn {
+    config: PluginConfig,
+    tracker: Arc<ConnTracker>,
+}
+
+#[async_trait]
+impl ProxyPlugin for PluginLimitConn {
+    fn name(&self) -> &str {
+        PLUGIN_NAME
+    }
+
+    fn priority(&self) -> i32 {
+        PRIORITY
+    }
+
+    async fn request_filter(&self, session: &mut Session, ctx: &mut ProxyContext) -> Result<bool> {
+        let key = self.resolve_key(session, ctx).into_owned();
+        if key.is_empty() {
+            log::debug!("limit-conn key '{}' is empty, skipping", self.config.key);
+            return Ok(false);
+        }
+
+        let Some((current, slot)) = self.tracker.acquire(&key, self.config.capacity()) else {
+            LIMIT_CONN_REQUESTS.with_label_values(&["rejected"]).inc();
+            session.set_keepalive(None);
+            ResponseBuilder::send_proxy_error(
+                session,
+                StatusCode::from_u16(self.config.rejected_code)
+                    .unwrap_or(StatusCode::SERVICE_UNAVAILABLE),
+                self.config.rejected_msg.as_deref(),
+                None,
+            )
+            .await?;
+            return Ok(true);
+        };
+
+        match ctx.get_mut::<Vec<ConnSlot>>(SLOTS_CTX_KEY) {
+            Some(slots) => slots.push(slot),
+            None => ctx.set(SLOTS_CTX_KEY, vec![slot]),
+        }
+
+        if let Some(delay) = self.config.delay_for(current) {
+            LIMIT_CONN_REQUESTS.with_label_values(&["delayed"]).inc();
+            tokio::time::sleep(delay).await;
+        } else {
+            LIMIT_CONN_REQUESTS.with_label_values(&["allowed"]).inc();
+        }
+        Ok(false)
+    }
+
+    async fn logging(&self, _session: &mut Session, _e: Option<&Error>, ctx: &mut ProxyContext) {
+        // Dropping the slots releases them; later limit-conn instances find nothing left.
+        if let Some(vars) = ctx.vars.as_mut() {
+            vars.remove(SLOTS_CTX_KEY);
+        }
+    }
+}
+
+impl PluginLimitConn {
+    fn resolve_key<'a>(&self, session: &'a mut Session, ctx: &'a ProxyContext) -> Cow<'a, str> {
+        if self.config.key_type == UpstreamHashOn::VARS {
+            match self.config.key.as_str() {
+                "consumer_name" => {
+                    return Cow::Borrowed(ctx.authenticated_identity.as_deref().unwrap_or(""))
+                }
+                "route_id" => return Cow::Borrowed(ctx.route.as_ref().map_or("", |r| r.id())),
+                key => {
+                    if let Some(name) = key.strip_prefix("var_") {
+                        return Cow::Borrowed(ctx.get_str(name).unwrap_or_default());
+                    }
+                }
+            }
+        }
+        request_selector_key(session, &self.config.key_type, self.config.key.as_str())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn config_requires_positive_conn() {
+        assert!(PluginConfig::try_from(json!({"conn": 0})).is_err());
+        let config = PluginConfig::try_from(json!({"conn": 2, "burst": 1})).unwrap();
+        assert_eq!(config.key, "remote_addr");
+        assert_eq!(config.capacity(), 3);
+        assert_eq!(config.rejected_code, 503);
+    }
+
+    #[test]
+    fn slots_are_bounded_and_released_on_drop() {
+        let tracker = Arc::new(ConnTracker::default());
+        let (first, slot_a) = tracker.acquire("k", 2).unwrap();
+        let (second, slot_b) = tracker.acquire("k", 2).unwrap();
+        assert_eq!((first, second), (1, 2));
+        assert!(tracker.acquire("k", 2).is_none());
+
+        drop(slot_a);
+        assert_eq!(tracker.in_flight("k"), 1);
+        drop(slot_b);
+        assert_eq!(tracker.in_flight("k"), 0);
+        assert!(tracker.counts.is_empty());
+    }
+
+    #[test]
+    fn burst_requests_are_delayed() {
+        let config = PluginConfig::try_from(json!({
+            "conn": 2,
+            "burst": 4,
+            "default_conn_delay": 0.1
+        }))
+        .unwrap();
+        assert_eq!(config.delay_for(2), None);
+        assert_eq!(config.delay_for(3), Some(Duration::from_millis(100)));
+        assert_eq!(config.delay_for(5), Some(Duration::from_millis(200)));
+
+        let fixed = PluginConfig::try_from(json!({
+            "conn": 2,
+            "burst": 4,
+            "default_conn_delay": 0.1,
+            "only_use_default_delay": true
+        }))
+        .unwrap();
+        assert_eq!(fixed.delay_for(5), Some(Duration::from_millis(100)));
+    }
+}
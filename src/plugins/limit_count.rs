@@ -1,4 +1,8 @@
-use std::{sync::Arc, time::Duration};
+use std::{
+    borrow::Cow,
+    sync::Arc,
+    time::{Duration, Instant},
+};
 
 use once_cell::sync::Lazy;
 use prometheus::{register_int_counter_vec, IntCounterVec};
@@ -6,20 +10,25 @@ use prometheus::{register_int_counter_vec, IntCounterVec};
 use async_trait::async_trait;
 use http::StatusCode;
 use pingora_error::Result;
-use pingora_limits::rate::Rate;
 use pingora_proxy::Session;
 use serde::{Deserialize, Serialize};
-use serde_json::Value as JsonValue;
+use serde_json::{json, Value as JsonValue};
 use validator::{Validate, ValidationError};
 
 use crate::{
     config::UpstreamHashOn,
-    core::{ProxyContext, ProxyError, ProxyPlugin, ProxyResult},
+    core::{
+        shared_dict::{shared_dict, SharedDict},
+        ProxyContext, ProxyError, ProxyPlugin, ProxyResult,
+    },
     utils::{request::request_selector_key, response::ResponseBuilder},
 };
 
 pub const PLUGIN_NAME: &str = "limit-count";
-const PRIORITY: i32 = 1002;
+pub const PRIORITY: i32 = 1002;
+
+/// Room for the counters of distinct keys, per instance and for all groups together.
+const MAX_KEYS: usize = 65536;
 
 static RATE_LIMIT_REQUESTS: Lazy<IntCounterVec> = Lazy::new(|| {
     register_int_counter_vec!(
@@ -37,14 +46,55 @@ static RATE_LIMIT_REQUESTS: Lazy<IntCounterVec> = Lazy::new(|| {
 pub fn create_limit_count_plugin(cfg: JsonValue) -> ProxyResult<Arc<dyn ProxyPlugin>> {
     let config = PluginConfig::try_from(cfg)?;
 
-    let rate = Rate::new(Duration::from_secs(config.time_window as u64));
+    let (counters, prefix) = counters(&config)?;
+
+    Ok(Arc::new(PluginRateLimit {
+        config,
+        counters,
+        prefix,
+    }))
+}
+
+/// The counters of `config`'s instance and the prefix of its keys in them. Instances of
+/// a group share the counters of the `limit-count` namespace, which also survive route
+/// reloads.
+fn counters(config: &PluginConfig) -> ProxyResult<(Arc<SharedDict<Window>>, String)> {
+    let Some(group) = &config.group else {
+        return Ok((SharedDict::tracked(PLUGIN_NAME, MAX_KEYS), String::new()));
+    };
+    Ok((
+        shared_dict(PLUGIN_NAME, MAX_KEYS)?,
+        format!("{group}/{}/", config.time_window),
+    ))
+}
 
-    Ok(Arc::new(PluginRateLimit { config, rate }))
+/// JSON Schema of the limit-count plugin configuration.
+pub fn schema() -> JsonValue {
+    json!({
+        "type": "object",
+        "required": ["time_window", "count"],
+        "properties": {
+            "key_type": {"type": "string", "enum": ["vars", "head", "cookie"], "default": "vars"},
+            "key": {"type": "string", "minLength": 1, "default": "remote_addr"},
+            "time_window": {"type": "integer", "minimum": 1, "maximum": 86400},
+            "count": {"type": "integer", "minimum": 1},
+            "rejected_code": {"type": "integer", "minimum": 400, "maximum": 599, "default": 503},
+            "rejected_msg": {"type": "string"},
+            "show_limit_quota_header": {"type": "boolean", "default": true},
+            "key_missing_policy": {
+                "type": "string",
+                "enum": ["allow", "deny", "default"],
+                "default": "allow"
+            },
+            "scope": {"type": "string", "enum": ["local", "cluster"], "default": "local"},
+            "group": {"type": "string", "minLength": 1, "maxLength": 64}
+        }
+    })
 }
 
 /// Configuration for the Limit Count plugin.
 #[derive(Default, Debug, Serialize, Deserialize, Validate)]
-struct PluginConfig {
+pub(super) struct PluginConfig {
     /// Type of key to use for rate limiting (e.g., `IP`, `HEADER`, `VARS`).
     /// Defaults to `vars` for APISIX compatibility.
     #[serde(default)]
@@ -74,7 +124,8 @@ struct PluginConfig {
     #[serde(default)]
     rejected_msg: Option<String>,
 
-    /// Whether to include `X-Rate-Limit-*` headers in the response (default: true).
+    /// Whether to include `X-RateLimit-*`, `RateLimit-*` and the legacy `X-Rate-Limit-*`
+    /// headers in the response (default: true).
     #[serde(default = "PluginConfig::default_show_limit_quota_header")]
     show_limit_quota_header: bool,
 
@@ -85,6 +136,12 @@ struct PluginConfig {
     /// Rate limiting is process-local in this release; cluster scope needs a shared backend.
     #[serde(default)]
     scope: Scope,
+
+    /// Quota shared by every instance with the same group and `time_window`, so several
+    /// routes consume from one counter; each still applies its own `count`.
+    #[serde(default)]
+    #[validate(length(min = 1, max = 64))]
+    group: Option<String>,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone, Copy, Default)]
@@ -163,7 +220,23 @@ fn validate_key(key: &str) -> Result<(), ValidationError> {
 /// the effective limit is approximately `config.count * replica_count`.
 pub struct PluginRateLimit {
     config: PluginConfig,
-    rate: Rate,
+    counters: Arc<SharedDict<Window>>,
+    prefix: String,
+}
+
+/// Requests counted for one key in its current window.
+struct Window {
+    started: Instant,
+    used: isize,
+}
+
+/// Outcome of counting one request against the quota.
+struct Quota {
+    limited: bool,
+    used: isize,
+    remaining: isize,
+    /// Seconds until the current window ends.
+    reset: u64,
 }
 
 #[async_trait]
@@ -177,7 +250,7 @@ impl ProxyPlugin for PluginRateLimit {
     }
 
     async fn request_filter(&self, session: &mut Session, ctx: &mut ProxyContext) -> Result<bool> {
-        let key = request_selector_key(session, &self.config.key_type, self.config.key.as_str());
+        let key = self.resolve_key(session, ctx);
 
         // Handle empty key based on policy
         if key.is_empty() {
@@ -185,28 +258,8 @@ impl ProxyPlugin for PluginRateLimit {
         }
 
         // Check rate limit without forcing borrowed selector keys to allocate.
-        let (is_limited, current_count, remaining) = self.check_rate_limit(key.as_ref());
-
-        if is_limited {
-            RATE_LIMIT_REQUESTS
-                .with_label_values(&["rejected", "local"])
-                .inc();
-            return self
-                .handle_rate_limit(session, current_count, remaining)
-                .await;
-        }
-        RATE_LIMIT_REQUESTS
-            .with_label_values(&["allowed", "local"])
-            .inc();
-
-        // Store rate limit info in context for potential use by other plugins
-        if self.config.show_limit_quota_header {
-            ctx.set("rate_limit_limit", self.config.count.to_string());
-            ctx.set("rate_limit_remaining", remaining.to_string());
-            ctx.set("rate_limit_reset", self.config.time_window.to_string());
-        }
-
-        Ok(false)
+        let quota = self.check_rate_limit(key.as_ref());
+        self.apply_quota(session, ctx, quota).await
     }
 
     async fn response_filter(
@@ -216,27 +269,24 @@ impl ProxyPlugin for PluginRateLimit {
         ctx: &mut ProxyContext,
     ) -> Result<()> {
         if self.config.show_limit_quota_header {
-            for (context_key, header) in [
-                ("rate_limit_limit", "X-Rate-Limit-Limit"),
-                ("rate_limit_remaining", "X-Rate-Limit-Remaining"),
-                ("rate_limit_reset", "X-Rate-Limit-Reset"),
-            ] {
-                if let Some(value) = ctx.get_str(context_key) {
-                    upstream_response.insert_header(header, value)?;
-                }
-            }
-            // Only expose the implementation scope when this plugin recorded
-            // quota data for the request. A short-circuited request may still
-            // reach this filter without ever being rate-limited.
-            if ctx.get_str("rate_limit_limit").is_some() {
-                upstream_response.insert_header("X-RateLimit-Scope", "local")?;
-            }
+            insert_quota_headers(upstream_response, ctx)?;
         }
         Ok(())
     }
 }
 
 impl PluginRateLimit {
+    /// Resolve the rate limit key; `var_<name>` reads a value set by an earlier plugin
+    /// (e.g. `var_ua_class` from ua-restriction) from the request context.
+    fn resolve_key<'a>(&self, session: &'a mut Session, ctx: &ProxyContext) -> Cow<'a, str> {
+        if self.config.key_type == UpstreamHashOn::VARS {
+            if let Some(name) = self.config.key.strip_prefix("var_") {
+                return Cow::Owned(ctx.get_str(name).unwrap_or_default().to_string());
+            }
+        }
+        request_selector_key(session, &self.config.key_type, self.config.key.as_str())
+    }
+
     /// Handle requests with missing keys based on configured policy
     async fn handle_missing_key(
         &self,
@@ -265,52 +315,77 @@ impl PluginRateLimit {
             }
             KeyMissingPolicy::Default => {
                 // Use a default key for all requests with missing keys
-                let (is_limited, current_count, remaining) =
-                    self.check_rate_limit("_default_rate_limit_key");
-
-                if is_limited {
-                    RATE_LIMIT_REQUESTS
-                        .with_label_values(&["rejected", "local"])
-                        .inc();
-                    self.handle_rate_limit(session, current_count, remaining)
-                        .await
-                } else {
-                    RATE_LIMIT_REQUESTS
-                        .with_label_values(&["allowed", "local"])
-                        .inc();
-                    if self.config.show_limit_quota_header {
-                        ctx.set("rate_limit_limit", self.config.count.to_string());
-                        ctx.set("rate_limit_remaining", remaining.to_string());
-                        ctx.set("rate_limit_reset", self.config.time_window.to_string());
-                    }
-                    Ok(false)
-                }
+                let quota = self.check_rate_limit("_default_rate_limit_key");
+                self.apply_quota(session, ctx, quota).await
             }
         }
     }
 
     /// Check if the request exceeds the rate limit and return detailed information
-    fn check_rate_limit(&self, key: &str) -> (bool, isize, isize) {
-        // Rate::observe requires a sized hash key. Passing &&str keeps this allocation-free.
-        let current_count = self.rate.observe(&key, 1);
-        let remaining = (self.config.count as isize) - current_count;
-        let is_limited = current_count > self.config.count as isize;
-
-        (is_limited, current_count, remaining.max(0))
+    fn check_rate_limit(&self, key: &str) -> Quota {
+        let window = Duration::from_secs(self.config.time_window as u64);
+        // The window restarts with the first request after it ends, not on a fixed clock.
+        let (used, started) = self.counters.update(
+            format!("{}{key}", self.prefix),
+            Some(window),
+            || Window {
+                started: Instant::now(),
+                used: 0,
+            },
+            |counter| {
+                if counter.started.elapsed() >= window {
+                    counter.started = Instant::now();
+                    counter.used = 0;
+                }
+                counter.used += 1;
+                (counter.used, counter.started)
+            },
+        );
+        let remaining = (self.config.count as isize) - used;
+        let reset = window
+            .saturating_sub(started.elapsed())
+            .as_secs_f64()
+            .ceil() as u64;
+
+        Quota {
+            limited: used > self.config.count as isize,
+            used,
+            remaining: remaining.max(0),
+            reset: reset.max(1),
+        }
     }
 
-    /// Handle rate-limited requests by sending a rejection response with detailed headers
-    async fn handle_rate_limit(
+    /// Reject a request over quota, or record the quota for the response headers.
+    async fn apply_quota(
         &self,
         session: &mut Session,
-        current_count: isize,
-        remaining: isize,
+        ctx: &mut ProxyContext,
+        quota: Quota,
     ) -> Result<bool> {
+        if quota.limited {
+            RATE_LIMIT_REQUESTS
+                .with_label_values(&["rejected", "local"])
+                .inc();
+            return self.handle_rate_limit(session, &quota).await;
+        }
+        RATE_LIMIT_REQUESTS
+            .with_label_values(&["allowed", "local"])
+            .inc();
+
+        // Store rate limit info in context for potential use by other plugins
+        if self.config.show_limit_quota_header {
+            ctx.set("rate_limit_limit", self.config.count.to_string());
+            ctx.set("rate_limit_remaining", quota.remaining.to_string());
+            ctx.set("rate_limit_reset", quota.reset.to_string());
+        }
+        Ok(false)
+    }
+
+    /// Handle rate-limited requests by sending a rejection response with detailed headers
+    async fn handle_rate_limit(&self, session: &mut Session, quota: &Quota) -> Result<bool> {
         let headers = build_rate_limit_headers(
             self.config.count,
-            remaining,
-            self.config.time_window,
-            current_count,
+            quota,
             self.config.show_limit_quota_header,
         );
 
@@ -335,27 +410,67 @@ impl PluginRateLimit {
     }
 }
 
+/// Quota values recorded in the context and the headers each is sent in. The
+/// `X-Rate-Limit-*` names predate the others and are kept for existing clients.
+const QUOTA_HEADERS: [(&str, [&str; 3]); 3] = [
+    (
+        "rate_limit_limit",
+        ["X-RateLimit-Limit", "RateLimit-Limit", "X-Rate-Limit-Limit"],
+    ),
+    (
+        "rate_limit_remaining",
+        [
+            "X-RateLimit-Remaining",
+            "RateLimit-Remaining",
+            "X-Rate-Limit-Remaining",
+        ],
+    ),
+    (
+        "rate_limit_reset",
+        ["X-RateLimit-Reset", "RateLimit-Reset", "X-Rate-Limit-Reset"],
+    ),
+];
+
+/// Add the quota recorded for an allowed request to its response.
+fn insert_quota_headers(resp: &mut pingora_http::ResponseHeader, ctx: &ProxyContext) -> Result<()> {
+    for (context_key, headers) in QUOTA_HEADERS {
+        if let Some(value) = ctx.get_str(context_key) {
+            for header in headers {
+                resp.insert_header(header, value)?;
+            }
+        }
+    }
+    // Only expose the implementation scope when this plugin recorded
+    // quota data for the request. A short-circuited request may still
+    // reach this filter without ever being rate-limited.
+    if ctx.get_str("rate_limit_limit").is_some() {
+        resp.insert_header("X-RateLimit-Scope", "local")?;
+    }
+    Ok(())
+}
+
 /// Build the rate-limit response headers for a rejected request.
 ///
 /// `X-RateLimit-Scope: local` advertises that this limiter is per-instance/in-memory,
 /// so operators know the effective limit scales with replica count.
-fn build_rate_limit_headers(
-    count: u32,
-    remaining: isize,
-    time_window: u32,
-    current_count: isize,
-    show: bool,
-) -> Vec<(&'static str, String)> {
+fn build_rate_limit_headers(count: u32, quota: &Quota, show: bool) -> Vec<(&'static str, String)> {
     if !show {
         return Vec::new();
     }
 
     vec![
+        ("X-RateLimit-Limit", count.to_string()),
+        ("X-RateLimit-Remaining", quota.remaining.to_string()),
+        ("X-RateLimit-Reset", quota.reset.to_string()),
+        ("X-RateLimit-Used", quota.used.to_string()),
+        ("RateLimit-Limit", count.to_string()),
+        ("RateLimit-Remaining", quota.remaining.to_string()),
+        ("RateLimit-Reset", quota.reset.to_string()),
         ("X-Rate-Limit-Limit", count.to_string()),
-        ("X-Rate-Limit-Remaining", remaining.to_string()),
-        ("X-Rate-Limit-Reset", time_window.to_string()),
-        ("X-Rate-Limit-Used", current_count.to_string()),
-        ("Retry-After", time_window.to_string()),
+        ("X-Rate-Limit-Remaining", quota.remaining.to_string()),
+        ("X-Rate-Limit-Reset", quota.reset.to_string()),
+        ("X-Rate-Limit-Used", quota.used.to_string()),
+        ("Retry-After", quota.reset.to_string()),
         ("X-RateLimit-Scope", "local".to_string()),
     ]
 }
@@ -364,9 +479,18 @@ fn build_rate_limit_headers(
 mod tests {
     use super::*;
 
+    fn quota() -> Quota {
+        Quota {
+            limited: true,
+            used: 11,
+            remaining: 0,
+            reset: 42,
+        }
+    }
+
     #[test]
     fn rate_limit_response_includes_local_scope() {
-        let headers = build_rate_limit_headers(10, 3, 60, 7, true);
+        let headers = build_rate_limit_headers(10, &quota(), true);
         let scope = headers
             .iter()
             .find(|(name, _)| *name == "X-RateLimit-Scope")
@@ -376,7 +500,91 @@ mod tests {
 
     #[test]
     fn build_rate_limit_headers_respects_show_flag() {
-        let headers = build_rate_limit_headers(10, 3, 60, 7, false);
+        let headers = build_rate_limit_headers(10, &quota(), false);
         assert!(headers.is_empty());
     }
+
+    #[test]
+    fn rejection_reports_time_until_reset() {
+        let headers = build_rate_limit_headers(10, &quota(), true);
+        let header = |name| {
+            headers
+                .iter()
+                .find(|(header, _)| *header == name)
+                .map(|(_, value)| value.as_str())
+        };
+        assert_eq!(header("RateLimit-Reset"), Some("42"));
+        assert_eq!(header("Retry-After"), Some("42"));
+        assert_eq!(header("X-RateLimit-Remaining"), Some("0"));
+        assert_eq!(header("X-Rate-Limit-Remaining"), Some("0"));
+        assert_eq!(header("X-Rate-Limit-Used"), Some("11"));
+    }
+
+    #[test]
+    fn allowed_responses_carry_all_three_header_families() {
+        let mut ctx = ProxyContext::default();
+        ctx.set("rate_limit_limit", "10".to_string());
+        ctx.set("rate_limit_remaining", "7".to_string());
+        ctx.set("rate_limit_reset", "42".to_string());
+        let mut resp = pingora_http::ResponseHeader::build(200, None).unwrap();
+        insert_quota_headers(&mut resp, &ctx).unwrap();
+
+        let header = |name: &str| resp.headers.get(name).and_then(|v| v.to_str().ok());
+        for prefix in ["X-RateLimit-", "RateLimit-", "X-Rate-Limit-"] {
+            assert_eq!(header(&format!("{prefix}Limit")), Some("10"), "{prefix}");
+            assert_eq!(header(&format!("{prefix}Remaining")), Some("7"), "{prefix}");
+            assert_eq!(header(&format!("{prefix}Reset")), Some("42"), "{prefix}");
+        }
+        assert_eq!(header("X-RateLimit-Scope"), Some("local"));
+    }
+
+    fn config(group: &str, count: u32) -> PluginConfig {
+        PluginConfig::try_from(json!({
+            "time_window": 60,
+            "count": count,
+            "group": group,
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn routes_in_a_group_share_one_quota() {
+        let plugin = |config: PluginConfig| {
+            let (counters, prefix) = counters(&config).unwrap();
+            PluginRateLimit {
+                config,
+                counters,
+                prefix,
+            }
+        };
+        let a = plugin(config("quota-group-test", 2));
+        let b = plugin(config("quota-group-test", 2));
+        let other = plugin(config("quota-group-other", 2));
+
+        assert!(!a.check_rate_limit("client").limited);
+        let quota = b.check_rate_limit("client");
+        assert_eq!((quota.limited, quota.remaining), (false, 0));
+        assert!((1..=60).contains(&quota.reset));
+        assert!(a.check_rate_limit("client").limited);
+        assert!(!other.check_rate_limit("client").limited);
+    }
+
+    #[test]
+    fn instances_count_each_key_on_their_own() {
+        let plugin = || {
+            let config = PluginConfig::try_from(json!({"time_window": 60, "count": 1})).unwrap();
+            let (counters, prefix) = counters(&config).unwrap();
+            PluginRateLimit {
+                config,
+                counters,
+                prefix,
+            }
+        };
+        let (a, b) = (plugin(), plugin());
+        assert!(!a.check_rate_limit("client").limited);
+        assert!(a.check_rate_limit("client").limited);
+        assert!(!a.check_rate_limit("other").limited);
+        assert!(!b.check_rate_limit("client").limited);
+        assert_eq!(a.counters.len(), 2);
+    }
 }
@@ -0,0 +1,317 @@
+use std::{
+    collections::HashMap,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use async_trait::async_trait;
+use bytes::Bytes;
+use http::{header, HeaderName, HeaderValue, StatusCode};
+use once_cell::sync::Lazy;
+use pingora_error::Result;
+use pingora_http::ResponseHeader;
+use pingora_proxy::Session;
+use rand::{distributions::Alphanumeric, Rng};
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Map, Value as JsonValue};
+use validator::{Validate, ValidationError};
+
+use crate::core::{ProxyContext, ProxyError, ProxyPlugin, ProxyResult};
+
+pub const PLUGIN_NAME: &str = "mocking";
+pub const PRIORITY: i32 = 10900;
+
+const MOCK_HEADER: &str = "x-mock-by";
+
+/// `{{name}}` placeholders of `response_example`.
+static PLACEHOLDER_RE: Lazy<Regex> =
+    Lazy::new(|| Regex::new(r"\{\{\s*(seq|uuid|random_int|timestamp)\s*\}\}").unwrap());
+
+/// Creates a mocking plugin that answers requests without contacting the upstream.
+pub fn create_mocking_plugin(cfg: JsonValue) -> ProxyResult<Arc<dyn ProxyPlugin>> {
+    let config = PluginConfig::try_from(cfg)?;
+    Ok(Arc::new(PluginMocking {
+        config,
+        seq: AtomicU64::new(0),
+    }))
+}
+
+/// JSON Schema of the mocking plugin configuration.
+pub fn schema() -> JsonValue {
+    json!({
+        "type": "object",
+        "properties": {
+            "response_status": {"type": "integer", "minimum": 100, "maximum": 599, "default": 200},
+            "content_type": {"type": "string", "default": "application/json;charset=utf8"},
+            "response_headers": {"type": "object", "additionalProperties": {"type": "string"}},
+            "response_example": {"type": "string"},
+            "response_schema": {"type": "object"},
+            "delay": {"type": "number", "minimum": 0, "default": 0},
+            "with_mock_header": {"type": "boolean", "default": true}
+        }
+    })
+}
+
+#[derive(Debug, Serialize, Deserialize, Validate)]
+#[validate(schema(function = "PluginConfig::validate_body"))]
+pub(super) struct PluginConfig {
+    #[serde(default = "PluginConfig::default_response_status")]
+    #[validate(range(min = 100, max = 599))]
+    response_status: u16,
+    #[serde(default = "PluginConfig::default_content_type")]
+    content_type: String,
+    #[serde(default)]
+    response_headers: HashMap<String, String>,
+    /// Body template; `{{seq}}`, `{{uuid}}`, `{{random_int}}` and `{{timestamp}}` are
+    /// replaced per response.
+    #[serde(default)]
+    response_example: Option<String>,
+    /// JSON Schema a random body is generated from on every request.
+    #[serde(default)]
+    response_schema: Option<JsonValue>,
+    /// Seconds to wait before responding.
+    #[serde(default)]
+    #[validate(range(min = 0.0))]
+    delay: f64,
+    /// Mark responses with `x-mock-by: pingsix`.
+    #[serde(default = "PluginConfig::default_with_mock_header")]
+    with_mock_header: bool,
+}
+
+impl PluginConfig {
+    fn default_response_status() -> u16 {
+        200
+    }
+
+    fn default_content_type() -> String {
+        "application/json;charset=utf8".to_string()
+    }
+
+    fn default_with_mock_header() -> bool {
+        true
+    }
+
+    fn validate_body(&self) -> Result<(), ValidationError> {
+        if self.response_example.is_some() && self.response_schema.is_some() {
+            return Err(ValidationError::new(
+                "set only one of response_example and response_schema",
+            ));
+        }
+        if HeaderValue::try_from(self.content_type.as_str()).is_err()
+            || self.response_headers.iter().any(|(name, value)| {
+                HeaderName::try_from(name.as_str()).is_err()
+                    || HeaderValue::try_from(value.as_str()).is_err()
+            })
+        {
+            return Err(ValidationError::new("invalid response header"));
+        }
+        Ok(())
+    }
+}
+
+impl TryFrom<JsonValue> for PluginConfig {
+    type Error = ProxyError;
+
+    fn try_from(value: JsonValue) -> Result<Self, Self::Error> {
+        let config: PluginConfig = serde_json::from_value(value)
+            .map_err(|e| ProxyError::serialization_error("Invalid mocking plugin config", e))?;
+
+        config.validate()?;
+
+        Ok(config)
+    }
+}
+
+/// A random value matching the `type`, `enum`, bounds and `properties`/`items` of a
+/// JSON Schema. `example` values are used as-is.
+fn generate(schema: &JsonValue, rng: &mut impl Rng) -> JsonValue {
+    if let Some(example) = schema.get("example") {
+        return example.clone();
+    }
+    if let Some(options) = schema.get("enum").and_then(JsonValue::as_array) {
+        if !options.is_empty() {
+            return options[rng.gen_range(0..options.len())].clone();
+        }
+    }
+    let kind = match schema.get("type") {
+        Some(JsonValue::String(kind)) => kind.as_str(),
+        Some(JsonValue::Array(kinds)) => kinds.first().and_then(JsonValue::as_str).unwrap_or(""),
+        _ if schema.get("properties").is_some() => "object",
+        _ if schema.get("items").is_some() => "array",
+        _ => "",
+    };
+    let bound = |name: &str, default: f64| {
+        schema
+            .get(name)
+            .and_then(JsonValue::as_f64)
+            .unwrap_or(default)
+    };
+    match kind {
+        "object" => {
+            let properties = schema.get("properties").and_then(JsonValue::as_object);
+            let object: Map<String, JsonValue> = properties
+                .into_iter()
+                .flatten()
+                .map(|(name, property)| (name.clone(), generate(property, rng)))
+                .collect();
+            JsonValue::Object(object)
+        }
+        "array" => {
+            let min = bound("minItems", 1.0) as usize;
+            let max = (bound("maxItems", 3.0) as usize).max(min);
+            let items = schema.get("items").cloned().unwrap_or(json!({}));
+            (0..rng.gen_range(min..=max))
+                .map(|_| generate(&items, rng))
+                .collect()
+        }
+        "integer" => {
+            let min = bound("minimum", 0.0) as i64;
+            let max = (bound("maximum", 100.0) as i64).max(min);
+            json!(rng.gen_range(min..=max))
+        }
+        "number" => {
+            let min = bound("minimum", 0.0);
+            let max = bound("maximum", 100.0).max(min);
+            json!(min + rng.gen::<f64>() * (max - min))
+        }
+        "boolean" => json!(rng.gen::<bool>()),
+        "null" => JsonValue::Null,
+        _ => {
+            let min = bound("minLength", 1.0) as usize;
+            let max = (bound("maxLength", 8.0) as usize).max(min);
+            let len = rng.gen_range(min..=max);
+            json!(rng
+                .sample_iter(&Alphanumeric)
+                .take(len)
+                .map(char::from)
+                .collect::<String>())
+        }
+    }
+}
+
+pub struct PluginMocking {
+    config: PluginConfig,
+    /// Source of `{{seq}}`, counting responses of this plugin instance from 1.
+    seq: AtomicU64,
+}
+
+impl PluginMocking {
+    fn body(&self) -> String {
+        if let Some(schema) = &self.config.response_schema {
+            return generate(schema, &mut rand::thread_rng()).to_string();
+        }
+        let Some(example) = &self.config.response_example else {
+            return String::new();
+        };
+        PLACEHOLDER_RE
+            .replace_all(example, |caps: &regex::Captures| match &caps[1] {
+                "seq" => (self.seq.fetch_add(1, Ordering::Relaxed) + 1).to_string(),
+                "uuid" => uuid::Uuid::new_v4().to_string(),
+                "random_int" => rand::thread_rng().gen_range(0..1_000_000).to_string(),
+                _ => SystemTime::now()
+                    .duration_since(UNIX_EPOCH)
+                    .map(|d| d.as_secs())
+                    .unwrap_or_default()
+                    .to_string(),
+            })
+            .into_owned()
+    }
+}
+
+#[async_trait]
+impl ProxyPlugin for PluginMocking {
+    fn name(&self) -> &str {
+        PLUGIN_NAME
+    }
+
+    fn priority(&self) -> i32 {
+        PRIORITY
+    }
+
+    async fn request_filter(&self, session: &mut Session, _ctx: &mut ProxyContext) -> Result<bool> {
+        if self.config.delay > 0.0 {
+            tokio::time::sleep(Duration::from_secs_f64(self.config.delay)).await;
+        }
+
+        let body = self.body();
+        let status = StatusCode::from_u16(self.config.response_status).unwrap_or(StatusCode::OK);
+        let mut resp = ResponseHeader::build(status, None)?;
+        resp.insert_header(header::CONTENT_TYPE, self.config.content_type.as_str())?;
+        resp.insert_header(header::CONTENT_LENGTH, body.len().to_string())?;
+        for (name, value) in &self.config.response_headers {
+            resp.insert_header(name.clone(), value.as_str())?;
+        }
+        if self.config.with_mock_header {
+            resp.insert_header(MOCK_HEADER, "pingsix")?;
+        }
+
+        session
+            .write_response_header(Box::new(resp), body.is_empty())
+            .await?;
+        if !body.is_empty() {
+            session
+                .write_response_body(Some(Bytes::from(body)), true)
+                .await?;
+        }
+        Ok(true)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn plugin(cfg: JsonValue) -> PluginMocking {
+        PluginMocking {
+            config: PluginConfig::try_from(cfg).unwrap(),
+            seq: AtomicU64::new(0),
+        }
+    }
+
+    #[test]
+    fn example_placeholders_are_filled_per_response() {
+        let mock = plugin(json!({"response_example": r#"{"id": {{seq}}, "ref": "{{ uuid }}"}"#}));
+        let first: JsonValue = serde_json::from_str(&mock.body()).unwrap();
+        let second: JsonValue = serde_json::from_str(&mock.body()).unwrap();
+        assert_eq!(
+            (first["id"].as_u64(), second["id"].as_u64()),
+            (Some(1), Some(2))
+        );
+        assert_eq!(first["ref"].as_str().unwrap().len(), 36);
+        assert_ne!(first["ref"], second["ref"]);
+        assert_eq!(plugin(json!({})).body(), "");
+    }
+
+    #[test]
+    fn schema_bodies_follow_the_schema() {
+        let mock = plugin(json!({"response_schema": {
+            "type": "object",
+            "properties": {
+                "count": {"type": "integer", "minimum": 5, "maximum": 9},
+                "state": {"enum": ["open", "closed"]},
+                "tags": {"type": "array", "items": {"type": "string"}, "maxItems": 2},
+                "owner": {"type": "string", "example": "ops"}
+            }
+        }}));
+        let body: JsonValue = serde_json::from_str(&mock.body()).unwrap();
+        assert!((5..=9).contains(&body["count"].as_i64().unwrap()));
+        assert!(body["state"] == "open" || body["state"] == "closed");
+        assert!((1..=2).contains(&body["tags"].as_array().unwrap().len()));
+        assert_eq!(body["owner"], "ops");
+    }
+
+    #[test]
+    fn config_validation() {
+        assert!(PluginConfig::try_from(json!({"response_status": 99})).is_err());
+        assert!(PluginConfig::try_from(json!({
+            "response_example": "{}",
+            "response_schema": {"type": "object"}
+        }))
+        .is_err());
+        assert!(PluginConfig::try_from(json!({"response_headers": {"bad header": "x"}})).is_err());
+    }
+}
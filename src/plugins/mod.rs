@@ -1,9 +1,18 @@
+pub mod ai_proxy;
 pub mod basic_auth;
+pub mod batch_requests;
+pub mod body_transformer;
 pub mod brotli;
 pub mod cache;
+pub mod client_control;
+pub mod consumer_restriction;
 pub mod cors;
 pub mod csrf;
+pub mod debug_headers;
+pub mod debug_routing;
 pub mod echo;
+pub mod error_page;
+pub mod fallback;
 pub mod fault_injection;
 pub mod file_logger;
 pub mod grpc_web;
@@ -11,82 +20,121 @@ pub mod gzip;
 pub mod ip_restriction;
 pub mod jwt_auth;
 pub mod key_auth;
+pub mod limit_bandwidth;
+pub mod limit_conn;
 pub mod limit_count;
+pub mod mocking;
 pub mod prometheus;
 pub mod proxy_rewrite;
 pub mod redirect;
+pub mod request_decompression;
 pub mod request_id;
+pub mod request_validation;
 pub mod response_rewrite;
+pub mod security_headers;
 pub mod traffic_split;
+pub mod ua_restriction;
+pub mod uri_blocker;
+pub mod waf;
+pub mod workflow;
+pub mod zstd;
 
 use std::{collections::HashMap, sync::Arc};
 
 use once_cell::sync::Lazy;
-use serde_json::Value as JsonValue;
+use serde_json::{json, Value as JsonValue};
 
 use crate::{
-    core::{PluginCreateFn, ProxyError, ProxyPlugin, ProxyResult},
+    core::{metrics, PluginCreateFn, ProxyError, ProxyPlugin, ProxyResult},
     proxy::upstream::{PreparedUpstreams, ProxyUpstream},
+    utils::json_schema,
 };
 
-/// Global registry mapping plugin names to their factory functions.
+/// Registry entry of a plugin type.
+struct PluginEntry {
+    priority: i32,
+    builder: PluginCreateFn,
+    schema: fn() -> JsonValue,
+    /// Parses a configuration and serializes it back, serde defaults included; `None`
+    /// for plugins whose configuration is not serializable.
+    #[cfg(test)]
+    config: Option<fn(JsonValue) -> serde_json::Result<JsonValue>>,
+}
+
+/// Registry entry of `$module`, whose configuration type is `PluginConfig` unless named.
+/// `_` marks a configuration that cannot be serialized back.
+macro_rules! plugin_entry {
+    ($module:ident, $builder:ident) => {
+        plugin_entry!($module, $builder, PluginConfig)
+    };
+    ($module:ident, $builder:ident, _) => {
+        plugin_entry!(@entry $module, $builder, None)
+    };
+    ($module:ident, $builder:ident, $config:ident) => {
+        plugin_entry!(@entry $module, $builder, Some(tests::round_trip::<$module::$config>))
+    };
+    (@entry $module:ident, $builder:ident, $config:expr) => {
+        (
+            $module::PLUGIN_NAME,
+            PluginEntry {
+                priority: $module::PRIORITY,
+                builder: $module::$builder,
+                schema: $module::schema,
+                #[cfg(test)]
+                config: $config,
+            },
+        )
+    };
+}
+
+/// Global registry mapping plugin names to their priority, factory function and
+/// configuration schema.
 ///
 /// Plugins are listed in descending priority order (higher priority = executes first).
 /// The priority value determines execution order in the plugin chain.
-static PLUGIN_BUILDER_REGISTRY: Lazy<HashMap<&'static str, PluginCreateFn>> = Lazy::new(|| {
-    let arr: Vec<(&str, PluginCreateFn)> = vec![
-        (
-            request_id::PLUGIN_NAME,
-            request_id::create_request_id_plugin,
-        ), // 12015
-        (
-            fault_injection::PLUGIN_NAME,
-            fault_injection::create_fault_injection_plugin,
-        ), // 11000
-        (cors::PLUGIN_NAME, cors::create_cors_plugin), // 4000
-        (
-            ip_restriction::PLUGIN_NAME,
-            ip_restriction::create_ip_restriction_plugin,
-        ), // 3000
-        (csrf::PLUGIN_NAME, csrf::create_csrf_plugin), // 2980
-        (
-            basic_auth::PLUGIN_NAME,
-            basic_auth::create_basic_auth_plugin,
-        ), // 2520
-        (jwt_auth::PLUGIN_NAME, jwt_auth::create_jwt_auth_plugin), // 2510
-        (key_auth::PLUGIN_NAME, key_auth::create_key_auth_plugin), // 2500
-        (cache::PLUGIN_NAME, cache::create_cache_plugin), // 1085
-        (
-            proxy_rewrite::PLUGIN_NAME,
-            proxy_rewrite::create_proxy_rewrite_plugin,
-        ), // 1008
-        (brotli::PLUGIN_NAME, brotli::create_brotli_plugin), // 996
-        (gzip::PLUGIN_NAME, gzip::create_gzip_plugin), // 995
-        (
-            traffic_split::PLUGIN_NAME,
-            traffic_split::create_traffic_split_plugin,
-        ), // 966
-        (redirect::PLUGIN_NAME, redirect::create_redirect_plugin), // 900
-        (
-            response_rewrite::PLUGIN_NAME,
-            response_rewrite::create_response_rewrite_plugin,
-        ), // 899
-        (grpc_web::PLUGIN_NAME, grpc_web::create_grpc_web_plugin), // 505
-        (
-            limit_count::PLUGIN_NAME,
-            limit_count::create_limit_count_plugin,
-        ), // 503
-        (
-            prometheus::PLUGIN_NAME,
-            prometheus::create_prometheus_plugin,
-        ), // 500
-        (echo::PLUGIN_NAME, echo::create_echo_plugin), // 412
-        (
-            file_logger::PLUGIN_NAME,
-            file_logger::create_file_logger_plugin,
-        ), // 399
-    ];
-    arr.into_iter().collect()
+static PLUGIN_BUILDER_REGISTRY: Lazy<HashMap<&'static str, PluginEntry>> = Lazy::new(|| {
+    HashMap::from([
+        plugin_entry!(batch_requests, create_batch_requests_plugin),
+        plugin_entry!(client_control, create_client_control_plugin),
+        plugin_entry!(request_id, create_request_id_plugin),
+        plugin_entry!(debug_headers, create_debug_headers_plugin),
+        plugin_entry!(error_page, create_error_page_plugin),
+        plugin_entry!(fault_injection, create_fault_injection_plugin),
+        plugin_entry!(mocking, create_mocking_plugin),
+        plugin_entry!(cors, create_cors_plugin),
+        plugin_entry!(ip_restriction, create_ip_restriction_plugin, RawConfig),
+        plugin_entry!(ua_restriction, create_ua_restriction_plugin),
+        plugin_entry!(request_decompression, create_request_decompression_plugin),
+        plugin_entry!(request_validation, create_request_validation_plugin),
+        plugin_entry!(csrf, create_csrf_plugin),
+        plugin_entry!(waf, create_waf_plugin),
+        plugin_entry!(uri_blocker, create_uri_blocker_plugin),
+        plugin_entry!(basic_auth, create_basic_auth_plugin),
+        plugin_entry!(jwt_auth, create_jwt_auth_plugin),
+        plugin_entry!(key_auth, create_key_auth_plugin),
+        plugin_entry!(consumer_restriction, create_consumer_restriction_plugin),
+        plugin_entry!(cache, create_cache_plugin),
+        plugin_entry!(ai_proxy, create_ai_proxy_plugin),
+        plugin_entry!(body_transformer, create_body_transformer_plugin),
+        plugin_entry!(proxy_rewrite, create_proxy_rewrite_plugin),
+        plugin_entry!(fallback, create_fallback_plugin),
+        plugin_entry!(workflow, create_workflow_plugin, _),
+        plugin_entry!(limit_conn, create_limit_conn_plugin),
+        plugin_entry!(limit_count, create_limit_count_plugin),
+        plugin_entry!(limit_bandwidth, create_limit_bandwidth_plugin),
+        plugin_entry!(zstd, create_zstd_plugin),
+        plugin_entry!(brotli, create_brotli_plugin),
+        plugin_entry!(gzip, create_gzip_plugin),
+        plugin_entry!(traffic_split, create_traffic_split_plugin),
+        plugin_entry!(debug_routing, create_debug_routing_plugin),
+        plugin_entry!(redirect, create_redirect_plugin),
+        plugin_entry!(response_rewrite, create_response_rewrite_plugin),
+        plugin_entry!(security_headers, create_security_headers_plugin),
+        plugin_entry!(grpc_web, create_grpc_web_plugin, _),
+        plugin_entry!(prometheus, create_prometheus_plugin, PrometheusConfig),
+        plugin_entry!(echo, create_echo_plugin),
+        plugin_entry!(file_logger, create_file_logger_plugin),
+    ])
 });
 
 /// Creates plugin instances from configuration using a factory pattern.
@@ -111,16 +159,283 @@ pub(crate) fn build_plugin_with_upstreams(
     owner: &str,
 ) -> ProxyResult<Arc<dyn ProxyPlugin>> {
     if name == traffic_split::PLUGIN_NAME {
+        validate_plugin_config(name, &cfg).inspect_err(|_| record_build_failure(name))?;
         return traffic_split::create_traffic_split_plugin_with_upstreams(
             cfg, upstreams, prepared, owner,
-        );
+        )
+        .inspect_err(|_| record_build_failure(name));
+    }
+    if name == workflow::PLUGIN_NAME {
+        validate_plugin_config(name, &cfg).inspect_err(|_| record_build_failure(name))?;
+        return workflow::create_workflow_plugin_with_upstreams(cfg, upstreams)
+            .inspect_err(|_| record_build_failure(name));
+    }
+    if name == ai_proxy::PLUGIN_NAME {
+        validate_plugin_config(name, &cfg).inspect_err(|_| record_build_failure(name))?;
+        return ai_proxy::create_ai_proxy_plugin_with_upstreams(cfg, prepared, owner)
+            .inspect_err(|_| record_build_failure(name));
+    }
+    if name == fallback::PLUGIN_NAME {
+        validate_plugin_config(name, &cfg).inspect_err(|_| record_build_failure(name))?;
+        return fallback::create_fallback_plugin_with_upstreams(cfg, upstreams)
+            .inspect_err(|_| record_build_failure(name));
     }
     build_plugin(name, cfg)
 }
 
+/// Whether a plugin named `name` is registered.
+pub fn is_registered(name: &str) -> bool {
+    PLUGIN_BUILDER_REGISTRY.contains_key(name)
+}
+
+/// `(name, priority)` of every registered plugin in execution order.
+pub fn registered_plugins() -> Vec<(&'static str, i32)> {
+    let mut plugins: Vec<_> = PLUGIN_BUILDER_REGISTRY
+        .iter()
+        .map(|(name, entry)| (*name, entry.priority))
+        .collect();
+    plugins.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(b.0)));
+    plugins
+}
+
+/// JSON Schema of the configuration of the plugin named `name`.
+pub fn plugin_schema(name: &str) -> Option<JsonValue> {
+    PLUGIN_BUILDER_REGISTRY
+        .get(name)
+        .map(|entry| (entry.schema)())
+}
+
+/// Check `cfg` against the schema of the plugin named `name`, including the shared
+/// `_meta` settings.
+pub fn validate_plugin_config(name: &str, cfg: &JsonValue) -> ProxyResult<()> {
+    let schema = plugin_schema(name)
+        .ok_or_else(|| ProxyError::Plugin(format!("Unknown plugin type: {name}")))?;
+    let meta_schema = json!({
+        "properties": {
+            "_meta": {
+                "type": "object",
+                "properties": {"disable": {"type": "boolean"}}
+            }
+        }
+    });
+    json_schema::validate(&schema, cfg)
+        .and_then(|_| json_schema::validate(&meta_schema, cfg))
+        .map_err(|e| ProxyError::validation_error(format!("Invalid {name} plugin config: {e}")))
+}
+
+/// Whether a plugin config is switched off with `_meta.disable`. Disabled plugins are
+/// still validated and built, but left out of the plugin executors.
+pub fn is_disabled(cfg: &JsonValue) -> bool {
+    cfg.pointer("/_meta/disable")
+        .and_then(JsonValue::as_bool)
+        .unwrap_or(false)
+}
+
 pub fn build_plugin(name: &str, cfg: JsonValue) -> ProxyResult<Arc<dyn ProxyPlugin>> {
-    let builder = PLUGIN_BUILDER_REGISTRY
+    let entry = PLUGIN_BUILDER_REGISTRY
         .get(name)
         .ok_or_else(|| ProxyError::Plugin(format!("Unknown plugin type: {name}")))?;
-    builder(cfg)
+    validate_plugin_config(name, &cfg)
+        .and_then(|_| (entry.builder)(cfg))
+        .inspect_err(|_| record_build_failure(name))
+}
+
+fn record_build_failure(name: &str) {
+    metrics::PLUGIN_BUILD_FAILURES
+        .with_label_values(&[name])
+        .inc();
+}
+
+#[cfg(test)]
+mod tests {
+    use serde::{de::DeserializeOwned, Serialize};
+
+    use super::*;
+    use crate::proxy::upstream::{ai_proxy_key, discovery::prepare_static_upstream};
+
+    pub(super) fn round_trip<T: DeserializeOwned + Serialize>(
+        cfg: JsonValue,
+    ) -> serde_json::Result<JsonValue> {
+        serde_json::to_value(serde_json::from_value::<T>(cfg)?)
+    }
+
+    /// The smallest value `schema` accepts: defaults where given, otherwise the first
+    /// option or the lower bound, with only the required properties of objects.
+    fn minimal_value(schema: &JsonValue) -> JsonValue {
+        if let Some(value) = schema.get("default").or(schema.pointer("/enum/0")) {
+            return value.clone();
+        }
+        let kind = match &schema["type"] {
+            JsonValue::Array(kinds) => kinds[0].as_str(),
+            kind => kind.as_str(),
+        };
+        let bound = |keyword: &str| schema[keyword].as_u64().unwrap_or(0) as usize;
+        match kind {
+            Some("string") => json!("x".repeat(bound("minLength").max(1))),
+            Some("integer") => json!(schema["minimum"].as_i64().unwrap_or(1).max(1)),
+            Some("number") => json!(schema["minimum"].as_f64().unwrap_or(1.0).max(1.0)),
+            Some("boolean") => json!(false),
+            Some("array") => {
+                JsonValue::Array(vec![minimal_value(&schema["items"]); bound("minItems")])
+            }
+            _ => schema["required"]
+                .as_array()
+                .into_iter()
+                .flatten()
+                .filter_map(JsonValue::as_str)
+                .map(|name| (name.to_string(), minimal_value(&schema["properties"][name])))
+                .collect::<serde_json::Map<_, _>>()
+                .into(),
+        }
+    }
+
+    /// Whether `value`, which serde filled in for an omitted setting, is the default
+    /// `schema` declares. Empty collections and absent values need no declared default.
+    fn is_declared_default(schema: &JsonValue, value: &JsonValue) -> bool {
+        if let Some(default) = schema.get("default") {
+            return match (default.as_f64(), value.as_f64()) {
+                (Some(default), Some(value)) => default == value,
+                _ => default == value,
+            };
+        }
+        match value {
+            JsonValue::Null => true,
+            JsonValue::Array(items) => items.is_empty(),
+            JsonValue::Object(fields) => fields
+                .iter()
+                .all(|(field, value)| is_declared_default(&schema["properties"][field], value)),
+            _ => false,
+        }
+    }
+
+    #[test]
+    fn schemas_cover_every_config_field_and_default() {
+        // Plugins whose required settings the schema alone cannot produce.
+        let samples = HashMap::from([
+            ("fault-injection", json!({"abort": {"http_status": 503}})),
+            ("ua-restriction", json!({"denylist": ["curl"]})),
+            (
+                "waf",
+                json!({"rules": ["SecRule ARGS \"@contains attack\" \"id:1,deny\""]}),
+            ),
+            ("uri-blocker", json!({"block_rules": ["^/admin"]})),
+            (
+                "basic-auth",
+                json!({"username": "user", "password": "secret"}),
+            ),
+            (
+                "jwt-auth",
+                json!({"secret": "0123456789abcdef0123456789abcdef"}),
+            ),
+            ("key-auth", json!({"key": "secret"})),
+            ("consumer-restriction", json!({"whitelist": ["jack"]})),
+            (
+                "body-transformer",
+                json!({"request": {"output_format": "json"}}),
+            ),
+            (
+                "ai-proxy",
+                json!({
+                    "provider": "openai-compatible",
+                    "override": {"endpoint": "http://127.0.0.1:8000/v1/chat/completions"}
+                }),
+            ),
+            ("fallback", json!({"response": {}})),
+            (
+                "workflow",
+                json!({"rules": [{"actions": [{"return": {"code": 403}}]}]}),
+            ),
+            (
+                "traffic-split",
+                json!({"rules": [{"weighted_upstreams": [{"weight": 1}]}]}),
+            ),
+        ]);
+        let mut problems = Vec::new();
+        for (name, _) in registered_plugins() {
+            let schema = plugin_schema(name).unwrap();
+            let cfg = samples
+                .get(name)
+                .cloned()
+                .unwrap_or_else(|| minimal_value(&schema));
+            let mut prepared = PreparedUpstreams::new();
+            if name == ai_proxy::PLUGIN_NAME {
+                let endpoint = ai_proxy::endpoint_upstream(&cfg).unwrap();
+                prepared.insert(
+                    ai_proxy_key("test"),
+                    prepare_static_upstream(&endpoint).unwrap(),
+                );
+            }
+            if let Err(e) =
+                build_plugin_with_upstreams(name, cfg.clone(), &HashMap::new(), &prepared, "test")
+            {
+                problems.push(format!("{name} does not build from {cfg}: {e}"));
+                continue;
+            }
+
+            let properties = schema["properties"]
+                .as_object()
+                .cloned()
+                .unwrap_or_default();
+            let Some(config) = PLUGIN_BUILDER_REGISTRY[name].config else {
+                // Without a serializable configuration every setting must be required.
+                let required = schema["required"].as_array().cloned().unwrap_or_default();
+                for property in properties.keys() {
+                    if !required.contains(&json!(property)) {
+                        problems.push(format!("{name}: optional '{property}' is unchecked"));
+                    }
+                }
+                continue;
+            };
+            let parsed = config(cfg.clone()).unwrap();
+            for (field, value) in parsed.as_object().unwrap() {
+                let Some(property) = properties.get(field) else {
+                    problems.push(format!("{name}: '{field}' is missing from the schema"));
+                    continue;
+                };
+                if cfg.get(field).is_none() && !is_declared_default(property, value) {
+                    problems.push(format!(
+                        "{name}: '{field}' defaults to {value}, the schema declares {}",
+                        property.get("default").unwrap_or(&JsonValue::Null)
+                    ));
+                }
+            }
+        }
+        assert!(problems.is_empty(), "{}", problems.join("\n"));
+    }
+
+    #[test]
+    fn configs_are_checked_against_the_plugin_schema() {
+        for (name, _) in registered_plugins() {
+            let schema = plugin_schema(name).unwrap();
+            assert!(schema["type"] == "object" || schema["type"][0] == "object");
+        }
+        assert!(plugin_schema("no-such-plugin").is_none());
+
+        let err = build_plugin(
+            "limit-count",
+            json!({"time_window": 60, "count": 10, "rejected_code": 200}),
+        )
+        .err()
+        .unwrap();
+        assert_eq!(
+            err.to_string(),
+            "Validation error: Invalid limit-count plugin config: /rejected_code: must be at least 400"
+        );
+    }
+
+    #[test]
+    fn meta_disable_is_validated() {
+        let cfg = json!({"time_window": 60, "count": 10, "_meta": {"disable": true}});
+        assert!(is_disabled(&cfg));
+        assert!(build_plugin("limit-count", cfg).is_ok());
+        assert!(!is_disabled(&json!({"_meta": {}})));
+
+        let err = validate_plugin_config("echo", &json!({"body": "", "_meta": {"disable": "yes"}}))
+            .unwrap_err()
+            .to_string();
+        assert!(
+            err.ends_with("/_meta/disable: expected boolean"),
+            "got: {err}"
+        );
+    }
 }
@@ -10,7 +10,7 @@ use prometheus::{
     HistogramVec, IntCounter, IntCounterVec,
 };
 use regex::Regex;
-use serde_json::Value as JsonValue;
+use serde_json::{json, Value as JsonValue};
 
 use crate::core::{ProxyContext, ProxyError, ProxyPlugin, ProxyResult};
 
@@ -53,6 +53,7 @@ static STATUS: Lazy<IntCounterVec> = Lazy::new(|| {
             "matched_host",  // Matched Host
             "service",       // Service ID
             "node",          // Node ID
+            "api_group",     // Route's API group
         ]
     )
     .expect("Failed to register prometheus metric: http_status")
@@ -65,7 +66,7 @@ static LATENCY: Lazy<HistogramVec> = Lazy::new(|| {
         "HTTP request latency in milliseconds per service in pingsix",
     )
     .buckets(DEFAULT_BUCKETS.to_vec());
-    register_histogram_vec!(opts, &["type", "route", "service", "node"])
+    register_histogram_vec!(opts, &["type", "route", "service", "node", "api_group"])
         .expect("Failed to register prometheus metric: http_latency")
 });
 
@@ -105,10 +106,10 @@ static RESPONSE_SIZE: Lazy<HistogramVec> = Lazy::new(|| {
 });
 
 pub const PLUGIN_NAME: &str = "prometheus";
-const PRIORITY: i32 = 500;
+pub const PRIORITY: i32 = 500;
 
 /// Configuration for the Prometheus plugin
-#[derive(Debug, Clone, serde::Deserialize)]
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
 pub struct PrometheusConfig {
     /// Maximum length for path template labels to prevent cardinality explosion
     /// Default: 100 characters
@@ -168,6 +169,18 @@ pub fn create_prometheus_plugin(cfg: JsonValue) -> ProxyResult<Arc<dyn ProxyPlug
     }))
 }
 
+/// JSON Schema of the prometheus plugin configuration.
+pub fn schema() -> JsonValue {
+    json!({
+        "type": ["object", "null"],
+        "properties": {
+            "max_label_length": {"type": "integer", "minimum": 0, "default": 100},
+            "max_unique_paths": {"type": "integer", "minimum": 0, "default": 1000},
+            "max_unique_hosts": {"type": "integer", "minimum": 0, "default": 100}
+        }
+    })
+}
+
 pub struct PluginPrometheus {
     config: PrometheusConfig,
     /// Set of unique normalized paths seen so far
@@ -218,6 +231,8 @@ impl ProxyPlugin for PluginPrometheus {
             .as_ref()
             .map_or_else(|| "unknown", |r| r.service_id().unwrap_or("unknown"));
 
+        let api_group = route.as_ref().and_then(|r| r.api_group()).unwrap_or("");
+
         // Extract node from context variables (assumes HttpService::upstream_peer sets ctx["upstream"]) as String
         let node = ctx
             .peer
@@ -233,13 +248,14 @@ impl ProxyPlugin for PluginPrometheus {
                 &host,
                 service,
                 node.as_ref(),
+                api_group,
             ])
             .inc();
 
         // Record request latency
         let elapsed_ms = ctx.elapsed_ms_f64();
         LATENCY
-            .with_label_values(&["request", route_id, service, node.as_ref()])
+            .with_label_values(&["request", route_id, service, node.as_ref(), api_group])
             .observe(elapsed_ms);
 
         // Record bandwidth metrics
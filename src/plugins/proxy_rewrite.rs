@@ -5,14 +5,17 @@ use pingora_http::RequestHeader;
 use pingora_proxy::Session;
 use regex::Regex;
 use serde::{Deserialize, Serialize};
-use serde_json::Value as JsonValue;
+use serde_json::{json, Value as JsonValue};
 use std::sync::Arc;
 use validator::{Validate, ValidationError};
 
-use crate::core::{apply_regex_uri_template, ProxyContext, ProxyError, ProxyPlugin, ProxyResult};
+use crate::core::{
+    apply_regex_uri_template, expand_route_params, ProxyContext, ProxyError, ProxyPlugin,
+    ProxyResult,
+};
 
 pub const PLUGIN_NAME: &str = "proxy-rewrite";
-const PRIORITY: i32 = 1008;
+pub const PRIORITY: i32 = 1008;
 
 pub fn create_proxy_rewrite_plugin(cfg: JsonValue) -> ProxyResult<Arc<dyn ProxyPlugin>> {
     let config = PluginConfig::try_from(cfg)?;
@@ -37,6 +40,35 @@ pub fn create_proxy_rewrite_plugin(cfg: JsonValue) -> ProxyResult<Arc<dyn ProxyP
     }))
 }
 
+/// JSON Schema of the proxy-rewrite plugin configuration.
+pub fn schema() -> JsonValue {
+    let headers = json!({
+        "type": "array",
+        "items": {
+            "type": "object",
+            "required": ["name", "value"],
+            "properties": {"name": {"type": "string"}, "value": {"type": "string"}}
+        }
+    });
+    json!({
+        "type": "object",
+        "properties": {
+            "uri": {"type": "string"},
+            "method": {"type": "string"},
+            "regex_uri": {"type": "array", "items": {"type": "string"}},
+            "host": {"type": "string"},
+            "headers": {
+                "type": "object",
+                "properties": {
+                    "add": headers,
+                    "set": headers,
+                    "remove": {"type": "array", "items": {"type": "string"}}
+                }
+            }
+        }
+    })
+}
+
 #[derive(Clone, Default, Debug, Serialize, Deserialize, Validate)]
 struct Head {
     name: String,
@@ -54,13 +86,15 @@ struct Headers {
 }
 
 #[derive(Default, Debug, Serialize, Deserialize, Validate)]
-struct PluginConfig {
+pub(super) struct PluginConfig {
     /// The URI to rewrite to. Takes precedence over `regex_uri` if both are set.
+    /// `$param_<name>` expands to a parameter of the matched route pattern.
     uri: Option<String>,
     method: Option<String>,
     #[serde(default)]
     #[validate(custom(function = "PluginConfig::validate_regex_uri"))]
     regex_uri: Vec<String>,
+    /// Host header to send; may reference route parameters like `uri`.
     host: Option<String>,
     headers: Option<Headers>,
 }
@@ -117,10 +151,11 @@ impl ProxyPlugin for PluginProxyRewrite {
         &self,
         session: &mut Session,
         upstream_request: &mut RequestHeader,
-        _ctx: &mut ProxyContext,
+        ctx: &mut ProxyContext,
     ) -> Result<()> {
+        let params = ctx.route_params.as_deref();
         if let Some(path_and_query) = session.req_header().uri.path_and_query() {
-            if let Some(uri) = self.construct_path_and_query(Some(path_and_query)) {
+            if let Some(uri) = self.construct_path_and_query(Some(path_and_query), params) {
                 upstream_request.set_uri(uri);
             }
         }
@@ -135,8 +170,9 @@ impl ProxyPlugin for PluginProxyRewrite {
         }
 
         if let Some(ref host) = self.config.host {
+            let host = expand_route_params(host, params);
             upstream_request
-                .insert_header(http::header::HOST, host)
+                .insert_header(http::header::HOST, host.as_ref())
                 .map_err(|e| ProxyError::Internal(format!("Invalid host: {e}")))?;
         }
 
@@ -152,8 +188,10 @@ impl PluginProxyRewrite {
     fn construct_path_and_query(
         &self,
         path_and_query: Option<&http::uri::PathAndQuery>,
+        params: Option<&[(String, String)]>,
     ) -> Option<Uri> {
         if let Some(ref path) = self.config.uri {
+            let path = expand_route_params(path, params);
             let query = path_and_query.and_then(|pq| pq.query()).unwrap_or("");
             return if query.is_empty() {
                 path.parse().ok()
@@ -1,21 +1,25 @@
 use std::sync::Arc;
 
 use async_trait::async_trait;
-use http::{header, uri::Scheme, StatusCode, Uri};
+use http::{
+    header,
+    uri::{Authority, Scheme},
+    StatusCode, Uri,
+};
 use ipnetwork::IpNetwork;
 use pingora_error::Result;
 use pingora_http::{RequestHeader, ResponseHeader};
 use pingora_proxy::Session;
 use regex::Regex;
 use serde::{Deserialize, Serialize};
-use serde_json::Value as JsonValue;
+use serde_json::{json, Value as JsonValue};
 use validator::{Validate, ValidationError};
 
 use crate::core::{apply_regex_uri_template, ProxyContext, ProxyError, ProxyPlugin, ProxyResult};
 use crate::utils::request::get_direct_client_ip;
 
 pub const PLUGIN_NAME: &str = "redirect";
-const PRIORITY: i32 = 900;
+pub const PRIORITY: i32 = 900;
 
 pub fn create_redirect_plugin(cfg: JsonValue) -> ProxyResult<Arc<dyn ProxyPlugin>> {
     let config = PluginConfig::try_from(cfg)?;
@@ -49,8 +53,26 @@ pub fn create_redirect_plugin(cfg: JsonValue) -> ProxyResult<Arc<dyn ProxyPlugin
     }))
 }
 
+/// JSON Schema of the redirect plugin configuration.
+pub fn schema() -> JsonValue {
+    json!({
+        "type": "object",
+        "required": ["regex_uri"],
+        "properties": {
+            "http_to_https": {"type": "boolean", "default": false},
+            "uri": {"type": "string"},
+            "regex_uri": {"type": "array", "items": {"type": "string"}},
+            "ret_code": {"type": "integer", "enum": [301, 302, 303, 307, 308], "default": 302},
+            "append_query_string": {"type": "boolean", "default": false},
+            "redirect_host": {"type": "string"},
+            "https_port": {"type": "integer", "minimum": 1, "maximum": 65535},
+            "trusted_proxies": {"type": "array", "items": {"type": "string"}}
+        }
+    })
+}
+
 #[derive(Default, Debug, Serialize, Deserialize, Validate)]
-struct PluginConfig {
+pub(super) struct PluginConfig {
     /// If true, redirects HTTP requests to HTTPS. Takes precedence over `uri` and `regex_uri`.
     ///
     /// The redirect triggers when the request is not already over HTTPS:
@@ -70,8 +92,10 @@ struct PluginConfig {
     /// List of regex pattern and replacement template pairs for URI rewriting.
     #[validate(custom(function = "PluginConfig::validate_regex_uri"))]
     regex_uri: Vec<String>,
-    /// HTTP status code for the redirect (e.g., 301, 302, 307, 308). Defaults to 302 (temporary redirect).
+    /// HTTP status code for the redirect: 301, 302, 303, 307 or 308. Defaults to 302 (temporary redirect).
+    /// Use 307/308 to make clients repeat the original method and body.
     #[serde(default = "PluginConfig::default_ret_code")]
+    #[validate(custom(function = "PluginConfig::validate_ret_code"))]
     ret_code: u16,
     /// If true, appends the original query string to the redirect URI, even if the target URI has a query string.
     #[serde(default)]
@@ -79,6 +103,10 @@ struct PluginConfig {
     /// Fixed host for the HTTPS redirect Location. Required when `http_to_https`
     /// is true. Prevents host-header injection into the Location header.
     redirect_host: Option<String>,
+    /// Port of the HTTPS listener used in the `http_to_https` Location.
+    /// Omitted from the URL when it is 443 (the default).
+    #[validate(range(min = 1))]
+    https_port: Option<u16>,
     /// CIDR/IP networks whose immediate peer address may be trusted for
     /// `X-Forwarded-Proto`. Empty (default) means XFP is never trusted.
     #[serde(default)]
@@ -90,6 +118,13 @@ impl PluginConfig {
         302 // Default to temporary redirect (FOUND)
     }
 
+    fn validate_ret_code(ret_code: u16) -> Result<(), ValidationError> {
+        match ret_code {
+            301 | 302 | 303 | 307 | 308 => Ok(()),
+            _ => Err(ValidationError::new("invalid_redirect_code")),
+        }
+    }
+
     fn validate_regex_uri(regex_uri: &[String]) -> Result<(), ValidationError> {
         if !regex_uri.len().is_multiple_of(2) {
             return Err(ValidationError::new("regex_uri_length"));
@@ -126,8 +161,19 @@ impl TryFrom<JsonValue> for PluginConfig {
                         .into(),
                 ));
             }
+            if config.https_port.is_some()
+                && host
+                    .parse::<Authority>()
+                    .ok()
+                    .and_then(|a| a.port_u16())
+                    .is_some()
+            {
+                return Err(ProxyError::Configuration(
+                    "redirect plugin: set the port either in redirect_host or in https_port, not both"
+                        .into(),
+                ));
+            }
         }
-
         Ok(config)
     }
 }
@@ -207,9 +253,10 @@ impl PluginRedirect {
         let authority = self
             .config
             .redirect_host
-            .as_ref()
-            .map(|h| h.trim().to_string())
+            .as_deref()
+            .map(str::trim)
             .filter(|h| !h.is_empty())
+            .map(|h| https_authority(h, self.config.https_port))
             .ok_or_else(|| {
                 ProxyError::Internal(
                     "redirect_host missing for http_to_https (should have been rejected at load)"
@@ -327,6 +374,14 @@ impl PluginRedirect {
     }
 }
 
+/// Append `port` to `host` unless it is the HTTPS default (443).
+fn https_authority(host: &str, port: Option<u16>) -> String {
+    match port {
+        Some(port) if port != 443 => format!("{host}:{port}"),
+        _ => host.to_string(),
+    }
+}
+
 fn session_has_tls(session: &Session) -> bool {
     session
         .digest()
@@ -512,4 +567,53 @@ mod tests {
         .unwrap();
         assert!(cfg.redirect_host.is_none());
     }
+
+    #[test]
+    fn https_port_is_appended_unless_default() {
+        assert_eq!(https_authority("example.com", None), "example.com");
+        assert_eq!(https_authority("example.com", Some(443)), "example.com");
+        assert_eq!(
+            https_authority("example.com", Some(8443)),
+            "example.com:8443"
+        );
+    }
+
+    #[test]
+    fn https_port_conflicts_with_port_in_redirect_host() {
+        let err = PluginConfig::try_from(serde_json::json!({
+            "http_to_https": true,
+            "redirect_host": "example.com:8443",
+            "https_port": 9443,
+            "regex_uri": []
+        }))
+        .unwrap_err();
+        assert!(err.to_string().contains("https_port"));
+
+        let err = PluginConfig::try_from(serde_json::json!({
+            "http_to_https": true,
+            "redirect_host": "example.com",
+            "https_port": 0,
+            "regex_uri": []
+        }))
+        .unwrap_err();
+        assert!(err.to_string().contains("https_port"), "{err}");
+    }
+
+    #[test]
+    fn ret_code_accepts_308_and_rejects_non_redirects() {
+        let cfg = PluginConfig::try_from(serde_json::json!({
+            "uri": "/new",
+            "ret_code": 308,
+            "regex_uri": []
+        }))
+        .unwrap();
+        assert_eq!(cfg.ret_code, 308);
+
+        assert!(PluginConfig::try_from(serde_json::json!({
+            "uri": "/new",
+            "ret_code": 200,
+            "regex_uri": []
+        }))
+        .is_err());
+    }
 }
@@ -0,0 +1,420 @@
+use std::{
+    fmt,
+    io::{self, Write},
+    sync::Arc,
+};
+
+use async_trait::async_trait;
+use bytes::Bytes;
+use flate2::write::MultiGzDecoder;
+use http::header;
+use pingora_error::{Error, ErrorType, Result};
+use pingora_http::RequestHeader;
+use pingora_proxy::Session;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value as JsonValue};
+use validator::Validate;
+
+use crate::{
+    core::{ProxyContext, ProxyError, ProxyPlugin, ProxyResult},
+    utils::request::{request_has_body, set_chunked_body},
+};
+
+pub const PLUGIN_NAME: &str = "request-decompression";
+pub const PRIORITY: i32 = 2990;
+
+/// Context key of the request's [`Decoder`].
+const DECODER_KEY: &str = "request-decompression";
+
+/// Output the ratio limit always allows, so small bodies that compress very well are
+/// not mistaken for bombs.
+const RATIO_GRACE_BYTES: usize = 1024 * 1024;
+
+/// Creates a request-decompression plugin that inflates gzip and brotli request bodies.
+pub fn create_request_decompression_plugin(cfg: JsonValue) -> ProxyResult<Arc<dyn ProxyPlugin>> {
+    let config = PluginConfig::try_from(cfg)?;
+    Ok(Arc::new(PluginRequestDecompression { config }))
+}
+
+/// JSON Schema of the request-decompression plugin configuration.
+pub fn schema() -> JsonValue {
+    json!({
+        "type": "object",
+        "properties": {
+            "encodings": {
+                "type": "array",
+                "items": {"type": "string", "enum": ["gzip", "br"]},
+                "default": ["gzip", "br"]
+            },
+            "max_body_bytes": {"type": "integer", "minimum": 1, "default": PluginConfig::default_max_body_bytes()},
+            "max_ratio": {"type": "integer", "minimum": 1, "default": PluginConfig::default_max_ratio()}
+        }
+    })
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+enum Encoding {
+    #[serde(rename = "gzip")]
+    Gzip,
+    #[serde(rename = "br")]
+    Brotli,
+}
+
+impl Encoding {
+    /// The encoding of a `Content-Encoding` value; `None` for stacked or other codings.
+    fn from_header(value: &str) -> Option<Self> {
+        let value = value.trim();
+        if value.eq_ignore_ascii_case("gzip") || value.eq_ignore_ascii_case("x-gzip") {
+            Some(Self::Gzip)
+        } else if value.eq_ignore_ascii_case("br") {
+            Some(Self::Brotli)
+        } else {
+            None
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Validate)]
+pub(super) struct PluginConfig {
+    /// Content codings to decompress; bodies in other codings pass through untouched.
+    #[serde(default = "PluginConfig::default_encodings")]
+    encodings: Vec<Encoding>,
+    /// Largest decompressed body.
+    #[serde(default = "PluginConfig::default_max_body_bytes")]
+    #[validate(range(min = 1))]
+    max_body_bytes: usize,
+    /// Largest ratio of decompressed to compressed bytes, past the first MiB.
+    #[serde(default = "PluginConfig::default_max_ratio")]
+    #[validate(range(min = 1))]
+    max_ratio: usize,
+}
+
+impl PluginConfig {
+    fn default_encodings() -> Vec<Encoding> {
+        vec![Encoding::Gzip, Encoding::Brotli]
+    }
+
+    fn default_max_body_bytes() -> usize {
+        10 * 1024 * 1024
+    }
+
+    fn default_max_ratio() -> usize {
+        100
+    }
+}
+
+impl TryFrom<JsonValue> for PluginConfig {
+    type Error = ProxyError;
+
+    fn try_from(value: JsonValue) -> Result<Self, Self::Error> {
+        let config: PluginConfig = serde_json::from_value(value).map_err(|e| {
+            ProxyError::serialization_error("Invalid request-decompression plugin config", e)
+        })?;
+
+        config.validate()?;
+
+        Ok(config)
+    }
+}
+
+/// Why a body could not be decompressed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DecodeError {
+    /// Over `max_body_bytes` or `max_ratio`.
+    TooLarge,
+    /// Not valid in its declared coding.
+    Invalid,
+}
+
+impl DecodeError {
+    pub fn status(self) -> u16 {
+        match self {
+            Self::TooLarge => 413,
+            Self::Invalid => 400,
+        }
+    }
+}
+
+impl fmt::Display for DecodeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::TooLarge => f.write_str("decompressed request body exceeds the allowed size"),
+            Self::Invalid => f.write_str("request body does not match its Content-Encoding"),
+        }
+    }
+}
+
+/// Decompressed output, refusing writes past `limit` in total.
+struct Output {
+    buf: Vec<u8>,
+    written: usize,
+    limit: usize,
+    exceeded: bool,
+}
+
+impl Write for Output {
+    fn write(&mut self, data: &[u8]) -> io::Result<usize> {
+        if self.written + data.len() > self.limit {
+            self.exceeded = true;
+            return Err(io::Error::other("decompression limit exceeded"));
+        }
+        self.written += data.len();
+        self.buf.extend_from_slice(data);
+        Ok(data.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+enum Inflater {
+    /// Decodes every member of a multi-member gzip body, not only the first.
+    Gzip(Box<MultiGzDecoder<Output>>),
+    Brotli(Box<brotli::DecompressorWriter<Output>>),
+}
+
+/// Streaming decompressor of one request body, bounded as configured.
+pub struct Decoder {
+    encoding: Encoding,
+    max_body_bytes: usize,
+    max_ratio: usize,
+    consumed: usize,
+    inflater: Inflater,
+}
+
+impl Decoder {
+    fn new(encoding: Encoding, max_body_bytes: usize, max_ratio: usize) -> Self {
+        let output = Output {
+            buf: Vec::new(),
+            written: 0,
+            limit: 0,
+            exceeded: false,
+        };
+        let inflater = match encoding {
+            Encoding::Gzip => Inflater::Gzip(Box::new(MultiGzDecoder::new(output))),
+            Encoding::Brotli => {
+                Inflater::Brotli(Box::new(brotli::DecompressorWriter::new(output, 4096)))
+            }
+        };
+        Self {
+            encoding,
+            max_body_bytes,
+            max_ratio,
+            consumed: 0,
+            inflater,
+        }
+    }
+
+    fn output(&mut self) -> &mut Output {
+        match &mut self.inflater {
+            Inflater::Gzip(decoder) => decoder.get_mut(),
+            Inflater::Brotli(decoder) => decoder.get_mut(),
+        }
+    }
+
+    /// Decompress the next chunk of the body.
+    pub fn decode(&mut self, input: &[u8], end: bool) -> Result<Bytes, DecodeError> {
+        self.consumed += input.len();
+        if end && self.consumed == 0 {
+            return Ok(Bytes::new());
+        }
+        let limit = self
+            .consumed
+            .saturating_mul(self.max_ratio)
+            .max(RATIO_GRACE_BYTES)
+            .min(self.max_body_bytes);
+        self.output().limit = limit;
+
+        let result = match &mut self.inflater {
+            Inflater::Gzip(decoder) => {
+                decoder
+                    .write_all(input)
+                    .and_then(|()| if end { decoder.try_finish() } else { Ok(()) })
+            }
+            Inflater::Brotli(decoder) => {
+                decoder
+                    .write_all(input)
+                    .and_then(|()| if end { decoder.close() } else { Ok(()) })
+            }
+        };
+        let output = self.output();
+        if output.exceeded {
+            return Err(DecodeError::TooLarge);
+        }
+        result.map_err(|_| DecodeError::Invalid)?;
+        Ok(std::mem::take(&mut output.buf).into())
+    }
+}
+
+/// Decompress a body an earlier phase read whole, such as request-validation's
+/// `request_filter`. The body is returned as is when this request is not decompressed.
+pub fn decompress_buffered(ctx: &ProxyContext, body: Bytes) -> Result<Bytes, DecodeError> {
+    let Some(decoder) = ctx.get::<Decoder>(DECODER_KEY) else {
+        return Ok(body);
+    };
+    // The streaming decoder stays untouched for the body replayed to the upstream.
+    Decoder::new(decoder.encoding, decoder.max_body_bytes, decoder.max_ratio).decode(&body, true)
+}
+
+pub struct PluginRequestDecompression {
+    config: PluginConfig,
+}
+
+#[async_trait]
+impl ProxyPlugin for PluginRequestDecompression {
+    fn name(&self) -> &str {
+        PLUGIN_NAME
+    }
+
+    fn priority(&self) -> i32 {
+        PRIORITY
+    }
+
+    async fn request_filter(&self, session: &mut Session, ctx: &mut ProxyContext) -> Result<bool> {
+        // Without a body there is nothing to decode and the framing stays as it is.
+        if !request_has_body(session.req_header()) {
+            return Ok(false);
+        }
+        let encoding = session
+            .req_header()
+            .headers
+            .get(header::CONTENT_ENCODING)
+            .and_then(|value| value.to_str().ok())
+            .and_then(Encoding::from_header)
+            .filter(|encoding| self.config.encodings.contains(encoding));
+        if let Some(encoding) = encoding {
+            ctx.set(
+                DECODER_KEY,
+                Decoder::new(encoding, self.config.max_body_bytes, self.config.max_ratio),
+            );
+        }
+        Ok(false)
+    }
+
+    async fn upstream_request_filter(
+        &self,
+        _session: &mut Session,
+        upstream_request: &mut RequestHeader,
+        ctx: &mut ProxyContext,
+    ) -> Result<()> {
+        if ctx.get::<Decoder>(DECODER_KEY).is_none() {
+            return Ok(());
+        }
+        upstream_request.remove_header(&header::CONTENT_ENCODING);
+        set_chunked_body(upstream_request)
+    }
+
+    fn has_request_body_filter(&self) -> bool {
+        true
+    }
+
+    async fn request_body_filter(
+        &self,
+        _session: &mut Session,
+        body: &mut Option<Bytes>,
+        end_of_stream: bool,
+        ctx: &mut ProxyContext,
+    ) -> Result<()> {
+        let Some(decoder) = ctx.get_mut::<Decoder>(DECODER_KEY) else {
+            return Ok(());
+        };
+        if body.is_none() && !end_of_stream {
+            return Ok(());
+        }
+        let input = body.take().unwrap_or_default();
+        match decoder.decode(&input, end_of_stream) {
+            Ok(output) => {
+                *body = (!output.is_empty() || end_of_stream).then_some(output);
+                Ok(())
+            }
+            Err(e) => Error::e_explain(ErrorType::HTTPStatus(e.status()), e.to_string()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use flate2::{write::GzEncoder, Compression};
+
+    use super::*;
+
+    fn gzip(data: &[u8]) -> Vec<u8> {
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(data).unwrap();
+        encoder.finish().unwrap()
+    }
+
+    fn brotli(data: &[u8]) -> Vec<u8> {
+        let mut encoder = brotli::CompressorWriter::new(Vec::new(), 4096, 5, 22);
+        encoder.write_all(data).unwrap();
+        encoder.into_inner()
+    }
+
+    fn decode_in_chunks(mut decoder: Decoder, body: &[u8]) -> Result<Vec<u8>, DecodeError> {
+        let mut output = Vec::new();
+        let chunks: Vec<_> = body.chunks(7).collect();
+        for (i, chunk) in chunks.iter().enumerate() {
+            output.extend_from_slice(&decoder.decode(chunk, i == chunks.len() - 1)?);
+        }
+        Ok(output)
+    }
+
+    #[test]
+    fn bodies_are_decompressed_across_chunks() {
+        let body = br#"{"items": [1, 2, 3], "note": "compressed request"}"#;
+        for (encoding, compressed) in [
+            (Encoding::Gzip, gzip(body)),
+            (Encoding::Brotli, brotli(body)),
+        ] {
+            let decoder = Decoder::new(encoding, 1024, 100);
+            assert_eq!(decode_in_chunks(decoder, &compressed).unwrap(), body);
+        }
+
+        let decoder = Decoder::new(Encoding::Gzip, 1024, 100);
+        assert_eq!(
+            decode_in_chunks(decoder, b"not gzip at all"),
+            Err(DecodeError::Invalid)
+        );
+        let truncated = gzip(body);
+        let decoder = Decoder::new(Encoding::Gzip, 1024, 100);
+        assert_eq!(
+            decode_in_chunks(decoder, &truncated[..truncated.len() - 4]),
+            Err(DecodeError::Invalid)
+        );
+    }
+
+    #[test]
+    fn multi_member_gzip_is_decoded_whole() {
+        let mut body = gzip(b"first member, ");
+        body.extend(gzip(b"second member"));
+        let decoder = Decoder::new(Encoding::Gzip, 1024, 100);
+        assert_eq!(
+            decode_in_chunks(decoder, &body).unwrap(),
+            b"first member, second member"
+        );
+    }
+
+    #[test]
+    fn bombs_are_stopped_by_size_and_ratio() {
+        let bomb = gzip(&vec![0; 4 * 1024 * 1024]);
+        let decoder = Decoder::new(Encoding::Gzip, 64 * 1024, 1000);
+        assert_eq!(decode_in_chunks(decoder, &bomb), Err(DecodeError::TooLarge));
+        // Within max_body_bytes, but zeros compress far beyond 100:1.
+        let decoder = Decoder::new(Encoding::Gzip, 8 * 1024 * 1024, 100);
+        assert_eq!(decode_in_chunks(decoder, &bomb), Err(DecodeError::TooLarge));
+        let decoder = Decoder::new(Encoding::Gzip, 8 * 1024 * 1024, 2000);
+        assert_eq!(
+            decode_in_chunks(decoder, &bomb).unwrap().len(),
+            4 * 1024 * 1024
+        );
+    }
+
+    #[test]
+    fn content_encoding_is_matched_exactly() {
+        assert_eq!(Encoding::from_header(" GZIP"), Some(Encoding::Gzip));
+        assert_eq!(Encoding::from_header("br"), Some(Encoding::Brotli));
+        assert_eq!(Encoding::from_header("gzip, br"), None);
+        assert!(PluginConfig::try_from(json!({"encodings": ["zstd"]})).is_err());
+    }
+}
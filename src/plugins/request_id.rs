@@ -1,4 +1,10 @@
-use std::sync::Arc;
+use std::{
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+    time::{SystemTime, UNIX_EPOCH},
+};
 
 use async_trait::async_trait;
 
@@ -7,9 +13,9 @@ use pingora_http::ResponseHeader;
 use pingora_proxy::Session;
 use rand::prelude::*;
 use serde::{Deserialize, Serialize};
-use serde_json::Value as JsonValue;
+use serde_json::{json, Value as JsonValue};
 use uuid::Uuid;
-use validator::{Validate, ValidationError};
+use validator::Validate;
 
 use crate::{
     core::{ProxyContext, ProxyError, ProxyPlugin, ProxyResult},
@@ -17,17 +23,23 @@ use crate::{
 };
 
 pub const PLUGIN_NAME: &str = "request-id";
-const PRIORITY: i32 = 12015;
+pub const PRIORITY: i32 = 12015;
 
 // Note: Request ID is now stored directly in ProxyContext.request_id field
 /// Default header name for request ID
 const DEFAULT_REQUEST_ID_HEADER: &str = "X-Request-Id";
-/// UUID algorithm identifier for request ID generation
-const ALGORITHM_UUID: &str = "uuid";
-/// Range ID algorithm identifier for request ID generation
-const ALGORITHM_RANGE_ID: &str = "range_id";
 /// Default character set used for generating range-based request IDs
 const DEFAULT_CHAR_SET: &str = "abcdefghijklmnopqrstuvwxyzABCDEFGHIGKLMNOPQRSTUVWXYZ0123456789";
+/// URL-safe alphabet used by nanoid.
+const NANOID_ALPHABET: &[u8] = b"_-0123456789abcdefghijklmnopqrstuvwxyzABCDEFGHIJKLMNOPQRSTUVWXYZ";
+/// Crockford base32 alphabet used by ULID.
+const ULID_ALPHABET: &[u8] = b"0123456789ABCDEFGHJKMNPQRSTVWXYZ";
+/// Sequence bits of a snowflake ID; the worker ID takes the 10 bits above them.
+const SNOWFLAKE_SEQUENCE_BITS: u32 = 12;
+const SNOWFLAKE_SEQUENCE_MASK: u64 = (1 << SNOWFLAKE_SEQUENCE_BITS) - 1;
+
+/// Last issued snowflake slot: absolute milliseconds shifted above a 12-bit sequence.
+static SNOWFLAKE_STATE: AtomicU64 = AtomicU64::new(0);
 
 /// Creates a Request ID plugin instance with the given configuration.
 pub fn create_request_id_plugin(cfg: JsonValue) -> ProxyResult<Arc<dyn ProxyPlugin>> {
@@ -35,18 +47,85 @@ pub fn create_request_id_plugin(cfg: JsonValue) -> ProxyResult<Arc<dyn ProxyPlug
     Ok(Arc::new(PluginRequestID { config }))
 }
 
+/// JSON Schema of the request-id plugin configuration.
+pub fn schema() -> JsonValue {
+    json!({
+        "type": "object",
+        "properties": {
+            "header_name": {"type": "string", "default": DEFAULT_REQUEST_ID_HEADER},
+            "include_in_response": {"type": "boolean", "default": true},
+            "honor_incoming": {"type": "boolean", "default": true},
+            "max_incoming_length": {"type": "integer", "minimum": 1, "maximum": 1024, "default": 128},
+            "algorithm": {
+                "type": "string",
+                "enum": ["uuid", "range_id", "ulid", "snowflake", "nanoid"],
+                "default": "uuid"
+            },
+            "range_id": {
+                "type": "object",
+                "properties": {
+                    "char_set": {"type": "string", "default": DEFAULT_CHAR_SET},
+                    "length": {"type": "integer", "minimum": 0, "default": 16}
+                }
+            },
+            "snowflake": {
+                "type": "object",
+                "properties": {
+                    "worker_id": {"type": "integer", "minimum": 0, "maximum": 1023, "default": 0},
+                    "epoch_ms": {"type": "integer", "minimum": 0, "default": 1_704_067_200_000_u64}
+                }
+            },
+            "nanoid": {
+                "type": "object",
+                "properties": {
+                    "length": {"type": "integer", "minimum": 8, "maximum": 64, "default": 21}
+                }
+            }
+        }
+    })
+}
+
+/// ID generation algorithm.
+#[derive(Default, Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum Algorithm {
+    /// Random UUID v4.
+    #[default]
+    Uuid,
+    /// Random string drawn from `range_id.char_set`.
+    RangeId,
+    /// Lexicographically sortable 26-character ULID.
+    Ulid,
+    /// Time-ordered 64-bit integer: timestamp, worker ID and sequence.
+    Snowflake,
+    /// Compact URL-safe random string.
+    Nanoid,
+}
+
 /// Configuration for the Request ID plugin.
 #[derive(Default, Debug, Serialize, Deserialize, Validate)]
-struct PluginConfig {
+pub(super) struct PluginConfig {
     #[serde(default = "PluginConfig::default_header_name")]
     header_name: String,
     #[serde(default = "PluginConfig::default_include_in_response")]
     include_in_response: bool,
-    #[serde(default = "PluginConfig::default_algorithm")]
-    #[validate(custom(function = "PluginConfig::validate_algorithm"))]
-    algorithm: String,
+    /// Keep a well-formed ID sent by the client (or an upstream proxy) instead of replacing it.
+    #[serde(default = "PluginConfig::default_honor_incoming")]
+    honor_incoming: bool,
+    /// Longest incoming ID that is honored; longer values are replaced.
+    #[serde(default = "PluginConfig::default_max_incoming_length")]
+    #[validate(range(min = 1, max = 1024))]
+    max_incoming_length: usize,
+    #[serde(default)]
+    algorithm: Algorithm,
     #[serde(default)]
     range_id: RangeID,
+    #[serde(default)]
+    #[validate(nested)]
+    snowflake: Snowflake,
+    #[serde(default)]
+    #[validate(nested)]
+    nanoid: NanoID,
 }
 
 impl PluginConfig {
@@ -58,22 +137,16 @@ impl PluginConfig {
         true
     }
 
-    fn default_algorithm() -> String {
-        ALGORITHM_UUID.to_string()
+    fn default_honor_incoming() -> bool {
+        true
     }
 
-    fn validate_algorithm(algorithm: &String) -> Result<(), ValidationError> {
-        if algorithm == ALGORITHM_UUID || algorithm == ALGORITHM_RANGE_ID {
-            Ok(())
-        } else {
-            Err(ValidationError::new(
-                "algorithm must be either 'uuid' or 'range_id'",
-            ))
-        }
+    fn default_max_incoming_length() -> usize {
+        128
     }
 }
 
-#[derive(Default, Debug, Serialize, Deserialize)]
+#[derive(Debug, Serialize, Deserialize)]
 struct RangeID {
     #[serde(default = "RangeID::default_char_set")]
     char_set: String,
@@ -91,6 +164,62 @@ impl RangeID {
     }
 }
 
+impl Default for RangeID {
+    fn default() -> Self {
+        Self {
+            char_set: Self::default_char_set(),
+            length: Self::default_length(),
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Validate)]
+struct Snowflake {
+    /// Identifies this gateway instance; must differ between instances sharing logs.
+    #[serde(default)]
+    #[validate(range(max = 1023))]
+    worker_id: u16,
+    /// Custom epoch in Unix milliseconds (default 2024-01-01T00:00:00Z).
+    #[serde(default = "Snowflake::default_epoch_ms")]
+    epoch_ms: u64,
+}
+
+impl Snowflake {
+    fn default_epoch_ms() -> u64 {
+        1_704_067_200_000
+    }
+}
+
+impl Default for Snowflake {
+    fn default() -> Self {
+        Self {
+            worker_id: 0,
+            epoch_ms: Self::default_epoch_ms(),
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Validate)]
+struct NanoID {
+    #[serde(default = "NanoID::default_length")]
+    #[validate(range(min = 8, max = 64))]
+    length: u32,
+}
+
+impl NanoID {
+    fn default_length() -> u32 {
+        21
+    }
+}
+
+impl Default for NanoID {
+    fn default() -> Self {
+        Self {
+            length: Self::default_length(),
+        }
+    }
+}
+
 impl TryFrom<JsonValue> for PluginConfig {
     type Error = ProxyError;
 
@@ -104,16 +233,25 @@ impl TryFrom<JsonValue> for PluginConfig {
     }
 }
 
+fn unix_millis() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or_default()
+}
+
 pub struct PluginRequestID {
     config: PluginConfig,
 }
 
 impl PluginRequestID {
     fn get_request_id(&self) -> String {
-        match self.config.algorithm.as_str() {
-            ALGORITHM_UUID => Uuid::new_v4().to_string(),
-            ALGORITHM_RANGE_ID => self.get_range_id(),
-            _ => Uuid::new_v4().to_string(), // Fallback for invalid algorithm
+        match self.config.algorithm {
+            Algorithm::Uuid => Uuid::new_v4().to_string(),
+            Algorithm::RangeId => self.get_range_id(),
+            Algorithm::Ulid => Self::get_ulid(),
+            Algorithm::Snowflake => self.get_snowflake().to_string(),
+            Algorithm::Nanoid => self.get_nanoid(),
         }
     }
 
@@ -138,6 +276,63 @@ impl PluginRequestID {
             .map(|_| chars.choose(&mut rng).copied().unwrap_or('?'))
             .collect()
     }
+
+    /// 48-bit millisecond timestamp followed by 80 random bits, Crockford base32 encoded.
+    fn get_ulid() -> String {
+        let random: u128 = rand::thread_rng().gen::<u128>() & ((1 << 80) - 1);
+        let value = (u128::from(unix_millis()) << 80) | random;
+        (0..26)
+            .map(|i| ULID_ALPHABET[((value >> (125 - 5 * i)) & 0x1f) as usize] as char)
+            .collect()
+    }
+
+    /// Next snowflake ID. When a millisecond's sequence is exhausted the slot moves to the
+    /// next millisecond, so IDs stay unique and increasing within this process.
+    fn get_snowflake(&self) -> u64 {
+        let now = unix_millis();
+        let mut prev = SNOWFLAKE_STATE.load(Ordering::Relaxed);
+        loop {
+            let prev_ms = prev >> SNOWFLAKE_SEQUENCE_BITS;
+            let next = if now > prev_ms {
+                now << SNOWFLAKE_SEQUENCE_BITS
+            } else if prev & SNOWFLAKE_SEQUENCE_MASK < SNOWFLAKE_SEQUENCE_MASK {
+                prev + 1
+            } else {
+                (prev_ms + 1) << SNOWFLAKE_SEQUENCE_BITS
+            };
+            match SNOWFLAKE_STATE.compare_exchange_weak(
+                prev,
+                next,
+                Ordering::AcqRel,
+                Ordering::Relaxed,
+            ) {
+                Ok(_) => {
+                    let cfg = &self.config.snowflake;
+                    let millis = (next >> SNOWFLAKE_SEQUENCE_BITS).saturating_sub(cfg.epoch_ms);
+                    return (millis << 22)
+                        | (u64::from(cfg.worker_id) << SNOWFLAKE_SEQUENCE_BITS)
+                        | (next & SNOWFLAKE_SEQUENCE_MASK);
+                }
+                Err(current) => prev = current,
+            }
+        }
+    }
+
+    fn get_nanoid(&self) -> String {
+        let mut rng = rand::thread_rng();
+        (0..self.config.nanoid.length)
+            .map(|_| NANOID_ALPHABET[rng.gen_range(0..NANOID_ALPHABET.len())] as char)
+            .collect()
+    }
+
+    /// Whether an incoming ID is safe to keep: bounded length and visible ASCII only,
+    /// so it cannot break log lines or smuggle header content.
+    fn accept_incoming(&self, value: &str) -> bool {
+        self.config.honor_incoming
+            && !value.is_empty()
+            && value.len() <= self.config.max_incoming_length
+            && value.bytes().all(|b| b.is_ascii_graphic())
+    }
 }
 
 #[async_trait]
@@ -150,26 +345,38 @@ impl ProxyPlugin for PluginRequestID {
         PRIORITY
     }
 
-    async fn request_filter(&self, session: &mut Session, ctx: &mut ProxyContext) -> Result<bool> {
-        // Retrieve request ID from header, or generate a new one
-        let value =
-            match request::get_req_header_value(session.req_header(), &self.config.header_name) {
-                Some(s) => s.to_string(),
-                None => {
-                    let request_id = self.get_request_id();
-                    session
-                        .req_header_mut()
-                        .insert_header(self.config.header_name.clone(), &request_id)
-                        .map_err(|e| {
-                            ProxyError::Internal(format!("Session insert header fail: {e}"))
-                        })?;
-                    request_id
-                }
-            };
+    /// Assigns the ID before routing so that every request, including unmatched and
+    /// short-circuited ones, has `$request_id` available to log formats.
+    async fn early_request_filter(
+        &self,
+        session: &mut Session,
+        ctx: &mut ProxyContext,
+    ) -> Result<()> {
+        // A global rule and a route may both enable the plugin; the first one wins.
+        if ctx.request_id().is_some() {
+            return Ok(());
+        }
+
+        let incoming =
+            request::get_req_header_value(session.req_header(), &self.config.header_name)
+                .filter(|value| self.accept_incoming(value));
+        let value = match incoming {
+            Some(s) => s.to_string(),
+            None => {
+                let request_id = self.get_request_id();
+                session
+                    .req_header_mut()
+                    .insert_header(self.config.header_name.clone(), &request_id)
+                    .map_err(|e| {
+                        ProxyError::Internal(format!("Session insert header fail: {e}"))
+                    })?;
+                request_id
+            }
+        };
 
         ctx.set_request_id(value);
 
-        Ok(false)
+        Ok(())
     }
 
     async fn response_filter(
@@ -191,3 +398,47 @@ impl ProxyPlugin for PluginRequestID {
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn plugin(cfg: JsonValue) -> PluginRequestID {
+        PluginRequestID {
+            config: PluginConfig::try_from(cfg).unwrap(),
+        }
+    }
+
+    #[test]
+    fn generated_ids_match_their_algorithm() {
+        let ulid = plugin(json!({"algorithm": "ulid"})).get_request_id();
+        assert_eq!(ulid.len(), 26);
+        assert!(ulid.bytes().all(|b| ULID_ALPHABET.contains(&b)));
+
+        let nanoid = plugin(json!({"algorithm": "nanoid", "nanoid": {"length": 12}}));
+        let id = nanoid.get_request_id();
+        assert_eq!(id.len(), 12);
+        assert!(id.bytes().all(|b| NANOID_ALPHABET.contains(&b)));
+
+        assert!(PluginConfig::try_from(json!({"algorithm": "sha1"})).is_err());
+    }
+
+    #[test]
+    fn snowflake_ids_increase_and_carry_worker_id() {
+        let p = plugin(json!({"algorithm": "snowflake", "snowflake": {"worker_id": 5}}));
+        let ids: Vec<u64> = (0..5000).map(|_| p.get_snowflake()).collect();
+        assert!(ids.windows(2).all(|w| w[0] < w[1]));
+        assert!(ids.iter().all(|id| (id >> 12) & 0x3ff == 5));
+        assert!(PluginConfig::try_from(json!({"snowflake": {"worker_id": 1024}})).is_err());
+    }
+
+    #[test]
+    fn incoming_ids_are_honored_only_when_well_formed() {
+        let p = plugin(json!({"max_incoming_length": 16}));
+        assert!(p.accept_incoming("abc-123"));
+        assert!(!p.accept_incoming("has space"));
+        assert!(!p.accept_incoming(&"x".repeat(17)));
+        assert!(!plugin(json!({"honor_incoming": false})).accept_incoming("abc-123"));
+    }
+}
@@ -0,0 +1,431 @@
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use bytes::{Bytes, BytesMut};
+use http::{header, StatusCode};
+use pingora_error::Result;
+use pingora_http::{RequestHeader, ResponseHeader};
+use pingora_proxy::Session;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Map, Value as JsonValue};
+use url::form_urlencoded;
+use validator::Validate;
+
+use super::request_decompression;
+use crate::core::{ProxyContext, ProxyError, ProxyPlugin, ProxyResult};
+use crate::utils::{json_schema, response::content_type};
+
+pub const PLUGIN_NAME: &str = "request-validation";
+pub const PRIORITY: i32 = 2800;
+
+/// Bodies are validated before the upstream is contacted and replayed to it from
+/// Pingora's retry buffer, which holds at most this much.
+const MAX_BODY_BYTES: usize = 64 * 1024;
+
+/// Creates a request-validation plugin that checks requests against JSON Schemas.
+pub fn create_request_validation_plugin(cfg: JsonValue) -> ProxyResult<Arc<dyn ProxyPlugin>> {
+    let config = PluginConfig::try_from(cfg)?;
+    Ok(Arc::new(PluginRequestValidation { config }))
+}
+
+/// JSON Schema of the request-validation plugin configuration.
+pub fn schema() -> JsonValue {
+    json!({
+        "type": "object",
+        "properties": {
+            "header_schema": {"type": "object"},
+            "query_schema": {"type": "object"},
+            "body_schema": {"type": "object"},
+            "max_body_bytes": {"type": "integer", "minimum": 1, "maximum": MAX_BODY_BYTES, "default": MAX_BODY_BYTES},
+            "rejected_code": {"type": "integer", "minimum": 400, "maximum": 599, "default": 400}
+        }
+    })
+}
+
+#[derive(Debug, Serialize, Deserialize, Validate)]
+pub(super) struct PluginConfig {
+    /// Schema of the request headers as an object keyed by lowercase header name.
+    #[serde(default)]
+    header_schema: Option<JsonValue>,
+    /// Schema of the query string as an object keyed by parameter name.
+    #[serde(default)]
+    query_schema: Option<JsonValue>,
+    /// Schema of the JSON, form or multipart request body.
+    #[serde(default)]
+    body_schema: Option<JsonValue>,
+    #[serde(default = "PluginConfig::default_max_body_bytes")]
+    #[validate(range(min = 1, max = 65536))]
+    max_body_bytes: usize,
+    #[serde(default = "PluginConfig::default_rejected_code")]
+    #[validate(range(min = 400, max = 599))]
+    rejected_code: u16,
+}
+
+impl PluginConfig {
+    fn default_max_body_bytes() -> usize {
+        MAX_BODY_BYTES
+    }
+
+    fn default_rejected_code() -> u16 {
+        400
+    }
+}
+
+impl TryFrom<JsonValue> for PluginConfig {
+    type Error = ProxyError;
+
+    fn try_from(value: JsonValue) -> Result<Self, Self::Error> {
+        let config: PluginConfig = serde_json::from_value(value).map_err(|e| {
+            ProxyError::serialization_error("Invalid request validation plugin config", e)
+        })?;
+
+        config.validate()?;
+
+        Ok(config)
+    }
+}
+
+/// A failed check: where in the request, and the validator's `path: message` error.
+#[derive(Debug, PartialEq)]
+struct Violation {
+    location: &'static str,
+    status: Option<u16>,
+    error: String,
+}
+
+impl Violation {
+    fn new(location: &'static str, error: impl Into<String>) -> Self {
+        Self {
+            location,
+            status: None,
+            error: error.into(),
+        }
+    }
+
+    fn body(&self) -> JsonValue {
+        let (path, message) = self
+            .error
+            .split_once(": ")
+            .filter(|(path, _)| path.starts_with('/'))
+            .unwrap_or(("/", self.error.as_str()));
+        json!({
+            "error": "invalid_request",
+            "details": [{"in": self.location, "path": path, "message": message}]
+        })
+    }
+}
+
+/// Parse string `values` as the `integer`, `number` or `boolean` their property schema
+/// asks for, since headers, query strings and forms carry only text.
+fn coerce(schema: &JsonValue, values: Vec<(String, String)>) -> JsonValue {
+    let mut object = Map::new();
+    for (name, value) in values {
+        if object.contains_key(&name) {
+            continue;
+        }
+        let wanted = schema
+            .pointer(&format!(
+                "/properties/{}/type",
+                name.replace('~', "~0").replace('/', "~1")
+            ))
+            .map(|kind| match kind {
+                JsonValue::Array(kinds) => kinds.iter().filter_map(JsonValue::as_str).collect(),
+                _ => vec![kind.as_str().unwrap_or_default()],
+            })
+            .unwrap_or_default();
+        let typed = wanted.iter().find_map(|kind| match *kind {
+            "integer" => value.parse::<i64>().ok().map(JsonValue::from),
+            "number" => value.parse::<f64>().ok().map(JsonValue::from),
+            "boolean" => value.parse::<bool>().ok().map(JsonValue::from),
+            _ => None,
+        });
+        object.insert(name, typed.unwrap_or(JsonValue::String(value)));
+    }
+    JsonValue::Object(object)
+}
+
+fn header_values(req: &RequestHeader) -> Vec<(String, String)> {
+    req.headers
+        .iter()
+        .filter_map(|(name, value)| {
+            Some((name.as_str().to_string(), value.to_str().ok()?.to_string()))
+        })
+        .collect()
+}
+
+fn query_values(req: &RequestHeader) -> Vec<(String, String)> {
+    form_urlencoded::parse(req.uri.query().unwrap_or_default().as_bytes())
+        .into_owned()
+        .collect()
+}
+
+/// The request body as JSON: parsed JSON, form fields, or multipart fields where file
+/// parts become `{filename, content_type, size}`.
+fn parse_body(content_type: &str, body: &[u8], schema: &JsonValue) -> Result<JsonValue, String> {
+    let mime = content_type
+        .split(';')
+        .next()
+        .unwrap_or_default()
+        .trim()
+        .to_ascii_lowercase();
+    match mime.as_str() {
+        "" | "application/json" => {}
+        "application/x-www-form-urlencoded" => {
+            return Ok(coerce(
+                schema,
+                form_urlencoded::parse(body).into_owned().collect(),
+            ));
+        }
+        "multipart/form-data" => return parse_multipart(content_type, body, schema),
+        _ if mime.ends_with("+json") => {}
+        _ => return Err(format!("unsupported content type '{mime}'")),
+    }
+    serde_json::from_slice(body).map_err(|e| format!("invalid JSON: {e}"))
+}
+
+fn parse_multipart(
+    content_type: &str,
+    body: &[u8],
+    schema: &JsonValue,
+) -> Result<JsonValue, String> {
+    let boundary = content_type
+        .split(';')
+        .filter_map(|param| param.trim().split_once('='))
+        .find(|(name, _)| name.eq_ignore_ascii_case("boundary"))
+        .map(|(_, value)| value.trim_matches('"'))
+        .filter(|boundary| !boundary.is_empty())
+        .ok_or("multipart body without boundary")?;
+    let delimiter = format!("--{boundary}");
+    let body = String::from_utf8_lossy(body);
+
+    let mut fields = Vec::new();
+    let mut files = Map::new();
+    for part in body.split(delimiter.as_str()).skip(1) {
+        if part.starts_with("--") {
+            break;
+        }
+        let part = part.strip_prefix("\r\n").unwrap_or(part);
+        let part = part.strip_suffix("\r\n").unwrap_or(part);
+        let (head, content) = part
+            .split_once("\r\n\r\n")
+            .ok_or("malformed multipart part")?;
+        let mut name = None;
+        let mut filename = None;
+        let mut part_type = None;
+        for line in head.lines() {
+            let Some((header, value)) = line.split_once(':') else {
+                continue;
+            };
+            if header.trim().eq_ignore_ascii_case("content-type") {
+                part_type = Some(value.trim().to_string());
+            } else if header.trim().eq_ignore_ascii_case("content-disposition") {
+                for param in value.split(';').skip(1) {
+                    match param.trim().split_once('=') {
+                        Some(("name", v)) => name = Some(v.trim_matches('"').to_string()),
+                        Some(("filename", v)) => filename = Some(v.trim_matches('"').to_string()),
+                        _ => {}
+                    }
+                }
+            }
+        }
+        let name = name.ok_or("multipart part without a name")?;
+        match filename {
+            Some(filename) => {
+                files.insert(
+                    name,
+                    json!({"filename": filename, "content_type": part_type, "size": content.len()}),
+                );
+            }
+            None => fields.push((name, content.to_string())),
+        }
+    }
+
+    let JsonValue::Object(mut object) = coerce(schema, fields) else {
+        unreachable!("coerce returns an object");
+    };
+    object.extend(files);
+    Ok(JsonValue::Object(object))
+}
+
+pub struct PluginRequestValidation {
+    config: PluginConfig,
+}
+
+impl PluginRequestValidation {
+    fn check_head(&self, req: &RequestHeader) -> Result<(), Violation> {
+        if let Some(schema) = &self.config.header_schema {
+            json_schema::validate(schema, &coerce(schema, header_values(req)))
+                .map_err(|e| Violation::new("header", e))?;
+        }
+        if let Some(schema) = &self.config.query_schema {
+            json_schema::validate(schema, &coerce(schema, query_values(req)))
+                .map_err(|e| Violation::new("query", e))?;
+        }
+        Ok(())
+    }
+
+    /// Read the whole body, keeping it in the retry buffer for the upstream.
+    async fn read_body(&self, session: &mut Session) -> Result<Result<Bytes, Violation>> {
+        session.enable_retry_buffering();
+        let mut body = BytesMut::new();
+        while let Some(chunk) = session.read_request_body().await? {
+            body.extend_from_slice(&chunk);
+            if body.len() > self.config.max_body_bytes {
+                return Ok(Err(Violation {
+                    status: Some(413),
+                    ..Violation::new(
+                        "body",
+                        format!("body exceeds {} bytes", self.config.max_body_bytes),
+                    )
+                }));
+            }
+        }
+        Ok(Ok(body.freeze()))
+    }
+
+    async fn check_body(
+        &self,
+        session: &mut Session,
+        ctx: &ProxyContext,
+        schema: &JsonValue,
+    ) -> Result<Result<(), Violation>> {
+        let body = match self.read_body(session).await? {
+            Ok(body) => body,
+            Err(violation) => return Ok(Err(violation)),
+        };
+        let body = match request_decompression::decompress_buffered(ctx, body) {
+            Ok(body) => body,
+            Err(e) => {
+                return Ok(Err(Violation {
+                    status: Some(e.status()),
+                    ..Violation::new("body", e.to_string())
+                }))
+            }
+        };
+        if body.is_empty() {
+            return Ok(Err(Violation::new("body", "request body is required")));
+        }
+        let content_type = session
+            .req_header()
+            .headers
+            .get(header::CONTENT_TYPE)
+            .and_then(|v| v.to_str().ok())
+            .unwrap_or_default();
+        Ok(parse_body(content_type, &body, schema)
+            .and_then(|value| json_schema::validate(schema, &value))
+            .map_err(|e| Violation::new("body", e)))
+    }
+
+    async fn reject(&self, session: &mut Session, violation: Violation) -> Result<()> {
+        let status = violation.status.unwrap_or(self.config.rejected_code);
+        let body = violation.body().to_string();
+        let mut resp = ResponseHeader::build(
+            StatusCode::from_u16(status).unwrap_or(StatusCode::BAD_REQUEST),
+            None,
+        )?;
+        resp.insert_header(header::CONTENT_TYPE, content_type::APPLICATION_JSON)?;
+        resp.insert_header(header::CONTENT_LENGTH, body.len().to_string())?;
+        session.write_response_header(Box::new(resp), false).await?;
+        session
+            .write_response_body(Some(Bytes::from(body)), true)
+            .await
+    }
+}
+
+#[async_trait]
+impl ProxyPlugin for PluginRequestValidation {
+    fn name(&self) -> &str {
+        PLUGIN_NAME
+    }
+
+    fn priority(&self) -> i32 {
+        PRIORITY
+    }
+
+    async fn request_filter(&self, session: &mut Session, ctx: &mut ProxyContext) -> Result<bool> {
+        let mut result = self.check_head(session.req_header());
+        if result.is_ok() {
+            if let Some(schema) = &self.config.body_schema {
+                result = self.check_body(session, ctx, schema).await?;
+            }
+        }
+        match result {
+            Ok(()) => Ok(false),
+            Err(violation) => {
+                self.reject(session, violation).await?;
+                Ok(true)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn text_values_are_coerced_to_the_schema_types() {
+        let schema = json!({
+            "type": "object",
+            "required": ["page"],
+            "properties": {
+                "page": {"type": "integer", "minimum": 1},
+                "draft": {"type": "boolean"},
+                "q": {"type": "string"}
+            }
+        });
+        let mut req = RequestHeader::build("GET", b"/search?page=2&draft=true&q=10", None).unwrap();
+        let query = coerce(&schema, query_values(&req));
+        assert_eq!(query, json!({"page": 2, "draft": true, "q": "10"}));
+        assert!(json_schema::validate(&schema, &query).is_ok());
+
+        req.set_uri("/search?page=zero".parse().unwrap());
+        let error =
+            json_schema::validate(&schema, &coerce(&schema, query_values(&req))).unwrap_err();
+        assert_eq!(
+            Violation::new("query", error).body(),
+            json!({
+                "error": "invalid_request",
+                "details": [{"in": "query", "path": "/page", "message": "expected integer"}]
+            })
+        );
+    }
+
+    #[test]
+    fn bodies_are_parsed_by_content_type() {
+        let schema = json!({"properties": {"age": {"type": "integer"}}});
+        assert_eq!(
+            parse_body("application/json", br#"{"age": 3}"#, &schema).unwrap(),
+            json!({"age": 3})
+        );
+        assert_eq!(
+            parse_body(
+                "application/x-www-form-urlencoded",
+                b"age=3&name=a+b",
+                &schema
+            )
+            .unwrap(),
+            json!({"age": 3, "name": "a b"})
+        );
+        assert!(parse_body("text/plain", b"age", &schema)
+            .unwrap_err()
+            .contains("unsupported content type"));
+
+        let multipart = "--XyZ\r\nContent-Disposition: form-data; name=\"age\"\r\n\r\n41\r\n\
+            --XyZ\r\nContent-Disposition: form-data; name=\"avatar\"; filename=\"me.png\"\r\n\
+            Content-Type: image/png\r\n\r\nPNGDATA\r\n--XyZ--\r\n";
+        assert_eq!(
+            parse_body(
+                "multipart/form-data; boundary=XyZ",
+                multipart.as_bytes(),
+                &schema
+            )
+            .unwrap(),
+            json!({
+                "age": 41,
+                "avatar": {"filename": "me.png", "content_type": "image/png", "size": 7}
+            })
+        );
+        assert!(parse_body("multipart/form-data", multipart.as_bytes(), &schema).is_err());
+    }
+}
@@ -2,13 +2,16 @@ use std::collections::HashMap;
 use std::sync::Arc;
 
 use async_trait::async_trait;
-use http::StatusCode;
+use bytes::Bytes;
+use http::{header, StatusCode};
+use once_cell::sync::Lazy;
 use pingora_error::Result;
 use pingora_http::ResponseHeader;
 use pingora_proxy::Session;
+use regex::{bytes::Regex as BytesRegex, Regex};
 use serde::{Deserialize, Serialize};
-use serde_json::Value as JsonValue;
-use validator::Validate;
+use serde_json::{json, Value as JsonValue};
+use validator::{Validate, ValidationError};
 
 use crate::{
     config::UpstreamHashOn,
@@ -17,11 +20,73 @@ use crate::{
 };
 
 pub const PLUGIN_NAME: &str = "response-rewrite";
-const PRIORITY: i32 = 899;
+pub const PRIORITY: i32 = 899;
+
+/// Context key for the per-request body rewrite state.
+const BODY_STATE_KEY: &str = "response-rewrite-body";
+
+/// Template variables expanded in header values and body replacements.
+static TEMPLATE_VAR_RE: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(r"\$(remote_addr|upstream_addr|request_id)\b")
+        .expect("template variable regex must compile")
+});
 
 pub fn create_response_rewrite_plugin(cfg: JsonValue) -> ProxyResult<Arc<dyn ProxyPlugin>> {
     let config = PluginConfig::try_from(cfg)?;
-    Ok(Arc::new(PluginResponseRewrite { config }))
+
+    // Validation ensures every pattern compiles; propagate error if it somehow doesn't
+    let filters = config
+        .filters
+        .iter()
+        .map(|f| {
+            BytesRegex::new(&f.regex)
+                .map(|re| (re, f.scope == FilterScope::Global))
+                .map_err(|e| {
+                    ProxyError::Plugin(format!(
+                        "Invalid response-rewrite regex pattern '{}': {e}",
+                        f.regex
+                    ))
+                })
+        })
+        .collect::<ProxyResult<Vec<_>>>()?;
+
+    Ok(Arc::new(PluginResponseRewrite { config, filters }))
+}
+
+/// JSON Schema of the response-rewrite plugin configuration.
+pub fn schema() -> JsonValue {
+    let header_map = json!({"type": "object", "additionalProperties": {"type": "string"}});
+    json!({
+        "type": "object",
+        "properties": {
+            "status_code": {"type": "integer", "minimum": 0},
+            "headers": {"anyOf": [
+                header_map,
+                {
+                    "type": "object",
+                    "properties": {
+                        "add": {"type": "array", "items": {"type": "string"}},
+                        "set": header_map,
+                        "remove": {"type": "array", "items": {"type": "string"}}
+                    }
+                }
+            ]},
+            "vars": {"type": "array", "items": {"type": "array", "items": {"type": "string"}}},
+            "filters": {
+                "type": "array",
+                "items": {
+                    "type": "object",
+                    "required": ["regex", "replace"],
+                    "properties": {
+                        "regex": {"type": "string"},
+                        "replace": {"type": "string"},
+                        "scope": {"type": "string", "enum": ["once", "global"], "default": "once"}
+                    }
+                }
+            },
+            "max_match_bytes": {"type": "integer", "minimum": 1, "maximum": 1048576, "default": 4096}
+        }
+    })
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -40,12 +105,55 @@ enum HeadersConfig {
     },
 }
 
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum FilterScope {
+    /// Replace only the first match in the body.
+    #[default]
+    Once,
+    /// Replace every match in the body.
+    Global,
+}
+
+/// Body replacement rule applied to the response as it streams through.
 #[derive(Debug, Serialize, Deserialize, Validate)]
-struct PluginConfig {
+struct BodyFilter {
+    #[validate(custom(function = "PluginConfig::validate_regex"))]
+    regex: String,
+    /// Replacement text. `$1`/`${name}` refer to capture groups; template variables
+    /// such as `$request_id` are expanded once per request.
+    replace: String,
+    #[serde(default)]
+    scope: FilterScope,
+}
+
+#[derive(Debug, Serialize, Deserialize, Validate)]
+pub(super) struct PluginConfig {
     status_code: Option<u16>,
     headers: Option<HeadersConfig>,
     /// Format like [["arg_name", "==", "val"], ["http_x", "!=", "reg"]]
     vars: Option<Vec<Vec<String>>>,
+    /// Regex replacements applied to the response body, in order.
+    #[serde(default)]
+    #[validate(nested)]
+    filters: Vec<BodyFilter>,
+    /// Longest match a body filter is expected to produce. This many trailing bytes
+    /// are held back between chunks so matches spanning chunk boundaries are found.
+    #[serde(default = "PluginConfig::default_max_match_bytes")]
+    #[validate(range(min = 1, max = 1048576))]
+    max_match_bytes: usize,
+}
+
+impl PluginConfig {
+    fn default_max_match_bytes() -> usize {
+        4096
+    }
+
+    fn validate_regex(pattern: &str) -> Result<(), ValidationError> {
+        BytesRegex::new(pattern)
+            .map(|_| ())
+            .map_err(|_| ValidationError::new("invalid_regex"))
+    }
 }
 
 impl TryFrom<JsonValue> for PluginConfig {
@@ -62,6 +170,77 @@ impl TryFrom<JsonValue> for PluginConfig {
 
 pub struct PluginResponseRewrite {
     config: PluginConfig,
+    filters: Vec<(BytesRegex, bool)>, // Precompiled body regex and global-scope flag
+}
+
+/// Streaming state of one body filter for the current response.
+struct FilterStage {
+    /// Replacement with template variables already expanded.
+    replacement: Vec<u8>,
+    /// Unemitted tail that may still be part of a match completed by the next chunk.
+    carry: Vec<u8>,
+    /// Set once a `once`-scoped filter has replaced its match.
+    done: bool,
+}
+
+impl FilterStage {
+    fn new(replacement: Vec<u8>) -> Self {
+        Self {
+            replacement,
+            carry: Vec::new(),
+            done: false,
+        }
+    }
+
+    /// Feed `input` through the filter and return the bytes that are safe to emit.
+    ///
+    /// Until the end of stream, the last `window` bytes are retained unless they were
+    /// consumed by a match starting before the window.
+    fn process(
+        &mut self,
+        re: &BytesRegex,
+        global: bool,
+        window: usize,
+        input: &[u8],
+        end_of_stream: bool,
+    ) -> Vec<u8> {
+        self.carry.extend_from_slice(input);
+        let buf = std::mem::take(&mut self.carry);
+        if self.done {
+            return buf;
+        }
+
+        let safe = if end_of_stream {
+            buf.len()
+        } else {
+            buf.len().saturating_sub(window)
+        };
+        let mut out = Vec::with_capacity(buf.len());
+        let mut last = 0;
+        for caps in re.captures_iter(&buf) {
+            let Some(m) = caps.get(0) else { continue };
+            if !end_of_stream && m.start() >= safe {
+                break;
+            }
+            out.extend_from_slice(&buf[last..m.start()]);
+            caps.expand(&self.replacement, &mut out);
+            last = m.end();
+            if !global {
+                self.done = true;
+                break;
+            }
+        }
+
+        let cut = if self.done { buf.len() } else { last.max(safe) };
+        out.extend_from_slice(&buf[last..cut]);
+        self.carry = buf[cut..].to_vec();
+        out
+    }
+}
+
+/// Per-request body rewrite state stored in the proxy context.
+struct BodyRewriteState {
+    stages: Vec<FilterStage>,
 }
 
 impl PluginResponseRewrite {
@@ -103,28 +282,74 @@ impl PluginResponseRewrite {
         true
     }
 
-    /// Expand header templates by swapping `$var` placeholders with actual values.
-    fn expand_vars(&self, session: &mut Session, val: &str) -> String {
+    /// Expand `$var` placeholders with actual values.
+    ///
+    /// With `escape_dollar`, `$` in substituted values is doubled so the result can be
+    /// used as a regex replacement without values being read as capture references.
+    fn expand_vars(
+        &self,
+        session: &mut Session,
+        ctx: &ProxyContext,
+        val: &str,
+        escape_dollar: bool,
+    ) -> String {
         if !val.contains('$') {
             return val.to_string();
         }
 
-        // Minimal implementation; extend with regex matching if more placeholders are introduced.
-        let mut result = val.to_string();
-        let placeholders = ["$remote_addr", "$upstream_addr", "$request_id"];
-
-        for p in placeholders {
-            if result.contains(p) {
-                let actual = match p {
-                    "$remote_addr" => {
+        TEMPLATE_VAR_RE
+            .replace_all(val, |caps: &regex::Captures| {
+                let actual = match &caps[1] {
+                    "remote_addr" => {
                         request_selector_key(session, &UpstreamHashOn::VARS, "remote_addr")
+                            .into_owned()
                     }
-                    _ => std::borrow::Cow::Borrowed(""),
+                    "upstream_addr" => ctx
+                        .peer
+                        .as_ref()
+                        .map(|peer| peer._address.to_string())
+                        .unwrap_or_default(),
+                    "request_id" => ctx.request_id().unwrap_or_default().to_string(),
+                    _ => String::new(),
                 };
-                result = result.replace(p, &actual);
-            }
+                if escape_dollar {
+                    actual.replace('$', "$$")
+                } else {
+                    actual
+                }
+            })
+            .into_owned()
+    }
+
+    /// Prepare streaming body replacement and drop headers invalidated by it.
+    fn start_body_rewrite(
+        &self,
+        session: &mut Session,
+        upstream_response: &mut ResponseHeader,
+        ctx: &mut ProxyContext,
+    ) {
+        // Regexes operate on the raw bytes, so encoded bodies are left untouched.
+        let encoded = upstream_response
+            .headers
+            .get(header::CONTENT_ENCODING)
+            .is_some_and(|v| !v.as_bytes().eq_ignore_ascii_case(b"identity"));
+        if encoded {
+            log::debug!("response-rewrite: skipping body filters for encoded response");
+            return;
+        }
+
+        let stages = self
+            .config
+            .filters
+            .iter()
+            .map(|f| FilterStage::new(self.expand_vars(session, ctx, &f.replace, true).into()))
+            .collect();
+        ctx.set(BODY_STATE_KEY, BodyRewriteState { stages });
+
+        upstream_response.remove_header(&header::CONTENT_LENGTH);
+        if !session.is_http2() {
+            let _ = upstream_response.insert_header(header::TRANSFER_ENCODING, "chunked");
         }
-        result
     }
 }
 
@@ -137,11 +362,15 @@ impl ProxyPlugin for PluginResponseRewrite {
         PRIORITY
     }
 
+    fn has_response_body_filter(&self) -> bool {
+        !self.filters.is_empty()
+    }
+
     async fn response_filter(
         &self,
         session: &mut Session,
         upstream_response: &mut ResponseHeader,
-        _ctx: &mut ProxyContext,
+        ctx: &mut ProxyContext,
     ) -> Result<()> {
         // 1. Check matching conditions
         if !self.match_vars(session, &self.config.vars) {
@@ -160,7 +389,7 @@ impl ProxyPlugin for PluginResponseRewrite {
             match h_cfg {
                 HeadersConfig::Simple(headers) => {
                     for (k, v) in headers {
-                        let val = self.expand_vars(session, v);
+                        let val = self.expand_vars(session, ctx, v, false);
                         upstream_response.insert_header(k.clone(), val)?;
                     }
                 }
@@ -171,13 +400,13 @@ impl ProxyPlugin for PluginResponseRewrite {
                     }
                     // Set
                     for (k, v) in set {
-                        let val = self.expand_vars(session, v);
+                        let val = self.expand_vars(session, ctx, v, false);
                         upstream_response.insert_header(k.clone(), val)?;
                     }
                     // Add
                     for entry in add {
                         if let Some((k, v)) = entry.split_once(':') {
-                            let val = self.expand_vars(session, v.trim());
+                            let val = self.expand_vars(session, ctx, v.trim(), false);
                             upstream_response.append_header(k.trim().to_string(), val)?;
                         }
                     }
@@ -185,6 +414,78 @@ impl ProxyPlugin for PluginResponseRewrite {
             }
         }
 
+        // 4. Arm streaming body filters; streaming responses are left untouched
+        if !self.filters.is_empty() && !ctx.streaming {
+            self.start_body_rewrite(session, upstream_response, ctx);
+        }
+
         Ok(())
     }
+
+    fn response_body_filter(
+        &self,
+        _session: &mut Session,
+        body: &mut Option<Bytes>,
+        end_of_stream: bool,
+        ctx: &mut ProxyContext,
+    ) -> Result<()> {
+        let Some(state) = ctx.get_mut::<BodyRewriteState>(BODY_STATE_KEY) else {
+            return Ok(());
+        };
+
+        let mut data = body.take().map(|b| b.to_vec()).unwrap_or_default();
+        for (stage, (re, global)) in state.stages.iter_mut().zip(&self.filters) {
+            data = stage.process(
+                re,
+                *global,
+                self.config.max_match_bytes,
+                &data,
+                end_of_stream,
+            );
+        }
+
+        // Emitting an empty chunk mid-stream could terminate a chunked response.
+        *body = (!data.is_empty()).then(|| Bytes::from(data));
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn run(pattern: &str, replace: &str, global: bool, window: usize, chunks: &[&str]) -> String {
+        let re = BytesRegex::new(pattern).unwrap();
+        let mut stage = FilterStage::new(replace.as_bytes().to_vec());
+        let mut out = Vec::new();
+        for (i, chunk) in chunks.iter().enumerate() {
+            let eos = i + 1 == chunks.len();
+            out.extend(stage.process(&re, global, window, chunk.as_bytes(), eos));
+        }
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn matches_spanning_chunk_boundaries_are_replaced() {
+        let out = run(
+            "hello world",
+            "bye",
+            true,
+            16,
+            &["say hel", "lo wo", "rld and hello world", ""],
+        );
+        assert_eq!(out, "say bye and bye");
+    }
+
+    #[test]
+    fn once_scope_replaces_first_match_only() {
+        let out = run("a(\\d)", "b$1", false, 4, &["xa1a", "2a3"]);
+        assert_eq!(out, "xb1a2a3");
+    }
+
+    #[test]
+    fn config_rejects_invalid_body_regex() {
+        let cfg = serde_json::json!({"filters": [{"regex": "(", "replace": "x"}]});
+        assert!(PluginConfig::try_from(cfg).is_err());
+    }
 }
@@ -0,0 +1,269 @@
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use http::HeaderValue;
+use pingora_error::Result;
+use pingora_http::ResponseHeader;
+use pingora_proxy::Session;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value as JsonValue};
+use validator::Validate;
+
+use crate::core::{ProxyContext, ProxyError, ProxyPlugin, ProxyResult};
+
+pub const PLUGIN_NAME: &str = "security-headers";
+pub const PRIORITY: i32 = 890;
+
+/// Context key holding the policy of the most specific plugin instance.
+const POLICY_CTX_KEY: &str = "security-headers-policy";
+
+const DEFAULT_HSTS: &str = "max-age=31536000; includeSubDomains";
+const DEFAULT_CONTENT_TYPE_OPTIONS: &str = "nosniff";
+const DEFAULT_FRAME_OPTIONS: &str = "SAMEORIGIN";
+const DEFAULT_REFERRER_POLICY: &str = "strict-origin-when-cross-origin";
+const DEFAULT_CSP: &str =
+    "default-src 'self'; object-src 'none'; base-uri 'self'; frame-ancestors 'self'";
+
+/// Creates a security-headers plugin from its configuration.
+pub fn create_security_headers_plugin(cfg: JsonValue) -> ProxyResult<Arc<dyn ProxyPlugin>> {
+    let config = PluginConfig::try_from(cfg)?;
+    let policy = Arc::new(config.policy()?);
+    Ok(Arc::new(PluginSecurityHeaders { policy }))
+}
+
+/// JSON Schema of the security-headers plugin configuration.
+pub fn schema() -> JsonValue {
+    let setting = |default: bool| {
+        json!({
+            "anyOf": [{"type": "boolean"}, {"type": "string", "minLength": 1}],
+            "default": default
+        })
+    };
+    json!({
+        "type": "object",
+        "properties": {
+            "hsts": setting(true),
+            "content_type_options": setting(true),
+            "frame_options": setting(true),
+            "referrer_policy": setting(true),
+            "content_security_policy": setting(false),
+            "override_upstream": {"type": "boolean", "default": false}
+        }
+    })
+}
+
+/// A header toggle: `true` sends the default value, `false` omits the header, and a
+/// string sends that value.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(untagged)]
+enum HeaderSetting {
+    Enabled(bool),
+    Value(String),
+}
+
+impl HeaderSetting {
+    fn resolve(&self, default: &str) -> Option<String> {
+        match self {
+            Self::Enabled(true) => Some(default.to_string()),
+            Self::Enabled(false) => None,
+            Self::Value(value) => Some(value.clone()),
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Validate)]
+pub(super) struct PluginConfig {
+    /// `Strict-Transport-Security`, on by default.
+    #[serde(default = "PluginConfig::enabled")]
+    hsts: HeaderSetting,
+    /// `X-Content-Type-Options`, on by default.
+    #[serde(default = "PluginConfig::enabled")]
+    content_type_options: HeaderSetting,
+    /// `X-Frame-Options`, on by default.
+    #[serde(default = "PluginConfig::enabled")]
+    frame_options: HeaderSetting,
+    /// `Referrer-Policy`, on by default.
+    #[serde(default = "PluginConfig::enabled")]
+    referrer_policy: HeaderSetting,
+    /// `Content-Security-Policy`, off by default since a policy has to fit the pages.
+    #[serde(default = "PluginConfig::disabled")]
+    content_security_policy: HeaderSetting,
+    /// Replace values the upstream already set instead of keeping them.
+    #[serde(default)]
+    override_upstream: bool,
+}
+
+impl PluginConfig {
+    fn enabled() -> HeaderSetting {
+        HeaderSetting::Enabled(true)
+    }
+
+    fn disabled() -> HeaderSetting {
+        HeaderSetting::Enabled(false)
+    }
+
+    /// Resolves the settings into the headers to send, validating each value.
+    fn policy(&self) -> ProxyResult<Policy> {
+        let settings = [
+            ("strict-transport-security", &self.hsts, DEFAULT_HSTS),
+            (
+                "x-content-type-options",
+                &self.content_type_options,
+                DEFAULT_CONTENT_TYPE_OPTIONS,
+            ),
+            (
+                "x-frame-options",
+                &self.frame_options,
+                DEFAULT_FRAME_OPTIONS,
+            ),
+            (
+                "referrer-policy",
+                &self.referrer_policy,
+                DEFAULT_REFERRER_POLICY,
+            ),
+            (
+                "content-security-policy",
+                &self.content_security_policy,
+                DEFAULT_CSP,
+            ),
+        ];
+        let mut headers = Vec::new();
+        for (name, setting, default) in settings {
+            let Some(value) = setting.resolve(default) else {
+                continue;
+            };
+            let value = HeaderValue::from_str(&value).map_err(|e| {
+                ProxyError::validation_error(format!("Invalid {name} value '{value}': {e}"))
+            })?;
+            headers.push((name, value));
+        }
+        Ok(Policy {
+            headers,
+            override_upstream: self.override_upstream,
+        })
+    }
+}
+
+impl TryFrom<JsonValue> for PluginConfig {
+    type Error = ProxyError;
+
+    fn try_from(value: JsonValue) -> Result<Self, Self::Error> {
+        let config: PluginConfig = serde_json::from_value(value).map_err(|e| {
+            ProxyError::serialization_error("Invalid security headers plugin config", e)
+        })?;
+        config.validate()?;
+        Ok(config)
+    }
+}
+
+/// Headers one plugin instance sends.
+#[derive(Debug)]
+struct Policy {
+    headers: Vec<(&'static str, HeaderValue)>,
+    override_upstream: bool,
+}
+
+impl Policy {
+    fn apply(&self, resp: &mut ResponseHeader) -> Result<()> {
+        for (name, value) in &self.headers {
+            if self.override_upstream || resp.headers.get(*name).is_none() {
+                resp.insert_header(*name, value.clone())?;
+            }
+        }
+        Ok(())
+    }
+}
+
+pub struct PluginSecurityHeaders {
+    policy: Arc<Policy>,
+}
+
+#[async_trait]
+impl ProxyPlugin for PluginSecurityHeaders {
+    fn name(&self) -> &str {
+        PLUGIN_NAME
+    }
+
+    fn priority(&self) -> i32 {
+        PRIORITY
+    }
+
+    async fn request_filter(&self, _session: &mut Session, ctx: &mut ProxyContext) -> Result<bool> {
+        // Global rules run before route plugins, so the route's policy replaces the
+        // global one and the response gets exactly one set of headers.
+        ctx.set(POLICY_CTX_KEY, Some(self.policy.clone()));
+        Ok(false)
+    }
+
+    async fn response_filter(
+        &self,
+        _session: &mut Session,
+        upstream_response: &mut ResponseHeader,
+        ctx: &mut ProxyContext,
+    ) -> Result<()> {
+        let Some(policy) = ctx.get_mut::<Option<Arc<Policy>>>(POLICY_CTX_KEY) else {
+            return Ok(());
+        };
+        if let Some(policy) = policy.take() {
+            policy.apply(upstream_response)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn policy(cfg: JsonValue) -> Policy {
+        PluginConfig::try_from(cfg).unwrap().policy().unwrap()
+    }
+
+    fn header<'a>(resp: &'a ResponseHeader, name: &str) -> Option<&'a str> {
+        resp.headers.get(name).and_then(|v| v.to_str().ok())
+    }
+
+    #[test]
+    fn defaults_send_everything_but_csp() {
+        let mut resp = ResponseHeader::build(200, None).unwrap();
+        policy(json!({})).apply(&mut resp).unwrap();
+        assert_eq!(
+            header(&resp, "strict-transport-security"),
+            Some(DEFAULT_HSTS)
+        );
+        assert_eq!(header(&resp, "x-content-type-options"), Some("nosniff"));
+        assert_eq!(header(&resp, "x-frame-options"), Some("SAMEORIGIN"));
+        assert_eq!(
+            header(&resp, "referrer-policy"),
+            Some("strict-origin-when-cross-origin")
+        );
+        assert!(header(&resp, "content-security-policy").is_none());
+    }
+
+    #[test]
+    fn overrides_disable_replace_and_respect_upstream_values() {
+        let mut resp = ResponseHeader::build(200, None).unwrap();
+        resp.insert_header("x-frame-options", "DENY").unwrap();
+        policy(json!({
+            "hsts": false,
+            "frame_options": "SAMEORIGIN",
+            "content_security_policy": true
+        }))
+        .apply(&mut resp)
+        .unwrap();
+        assert!(header(&resp, "strict-transport-security").is_none());
+        assert_eq!(header(&resp, "x-frame-options"), Some("DENY"));
+        assert_eq!(header(&resp, "content-security-policy"), Some(DEFAULT_CSP));
+
+        policy(json!({"frame_options": "SAMEORIGIN", "override_upstream": true}))
+            .apply(&mut resp)
+            .unwrap();
+        assert_eq!(header(&resp, "x-frame-options"), Some("SAMEORIGIN"));
+    }
+
+    #[test]
+    fn invalid_header_values_are_rejected() {
+        let config = PluginConfig::try_from(json!({"referrer_policy": "no-referrer\n"})).unwrap();
+        assert!(config.policy().is_err());
+    }
+}
@@ -3,7 +3,7 @@ use pingora_error::Result;
 use pingora_proxy::Session;
 use rand::Rng;
 use serde::{Deserialize, Serialize};
-use serde_json::Value as JsonValue;
+use serde_json::{json, Value as JsonValue};
 use std::{collections::HashMap, sync::Arc};
 use validator::Validate;
 
@@ -15,7 +15,7 @@ use crate::proxy::upstream::{traffic_split_key, PreparedUpstreams, ProxyUpstream
 use crate::utils::request::request_selector_key;
 
 pub const PLUGIN_NAME: &str = "traffic-split";
-const PRIORITY: i32 = 966;
+pub const PRIORITY: i32 = 966;
 
 #[derive(Debug, Serialize, Deserialize, Validate)]
 struct WeightedUpstream {
@@ -34,9 +34,9 @@ struct MatchRule {
 }
 
 #[derive(Debug, Serialize, Deserialize, Validate)]
-struct PluginConfig {
+pub(super) struct PluginConfig {
     #[validate(nested)]
-    pub rules: Vec<MatchRule>,
+    rules: Vec<MatchRule>,
 }
 
 /// Weighted target after compilation. `PassThrough` keeps the route default upstream.
@@ -173,10 +173,47 @@ pub fn create_traffic_split_plugin(cfg: JsonValue) -> ProxyResult<Arc<dyn ProxyP
     create_traffic_split_plugin_with_upstreams(cfg, &HashMap::new(), &HashMap::new(), "admin")
 }
 
+/// JSON Schema of the traffic-split plugin configuration. Inline upstreams are checked
+/// against the upstream schema when the plugin is built.
+pub fn schema() -> JsonValue {
+    json!({
+        "type": "object",
+        "required": ["rules"],
+        "properties": {
+            "rules": {
+                "type": "array",
+                "minItems": 1,
+                "items": {
+                    "type": "object",
+                    "required": ["weighted_upstreams"],
+                    "properties": {
+                        "vars": {
+                            "type": "array",
+                            "items": {"type": "array", "items": {"type": "string"}}
+                        },
+                        "weighted_upstreams": {
+                            "type": "array",
+                            "items": {
+                                "type": "object",
+                                "required": ["weight"],
+                                "properties": {
+                                    "upstream_id": {"type": "string"},
+                                    "upstream": {"type": "object"},
+                                    "weight": {"type": "integer", "minimum": 0}
+                                }
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    })
+}
+
 /// Validate traffic-split JSON without resolving named upstreams (Admin pre-check).
 pub fn validate_traffic_split_config(cfg: &JsonValue) -> ProxyResult<()> {
     let config: PluginConfig = serde_json::from_value(cfg.clone())
-        .map_err(|e| ProxyError::Serialization(e.to_string()))?;
+        .map_err(|e| ProxyError::serialization_error("Invalid traffic split plugin config", e))?;
     config.validate()?;
     if config.rules.is_empty() {
         return Err(ProxyError::Plugin(
@@ -219,7 +256,7 @@ pub fn validate_traffic_split_config(cfg: &JsonValue) -> ProxyResult<()> {
 /// checked against the same resource set as `CandidateSnapshot::build`.
 pub fn named_upstream_ids(cfg: &JsonValue) -> ProxyResult<Vec<String>> {
     let config: PluginConfig = serde_json::from_value(cfg.clone())
-        .map_err(|e| ProxyError::Serialization(e.to_string()))?;
+        .map_err(|e| ProxyError::serialization_error("Invalid traffic split plugin config", e))?;
     let mut ids = Vec::new();
     for rule in &config.rules {
         for wu in &rule.weighted_upstreams {
@@ -237,8 +274,8 @@ pub(crate) fn create_traffic_split_plugin_with_upstreams(
     prepared: &PreparedUpstreams,
     owner: &str,
 ) -> ProxyResult<Arc<dyn ProxyPlugin>> {
-    let config: PluginConfig =
-        serde_json::from_value(cfg).map_err(|e| ProxyError::Serialization(e.to_string()))?;
+    let config: PluginConfig = serde_json::from_value(cfg)
+        .map_err(|e| ProxyError::serialization_error("Invalid traffic split plugin config", e))?;
 
     config.validate()?;
 
@@ -345,6 +382,11 @@ mod tests {
             pass_host: UpstreamPassHost::PASS,
             upstream_host: None,
             tls: None,
+            zone_aware: None,
+            discovery_type: None,
+            priorities: HashMap::new(),
+            sticky: None,
+            labels: Default::default(),
         }
     }
 
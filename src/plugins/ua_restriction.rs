@@ -0,0 +1,251 @@
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use http::StatusCode;
+use pingora_error::Result;
+use pingora_proxy::Session;
+use regex::{RegexSet, RegexSetBuilder};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value as JsonValue};
+use validator::{Validate, ValidationError};
+
+use crate::{
+    core::{ProxyContext, ProxyError, ProxyPlugin, ProxyResult},
+    utils::{request::get_req_header_value, response::ResponseBuilder},
+};
+
+pub const PLUGIN_NAME: &str = "ua-restriction";
+pub const PRIORITY: i32 = 2999;
+
+/// Context key holding the user-agent classification, readable as `$var_ua_class` in
+/// file-logger formats and as `key: var_ua_class` in limit-count.
+pub const UA_CLASS_CTX_KEY: &str = "ua_class";
+
+/// Patterns enabled by `bot_detection`: crawlers, headless browsers and HTTP libraries.
+const BOT_PATTERNS: &[&str] = &[
+    r"bot\b|crawl|spider|slurp|archiver|facebookexternalhit",
+    r"headlesschrome|phantomjs|selenium|puppeteer|playwright",
+    r"^(curl|wget|httpie|python-requests|python-urllib|aiohttp|go-http-client|okhttp)\b",
+    r"^(java|libwww-perl|scrapy|apache-httpclient|node-fetch|axios)\b",
+];
+
+pub fn create_ua_restriction_plugin(cfg: JsonValue) -> ProxyResult<Arc<dyn ProxyPlugin>> {
+    let config = PluginConfig::try_from(cfg)?;
+    Ok(Arc::new(PluginUaRestriction::new(config)?))
+}
+
+/// JSON Schema of the ua-restriction plugin configuration.
+pub fn schema() -> JsonValue {
+    let patterns = json!({"type": "array", "items": {"type": "string"}});
+    json!({
+        "type": "object",
+        "properties": {
+            "allowlist": patterns,
+            "denylist": patterns,
+            "bot_detection": {"type": "boolean", "default": false},
+            "bypass_missing": {"type": "boolean", "default": false},
+            "action": {"type": "string", "enum": ["reject", "tag"], "default": "reject"},
+            "rejected_code": {"type": "integer", "minimum": 200, "maximum": 599, "default": 403},
+            "rejected_msg": {"type": "string"}
+        }
+    })
+}
+
+/// Compile user-agent patterns into a case-insensitive set.
+fn compile<I, S>(patterns: I) -> ProxyResult<RegexSet>
+where
+    I: IntoIterator<Item = S>,
+    S: AsRef<str>,
+{
+    RegexSetBuilder::new(patterns)
+        .case_insensitive(true)
+        .build()
+        .map_err(|e| ProxyError::Plugin(format!("Invalid ua-restriction pattern: {e}")))
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum UaAction {
+    /// Reject matching requests.
+    #[default]
+    Reject,
+    /// Let matching requests through and record the classification in the context.
+    Tag,
+}
+
+#[derive(Debug, Serialize, Deserialize, Validate)]
+#[validate(schema(function = "PluginConfig::validate_has_rules"))]
+pub(super) struct PluginConfig {
+    /// User-agent regexes that are always let through, checked first. When it is the
+    /// only rule source, every other user agent is denied.
+    #[serde(default)]
+    #[validate(custom(function = "PluginConfig::validate_patterns"))]
+    allowlist: Vec<String>,
+    /// User-agent regexes handled according to `action`.
+    #[serde(default)]
+    #[validate(custom(function = "PluginConfig::validate_patterns"))]
+    denylist: Vec<String>,
+    /// Also treat well-known crawlers, headless browsers and HTTP libraries as bots.
+    #[serde(default)]
+    bot_detection: bool,
+    /// Let requests without a `User-Agent` header through instead of handling them as denied.
+    #[serde(default)]
+    bypass_missing: bool,
+    #[serde(default)]
+    action: UaAction,
+    #[serde(default = "PluginConfig::default_rejected_code")]
+    #[validate(range(min = 200, max = 599))]
+    rejected_code: u16,
+    rejected_msg: Option<String>,
+}
+
+impl PluginConfig {
+    fn default_rejected_code() -> u16 {
+        403
+    }
+
+    fn validate_has_rules(&self) -> Result<(), ValidationError> {
+        if self.allowlist.is_empty() && self.denylist.is_empty() && !self.bot_detection {
+            return Err(ValidationError::new("no_ua_rules"));
+        }
+        Ok(())
+    }
+
+    fn validate_patterns(patterns: &[String]) -> Result<(), ValidationError> {
+        RegexSet::new(patterns)
+            .map(|_| ())
+            .map_err(|_| ValidationError::new("invalid_regex"))
+    }
+}
+
+impl TryFrom<JsonValue> for PluginConfig {
+    type Error = ProxyError;
+
+    fn try_from(value: JsonValue) -> Result<Self, Self::Error> {
+        let config: PluginConfig = serde_json::from_value(value).map_err(|e| {
+            ProxyError::serialization_error("Failed to parse ua-restriction plugin config", e)
+        })?;
+        config.validate()?;
+        Ok(config)
+    }
+}
+
+pub struct PluginUaRestriction {
+    config: PluginConfig,
+    allowlist: RegexSet,
+    denylist: RegexSet,
+    bots: Option<RegexSet>,
+}
+
+impl PluginUaRestriction {
+    fn new(config: PluginConfig) -> ProxyResult<Self> {
+        Ok(Self {
+            allowlist: compile(&config.allowlist)?,
+            denylist: compile(&config.denylist)?,
+            bots: config
+                .bot_detection
+                .then(|| compile(BOT_PATTERNS))
+                .transpose()?,
+            config,
+        })
+    }
+
+    /// Classify a user agent: `None` when allowed, otherwise the tag to record.
+    fn classify(&self, user_agent: Option<&str>) -> Option<&'static str> {
+        let Some(ua) = user_agent else {
+            return (!self.config.bypass_missing).then_some("missing");
+        };
+        if self.allowlist.is_match(ua) {
+            return None;
+        }
+        if self.bots.as_ref().is_some_and(|bots| bots.is_match(ua)) {
+            return Some("bot");
+        }
+        // An allowlist on its own admits only the user agents it names.
+        let allowlist_only = self.bots.is_none() && self.config.denylist.is_empty();
+        (allowlist_only || self.denylist.is_match(ua)).then_some("denied")
+    }
+}
+
+#[async_trait]
+impl ProxyPlugin for PluginUaRestriction {
+    fn name(&self) -> &str {
+        PLUGIN_NAME
+    }
+
+    fn priority(&self) -> i32 {
+        PRIORITY
+    }
+
+    async fn request_filter(&self, session: &mut Session, ctx: &mut ProxyContext) -> Result<bool> {
+        let ua = get_req_header_value(session.req_header(), "user-agent");
+        let Some(class) = self.classify(ua) else {
+            return Ok(false);
+        };
+
+        if self.config.action == UaAction::Tag {
+            ctx.set(UA_CLASS_CTX_KEY, class.to_string());
+            return Ok(false);
+        }
+
+        let status =
+            StatusCode::from_u16(self.config.rejected_code).unwrap_or(StatusCode::FORBIDDEN);
+        ResponseBuilder::send_proxy_error(
+            session,
+            status,
+            self.config.rejected_msg.as_deref(),
+            None,
+        )
+        .await?;
+        Ok(true)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn plugin(cfg: JsonValue) -> PluginUaRestriction {
+        PluginUaRestriction::new(PluginConfig::try_from(cfg).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn allowlist_overrides_bot_detection() {
+        let p = plugin(
+            json!({"allowlist": ["googlebot"], "denylist": ["^evil"], "bot_detection": true}),
+        );
+        assert_eq!(
+            p.classify(Some("Mozilla/5.0 (compatible; Googlebot/2.1)")),
+            None
+        );
+        assert_eq!(p.classify(Some("curl/8.4.0")), Some("bot"));
+        assert_eq!(p.classify(Some("EvilScanner")), Some("denied"));
+        assert_eq!(p.classify(Some("Mozilla/5.0 (X11; Linux x86_64)")), None);
+        assert_eq!(p.classify(None), Some("missing"));
+    }
+
+    #[test]
+    fn allowlist_alone_denies_other_user_agents() {
+        let p = plugin(json!({"allowlist": ["^internal-client/"]}));
+        assert_eq!(p.classify(Some("internal-client/1.2")), None);
+        assert_eq!(
+            p.classify(Some("Mozilla/5.0 (X11; Linux x86_64)")),
+            Some("denied")
+        );
+        assert_eq!(p.classify(None), Some("missing"));
+    }
+
+    #[test]
+    fn bypass_missing_allows_requests_without_user_agent() {
+        let p = plugin(json!({"denylist": ["scanner"], "bypass_missing": true}));
+        assert_eq!(p.classify(None), None);
+    }
+
+    #[test]
+    fn config_requires_a_rule_source() {
+        assert!(PluginConfig::try_from(json!({})).is_err());
+        assert!(PluginConfig::try_from(json!({"denylist": ["("]})).is_err());
+        assert!(PluginConfig::try_from(json!({"bot_detection": true, "action": "tag"})).is_ok());
+    }
+}
@@ -0,0 +1,192 @@
+use std::{collections::HashMap, sync::Arc};
+
+use async_trait::async_trait;
+use http::StatusCode;
+use pingora_error::Result;
+use pingora_proxy::Session;
+use regex::{RegexSet, RegexSetBuilder};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value as JsonValue};
+use validator::{Validate, ValidationError};
+
+use crate::{
+    core::{ProxyContext, ProxyError, ProxyPlugin, ProxyResult},
+    utils::response::ResponseBuilder,
+};
+
+pub const PLUGIN_NAME: &str = "uri-blocker";
+pub const PRIORITY: i32 = 2900;
+
+/// Creates a uri-blocker plugin that rejects requests whose URI or headers match a rule.
+///
+/// Rules are compiled into one `RegexSet` per target so each request is scanned once
+/// per target regardless of how many rules are configured.
+pub fn create_uri_blocker_plugin(cfg: JsonValue) -> ProxyResult<Arc<dyn ProxyPlugin>> {
+    let config = PluginConfig::try_from(cfg)?;
+
+    let uri_rules = config.compile(&config.block_rules)?;
+    let header_rules = config
+        .header_rules
+        .iter()
+        .map(|(name, rules)| Ok((name.to_ascii_lowercase(), config.compile(rules)?)))
+        .collect::<ProxyResult<Vec<_>>>()?;
+
+    Ok(Arc::new(PluginUriBlocker {
+        config,
+        uri_rules,
+        header_rules,
+    }))
+}
+
+/// JSON Schema of the uri-blocker plugin configuration.
+pub fn schema() -> JsonValue {
+    let patterns = json!({"type": "array", "items": {"type": "string"}});
+    json!({
+        "type": "object",
+        "properties": {
+            "block_rules": patterns,
+            "header_rules": {"type": "object", "additionalProperties": patterns},
+            "rejected_code": {"type": "integer", "minimum": 200, "maximum": 599, "default": 403},
+            "rejected_msg": {"type": "string"},
+            "case_insensitive": {"type": "boolean", "default": false}
+        }
+    })
+}
+
+#[derive(Debug, Serialize, Deserialize, Validate)]
+#[validate(schema(function = "PluginConfig::validate_has_rules"))]
+pub(super) struct PluginConfig {
+    /// Regexes matched against the request URI (path and query string, as received).
+    #[serde(default)]
+    #[validate(custom(function = "PluginConfig::validate_rules"))]
+    block_rules: Vec<String>,
+    /// Regexes matched against the values of the named request headers.
+    #[serde(default)]
+    #[validate(custom(function = "PluginConfig::validate_header_rules"))]
+    header_rules: HashMap<String, Vec<String>>,
+    #[serde(default = "PluginConfig::default_rejected_code")]
+    #[validate(range(min = 200, max = 599))]
+    rejected_code: u16,
+    rejected_msg: Option<String>,
+    #[serde(default)]
+    case_insensitive: bool,
+}
+
+impl PluginConfig {
+    fn default_rejected_code() -> u16 {
+        403
+    }
+
+    fn validate_has_rules(&self) -> Result<(), ValidationError> {
+        if self.block_rules.is_empty() && self.header_rules.values().all(Vec::is_empty) {
+            return Err(ValidationError::new("no_block_rules"));
+        }
+        Ok(())
+    }
+
+    fn validate_rules(rules: &[String]) -> Result<(), ValidationError> {
+        RegexSet::new(rules)
+            .map(|_| ())
+            .map_err(|_| ValidationError::new("invalid_regex"))
+    }
+
+    fn validate_header_rules(rules: &HashMap<String, Vec<String>>) -> Result<(), ValidationError> {
+        rules
+            .values()
+            .try_for_each(|rules| Self::validate_rules(rules))
+    }
+
+    fn compile(&self, rules: &[String]) -> ProxyResult<RegexSet> {
+        RegexSetBuilder::new(rules)
+            .case_insensitive(self.case_insensitive)
+            .build()
+            .map_err(|e| ProxyError::Plugin(format!("Invalid uri-blocker rule: {e}")))
+    }
+}
+
+impl TryFrom<JsonValue> for PluginConfig {
+    type Error = ProxyError;
+
+    fn try_from(value: JsonValue) -> Result<Self, Self::Error> {
+        let config: PluginConfig = serde_json::from_value(value).map_err(|e| {
+            ProxyError::serialization_error("Failed to parse uri-blocker plugin config", e)
+        })?;
+        config.validate()?;
+        Ok(config)
+    }
+}
+
+pub struct PluginUriBlocker {
+    config: PluginConfig,
+    uri_rules: RegexSet,
+    header_rules: Vec<(String, RegexSet)>, // Lowercased header name and its compiled rules
+}
+
+impl PluginUriBlocker {
+    fn is_blocked(&self, session: &Session) -> bool {
+        let req = session.req_header();
+        if self.uri_rules.is_match(&req.uri.to_string()) {
+            return true;
+        }
+
+        self.header_rules.iter().any(|(name, rules)| {
+            req.headers
+                .get_all(name.as_str())
+                .iter()
+                .filter_map(|v| v.to_str().ok())
+                .any(|v| rules.is_match(v))
+        })
+    }
+}
+
+#[async_trait]
+impl ProxyPlugin for PluginUriBlocker {
+    fn name(&self) -> &str {
+        PLUGIN_NAME
+    }
+
+    fn priority(&self) -> i32 {
+        PRIORITY
+    }
+
+    async fn request_filter(&self, session: &mut Session, _ctx: &mut ProxyContext) -> Result<bool> {
+        if !self.is_blocked(session) {
+            return Ok(false);
+        }
+
+        let status =
+            StatusCode::from_u16(self.config.rejected_code).unwrap_or(StatusCode::FORBIDDEN);
+        ResponseBuilder::send_proxy_error(
+            session,
+            status,
+            self.config.rejected_msg.as_deref(),
+            None,
+        )
+        .await?;
+        Ok(true)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn config_requires_at_least_one_rule() {
+        assert!(PluginConfig::try_from(json!({})).is_err());
+        assert!(PluginConfig::try_from(json!({"block_rules": ["("]})).is_err());
+        assert!(PluginConfig::try_from(json!({"header_rules": {"user-agent": ["curl"]}})).is_ok());
+    }
+
+    #[test]
+    fn case_insensitive_rules_match_any_case() {
+        let config = PluginConfig::try_from(
+            json!({"block_rules": ["select.+from"], "case_insensitive": true}),
+        )
+        .unwrap();
+        let rules = config.compile(&config.block_rules).unwrap();
+        assert!(rules.is_match("/search?q=SELECT%20*%20FROM%20users"));
+        assert!(!rules.is_match("/search?q=selected"));
+    }
+}
@@ -0,0 +1,1274 @@
+//! Native WAF engine for a ModSecurity `SecRule` subset.
+//!
+//! Rule files (for example a trimmed OWASP CRS) and inline rules are parsed when the
+//! plugin is built, so a route only pays for matching at request time. Phase 1 rules
+//! see the request line, query and headers and are evaluated in `request_filter`.
+//! Phase 2 rules also see the body: it is buffered in `request_body_filter` up to
+//! `max_body_bytes` and held back from the upstream until the rules have passed.
+//! `ARGS_POST` is parsed from `application/x-www-form-urlencoded` bodies and
+//! `REQUEST_BODY` holds the raw body of any type. Rules without a `phase` action run
+//! in phase 1.
+//!
+//! Supported: `SecRule` with `|`-separated variables (with `:key`, `:/regex/` and `!`
+//! exclusions), the common string/regex/numeric operators, `chain`, and the `id`,
+//! `phase`, `deny`/`block`/`pass`, `status`, `msg` and `t:` actions. `block` takes its
+//! disruptive action from the preceding `SecDefaultAction` of the rule's phase (`pass`
+//! without one), as in ModSecurity. Rules that need anything else (phase 3 and later,
+//! `TX` collections, `@detectSQLi`, transformations that are not implemented, ...) are
+//! skipped with a warning; other directives are ignored. An unknown transformation
+//! name is an error.
+
+use std::{fs, sync::Arc};
+
+use async_trait::async_trait;
+use bytes::{Bytes, BytesMut};
+use http::{header, StatusCode};
+use once_cell::sync::Lazy;
+use pingora_error::{Error, ErrorType, Result};
+use pingora_http::RequestHeader;
+use pingora_proxy::Session;
+use prometheus::{register_int_counter_vec, IntCounterVec};
+use regex::{Regex, RegexBuilder};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value as JsonValue};
+use tokio::runtime::RuntimeFlavor;
+use validator::Validate;
+
+use crate::{
+    core::{ProxyContext, ProxyError, ProxyPlugin, ProxyResult},
+    utils::{request::request_has_body, response::ResponseBuilder},
+};
+
+pub const PLUGIN_NAME: &str = "waf";
+pub const PRIORITY: i32 = 2910;
+
+/// Context key for the request body buffered for phase 2 rules.
+const BODY_STATE_KEY: &str = "waf-request-body";
+
+static WAF_RULE_MATCHES: Lazy<IntCounterVec> = Lazy::new(|| {
+    register_int_counter_vec!(
+        "pingsix_waf_rule_matches_total",
+        "Requests matched by a WAF rule",
+        &["rule", "mode"]
+    )
+    .expect("waf metric registration must succeed")
+});
+
+/// Creates a WAF plugin from rule files and inline `SecRule` directives.
+pub fn create_waf_plugin(cfg: JsonValue) -> ProxyResult<Arc<dyn ProxyPlugin>> {
+    let config = PluginConfig::try_from(cfg)?;
+
+    let mut rules = Vec::new();
+    for path in &config.rule_files {
+        let text = read_rule_file(path).map_err(|e| {
+            ProxyError::Configuration(format!("Failed to read WAF rule file '{path}': {e}"))
+        })?;
+        rules.extend(parse_rules(&text, path)?);
+    }
+    for (i, text) in config.rules.iter().enumerate() {
+        rules.extend(parse_rules(text, &format!("inline rule #{i}"))?);
+    }
+    if rules.is_empty() {
+        return Err(ProxyError::validation_error(
+            "WAF plugin has no supported rules",
+        ));
+    }
+
+    Ok(Arc::new(PluginWaf { config, rules }))
+}
+
+/// Reads a rule file. Plugins are also built on etcd and Admin API updates, so on a
+/// multi-threaded runtime the worker's other tasks are handed off for the read.
+fn read_rule_file(path: &str) -> std::io::Result<String> {
+    match tokio::runtime::Handle::try_current() {
+        Ok(handle) if handle.runtime_flavor() == RuntimeFlavor::MultiThread => {
+            tokio::task::block_in_place(|| fs::read_to_string(path))
+        }
+        _ => fs::read_to_string(path),
+    }
+}
+
+/// JSON Schema of the waf plugin configuration.
+pub fn schema() -> JsonValue {
+    json!({
+        "type": "object",
+        "properties": {
+            "rule_files": {"type": "array", "items": {"type": "string"}},
+            "rules": {"type": "array", "items": {"type": "string"}},
+            "mode": {"type": "string", "enum": ["block", "detect"], "default": "block"},
+            "rejected_code": {"type": "integer", "minimum": 400, "maximum": 599, "default": 403},
+            "rejected_msg": {"type": "string"},
+            "max_body_bytes": {"type": "integer", "minimum": 1, "default": 1024 * 1024}
+        }
+    })
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum WafMode {
+    /// Reject requests matched by a disruptive rule.
+    #[default]
+    Block,
+    /// Only log and count matches.
+    Detect,
+}
+
+#[derive(Debug, Serialize, Deserialize, Validate)]
+pub(super) struct PluginConfig {
+    /// Paths of ModSecurity-style rule files, read when the plugin is built.
+    #[serde(default)]
+    rule_files: Vec<String>,
+    /// Inline `SecRule` directives.
+    #[serde(default)]
+    rules: Vec<String>,
+    #[serde(default)]
+    mode: WafMode,
+    /// Status used when a denying rule has no `status` action.
+    #[serde(default = "PluginConfig::default_rejected_code")]
+    #[validate(range(min = 400, max = 599))]
+    rejected_code: u16,
+    rejected_msg: Option<String>,
+    /// Largest request body buffered for phase 2 rules. Larger bodies are rejected
+    /// with 413 in block mode; detect mode inspects only the first `max_body_bytes`.
+    #[serde(default = "PluginConfig::default_max_body_bytes")]
+    #[validate(range(min = 1))]
+    max_body_bytes: usize,
+}
+
+impl PluginConfig {
+    fn default_rejected_code() -> u16 {
+        403
+    }
+
+    fn default_max_body_bytes() -> usize {
+        1024 * 1024
+    }
+}
+
+impl TryFrom<JsonValue> for PluginConfig {
+    type Error = ProxyError;
+
+    fn try_from(value: JsonValue) -> Result<Self, Self::Error> {
+        let config: PluginConfig = serde_json::from_value(value)
+            .map_err(|e| ProxyError::serialization_error("Failed to parse waf plugin config", e))?;
+        config.validate()?;
+        Ok(config)
+    }
+}
+
+// =============================================================================
+// RULE MODEL
+// =============================================================================
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Collection {
+    RequestUri,
+    RequestFilename,
+    RequestBasename,
+    QueryString,
+    RequestMethod,
+    RequestProtocol,
+    RequestLine,
+    /// Query and form body arguments.
+    Args,
+    ArgsNames,
+    ArgsGet,
+    ArgsGetNames,
+    ArgsPost,
+    ArgsPostNames,
+    RequestBody,
+    RequestHeaders,
+    RequestHeadersNames,
+    RequestCookies,
+    RequestCookiesNames,
+}
+
+impl Collection {
+    fn parse(name: &str) -> Option<Self> {
+        Some(match name.to_ascii_uppercase().as_str() {
+            "REQUEST_URI" | "REQUEST_URI_RAW" => Self::RequestUri,
+            "REQUEST_FILENAME" => Self::RequestFilename,
+            "REQUEST_BASENAME" => Self::RequestBasename,
+            "QUERY_STRING" => Self::QueryString,
+            "REQUEST_METHOD" => Self::RequestMethod,
+            "REQUEST_PROTOCOL" => Self::RequestProtocol,
+            "REQUEST_LINE" => Self::RequestLine,
+            "ARGS" => Self::Args,
+            "ARGS_NAMES" => Self::ArgsNames,
+            "ARGS_GET" => Self::ArgsGet,
+            "ARGS_GET_NAMES" => Self::ArgsGetNames,
+            "ARGS_POST" => Self::ArgsPost,
+            "ARGS_POST_NAMES" => Self::ArgsPostNames,
+            "REQUEST_BODY" => Self::RequestBody,
+            "REQUEST_HEADERS" => Self::RequestHeaders,
+            "REQUEST_HEADERS_NAMES" => Self::RequestHeadersNames,
+            "REQUEST_COOKIES" => Self::RequestCookies,
+            "REQUEST_COOKIES_NAMES" => Self::RequestCookiesNames,
+            _ => return None,
+        })
+    }
+
+    /// Whether the collection is only populated once the body has been read.
+    fn needs_body(self) -> bool {
+        matches!(
+            self,
+            Self::ArgsPost | Self::ArgsPostNames | Self::RequestBody
+        )
+    }
+}
+
+#[derive(Debug)]
+enum KeySelector {
+    Name(String),
+    Pattern(Regex),
+}
+
+impl KeySelector {
+    fn parse(key: &str) -> std::result::Result<Self, String> {
+        match key.strip_prefix('/').and_then(|k| k.strip_suffix('/')) {
+            Some(pattern) => RegexBuilder::new(pattern)
+                .case_insensitive(true)
+                .build()
+                .map(Self::Pattern)
+                .map_err(|e| format!("invalid key regex '{pattern}': {e}")),
+            None => Ok(Self::Name(key.to_ascii_lowercase())),
+        }
+    }
+
+    fn matches(&self, name: &str) -> bool {
+        match self {
+            Self::Name(n) => n.eq_ignore_ascii_case(name),
+            Self::Pattern(re) => re.is_match(name),
+        }
+    }
+}
+
+#[derive(Debug)]
+struct Target {
+    collection: Collection,
+    key: Option<KeySelector>,
+}
+
+#[derive(Debug)]
+struct Exclusion {
+    collection: Collection,
+    key: KeySelector,
+}
+
+#[derive(Debug)]
+enum Operator {
+    Rx(Regex),
+    Pm(Vec<String>),
+    Contains(String),
+    Streq(String),
+    BeginsWith(String),
+    EndsWith(String),
+    Within(String),
+    Eq(i64),
+    Gt(i64),
+    Lt(i64),
+    Ge(i64),
+    Le(i64),
+}
+
+impl Operator {
+    fn parse(spec: &str) -> std::result::Result<Self, String> {
+        let (name, arg) = match spec.strip_prefix('@') {
+            Some(rest) => rest.split_once(' ').unwrap_or((rest, "")),
+            None => ("rx", spec),
+        };
+        let number = || {
+            arg.trim()
+                .parse::<i64>()
+                .map_err(|_| format!("unsupported non-numeric operand '{arg}'"))
+        };
+        Ok(match name {
+            "rx" => Self::Rx(Regex::new(arg).map_err(|e| format!("invalid @rx: {e}"))?),
+            "pm" => Self::Pm(arg.split_whitespace().map(str::to_lowercase).collect()),
+            "contains" => Self::Contains(arg.to_string()),
+            "streq" => Self::Streq(arg.to_string()),
+            "beginsWith" => Self::BeginsWith(arg.to_string()),
+            "endsWith" => Self::EndsWith(arg.to_string()),
+            "within" => Self::Within(arg.to_string()),
+            "eq" => Self::Eq(number()?),
+            "gt" => Self::Gt(number()?),
+            "lt" => Self::Lt(number()?),
+            "ge" => Self::Ge(number()?),
+            "le" => Self::Le(number()?),
+            other => return Err(format!("unsupported operator @{other}")),
+        })
+    }
+
+    fn matches(&self, value: &str) -> bool {
+        let number = || value.trim().parse::<i64>().ok();
+        match self {
+            Self::Rx(re) => re.is_match(value),
+            Self::Pm(phrases) => {
+                let lower = value.to_lowercase();
+                phrases.iter().any(|p| lower.contains(p.as_str()))
+            }
+            Self::Contains(s) => value.contains(s.as_str()),
+            Self::Streq(s) => value == s,
+            Self::BeginsWith(s) => value.starts_with(s.as_str()),
+            Self::EndsWith(s) => value.ends_with(s.as_str()),
+            Self::Within(s) => s.contains(value),
+            Self::Eq(n) => number().is_some_and(|v| v == *n),
+            Self::Gt(n) => number().is_some_and(|v| v > *n),
+            Self::Lt(n) => number().is_some_and(|v| v < *n),
+            Self::Ge(n) => number().is_some_and(|v| v >= *n),
+            Self::Le(n) => number().is_some_and(|v| v <= *n),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Transform {
+    Lowercase,
+    UrlDecode,
+    CompressWhitespace,
+    RemoveWhitespace,
+    Trim,
+}
+
+impl Transform {
+    fn apply(self, value: String) -> String {
+        match self {
+            Self::Lowercase => value.to_lowercase(),
+            Self::UrlDecode => url_decode(&value),
+            Self::CompressWhitespace => value.split_whitespace().collect::<Vec<_>>().join(" "),
+            Self::RemoveWhitespace => value.chars().filter(|c| !c.is_whitespace()).collect(),
+            Self::Trim => value.trim().to_string(),
+        }
+    }
+}
+
+/// Transformations ModSecurity defines but this engine does not implement.
+const UNIMPLEMENTED_TRANSFORMS: &[&str] = &[
+    "base64decode",
+    "base64decodeext",
+    "base64encode",
+    "cmdline",
+    "cssdecode",
+    "escapeseqdecode",
+    "hexdecode",
+    "hexencode",
+    "htmlentitydecode",
+    "jsdecode",
+    "length",
+    "md5",
+    "normalisepath",
+    "normalizepath",
+    "normalisepathwin",
+    "normalizepathwin",
+    "parityeven7bit",
+    "parityodd7bit",
+    "parityzero7bit",
+    "removecomments",
+    "removecommentschar",
+    "removenulls",
+    "replacecomments",
+    "replacenulls",
+    "sha1",
+    "sqlhexdecode",
+    "trimleft",
+    "trimright",
+    "uppercase",
+    "urlencode",
+    "utf8tounicode",
+];
+
+/// Disruptive action a `block` rule takes, set by `SecDefaultAction`.
+#[derive(Debug, Clone, Copy, Default)]
+struct DefaultAction {
+    deny: bool,
+    status: Option<u16>,
+}
+
+#[derive(Debug)]
+struct Rule {
+    id: u64,
+    /// 1 for header rules, 2 for rules that also see the body. Chain links inherit
+    /// the phase of the chain's first rule.
+    phase: u8,
+    targets: Vec<Target>,
+    exclusions: Vec<Exclusion>,
+    operator: Operator,
+    negated: bool,
+    transforms: Vec<Transform>,
+    deny: bool,
+    status: Option<u16>,
+    msg: Option<String>,
+    chained: Option<Box<Rule>>,
+}
+
+// =============================================================================
+// PARSING
+// =============================================================================
+
+/// Parse a rule text, skipping unsupported rules with a warning.
+///
+/// Only malformed rules that look supported (bad regex, missing `id`) are errors.
+/// A chain is kept only if every rule in it is supported.
+fn parse_rules(text: &str, source: &str) -> ProxyResult<Vec<Rule>> {
+    let mut rules = Vec::new();
+    let mut skipped = 0usize;
+    // Chain being assembled and whether any link in it was unsupported.
+    let mut pending: Option<Rule> = None;
+    let mut skip_chain = false;
+    // `SecDefaultAction` of phases 1 and 2.
+    let mut default_actions = [DefaultAction::default(); 2];
+
+    for directive in logical_lines(text) {
+        let tokens = tokenize(&directive);
+        let Some(name) = tokens.first() else { continue };
+        if name == "SecDefaultAction" {
+            let actions = tokens.get(1).map(String::as_str).unwrap_or_default();
+            if let Some((phase, action)) = parse_default_action(actions)
+                .map_err(|e| ProxyError::validation_error(format!("{source}: {e}")))?
+            {
+                default_actions[usize::from(phase - 1)] = action;
+            }
+            continue;
+        }
+        if name != "SecRule" {
+            log::debug!("{source}: ignoring WAF directive {name}");
+            continue;
+        }
+        if tokens.len() < 3 {
+            return Err(ProxyError::validation_error(format!(
+                "{source}: malformed SecRule: {directive}"
+            )));
+        }
+
+        let actions = tokens.get(3).map(String::as_str).unwrap_or_default();
+        let in_chain = pending.is_some() || skip_chain;
+        match parse_rule(&tokens[1], &tokens[2], actions, in_chain, &default_actions) {
+            Ok(rule) => match pending.as_mut() {
+                Some(head) => append_chain(head, rule),
+                None => pending = Some(rule),
+            },
+            Err(RuleError::Unsupported(reason)) => {
+                log::warn!("{source}: skipping WAF rule: {reason}");
+                skipped += 1;
+                skip_chain = true;
+            }
+            Err(RuleError::Invalid(reason)) => {
+                return Err(ProxyError::validation_error(format!(
+                    "{source}: invalid WAF rule: {reason}"
+                )));
+            }
+        }
+
+        if !actions_of(actions).iter().any(|a| a == "chain") {
+            if let Some(rule) = pending.take().filter(|_| !skip_chain) {
+                rules.push(check_phase(rule, source)?);
+            }
+            skip_chain = false;
+        }
+    }
+
+    if let Some(rule) = pending.filter(|_| !skip_chain) {
+        rules.push(check_phase(rule, source)?);
+    }
+    if skipped > 0 {
+        log::warn!("{source}: {skipped} WAF rules skipped as unsupported");
+    }
+    Ok(rules)
+}
+
+/// Reject phase 1 rules that inspect the body, which is not read yet in that phase.
+fn check_phase(rule: Rule, source: &str) -> ProxyResult<Rule> {
+    if rule.phase == 1 && rule.needs_body() {
+        return Err(ProxyError::validation_error(format!(
+            "{source}: invalid WAF rule: rule {} inspects the request body and needs phase:2",
+            rule.id
+        )));
+    }
+    Ok(rule)
+}
+
+fn append_chain(head: &mut Rule, rule: Rule) {
+    match head.chained.as_mut() {
+        Some(next) => append_chain(next, rule),
+        None => head.chained = Some(Box::new(rule)),
+    }
+}
+
+enum RuleError {
+    Unsupported(String),
+    Invalid(String),
+}
+
+/// Parse a `phase` action value; `None` for phases after the request body.
+fn parse_phase(value: &str) -> Option<u8> {
+    match value {
+        "1" => Some(1),
+        "2" | "request" => Some(2),
+        _ => None,
+    }
+}
+
+/// Parse the actions of a `SecDefaultAction` into its phase and action; `None` for
+/// phases rules cannot use.
+fn parse_default_action(actions: &str) -> std::result::Result<Option<(u8, DefaultAction)>, String> {
+    let mut phase = 1;
+    let mut action = DefaultAction::default();
+    for item in actions_of(actions) {
+        let (name, value) = match item.split_once(':') {
+            Some((n, v)) => (n.trim(), v.trim().trim_matches('\'')),
+            None => (item.trim(), ""),
+        };
+        match name {
+            "phase" => match parse_phase(value) {
+                Some(p) => phase = p,
+                None => return Ok(None),
+            },
+            "deny" | "drop" => action.deny = true,
+            "pass" => action.deny = false,
+            "status" => {
+                action.status = Some(
+                    value
+                        .parse()
+                        .map_err(|_| format!("invalid SecDefaultAction status '{value}'"))?,
+                )
+            }
+            _ => {}
+        }
+    }
+    Ok(Some((phase, action)))
+}
+
+fn parse_rule(
+    variables: &str,
+    operator: &str,
+    actions: &str,
+    in_chain: bool,
+    default_actions: &[DefaultAction; 2],
+) -> std::result::Result<Rule, RuleError> {
+    let mut rule = Rule {
+        id: 0,
+        phase: 1,
+        targets: Vec::new(),
+        exclusions: Vec::new(),
+        operator: Operator::Streq(String::new()),
+        negated: false,
+        transforms: Vec::new(),
+        deny: false,
+        status: None,
+        msg: None,
+        chained: None,
+    };
+
+    for var in variables.split('|') {
+        let (exclude, var) = match var.strip_prefix('!') {
+            Some(v) => (true, v),
+            None => (false, var),
+        };
+        if var.starts_with('&') {
+            return Err(RuleError::Unsupported(format!("counting variable {var}")));
+        }
+        let (name, key) = match var.split_once(':') {
+            Some((name, key)) => (name, Some(key)),
+            None => (var, None),
+        };
+        let collection = Collection::parse(name)
+            .ok_or_else(|| RuleError::Unsupported(format!("variable {name}")))?;
+        let key = key
+            .map(KeySelector::parse)
+            .transpose()
+            .map_err(RuleError::Invalid)?;
+        match (exclude, key) {
+            (true, Some(key)) => rule.exclusions.push(Exclusion { collection, key }),
+            (true, None) => {
+                return Err(RuleError::Unsupported(format!(
+                    "whole-collection exclusion !{name}"
+                )))
+            }
+            (false, key) => rule.targets.push(Target { collection, key }),
+        }
+    }
+
+    let (negated, op) = match operator.strip_prefix('!') {
+        Some(op) => (true, op),
+        None => (false, operator),
+    };
+    rule.negated = negated;
+    rule.operator = Operator::parse(op).map_err(|e| {
+        if e.starts_with("unsupported") {
+            RuleError::Unsupported(e)
+        } else {
+            RuleError::Invalid(e)
+        }
+    })?;
+
+    let mut has_id = false;
+    let mut block = false;
+    for action in actions_of(actions) {
+        let (name, value) = match action.split_once(':') {
+            Some((n, v)) => (n.trim(), v.trim().trim_matches('\'')),
+            None => (action.trim(), ""),
+        };
+        match name {
+            "id" => {
+                rule.id = value
+                    .parse()
+                    .map_err(|_| RuleError::Invalid(format!("invalid id '{value}'")))?;
+                has_id = true;
+            }
+            "phase" => {
+                rule.phase = parse_phase(value).ok_or_else(|| {
+                    RuleError::Unsupported(format!(
+                        "phase {value} (only request phases are inspected)"
+                    ))
+                })?
+            }
+            "deny" | "drop" => (rule.deny, block) = (true, false),
+            "block" => block = true,
+            "pass" => (rule.deny, block) = (false, false),
+            "status" => {
+                rule.status = Some(
+                    value
+                        .parse()
+                        .map_err(|_| RuleError::Invalid(format!("invalid status '{value}'")))?,
+                )
+            }
+            "msg" => rule.msg = Some(value.to_string()),
+            // Chain links are assembled by `parse_rules`.
+            "chain" => {}
+            "t" => match value.to_ascii_lowercase().as_str() {
+                "none" => rule.transforms.clear(),
+                "lowercase" => rule.transforms.push(Transform::Lowercase),
+                "urldecode" | "urldecodeuni" => rule.transforms.push(Transform::UrlDecode),
+                "compresswhitespace" => rule.transforms.push(Transform::CompressWhitespace),
+                "removewhitespace" => rule.transforms.push(Transform::RemoveWhitespace),
+                "trim" => rule.transforms.push(Transform::Trim),
+                other if UNIMPLEMENTED_TRANSFORMS.contains(&other) => {
+                    return Err(RuleError::Unsupported(format!("transformation t:{value}")))
+                }
+                _ => {
+                    return Err(RuleError::Invalid(format!(
+                        "unknown transformation t:{value}"
+                    )))
+                }
+            },
+            // Metadata, logging and anomaly-scoring actions do not affect a standalone match.
+            "log" | "nolog" | "auditlog" | "noauditlog" | "capture" | "severity" | "tag"
+            | "ver" | "rev" | "logdata" | "accuracy" | "maturity" | "setvar" | "multiMatch" => {}
+            other => return Err(RuleError::Unsupported(format!("action {other}"))),
+        }
+    }
+    if !has_id && !in_chain {
+        return Err(RuleError::Invalid(format!("rule without id: {variables}")));
+    }
+    if block {
+        let default_action = default_actions[usize::from(rule.phase - 1)];
+        rule.deny = default_action.deny;
+        rule.status = rule.status.or(default_action.status);
+    }
+
+    Ok(rule)
+}
+
+/// Join `\`-continued lines and drop comments and blanks.
+fn logical_lines(text: &str) -> Vec<String> {
+    let mut out = Vec::new();
+    let mut current = String::new();
+    for line in text.lines() {
+        let line = line.trim();
+        if current.is_empty() && (line.is_empty() || line.starts_with('#')) {
+            continue;
+        }
+        match line.strip_suffix('\\') {
+            Some(part) => {
+                current.push_str(part.trim_end());
+                current.push(' ');
+            }
+            None => {
+                current.push_str(line);
+                out.push(std::mem::take(&mut current));
+            }
+        }
+    }
+    if !current.trim().is_empty() {
+        out.push(current);
+    }
+    out
+}
+
+/// Split a directive on whitespace, keeping double-quoted arguments intact.
+fn tokenize(line: &str) -> Vec<String> {
+    let mut tokens = Vec::new();
+    let mut current = String::new();
+    let mut in_quotes = false;
+    let mut chars = line.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' if in_quotes && chars.peek() == Some(&'"') => {
+                current.push('"');
+                chars.next();
+            }
+            '"' => in_quotes = !in_quotes,
+            c if c.is_whitespace() && !in_quotes => {
+                if !current.is_empty() {
+                    tokens.push(std::mem::take(&mut current));
+                }
+            }
+            c => current.push(c),
+        }
+    }
+    if !current.is_empty() {
+        tokens.push(current);
+    }
+    tokens
+}
+
+/// Split an action list on commas outside single quotes.
+fn actions_of(actions: &str) -> Vec<String> {
+    let mut out = Vec::new();
+    let mut current = String::new();
+    let mut in_quotes = false;
+    for c in actions.chars() {
+        match c {
+            '\'' => {
+                in_quotes = !in_quotes;
+                current.push(c);
+            }
+            ',' if !in_quotes => out.push(std::mem::take(&mut current).trim().to_string()),
+            c => current.push(c),
+        }
+    }
+    if !current.trim().is_empty() {
+        out.push(current.trim().to_string());
+    }
+    out
+}
+
+/// Percent-decode a value, turning `+` into a space; invalid escapes are kept.
+fn url_decode(value: &str) -> String {
+    let bytes = value.as_bytes();
+    let hex = |b: u8| (b as char).to_digit(16).map(|d| d as u8);
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'+' => out.push(b' '),
+            b'%' if i + 2 < bytes.len() => match (hex(bytes[i + 1]), hex(bytes[i + 2])) {
+                (Some(hi), Some(lo)) => {
+                    out.push(hi << 4 | lo);
+                    i += 2;
+                }
+                _ => out.push(b'%'),
+            },
+            b => out.push(b),
+        }
+        i += 1;
+    }
+    String::from_utf8_lossy(&out).into_owned()
+}
+
+// =============================================================================
+// EVALUATION
+// =============================================================================
+
+/// Request values extracted once and shared by all rules.
+struct RequestData {
+    uri: String,
+    line: String,
+    path: String,
+    query: String,
+    method: String,
+    protocol: String,
+    args: Vec<(String, String)>,
+    post_args: Vec<(String, String)>,
+    body: String,
+    headers: Vec<(String, String)>,
+    cookies: Vec<(String, String)>,
+}
+
+impl RequestData {
+    fn from_header(req: &RequestHeader) -> Self {
+        let query = req.uri.query().unwrap_or_default().to_string();
+        let args = url::form_urlencoded::parse(query.as_bytes())
+            .map(|(k, v)| (k.into_owned(), v.into_owned()))
+            .collect();
+        let headers: Vec<(String, String)> = req
+            .headers
+            .iter()
+            .map(|(k, v)| {
+                (
+                    k.as_str().to_string(),
+                    String::from_utf8_lossy(v.as_bytes()).into_owned(),
+                )
+            })
+            .collect();
+        let cookies = headers
+            .iter()
+            .filter(|(k, _)| k == "cookie")
+            .flat_map(|(_, v)| v.split(';'))
+            .filter_map(|pair| {
+                let (k, v) = pair.trim().split_once('=')?;
+                Some((k.to_string(), v.to_string()))
+            })
+            .collect();
+        let uri = req.uri.to_string();
+        let method = req.method.as_str().to_string();
+        let protocol = format!("{:?}", req.version);
+        Self {
+            line: format!("{method} {uri} {protocol}"),
+            uri,
+            path: req.uri.path().to_string(),
+            query,
+            method,
+            protocol,
+            args,
+            post_args: Vec::new(),
+            body: String::new(),
+            headers,
+            cookies,
+        }
+    }
+
+    /// Add the request body for phase 2 rules.
+    fn set_body(&mut self, body: &[u8]) {
+        let form = self.headers.iter().any(|(k, v)| {
+            k == header::CONTENT_TYPE.as_str()
+                && v.to_ascii_lowercase()
+                    .starts_with("application/x-www-form-urlencoded")
+        });
+        if form {
+            self.post_args = url::form_urlencoded::parse(body)
+                .map(|(k, v)| (k.into_owned(), v.into_owned()))
+                .collect();
+        }
+        self.body = String::from_utf8_lossy(body).into_owned();
+    }
+
+    /// Values of a target, after key selection and exclusions.
+    fn values<'a>(&'a self, target: &'a Target, exclusions: &'a [Exclusion]) -> Vec<&'a str> {
+        let keyed =
+            |pairs: &mut dyn Iterator<Item = &'a (String, String)>, names: bool| -> Vec<&'a str> {
+                pairs
+                    .filter(|(k, _)| target.key.as_ref().is_none_or(|sel| sel.matches(k)))
+                    .filter(|(k, _)| {
+                        !exclusions.iter().any(|ex| {
+                            Self::base(ex.collection) == Self::base(target.collection)
+                                && ex.key.matches(k)
+                        })
+                    })
+                    .map(|(k, v)| if names { k.as_str() } else { v.as_str() })
+                    .collect()
+            };
+        match target.collection {
+            Collection::RequestUri => vec![&self.uri],
+            Collection::RequestFilename => vec![&self.path],
+            Collection::RequestBasename => {
+                vec![self.path.rsplit('/').next().unwrap_or_default()]
+            }
+            Collection::QueryString => vec![&self.query],
+            Collection::RequestMethod => vec![&self.method],
+            Collection::RequestProtocol => vec![&self.protocol],
+            Collection::RequestLine => vec![&self.line],
+            Collection::Args => keyed(&mut self.args.iter().chain(&self.post_args), false),
+            Collection::ArgsNames => keyed(&mut self.args.iter().chain(&self.post_args), true),
+            Collection::ArgsGet => keyed(&mut self.args.iter(), false),
+            Collection::ArgsGetNames => keyed(&mut self.args.iter(), true),
+            Collection::ArgsPost => keyed(&mut self.post_args.iter(), false),
+            Collection::ArgsPostNames => keyed(&mut self.post_args.iter(), true),
+            Collection::RequestBody => vec![&self.body],
+            Collection::RequestHeaders => keyed(&mut self.headers.iter(), false),
+            Collection::RequestHeadersNames => keyed(&mut self.headers.iter(), true),
+            Collection::RequestCookies => keyed(&mut self.cookies.iter(), false),
+            Collection::RequestCookiesNames => keyed(&mut self.cookies.iter(), true),
+        }
+    }
+
+    /// Map `*_NAMES` collections onto their value collection for exclusions.
+    fn base(collection: Collection) -> Collection {
+        match collection {
+            Collection::ArgsNames => Collection::Args,
+            Collection::ArgsGetNames => Collection::ArgsGet,
+            Collection::ArgsPostNames => Collection::ArgsPost,
+            Collection::RequestHeadersNames => Collection::RequestHeaders,
+            Collection::RequestCookiesNames => Collection::RequestCookies,
+            other => other,
+        }
+    }
+}
+
+impl Rule {
+    fn matches(&self, data: &RequestData) -> bool {
+        let hit = self.targets.iter().any(|target| {
+            data.values(target, &self.exclusions)
+                .into_iter()
+                .any(|value| {
+                    let value = self
+                        .transforms
+                        .iter()
+                        .fold(value.to_string(), |v, t| t.apply(v));
+                    self.operator.matches(&value) != self.negated
+                })
+        });
+        hit && self.chained.as_ref().is_none_or(|next| next.matches(data))
+    }
+
+    /// Whether any rule in the chain inspects a body collection.
+    fn needs_body(&self) -> bool {
+        self.targets.iter().any(|t| t.collection.needs_body())
+            || self.chained.as_ref().is_some_and(|next| next.needs_body())
+    }
+}
+
+/// Request body buffered for phase 2 rules, with the request data of phase 1.
+struct BodyInspection {
+    data: RequestData,
+    body: BytesMut,
+}
+
+pub struct PluginWaf {
+    config: PluginConfig,
+    rules: Vec<Rule>,
+}
+
+impl PluginWaf {
+    /// Evaluate the rules of `phase`, counting and logging matches. Returns the status
+    /// to reject the request with when a denying rule matches in block mode.
+    fn inspect(&self, data: &RequestData, phase: u8) -> Option<StatusCode> {
+        let mode = match self.config.mode {
+            WafMode::Block => "block",
+            WafMode::Detect => "detect",
+        };
+
+        for rule in self.rules.iter().filter(|rule| rule.phase == phase) {
+            if !rule.matches(data) {
+                continue;
+            }
+            WAF_RULE_MATCHES
+                .with_label_values(&[&rule.id.to_string(), mode])
+                .inc();
+            log::warn!(
+                "WAF rule {} matched {} {}: {}",
+                rule.id,
+                data.method,
+                data.uri,
+                rule.msg.as_deref().unwrap_or("")
+            );
+            if !rule.deny || self.config.mode == WafMode::Detect {
+                continue;
+            }
+
+            return Some(
+                rule.status
+                    .and_then(|code| StatusCode::from_u16(code).ok())
+                    .or_else(|| StatusCode::from_u16(self.config.rejected_code).ok())
+                    .unwrap_or(StatusCode::FORBIDDEN),
+            );
+        }
+        None
+    }
+
+    fn has_phase2_rules(&self) -> bool {
+        self.rules.iter().any(|rule| rule.phase == 2)
+    }
+
+    /// Buffer a body chunk and run phase 2 rules at the end of the body.
+    ///
+    /// In block mode chunks are held back so nothing reaches the upstream before the
+    /// rules pass; detect mode copies them and lets them through.
+    fn filter_body(
+        &self,
+        state: &mut BodyInspection,
+        body: &mut Option<Bytes>,
+        end_of_stream: bool,
+    ) -> Result<()> {
+        let block = self.config.mode == WafMode::Block;
+        if block {
+            if let Some(chunk) = body.take() {
+                state.body.extend_from_slice(&chunk);
+            }
+            if state.body.len() > self.config.max_body_bytes {
+                return Error::e_explain(
+                    ErrorType::HTTPStatus(413),
+                    "request body exceeds waf max_body_bytes",
+                );
+            }
+        } else if let Some(chunk) = body.as_ref() {
+            let room = self.config.max_body_bytes.saturating_sub(state.body.len());
+            state
+                .body
+                .extend_from_slice(&chunk[..chunk.len().min(room)]);
+        }
+        if !end_of_stream {
+            return Ok(());
+        }
+
+        state.data.set_body(&state.body);
+        if let Some(status) = self.inspect(&state.data, 2) {
+            return Error::e_explain(
+                ErrorType::HTTPStatus(status.as_u16()),
+                "request body blocked by WAF rule",
+            );
+        }
+        if block {
+            *body = Some(std::mem::take(&mut state.body).freeze());
+        }
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl ProxyPlugin for PluginWaf {
+    fn name(&self) -> &str {
+        PLUGIN_NAME
+    }
+
+    fn priority(&self) -> i32 {
+        PRIORITY
+    }
+
+    async fn request_filter(&self, session: &mut Session, ctx: &mut ProxyContext) -> Result<bool> {
+        let data = RequestData::from_header(session.req_header());
+        let mut status = self.inspect(&data, 1);
+        if status.is_none() && self.has_phase2_rules() {
+            // Without a body phase 2 is decided now; otherwise once the body is read.
+            if request_has_body(session.req_header()) {
+                ctx.set(
+                    BODY_STATE_KEY,
+                    BodyInspection {
+                        data,
+                        body: BytesMut::new(),
+                    },
+                );
+                return Ok(false);
+            }
+            status = self.inspect(&data, 2);
+        }
+
+        let Some(status) = status else {
+            return Ok(false);
+        };
+        ResponseBuilder::send_proxy_error(
+            session,
+            status,
+            self.config.rejected_msg.as_deref(),
+            None,
+        )
+        .await?;
+        Ok(true)
+    }
+
+    fn has_request_body_filter(&self) -> bool {
+        self.has_phase2_rules()
+    }
+
+    async fn request_body_filter(
+        &self,
+        _session: &mut Session,
+        body: &mut Option<Bytes>,
+        end_of_stream: bool,
+        ctx: &mut ProxyContext,
+    ) -> Result<()> {
+        match ctx.get_mut::<BodyInspection>(BODY_STATE_KEY) {
+            Some(state) => self.filter_body(state, body, end_of_stream),
+            None => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn request(uri: &str, headers: &[(&str, &str)]) -> RequestData {
+        let mut req = RequestHeader::build("GET", uri.as_bytes(), None).unwrap();
+        for (k, v) in headers {
+            req.insert_header(k.to_string(), *v).unwrap();
+        }
+        RequestData::from_header(&req)
+    }
+
+    #[test]
+    fn parses_multiline_rules_and_skips_unsupported() {
+        let text = r#"
+# comment
+SecRuleEngine On
+SecRule ARGS|REQUEST_HEADERS:User-Agent "@rx (?i)union\s+select" \
+    "id:1001,phase:1,deny,status:406,msg:'SQLi',t:none,t:urlDecode"
+SecRule REQUEST_BODY "@rx evil" "id:1002,phase:2,deny"
+SecRule RESPONSE_BODY "@rx evil" "id:1003,phase:4,deny"
+SecRule TX:ANOMALY_SCORE "@ge 5" "id:1004,phase:1,deny"
+SecRule ARGS "@rx evil" "id:1005,phase:1,deny,t:htmlEntityDecode"
+"#;
+        let rules = parse_rules(text, "test").unwrap();
+        let ids: Vec<(u64, u8)> = rules.iter().map(|r| (r.id, r.phase)).collect();
+        assert_eq!(ids, [(1001, 1), (1002, 2)]);
+        assert_eq!(rules[0].status, Some(406));
+
+        assert!(rules[0].matches(&request("/?q=1%20UNION%20SELECT%20x", &[])));
+        assert!(rules[0].matches(&request("/", &[("user-agent", "union select")])));
+        assert!(!rules[0].matches(&request("/?q=onion", &[])));
+    }
+
+    #[test]
+    fn chained_rules_require_every_link() {
+        let text = r#"
+SecRule REQUEST_METHOD "@streq POST" "id:2001,phase:1,deny,chain"
+    SecRule REQUEST_FILENAME "@beginsWith /admin" "t:lowercase"
+"#;
+        let rules = parse_rules(text, "test").unwrap();
+        assert_eq!(rules.len(), 1);
+
+        let mut req = RequestHeader::build("POST", b"/ADMIN/users", None).unwrap();
+        req.insert_header("host", "example.com").unwrap();
+        assert!(rules[0].matches(&RequestData::from_header(&req)));
+        assert!(!rules[0].matches(&request("/admin/users", &[])));
+    }
+
+    #[test]
+    fn block_follows_the_default_action() {
+        let text = r#"
+SecRule ARGS "@contains a" "id:4001,phase:1,block"
+SecDefaultAction "phase:2,log,deny,status:400"
+SecRule ARGS "@contains b" "id:4002,phase:1,block"
+SecDefaultAction "phase:1,log,deny,status:401"
+SecRule ARGS "@contains c" "id:4003,phase:1,block"
+SecRule ARGS "@contains d" "id:4004,phase:1,block,status:402"
+SecRule ARGS "@contains e" "id:4005,phase:1,pass"
+"#;
+        let rules = parse_rules(text, "test").unwrap();
+        let disruptive: Vec<(bool, Option<u16>)> =
+            rules.iter().map(|r| (r.deny, r.status)).collect();
+        assert_eq!(
+            disruptive,
+            [
+                (false, None),
+                (false, None),
+                (true, Some(401)),
+                (true, Some(402)),
+                (false, None)
+            ]
+        );
+    }
+
+    fn plugin(rules: &str, config: JsonValue) -> PluginWaf {
+        PluginWaf {
+            config: PluginConfig::try_from(config).unwrap(),
+            rules: parse_rules(rules, "test").unwrap(),
+        }
+    }
+
+    fn post(content_type: &str) -> BodyInspection {
+        let mut req = RequestHeader::build("POST", b"/comments", None).unwrap();
+        req.insert_header("content-type", content_type).unwrap();
+        BodyInspection {
+            data: RequestData::from_header(&req),
+            body: BytesMut::new(),
+        }
+    }
+
+    /// Feed `chunks` through the body filter, returning what reached the upstream.
+    fn send_body(
+        waf: &PluginWaf,
+        state: &mut BodyInspection,
+        chunks: &[&str],
+    ) -> Result<Vec<Bytes>> {
+        let mut forwarded = Vec::new();
+        for (i, chunk) in chunks.iter().enumerate() {
+            let mut body = Some(Bytes::copy_from_slice(chunk.as_bytes()));
+            waf.filter_body(state, &mut body, i + 1 == chunks.len())?;
+            forwarded.extend(body);
+        }
+        Ok(forwarded)
+    }
+
+    const BODY_RULES: &str = r#"
+SecDefaultAction "phase:2,log,deny,status:406"
+SecRule ARGS_POST:comment "@contains <script" "id:6001,phase:2,block,t:lowercase"
+SecRule REQUEST_BODY "@contains DROP TABLE" "id:6002,phase:2,deny"
+"#;
+
+    #[test]
+    fn phase2_rules_block_bad_bodies() {
+        let waf = plugin(BODY_RULES, json!({}));
+        assert!(waf.has_phase2_rules());
+
+        let mut state = post("application/x-www-form-urlencoded");
+        let err = send_body(&waf, &mut state, &["name=bob&comment=%3CSCR", "IPT%3E"]).unwrap_err();
+        assert_eq!(err.etype(), &ErrorType::HTTPStatus(406));
+
+        let mut state = post("application/json");
+        let err = send_body(&waf, &mut state, &[r#"{"q": "1; DROP TABLE users"}"#]).unwrap_err();
+        assert_eq!(err.etype(), &ErrorType::HTTPStatus(403));
+
+        // A clean body is held back and released whole once the rules pass.
+        let mut state = post("application/x-www-form-urlencoded");
+        let forwarded = send_body(&waf, &mut state, &["name=bob&", "comment=hello"]).unwrap();
+        assert_eq!(forwarded, [Bytes::from_static(b"name=bob&comment=hello")]);
+    }
+
+    #[test]
+    fn phase2_body_limit_and_detect_mode() {
+        let waf = plugin(BODY_RULES, json!({"max_body_bytes": 8}));
+        let mut state = post("text/plain");
+        let err = send_body(&waf, &mut state, &["0123456789"]).unwrap_err();
+        assert_eq!(err.etype(), &ErrorType::HTTPStatus(413));
+
+        // Detect mode only counts matches and streams the body through.
+        let waf = plugin(BODY_RULES, json!({"mode": "detect"}));
+        let mut state = post("text/plain");
+        let forwarded = send_body(&waf, &mut state, &["DROP ", "TABLE"]).unwrap();
+        assert_eq!(forwarded.len(), 2);
+    }
+
+    #[test]
+    fn body_collections_need_phase2() {
+        let text = r#"SecRule REQUEST_BODY "@contains evil" "id:7001,deny""#;
+        assert!(parse_rules(text, "test").is_err());
+
+        let text = r#"
+SecRule REQUEST_METHOD "@streq POST" "id:7002,phase:1,deny,chain"
+    SecRule ARGS_POST "@contains evil""#;
+        assert!(parse_rules(text, "test").is_err());
+    }
+
+    #[test]
+    fn unknown_transformations_are_rejected() {
+        let text = r#"SecRule ARGS "@contains a" "id:5001,phase:1,deny,t:lowercse""#;
+        assert!(parse_rules(text, "test").is_err());
+    }
+
+    #[test]
+    fn exclusions_remove_selected_keys() {
+        let text = r#"SecRule ARGS|!ARGS:token "@contains <script" "id:3001,deny""#;
+        let rules = parse_rules(text, "test").unwrap();
+        assert!(!rules[0].matches(&request("/?token=%3Cscript", &[])));
+        assert!(rules[0].matches(&request("/?q=%3Cscript", &[])));
+    }
+
+    #[test]
+    fn rejected_code_must_be_an_error_status() {
+        for code in [200, 302, 399, 600] {
+            assert!(
+                PluginConfig::try_from(json!({"rejected_code": code})).is_err(),
+                "{code}"
+            );
+        }
+        for code in [400, 403, 599] {
+            assert!(PluginConfig::try_from(json!({"rejected_code": code})).is_ok());
+        }
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn rule_files_load_on_a_runtime_worker() {
+        let path = std::env::temp_dir().join(format!("pingsix-waf-{}.conf", std::process::id()));
+        std::fs::write(&path, r#"SecRule ARGS "@contains evil" "id:8001,deny""#).unwrap();
+        let cfg = json!({"rule_files": [path.to_str().unwrap()]});
+        let waf = tokio::spawn(async move { create_waf_plugin(cfg) })
+            .await
+            .unwrap();
+        let _ = std::fs::remove_file(&path);
+        assert!(waf.is_ok());
+
+        let missing = json!({"rule_files": ["/nonexistent/pingsix-waf.conf"]});
+        assert!(create_waf_plugin(missing).is_err());
+    }
+}
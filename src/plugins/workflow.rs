@@ -0,0 +1,442 @@
+use std::{borrow::Cow, collections::HashMap, sync::Arc};
+
+use async_trait::async_trait;
+use bytes::Bytes;
+use http::{header, HeaderName, HeaderValue, StatusCode};
+use pingora_error::Result;
+use pingora_http::ResponseHeader;
+use pingora_proxy::Session;
+use regex::{Regex, RegexBuilder};
+use serde::Deserialize;
+use serde_json::{json, Value as JsonValue};
+
+use crate::config::UpstreamHashOn;
+use crate::core::{ProxyContext, ProxyError, ProxyPlugin, ProxyResult, UpstreamSelector};
+use crate::proxy::upstream::ProxyUpstream;
+use crate::utils::request::{get_cookie_value, get_request_host, request_selector_key};
+
+pub const PLUGIN_NAME: &str = "workflow";
+pub const PRIORITY: i32 = 1006;
+
+const OPERATORS: [&str; 8] = ["==", "!=", "~~", "~*", ">", ">=", "<", "<="];
+
+/// Creates a workflow plugin. Rules with `upstream_id` actions need the named upstreams
+/// of the runtime, see [`create_workflow_plugin_with_upstreams`].
+pub fn create_workflow_plugin(cfg: JsonValue) -> ProxyResult<Arc<dyn ProxyPlugin>> {
+    create_workflow_plugin_with_upstreams(cfg, &HashMap::new())
+}
+
+/// JSON Schema of the workflow plugin configuration.
+pub fn schema() -> JsonValue {
+    let headers = json!({"type": "object", "additionalProperties": {"type": "string"}});
+    json!({
+        "type": "object",
+        "required": ["rules"],
+        "properties": {
+            "rules": {
+                "type": "array",
+                "minItems": 1,
+                "items": {
+                    "type": "object",
+                    "required": ["actions"],
+                    "properties": {
+                        "case": {
+                            "type": "array",
+                            "items": {
+                                "type": "array",
+                                "minItems": 3,
+                                "maxItems": 3,
+                                "items": {"type": "string"}
+                            }
+                        },
+                        "actions": {
+                            "type": "array",
+                            "minItems": 1,
+                            "items": {"anyOf": [
+                                {
+                                    "type": "object",
+                                    "required": ["return"],
+                                    "properties": {"return": {
+                                        "type": "object",
+                                        "required": ["code"],
+                                        "properties": {
+                                            "code": {"type": "integer", "minimum": 100, "maximum": 599},
+                                            "body": {"type": "string"},
+                                            "headers": headers
+                                        }
+                                    }},
+                                    "additionalProperties": false
+                                },
+                                {
+                                    "type": "object",
+                                    "required": ["set_headers"],
+                                    "properties": {"set_headers": headers},
+                                    "additionalProperties": false
+                                },
+                                {
+                                    "type": "object",
+                                    "required": ["upstream_id"],
+                                    "properties": {"upstream_id": {"type": "string", "minLength": 1}},
+                                    "additionalProperties": false
+                                }
+                            ]}
+                        }
+                    }
+                }
+            }
+        }
+    })
+}
+
+#[derive(Debug, Deserialize)]
+pub(super) struct PluginConfig {
+    /// Evaluated in order; the actions of the first matching rule run.
+    rules: Vec<Rule>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Rule {
+    /// `[var, operator, value]` conditions that must all hold. Empty matches every request.
+    #[serde(default)]
+    case: Vec<Condition>,
+    actions: Vec<Action>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum Action {
+    /// Respond directly and stop processing the request.
+    Return {
+        code: u16,
+        #[serde(default)]
+        body: String,
+        #[serde(default)]
+        headers: HashMap<String, String>,
+    },
+    /// Set request headers seen by later plugins and the upstream.
+    SetHeaders(HashMap<String, String>),
+    /// Send the request to a named upstream instead of the route's.
+    UpstreamId(String),
+}
+
+fn validate_actions(actions: &[Action]) -> Result<(), String> {
+    if actions.is_empty() {
+        return Err("rules need at least one action".to_string());
+    }
+    for action in actions {
+        let headers = match action {
+            Action::Return { code, headers, .. } => {
+                if StatusCode::from_u16(*code).is_err() {
+                    return Err(format!("invalid return code {code}"));
+                }
+                headers
+            }
+            Action::SetHeaders(headers) => headers,
+            Action::UpstreamId(_) => continue,
+        };
+        for (name, value) in headers {
+            if HeaderName::try_from(name.as_str()).is_err()
+                || HeaderValue::try_from(value.as_str()).is_err()
+            {
+                return Err(format!("invalid header '{name}'"));
+            }
+        }
+    }
+    Ok(())
+}
+
+/// A compiled `[var, operator, value]` condition.
+#[derive(Debug)]
+struct Condition {
+    var: String,
+    op: Operator,
+}
+
+#[derive(Debug)]
+enum Operator {
+    Eq(String),
+    Ne(String),
+    Regex(Regex),
+    Gt(f64),
+    Ge(f64),
+    Lt(f64),
+    Le(f64),
+}
+
+impl<'de> Deserialize<'de> for Condition {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let parts = Vec::<String>::deserialize(deserializer)?;
+        Condition::compile(parts).map_err(serde::de::Error::custom)
+    }
+}
+
+impl Condition {
+    fn compile(parts: Vec<String>) -> Result<Self, String> {
+        let [var, op, value]: [String; 3] = parts
+            .try_into()
+            .map_err(|_| "conditions must be [var, operator, value]".to_string())?;
+        let number = || {
+            value
+                .parse::<f64>()
+                .map_err(|_| format!("operator '{op}' needs a number, got '{value}'"))
+        };
+        let op = match op.as_str() {
+            "==" => Operator::Eq(value.clone()),
+            "!=" => Operator::Ne(value.clone()),
+            "~~" | "~*" => Operator::Regex(
+                RegexBuilder::new(&value)
+                    .case_insensitive(op == "~*")
+                    .build()
+                    .map_err(|e| format!("invalid regex '{value}': {e}"))?,
+            ),
+            ">" => Operator::Gt(number()?),
+            ">=" => Operator::Ge(number()?),
+            "<" => Operator::Lt(number()?),
+            "<=" => Operator::Le(number()?),
+            _ => {
+                return Err(format!(
+                    "unknown operator '{op}', expected one of {}",
+                    OPERATORS.join(" ")
+                ))
+            }
+        };
+        Ok(Self { var, op })
+    }
+
+    fn matches(&self, actual: &str) -> bool {
+        let number = || actual.trim().parse::<f64>().ok();
+        match &self.op {
+            Operator::Eq(value) => actual == value,
+            Operator::Ne(value) => actual != value,
+            Operator::Regex(regex) => regex.is_match(actual),
+            Operator::Gt(value) => number().is_some_and(|n| n > *value),
+            Operator::Ge(value) => number().is_some_and(|n| n >= *value),
+            Operator::Lt(value) => number().is_some_and(|n| n < *value),
+            Operator::Le(value) => number().is_some_and(|n| n <= *value),
+        }
+    }
+}
+
+impl TryFrom<JsonValue> for PluginConfig {
+    type Error = ProxyError;
+
+    fn try_from(value: JsonValue) -> Result<Self, Self::Error> {
+        let config: PluginConfig = serde_json::from_value(value)
+            .map_err(|e| ProxyError::serialization_error("Invalid workflow plugin config", e))?;
+
+        if config.rules.is_empty() {
+            return Err(ProxyError::validation_error(
+                "Workflow plugin requires at least one rule",
+            ));
+        }
+        for rule in &config.rules {
+            validate_actions(&rule.actions).map_err(|e| {
+                ProxyError::validation_error(format!("Invalid workflow plugin config: {e}"))
+            })?;
+        }
+
+        Ok(config)
+    }
+}
+
+/// Validate workflow JSON without resolving named upstreams (Admin pre-check).
+pub fn validate_workflow_config(cfg: &JsonValue) -> ProxyResult<()> {
+    PluginConfig::try_from(cfg.clone()).map(|_| ())
+}
+
+/// Collect the `upstream_id` actions of a workflow config value.
+pub fn named_upstream_ids(cfg: &JsonValue) -> ProxyResult<Vec<String>> {
+    let config = PluginConfig::try_from(cfg.clone())?;
+    Ok(config
+        .rules
+        .iter()
+        .flat_map(|rule| &rule.actions)
+        .filter_map(|action| match action {
+            Action::UpstreamId(id) => Some(id.clone()),
+            _ => None,
+        })
+        .collect())
+}
+
+pub(crate) fn create_workflow_plugin_with_upstreams(
+    cfg: JsonValue,
+    upstreams: &HashMap<String, Arc<ProxyUpstream>>,
+) -> ProxyResult<Arc<dyn ProxyPlugin>> {
+    let config = PluginConfig::try_from(cfg)?;
+
+    let mut targets = HashMap::new();
+    for id in config
+        .rules
+        .iter()
+        .flat_map(|rule| &rule.actions)
+        .filter_map(|action| match action {
+            Action::UpstreamId(id) => Some(id),
+            _ => None,
+        })
+    {
+        let upstream = upstreams.get(id).cloned().ok_or_else(|| {
+            ProxyError::Configuration(format!("Workflow references missing upstream '{id}'"))
+        })?;
+        targets.insert(id.clone(), upstream as Arc<dyn UpstreamSelector>);
+    }
+
+    Ok(Arc::new(PluginWorkflow { config, targets }))
+}
+
+pub struct PluginWorkflow {
+    config: PluginConfig,
+    /// Named upstreams of `upstream_id` actions, resolved at build time.
+    targets: HashMap<String, Arc<dyn UpstreamSelector>>,
+}
+
+impl PluginWorkflow {
+    /// The value of `var` for this request. Besides the request variables of
+    /// `vars`-keyed plugins, accepts `http_<header>`, `cookie_<name>`, `host`,
+    /// `request_method`, `route_id` and `consumer_name`.
+    fn resolve_var<'a>(session: &'a mut Session, ctx: &'a ProxyContext, var: &str) -> Cow<'a, str> {
+        if let Some(name) = var.strip_prefix("http_") {
+            let name = name.replace('_', "-");
+            return request_selector_key(session, &UpstreamHashOn::HEAD, &name);
+        }
+        if let Some(name) = var.strip_prefix("cookie_") {
+            return Cow::Borrowed(get_cookie_value(session.req_header(), name).unwrap_or_default());
+        }
+        match var {
+            "host" => Cow::Borrowed(get_request_host(session.req_header()).unwrap_or_default()),
+            "request_method" => Cow::Borrowed(session.req_header().method.as_str()),
+            "route_id" => Cow::Borrowed(ctx.route.as_ref().map_or("", |r| r.id())),
+            "consumer_name" => Cow::Borrowed(ctx.authenticated_identity.as_deref().unwrap_or("")),
+            _ => request_selector_key(session, &UpstreamHashOn::VARS, var),
+        }
+    }
+
+    fn rule_matches(session: &mut Session, ctx: &ProxyContext, rule: &Rule) -> bool {
+        rule.case
+            .iter()
+            .all(|condition| condition.matches(&Self::resolve_var(session, ctx, &condition.var)))
+    }
+
+    async fn respond(
+        session: &mut Session,
+        code: u16,
+        body: &str,
+        headers: &HashMap<String, String>,
+    ) -> Result<()> {
+        let status = StatusCode::from_u16(code).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR);
+        let mut resp = ResponseHeader::build(status, None)?;
+        resp.insert_header(header::CONTENT_LENGTH, body.len().to_string())?;
+        for (name, value) in headers {
+            resp.insert_header(name.clone(), value.as_str())?;
+        }
+        session
+            .write_response_header(Box::new(resp), body.is_empty())
+            .await?;
+        if !body.is_empty() {
+            session
+                .write_response_body(Some(Bytes::copy_from_slice(body.as_bytes())), true)
+                .await?;
+        }
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl ProxyPlugin for PluginWorkflow {
+    fn name(&self) -> &str {
+        PLUGIN_NAME
+    }
+
+    fn priority(&self) -> i32 {
+        PRIORITY
+    }
+
+    async fn request_filter(&self, session: &mut Session, ctx: &mut ProxyContext) -> Result<bool> {
+        let Some(rule) = self
+            .config
+            .rules
+            .iter()
+            .find(|rule| Self::rule_matches(session, ctx, rule))
+        else {
+            return Ok(false);
+        };
+
+        for action in &rule.actions {
+            match action {
+                Action::Return {
+                    code,
+                    body,
+                    headers,
+                } => {
+                    Self::respond(session, *code, body, headers).await?;
+                    return Ok(true);
+                }
+                Action::SetHeaders(headers) => {
+                    for (name, value) in headers {
+                        session
+                            .req_header_mut()
+                            .insert_header(name.clone(), value.as_str())?;
+                    }
+                }
+                Action::UpstreamId(id) => {
+                    ctx.upstream_override = self.targets.get(id).cloned();
+                }
+            }
+        }
+        Ok(false)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn condition(var: &str, op: &str, value: &str) -> Result<Condition, String> {
+        Condition::compile(vec![var.into(), op.into(), value.into()])
+    }
+
+    #[test]
+    fn conditions_compare_strings_patterns_and_numbers() {
+        assert!(condition("arg_v", "==", "2").unwrap().matches("2"));
+        assert!(condition("arg_v", "!=", "2").unwrap().matches("3"));
+        assert!(condition("http_user_agent", "~*", "^curl/")
+            .unwrap()
+            .matches("Curl/8.0"));
+        assert!(!condition("uri", "~~", "^/api/").unwrap().matches("/web/"));
+        assert!(condition("arg_n", ">=", "10").unwrap().matches("10"));
+        assert!(!condition("arg_n", "<", "10").unwrap().matches("abc"));
+
+        assert!(condition("arg_n", ">", "ten").is_err());
+        assert!(condition("uri", "~~", "(").is_err());
+        assert!(condition("uri", "in", "a").is_err());
+        assert!(Condition::compile(vec!["uri".into(), "==".into()]).is_err());
+    }
+
+    #[test]
+    fn config_validation_and_upstream_references() {
+        let cfg = json!({"rules": [
+            {
+                "case": [["http_x_tier", "==", "beta"]],
+                "actions": [{"set_headers": {"X-Beta": "1"}}, {"upstream_id": "beta"}]
+            },
+            {"actions": [{"return": {"code": 403, "body": "closed"}}]}
+        ]});
+        assert!(validate_workflow_config(&cfg).is_ok());
+        assert_eq!(named_upstream_ids(&cfg).unwrap(), ["beta"]);
+        assert!(create_workflow_plugin(cfg)
+            .err()
+            .unwrap()
+            .to_string()
+            .contains("missing upstream 'beta'"));
+
+        assert!(validate_workflow_config(&json!({"rules": []})).is_err());
+        assert!(validate_workflow_config(&json!({"rules": [{"actions": []}]})).is_err());
+        assert!(validate_workflow_config(&json!({"rules": [
+            {"actions": [{"set_headers": {"bad header": "x"}}]}
+        ]}))
+        .is_err());
+        assert!(validate_workflow_config(&json!({"rules": [
+            {"actions": [{"return": {"code": 42}}]}
+        ]}))
+        .is_err());
+    }
+}
@@ -0,0 +1,108 @@
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use pingora::protocols::http::compression::Algorithm;
+use pingora_error::Result;
+use pingora_http::ResponseHeader;
+use pingora_proxy::Session;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value as JsonValue};
+use validator::Validate;
+
+use crate::{
+    core::{ProxyContext, ProxyError, ProxyPlugin, ProxyResult},
+    utils::compression::{self, CompressionRules},
+};
+
+pub const PLUGIN_NAME: &str = "zstd";
+pub const PRIORITY: i32 = 997;
+
+/// Creates a Zstd plugin instance with the given configuration.
+pub fn create_zstd_plugin(cfg: JsonValue) -> ProxyResult<Arc<dyn ProxyPlugin>> {
+    let config = PluginConfig::try_from(cfg)?;
+    Ok(Arc::new(PluginZstd { config }))
+}
+
+/// JSON Schema of the zstd plugin configuration.
+pub fn schema() -> JsonValue {
+    let mut properties = CompressionRules::schema_properties();
+    properties.insert(
+        "comp_level".into(),
+        json!({"type": "integer", "minimum": 0, "maximum": 22, "default": 3}),
+    );
+    json!({"type": "object", "properties": properties})
+}
+
+/// Configuration for the Zstd plugin.
+#[derive(Default, Debug, Serialize, Deserialize, Validate)]
+pub(super) struct PluginConfig {
+    /// Compression level (0-22) for Zstd.
+    #[serde(default = "PluginConfig::default_comp_level")]
+    #[validate(range(min = 0, max = 22))]
+    comp_level: u32,
+
+    /// Which responses are compressed (`min_length`, `types`, `vary`).
+    #[serde(flatten)]
+    #[validate(nested)]
+    rules: CompressionRules,
+}
+
+impl PluginConfig {
+    fn default_comp_level() -> u32 {
+        3
+    }
+}
+
+impl TryFrom<JsonValue> for PluginConfig {
+    type Error = ProxyError;
+
+    fn try_from(value: JsonValue) -> Result<Self, Self::Error> {
+        let config: PluginConfig = serde_json::from_value(value)
+            .map_err(|e| ProxyError::serialization_error("Invalid zstd plugin config", e))?;
+
+        config.validate()?;
+
+        Ok(config)
+    }
+}
+
+/// Zstd plugin implementation.
+pub struct PluginZstd {
+    config: PluginConfig,
+}
+
+#[async_trait]
+impl ProxyPlugin for PluginZstd {
+    fn name(&self) -> &str {
+        PLUGIN_NAME
+    }
+
+    fn priority(&self) -> i32 {
+        PRIORITY
+    }
+
+    async fn early_request_filter(
+        &self,
+        session: &mut Session,
+        ctx: &mut ProxyContext,
+    ) -> Result<()> {
+        compression::enable_codec(session, ctx, Algorithm::Zstd, self.config.comp_level);
+
+        Ok(())
+    }
+
+    async fn response_filter(
+        &self,
+        session: &mut Session,
+        upstream_response: &mut ResponseHeader,
+        ctx: &mut ProxyContext,
+    ) -> Result<()> {
+        // Streaming responses already had compression turned off.
+        if ctx.streaming {
+            return Ok(());
+        }
+        self.config
+            .rules
+            .apply(session, upstream_response, Algorithm::Zstd)
+    }
+}
@@ -0,0 +1,157 @@
+//! Certificate expiry monitoring.
+//!
+//! Every `pingsix.cert_expiry.interval` seconds the certificates of all loaded `SSL`
+//! resources and of the TLS listeners are checked. The time left until each expires is
+//! exported as `pingsix_certificate_expiry_seconds{sni,ssl}` (negative once expired),
+//! and certificates within `warn_days` of expiring are logged as warnings.
+
+use std::time::Duration;
+
+use async_trait::async_trait;
+use once_cell::sync::Lazy;
+use openssl::asn1::Asn1Time;
+use pingora::tls::x509::{X509Ref, X509};
+use pingora_core::{
+    server::ShutdownWatch,
+    services::background::{background_service, BackgroundService, GenBackgroundService},
+};
+use prometheus::{register_int_gauge_vec, IntGaugeVec};
+
+use super::runtime::RUNTIME;
+use crate::config::{CertExpiry, Listener};
+
+/// `sni` label of listener certificates, which are served when no SNI matches.
+const LISTENER_SNI: &str = "*";
+
+static CERTIFICATE_EXPIRY: Lazy<IntGaugeVec> = Lazy::new(|| {
+    register_int_gauge_vec!(
+        "pingsix_certificate_expiry_seconds",
+        "Seconds until the certificate served for an SNI expires",
+        &["sni", "ssl"]
+    )
+    .expect("certificate expiry metric registration must succeed")
+});
+
+/// Build the monitor. Listener certificates are read once here, since listeners do not
+/// reload them.
+pub fn service(config: CertExpiry, listeners: &[Listener]) -> GenBackgroundService<ExpiryMonitor> {
+    let listeners = listeners
+        .iter()
+        .filter_map(|listener| {
+            let tls = listener.tls.as_ref()?;
+            let cert = std::fs::read(&tls.cert_path)
+                .ok()
+                .and_then(|pem| X509::from_pem(&pem).ok());
+            if cert.is_none() {
+                log::warn!(
+                    "Cannot check expiry of listener certificate '{}'",
+                    tls.cert_path
+                );
+            }
+            Some((format!("listener:{}", listener.address), cert?))
+        })
+        .collect();
+    background_service(
+        "certificate expiry monitor",
+        ExpiryMonitor { config, listeners },
+    )
+}
+
+/// Periodically exports and checks certificate expiry.
+pub struct ExpiryMonitor {
+    config: CertExpiry,
+    listeners: Vec<(String, X509)>,
+}
+
+#[async_trait]
+impl BackgroundService for ExpiryMonitor {
+    async fn start(&self, mut shutdown: ShutdownWatch) {
+        let mut interval = tokio::time::interval(Duration::from_secs(self.config.interval));
+        loop {
+            tokio::select! {
+                _ = shutdown.changed() => return,
+                _ = interval.tick() => self.scan(),
+            }
+        }
+    }
+}
+
+impl ExpiryMonitor {
+    fn scan(&self) {
+        // Start over so removed SSL resources and SNIs disappear from the metric.
+        CERTIFICATE_EXPIRY.reset();
+        let runtime = RUNTIME.load();
+        for ssl in runtime.ssls.values() {
+            self.check(&ssl.inner.id, &ssl.inner.snis, ssl.certificate());
+        }
+        for (name, cert) in &self.listeners {
+            self.check(name, &[LISTENER_SNI.to_string()], cert);
+        }
+    }
+
+    fn check(&self, ssl: &str, snis: &[String], cert: &X509Ref) {
+        let Some(left) = seconds_until_expiry(cert) else {
+            log::warn!("Cannot read the expiry of certificate '{ssl}'");
+            return;
+        };
+        for sni in snis {
+            CERTIFICATE_EXPIRY.with_label_values(&[sni, ssl]).set(left);
+        }
+        if left <= 0 {
+            log::warn!("Certificate '{ssl}' for {snis:?} has expired");
+        } else if left <= (self.config.warn_days * 86400) as i64 {
+            log::warn!(
+                "Certificate '{ssl}' for {snis:?} expires in {} days",
+                left / 86400
+            );
+        }
+    }
+}
+
+/// Seconds from now until `cert` expires, negative once it has.
+fn seconds_until_expiry(cert: &X509Ref) -> Option<i64> {
+    let now = Asn1Time::days_from_now(0).ok()?;
+    let diff = now.diff(cert.not_after()).ok()?;
+    Some(i64::from(diff.days) * 86400 + i64::from(diff.secs))
+}
+
+#[cfg(test)]
+mod tests {
+    use openssl::{hash::MessageDigest, pkey::PKey, x509::X509Builder};
+
+    use super::*;
+
+    fn certificate_valid_for(days: u32) -> X509 {
+        let key = PKey::private_key_from_pem(include_bytes!("testdata/example.key")).unwrap();
+        let mut builder = X509Builder::new().unwrap();
+        builder.set_pubkey(&key).unwrap();
+        builder
+            .set_not_before(&Asn1Time::days_from_now(0).unwrap())
+            .unwrap();
+        builder
+            .set_not_after(&Asn1Time::days_from_now(days).unwrap())
+            .unwrap();
+        builder.sign(&key, MessageDigest::sha256()).unwrap();
+        builder.build()
+    }
+
+    #[test]
+    fn expiry_is_counted_in_seconds_from_now() {
+        let left = seconds_until_expiry(&certificate_valid_for(2)).unwrap();
+        assert!((2 * 86400 - 5..=2 * 86400).contains(&left), "{left}");
+
+        let monitor = ExpiryMonitor {
+            config: CertExpiry::default(),
+            listeners: Vec::new(),
+        };
+        monitor.check(
+            "soon",
+            &["soon.example.com".to_string()],
+            &certificate_valid_for(2),
+        );
+        let exported = CERTIFICATE_EXPIRY
+            .with_label_values(&["soon.example.com", "soon"])
+            .get();
+        assert!((2 * 86400 - 5..=2 * 86400).contains(&exported));
+    }
+}
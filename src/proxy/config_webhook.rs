@@ -0,0 +1,269 @@
+//! Route table change notifications.
+//!
+//! With `pingsix.config_webhook` set, every published snapshot whose routes differ
+//! from the previous one is POSTed to the webhook as a JSON summary carrying the
+//! configuration revision, for audit trails and deployment tooling. Notifications are
+//! queued at publish time and delivered in order by a background service, once each;
+//! failed deliveries are logged and counted but not retried.
+
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use async_trait::async_trait;
+use bytes::Bytes;
+use once_cell::sync::{Lazy, OnceCell};
+use pingora_core::{
+    connectors::http::Connector as HttpConnector,
+    server::ShutdownWatch,
+    services::background::{background_service, BackgroundService, GenBackgroundService},
+    upstreams::peer::HttpPeer,
+};
+use pingora_error::{Error, ErrorType, OrErr, Result};
+use pingora_http::RequestHeader;
+use prometheus::{register_int_counter_vec, IntCounterVec};
+use serde::Serialize;
+use serde_json::{json, Value as JsonValue};
+use tokio::sync::mpsc;
+
+use super::runtime::RuntimeSnapshot;
+use crate::{config::ConfigWebhook, core::status};
+
+static DELIVERIES: Lazy<IntCounterVec> = Lazy::new(|| {
+    register_int_counter_vec!(
+        "pingsix_config_webhook_deliveries_total",
+        "Route table change notifications sent to the config webhook",
+        &["result"]
+    )
+    .expect("config webhook metric registration must succeed")
+});
+
+/// Queue of pending notifications, set when a webhook is configured.
+static QUEUE: OnceCell<mpsc::UnboundedSender<JsonValue>> = OnceCell::new();
+
+/// Route ids that differ between two snapshots, sorted.
+#[derive(Debug, Default, PartialEq, Eq, Serialize)]
+struct RouteChanges {
+    added: Vec<String>,
+    updated: Vec<String>,
+    removed: Vec<String>,
+}
+
+impl RouteChanges {
+    fn between(previous: &RuntimeSnapshot, current: &RuntimeSnapshot) -> Self {
+        let mut changes = Self::default();
+        for (id, route) in current.routes.iter() {
+            match previous.routes.get(id) {
+                None => changes.added.push(id.clone()),
+                Some(old) if !Arc::ptr_eq(old, route) && old.inner != route.inner => {
+                    changes.updated.push(id.clone())
+                }
+                Some(_) => {}
+            }
+        }
+        changes.removed = previous
+            .routes
+            .keys()
+            .filter(|id| !current.routes.contains_key(*id))
+            .cloned()
+            .collect();
+        changes.added.sort();
+        changes.updated.sort();
+        changes.removed.sort();
+        changes
+    }
+
+    fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.updated.is_empty() && self.removed.is_empty()
+    }
+}
+
+/// Enable notifications. Returns the service delivering them, to be added to the
+/// server; later calls return `None`.
+pub fn init(config: Option<ConfigWebhook>) -> Option<GenBackgroundService<WebhookService>> {
+    let config = config?;
+    let (sender, receiver) = mpsc::unbounded_channel();
+    QUEUE.set(sender).ok()?;
+    Some(background_service(
+        "config webhook",
+        WebhookService {
+            config,
+            receiver: Mutex::new(Some(receiver)),
+            connector: HttpConnector::new(None),
+        },
+    ))
+}
+
+/// Queue a notification if the route table changed between the two snapshots.
+pub fn notify(previous: &RuntimeSnapshot, current: &RuntimeSnapshot) {
+    let Some(queue) = QUEUE.get() else {
+        return;
+    };
+    let changes = RouteChanges::between(previous, current);
+    if changes.is_empty() {
+        return;
+    }
+    let timestamp = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default();
+    let _ = queue.send(json!({
+        "event": "routes_changed",
+        "config_revision": status::config_revision(),
+        "revision": current.revision,
+        "timestamp": timestamp,
+        "route_count": current.routes.len(),
+        "routes": changes,
+    }));
+}
+
+/// Delivers queued notifications to the configured webhook.
+pub struct WebhookService {
+    config: ConfigWebhook,
+    receiver: Mutex<Option<mpsc::UnboundedReceiver<JsonValue>>>,
+    connector: HttpConnector,
+}
+
+#[async_trait]
+impl BackgroundService for WebhookService {
+    async fn start(&self, mut shutdown: ShutdownWatch) {
+        let Some(mut receiver) = self
+            .receiver
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .take()
+        else {
+            return;
+        };
+        loop {
+            tokio::select! {
+                _ = shutdown.changed() => return,
+                event = receiver.recv() => match event {
+                    Some(event) => self.deliver(&event).await,
+                    None => return,
+                },
+            }
+        }
+    }
+}
+
+impl WebhookService {
+    async fn deliver(&self, event: &JsonValue) {
+        let timeout = Duration::from_secs(self.config.timeout);
+        let result = match tokio::time::timeout(timeout, self.send(event)).await {
+            Ok(Ok(status)) if (200..300).contains(&status) => Ok(()),
+            Ok(Ok(status)) => Err(format!("receiver answered {status}")),
+            Ok(Err(e)) => Err(e.to_string()),
+            Err(_) => Err("timed out".to_string()),
+        };
+        match result {
+            Ok(()) => DELIVERIES.with_label_values(&["success"]).inc(),
+            Err(e) => {
+                log::warn!("Config webhook delivery to {} failed: {e}", self.config.url);
+                DELIVERIES.with_label_values(&["failure"]).inc();
+            }
+        }
+    }
+
+    async fn send(&self, event: &JsonValue) -> Result<u16> {
+        let url = url::Url::parse(&self.config.url)
+            .or_err(ErrorType::InternalError, "invalid config webhook url")?;
+        let host = url.host_str().unwrap_or_default();
+        let port = url.port_or_known_default().unwrap_or(80);
+        let address = tokio::net::lookup_host((host, port))
+            .await
+            .or_err(
+                ErrorType::ConnectNoRoute,
+                "config webhook host did not resolve",
+            )?
+            .next()
+            .ok_or_else(|| {
+                Error::explain(
+                    ErrorType::ConnectNoRoute,
+                    "config webhook host has no address",
+                )
+            })?;
+        let peer = HttpPeer::new(address, url.scheme() == "https", host.to_string());
+
+        let body = Bytes::from(event.to_string());
+        let path = match url.query() {
+            Some(query) => format!("{}?{query}", url.path()),
+            None => url.path().to_string(),
+        };
+        let req = build_request(&path, host, &self.config.headers, body.len())?;
+
+        let (mut client, _) = self.connector.get_http_session(&peer).await?;
+        client.write_request_header(Box::new(req)).await?;
+        client.write_request_body(body, true).await?;
+        client.finish_request_body().await?;
+        client.read_response_header().await?;
+        let status = client
+            .response_header()
+            .map_or(0, |resp| resp.status.as_u16());
+        Ok(status)
+    }
+}
+
+fn build_request(
+    path: &str,
+    host: &str,
+    headers: &HashMap<String, String>,
+    content_length: usize,
+) -> Result<RequestHeader> {
+    let mut req = RequestHeader::build("POST", path.as_bytes(), None)?;
+    req.insert_header("Host", host)?;
+    req.insert_header("Content-Type", "application/json")?;
+    req.insert_header("Content-Length", content_length.to_string())?;
+    for (name, value) in headers {
+        req.insert_header(name.clone(), value.as_str())?;
+    }
+    Ok(req)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn route_changes_ignore_rebuilt_but_unchanged_routes() {
+        use crate::proxy::control_plane::{CandidateSnapshot, ResourceConfigSet};
+
+        let snapshot = |routes: &[(&str, &str)]| {
+            let mut set = ResourceConfigSet::default();
+            set.upstreams.insert(
+                "u1".into(),
+                serde_json::from_value(json!({"id": "u1", "nodes": {"127.0.0.1:80": 1}})).unwrap(),
+            );
+            for (id, uri) in routes {
+                let route = json!({"id": id, "uri": uri, "upstream_id": "u1"});
+                set.routes
+                    .insert(id.to_string(), serde_json::from_value(route).unwrap());
+            }
+            RuntimeSnapshot::compile(CandidateSnapshot::build(set).unwrap(), 1).unwrap()
+        };
+
+        let before = snapshot(&[("a", "/a"), ("b", "/b"), ("c", "/c")]);
+        let after = snapshot(&[("a", "/a"), ("b", "/b2"), ("d", "/d")]);
+        assert_eq!(
+            RouteChanges::between(&before, &after),
+            RouteChanges {
+                added: vec!["d".into()],
+                updated: vec!["b".into()],
+                removed: vec!["c".into()],
+            }
+        );
+        assert!(RouteChanges::between(&after, &after).is_empty());
+    }
+
+    #[test]
+    fn request_carries_configured_headers() {
+        let headers = HashMap::from([("Authorization".to_string(), "Bearer t".to_string())]);
+        let req = build_request("/hooks?x=1", "deploy.example.com", &headers, 12).unwrap();
+        assert_eq!(req.uri.to_string(), "/hooks?x=1");
+        assert_eq!(req.headers["host"], "deploy.example.com");
+        assert_eq!(req.headers["authorization"], "Bearer t");
+        assert_eq!(req.headers["content-length"], "12");
+    }
+}
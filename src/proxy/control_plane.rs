@@ -4,7 +4,8 @@
 //! compile it into a `RuntimeSnapshot`, and publish only on full success.
 
 use std::{
-    collections::HashMap,
+    borrow::Cow,
+    collections::{HashMap, HashSet},
     sync::{Arc, Mutex},
 };
 
@@ -12,7 +13,6 @@ use futures::{stream, StreamExt, TryStreamExt};
 use tokio::sync::{mpsc, Mutex as AsyncMutex};
 use tokio_util::sync::CancellationToken;
 
-use etcd_client::{Event, GetResponse};
 use once_cell::sync::Lazy;
 use prometheus::{register_int_counter_vec, register_int_gauge, IntCounterVec, IntGauge};
 use validator::Validate;
@@ -21,9 +21,14 @@ use crate::{
     config::{
         self,
         etcd::{canonicalize_prefix, json_to_resource},
-        GlobalRule, Identifiable, Route, Service, Upstream, SSL,
+        provider::ConfigChange,
+        GlobalRule, Identifiable, IpList, PluginConfig, Route, Service, Upstream, SSL,
+    },
+    core::{
+        metrics,
+        status::{self, SkippedResource},
+        ProxyError, ProxyResult,
     },
-    core::{status, ProxyError, ProxyResult},
 };
 
 static PREPARATION_ATTEMPTS: Lazy<IntCounterVec> = Lazy::new(|| {
@@ -44,12 +49,13 @@ static PENDING_REVISION: Lazy<IntGauge> = Lazy::new(|| {
 
 use super::{
     global_rule::ProxyGlobalRule,
-    route::ProxyRoute,
+    ip_list::ProxyIpList,
+    route::{virtual_host_route, virtual_host_route_id, ProxyRoute},
     runtime::{RuntimeSnapshot, RUNTIME},
     service::ProxyService,
     ssl::ProxySSL,
     upstream::{
-        discovery::prepare_upstream, inline_key, named_key, prepare_static_upstream,
+        ai_proxy_key, discovery::prepare_upstream, inline_key, named_key, prepare_static_upstream,
         traffic_split_key, PreparedUpstreams, ProxyUpstream,
     },
 };
@@ -62,6 +68,8 @@ pub struct ResourceConfigSet {
     pub global_rules: HashMap<String, GlobalRule>,
     pub routes: HashMap<String, Route>,
     pub ssls: HashMap<String, SSL>,
+    pub ip_lists: HashMap<String, IpList>,
+    pub plugin_configs: HashMap<String, PluginConfig>,
 }
 
 impl ResourceConfigSet {
@@ -82,6 +90,13 @@ impl ResourceConfigSet {
         for ssl in &config.ssls {
             set.ssls.insert(ssl.id.clone(), ssl.clone());
         }
+        for list in &config.ip_lists {
+            set.ip_lists.insert(list.id.clone(), list.clone());
+        }
+        for plugin_config in &config.plugin_configs {
+            set.plugin_configs
+                .insert(plugin_config.id.clone(), plugin_config.clone());
+        }
         set
     }
 
@@ -92,29 +107,67 @@ impl ResourceConfigSet {
             && self.global_rules.is_empty()
             && self.routes.is_empty()
             && self.ssls.is_empty()
+            && self.ip_lists.is_empty()
+            && self.plugin_configs.is_empty()
     }
 
-    pub fn from_etcd_list(response: &GetResponse, prefix: &str) -> ProxyResult<Self> {
-        let canonical = canonicalize_prefix(prefix);
-        let mut set = Self::default();
-        for kv in response.kvs() {
-            if is_metadata_key(kv.key()) {
-                continue;
+    /// Remove the resource `resource_type/id`, if present.
+    fn remove(&mut self, resource_type: &str, id: &str) -> ProxyResult<()> {
+        match resource_type {
+            "upstreams" => {
+                self.upstreams.remove(id);
             }
-            let key = String::from_utf8_lossy(kv.key());
-            if !key.starts_with(&canonical) {
-                log::warn!("Ignoring etcd key outside configured namespace: {key}");
-                continue;
+            "services" => {
+                self.services.remove(id);
+            }
+            "global_rules" => {
+                self.global_rules.remove(id);
+            }
+            "routes" => {
+                self.routes.remove(id);
+            }
+            "ssls" => {
+                self.ssls.remove(id);
+            }
+            "ip_lists" => {
+                self.ip_lists.remove(id);
+            }
+            "plugin_configs" => {
+                self.plugin_configs.remove(id);
+            }
+            other => {
+                return Err(ProxyError::Configuration(format!(
+                    "Unhandled resource type: {other}"
+                )));
             }
-            insert_kv(&mut set, kv.key(), kv.value(), &canonical)?;
         }
-        Ok(set)
+        Ok(())
+    }
+
+    /// The plugins of `route` merged with those of its `plugin_config_id`; the
+    /// route's own plugins win. Borrowed when the route uses no plugin config.
+    fn route_plugins<'a>(
+        &'a self,
+        route: &'a Route,
+    ) -> ProxyResult<Cow<'a, HashMap<String, serde_json::Value>>> {
+        let Some(id) = &route.plugin_config_id else {
+            return Ok(Cow::Borrowed(&route.plugins));
+        };
+        let plugin_config = self.plugin_configs.get(id).ok_or_else(|| {
+            ProxyError::Configuration(format!(
+                "Route '{}' references missing plugin_config '{id}'",
+                route.id
+            ))
+        })?;
+        let mut plugins = plugin_config.plugins.clone();
+        plugins.extend(route.plugins.clone());
+        Ok(Cow::Owned(plugins))
     }
 }
 
 /// Insert a single `(key, value)` pair into a `ResourceConfigSet`.
 ///
-/// Shared by `from_etcd_list` and the admin CAS path (which builds a candidate
+/// Shared by provider listings and the admin CAS path (which builds a candidate
 /// set from a full-graph read before validating references).
 fn insert_kv(
     set: &mut ResourceConfigSet,
@@ -150,6 +203,16 @@ fn insert_kv(
             resource.set_id(id.clone());
             set.ssls.insert(id, resource);
         }
+        "ip_lists" => {
+            let mut resource = json_to_resource::<IpList>(value)?;
+            resource.set_id(id.clone());
+            set.ip_lists.insert(id, resource);
+        }
+        "plugin_configs" => {
+            let mut resource = json_to_resource::<PluginConfig>(value)?;
+            resource.set_id(id.clone());
+            set.plugin_configs.insert(id, resource);
+        }
         other => {
             return Err(ProxyError::Configuration(format!(
                 "Unknown etcd resource type: {other}"
@@ -190,8 +253,13 @@ pub fn build_config_set_from_kvs(
 /// - `route.service_id` must resolve to an existing service.
 /// - `route.upstream_id` (when no inline upstream) must resolve to an existing upstream.
 /// - `service.upstream_id` (when no inline upstream) must resolve to an existing upstream.
-/// - `traffic-split` `weighted_upstreams[].upstream_id` on routes, services, and
-///   global rules must resolve to an existing upstream.
+/// - `traffic-split` `weighted_upstreams[].upstream_id`, `workflow` `upstream_id`
+///   actions and the `fallback` `upstream_id` on routes, services, and global rules
+///   must resolve to an existing upstream.
+/// - `ip-restriction` `whitelist_lists`/`blacklist_lists` must resolve to existing
+///   IP lists.
+/// - `route.plugin_config_id` must resolve to an existing plugin config, whose
+///   plugins are checked like those of routes.
 ///
 /// Used by the Admin write path so PUT/DELETE do not pay the cost of building
 /// the full runtime graph (and its `Configuring ...` log noise) just to check
@@ -222,9 +290,23 @@ pub fn validate_config_set(set: &ResourceConfigSet) -> ProxyResult<()> {
             ProxyError::Configuration(format!("SSL '{}' validation failed: {e}", ssl.id))
         })?;
     }
+    for list in set.ip_lists.values() {
+        list.validate().map_err(|e| {
+            ProxyError::Configuration(format!("IpList '{}' validation failed: {e}", list.id))
+        })?;
+    }
+    for plugin_config in set.plugin_configs.values() {
+        plugin_config.validate().map_err(|e| {
+            ProxyError::Configuration(format!(
+                "PluginConfig '{}' validation failed: {e}",
+                plugin_config.id
+            ))
+        })?;
+    }
 
     // Cross-resource reference checks.
     for route in set.routes.values() {
+        set.route_plugins(route)?;
         if let Some(id) = &route.service_id {
             if !set.services.contains_key(id) {
                 return Err(ProxyError::Configuration(format!(
@@ -251,7 +333,7 @@ pub fn validate_config_set(set: &ResourceConfigSet) -> ProxyResult<()> {
     }
     for service in set.services.values() {
         if service.upstream.is_none() {
-            if let Some(id) = &service.upstream_id {
+            for id in service.upstream_ids() {
                 if !set.upstreams.contains_key(id) {
                     return Err(ProxyError::Configuration(format!(
                         "Service '{}' references missing upstream '{}'",
@@ -273,10 +355,235 @@ pub fn validate_config_set(set: &ResourceConfigSet) -> ProxyResult<()> {
             &set.upstreams,
         )?;
     }
+    for plugin_config in set.plugin_configs.values() {
+        validate_plugin_upstream_refs(
+            &format!("PluginConfig '{}'", plugin_config.id),
+            &plugin_config.plugins,
+            &set.upstreams,
+        )?;
+    }
+    validate_ip_list_refs(set)
+}
+
+/// Resources that must be removed together with `key_type/id` so every remaining
+/// reference still resolves (used by cascading Admin deletes).
+///
+/// Deleting an upstream removes the services, global rules and routes that use it
+/// (directly, as a blue/green side or through traffic-split, workflow or fallback); deleting a service removes its routes and
+/// those using a removed upstream; deleting an IP list removes the resources whose
+/// `ip-restriction` names it. Plugin configs are removed like services, together with
+/// the routes using them. Returns sorted `(key_type, id)` pairs, excluding the target
+/// itself.
+pub fn dependents_of(
+    set: &ResourceConfigSet,
+    key_type: &str,
+    id: &str,
+) -> Vec<(&'static str, String)> {
+    let mut upstreams = HashSet::new();
+    let mut services = HashSet::new();
+    let mut ip_lists = HashSet::new();
+    let mut plugin_configs = HashSet::new();
+    match key_type {
+        "upstreams" => upstreams.insert(id.to_string()),
+        "services" => services.insert(id.to_string()),
+        "ip_lists" => ip_lists.insert(id.to_string()),
+        "plugin_configs" => plugin_configs.insert(id.to_string()),
+        _ => return Vec::new(),
+    };
+
+    let mut dependents = Vec::new();
+    for plugin_config in set.plugin_configs.values() {
+        if uses_removed_upstream(None, false, &plugin_config.plugins, &upstreams)
+            || uses_removed_ip_list(&plugin_config.plugins, &ip_lists)
+        {
+            plugin_configs.insert(plugin_config.id.clone());
+            dependents.push(("plugin_configs", plugin_config.id.clone()));
+        }
+    }
+    for service in set.services.values() {
+        if uses_removed_upstream(
+            service.upstream_id.as_ref(),
+            service.upstream.is_some(),
+            &service.plugins,
+            &upstreams,
+        ) || service
+            .blue_green
+            .as_ref()
+            .is_some_and(|bg| upstreams.contains(&bg.blue) || upstreams.contains(&bg.green))
+            || uses_removed_ip_list(&service.plugins, &ip_lists)
+        {
+            services.insert(service.id.clone());
+            dependents.push(("services", service.id.clone()));
+        }
+    }
+    for rule in set.global_rules.values() {
+        if uses_removed_upstream(None, false, &rule.plugins, &upstreams)
+            || uses_removed_ip_list(&rule.plugins, &ip_lists)
+        {
+            dependents.push(("global_rules", rule.id.clone()));
+        }
+    }
+    for route in set.routes.values() {
+        let removed_service = route
+            .service_id
+            .as_ref()
+            .is_some_and(|id| services.contains(id));
+        let removed_plugin_config = route
+            .plugin_config_id
+            .as_ref()
+            .is_some_and(|id| plugin_configs.contains(id));
+        if removed_service
+            || removed_plugin_config
+            || uses_removed_upstream(
+                route.upstream_id.as_ref(),
+                route.upstream.is_some(),
+                &route.plugins,
+                &upstreams,
+            )
+            || uses_removed_ip_list(&route.plugins, &ip_lists)
+        {
+            dependents.push(("routes", route.id.clone()));
+        }
+    }
+    dependents.sort();
+    dependents
+}
+
+fn uses_removed_upstream(
+    upstream_id: Option<&String>,
+    has_inline_upstream: bool,
+    plugins: &HashMap<String, serde_json::Value>,
+    removed: &HashSet<String>,
+) -> bool {
+    if !has_inline_upstream && upstream_id.is_some_and(|id| removed.contains(id)) {
+        return true;
+    }
+    plugin_upstream_ids(plugins)
+        .iter()
+        .any(|id| removed.contains(id))
+}
+
+/// Named upstreams referenced by `traffic-split`, `workflow` and `fallback` plugin configs.
+fn plugin_upstream_ids(plugins: &HashMap<String, serde_json::Value>) -> Vec<String> {
+    let mut ids = Vec::new();
+    if let Some(value) = plugins.get(crate::plugins::traffic_split::PLUGIN_NAME) {
+        ids.extend(crate::plugins::traffic_split::named_upstream_ids(value).unwrap_or_default());
+    }
+    if let Some(value) = plugins.get(crate::plugins::workflow::PLUGIN_NAME) {
+        ids.extend(crate::plugins::workflow::named_upstream_ids(value).unwrap_or_default());
+    }
+    if let Some(value) = plugins.get(crate::plugins::fallback::PLUGIN_NAME) {
+        ids.extend(crate::plugins::fallback::named_upstream_ids(value).unwrap_or_default());
+    }
+    ids
+}
+
+fn uses_removed_ip_list(
+    plugins: &HashMap<String, serde_json::Value>,
+    removed: &HashSet<String>,
+) -> bool {
+    !removed.is_empty()
+        && plugins
+            .get(crate::plugins::ip_restriction::PLUGIN_NAME)
+            .is_some_and(|value| {
+                crate::plugins::ip_restriction::named_ip_list_ids(value)
+                    .iter()
+                    .any(|id| removed.contains(id))
+            })
+}
+
+/// Check that every `ip-restriction` list reference resolves.
+fn validate_ip_list_refs(set: &ResourceConfigSet) -> ProxyResult<()> {
+    for route in set.routes.values() {
+        validate_plugin_ip_list_refs(
+            &format!("Route '{}'", route.id),
+            &route.plugins,
+            &set.ip_lists,
+        )?;
+    }
+    for service in set.services.values() {
+        validate_plugin_ip_list_refs(
+            &format!("Service '{}'", service.id),
+            &service.plugins,
+            &set.ip_lists,
+        )?;
+    }
+    for rule in set.global_rules.values() {
+        validate_plugin_ip_list_refs(
+            &format!("GlobalRule '{}'", rule.id),
+            &rule.plugins,
+            &set.ip_lists,
+        )?;
+    }
+    for plugin_config in set.plugin_configs.values() {
+        validate_plugin_ip_list_refs(
+            &format!("PluginConfig '{}'", plugin_config.id),
+            &plugin_config.plugins,
+            &set.ip_lists,
+        )?;
+    }
     Ok(())
 }
 
-/// Validate plugin-embedded named upstream references (currently traffic-split).
+/// Tolerant counterpart of [`validate_ip_list_refs`]: resources naming a missing list
+/// are removed from `set` and recorded in `skipped`.
+fn drop_unresolved_ip_list_refs(set: &mut ResourceConfigSet, skipped: &mut Vec<SkippedResource>) {
+    let check =
+        |owner: String, plugins| validate_plugin_ip_list_refs(&owner, plugins, &set.ip_lists);
+    let mut unresolved = Vec::new();
+    for (id, route) in &set.routes {
+        unresolved.push(("routes", id, check(format!("Route '{id}'"), &route.plugins)));
+    }
+    for (id, service) in &set.services {
+        unresolved.push((
+            "services",
+            id,
+            check(format!("Service '{id}'"), &service.plugins),
+        ));
+    }
+    for (id, rule) in &set.global_rules {
+        unresolved.push((
+            "global_rules",
+            id,
+            check(format!("GlobalRule '{id}'"), &rule.plugins),
+        ));
+    }
+    for (id, plugin_config) in &set.plugin_configs {
+        unresolved.push((
+            "plugin_configs",
+            id,
+            check(format!("PluginConfig '{id}'"), &plugin_config.plugins),
+        ));
+    }
+    let unresolved: Vec<_> = unresolved
+        .into_iter()
+        .filter_map(|(resource_type, id, result)| Some((resource_type, id.clone(), result.err()?)))
+        .collect();
+    for (resource_type, id, error) in unresolved {
+        log::warn!("Skipping {resource_type}/{id}: {error}");
+        skipped.push(SkippedResource::new(resource_type, &id, error));
+        let _ = set.remove(resource_type, &id);
+    }
+}
+
+fn validate_plugin_ip_list_refs(
+    owner: &str,
+    plugins: &HashMap<String, serde_json::Value>,
+    ip_lists: &HashMap<String, IpList>,
+) -> ProxyResult<()> {
+    if let Some(value) = plugins.get(crate::plugins::ip_restriction::PLUGIN_NAME) {
+        for id in crate::plugins::ip_restriction::named_ip_list_ids(value) {
+            if !ip_lists.contains_key(&id) {
+                return Err(ProxyError::Configuration(format!(
+                    "{owner} ip-restriction references missing ip_list '{id}'"
+                )));
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Validate plugin-embedded named upstream references (traffic-split, workflow and fallback).
 fn validate_plugin_upstream_refs(
     owner: &str,
     plugins: &HashMap<String, serde_json::Value>,
@@ -292,6 +599,24 @@ fn validate_plugin_upstream_refs(
             }
         }
     }
+    if let Some(value) = plugins.get(crate::plugins::workflow::PLUGIN_NAME) {
+        for id in crate::plugins::workflow::named_upstream_ids(value)? {
+            if !upstreams.contains_key(&id) {
+                return Err(ProxyError::Configuration(format!(
+                    "{owner} workflow references missing upstream '{id}'"
+                )));
+            }
+        }
+    }
+    if let Some(value) = plugins.get(crate::plugins::fallback::PLUGIN_NAME) {
+        for id in crate::plugins::fallback::named_upstream_ids(value)? {
+            if !upstreams.contains_key(&id) {
+                return Err(ProxyError::Configuration(format!(
+                    "{owner} fallback references missing upstream '{id}'"
+                )));
+            }
+        }
+    }
     Ok(())
 }
 
@@ -302,12 +627,13 @@ pub struct CandidateSnapshot {
     pub global_rules: HashMap<String, Arc<ProxyGlobalRule>>,
     pub routes: HashMap<String, Arc<ProxyRoute>>,
     pub ssls: HashMap<String, Arc<ProxySSL>>,
+    pub ip_lists: HashMap<String, Arc<ProxyIpList>>,
 }
 
 impl CandidateSnapshot {
     /// Build every runtime object from the same raw resource graph.
     ///
-    /// Dependency order: upstreams → services → global rules → routes → ssls.
+    /// Dependency order: upstreams → services → global rules → routes → ssls → IP lists.
     /// Constructors must not read mutable global state other than the previously
     /// published [`RUNTIME`] snapshot used for Arc reuse.
     pub fn build(config: ResourceConfigSet) -> ProxyResult<Self> {
@@ -321,39 +647,60 @@ impl CandidateSnapshot {
         config: ResourceConfigSet,
         prepared: &PreparedUpstreams,
     ) -> ProxyResult<Self> {
-        for upstream in config.upstreams.values() {
-            upstream.validate().map_err(|e| {
-                ProxyError::Configuration(format!(
-                    "Upstream '{}' validation failed: {e}",
-                    upstream.id
-                ))
-            })?;
-        }
-        for service in config.services.values() {
-            service.validate().map_err(|e| {
-                ProxyError::Configuration(format!(
-                    "Service '{}' validation failed: {e}",
-                    service.id
-                ))
-            })?;
-        }
-        for rule in config.global_rules.values() {
-            rule.validate().map_err(|e| {
-                ProxyError::Configuration(format!(
-                    "GlobalRule '{}' validation failed: {e}",
-                    rule.id
-                ))
-            })?;
-        }
-        for route in config.routes.values() {
-            route.validate().map_err(|e| {
-                ProxyError::Configuration(format!("Route '{}' validation failed: {e}", route.id))
-            })?;
+        Self::build_prepared_with(config, prepared, None)
+    }
+
+    /// [`Self::build_prepared`] that, when `skipped` is given, leaves out each resource
+    /// failing validation or compilation (and so everything depending on it) and
+    /// records it there instead of failing the whole candidate.
+    pub(crate) fn build_prepared_with(
+        mut config: ResourceConfigSet,
+        prepared: &PreparedUpstreams,
+        mut skipped: Option<&mut Vec<SkippedResource>>,
+    ) -> ProxyResult<Self> {
+        let skipped = &mut skipped;
+        validate_resources(&mut config.upstreams, "upstreams", "Upstream", skipped)?;
+        validate_resources(&mut config.services, "services", "Service", skipped)?;
+        validate_resources(
+            &mut config.global_rules,
+            "global_rules",
+            "GlobalRule",
+            skipped,
+        )?;
+        validate_resources(&mut config.routes, "routes", "Route", skipped)?;
+        validate_resources(&mut config.ssls, "ssls", "SSL", skipped)?;
+        validate_resources(&mut config.ip_lists, "ip_lists", "IpList", skipped)?;
+        validate_resources(
+            &mut config.plugin_configs,
+            "plugin_configs",
+            "PluginConfig",
+            skipped,
+        )?;
+        // Routes are compiled with their plugin config merged in, so a changed plugin
+        // config rebuilds exactly the routes using it.
+        let mut resolved_routes = HashMap::with_capacity(config.routes.len());
+        for (id, route) in &config.routes {
+            let plugins = match config.route_plugins(route) {
+                Ok(plugins) => plugins.into_owned(),
+                Err(e) => {
+                    skip_or_fail(skipped, "routes", id, e)?;
+                    continue;
+                }
+            };
+            resolved_routes.insert(
+                id.clone(),
+                Route {
+                    plugins,
+                    ..route.clone()
+                },
+            );
         }
-        for ssl in config.ssls.values() {
-            ssl.validate().map_err(|e| {
-                ProxyError::Configuration(format!("SSL '{}' validation failed: {e}", ssl.id))
-            })?;
+
+        // A `virtual_host` service serves its hosts through a fallback route bound to it.
+        for (id, service) in &config.services {
+            if service.virtual_host {
+                resolved_routes.insert(virtual_host_route_id(id), virtual_host_route(id));
+            }
         }
 
         let previous = RUNTIME.load();
@@ -364,14 +711,25 @@ impl CandidateSnapshot {
             log::info!("Configuring upstream: {id}");
             let arc = match previous.upstreams.get(&id) {
                 Some(existing) if existing.inner == upstream => existing.clone(),
-                _ => {
+                existing => {
                     all_named_upstreams_reused = false;
-                    Arc::new(ProxyUpstream::build(
-                        upstream,
-                        prepared.get(&named_key(&id)).cloned().ok_or_else(|| {
+                    let built = prepared
+                        .get(&named_key(&id))
+                        .cloned()
+                        .ok_or_else(|| {
                             ProxyError::Configuration(format!("Upstream '{id}' was not prepared"))
-                        })?,
-                    )?)
+                        })
+                        .and_then(|prepared| match existing {
+                            Some(existing) => existing.rebuild(upstream, prepared),
+                            None => ProxyUpstream::build(upstream, prepared),
+                        });
+                    match built {
+                        Ok(upstream) => Arc::new(upstream),
+                        Err(e) => {
+                            skip_or_fail(skipped, "upstreams", &id, e)?;
+                            continue;
+                        }
+                    }
                 }
             };
             upstreams.insert(id, arc);
@@ -388,13 +746,21 @@ impl CandidateSnapshot {
         let mut services = HashMap::with_capacity(config.services.len());
         for (id, service) in config.services {
             log::info!("Configuring service: {id}");
-            let arc = if all_named_upstreams_reused {
-                match previous.services.get(&id) {
-                    Some(existing) if existing.inner == service => existing.clone(),
-                    _ => Arc::new(ProxyService::build(service, &upstreams, prepared)?),
-                }
-            } else {
-                Arc::new(ProxyService::build(service, &upstreams, prepared)?)
+            let existing = previous.services.get(&id);
+            let rebuild = needs_rebuild(
+                "service",
+                existing.map(|e| e.inner == service),
+                all_named_upstreams_reused,
+            );
+            let arc = match existing {
+                Some(existing) if !rebuild => existing.clone(),
+                _ => match ProxyService::build(service, &upstreams, prepared) {
+                    Ok(service) => Arc::new(service),
+                    Err(e) => {
+                        skip_or_fail(skipped, "services", &id, e)?;
+                        continue;
+                    }
+                },
             };
             services.insert(id, arc);
         }
@@ -402,36 +768,48 @@ impl CandidateSnapshot {
         let mut global_rules = HashMap::with_capacity(config.global_rules.len());
         for (id, rule) in config.global_rules {
             log::info!("Configuring global rule: {id}");
-            let arc = if all_named_upstreams_reused {
-                match previous.global_rules.get(&id) {
-                    Some(existing) if existing.inner == rule => existing.clone(),
-                    _ => Arc::new(ProxyGlobalRule::build(rule, &upstreams, prepared)?),
-                }
-            } else {
-                Arc::new(ProxyGlobalRule::build(rule, &upstreams, prepared)?)
+            let existing = previous.global_rules.get(&id);
+            let rebuild = needs_rebuild(
+                "global_rule",
+                existing.map(|e| e.inner == rule),
+                all_named_upstreams_reused,
+            );
+            let arc = match existing {
+                Some(existing) if !rebuild => existing.clone(),
+                _ => match ProxyGlobalRule::build(rule, &upstreams, prepared) {
+                    Ok(rule) => Arc::new(rule),
+                    Err(e) => {
+                        skip_or_fail(skipped, "global_rules", &id, e)?;
+                        continue;
+                    }
+                },
             };
             global_rules.insert(id, arc);
         }
 
-        let services_stable = all_named_upstreams_reused
-            && previous.services.keys().all(|id| {
-                services
-                    .get(id)
-                    .zip(previous.services.get(id))
-                    .is_some_and(|(a, b)| Arc::ptr_eq(a, b))
-            })
-            && previous.services.len() == services.len();
-
-        let mut routes = HashMap::with_capacity(config.routes.len());
-        for (id, route) in config.routes {
+        // A route is rebuilt when its own config changed or when the service it is
+        // bound to was rebuilt (its generation moved), so a service edit invalidates
+        // exactly the executors that merged its plugins.
+        let mut routes = HashMap::with_capacity(resolved_routes.len());
+        for (id, route) in resolved_routes {
             log::info!("Configuring route: {id}");
-            let arc = if services_stable {
-                match previous.routes.get(&id) {
-                    Some(existing) if existing.inner == route => existing.clone(),
-                    _ => Arc::new(ProxyRoute::build(route, &upstreams, &services, prepared)?),
-                }
-            } else {
-                Arc::new(ProxyRoute::build(route, &upstreams, &services, prepared)?)
+            let existing = previous.routes.get(&id);
+            let dependencies_reused = all_named_upstreams_reused
+                && existing.is_none_or(|existing| existing.is_bound_to(&services));
+            let rebuild = needs_rebuild(
+                "route",
+                existing.map(|e| e.inner == route),
+                dependencies_reused,
+            );
+            let arc = match existing {
+                Some(existing) if !rebuild => existing.clone(),
+                _ => match ProxyRoute::build(route, &upstreams, &services, prepared) {
+                    Ok(route) => Arc::new(route),
+                    Err(e) => {
+                        skip_or_fail(skipped, "routes", &id, e)?;
+                        continue;
+                    }
+                },
             };
             routes.insert(id, arc);
         }
@@ -441,21 +819,100 @@ impl CandidateSnapshot {
             log::info!("Configuring ssl: {id}");
             let arc = match previous.ssls.get(&id) {
                 Some(existing) if existing.inner == ssl => existing.clone(),
-                _ => Arc::new(ProxySSL::try_from(ssl)?),
+                _ => match ProxySSL::try_from(ssl) {
+                    Ok(ssl) => Arc::new(ssl),
+                    Err(e) => {
+                        skip_or_fail(skipped, "ssls", &id, e)?;
+                        continue;
+                    }
+                },
             };
             ssls.insert(id, arc);
         }
 
+        let mut ip_lists = HashMap::with_capacity(config.ip_lists.len());
+        for (id, list) in config.ip_lists {
+            log::info!("Configuring ip list: {id}");
+            let arc = match previous.ip_lists.get(&id) {
+                Some(existing) if existing.inner == list => existing.clone(),
+                _ => match ProxyIpList::try_from(list) {
+                    Ok(list) => Arc::new(list),
+                    Err(e) => {
+                        skip_or_fail(skipped, "ip_lists", &id, e)?;
+                        continue;
+                    }
+                },
+            };
+            ip_lists.insert(id, arc);
+        }
+
         Ok(Self {
             upstreams,
             services,
             global_rules,
             routes,
             ssls,
+            ip_lists,
         })
     }
 }
 
+/// Whether a compiled resource must be built again rather than carried over from the
+/// previous snapshot. `same_config` is `None` for a new resource. Records the outcome
+/// in the executor rebuild metrics.
+fn needs_rebuild(resource: &str, same_config: Option<bool>, dependencies_reused: bool) -> bool {
+    let reason = match same_config {
+        None => "created",
+        Some(false) => "changed",
+        Some(true) if !dependencies_reused => "dependency",
+        Some(true) => {
+            metrics::EXECUTOR_REUSES
+                .with_label_values(&[resource])
+                .inc();
+            return false;
+        }
+    };
+    metrics::EXECUTOR_REBUILDS
+        .with_label_values(&[resource, reason])
+        .inc();
+    true
+}
+
+/// Fail a strict build with `error`, or record the resource as skipped when the
+/// build is tolerant.
+fn skip_or_fail(
+    skipped: &mut Option<&mut Vec<SkippedResource>>,
+    resource_type: &str,
+    id: &str,
+    error: ProxyError,
+) -> ProxyResult<()> {
+    let Some(skipped) = skipped else {
+        return Err(error);
+    };
+    log::warn!("Skipping {resource_type}/{id}: {error}");
+    skipped.push(SkippedResource::new(resource_type, id, error));
+    Ok(())
+}
+
+/// Per-resource `Validate` pass; see [`skip_or_fail`] for invalid resources.
+fn validate_resources<T: Validate>(
+    resources: &mut HashMap<String, T>,
+    resource_type: &str,
+    label: &str,
+    skipped: &mut Option<&mut Vec<SkippedResource>>,
+) -> ProxyResult<()> {
+    let invalid: Vec<(String, String)> = resources
+        .iter()
+        .filter_map(|(id, resource)| Some((id.clone(), resource.validate().err()?.to_string())))
+        .collect();
+    for (id, e) in invalid {
+        let error = ProxyError::Configuration(format!("{label} '{id}' validation failed: {e}"));
+        skip_or_fail(skipped, resource_type, &id, error)?;
+        resources.remove(&id);
+    }
+    Ok(())
+}
+
 /// Prepare every upstream occurrence synchronously. This is used only for
 /// static startup; DNS occurrences return an error directing callers to the
 /// asynchronous preparation path.
@@ -476,7 +933,11 @@ async fn prepare_candidate(config: &ResourceConfigSet) -> ProxyResult<PreparedUp
         if let Some(upstream) = &route.upstream {
             jobs.push((inline_key(&format!("route/{id}")), upstream.clone()));
         }
-        collect_plugin_upstreams(&mut jobs, &format!("route/{id}"), &route.plugins)?;
+        collect_plugin_upstreams(
+            &mut jobs,
+            &format!("route/{id}"),
+            &*config.route_plugins(route)?,
+        )?;
     }
     for (id, service) in &config.services {
         if let Some(upstream) = &service.upstream {
@@ -497,6 +958,95 @@ async fn prepare_candidate(config: &ResourceConfigSet) -> ProxyResult<PreparedUp
     Ok(prepared.into_iter().collect())
 }
 
+/// [`prepare_candidate`] for a tolerant static load: a resource whose upstreams cannot
+/// be prepared (or whose traffic-split config is unreadable) is removed from `config`
+/// and recorded in `skipped`.
+async fn prepare_candidate_tolerant(
+    config: &mut ResourceConfigSet,
+    skipped: &mut Vec<SkippedResource>,
+) -> PreparedUpstreams {
+    let previous = RUNTIME.load();
+    let mut failed: Vec<(&'static str, String, ProxyError)> = Vec::new();
+    let mut jobs: Vec<(&'static str, String, String, Upstream)> = config
+        .upstreams
+        .iter()
+        .filter(|(id, upstream)| {
+            previous
+                .upstreams
+                .get(*id)
+                .is_none_or(|existing| existing.inner != **upstream)
+        })
+        .map(|(id, upstream)| ("upstreams", id.clone(), named_key(id), upstream.clone()))
+        .collect();
+    let mut owners = Vec::new();
+    for (id, route) in &config.routes {
+        let plugins = config.route_plugins(route);
+        owners.push(("routes", id, "route", route.upstream.as_ref(), plugins));
+    }
+    for (id, service) in &config.services {
+        let plugins = Ok(Cow::Borrowed(&service.plugins));
+        owners.push((
+            "services",
+            id,
+            "service",
+            service.upstream.as_ref(),
+            plugins,
+        ));
+    }
+    for (id, rule) in &config.global_rules {
+        let plugins = Ok(Cow::Borrowed(&rule.plugins));
+        owners.push(("global_rules", id, "global-rule", None, plugins));
+    }
+    for (resource_type, id, kind, inline, plugins) in owners {
+        let owner = format!("{kind}/{id}");
+        if let Some(upstream) = inline {
+            jobs.push((
+                resource_type,
+                id.clone(),
+                inline_key(&owner),
+                upstream.clone(),
+            ));
+        }
+        match plugins.and_then(|plugins| plugin_upstream_jobs(&owner, &plugins)) {
+            Ok(plugin_jobs) => jobs.extend(
+                plugin_jobs
+                    .into_iter()
+                    .map(|(key, upstream)| (resource_type, id.clone(), key, upstream)),
+            ),
+            Err(e) => failed.push((resource_type, id.clone(), e)),
+        }
+    }
+
+    let upstreams: Vec<Upstream> = jobs.iter().map(|job| job.3.clone()).collect();
+    let results = stream::iter(upstreams.into_iter().enumerate())
+        .map(|(index, upstream)| async move { (index, prepare_upstream(&upstream).await) })
+        .buffer_unordered(8)
+        .collect::<Vec<_>>()
+        .await;
+    let mut prepared = PreparedUpstreams::new();
+    for (index, result) in results {
+        let (resource_type, id, key, _) = &jobs[index];
+        match result {
+            Ok(upstream) => {
+                prepared.insert(key.clone(), upstream);
+            }
+            Err(e) => failed.push((*resource_type, id.clone(), e)),
+        }
+    }
+    for (resource_type, id, error) in failed {
+        if skipped
+            .iter()
+            .any(|s| s.resource_type == resource_type && s.id == id)
+        {
+            continue;
+        }
+        log::warn!("Skipping {resource_type}/{id}: {error}");
+        skipped.push(SkippedResource::new(resource_type, &id, error));
+        let _ = config.remove(resource_type, &id);
+    }
+    prepared
+}
+
 /// Parse inline upstreams declared inside a `traffic-split` plugin into
 /// `(prepared-key, upstream)` pairs. Shared by the async and static paths.
 fn traffic_split_upstream_jobs(
@@ -533,12 +1083,28 @@ fn traffic_split_upstream_jobs(
     Ok(jobs)
 }
 
+/// Upstreams declared inside plugin configs: `traffic-split` inline upstreams and the
+/// `ai-proxy` provider endpoint.
+fn plugin_upstream_jobs(
+    owner: &str,
+    plugins: &HashMap<String, serde_json::Value>,
+) -> ProxyResult<Vec<(String, Upstream)>> {
+    let mut jobs = traffic_split_upstream_jobs(owner, plugins)?;
+    if let Some(value) = plugins.get(crate::plugins::ai_proxy::PLUGIN_NAME) {
+        jobs.push((
+            ai_proxy_key(owner),
+            crate::plugins::ai_proxy::endpoint_upstream(value)?,
+        ));
+    }
+    Ok(jobs)
+}
+
 fn collect_plugin_upstreams(
     jobs: &mut Vec<(String, Upstream)>,
     owner: &str,
     plugins: &HashMap<String, serde_json::Value>,
 ) -> ProxyResult<()> {
-    jobs.extend(traffic_split_upstream_jobs(owner, plugins)?);
+    jobs.extend(plugin_upstream_jobs(owner, plugins)?);
     Ok(())
 }
 
@@ -554,7 +1120,11 @@ fn prepare_static_candidate(config: &ResourceConfigSet) -> ProxyResult<PreparedU
                 prepare_static_upstream(upstream)?,
             );
         }
-        prepare_static_plugin_upstreams(&mut prepared, &format!("route/{id}"), &route.plugins)?;
+        prepare_static_plugin_upstreams(
+            &mut prepared,
+            &format!("route/{id}"),
+            &*config.route_plugins(route)?,
+        )?;
     }
     for (id, service) in &config.services {
         if let Some(upstream) = &service.upstream {
@@ -580,7 +1150,7 @@ fn prepare_static_plugin_upstreams(
     owner: &str,
     plugins: &HashMap<String, serde_json::Value>,
 ) -> ProxyResult<()> {
-    for (key, upstream) in traffic_split_upstream_jobs(owner, plugins)? {
+    for (key, upstream) in plugin_upstream_jobs(owner, plugins)? {
         prepared.insert(key, prepare_static_upstream(&upstream)?);
     }
     Ok(())
@@ -611,6 +1181,9 @@ pub struct ControlPlane {
     raw: Mutex<ResourceConfigSet>,
     /// Latest submitted graph, including candidates still awaiting DNS.
     target: Mutex<Option<CandidatePreparation>>,
+    /// Watch batches applied on top of `target` whose references do not resolve yet.
+    /// Later batches build on it until the graph validates and can be submitted.
+    staged: Mutex<Option<ResourceConfigSet>>,
     /// Serializes only short raw-candidate creation and fenced publish commits.
     write_lock: Mutex<()>,
     latest_generation: Mutex<u64>,
@@ -620,8 +1193,8 @@ pub struct ControlPlane {
     worker_tx: Mutex<Option<mpsc::Sender<()>>>,
     worker_task: Mutex<Option<tokio::task::JoinHandle<()>>>,
     active_cancellation: Mutex<Option<CancellationToken>>,
-    /// Canonical etcd namespace (`/prefix/`) used to reject foreign keys.
-    etcd_prefix: Mutex<Option<String>>,
+    /// Canonical config-store namespace (`/prefix/`) used to reject foreign keys.
+    config_prefix: Mutex<Option<String>>,
 }
 
 impl ControlPlane {
@@ -629,19 +1202,20 @@ impl ControlPlane {
         Self {
             raw: Mutex::new(ResourceConfigSet::default()),
             target: Mutex::new(None),
+            staged: Mutex::new(None),
             write_lock: Mutex::new(()),
             latest_generation: Mutex::new(0),
             preparation: AsyncMutex::new(()),
             worker_tx: Mutex::new(None),
             worker_task: Mutex::new(None),
             active_cancellation: Mutex::new(None),
-            etcd_prefix: Mutex::new(None),
+            config_prefix: Mutex::new(None),
         }
     }
 
-    /// Record the active etcd namespace for watch/list key validation.
-    pub fn set_etcd_prefix(&self, prefix: String) {
-        *self.etcd_prefix.lock().unwrap_or_else(|e| e.into_inner()) =
+    /// Record the active config-store namespace for watch/list key validation.
+    pub fn set_config_prefix(&self, prefix: String) {
+        *self.config_prefix.lock().unwrap_or_else(|e| e.into_inner()) =
             Some(canonicalize_prefix(&prefix));
     }
 
@@ -702,14 +1276,16 @@ impl ControlPlane {
         revision: i64,
     ) -> ProxyResult<()> {
         let _writer = self.write_lock.lock().unwrap_or_else(|e| e.into_inner());
+        // A full listing is authoritative; whatever was staged is superseded.
+        self.staged.lock().unwrap_or_else(|e| e.into_inner()).take();
         self.submit(resources, revision)
     }
 
-    pub fn submit_events(&self, events: &[Event], revision: i64) -> ProxyResult<()> {
+    pub fn submit_events(&self, events: &[ConfigChange], revision: i64) -> ProxyResult<()> {
         if events.is_empty()
             || events
                 .iter()
-                .all(|event| event.kv().is_some_and(|kv| is_metadata_key(kv.key())))
+                .all(|change| is_metadata_key(change.key().as_bytes()))
         {
             return Ok(());
         }
@@ -720,15 +1296,44 @@ impl ControlPlane {
                 RUNTIME.load().revision
             )));
         }
-        let mut candidate = self
-            .target
-            .lock()
-            .unwrap_or_else(|e| e.into_inner())
-            .as_ref()
-            .map(|target| target.raw.clone())
-            .unwrap_or_else(|| self.raw.lock().unwrap_or_else(|e| e.into_inner()).clone());
+        match self.stage_events(events, revision)? {
+            Some(candidate) => self.submit(candidate, revision),
+            None => Ok(()),
+        }
+    }
+
+    /// Apply a watch batch to the latest graph and validate it. Returns the candidate
+    /// when it is consistent; otherwise (e.g. a route arriving before the upstream it
+    /// references) the batch is staged and `None` returned, so nothing is published
+    /// until a later revision completes the graph.
+    fn stage_events(
+        &self,
+        events: &[ConfigChange],
+        revision: i64,
+    ) -> ProxyResult<Option<ResourceConfigSet>> {
+        let mut staged = self.staged.lock().unwrap_or_else(|e| e.into_inner());
+        let mut candidate = match staged.as_ref() {
+            Some(staged) => staged.clone(),
+            None => self
+                .target
+                .lock()
+                .unwrap_or_else(|e| e.into_inner())
+                .as_ref()
+                .map(|target| target.raw.clone())
+                .unwrap_or_else(|| self.raw.lock().unwrap_or_else(|e| e.into_inner()).clone()),
+        };
         apply_coalesced_events(&mut candidate, events)?;
-        self.submit(candidate, revision)
+        if let Err(e) = validate_config_set(&candidate) {
+            log::warn!("Staging watch revision {revision} until the graph validates: {e}");
+            status::record_preparation_error(format!("Revision {revision} staged: {e}"));
+            PENDING_REVISION.set(revision);
+            *staged = Some(candidate);
+            return Ok(None);
+        }
+        if staged.take().is_some() {
+            log::info!("Staged watch batches validate at revision {revision}");
+        }
+        Ok(Some(candidate))
     }
 
     fn submit(&self, resources: ResourceConfigSet, revision: i64) -> ProxyResult<()> {
@@ -805,6 +1410,7 @@ impl ControlPlane {
         if revision < RUNTIME.load().revision {
             return Ok(());
         }
+        let _timer = metrics::CONFIG_RELOAD_DURATION.start_timer();
         let candidate = CandidateSnapshot::build_prepared(raw.clone(), &prepared)?;
         let published = RUNTIME.publish(RuntimeSnapshot::compile(candidate, revision)?)?;
         *self.raw.lock().unwrap_or_else(|e| e.into_inner()) = raw;
@@ -856,20 +1462,31 @@ impl ControlPlane {
         revision: i64,
     ) -> ProxyResult<Arc<RuntimeSnapshot>> {
         let _writer = self.write_lock.lock().unwrap_or_else(|e| e.into_inner());
+        self.staged.lock().unwrap_or_else(|e| e.into_inner()).take();
         // Full list / static reload is authoritative and may move the revision cursor freely.
         self.build_and_publish_locked(resources, revision)
     }
 
     /// Publish a candidate whose upstreams were prepared outside the sync writer
-    /// (static boot DNS path).
+    /// (static boot DNS path). With `skipped`, resources that fail to compile are left
+    /// out and recorded there.
     pub(crate) fn replace_all_prepared(
         &self,
-        resources: ResourceConfigSet,
+        mut resources: ResourceConfigSet,
         prepared: &PreparedUpstreams,
         revision: i64,
+        mut skipped: Option<&mut Vec<SkippedResource>>,
     ) -> ProxyResult<Arc<RuntimeSnapshot>> {
         let _writer = self.write_lock.lock().unwrap_or_else(|e| e.into_inner());
-        let candidate = CandidateSnapshot::build_prepared(resources.clone(), prepared)?;
+        let _timer = metrics::CONFIG_RELOAD_DURATION.start_timer();
+        let candidate = CandidateSnapshot::build_prepared_with(
+            resources.clone(),
+            prepared,
+            skipped.as_deref_mut(),
+        )?;
+        for resource in skipped.into_iter().flatten() {
+            resources.remove(&resource.resource_type, &resource.id)?;
+        }
         let snapshot = RuntimeSnapshot::compile(candidate, revision)?;
         let published = RUNTIME.publish(snapshot)?;
         *self.raw.lock().unwrap_or_else(|e| e.into_inner()) = resources.clone();
@@ -887,7 +1504,7 @@ impl ControlPlane {
 
     pub fn apply_events(
         &self,
-        events: &[Event],
+        events: &[ConfigChange],
         revision: i64,
     ) -> ProxyResult<Arc<RuntimeSnapshot>> {
         if events.is_empty() {
@@ -906,6 +1523,7 @@ impl ControlPlane {
         let mut candidate_raw = guard.clone();
         apply_coalesced_events(&mut candidate_raw, events)?;
 
+        let _timer = metrics::CONFIG_RELOAD_DURATION.start_timer();
         let candidate = CandidateSnapshot::build(candidate_raw.clone())?;
         let snapshot = RuntimeSnapshot::compile(candidate, revision)?;
         let published = RUNTIME.publish(snapshot)?;
@@ -918,6 +1536,7 @@ impl ControlPlane {
         candidate_raw: ResourceConfigSet,
         revision: i64,
     ) -> ProxyResult<Arc<RuntimeSnapshot>> {
+        let _timer = metrics::CONFIG_RELOAD_DURATION.start_timer();
         let candidate = CandidateSnapshot::build(candidate_raw.clone())?;
         let snapshot = RuntimeSnapshot::compile(candidate, revision)?;
         let published = RUNTIME.publish(snapshot)?;
@@ -946,16 +1565,64 @@ pub static CONTROL_PLANE: Lazy<ControlPlane> = Lazy::new(ControlPlane::new);
 /// Load static YAML configuration with bounded asynchronous DNS preparation.
 ///
 /// Unlike the etcd worker path, static boot must finish preparation before
-/// listeners start. Unresolvable DNS-only upstreams fail the process.
+/// listeners start. Unresolvable DNS-only upstreams fail the process, unless
+/// `pingsix.static_load_policy` is `tolerant`: then every resource that fails to
+/// load is skipped along with its dependents and reported by the status API.
 pub fn load_static_configurations(config: &config::Config) -> ProxyResult<Arc<RuntimeSnapshot>> {
+    let mut resources = ResourceConfigSet::from_yaml_config(config);
+    status::set_config_digest(Some(config.digest.clone()));
+    let snapshot = if is_tolerant(config) {
+        let mut skipped = config.skipped_resources.clone();
+        drop_unresolved_ip_list_refs(&mut resources, &mut skipped);
+        let prepared = run_static_preparation(async {
+            Ok(prepare_candidate_tolerant(&mut resources, &mut skipped).await)
+        })?;
+        let snapshot =
+            CONTROL_PLANE.replace_all_prepared(resources, &prepared, 0, Some(&mut skipped))?;
+        status::set_skipped_resources(skipped);
+        snapshot
+    } else {
+        validate_ip_list_refs(&resources)?;
+        let prepared = prepare_static_resources(&resources)?;
+        CONTROL_PLANE.replace_all_prepared(resources, &prepared, 0, None)?
+    };
+    status::mark_ready(status::ConfigSource::Yaml);
+    Ok(snapshot)
+}
+
+fn is_tolerant(config: &config::Config) -> bool {
+    config.pingsix.static_load_policy == config::StaticLoadPolicy::Tolerant
+}
+
+/// Compile static YAML resources into a snapshot without publishing it.
+///
+/// Used by `pingsix check` and `pingsix routes test`; hostnames are resolved exactly
+/// as they would be at startup.
+pub fn compile_static_configurations(config: &config::Config) -> ProxyResult<RuntimeSnapshot> {
     let resources = ResourceConfigSet::from_yaml_config(config);
+    let prepared = prepare_static_resources(&resources)?;
+    let candidate = CandidateSnapshot::build_prepared(resources, &prepared)?;
+    RuntimeSnapshot::compile(candidate, 0)
+}
+
+/// Run upstream preparation (including DNS) on a private runtime before any
+/// Pingora runtime exists.
+fn prepare_static_resources(resources: &ResourceConfigSet) -> ProxyResult<PreparedUpstreams> {
+    run_static_preparation(prepare_candidate(resources))
+}
+
+/// Drive a static preparation future to completion on a private runtime, cancelling
+/// it on SIGTERM.
+fn run_static_preparation(
+    preparation: impl std::future::Future<Output = ProxyResult<PreparedUpstreams>>,
+) -> ProxyResult<PreparedUpstreams> {
     let rt = tokio::runtime::Builder::new_current_thread()
         .enable_all()
         .build()
         .map_err(|e| {
             ProxyError::Configuration(format!("Failed to create DNS preparation runtime: {e}"))
         })?;
-    let prepared = rt.block_on(async {
+    rt.block_on(async {
         #[cfg(unix)]
         {
             use tokio::signal::unix::{signal, SignalKind};
@@ -963,7 +1630,7 @@ pub fn load_static_configurations(config: &config::Config) -> ProxyResult<Arc<Ru
                 ProxyError::Configuration(format!("Failed to install SIGTERM handler: {e}"))
             })?;
             tokio::select! {
-                result = prepare_candidate(&resources) => result,
+                result = preparation => result,
                 _ = sigterm.recv() => Err(ProxyError::Configuration(
                     "Static configuration DNS preparation cancelled by SIGTERM".into(),
                 )),
@@ -971,51 +1638,75 @@ pub fn load_static_configurations(config: &config::Config) -> ProxyResult<Arc<Ru
         }
         #[cfg(not(unix))]
         {
-            prepare_candidate(&resources).await
+            preparation.await
         }
-    })?;
-    let snapshot = CONTROL_PLANE.replace_all_prepared(resources, &prepared, 0)?;
-    status::mark_ready(status::ConfigSource::Yaml);
+    })
+}
+
+/// Re-read static YAML resources at runtime (SIGHUP) and publish them atomically.
+///
+/// Preparation failures leave the currently published snapshot untouched.
+pub async fn reload_static_configurations(
+    config: &config::Config,
+) -> ProxyResult<Arc<RuntimeSnapshot>> {
+    // The digest is in place before publishing, so change notifications carry it.
+    let previous_digest = status::set_config_digest(Some(config.digest.clone()));
+    let result = reload_static_resources(config).await;
+    if result.is_err() {
+        status::set_config_digest(previous_digest);
+    }
+    result
+}
+
+async fn reload_static_resources(config: &config::Config) -> ProxyResult<Arc<RuntimeSnapshot>> {
+    let mut resources = ResourceConfigSet::from_yaml_config(config);
+    if !is_tolerant(config) {
+        let prepared = prepare_candidate(&resources).await?;
+        return CONTROL_PLANE.replace_all_prepared(resources, &prepared, 0, None);
+    }
+    let mut skipped = config.skipped_resources.clone();
+    drop_unresolved_ip_list_refs(&mut resources, &mut skipped);
+    let prepared = prepare_candidate_tolerant(&mut resources, &mut skipped).await;
+    let snapshot =
+        CONTROL_PLANE.replace_all_prepared(resources, &prepared, 0, Some(&mut skipped))?;
+    status::set_skipped_resources(skipped);
     Ok(snapshot)
 }
 
-fn apply_coalesced_events(raw: &mut ResourceConfigSet, events: &[Event]) -> ProxyResult<()> {
-    // Preserve etcd causal order per key: later events overwrite earlier ones.
+fn apply_coalesced_events(raw: &mut ResourceConfigSet, events: &[ConfigChange]) -> ProxyResult<()> {
+    // Preserve causal order per key: later events overwrite earlier ones.
     let mut final_by_key: HashMap<String, CoalescedChange> = HashMap::new();
     let prefix = CONTROL_PLANE
-        .etcd_prefix
+        .config_prefix
         .lock()
         .unwrap_or_else(|e| e.into_inner())
         .clone();
 
     for event in events {
-        let kv = event
-            .kv()
-            .ok_or_else(|| ProxyError::Configuration("Etcd event missing key-value pair".into()))?;
-        let key_bytes = kv.key();
-        let key = String::from_utf8_lossy(key_bytes).into_owned();
+        let key = event.key();
+        let key_bytes = key.as_bytes();
         if is_metadata_key(key_bytes) {
             continue;
         }
         if let Some(ref canonical) = prefix {
             if !key.starts_with(canonical) {
-                log::warn!("Ignoring etcd event outside configured namespace: {key}");
+                log::warn!("Ignoring config event outside configured namespace: {key}");
                 continue;
             }
         }
         let (id, resource_type) = parse_key(key_bytes, prefix.as_deref()).map_err(|e| {
-            ProxyError::Configuration(format!("Failed to parse etcd key '{key}': {e}"))
+            ProxyError::Configuration(format!("Failed to parse config key '{key}': {e}"))
         })?;
 
-        let change = match event.event_type() {
-            etcd_client::EventType::Put => CoalescedChange::Put {
+        let change = match event {
+            ConfigChange::Put { value, .. } => CoalescedChange::Put {
                 resource_type,
                 id,
-                value: kv.value().to_vec(),
+                value: value.clone(),
             },
-            etcd_client::EventType::Delete => CoalescedChange::Delete { resource_type, id },
+            ConfigChange::Delete { .. } => CoalescedChange::Delete { resource_type, id },
         };
-        final_by_key.insert(key, change);
+        final_by_key.insert(key.to_string(), change);
     }
 
     for change in final_by_key.into_values() {
@@ -1072,34 +1763,23 @@ fn apply_raw_change(raw: &mut ResourceConfigSet, change: CoalescedChange) -> Pro
                 resource.set_id(id.clone());
                 raw.ssls.insert(id, resource);
             }
-            other => {
-                return Err(ProxyError::Configuration(format!(
-                    "Unhandled PUT resource type: {other}"
-                )));
-            }
-        },
-        CoalescedChange::Delete { resource_type, id } => match resource_type.as_str() {
-            "upstreams" => {
-                raw.upstreams.remove(&id);
-            }
-            "services" => {
-                raw.services.remove(&id);
-            }
-            "global_rules" => {
-                raw.global_rules.remove(&id);
-            }
-            "routes" => {
-                raw.routes.remove(&id);
+            "ip_lists" => {
+                let mut resource = json_to_resource::<IpList>(&value)?;
+                resource.set_id(id.clone());
+                raw.ip_lists.insert(id, resource);
             }
-            "ssls" => {
-                raw.ssls.remove(&id);
+            "plugin_configs" => {
+                let mut resource = json_to_resource::<PluginConfig>(&value)?;
+                resource.set_id(id.clone());
+                raw.plugin_configs.insert(id, resource);
             }
             other => {
                 return Err(ProxyError::Configuration(format!(
-                    "Unhandled DELETE resource type: {other}"
+                    "Unhandled PUT resource type: {other}"
                 )));
             }
         },
+        CoalescedChange::Delete { resource_type, id } => raw.remove(&resource_type, &id)?,
     }
     Ok(())
 }
@@ -1161,6 +1841,7 @@ mod tests {
         SelectionType, Upstream, UpstreamHashOn, UpstreamPassHost, UpstreamScheme,
     };
     use crate::proxy::runtime::RUNTIME_TEST_LOCK;
+    use pingora_http::RequestHeader;
     use std::collections::HashMap as StdHashMap;
 
     fn sample_upstream(id: &str, node: &str) -> Upstream {
@@ -1180,6 +1861,11 @@ mod tests {
             pass_host: UpstreamPassHost::PASS,
             upstream_host: None,
             tls: None,
+            zone_aware: None,
+            discovery_type: None,
+            priorities: HashMap::new(),
+            sticky: None,
+            labels: Default::default(),
         }
     }
 
@@ -1201,6 +1887,14 @@ mod tests {
                 upstream_id: Some("missing".into()),
                 service_id: None,
                 timeout: None,
+                streaming: false,
+                fallback: false,
+                shed_priority: Default::default(),
+                enabled: true,
+                labels: Default::default(),
+                plugin_config_id: Default::default(),
+                strip_path_prefix: None,
+                api_group: None,
             },
         );
         assert!(validate_config_set(&set).is_err());
@@ -1226,6 +1920,14 @@ mod tests {
                 upstream_id: Some("u1".into()),
                 service_id: Some("missing".into()),
                 timeout: None,
+                streaming: false,
+                fallback: false,
+                shed_priority: Default::default(),
+                enabled: true,
+                labels: Default::default(),
+                plugin_config_id: Default::default(),
+                strip_path_prefix: None,
+                api_group: None,
             },
         );
         assert!(validate_config_set(&set).is_err());
@@ -1242,6 +1944,10 @@ mod tests {
                 upstream: None,
                 upstream_id: Some("missing".into()),
                 hosts: vec![],
+                virtual_host: false,
+                enabled: true,
+                labels: Default::default(),
+                blue_green: None,
             },
         );
         assert!(validate_config_set(&set).is_err());
@@ -1278,6 +1984,14 @@ mod tests {
                 upstream_id: Some("u1".into()),
                 service_id: None,
                 timeout: None,
+                streaming: false,
+                fallback: false,
+                shed_priority: Default::default(),
+                enabled: true,
+                labels: Default::default(),
+                plugin_config_id: Default::default(),
+                strip_path_prefix: None,
+                api_group: None,
             },
         );
         let err = validate_config_set(&set).unwrap_err().to_string();
@@ -1311,6 +2025,10 @@ mod tests {
                 upstream: None,
                 upstream_id: Some("u1".into()),
                 hosts: vec![],
+                virtual_host: false,
+                enabled: true,
+                labels: Default::default(),
+                blue_green: None,
             },
         );
         let err = validate_config_set(&set).unwrap_err().to_string();
@@ -1336,6 +2054,11 @@ mod tests {
             crate::config::GlobalRule {
                 id: "g1".into(),
                 plugins,
+                enabled: true,
+                labels: Default::default(),
+                route_labels: Default::default(),
+                hosts: Default::default(),
+                uri_prefixes: Default::default(),
             },
         );
         let err = validate_config_set(&set).unwrap_err().to_string();
@@ -1377,6 +2100,14 @@ mod tests {
                 upstream_id: Some("u1".into()),
                 service_id: None,
                 timeout: None,
+                streaming: false,
+                fallback: false,
+                shed_priority: Default::default(),
+                enabled: true,
+                labels: Default::default(),
+                plugin_config_id: Default::default(),
+                strip_path_prefix: None,
+                api_group: None,
             },
         );
         assert!(validate_config_set(&set).is_ok());
@@ -1416,6 +2147,14 @@ mod tests {
                 upstream_id: Some("u1".into()),
                 service_id: None,
                 timeout: None,
+                streaming: false,
+                fallback: false,
+                shed_priority: Default::default(),
+                enabled: true,
+                labels: Default::default(),
+                plugin_config_id: Default::default(),
+                strip_path_prefix: None,
+                api_group: None,
             },
         );
         assert!(validate_config_set(&set).is_err());
@@ -1434,6 +2173,10 @@ mod tests {
                 upstream: None,
                 upstream_id: Some("u1".into()),
                 hosts: vec![],
+                virtual_host: false,
+                enabled: true,
+                labels: Default::default(),
+                blue_green: None,
             },
         );
         set.routes.insert(
@@ -1451,9 +2194,241 @@ mod tests {
                 upstream_id: Some("u1".into()),
                 service_id: Some("s1".into()),
                 timeout: None,
+                streaming: false,
+                fallback: false,
+                shed_priority: Default::default(),
+                enabled: true,
+                labels: Default::default(),
+                plugin_config_id: Default::default(),
+                strip_path_prefix: None,
+                api_group: None,
+            },
+        );
+        assert!(validate_config_set(&set).is_ok());
+    }
+
+    #[test]
+    fn dependents_of_upstream_cascade_through_services() {
+        let mut set = ResourceConfigSet::default();
+        set.upstreams
+            .insert("u1".into(), sample_upstream("u1", "10.0.0.1:80"));
+        set.upstreams
+            .insert("u2".into(), sample_upstream("u2", "10.0.0.2:80"));
+        set.services.insert(
+            "s1".into(),
+            crate::config::Service {
+                id: "s1".into(),
+                plugins: Default::default(),
+                upstream: None,
+                upstream_id: Some("u1".into()),
+                hosts: vec![],
+                virtual_host: false,
+                enabled: true,
+                labels: Default::default(),
+                blue_green: None,
+            },
+        );
+        for (id, service_id, upstream_id) in [("r1", Some("s1"), None), ("r2", None, Some("u2"))] {
+            set.routes.insert(
+                id.into(),
+                crate::config::Route {
+                    id: id.into(),
+                    uri: Some("/".into()),
+                    uris: vec![],
+                    methods: vec![],
+                    host: None,
+                    hosts: vec![],
+                    priority: 0,
+                    plugins: Default::default(),
+                    upstream: None,
+                    upstream_id: upstream_id.map(Into::into),
+                    service_id: service_id.map(Into::into),
+                    timeout: None,
+                    streaming: false,
+                    fallback: false,
+                    shed_priority: Default::default(),
+                    enabled: true,
+                    labels: Default::default(),
+                    plugin_config_id: Default::default(),
+                    strip_path_prefix: None,
+                    api_group: None,
+                },
+            );
+        }
+
+        assert_eq!(
+            dependents_of(&set, "upstreams", "u1"),
+            vec![("routes", "r1".to_string()), ("services", "s1".to_string())]
+        );
+        assert_eq!(
+            dependents_of(&set, "upstreams", "u2"),
+            vec![("routes", "r2".to_string())]
+        );
+        assert!(dependents_of(&set, "routes", "r1").is_empty());
+    }
+
+    #[test]
+    fn ip_list_references_are_validated_and_cascade() {
+        let mut set = ResourceConfigSet::default();
+        set.upstreams
+            .insert("u1".into(), sample_upstream("u1", "10.0.0.1:80"));
+        set.global_rules.insert(
+            "g1".into(),
+            crate::config::GlobalRule {
+                id: "g1".into(),
+                plugins: StdHashMap::from([(
+                    "ip-restriction".to_string(),
+                    serde_json::json!({"blacklist_lists": ["abuse"]}),
+                )]),
+                enabled: true,
+                labels: Default::default(),
+                route_labels: Default::default(),
+                hosts: Default::default(),
+                uri_prefixes: Default::default(),
+            },
+        );
+        assert!(validate_config_set(&set).is_err());
+
+        set.ip_lists.insert(
+            "abuse".into(),
+            crate::config::IpList {
+                id: "abuse".into(),
+                desc: None,
+                cidrs: vec!["203.0.113.0/24".into(), "2001:db8::/32".into()],
+            },
+        );
+        assert!(validate_config_set(&set).is_ok());
+        assert_eq!(
+            dependents_of(&set, "ip_lists", "abuse"),
+            vec![("global_rules", "g1".to_string())]
+        );
+
+        set.ip_lists
+            .get_mut("abuse")
+            .unwrap()
+            .cidrs
+            .push("bad".into());
+        assert!(validate_config_set(&set).is_err());
+    }
+
+    #[test]
+    fn tolerant_build_skips_resources_that_fail_to_compile() {
+        let mut set = ResourceConfigSet::default();
+        set.upstreams
+            .insert("u1".into(), sample_upstream("u1", "10.0.0.1:80"));
+        let route = |id: &str, plugins| crate::config::Route {
+            id: id.into(),
+            uri: Some(format!("/{id}")),
+            uris: vec![],
+            methods: vec![],
+            host: None,
+            hosts: vec![],
+            priority: 0,
+            plugins,
+            plugin_config_id: None,
+            upstream: None,
+            upstream_id: Some("u1".into()),
+            service_id: None,
+            timeout: None,
+            streaming: false,
+            fallback: false,
+            shed_priority: Default::default(),
+            enabled: true,
+            labels: Default::default(),
+            strip_path_prefix: None,
+            api_group: None,
+        };
+        set.routes
+            .insert("ok".into(), route("ok", StdHashMap::new()));
+        set.routes.insert(
+            "bad".into(),
+            route(
+                "bad",
+                StdHashMap::from([("no-such-plugin".to_string(), serde_json::json!({}))]),
+            ),
+        );
+
+        let prepared = prepare_static_candidate(&set).unwrap();
+        assert!(CandidateSnapshot::build_prepared(set.clone(), &prepared).is_err());
+
+        let mut skipped = Vec::new();
+        let candidate =
+            CandidateSnapshot::build_prepared_with(set, &prepared, Some(&mut skipped)).unwrap();
+        assert!(candidate.routes.contains_key("ok"));
+        assert!(!candidate.routes.contains_key("bad"));
+        assert_eq!(skipped.len(), 1);
+        assert_eq!(
+            (skipped[0].resource_type.as_str(), skipped[0].id.as_str()),
+            ("routes", "bad")
+        );
+    }
+
+    #[test]
+    fn plugin_configs_merge_into_routes_and_cascade() {
+        let mut set = ResourceConfigSet::default();
+        set.upstreams
+            .insert("u1".into(), sample_upstream("u1", "10.0.0.1:80"));
+        set.routes.insert(
+            "r1".into(),
+            crate::config::Route {
+                id: "r1".into(),
+                uri: Some("/".into()),
+                uris: vec![],
+                methods: vec![],
+                host: None,
+                hosts: vec![],
+                priority: 0,
+                plugins: StdHashMap::from([(
+                    "limit-count".to_string(),
+                    serde_json::json!({"count": 5, "time_window": 60}),
+                )]),
+                plugin_config_id: Some("pc1".into()),
+                upstream: None,
+                upstream_id: Some("u1".into()),
+                service_id: None,
+                timeout: None,
+                streaming: false,
+                fallback: false,
+                shed_priority: Default::default(),
+                enabled: true,
+                labels: Default::default(),
+                strip_path_prefix: None,
+                api_group: None,
+            },
+        );
+        assert!(validate_config_set(&set)
+            .unwrap_err()
+            .to_string()
+            .contains("missing plugin_config 'pc1'"));
+
+        set.plugin_configs.insert(
+            "pc1".into(),
+            crate::config::PluginConfig {
+                id: "pc1".into(),
+                plugins: StdHashMap::from([
+                    (
+                        "limit-count".to_string(),
+                        serde_json::json!({"count": 100, "time_window": 60}),
+                    ),
+                    ("request-id".to_string(), serde_json::json!({})),
+                ]),
+                ..Default::default()
             },
         );
         assert!(validate_config_set(&set).is_ok());
+        let plugins = set.route_plugins(&set.routes["r1"]).unwrap();
+        assert_eq!(plugins["limit-count"]["count"], 5, "route plugins win");
+        assert!(plugins.contains_key("request-id"));
+
+        let candidate = CandidateSnapshot::build(set.clone()).unwrap();
+        let executor = crate::core::RouteContext::build_plugin_executor(&*candidate.routes["r1"]);
+        assert!(executor.has_plugin("request-id"));
+        assert!(executor.has_plugin("limit-count"));
+
+        assert_eq!(
+            dependents_of(&set, "plugin_configs", "pc1"),
+            vec![("routes", "r1".to_string())]
+        );
     }
 
     #[test]
@@ -1595,6 +2570,14 @@ mod tests {
                 upstream_id: Some("missing".into()),
                 service_id: None,
                 timeout: None,
+                streaming: false,
+                fallback: false,
+                shed_priority: Default::default(),
+                enabled: true,
+                labels: Default::default(),
+                plugin_config_id: Default::default(),
+                strip_path_prefix: None,
+                api_group: None,
             },
         );
         assert!(plane.replace_all(bad, 4).is_err());
@@ -1631,6 +2614,14 @@ mod tests {
                 upstream_id: Some("missing".into()),
                 service_id: None,
                 timeout: None,
+                streaming: false,
+                fallback: false,
+                shed_priority: Default::default(),
+                enabled: true,
+                labels: Default::default(),
+                plugin_config_id: Default::default(),
+                strip_path_prefix: None,
+                api_group: None,
             },
         );
         assert!(plane.replace_all(bad, 2).is_err());
@@ -1639,6 +2630,26 @@ mod tests {
         assert_eq!(RUNTIME.load().revision, 1);
     }
 
+    #[test]
+    fn watch_batches_are_staged_until_references_resolve() {
+        let plane = ControlPlane::new();
+        let route = ConfigChange::Put {
+            key: "/apisix/routes/r1".into(),
+            value: serde_json::to_vec(&serde_json::json!({"uri": "/", "upstream_id": "u1"}))
+                .unwrap(),
+        };
+        assert!(plane.stage_events(&[route], 10).unwrap().is_none());
+
+        let upstream = ConfigChange::Put {
+            key: "/apisix/upstreams/u1".into(),
+            value: serde_json::to_vec(&sample_upstream("u1", "10.0.0.1:80")).unwrap(),
+        };
+        let candidate = plane.stage_events(&[upstream], 11).unwrap().unwrap();
+        assert!(candidate.routes.contains_key("r1"));
+        assert!(candidate.upstreams.contains_key("u1"));
+        assert!(plane.staged.lock().unwrap().is_none());
+    }
+
     #[test]
     fn empty_apply_events_does_not_change_revision() {
         let _guard = RUNTIME_TEST_LOCK.lock().unwrap_or_else(|e| e.into_inner());
@@ -1667,6 +2678,10 @@ mod tests {
                 upstream: None,
                 upstream_id: Some("u1".into()),
                 hosts: vec![],
+                virtual_host: false,
+                enabled: true,
+                labels: Default::default(),
+                blue_green: None,
             },
         );
         plane.replace_all(set.clone(), 1).unwrap();
@@ -1682,4 +2697,49 @@ mod tests {
         assert!(snap.upstreams["u1"].inner.nodes.contains_key("10.0.0.2:80"));
         assert_eq!(snap.revision, 2);
     }
+
+    #[test]
+    fn blue_green_service_switches_routes_between_upstreams() {
+        use crate::core::RouteContext;
+
+        let _guard = RUNTIME_TEST_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+        let plane = ControlPlane::new();
+        let mut set = ResourceConfigSet::default();
+        set.upstreams
+            .insert("blue".into(), sample_upstream("blue", "10.0.0.1:80"));
+        set.upstreams
+            .insert("green".into(), sample_upstream("green", "10.0.0.2:80"));
+        set.services.insert(
+            "s1".into(),
+            serde_json::from_value(serde_json::json!({
+                "id": "s1",
+                "blue_green": {"blue": "blue", "green": "green", "active": "green", "header": "x-env"}
+            }))
+            .unwrap(),
+        );
+        set.routes.insert(
+            "r1".into(),
+            serde_json::from_value(serde_json::json!({"id": "r1", "uri": "/", "service_id": "s1"}))
+                .unwrap(),
+        );
+        assert_eq!(
+            dependents_of(&set, "upstreams", "blue"),
+            vec![("routes", "r1".to_string()), ("services", "s1".to_string())]
+        );
+        plane.replace_all(set, 1).unwrap();
+
+        let snap = RUNTIME.load();
+        let side = |env: Option<&str>| {
+            let mut req = RequestHeader::build("GET", b"/", None).unwrap();
+            if let Some(env) = env {
+                req.insert_header("x-env", env).unwrap();
+            }
+            let selected = snap.routes["r1"].request_upstream(&req).unwrap();
+            Arc::as_ptr(&selected) as *const ()
+        };
+        let upstream = |id: &str| Arc::as_ptr(&snap.upstreams[id]) as *const ();
+        assert_eq!(side(None), upstream("green"));
+        assert_eq!(side(Some("Blue")), upstream("blue"));
+        assert_eq!(side(Some("other")), upstream("green"));
+    }
 }
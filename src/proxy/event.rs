@@ -1,14 +1,19 @@
-//! Etcd event handler that delegates to the unified ControlPlane.
+//! Config event handler that delegates to the unified ControlPlane.
 
 use async_trait::async_trait;
-use etcd_client::{Event, GetResponse};
+
+use validator::Validate;
 
 use crate::{
-    config::etcd::EtcdEventHandler,
-    core::{status, ProxyError, ProxyResult},
+    config::{
+        etcd::canonicalize_prefix,
+        provider::{ConfigChange, ConfigEventHandler, ConfigListing},
+        Maintenance,
+    },
+    core::{maintenance, status, ProxyResult},
 };
 
-use super::control_plane::{ResourceConfigSet, CONTROL_PLANE};
+use super::control_plane::{build_config_set_from_kvs, CONTROL_PLANE};
 
 pub struct ProxyEventHandler {
     prefix: String,
@@ -20,35 +25,65 @@ impl ProxyEventHandler {
             prefix: prefix.into(),
         }
     }
+
+    fn maintenance_key(&self) -> String {
+        format!(
+            "{}{}",
+            canonicalize_prefix(&self.prefix),
+            maintenance::MAINTENANCE_KEY
+        )
+    }
+}
+
+/// Applies a stored maintenance switch; invalid values keep the current one.
+fn apply_maintenance(value: Option<&[u8]>) {
+    let Some(value) = value else {
+        maintenance::set_stored(None);
+        return;
+    };
+    let parsed = serde_json::from_slice::<Maintenance>(value)
+        .map_err(|e| e.to_string())
+        .and_then(|m| m.validate().map(|_| m).map_err(|e| e.to_string()));
+    match parsed {
+        Ok(m) => maintenance::set_stored(Some(m)),
+        Err(e) => log::error!("Ignoring invalid maintenance switch: {e}"),
+    }
 }
 
 #[async_trait]
-impl EtcdEventHandler for ProxyEventHandler {
-    async fn handle_events(&self, events: &[Event]) -> ProxyResult<()> {
+impl ConfigEventHandler for ProxyEventHandler {
+    async fn handle_changes(&self, changes: &[ConfigChange], revision: i64) -> ProxyResult<()> {
         CONTROL_PLANE.start_preparation_worker();
-        if events.is_empty() {
+        if changes.is_empty() {
             return Ok(());
         }
 
-        // Use the highest revision observed in the batch when available.
-        let revision = events
-            .iter()
-            .filter_map(|event| event.kv().map(|kv| kv.mod_revision()))
-            .max()
-            .unwrap_or(0);
+        let maintenance_key = self.maintenance_key();
+        for change in changes.iter().filter(|c| c.key() == maintenance_key) {
+            match change {
+                ConfigChange::Put { value, .. } => apply_maintenance(Some(value)),
+                ConfigChange::Delete { .. } => apply_maintenance(None),
+            }
+        }
 
-        CONTROL_PLANE.submit_events(events, revision)?;
+        CONTROL_PLANE.submit_events(changes, revision)?;
         Ok(())
     }
 
-    async fn handle_list_response(&self, response: &GetResponse) -> ProxyResult<()> {
+    async fn handle_listing(&self, listing: ConfigListing) -> ProxyResult<()> {
         CONTROL_PLANE.start_preparation_worker();
-        let revision = response
-            .header()
-            .map(|h| h.revision())
-            .ok_or_else(|| ProxyError::etcd_error("Failed to get header from list response"))?;
+        let revision = listing.revision;
+
+        let resources = build_config_set_from_kvs(&listing.kvs, &self.prefix)?;
 
-        let resources = ResourceConfigSet::from_etcd_list(response, &self.prefix)?;
+        let maintenance_key = self.maintenance_key();
+        apply_maintenance(
+            listing
+                .kvs
+                .iter()
+                .find(|(key, _)| *key == maintenance_key)
+                .map(|(_, value)| value.as_slice()),
+        );
 
         // Empty full-lists are accepted. When PingSIX uses the ingress etcd
         // adapter, kube Service selection on ingress.pingsix.io/etcd-serving
@@ -1,12 +1,17 @@
 use std::{collections::HashMap, sync::Arc};
 
+use dashmap::DashMap;
+
 use crate::{
     config::{self, Identifiable},
     core::{
         sort_plugins_by_priority_desc, ProxyError, ProxyPlugin, ProxyPluginExecutor, ProxyResult,
     },
-    plugins::build_plugin_with_upstreams,
-    proxy::upstream::{PreparedUpstreams, ProxyUpstream},
+    plugins::{build_plugin_with_upstreams, is_disabled},
+    proxy::{
+        route::ProxyRoute,
+        upstream::{PreparedUpstreams, ProxyUpstream},
+    },
 };
 
 /// Represents a proxy service that manages upstreams.
@@ -26,6 +31,27 @@ impl Identifiable for ProxyGlobalRule {
 }
 
 impl ProxyGlobalRule {
+    fn is_scoped(&self) -> bool {
+        self.inner.is_scoped()
+    }
+
+    /// Whether a scoped rule applies to a request for `host` and `path` matched to
+    /// `route`. Every configured criterion must hold.
+    fn selects(&self, route: Option<&ProxyRoute>, host: Option<&str>, path: &str) -> bool {
+        let rule = &self.inner;
+        (rule.hosts.is_empty()
+            || host.is_some_and(|host| rule.hosts.iter().any(|p| host_matches(p, host))))
+            && (rule.uri_prefixes.is_empty()
+                || rule
+                    .uri_prefixes
+                    .iter()
+                    .any(|prefix| path.starts_with(prefix.as_str())))
+            && (rule.route_labels.is_empty()
+                || route.is_some_and(|route| {
+                    config::labels_match(&route.inner.labels, &rule.route_labels)
+                }))
+    }
+
     pub(crate) fn build(
         rule: config::GlobalRule,
         upstreams: &HashMap<String, Arc<ProxyUpstream>>,
@@ -39,6 +65,8 @@ impl ProxyGlobalRule {
         // Load plugins and log each one
         for (name, value) in rule.plugins {
             log::info!("Loading plugin: {name}");
+            // Plugins of a disabled rule are still built, so re-enabling cannot fail.
+            let disabled = !rule.enabled || is_disabled(&value);
             let plugin = build_plugin_with_upstreams(
                 &name,
                 value,
@@ -52,7 +80,9 @@ impl ProxyGlobalRule {
                     name, rule.id, e
                 ))
             })?;
-            proxy_global_rule.plugins.push(plugin);
+            if !disabled {
+                proxy_global_rule.plugins.push(plugin);
+            }
         }
 
         // Ensure deterministic order for plugins inside a single global rule.
@@ -62,10 +92,18 @@ impl ProxyGlobalRule {
     }
 }
 
+/// Executor of the global rules that apply to every request, i.e. those without
+/// `hosts`, `uri_prefixes` or `route_labels`. See [`ScopedGlobalRules`] for the others.
 pub(crate) fn build_global_plugin_executor(
     rules: &HashMap<String, Arc<ProxyGlobalRule>>,
 ) -> Arc<ProxyPluginExecutor> {
-    let mut rules: Vec<Arc<ProxyGlobalRule>> = rules.values().cloned().collect();
+    build_executor(rules.values().filter(|rule| !rule.is_scoped()))
+}
+
+fn build_executor<'a>(
+    rules: impl Iterator<Item = &'a Arc<ProxyGlobalRule>>,
+) -> Arc<ProxyPluginExecutor> {
+    let mut rules: Vec<&Arc<ProxyGlobalRule>> = rules.collect();
     rules.sort_by(|a, b| a.inner.id.cmp(&b.inner.id));
 
     let mut plugins_with_rule: Vec<(String, Arc<dyn ProxyPlugin>)> = Vec::new();
@@ -91,3 +129,100 @@ pub(crate) fn build_global_plugin_executor(
             .collect(),
     ))
 }
+
+/// Case-insensitive host match; `*.example.com` matches subdomains of `example.com`.
+fn host_matches(pattern: &str, host: &str) -> bool {
+    match pattern.strip_prefix("*.") {
+        Some(domain) => host
+            .len()
+            .checked_sub(domain.len() + 1)
+            .filter(|&dot| dot > 0)
+            .is_some_and(|dot| {
+                let host = host.as_bytes();
+                host[dot] == b'.' && host[dot + 1..].eq_ignore_ascii_case(domain.as_bytes())
+            }),
+        None => pattern.eq_ignore_ascii_case(host),
+    }
+}
+
+/// Global rules limited by `hosts`, `uri_prefixes` or `route_labels`.
+///
+/// Executors combining the unscoped rules with the scoped rules a request selects are
+/// built on first use and cached per selection, up to [`MAX_CACHED_SELECTIONS`].
+#[derive(Default)]
+pub struct ScopedGlobalRules {
+    /// Every global rule, so combined executors keep the usual ordering.
+    rules: Vec<Arc<ProxyGlobalRule>>,
+    /// Indexes into `rules` of the scoped rules.
+    scoped: Vec<usize>,
+    executors: DashMap<Vec<usize>, Arc<ProxyPluginExecutor>>,
+}
+
+/// Selections beyond this many get a fresh executor per request instead of a cached one.
+const MAX_CACHED_SELECTIONS: usize = 1024;
+
+impl ScopedGlobalRules {
+    pub(crate) fn build(rules: &HashMap<String, Arc<ProxyGlobalRule>>) -> Self {
+        let rules: Vec<Arc<ProxyGlobalRule>> = rules.values().cloned().collect();
+        let scoped = rules
+            .iter()
+            .enumerate()
+            .filter(|(_, rule)| rule.is_scoped())
+            .map(|(index, _)| index)
+            .collect();
+        Self {
+            rules,
+            scoped,
+            executors: DashMap::new(),
+        }
+    }
+
+    /// The global plugins of a request for `host` and `path` matched to `route`;
+    /// `unscoped` when no scoped rule selects the request.
+    pub fn executor_for(
+        &self,
+        route: Option<&ProxyRoute>,
+        host: Option<&str>,
+        path: &str,
+        unscoped: &Arc<ProxyPluginExecutor>,
+    ) -> Arc<ProxyPluginExecutor> {
+        let selected: Vec<usize> = self
+            .scoped
+            .iter()
+            .copied()
+            .filter(|&index| self.rules[index].selects(route, host, path))
+            .collect();
+        if selected.is_empty() {
+            return unscoped.clone();
+        }
+        if let Some(executor) = self.executors.get(&selected) {
+            return executor.clone();
+        }
+        let executor = build_executor(
+            self.rules
+                .iter()
+                .enumerate()
+                .filter(|(index, rule)| !rule.is_scoped() || selected.contains(index))
+                .map(|(_, rule)| rule),
+        );
+        if self.executors.len() < MAX_CACHED_SELECTIONS {
+            self.executors.insert(selected, executor.clone());
+        }
+        executor
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn host_patterns_match_exact_hosts_and_subdomains() {
+        assert!(host_matches("api.example.com", "API.example.com"));
+        assert!(!host_matches("api.example.com", "example.com"));
+        assert!(host_matches("*.example.com", "a.b.example.com"));
+        assert!(!host_matches("*.example.com", "example.com"));
+        assert!(!host_matches("*.example.com", "badexample.com"));
+        assert!(!host_matches("*.example.com", ".example.com"));
+    }
+}
@@ -1,15 +1,18 @@
 //! Whole-graph candidate construction, reference validation, and guarded etcd commit.
 //!
-//! Admin PUT/DELETE call this module; HTTP parsing and response mapping stay in the
+//! Admin PUT/DELETE and import call this module; HTTP parsing and response mapping stay in the
 //! admin adapter. Concrete etcd I/O stays in [`crate::config::etcd`].
 
 use std::collections::HashMap;
 use std::fmt;
 
 use crate::{
-    config::etcd::{EtcdClientWrapper, FullGraph},
+    config::etcd::{canonicalize_prefix, EtcdClientWrapper, FullGraph},
     core::ProxyError,
-    proxy::control_plane::{build_config_set_from_kvs, validate_config_set},
+    proxy::control_plane::{
+        build_config_set_from_kvs, dependents_of, is_metadata_key, parse_key, validate_config_set,
+    },
+    proxy::upstream::drain,
 };
 
 /// Outcomes of a guarded graph mutation that Admin maps to HTTP status codes.
@@ -124,12 +127,109 @@ pub async fn put_resource(
     Ok(committed)
 }
 
+/// Read-modify-write of a stored resource: `update` maps its current body to the new
+/// one, which is validated against the whole graph and committed only if the resource
+/// did not change since it was read.
+pub async fn update_resource(
+    etcd: &EtcdClientWrapper,
+    logical_key: &str,
+    update: impl FnOnce(&[u8]) -> Result<Vec<u8>, GraphMutationError>,
+) -> Result<i64, GraphMutationError> {
+    let graph = etcd.read_full_graph().await?;
+    let full_key = etcd.prefixed_key(logical_key);
+
+    let current = graph
+        .kvs
+        .get(&full_key)
+        .ok_or_else(|| GraphMutationError::NotFound("Resource not found".into()))?;
+    let body = update(current)?;
+
+    validate_candidate(&graph, &full_key, Some(&body), etcd.prefix())?;
+
+    etcd.graph_txn_put(
+        &full_key,
+        body,
+        graph.mod_revisions.get(&full_key).copied(),
+        graph.guard_mod_revision,
+    )
+    .await
+    .map_err(map_txn_error)
+}
+
+/// How a delete treats resources that reference its target.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DeleteMode {
+    /// Refuse to delete a resource that is still referenced.
+    Checked,
+    /// Skip the reference check and delete only the target, leaving its references
+    /// dangling (`?force=true`).
+    Force,
+    /// Delete the target and every resource depending on it (`?cascade=true`).
+    Cascade,
+}
+
+/// Physical keys removed by a delete of `full_key`.
+///
+/// [`DeleteMode::Checked`] requires that no other resource references the target and
+/// [`DeleteMode::Force`] removes it regardless. [`DeleteMode::Cascade`] removes every
+/// resource depending on it too, so the remaining graph still validates.
+pub fn plan_delete(
+    graph: &FullGraph,
+    full_key: &str,
+    mode: DeleteMode,
+    prefix: &str,
+) -> Result<Vec<String>, GraphMutationError> {
+    match mode {
+        DeleteMode::Checked => {
+            validate_candidate(graph, full_key, None, prefix)?;
+            return Ok(vec![full_key.to_string()]);
+        }
+        DeleteMode::Force => return Ok(vec![full_key.to_string()]),
+        DeleteMode::Cascade => {}
+    }
+
+    let canonical = canonicalize_prefix(prefix);
+    let (id, key_type) = parse_key(full_key.as_bytes(), Some(&canonical))
+        .map_err(|e| GraphMutationError::InvalidCandidate(format!("Invalid resource key: {e}")))?;
+    let current: Vec<(String, Vec<u8>)> = graph
+        .kvs
+        .iter()
+        .map(|(k, v)| (k.clone(), v.clone()))
+        .collect();
+    let current_set = build_config_set_from_kvs(&current, prefix).map_err(|e| {
+        GraphMutationError::InvalidCandidate(format!("Failed to build candidate config set: {e}"))
+    })?;
+
+    let mut keys = vec![full_key.to_string()];
+    keys.extend(dependents_of(&current_set, &key_type, &id).into_iter().map(
+        |(dependent_type, dependent_id)| format!("{canonical}{dependent_type}/{dependent_id}"),
+    ));
+
+    let remaining: Vec<(String, Vec<u8>)> = current
+        .into_iter()
+        .filter(|(k, _)| !keys.contains(k))
+        .collect();
+    let candidate_set = build_config_set_from_kvs(&remaining, prefix).map_err(|e| {
+        GraphMutationError::InvalidCandidate(format!("Failed to build candidate config set: {e}"))
+    })?;
+    validate_config_set(&candidate_set).map_err(|e| {
+        GraphMutationError::ReferentialConflict(format!(
+            "Resource is referenced by other resources: {e}"
+        ))
+    })?;
+    Ok(keys)
+}
+
 /// Delete a resource after existence check, whole-graph validation, and a
 /// guarded etcd transaction.
+///
+/// With [`DeleteMode::Cascade`], dependent resources are deleted in the same
+/// transaction (see [`plan_delete`]). Returns the logical keys that were removed.
 pub async fn delete_resource(
     etcd: &EtcdClientWrapper,
     logical_key: &str,
-) -> Result<(), GraphMutationError> {
+    mode: DeleteMode,
+) -> Result<Vec<String>, GraphMutationError> {
     let graph = etcd.read_full_graph().await?;
     let full_key = etcd.prefixed_key(logical_key);
 
@@ -138,13 +238,189 @@ pub async fn delete_resource(
         .get(&full_key)
         .ok_or_else(|| GraphMutationError::NotFound("Resource not found".into()))?;
 
-    validate_candidate(&graph, &full_key, None, etcd.prefix())?;
+    let keys = plan_delete(&graph, &full_key, mode, etcd.prefix())?;
+
+    if keys.len() == 1 {
+        etcd.graph_txn_delete(&full_key, expected_mod_revision, graph.guard_mod_revision)
+            .await
+            .map_err(map_txn_error)?;
+    } else {
+        etcd.graph_txn_batch(Vec::new(), keys.clone(), graph.guard_mod_revision)
+            .await
+            .map_err(map_txn_error)?;
+    }
+    forget_drains(&keys, etcd.prefix());
+
+    Ok(keys
+        .iter()
+        .map(|key| key.strip_prefix(etcd.prefix()).unwrap_or(key).to_string())
+        .collect())
+}
+
+/// Writes and deletes (physical keys) that apply an import to the current graph.
+#[derive(Debug, Default)]
+pub struct ImportPlan {
+    pub puts: Vec<(String, Vec<u8>)>,
+    pub deletes: Vec<String>,
+    /// Imported resources identical to what is already stored.
+    pub unchanged: usize,
+}
+
+impl ImportPlan {
+    /// Reject a plan whose transaction, including the graph guard update, exceeds the
+    /// `max_txn_ops` etcd accepts.
+    pub fn check_txn_ops(&self, max_txn_ops: usize) -> Result<(), GraphMutationError> {
+        let ops = self.puts.len() + self.deletes.len() + 1;
+        if ops > max_txn_ops {
+            return Err(GraphMutationError::InvalidCandidate(format!(
+                "Import needs {ops} etcd transaction operations but etcd.max_txn_ops is \
+                 {max_txn_ops}; raise etcd's --max-txn-ops and etcd.max_txn_ops together, \
+                 or import fewer resources at a time"
+            )));
+        }
+        Ok(())
+    }
+}
+
+/// Outcome of a committed import.
+#[derive(Debug)]
+pub struct ImportOutcome {
+    /// Committed etcd revision, or `None` when nothing needed to change.
+    pub revision: Option<i64>,
+    pub written: usize,
+    pub unchanged: usize,
+    pub deleted: usize,
+    /// Every key written or deleted.
+    pub changes: Vec<ImportedKey>,
+}
+
+/// A resource an import wrote or deleted, with its value in the graph the transaction
+/// was guarded on and the value it stored.
+#[derive(Debug)]
+pub struct ImportedKey {
+    /// Logical key, e.g. `routes/1`.
+    pub key: String,
+    pub before: Option<Vec<u8>>,
+    pub after: Option<Vec<u8>>,
+}
+
+/// Build and validate the candidate graph for an import of `resources` (physical keys).
+///
+/// Imported resources overwrite existing ones with the same key. With `replace`, every
+/// other stored resource is deleted, so the graph ends up matching the import exactly.
+pub fn plan_import(
+    graph: &FullGraph,
+    resources: Vec<(String, Vec<u8>)>,
+    replace: bool,
+    prefix: &str,
+) -> Result<ImportPlan, GraphMutationError> {
+    let imported: HashMap<&str, &[u8]> = resources
+        .iter()
+        .map(|(k, v)| (k.as_str(), v.as_slice()))
+        .collect();
+
+    let mut candidate_kvs: Vec<(String, Vec<u8>)> = resources.clone();
+    let mut deletes = Vec::new();
+    for (key, value) in &graph.kvs {
+        if imported.contains_key(key.as_str()) {
+            continue;
+        }
+        if replace && !is_metadata_key(key.as_bytes()) {
+            deletes.push(key.clone());
+        } else {
+            candidate_kvs.push((key.clone(), value.clone()));
+        }
+    }
+    deletes.sort();
+
+    let candidate_set = build_config_set_from_kvs(&candidate_kvs, prefix).map_err(|e| {
+        GraphMutationError::InvalidCandidate(format!("Failed to build candidate config set: {e}"))
+    })?;
+    validate_config_set(&candidate_set).map_err(|e| {
+        GraphMutationError::InvalidCandidate(format!("Imported configuration is invalid: {e}"))
+    })?;
+
+    let mut plan = ImportPlan {
+        deletes,
+        ..Default::default()
+    };
+    for (key, value) in resources {
+        if graph.kvs.get(&key) == Some(&value) {
+            plan.unchanged += 1;
+        } else {
+            plan.puts.push((key, value));
+        }
+    }
+    Ok(plan)
+}
 
-    etcd.graph_txn_delete(&full_key, expected_mod_revision, graph.guard_mod_revision)
+/// Import a batch of resources (`logical_key`, body) in one guarded etcd transaction
+/// after whole-graph validation.
+pub async fn import_resources(
+    etcd: &EtcdClientWrapper,
+    resources: Vec<(String, Vec<u8>)>,
+    replace: bool,
+) -> Result<ImportOutcome, GraphMutationError> {
+    let graph = etcd.read_full_graph().await?;
+    let resources = resources
+        .into_iter()
+        .map(|(key, body)| (etcd.prefixed_key(&key), body))
+        .collect();
+
+    let plan = plan_import(&graph, resources, replace, etcd.prefix())?;
+    plan.check_txn_ops(etcd.max_txn_ops())?;
+    let (written, deleted) = (plan.puts.len(), plan.deletes.len());
+    if written == 0 && deleted == 0 {
+        return Ok(ImportOutcome {
+            revision: None,
+            written,
+            unchanged: plan.unchanged,
+            deleted,
+            changes: Vec::new(),
+        });
+    }
+
+    let imported = |key: &String, after: Option<&Vec<u8>>| ImportedKey {
+        key: key
+            .strip_prefix(etcd.prefix())
+            .unwrap_or(key.as_str())
+            .to_string(),
+        before: graph.kvs.get(key).cloned(),
+        after: after.cloned(),
+    };
+    let changes = plan
+        .puts
+        .iter()
+        .map(|(key, value)| imported(key, Some(value)))
+        .chain(plan.deletes.iter().map(|key| imported(key, None)))
+        .collect();
+
+    let deletes = plan.deletes.clone();
+    let revision = etcd
+        .graph_txn_batch(plan.puts, plan.deletes, graph.guard_mod_revision)
         .await
         .map_err(map_txn_error)?;
+    forget_drains(&deletes, etcd.prefix());
+
+    Ok(ImportOutcome {
+        revision: Some(revision),
+        written,
+        unchanged: plan.unchanged,
+        deleted,
+        changes,
+    })
+}
 
-    Ok(())
+/// Drop the drain state of the upstreams among the deleted physical `keys`.
+fn forget_drains(keys: &[String], prefix: &str) {
+    let canonical = canonicalize_prefix(prefix);
+    for key in keys {
+        if let Ok((id, key_type)) = parse_key(key.as_bytes(), Some(&canonical)) {
+            if key_type == "upstreams" {
+                drain::clear(&id);
+            }
+        }
+    }
 }
 
 fn map_txn_error(e: ProxyError) -> GraphMutationError {
@@ -160,7 +436,8 @@ fn map_txn_error(e: ProxyError) -> GraphMutationError {
 mod tests {
     use super::*;
     use crate::config::{
-        SelectionType, Upstream, UpstreamHashOn, UpstreamPassHost, UpstreamScheme,
+        etcd::DEFAULT_MAX_TXN_OPS, SelectionType, Upstream, UpstreamHashOn, UpstreamPassHost,
+        UpstreamScheme,
     };
 
     fn sample_upstream_json(id: &str, node: &str) -> Vec<u8> {
@@ -180,6 +457,11 @@ mod tests {
             pass_host: UpstreamPassHost::PASS,
             upstream_host: None,
             tls: None,
+            zone_aware: None,
+            discovery_type: None,
+            priorities: HashMap::new(),
+            sticky: None,
+            labels: Default::default(),
         };
         serde_json::to_vec(&upstream).unwrap()
     }
@@ -282,6 +564,143 @@ mod tests {
         assert!(validate_candidate(&graph, &upstream_key, None, prefix).is_ok());
     }
 
+    #[test]
+    fn forced_delete_removes_only_the_target() {
+        let prefix = "/pingsix/";
+        let upstream_key = format!("{prefix}upstreams/u1");
+        let graph = graph_with(vec![
+            (
+                upstream_key.clone(),
+                sample_upstream_json("u1", "10.0.0.1:80"),
+            ),
+            (
+                format!("{prefix}upstreams/u2"),
+                sample_upstream_json("u2", "10.0.0.2:80"),
+            ),
+            (format!("{prefix}routes/r1"), sample_route_json("r1", "u1")),
+            (format!("{prefix}routes/r2"), sample_route_json("r2", "u2")),
+        ]);
+
+        assert!(matches!(
+            plan_delete(&graph, &upstream_key, DeleteMode::Checked, prefix),
+            Err(GraphMutationError::ReferentialConflict(_))
+        ));
+        let keys = plan_delete(&graph, &upstream_key, DeleteMode::Force, prefix).unwrap();
+        assert_eq!(keys, vec![upstream_key.clone()]);
+        let keys = plan_delete(&graph, &upstream_key, DeleteMode::Cascade, prefix).unwrap();
+        assert_eq!(keys, vec![upstream_key, format!("{prefix}routes/r1")]);
+    }
+
+    #[test]
+    fn import_plan_validates_references_across_the_batch() {
+        let prefix = "/pingsix/";
+        let graph = graph_with(vec![]);
+        let route_only = vec![(format!("{prefix}routes/r1"), sample_route_json("r1", "u1"))];
+        assert!(matches!(
+            plan_import(&graph, route_only.clone(), false, prefix),
+            Err(GraphMutationError::InvalidCandidate(_))
+        ));
+
+        let mut with_upstream = route_only;
+        with_upstream.push((
+            format!("{prefix}upstreams/u1"),
+            sample_upstream_json("u1", "10.0.0.1:80"),
+        ));
+        let plan = plan_import(&graph, with_upstream, false, prefix).unwrap();
+        assert_eq!(plan.puts.len(), 2);
+        assert!(plan.deletes.is_empty());
+    }
+
+    #[test]
+    fn import_plan_replace_deletes_missing_and_skips_unchanged() {
+        let prefix = "/pingsix/";
+        let upstream = (
+            format!("{prefix}upstreams/u1"),
+            sample_upstream_json("u1", "10.0.0.1:80"),
+        );
+        let graph = graph_with(vec![
+            upstream.clone(),
+            (format!("{prefix}routes/r1"), sample_route_json("r1", "u1")),
+        ]);
+
+        let plan = plan_import(&graph, vec![upstream.clone()], false, prefix).unwrap();
+        assert_eq!((plan.puts.len(), plan.unchanged), (0, 1));
+        assert!(plan.deletes.is_empty());
+
+        let plan = plan_import(&graph, vec![upstream], true, prefix).unwrap();
+        assert_eq!(plan.deletes, vec![format!("{prefix}routes/r1")]);
+    }
+
+    #[test]
+    fn imports_beyond_the_txn_op_limit_are_rejected() {
+        let prefix = "/pingsix/";
+        let graph = graph_with(vec![]);
+        let upstreams: Vec<(String, Vec<u8>)> = (0..DEFAULT_MAX_TXN_OPS)
+            .map(|i| {
+                let id = format!("u{i}");
+                (
+                    format!("{prefix}upstreams/{id}"),
+                    sample_upstream_json(&id, "10.0.0.1:80"),
+                )
+            })
+            .collect();
+
+        let plan = plan_import(&graph, upstreams.clone(), false, prefix).unwrap();
+        assert!(matches!(
+            plan.check_txn_ops(DEFAULT_MAX_TXN_OPS),
+            Err(GraphMutationError::InvalidCandidate(_))
+        ));
+        assert!(plan.check_txn_ops(DEFAULT_MAX_TXN_OPS + 1).is_ok());
+
+        let plan = plan_import(&graph, upstreams[1..].to_vec(), false, prefix).unwrap();
+        assert!(plan.check_txn_ops(DEFAULT_MAX_TXN_OPS).is_ok());
+    }
+
+    #[test]
+    fn replace_deletes_count_towards_the_txn_op_limit() {
+        let prefix = "/pingsix/";
+        let stored: Vec<(String, Vec<u8>)> = (0..DEFAULT_MAX_TXN_OPS)
+            .map(|i| {
+                let id = format!("u{i}");
+                (
+                    format!("{prefix}upstreams/{id}"),
+                    sample_upstream_json(&id, "10.0.0.1:80"),
+                )
+            })
+            .collect();
+        let graph = graph_with(stored);
+
+        // Rolling back to a snapshot holding one new upstream deletes all stored ones.
+        let snapshot = vec![(
+            format!("{prefix}upstreams/new"),
+            sample_upstream_json("new", "10.0.0.2:80"),
+        )];
+        let plan = plan_import(&graph, snapshot, true, prefix).unwrap();
+        assert_eq!(
+            (plan.puts.len(), plan.deletes.len()),
+            (1, DEFAULT_MAX_TXN_OPS)
+        );
+        assert!(plan.check_txn_ops(DEFAULT_MAX_TXN_OPS).is_err());
+    }
+
+    #[test]
+    fn deleting_an_upstream_forgets_its_drained_nodes() {
+        let prefix = "/pingsix/";
+        assert!(drain::set_drained("drain-deleted", "127.0.0.1:1980", true));
+        assert!(drain::set_drained("drain-kept", "127.0.0.1:1980", true));
+
+        forget_drains(
+            &[
+                format!("{prefix}upstreams/drain-deleted"),
+                format!("{prefix}routes/drain-kept"),
+            ],
+            prefix,
+        );
+        assert!(!drain::is_drained("drain-deleted", "127.0.0.1:1980"));
+        assert!(drain::is_drained("drain-kept", "127.0.0.1:1980"));
+        drain::clear("drain-kept");
+    }
+
     #[test]
     fn map_txn_error_preserves_cas_message() {
         let err = map_txn_error(ProxyError::CasConflict("ignored".into()));
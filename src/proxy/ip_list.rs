@@ -0,0 +1,40 @@
+use std::sync::Arc;
+
+use crate::{
+    config::{self, Identifiable},
+    core::ProxyError,
+    utils::ip_set::{parse_ip_set, IpSet},
+};
+
+use super::runtime::RUNTIME;
+
+/// Proxy IP list: a named CIDR set compiled for lookups.
+pub struct ProxyIpList {
+    pub inner: config::IpList,
+    pub set: IpSet,
+}
+
+impl Identifiable for ProxyIpList {
+    fn id(&self) -> &str {
+        &self.inner.id
+    }
+
+    fn set_id(&mut self, id: String) {
+        self.inner.id = id;
+    }
+}
+
+impl TryFrom<config::IpList> for ProxyIpList {
+    type Error = ProxyError;
+
+    fn try_from(value: config::IpList) -> Result<Self, Self::Error> {
+        let set = parse_ip_set(&format!("ip_list '{}'", value.id), &value.cidrs)
+            .map_err(ProxyError::Configuration)?;
+        Ok(Self { inner: value, set })
+    }
+}
+
+/// Look up an IP list in the published runtime snapshot.
+pub fn ip_list_fetch(id: &str) -> Option<Arc<ProxyIpList>> {
+    RUNTIME.load().ip_lists.get(id).cloned()
+}
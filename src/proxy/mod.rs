@@ -1,9 +1,12 @@
 //! Proxy resource management and control-plane coordination.
 
+pub mod cert_expiry;
+pub mod config_webhook;
 pub mod control_plane;
 pub mod event;
 pub mod global_rule;
 pub mod graph_mutation;
+pub mod ip_list;
 pub mod route;
 pub mod runtime;
 pub mod service;
@@ -1,23 +1,27 @@
 use std::time::Duration;
-use std::{collections::HashMap, sync::Arc};
+use std::{
+    collections::{HashMap, HashSet},
+    sync::Arc,
+};
 
 use matchit::{InsertError, Router as MatchRouter};
 use pingora_core::upstreams::peer::HttpPeer;
 use pingora_error::Result;
+use pingora_http::RequestHeader;
 use pingora_proxy::Session;
 
 use crate::{
     config::{self, Identifiable},
     core::{
-        sort_plugins_by_priority_desc, ErrorContext, ProxyError, ProxyPlugin, ProxyPluginExecutor,
-        ProxyResult, RouteContext, UpstreamSelector,
+        metrics, sort_plugins_by_priority_desc, ErrorContext, ProxyError, ProxyPlugin,
+        ProxyPluginExecutor, ProxyResult, RouteContext, UpstreamSelector,
     },
-    plugins::build_plugin_with_upstreams,
-    utils::request::get_request_host,
+    plugins::{build_plugin_with_upstreams, is_disabled},
+    utils::request::{get_request_host, get_request_port},
 };
 
 use super::{
-    service::ProxyService,
+    service::{BlueGreenSwitch, ProxyService},
     upstream::{inline_key, PreparedUpstreams, ProxyUpstream},
 };
 
@@ -62,6 +66,7 @@ fn route_cache_namespace_fingerprint(route: &config::Route, service: Option<&Pro
         timeout.connect.hash(&mut hasher);
         timeout.send.hash(&mut hasher);
         timeout.read.hash(&mut hasher);
+        timeout.total.hash(&mut hasher);
     }
     hash_plugin_map(&route.plugins, &mut hasher);
     if let Some(service) = service {
@@ -133,16 +138,25 @@ fn merge_route_and_service_plugins(
 /// Type alias for route match result: (params, route)
 pub type RouteMatchResult = Option<(Vec<(String, String)>, Arc<ProxyRoute>)>;
 
+/// Type alias for request match result: (matching step, params, route)
+pub type RequestMatchResult = Option<(MatchKind, Vec<(String, String)>, Arc<ProxyRoute>)>;
+
 /// Proxy route with all service and upstream dependencies bound at build time.
 pub struct ProxyRoute {
     pub inner: config::Route,
     pub plugins: Vec<Arc<dyn ProxyPlugin>>,
     resolved_upstream: Option<Arc<dyn UpstreamSelector>>,
+    /// Blue/green switch of the service, when the route has no upstream of its own.
+    blue_green: Option<Arc<BlueGreenSwitch>>,
     effective_hosts: Vec<String>,
     plugin_executor: Arc<ProxyPluginExecutor>,
     pub inline_upstream: Option<Arc<ProxyUpstream>>,
     /// Fingerprint of route/service identity and response-affecting plugins.
     cache_namespace_fingerprint: u64,
+    /// Whether the route and its service are enabled; disabled routes are not matched.
+    enabled: bool,
+    /// Generation of the service build merged into `plugin_executor`.
+    service_generation: Option<u64>,
 }
 
 impl Identifiable for ProxyRoute {
@@ -155,6 +169,38 @@ impl Identifiable for ProxyRoute {
     }
 }
 
+/// Id of the fallback route compiled for a `virtual_host` service. Configured route
+/// ids are store key segments without `/`, so these cannot collide with them.
+pub fn virtual_host_route_id(service_id: &str) -> String {
+    format!("services/{service_id}")
+}
+
+/// Fallback route serving the hosts of a `virtual_host` service through it.
+pub(crate) fn virtual_host_route(service_id: &str) -> config::Route {
+    config::Route {
+        id: virtual_host_route_id(service_id),
+        uri: None,
+        uris: Vec::new(),
+        methods: Vec::new(),
+        host: None,
+        hosts: Vec::new(),
+        priority: 0,
+        plugins: HashMap::new(),
+        plugin_config_id: None,
+        upstream: None,
+        upstream_id: None,
+        service_id: Some(service_id.to_string()),
+        timeout: None,
+        streaming: false,
+        fallback: true,
+        shed_priority: Default::default(),
+        enabled: true,
+        labels: HashMap::new(),
+        strip_path_prefix: None,
+        api_group: None,
+    }
+}
+
 impl ProxyRoute {
     pub(crate) fn build(
         route: config::Route,
@@ -209,6 +255,11 @@ impl ProxyRoute {
         } else {
             service.as_ref().and_then(|s| s.resolve_upstream())
         };
+        let blue_green = if route.upstream.is_none() && route.upstream_id.is_none() {
+            service.as_ref().and_then(|s| s.blue_green.clone())
+        } else {
+            None
+        };
 
         let effective_hosts = if !route.get_hosts().is_empty() {
             route.get_hosts().into_iter().map(str::to_string).collect()
@@ -220,7 +271,9 @@ impl ProxyRoute {
         };
 
         let mut plugins = Vec::with_capacity(route.plugins.len());
+        let mut disabled_plugins = Vec::new();
         for (name, value) in route.plugins.clone() {
+            let disabled = is_disabled(&value);
             let plugin = build_plugin_with_upstreams(
                 &name,
                 value,
@@ -229,11 +282,20 @@ impl ProxyRoute {
                 &format!("route/{}", route.id),
             )
             .map_err(|e| ProxyError::Plugin(format!("Failed to build plugin '{name}': {e}")))?;
-            plugins.push(plugin);
+            if disabled {
+                disabled_plugins.push(name);
+            } else {
+                plugins.push(plugin);
+            }
         }
         sort_plugins_by_priority_desc(plugins.as_mut_slice());
 
-        let plugin_names = build_plugin_name_index(&plugins);
+        // A plugin disabled on the route also switches off the service's plugin of
+        // the same name.
+        let mut plugin_names = build_plugin_name_index(&plugins);
+        plugin_names.extend(disabled_plugins);
+        plugin_names.sort();
+        plugin_names.dedup();
         let merged_plugins = if let Some(service) = &service {
             merge_route_and_service_plugins(&plugins, &service.plugins, &plugin_names)
         } else {
@@ -247,18 +309,42 @@ impl ProxyRoute {
 
         let cache_namespace_fingerprint =
             route_cache_namespace_fingerprint(&route, service.as_deref());
+        let enabled = route.enabled && service.as_ref().is_none_or(|s| s.inner.enabled);
+        let service_generation = service.as_ref().map(|s| s.generation);
 
         Ok(Self {
             inner: route,
             plugins,
             resolved_upstream,
+            blue_green,
             effective_hosts,
             plugin_executor,
             inline_upstream,
             cache_namespace_fingerprint,
+            enabled,
+            service_generation,
         })
     }
 
+    /// Whether this is the fallback route of a `virtual_host` service.
+    fn is_virtual_host(&self) -> bool {
+        self.inner.service_id.as_deref().is_some_and(|service_id| {
+            self.inner.fallback && self.inner.id == virtual_host_route_id(service_id)
+        })
+    }
+
+    /// Whether this route was built against the current build of its service, i.e.
+    /// its merged plugins, hosts and upstream are still valid.
+    pub(crate) fn is_bound_to(&self, services: &HashMap<String, Arc<ProxyService>>) -> bool {
+        let current = self
+            .inner
+            .service_id
+            .as_deref()
+            .and_then(|id| services.get(id))
+            .map(|s| s.generation);
+        current == self.service_generation
+    }
+
     fn get_hosts(&self) -> Vec<&str> {
         self.effective_hosts.iter().map(String::as_str).collect()
     }
@@ -326,9 +412,29 @@ impl RouteContext for ProxyRoute {
         self.resolved_upstream.clone()
     }
 
+    fn request_upstream(&self, req: &RequestHeader) -> Option<Arc<dyn UpstreamSelector>> {
+        self.blue_green.as_ref().map(|switch| switch.select(req))
+    }
+
     fn timeout(&self) -> Option<&config::Timeout> {
         self.inner.timeout.as_ref()
     }
+
+    fn streaming(&self) -> bool {
+        self.inner.streaming
+    }
+
+    fn shed_priority(&self) -> config::ShedPriority {
+        self.inner.shed_priority
+    }
+
+    fn strip_path_prefix(&self) -> Option<&str> {
+        self.inner.strip_path_prefix.as_deref()
+    }
+
+    fn api_group(&self) -> Option<&str> {
+        self.inner.api_group.as_deref()
+    }
 }
 
 impl ProxyRoute {
@@ -349,6 +455,7 @@ pub(crate) fn apply_route_timeout(route_timeout: Option<&config::Timeout>, p: &m
         connect,
         read,
         send,
+        ..
     }) = route_timeout
     {
         p.options.connection_timeout = Some(Duration::from_secs(*connect));
@@ -357,30 +464,166 @@ pub(crate) fn apply_route_timeout(route_timeout: Option<&config::Timeout>, p: &m
     }
 }
 
+/// Which matching step selected a route.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MatchKind {
+    /// Host, URI and method matched a route.
+    Route,
+    /// A CORS preflight matched a route serving the requested method.
+    Preflight,
+    /// Nothing else matched; the host's `fallback` route.
+    Fallback,
+}
+
+impl MatchKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            MatchKind::Route => "route",
+            MatchKind::Preflight => "preflight",
+            MatchKind::Fallback => "fallback",
+        }
+    }
+}
+
+/// URI router of one matcher shard; each URI maps to its routes by descending priority.
+type UriRouter = MatchRouter<Vec<Arc<ProxyRoute>>>;
+
+/// Route matcher sharded by host pattern. Each shard is an immutable router shared
+/// between snapshots, so a rebuild only re-inserts the routes of the shards that a
+/// route change touched.
 #[derive(Default)]
 pub struct MatchEntry {
     /// Router for non-host URI matching
-    non_host_uri: MatchRouter<Vec<Arc<ProxyRoute>>>,
-    /// Router for host URI matching
-    host_uris: MatchRouter<MatchRouter<Vec<Arc<ProxyRoute>>>>,
+    non_host_uri: Arc<UriRouter>,
+    /// URI routers by reversed host pattern, the source of `host_uris`
+    host_shards: HashMap<String, Arc<UriRouter>>,
+    /// Router over `host_shards`
+    host_uris: MatchRouter<Arc<UriRouter>>,
+    /// Whether any route host names a port (`api.example.com:8443`)
+    has_port_hosts: bool,
+    /// Fallback routes by reversed host pattern, the source of `fallback_hosts`
+    fallback_host_routes: HashMap<String, Vec<Arc<ProxyRoute>>>,
+    /// Router over `fallback_host_routes`
+    fallback_hosts: MatchRouter<Vec<Arc<ProxyRoute>>>,
+    /// Fallback routes without hosts, by descending priority
+    fallback: Vec<Arc<ProxyRoute>>,
 }
 
 impl MatchEntry {
-    pub(crate) fn build(
-        routes: &std::collections::HashMap<String, Arc<ProxyRoute>>,
+    /// Build a matcher for `routes`, reusing the shards of `previous` (a matcher and
+    /// the routes it was built from) that no added, removed or rebuilt route touches.
+    pub(crate) fn rebuild(
+        routes: &HashMap<String, Arc<ProxyRoute>>,
+        previous: Option<(&Self, &HashMap<String, Arc<ProxyRoute>>)>,
     ) -> ProxyResult<Self> {
+        let error = |id: &str, e: InsertError| {
+            ProxyError::Configuration(format!("Failed to build route matcher for '{id}': {e}"))
+        };
+
         let mut matcher = Self::default();
-        for route in routes.values() {
-            matcher.insert_route(route.clone()).map_err(|e| {
-                ProxyError::Configuration(format!(
-                    "Failed to build route matcher for '{}': {e}",
-                    route.inner.id
-                ))
-            })?;
+        let mut shards: HashMap<Option<String>, Vec<&Arc<ProxyRoute>>> = HashMap::new();
+        for route in routes.values().filter(|route| route.enabled) {
+            if route.inner.fallback {
+                matcher
+                    .insert_route(route.clone())
+                    .map_err(|e| error(&route.inner.id, e))?;
+                continue;
+            }
+            matcher.has_port_hosts |= route.get_hosts().iter().any(|host| host.contains(':'));
+            for key in Self::shard_keys(route) {
+                shards.entry(key).or_default().push(route);
+            }
+        }
+
+        // Shards holding an old or new version of a changed route are rebuilt.
+        let dirty: Option<HashSet<Option<String>>> = previous.map(|(_, previous_routes)| {
+            let changed = |id: &String| {
+                !routes
+                    .get(id)
+                    .zip(previous_routes.get(id))
+                    .is_some_and(|(a, b)| Arc::ptr_eq(a, b))
+            };
+            previous_routes
+                .iter()
+                .chain(routes.iter())
+                .filter(|(id, route)| changed(id) && route.enabled && !route.inner.fallback)
+                .flat_map(|(_, route)| Self::shard_keys(route))
+                .collect()
+        });
+        let reusable = |key: &Option<String>| -> Option<Arc<UriRouter>> {
+            let (previous, _) = previous?;
+            if dirty.as_ref().is_some_and(|dirty| dirty.contains(key)) {
+                return None;
+            }
+            match key {
+                None => Some(previous.non_host_uri.clone()),
+                Some(pattern) => previous.host_shards.get(pattern).cloned(),
+            }
+        };
+
+        let (mut rebuilt, mut reused) = (0, 0);
+        for (key, shard_routes) in shards {
+            let shard = match reusable(&key) {
+                Some(shard) => {
+                    reused += 1;
+                    shard
+                }
+                None => {
+                    rebuilt += 1;
+                    let mut router = UriRouter::new();
+                    for route in shard_routes {
+                        for uri in route.inner.get_uris() {
+                            Self::insert_into_router(&mut router, uri, route.clone())
+                                .map_err(|e| error(&route.inner.id, e))?;
+                        }
+                    }
+                    Arc::new(router)
+                }
+            };
+            match key {
+                None => matcher.non_host_uri = shard,
+                Some(pattern) => {
+                    matcher.host_shards.insert(pattern, shard);
+                }
+            }
         }
+        matcher.host_uris = Self::host_router(&matcher.host_shards)
+            .map_err(|e| ProxyError::Configuration(format!("Failed to build host matcher: {e}")))?;
+
+        metrics::ROUTE_MATCHER_SHARDS
+            .with_label_values(&["rebuilt"])
+            .inc_by(rebuilt);
+        metrics::ROUTE_MATCHER_SHARDS
+            .with_label_values(&["reused"])
+            .inc_by(reused);
         Ok(matcher)
     }
 
+    /// Shards a regular route is indexed in: one per host pattern, or the host-less
+    /// shard (`None`).
+    fn shard_keys(route: &ProxyRoute) -> Vec<Option<String>> {
+        let hosts = route.get_hosts();
+        if hosts.is_empty() {
+            return vec![None];
+        }
+        hosts
+            .into_iter()
+            .map(|host| Some(Self::reverse_host(host)))
+            .collect()
+    }
+
+    /// Router over the host shards. Rebuilt rather than updated in place: looking up
+    /// a pattern with `at_mut` would resolve an exact host to an overlapping wildcard.
+    fn host_router(
+        shards: &HashMap<String, Arc<UriRouter>>,
+    ) -> Result<MatchRouter<Arc<UriRouter>>, InsertError> {
+        let mut router = MatchRouter::new();
+        for (pattern, shard) in shards {
+            router.insert(pattern.clone(), shard.clone())?;
+        }
+        Ok(router)
+    }
+
     /// Converts host to reversed matchit-compatible pattern.
     /// Wildcard "*.example.com" becomes "moc.elpmaxe.{*subdomain}".
     /// Exact "api.example.com" becomes "moc.elpmaxe.ipa".
@@ -402,7 +645,7 @@ impl MatchEntry {
     }
 
     fn insert_into_router(
-        router: &mut MatchRouter<Vec<Arc<ProxyRoute>>>,
+        router: &mut UriRouter,
         uri: &str,
         proxy_route: Arc<ProxyRoute>,
     ) -> Result<(), InsertError> {
@@ -424,61 +667,99 @@ impl MatchEntry {
     /// Inserts a route into the match entry.
     pub fn insert_route(&mut self, proxy_route: Arc<ProxyRoute>) -> Result<(), InsertError> {
         let route_hosts = proxy_route.get_hosts();
-        let uris = proxy_route.inner.get_uris();
+        self.has_port_hosts |= route_hosts.iter().any(|host| host.contains(':'));
 
-        let has_hosts = if !route_hosts.is_empty() {
-            self.insert_host_uris(route_hosts.iter().copied(), &uris, &proxy_route)?;
-            true
-        } else {
-            false
-        };
+        if proxy_route.inner.fallback {
+            if route_hosts.is_empty() {
+                self.fallback.push(proxy_route.clone());
+                self.fallback
+                    .sort_by_key(|b| std::cmp::Reverse(b.inner.priority));
+                return Ok(());
+            }
+            for host in route_hosts {
+                let routes = self
+                    .fallback_host_routes
+                    .entry(Self::reverse_host(host))
+                    .or_default();
+                routes.push(proxy_route.clone());
+                // Configured fallback routes win over a virtual host service's.
+                routes.sort_by_key(|b| (std::cmp::Reverse(b.inner.priority), b.is_virtual_host()));
+            }
+            // Rebuilt rather than updated in place: looking up a pattern with `at_mut`
+            // would resolve an exact host to an overlapping wildcard entry.
+            let mut fallback_hosts = MatchRouter::new();
+            for (pattern, routes) in &self.fallback_host_routes {
+                fallback_hosts.insert(pattern.clone(), routes.clone())?;
+            }
+            self.fallback_hosts = fallback_hosts;
+            return Ok(());
+        }
 
-        if !has_hosts {
+        let uris = proxy_route.inner.get_uris();
+        for key in Self::shard_keys(&proxy_route) {
+            let shard = match key {
+                None => &mut self.non_host_uri,
+                Some(pattern) => self.host_shards.entry(pattern).or_default(),
+            };
             for uri in &uris {
-                Self::insert_into_router(&mut self.non_host_uri, uri, proxy_route.clone())?;
+                Self::insert_into_router(Arc::make_mut(shard), uri, proxy_route.clone())?;
             }
         }
+        if !route_hosts.is_empty() {
+            self.host_uris = Self::host_router(&self.host_shards)?;
+        }
 
         Ok(())
     }
 
-    fn insert_host_uris<'a>(
-        &mut self,
-        hosts: impl Iterator<Item = &'a str>,
-        uris: &[&str],
-        proxy_route: &Arc<ProxyRoute>,
-    ) -> Result<(), InsertError> {
-        for host in hosts {
-            let processed_host = Self::reverse_host(host);
-            let inner_router = self.host_uris.at_mut(processed_host.as_str());
-
-            let inner_router = match inner_router {
-                Ok(router) => router.value,
-                Err(_) => {
-                    let new_router = MatchRouter::new();
-                    self.host_uris.insert(processed_host.clone(), new_router)?;
-                    self.host_uris
-                        .at_mut(processed_host.as_str())
-                        .unwrap()
-                        .value
-                }
-            };
-
-            for uri in uris {
-                Self::insert_into_router(inner_router, uri, proxy_route.clone())?;
-            }
+    /// Reversed host lookup keys, most specific first: `host:port` when a port is
+    /// known and some route names one, then the bare host.
+    fn host_keys(&self, host: Option<&str>, port: Option<u16>) -> Vec<String> {
+        let Some(host) = host.filter(|h| !h.is_empty()) else {
+            return Vec::new();
+        };
+        let mut keys = Vec::with_capacity(2);
+        if let Some(port) = port.filter(|_| self.has_port_hosts) {
+            keys.push(Self::reverse_ascii_lowercase(&format!("{host}:{port}")));
         }
-        Ok(())
+        keys.push(Self::reverse_ascii_lowercase(host));
+        keys
     }
 
-    /// Matches a request to a route.
-    pub(crate) fn match_request(&self, session: &mut Session) -> RouteMatchResult {
-        let host = get_request_host(session.req_header());
-        let uri = session.req_header().uri.path();
-        let method = session.req_header().method.as_str();
+    /// Matches a request to a route: regular routes first, then CORS preflight
+    /// candidates, then the host's fallback route.
+    pub(crate) fn match_request(
+        &self,
+        session: &Session,
+        global_has_cors: bool,
+    ) -> RequestMatchResult {
+        self.match_header(
+            session.req_header(),
+            get_request_port(session),
+            global_has_cors,
+        )
+    }
 
-        log::debug!("match request: host={host:?}, uri={uri:?}, method={method:?}");
-        self.match_host_uri_method(host, uri, method)
+    /// Session-free [`Self::match_request`], used by the Admin API route explainer.
+    pub fn match_header(
+        &self,
+        request: &RequestHeader,
+        port: Option<u16>,
+        global_has_cors: bool,
+    ) -> RequestMatchResult {
+        let host = get_request_host(request);
+        let uri = request.uri.path();
+        let method = request.method.as_str();
+
+        log::debug!("match request: host={host:?}, port={port:?}, uri={uri:?}, method={method:?}");
+        if let Some((params, route)) = self.match_host_port_uri_method(host, port, uri, method) {
+            return Some((MatchKind::Route, params, route));
+        }
+        if let Some((params, route)) = self.match_preflight(request, port, global_has_cors) {
+            return Some((MatchKind::Preflight, params, route));
+        }
+        let (params, route) = self.match_fallback_host(host, port, method)?;
+        Some((MatchKind::Fallback, params, route))
     }
 
     /// Match host/URI/method without a Pingora session.
@@ -490,9 +771,20 @@ impl MatchEntry {
         uri: &str,
         method: &str,
     ) -> RouteMatchResult {
-        if let Some(host_str) = host.filter(|h| !h.is_empty()) {
-            // Reverse the host and let matchit handle wildcard suffix matching.
-            let reversed_host = Self::reverse_ascii_lowercase(host_str);
+        self.match_host_port_uri_method(host, None, uri, method)
+    }
+
+    /// Match host/port/URI/method. Routes whose host names the request port win over
+    /// routes with the bare host.
+    pub fn match_host_port_uri_method(
+        &self,
+        host: Option<&str>,
+        port: Option<u16>,
+        uri: &str,
+        method: &str,
+    ) -> RouteMatchResult {
+        // Reverse the host and let matchit handle wildcard suffix matching.
+        for reversed_host in self.host_keys(host, port) {
             if let Ok(v) = self.host_uris.at(&reversed_host) {
                 if let Some(result) = Self::match_uri_method(v.value, uri, method) {
                     return Some(result);
@@ -503,15 +795,42 @@ impl MatchEntry {
         Self::match_uri_method(&self.non_host_uri, uri, method)
     }
 
+    /// Fallback route for `host`/`port`: host-specific fallbacks first, then the
+    /// host-less ones.
+    pub fn match_fallback_host(
+        &self,
+        host: Option<&str>,
+        port: Option<u16>,
+        method: &str,
+    ) -> RouteMatchResult {
+        let allows = |route: &&Arc<ProxyRoute>| {
+            route.inner.methods.is_empty()
+                || route
+                    .inner
+                    .methods
+                    .iter()
+                    .any(|configured| *configured == method)
+        };
+        for reversed_host in self.host_keys(host, port) {
+            if let Ok(v) = self.fallback_hosts.at(&reversed_host) {
+                if let Some(route) = v.value.iter().find(allows) {
+                    return Some((Vec::new(), route.clone()));
+                }
+            }
+        }
+        let route = self.fallback.iter().find(allows)?;
+        Some((Vec::new(), route.clone()))
+    }
+
     /// Match a syntactically valid CORS preflight using its requested method.
     /// Normal OPTIONS routes remain preferred because callers invoke this only
     /// after normal matching fails.
-    pub(crate) fn match_preflight(
+    fn match_preflight(
         &self,
-        session: &mut Session,
+        request: &RequestHeader,
+        port: Option<u16>,
         global_has_cors: bool,
     ) -> RouteMatchResult {
-        let request = session.req_header();
         if request.method != http::Method::OPTIONS
             || !request.headers.contains_key(http::header::ORIGIN)
         {
@@ -525,8 +844,7 @@ impl MatchEntry {
             return None;
         }
         let uri = request.uri.path();
-        if let Some(host) = get_request_host(request).filter(|host| !host.is_empty()) {
-            let reversed_host = Self::reverse_ascii_lowercase(host);
+        for reversed_host in self.host_keys(get_request_host(request), port) {
             if let Ok(routes) = self.host_uris.at(&reversed_host) {
                 if let Some(result) =
                     Self::match_preflight_uri(routes.value, uri, method, global_has_cors)
@@ -539,7 +857,7 @@ impl MatchEntry {
     }
 
     fn match_preflight_uri(
-        match_router: &MatchRouter<Vec<Arc<ProxyRoute>>>,
+        match_router: &UriRouter,
         uri: &str,
         method: &str,
         global_has_cors: bool,
@@ -563,11 +881,7 @@ impl MatchEntry {
     }
 
     /// Matches a URI to a route.
-    fn match_uri_method(
-        match_router: &MatchRouter<Vec<Arc<ProxyRoute>>>,
-        uri: &str,
-        method: &str,
-    ) -> RouteMatchResult {
+    fn match_uri_method(match_router: &UriRouter, uri: &str, method: &str) -> RouteMatchResult {
         if let Ok(v) = match_router.at(uri) {
             let route = v.value.iter().find(|route| {
                 route.inner.methods.is_empty()
@@ -594,7 +908,7 @@ impl MatchEntry {
 mod tests {
     use super::*;
     use async_trait::async_trait;
-    use serde_json::Value as JsonValue;
+    use serde_json::{json, Value as JsonValue};
     use std::collections::HashMap;
 
     #[test]
@@ -777,6 +1091,14 @@ mod tests {
             upstream_id: None,
             service_id: None,
             timeout: None,
+            streaming: false,
+            fallback: false,
+            shed_priority: Default::default(),
+            enabled: true,
+            labels: Default::default(),
+            plugin_config_id: Default::default(),
+            strip_path_prefix: None,
+            api_group: None,
         };
 
         let upstreams = HashMap::new();
@@ -787,6 +1109,231 @@ mod tests {
         assert!(Arc::ptr_eq(&exec, &ProxyPluginExecutor::default_shared()));
     }
 
+    fn test_route(id: &str, hosts: &[&str], uri: Option<&str>, fallback: bool) -> Arc<ProxyRoute> {
+        let route_cfg = config::Route {
+            id: id.to_string(),
+            uri: uri.map(str::to_string),
+            uris: vec![],
+            methods: vec![],
+            host: None,
+            hosts: hosts.iter().map(|h| h.to_string()).collect(),
+            priority: 0,
+            plugins: HashMap::<String, JsonValue>::new(),
+            upstream: None,
+            upstream_id: None,
+            service_id: None,
+            timeout: None,
+            streaming: false,
+            fallback,
+            shed_priority: Default::default(),
+            enabled: true,
+            labels: Default::default(),
+            plugin_config_id: Default::default(),
+            strip_path_prefix: None,
+            api_group: None,
+        };
+        Arc::new(
+            ProxyRoute::build(route_cfg, &HashMap::new(), &HashMap::new(), &HashMap::new())
+                .unwrap(),
+        )
+    }
+
+    #[test]
+    fn test_port_hosts_and_fallback_routes() {
+        let mut matcher = MatchEntry::default();
+        for route in [
+            test_route("tls", &["api.example.com:8443"], Some("/"), false),
+            test_route("plain", &["api.example.com"], Some("/"), false),
+            test_route("wild-tls", &["*.example.com:8443"], Some("/"), false),
+            test_route("api-fallback", &["api.example.com"], None, true),
+            test_route("fallback", &[], None, true),
+        ] {
+            matcher.insert_route(route).unwrap();
+        }
+
+        let matched = |host, port| {
+            matcher
+                .match_host_port_uri_method(Some(host), port, "/", "GET")
+                .map(|(_, route)| route.inner.id.clone())
+        };
+        assert_eq!(matched("api.example.com", Some(8443)).unwrap(), "tls");
+        assert_eq!(matched("api.example.com", Some(80)).unwrap(), "plain");
+        assert_eq!(matched("api.example.com", None).unwrap(), "plain");
+        assert_eq!(matched("v1.example.com", Some(8443)).unwrap(), "wild-tls");
+        assert!(matched("v1.example.com", Some(80)).is_none());
+
+        let fallback = |host| {
+            matcher
+                .match_fallback_host(Some(host), Some(80), "GET")
+                .map(|(_, route)| route.inner.id.clone())
+        };
+        assert_eq!(fallback("api.example.com").unwrap(), "api-fallback");
+        assert_eq!(fallback("other.example.com").unwrap(), "fallback");
+    }
+
+    #[test]
+    fn virtual_host_service_serves_unmatched_requests_for_its_hosts() {
+        let service = config::Service {
+            id: "web".to_string(),
+            hosts: vec![
+                "www.example.com".to_string(),
+                "shop.example.com".to_string(),
+            ],
+            virtual_host: true,
+            ..Default::default()
+        };
+        let service = ProxyService::build(service, &HashMap::new(), &HashMap::new()).unwrap();
+        let services = HashMap::from([("web".to_string(), Arc::new(service))]);
+        let vhost = ProxyRoute::build(
+            virtual_host_route("web"),
+            &HashMap::new(),
+            &services,
+            &HashMap::new(),
+        )
+        .unwrap();
+
+        let mut matcher = MatchEntry::default();
+        matcher.insert_route(Arc::new(vhost)).unwrap();
+        matcher
+            .insert_route(test_route("shop-404", &["shop.example.com"], None, true))
+            .unwrap();
+        let fallback = |host| {
+            matcher
+                .match_fallback_host(Some(host), None, "GET")
+                .map(|(_, route)| route.inner.id.clone())
+        };
+        assert_eq!(fallback("www.example.com").unwrap(), "services/web");
+        assert_eq!(fallback("shop.example.com").unwrap(), "shop-404");
+        assert!(fallback("api.example.com").is_none());
+    }
+
+    #[test]
+    fn disabled_routes_services_and_plugins_are_skipped() {
+        let service = |id: &str, enabled: bool| {
+            let service = config::Service {
+                id: id.to_string(),
+                plugins: HashMap::from([("echo".to_string(), json!({"body": "service"}))]),
+                enabled,
+                ..Default::default()
+            };
+            let service = ProxyService::build(service, &HashMap::new(), &HashMap::new()).unwrap();
+            (id.to_string(), Arc::new(service))
+        };
+        let services = HashMap::from([service("on", true), service("off", false)]);
+        let route = |id: &str, service_id: &str, enabled: bool| {
+            let mut route_cfg = test_route(id, &[], Some(&format!("/{id}")), false)
+                .inner
+                .clone();
+            route_cfg.service_id = Some(service_id.to_string());
+            route_cfg.enabled = enabled;
+            route_cfg.plugins = HashMap::from([(
+                "echo".to_string(),
+                json!({"body": "route", "_meta": {"disable": true}}),
+            )]);
+            let route =
+                ProxyRoute::build(route_cfg, &HashMap::new(), &services, &HashMap::new()).unwrap();
+            (id.to_string(), Arc::new(route))
+        };
+        let routes = HashMap::from([
+            route("live", "on", true),
+            route("paused", "on", false),
+            route("orphaned", "off", true),
+        ]);
+
+        let matcher = MatchEntry::rebuild(&routes, None).unwrap();
+        let matched = |path| {
+            matcher
+                .match_host_port_uri_method(None, None, path, "GET")
+                .map(|(_, route)| route.inner.id.clone())
+        };
+        assert_eq!(matched("/live").unwrap(), "live");
+        assert!(matched("/paused").is_none());
+        assert!(matched("/orphaned").is_none());
+
+        // The disabled route plugin is not run and still overrides the service's.
+        assert!(routes["live"].plugins.is_empty());
+        assert_eq!(routes["live"].build_plugin_executor().plugins.len(), 0);
+    }
+
+    #[test]
+    fn rebuild_reuses_shards_untouched_by_route_changes() {
+        let routes = |api_uri: &str| {
+            HashMap::from([
+                (
+                    "wild".to_string(),
+                    test_route("wild", &["*.example.com"], Some("/wild"), false),
+                ),
+                (
+                    "api".to_string(),
+                    test_route("api", &["api.example.com"], Some(api_uri), false),
+                ),
+                (
+                    "other".to_string(),
+                    test_route("other", &["other.com"], Some("/"), false),
+                ),
+                ("any".to_string(), test_route("any", &[], Some("/"), false)),
+            ])
+        };
+        let before_routes = routes("/v1");
+        let before = MatchEntry::rebuild(&before_routes, None).unwrap();
+
+        let mut after_routes = routes("/v2");
+        for id in ["wild", "other", "any"] {
+            after_routes.insert(id.to_string(), before_routes[id].clone());
+        }
+        let after = MatchEntry::rebuild(&after_routes, Some((&before, &before_routes))).unwrap();
+
+        let shard = |matcher: &MatchEntry, host: &str| {
+            matcher.host_shards[&MatchEntry::reverse_host(host)].clone()
+        };
+        assert!(!Arc::ptr_eq(
+            &shard(&before, "api.example.com"),
+            &shard(&after, "api.example.com")
+        ));
+        for host in ["*.example.com", "other.com"] {
+            assert!(Arc::ptr_eq(&shard(&before, host), &shard(&after, host)));
+        }
+        assert!(Arc::ptr_eq(&before.non_host_uri, &after.non_host_uri));
+
+        let matched = |host, uri| {
+            after
+                .match_host_uri_method(Some(host), uri, "GET")
+                .map(|(_, route)| route.inner.id.clone())
+        };
+        assert_eq!(matched("api.example.com", "/v2").unwrap(), "api");
+        assert!(matched("api.example.com", "/v1").is_none());
+        // The exact host has its own shard next to the overlapping wildcard.
+        assert_eq!(matched("www.example.com", "/wild").unwrap(), "wild");
+        assert!(matched("www.example.com", "/v2").is_none());
+    }
+
+    #[test]
+    fn match_header_reports_the_matching_step() {
+        let mut matcher = MatchEntry::default();
+        for route in [
+            test_route("users", &["api.example.com"], Some("/users/{id}"), false),
+            test_route("fallback", &[], None, true),
+        ] {
+            matcher.insert_route(route).unwrap();
+        }
+
+        let matched = |path: &str| {
+            let mut request = RequestHeader::build("GET", path.as_bytes(), None).unwrap();
+            request
+                .insert_header("host", "api.example.com:8080")
+                .unwrap();
+            matcher
+                .match_header(&request, Some(8080), false)
+                .map(|(kind, params, route)| (kind, params, route.inner.id.clone()))
+                .unwrap()
+        };
+        let (kind, params, id) = matched("/users/7");
+        assert_eq!((kind, id.as_str()), (MatchKind::Route, "users"));
+        assert_eq!(params, [("id".to_string(), "7".to_string())]);
+        let (kind, _, id) = matched("/orders");
+        assert_eq!((kind, id.as_str()), (MatchKind::Fallback, "fallback"));
+    }
+
     #[test]
     fn apply_route_timeout_none_preserves_peer_timeouts() {
         use pingora_core::upstreams::peer::HttpPeer;
@@ -819,6 +1366,7 @@ mod tests {
             connect: 5,
             read: 5,
             send: 5,
+            total: None,
         };
         apply_route_timeout(Some(&route_timeout), &mut peer);
 
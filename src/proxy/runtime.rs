@@ -11,15 +11,18 @@ use std::{
 
 use arc_swap::ArcSwap;
 use once_cell::sync::Lazy;
+use pingora_http::RequestHeader;
 
 use crate::{
     config,
-    core::{ProxyPluginExecutor, ProxyResult},
+    core::{metrics, ProxyPluginExecutor, ProxyResult},
+    utils::request::get_request_host,
 };
 
 use super::{
     control_plane::CandidateSnapshot,
-    global_rule::{build_global_plugin_executor, ProxyGlobalRule},
+    global_rule::{build_global_plugin_executor, ProxyGlobalRule, ScopedGlobalRules},
+    ip_list::ProxyIpList,
     route::{MatchEntry as RouteMatcher, ProxyRoute},
     service::ProxyService,
     ssl::{MatchEntry as SslMatcher, ProxySSL},
@@ -40,8 +43,11 @@ pub struct RuntimeSnapshot {
     pub services: Arc<HashMap<String, Arc<ProxyService>>>,
     pub global_rules: Arc<HashMap<String, Arc<ProxyGlobalRule>>>,
     pub ssls: Arc<HashMap<String, Arc<ProxySSL>>>,
+    pub ip_lists: Arc<HashMap<String, Arc<ProxyIpList>>>,
     pub route_matcher: Arc<RouteMatcher>,
+    /// Plugins of the global rules that apply to every request.
     pub global_plugins: Arc<ProxyPluginExecutor>,
+    pub scoped_global_rules: Arc<ScopedGlobalRules>,
     pub ssl_matcher: Arc<SslMatcher>,
 }
 
@@ -54,8 +60,10 @@ impl RuntimeSnapshot {
             services: Arc::new(HashMap::new()),
             global_rules: Arc::new(HashMap::new()),
             ssls: Arc::new(HashMap::new()),
+            ip_lists: Arc::new(HashMap::new()),
             route_matcher: Arc::new(RouteMatcher::default()),
             global_plugins: ProxyPluginExecutor::default_shared(),
+            scoped_global_rules: Arc::new(ScopedGlobalRules::default()),
             ssl_matcher: Arc::new(SslMatcher::default()),
         }
     }
@@ -67,8 +75,17 @@ impl RuntimeSnapshot {
         let services = Arc::new(candidate.services);
         let global_rules = Arc::new(candidate.global_rules);
         let ssls = Arc::new(candidate.ssls);
-        let route_matcher = Arc::new(RouteMatcher::build(&routes)?);
+        let ip_lists = Arc::new(candidate.ip_lists);
+        let route_matcher = {
+            let _timer = metrics::ROUTE_MATCHER_REBUILD_DURATION.start_timer();
+            let previous = RUNTIME.load();
+            Arc::new(RouteMatcher::rebuild(
+                &routes,
+                Some((&previous.route_matcher, &previous.routes)),
+            )?)
+        };
         let global_plugins = build_global_plugin_executor(&global_rules);
+        let scoped_global_rules = Arc::new(ScopedGlobalRules::build(&global_rules));
         let ssl_matcher = Arc::new(SslMatcher::build(&ssls)?);
 
         Ok(Self {
@@ -78,11 +95,28 @@ impl RuntimeSnapshot {
             services,
             global_rules,
             ssls,
+            ip_lists,
             route_matcher,
             global_plugins,
+            scoped_global_rules,
             ssl_matcher,
         })
     }
+
+    /// Global plugins of `request` matched to `route`, including the scoped global
+    /// rules that select it.
+    pub fn global_plugins_for(
+        &self,
+        route: Option<&ProxyRoute>,
+        request: &RequestHeader,
+    ) -> Arc<ProxyPluginExecutor> {
+        self.scoped_global_rules.executor_for(
+            route,
+            get_request_host(request),
+            request.uri.path(),
+            &self.global_plugins,
+        )
+    }
 }
 
 fn health_check_fingerprint(upstream: &config::Upstream) -> HealthCheckFingerprint {
@@ -235,8 +269,9 @@ impl RuntimeStore {
     ///
     /// Order:
     /// 1. Start new / replacement checks without stopping displaced ones
-    /// 2. Store the runtime snapshot
-    /// 3. Discard displaced checks and stop removed keys
+    /// 2. Install node sets staged on load balancers shared with the previous snapshot
+    /// 3. Store the runtime snapshot
+    /// 4. Discard displaced checks and stop removed keys
     ///
     /// Registration is infallible (Candidate build owns fallible work). Displaced
     /// checks keep running until after snapshot commit.
@@ -296,9 +331,13 @@ impl RuntimeStore {
             }
         }
 
+        for upstream in snapshot.upstreams.values() {
+            upstream.commit_nodes();
+        }
         let snapshot = Arc::new(snapshot);
-        self.current.store(snapshot.clone());
+        let previous = self.current.swap(snapshot.clone());
         crate::core::status::set_published_revision(snapshot.revision);
+        super::config_webhook::notify(&previous, &snapshot);
 
         for d in displaced {
             d.discard();
@@ -355,6 +394,11 @@ mod tests {
             pass_host: UpstreamPassHost::PASS,
             upstream_host: None,
             tls: None,
+            zone_aware: None,
+            discovery_type: None,
+            priorities: HashMap::new(),
+            sticky: None,
+            labels: Default::default(),
         }
     }
 
@@ -422,6 +466,14 @@ mod tests {
                 upstream_id: Some("u1".into()),
                 service_id: None,
                 timeout: None,
+                streaming: false,
+                fallback: false,
+                shed_priority: Default::default(),
+                enabled: true,
+                labels: Default::default(),
+                plugin_config_id: Default::default(),
+                strip_path_prefix: None,
+                api_group: None,
             },
         );
         let snap2 = RuntimeSnapshot::compile(CandidateSnapshot::build(set).unwrap(), 2).unwrap();
@@ -432,6 +484,116 @@ mod tests {
         assert_eq!(gen1, gen2);
     }
 
+    #[test]
+    fn scoped_global_rules_apply_only_to_selected_requests() {
+        use crate::proxy::control_plane::{CandidateSnapshot, ResourceConfigSet};
+
+        let mut set = ResourceConfigSet::default();
+        set.upstreams
+            .insert("u1".into(), sample_upstream("u1", &[("10.0.0.1:80", 1)]));
+        for (id, labels) in [("r1", vec![("env", "prod")]), ("r2", vec![])] {
+            set.routes.insert(
+                id.into(),
+                crate::config::Route {
+                    id: id.into(),
+                    uri: Some(format!("/{id}")),
+                    uris: vec![],
+                    methods: vec![],
+                    host: None,
+                    hosts: vec![],
+                    priority: 0,
+                    plugins: Default::default(),
+                    upstream: None,
+                    upstream_id: Some("u1".into()),
+                    service_id: None,
+                    timeout: None,
+                    streaming: false,
+                    fallback: false,
+                    shed_priority: Default::default(),
+                    enabled: true,
+                    labels: labels
+                        .into_iter()
+                        .map(|(k, v)| (k.to_string(), v.to_string()))
+                        .collect(),
+                    plugin_config_id: Default::default(),
+                    strip_path_prefix: None,
+                    api_group: None,
+                },
+            );
+        }
+        for (id, plugin, route_labels) in [
+            ("g1", "request-id", vec![]),
+            ("g2", "cors", vec![("env", "prod")]),
+        ] {
+            set.global_rules.insert(
+                id.into(),
+                config::GlobalRule {
+                    id: id.into(),
+                    plugins: HashMap::from([(plugin.to_string(), serde_json::json!({}))]),
+                    route_labels: route_labels
+                        .into_iter()
+                        .map(|(k, v)| (k.to_string(), v.to_string()))
+                        .collect(),
+                    ..Default::default()
+                },
+            );
+        }
+        set.global_rules.insert(
+            "g3".into(),
+            config::GlobalRule {
+                id: "g3".into(),
+                plugins: HashMap::from([(
+                    "ip-restriction".to_string(),
+                    serde_json::json!({"whitelist": ["10.0.0.0/8"]}),
+                )]),
+                hosts: vec!["*.internal".into()],
+                uri_prefixes: vec!["/admin/".into()],
+                ..Default::default()
+            },
+        );
+
+        let snapshot = RuntimeSnapshot::compile(CandidateSnapshot::build(set).unwrap(), 1).unwrap();
+        let names = |executor: &ProxyPluginExecutor| -> Vec<String> {
+            executor
+                .plugins
+                .iter()
+                .map(|p| p.name().to_string())
+                .collect()
+        };
+        assert_eq!(names(&snapshot.global_plugins), ["request-id"]);
+
+        let request = |path: &str, host: &str| {
+            let mut header = RequestHeader::build("GET", path.as_bytes(), None).unwrap();
+            header.insert_header("Host", host).unwrap();
+            header
+        };
+        let r1 = snapshot.routes.get("r1").map(|r| r.as_ref());
+        let r2 = snapshot.routes.get("r2").map(|r| r.as_ref());
+
+        let labelled = snapshot.global_plugins_for(r1, &request("/r1", "a.example.com"));
+        assert_eq!(names(&labelled), ["request-id", "cors"]);
+        assert!(Arc::ptr_eq(
+            &labelled,
+            &snapshot.global_plugins_for(r1, &request("/r1", "b.example.com"))
+        ));
+        assert!(Arc::ptr_eq(
+            &snapshot.global_plugins_for(r2, &request("/r2", "a.example.com")),
+            &snapshot.global_plugins
+        ));
+
+        // g3 needs both an admin path and an internal host.
+        let admin = snapshot.global_plugins_for(None, &request("/admin/x", "ops.internal"));
+        assert_eq!(names(&admin), ["request-id", "ip-restriction"]);
+        assert!(Arc::ptr_eq(
+            &snapshot.global_plugins_for(None, &request("/admin/x", "ops.example.com")),
+            &snapshot.global_plugins
+        ));
+        assert!(Arc::ptr_eq(
+            &snapshot.global_plugins_for(None, &request("/other", "ops.internal")),
+            &snapshot.global_plugins
+        ));
+    }
+
     #[test]
     fn route_only_update_reuses_upstream_arc_and_keeps_backends_selectable() {
         let _guard = RUNTIME_TEST_LOCK.lock().unwrap_or_else(|e| e.into_inner());
@@ -467,6 +629,14 @@ mod tests {
                 upstream_id: Some("u1".into()),
                 service_id: None,
                 timeout: None,
+                streaming: false,
+                fallback: false,
+                shed_priority: Default::default(),
+                enabled: true,
+                labels: Default::default(),
+                plugin_config_id: Default::default(),
+                strip_path_prefix: None,
+                api_group: None,
             },
         );
         RUNTIME
@@ -484,7 +654,79 @@ mod tests {
     }
 
     #[test]
-    fn weight_only_upstream_change_replaces_health_check_generation() {
+    fn service_change_rebuilds_only_routes_bound_to_it() {
+        let _guard = RUNTIME_TEST_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+        use crate::core::RouteContext;
+        use crate::proxy::control_plane::{CandidateSnapshot, ResourceConfigSet};
+
+        let service = |id: &str, plugin: &str| config::Service {
+            id: id.into(),
+            plugins: HashMap::from([(plugin.to_string(), serde_json::json!({}))]),
+            upstream: None,
+            upstream_id: Some("u1".into()),
+            hosts: vec![],
+            virtual_host: false,
+            enabled: true,
+            labels: Default::default(),
+            blue_green: None,
+        };
+        let mut set = ResourceConfigSet::default();
+        set.upstreams
+            .insert("u1".into(), sample_upstream("u1", &[("10.0.0.1:80", 1)]));
+        set.services
+            .insert("s1".into(), service("s1", "request-id"));
+        set.services.insert("s2".into(), service("s2", "cors"));
+        for (id, service_id) in [("r1", Some("s1")), ("r2", Some("s2")), ("r3", None)] {
+            set.routes.insert(
+                id.into(),
+                crate::config::Route {
+                    id: id.into(),
+                    uri: Some(format!("/{id}")),
+                    uris: vec![],
+                    methods: vec![],
+                    host: None,
+                    hosts: vec![],
+                    priority: 0,
+                    plugins: Default::default(),
+                    upstream: None,
+                    upstream_id: service_id.is_none().then(|| "u1".to_string()),
+                    service_id: service_id.map(str::to_string),
+                    timeout: None,
+                    streaming: false,
+                    fallback: false,
+                    shed_priority: Default::default(),
+                    enabled: true,
+                    labels: Default::default(),
+                    plugin_config_id: Default::default(),
+                    strip_path_prefix: None,
+                    api_group: None,
+                },
+            );
+        }
+        RUNTIME
+            .publish(
+                RuntimeSnapshot::compile(CandidateSnapshot::build(set.clone()).unwrap(), 1)
+                    .unwrap(),
+            )
+            .unwrap();
+        let before = RUNTIME.load();
+
+        set.services.insert("s1".into(), service("s1", "cors"));
+        RUNTIME
+            .publish(RuntimeSnapshot::compile(CandidateSnapshot::build(set).unwrap(), 2).unwrap())
+            .unwrap();
+        let after = RUNTIME.load();
+
+        let r1 = after.routes["r1"].build_plugin_executor();
+        assert!(r1.has_plugin("cors") && !r1.has_plugin("request-id"));
+        assert!(!Arc::ptr_eq(&before.routes["r1"], &after.routes["r1"]));
+        assert!(Arc::ptr_eq(&before.routes["r2"], &after.routes["r2"]));
+        assert!(Arc::ptr_eq(&before.routes["r3"], &after.routes["r3"]));
+        assert!(Arc::ptr_eq(&before.services["s2"], &after.services["s2"]));
+    }
+
+    #[test]
+    fn weight_only_upstream_change_keeps_load_balancer_and_health_check_generation() {
         let _guard = RUNTIME_TEST_LOCK.lock().unwrap_or_else(|e| e.into_inner());
         use crate::proxy::control_plane::{CandidateSnapshot, ResourceConfigSet};
 
@@ -500,26 +742,59 @@ mod tests {
         let gen1 = RUNTIME.health_check_generation("upstream/u1").unwrap();
         let before = RUNTIME.load().upstreams.get("u1").cloned().unwrap();
 
-        // Weight is ignored by HC fingerprint but still rebuilds the LB.
         set.upstreams
             .insert("u1".into(), sample_upstream("u1", &[("10.0.0.1:80", 99)]));
-        RUNTIME
-            .publish(RuntimeSnapshot::compile(CandidateSnapshot::build(set).unwrap(), 2).unwrap())
-            .unwrap();
+        let candidate =
+            RuntimeSnapshot::compile(CandidateSnapshot::build(set).unwrap(), 2).unwrap();
+        assert_eq!(
+            before.backend_health()[0].weight,
+            1,
+            "staged nodes must not reach the live load balancer before publish"
+        );
+        RUNTIME.publish(candidate).unwrap();
         let after = RUNTIME.load().upstreams.get("u1").cloned().unwrap();
         let gen2 = RUNTIME.health_check_generation("upstream/u1").unwrap();
         assert!(!Arc::ptr_eq(&before, &after));
-        assert_eq!(
-            fingerprint_upstream_for_health_check(&before.inner),
-            fingerprint_upstream_for_health_check(&after.inner)
-        );
-        assert_ne!(
-            gen1, gen2,
-            "new LB Arc must get its own HC registration even when fingerprint matches"
-        );
+        assert!(Arc::ptr_eq(
+            &before.health_check_service(),
+            &after.health_check_service()
+        ));
+        assert_eq!(gen1, gen2, "the shared LB keeps its HC registration");
+        assert_eq!(after.backend_health()[0].weight, 99);
         assert!(after.select_backend_for_test().is_some());
     }
 
+    #[test]
+    fn non_node_upstream_change_rebuilds_load_balancer() {
+        let _guard = RUNTIME_TEST_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+        use crate::proxy::control_plane::{CandidateSnapshot, ResourceConfigSet};
+
+        let mut set = ResourceConfigSet::default();
+        set.upstreams
+            .insert("u1".into(), sample_upstream("u1", &[("10.0.0.1:80", 1)]));
+        RUNTIME
+            .publish(
+                RuntimeSnapshot::compile(CandidateSnapshot::build(set.clone()).unwrap(), 1)
+                    .unwrap(),
+            )
+            .unwrap();
+        let before = RUNTIME.load().upstreams.get("u1").cloned().unwrap();
+
+        let mut upstream = sample_upstream("u1", &[("10.0.0.2:80", 1)]);
+        upstream.r#type = SelectionType::Random;
+        set.upstreams.insert("u1".into(), upstream);
+        RUNTIME
+            .publish(RuntimeSnapshot::compile(CandidateSnapshot::build(set).unwrap(), 2).unwrap())
+            .unwrap();
+        let after = RUNTIME.load().upstreams.get("u1").cloned().unwrap();
+        assert!(!Arc::ptr_eq(
+            &before.health_check_service(),
+            &after.health_check_service()
+        ));
+        assert_eq!(before.backend_addresses(), vec!["10.0.0.1:80"]);
+        assert_eq!(after.backend_addresses(), vec!["10.0.0.2:80"]);
+    }
+
     #[test]
     fn upstream_node_change_replaces_health_check_generation() {
         let _guard = RUNTIME_TEST_LOCK.lock().unwrap_or_else(|e| e.into_inner());
@@ -1,22 +1,40 @@
-use std::{collections::HashMap, sync::Arc};
+use std::{
+    collections::HashMap,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+};
+
+use pingora_http::RequestHeader;
+use rand::Rng;
 
 use crate::{
-    config::{self, Identifiable},
+    config::{self, BlueGreenColor, Identifiable},
     core::{
         sort_plugins_by_priority_desc, ErrorContext, ProxyError, ProxyPlugin, ProxyResult,
         UpstreamSelector,
     },
-    plugins::build_plugin_with_upstreams,
+    plugins::{build_plugin_with_upstreams, is_disabled},
+    utils::request::get_cookie_value,
 };
 
 use super::upstream::{inline_key, PreparedUpstreams, ProxyUpstream};
 
+/// Source of [`ProxyService::generation`]; every build takes the next value.
+static NEXT_GENERATION: AtomicU64 = AtomicU64::new(1);
+
 /// Represents a proxy service that manages upstreams.
 pub struct ProxyService {
     pub inner: config::Service,
     pub upstream: Option<Arc<dyn UpstreamSelector>>,
     pub plugins: Vec<Arc<dyn ProxyPlugin>>,
     pub inline_upstream: Option<Arc<ProxyUpstream>>,
+    /// Blue/green switch; `upstream` is then its active side.
+    pub blue_green: Option<Arc<BlueGreenSwitch>>,
+    /// Unique per build, so routes bound to an older build of this service can tell
+    /// that their merged plugin executor is stale.
+    pub generation: u64,
 }
 
 impl Identifiable for ProxyService {
@@ -69,16 +87,28 @@ impl ProxyService {
         } else {
             None
         };
+        let blue_green = service
+            .blue_green
+            .as_ref()
+            .map(|config| BlueGreenSwitch::build(&service.id, config, upstreams).map(Arc::new))
+            .transpose()?;
+        let upstream = match &blue_green {
+            Some(switch) => Some(switch.side(switch.config.active)),
+            None => upstream,
+        };
 
         let mut proxy_service = ProxyService {
             inner: service.clone(),
             upstream,
             plugins: Vec::with_capacity(service.plugins.len()),
             inline_upstream,
+            blue_green,
+            generation: NEXT_GENERATION.fetch_add(1, Ordering::Relaxed),
         };
 
         // Load configured plugins
         for (name, value) in service.plugins {
+            let disabled = is_disabled(&value);
             let plugin = build_plugin_with_upstreams(
                 &name,
                 value,
@@ -92,7 +122,9 @@ impl ProxyService {
                     name, service.id, e
                 ))
             })?;
-            proxy_service.plugins.push(plugin);
+            if !disabled {
+                proxy_service.plugins.push(plugin);
+            }
         }
 
         // Pre-sort plugins once at build-time to avoid per-request sorting in route+service merges.
@@ -106,3 +138,75 @@ impl ProxyService {
         self.upstream.clone()
     }
 }
+
+/// Both sides of a service's `blue_green` setting, resolved to named upstreams.
+pub struct BlueGreenSwitch {
+    config: config::BlueGreen,
+    blue: Arc<dyn UpstreamSelector>,
+    green: Arc<dyn UpstreamSelector>,
+}
+
+impl BlueGreenSwitch {
+    fn build(
+        service_id: &str,
+        config: &config::BlueGreen,
+        upstreams: &HashMap<String, Arc<ProxyUpstream>>,
+    ) -> ProxyResult<Self> {
+        let side = |color: BlueGreenColor| {
+            let id = config.upstream_id(color);
+            upstreams
+                .get(id)
+                .cloned()
+                .map(|upstream| upstream as Arc<dyn UpstreamSelector>)
+                .ok_or_else(|| {
+                    ProxyError::Configuration(format!(
+                        "Service '{service_id}' references missing {} upstream '{id}'",
+                        color.as_str()
+                    ))
+                })
+        };
+        Ok(Self {
+            blue: side(BlueGreenColor::Blue)?,
+            green: side(BlueGreenColor::Green)?,
+            config: config.clone(),
+        })
+    }
+
+    fn side(&self, color: BlueGreenColor) -> Arc<dyn UpstreamSelector> {
+        match color {
+            BlueGreenColor::Blue => self.blue.clone(),
+            BlueGreenColor::Green => self.green.clone(),
+        }
+    }
+
+    /// Side serving `req`: the one named by the configured header, then cookie,
+    /// otherwise `active` with `weight` percent going to the other side.
+    pub fn color_for(&self, req: &RequestHeader) -> BlueGreenColor {
+        let header = self.config.header.as_deref().and_then(|name| {
+            req.headers
+                .get(name)
+                .and_then(|value| value.to_str().ok())
+                .and_then(BlueGreenColor::parse)
+        });
+        let cookie = || {
+            self.config
+                .cookie
+                .as_deref()
+                .and_then(|name| get_cookie_value(req, name))
+                .and_then(BlueGreenColor::parse)
+        };
+        header.or_else(cookie).unwrap_or_else(|| {
+            let active = self.config.active;
+            if self.config.weight > 0 && rand::thread_rng().gen_range(0..100) < self.config.weight {
+                active.other()
+            } else {
+                active
+            }
+        })
+    }
+
+    /// Upstream serving `req`.
+    pub fn select(&self, req: &RequestHeader) -> Arc<dyn UpstreamSelector> {
+        self.side(self.color_for(req))
+    }
+}
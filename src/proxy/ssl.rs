@@ -1,11 +1,13 @@
 use std::sync::Arc;
 
 use async_trait::async_trait;
+use base64::prelude::*;
 use log;
 use matchit::{InsertError, Router as MatchRouter};
+use openssl::pkcs12::Pkcs12;
 use pingora::listeners::TlsAccept;
 use pingora::tls::ext;
-use pingora::tls::pkey::PKey;
+use pingora::tls::pkey::{PKey, Private};
 use pingora::tls::ssl::{NameType, SslRef};
 use pingora::tls::x509::X509;
 use pingora_error::Result;
@@ -13,6 +15,7 @@ use pingora_error::Result;
 use crate::{
     config::{self, Identifiable},
     core::ProxyError,
+    utils::secret,
 };
 
 use super::runtime::RUNTIME;
@@ -24,19 +27,32 @@ pub struct ProxySSL {
     pub inner: config::SSL,
     // Store parsed cert and key, handle parsing errors during creation/update
     parsed_cert: X509,
-    parsed_key: PKey<pingora::tls::pkey::Private>,
+    parsed_key: PKey<Private>,
+    /// Intermediate certificates sent after `parsed_cert`.
+    parsed_chain: Vec<X509>,
 }
 
 impl TryFrom<config::SSL> for ProxySSL {
     type Error = ProxyError;
 
     fn try_from(value: config::SSL) -> std::result::Result<Self, Self::Error> {
-        let parsed_cert = X509::from_pem(value.cert.as_bytes()).map_err(|e| {
-            ProxyError::Configuration(format!("Failed to parse cert for '{}': {e}", value.id))
-        })?;
-        let parsed_key = PKey::private_key_from_pem(value.key.as_bytes()).map_err(|e| {
-            ProxyError::Configuration(format!("Failed to parse key for '{}': {e}", value.id))
-        })?;
+        let passphrase = value
+            .passphrase
+            .as_deref()
+            .map(secret::resolve)
+            .transpose()
+            .map_err(|e| {
+                ProxyError::Configuration(format!(
+                    "Failed to read passphrase for '{}': {e}",
+                    value.id
+                ))
+            })?
+            .unwrap_or_default();
+        let (parsed_cert, parsed_key, parsed_chain) = match &value.pkcs12 {
+            Some(bundle) => parse_pkcs12(bundle, &passphrase),
+            None => parse_pem(&value.cert, &value.key, &passphrase),
+        }
+        .map_err(|e| ProxyError::Configuration(format!("{e} for '{}'", value.id)))?;
 
         if !parsed_cert
             .public_key()
@@ -58,10 +74,50 @@ impl TryFrom<config::SSL> for ProxySSL {
             inner: value,
             parsed_cert,
             parsed_key,
+            parsed_chain,
         })
     }
 }
 
+type Material = (X509, PKey<Private>, Vec<X509>);
+
+fn parse_pem(cert: &str, key: &str, passphrase: &str) -> std::result::Result<Material, String> {
+    let mut certs = X509::stack_from_pem(cert.as_bytes())
+        .map_err(|e| format!("Failed to parse cert: {e}"))?
+        .into_iter();
+    let leaf = certs
+        .next()
+        .ok_or_else(|| "Failed to parse cert: no certificate found".to_string())?;
+    // A callback instead of OpenSSL's default, which would prompt on the terminal for
+    // an encrypted key without passphrase.
+    let key = PKey::private_key_from_pem_callback(key.as_bytes(), |buf| {
+        let len = passphrase.len().min(buf.len());
+        buf[..len].copy_from_slice(&passphrase.as_bytes()[..len]);
+        Ok(len)
+    })
+    .map_err(|e| format!("Failed to parse key (wrong or missing passphrase?): {e}"))?;
+    Ok((leaf, key, certs.collect()))
+}
+
+fn parse_pkcs12(bundle: &str, passphrase: &str) -> std::result::Result<Material, String> {
+    let der = BASE64_STANDARD
+        .decode(bundle.trim())
+        .map_err(|e| format!("PKCS#12 bundle is not valid base64: {e}"))?;
+    let parsed = Pkcs12::from_der(&der)
+        .and_then(|pkcs12| pkcs12.parse2(passphrase))
+        .map_err(|e| format!("Failed to open PKCS#12 bundle (wrong passphrase?): {e}"))?;
+    let cert = parsed
+        .cert
+        .ok_or_else(|| "PKCS#12 bundle has no certificate".to_string())?;
+    let key = parsed
+        .pkey
+        .ok_or_else(|| "PKCS#12 bundle has no private key".to_string())?;
+    let chain = parsed
+        .ca
+        .map_or_else(Vec::new, |ca| ca.into_iter().collect());
+    Ok((cert, key, chain))
+}
+
 impl Identifiable for ProxySSL {
     fn id(&self) -> &str {
         &self.inner.id
@@ -77,6 +133,11 @@ impl ProxySSL {
     fn get_snis(&self) -> &[String] {
         &self.inner.snis
     }
+
+    /// The leaf certificate served for the SSL's SNIs.
+    pub fn certificate(&self) -> &X509 {
+        &self.parsed_cert
+    }
 }
 
 #[derive(Default)]
@@ -165,6 +226,7 @@ impl DynamicCert {
                 ))
             })?,
             snis: Vec::new(),
+            ..Default::default()
         };
 
         let proxy_ssl = ProxySSL::try_from(ssl_config)?;
@@ -193,6 +255,11 @@ impl TlsAccept for DynamicCert {
         if let Err(e) = ext::ssl_use_private_key(ssl, &proxy_ssl.parsed_key) {
             log::error!("Failed to use private key: {e}");
         }
+        for cert in &proxy_ssl.parsed_chain {
+            if let Err(e) = ext::ssl_add_chain_cert(ssl, cert) {
+                log::error!("Failed to add chain certificate: {e}");
+            }
+        }
     }
 }
 
@@ -205,6 +272,8 @@ mod tests {
     const CERT: &str = include_str!("testdata/example.crt");
     const KEY: &str = include_str!("testdata/example.key");
     const OTHER_KEY: &str = include_str!("testdata/other.key");
+    const ENCRYPTED_KEY: &str = include_str!("testdata/example.enc.key");
+    const PKCS12: &str = include_str!("testdata/example.p12.b64");
 
     #[test]
     fn sni_key_normalization_lowercases() {
@@ -222,6 +291,7 @@ mod tests {
             cert: "not-a-cert".into(),
             key: "not-a-key".into(),
             snis: vec!["example.com".into()],
+            ..Default::default()
         };
         assert!(ProxySSL::try_from(ssl).is_err());
     }
@@ -233,6 +303,7 @@ mod tests {
             cert: CERT.into(),
             key: OTHER_KEY.into(),
             snis: vec!["example.com".into()],
+            ..Default::default()
         };
         match ProxySSL::try_from(ssl) {
             Err(e) => assert!(e.to_string().contains("do not match"), "{e}"),
@@ -247,6 +318,7 @@ mod tests {
             cert: CERT.into(),
             key: KEY.into(),
             snis: vec!["Example.COM".into()],
+            ..Default::default()
         };
         let proxy = Arc::new(ProxySSL::try_from(ssl).unwrap());
         let mut matcher = MatchEntry::default();
@@ -255,4 +327,44 @@ mod tests {
         assert!(matcher.match_sni("EXAMPLE.COM").is_some());
         assert!(matcher.match_sni("other.com").is_none());
     }
+
+    #[test]
+    fn encrypted_key_needs_its_passphrase() {
+        let ssl = |passphrase: Option<&str>| SSL {
+            id: "enc".into(),
+            cert: CERT.into(),
+            key: ENCRYPTED_KEY.into(),
+            passphrase: passphrase.map(Into::into),
+            snis: vec!["example.com".into()],
+            ..Default::default()
+        };
+        assert!(ProxySSL::try_from(ssl(None)).is_err());
+        assert!(ProxySSL::try_from(ssl(Some("wrong"))).is_err());
+        assert!(ProxySSL::try_from(ssl(Some("key-pass"))).is_ok());
+        match ProxySSL::try_from(ssl(Some("$env://PINGSIX_TEST_UNSET_PASSPHRASE"))) {
+            Err(e) => assert!(e.to_string().contains("is not set"), "{e}"),
+            Ok(_) => panic!("expected unset secret error"),
+        }
+    }
+
+    #[test]
+    fn pkcs12_bundle_is_opened_with_its_passphrase() {
+        let ssl = |passphrase: &str| SSL {
+            id: "p12".into(),
+            pkcs12: Some(PKCS12.into()),
+            passphrase: Some(passphrase.into()),
+            snis: vec!["example.com".into()],
+            ..Default::default()
+        };
+        let proxy = ProxySSL::try_from(ssl("bundle-pass")).unwrap();
+        assert_eq!(
+            proxy.parsed_cert.to_der().unwrap(),
+            X509::from_pem(CERT.as_bytes()).unwrap().to_der().unwrap()
+        );
+        assert!(ProxySSL::try_from(ssl("wrong")).is_err());
+
+        let mut garbled = ssl("bundle-pass");
+        garbled.pkcs12 = Some("not base64!".into());
+        assert!(ProxySSL::try_from(garbled).is_err());
+    }
 }
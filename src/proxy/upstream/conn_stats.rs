@@ -0,0 +1,149 @@
+//! Upstream connection reuse statistics.
+//!
+//! Each proxied request either reuses a pooled keepalive connection or opens a new one.
+//! Pingora pools connections per peer including its TLS SNI, so reuse is counted per
+//! upstream and SNI. New connections also record how long connecting took (TCP and,
+//! for TLS peers, the handshake), measured from peer selection.
+
+use std::time::Duration;
+
+use dashmap::DashMap;
+use once_cell::sync::Lazy;
+use prometheus::{register_histogram_vec, register_int_counter_vec, HistogramVec, IntCounterVec};
+use serde::Serialize;
+
+static UPSTREAM_CONNECTIONS: Lazy<IntCounterVec> = Lazy::new(|| {
+    register_int_counter_vec!(
+        "pingsix_upstream_connections_total",
+        "Upstream connections used by proxied requests, new or reused from the pool",
+        &["upstream", "sni", "outcome"]
+    )
+    .expect("upstream connection metric registration must succeed")
+});
+
+static UPSTREAM_CONNECT_DURATION: Lazy<HistogramVec> = Lazy::new(|| {
+    register_histogram_vec!(
+        "pingsix_upstream_connect_duration_seconds",
+        "Time to establish new upstream connections, including the TLS handshake",
+        &["upstream", "sni"],
+        vec![0.001, 0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5]
+    )
+    .expect("upstream connect duration metric registration must succeed")
+});
+
+/// Counters by `(upstream, sni)`, for the status API.
+static STATS: Lazy<DashMap<(String, String), ConnStats>> = Lazy::new(DashMap::new);
+
+#[derive(Default)]
+struct ConnStats {
+    new: u64,
+    reused: u64,
+    connect_total: Duration,
+    connect_max: Duration,
+}
+
+/// Reuse statistics of one upstream and SNI.
+#[derive(Debug, Serialize)]
+pub struct ConnReuse {
+    pub upstream: String,
+    /// Empty for plaintext peers.
+    pub sni: String,
+    pub new: u64,
+    pub reused: u64,
+    /// Share of requests served on a reused connection.
+    pub reuse_ratio: f64,
+    /// Mean and maximum time to establish a new connection.
+    pub avg_connect_ms: f64,
+    pub max_connect_ms: f64,
+}
+
+/// Record a request's upstream connection. `connect` is the time taken to establish a
+/// new connection, when known.
+pub fn record(upstream: &str, sni: &str, reused: bool, connect: Option<Duration>) {
+    let outcome = if reused { "reused" } else { "new" };
+    UPSTREAM_CONNECTIONS
+        .with_label_values(&[upstream, sni, outcome])
+        .inc();
+
+    let mut stats = STATS
+        .entry((upstream.to_string(), sni.to_string()))
+        .or_default();
+    if reused {
+        stats.reused += 1;
+        return;
+    }
+    stats.new += 1;
+    if let Some(connect) = connect {
+        UPSTREAM_CONNECT_DURATION
+            .with_label_values(&[upstream, sni])
+            .observe(connect.as_secs_f64());
+        stats.connect_total += connect;
+        stats.connect_max = stats.connect_max.max(connect);
+    }
+}
+
+/// Statistics of every upstream and SNI seen since startup, sorted.
+pub fn snapshot() -> Vec<ConnReuse> {
+    let mut snapshot: Vec<ConnReuse> = STATS
+        .iter()
+        .map(|entry| {
+            let ((upstream, sni), stats) = entry.pair();
+            let total = stats.new + stats.reused;
+            ConnReuse {
+                upstream: upstream.clone(),
+                sni: sni.clone(),
+                new: stats.new,
+                reused: stats.reused,
+                reuse_ratio: if total == 0 {
+                    0.0
+                } else {
+                    stats.reused as f64 / total as f64
+                },
+                avg_connect_ms: if stats.new == 0 {
+                    0.0
+                } else {
+                    stats.connect_total.as_secs_f64() * 1000.0 / stats.new as f64
+                },
+                max_connect_ms: stats.connect_max.as_secs_f64() * 1000.0,
+            }
+        })
+        .collect();
+    snapshot.sort_by(|a, b| (&a.upstream, &a.sni).cmp(&(&b.upstream, &b.sni)));
+    snapshot
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reuse_is_counted_per_upstream_and_sni() {
+        record(
+            "conn-stats-test",
+            "a.example.com",
+            false,
+            Some(Duration::from_millis(10)),
+        );
+        record(
+            "conn-stats-test",
+            "a.example.com",
+            false,
+            Some(Duration::from_millis(30)),
+        );
+        record("conn-stats-test", "a.example.com", true, None);
+        record("conn-stats-test", "a.example.com", true, None);
+        record("conn-stats-test", "b.example.com", true, None);
+
+        let stats: Vec<ConnReuse> = snapshot()
+            .into_iter()
+            .filter(|s| s.upstream == "conn-stats-test")
+            .collect();
+        assert_eq!(stats.len(), 2);
+        assert_eq!((stats[0].new, stats[0].reused), (2, 2));
+        assert_eq!(stats[0].reuse_ratio, 0.5);
+        assert!((stats[0].avg_connect_ms - 20.0).abs() < 1e-6);
+        assert!((stats[0].max_connect_ms - 30.0).abs() < 1e-6);
+        assert_eq!(stats[1].sni, "b.example.com");
+        assert_eq!(stats[1].reuse_ratio, 1.0);
+    }
+}
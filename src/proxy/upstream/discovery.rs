@@ -5,9 +5,15 @@ use std::{
     time::Duration,
 };
 
+use arc_swap::ArcSwap;
 use async_trait::async_trait;
 use futures::{future::join_all, FutureExt};
-use hickory_resolver::TokioResolver;
+use hickory_resolver::{
+    config::{LookupIpStrategy, NameServerConfig, ResolverConfig},
+    name_server::TokioConnectionProvider,
+    proto::xfer::Protocol,
+    TokioResolver,
+};
 use once_cell::sync::OnceCell;
 use pingora::{protocols::ALPN, upstreams::peer::HttpPeer};
 use pingora_core::utils::tls::CertKey;
@@ -19,34 +25,107 @@ use pingora_load_balancing::{
 use regex::Regex;
 
 use crate::{
-    config::{self, Upstream, UpstreamPassHost, UpstreamScheme, UpstreamTls},
-    core::{ProxyError, ProxyResult},
+    config::{
+        self, DnsIpPreference, Upstream, UpstreamDiscoveryType, UpstreamPassHost, UpstreamScheme,
+        UpstreamTls,
+    },
+    core::{memory, ProxyError, ProxyResult},
+};
+
+use super::{
+    dns_cache::{DnsCache, SrvTarget},
+    drain,
 };
 
-static GLOBAL_RESOLVER: OnceCell<Arc<TokioResolver>> = OnceCell::new();
+static GLOBAL_DNS_CACHE: OnceCell<Arc<DnsCache>> = OnceCell::new();
 
-fn get_global_resolver() -> ProxyResult<Arc<TokioResolver>> {
-    GLOBAL_RESOLVER
+/// The DNS cache shared by every discovery, in front of the resolver built from
+/// `pingsix.dns`.
+fn get_dns_cache() -> ProxyResult<Arc<DnsCache>> {
+    GLOBAL_DNS_CACHE
         .get_or_try_init(|| {
-            let builder = TokioResolver::builder_tokio().map_err(|e| {
-                ProxyError::Configuration(format!("Failed to create DNS resolver builder: {e}"))
-            })?;
-            Ok(Arc::new(builder.build()))
+            let dns = config::dns_settings();
+            let resolver = build_resolver(dns)?;
+            let cache = Arc::new(DnsCache::new(Arc::new(resolver), dns));
+            memory::register(memory::DNS, Arc::downgrade(&cache) as _);
+            Ok(cache)
         })
         .cloned()
 }
 
+/// Build the discovery resolver from `pingsix.dns`, starting from the system
+/// configuration unless nameservers are given.
+fn build_resolver(dns: Option<&config::Dns>) -> ProxyResult<TokioResolver> {
+    let nameservers = match dns {
+        Some(dns) => dns.nameserver_addrs().map_err(ProxyError::Configuration)?,
+        None => Vec::new(),
+    };
+
+    let mut builder = if nameservers.is_empty() {
+        TokioResolver::builder_tokio().map_err(|e| {
+            ProxyError::Configuration(format!("Failed to create DNS resolver builder: {e}"))
+        })?
+    } else {
+        let group: Vec<NameServerConfig> = nameservers
+            .into_iter()
+            .flat_map(|addr| {
+                [
+                    NameServerConfig::new(addr, Protocol::Udp),
+                    NameServerConfig::new(addr, Protocol::Tcp),
+                ]
+            })
+            .collect();
+        TokioResolver::builder_with_config(
+            ResolverConfig::from_parts(None, vec![], group),
+            TokioConnectionProvider::default(),
+        )
+    };
+
+    if let Some(dns) = dns {
+        let options = builder.options_mut();
+        if let Some(timeout) = dns.timeout {
+            options.timeout = Duration::from_secs(timeout);
+        }
+        if let Some(min_ttl) = dns.min_ttl {
+            options.positive_min_ttl = Some(Duration::from_secs(min_ttl));
+        }
+        if let Some(max_ttl) = dns.max_ttl {
+            options.positive_max_ttl = Some(Duration::from_secs(max_ttl));
+        }
+        if let Some(preference) = dns.ip_preference {
+            options.ip_strategy = lookup_ip_strategy(preference);
+        }
+    }
+
+    Ok(builder.build())
+}
+
+fn lookup_ip_strategy(preference: DnsIpPreference) -> LookupIpStrategy {
+    match preference {
+        DnsIpPreference::Ipv4Only => LookupIpStrategy::Ipv4Only,
+        DnsIpPreference::Ipv6Only => LookupIpStrategy::Ipv6Only,
+        DnsIpPreference::Ipv4AndIpv6 => LookupIpStrategy::Ipv4AndIpv6,
+        DnsIpPreference::Ipv4ThenIpv6 => LookupIpStrategy::Ipv4thenIpv6,
+        DnsIpPreference::Ipv6ThenIpv4 => LookupIpStrategy::Ipv6thenIpv4,
+    }
+}
+
 /// Loads a client certificate and key from PEM format strings.
 ///
 /// This function parses the certificate chain and private key from PEM encoded strings
-/// and creates a CertKey object that can be used for mTLS authentication.
-fn load_client_cert_key(tls_config: &UpstreamTls) -> ProxyResult<Arc<CertKey>> {
+/// and creates a CertKey object that can be used for mTLS authentication. Returns
+/// `None` when no client certificate is configured.
+fn load_client_cert_key(tls_config: &UpstreamTls) -> ProxyResult<Option<Arc<CertKey>>> {
     use pingora_core::tls::pkey::PKey;
     use pingora_core::tls::x509::X509;
 
+    let (Some(client_cert), Some(client_key)) = (&tls_config.client_cert, &tls_config.client_key)
+    else {
+        return Ok(None);
+    };
+
     // Parse the certificate chain
-    let cert_pem = tls_config.client_cert.as_bytes();
-    let certificates = X509::stack_from_pem(cert_pem)
+    let certificates = X509::stack_from_pem(client_cert.as_bytes())
         .or_err_with(pingora_error::ErrorType::InternalError, || {
             "Failed to parse client certificate PEM"
         })?;
@@ -58,8 +137,7 @@ fn load_client_cert_key(tls_config: &UpstreamTls) -> ProxyResult<Arc<CertKey>> {
     }
 
     // Parse the private key
-    let key_pem = tls_config.client_key.as_bytes();
-    let private_key = PKey::private_key_from_pem(key_pem)
+    let private_key = PKey::private_key_from_pem(client_key.as_bytes())
         .or_err_with(pingora_error::ErrorType::InternalError, || {
             "Failed to parse client private key PEM"
         })?;
@@ -67,19 +145,54 @@ fn load_client_cert_key(tls_config: &UpstreamTls) -> ProxyResult<Arc<CertKey>> {
     // Create CertKey using the new method
     let cert_key = CertKey::new(certificates, private_key);
 
-    Ok(Arc::new(cert_key))
+    Ok(Some(Arc::new(cert_key)))
+}
+
+/// Zone label attached to a backend's extensions for zone-aware selection.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) struct NodeZone(pub String);
+
+/// Failover priority attached to a backend's extensions; absent means `0`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) struct NodePriority(pub i32);
+
+/// Configured node key (as written in `nodes`) a backend was resolved from.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) struct NodeKey(pub String);
+
+/// Selection labels of a configured node, copied onto every backend it resolves to.
+#[derive(Clone, Debug, Default)]
+pub(crate) struct NodeLabels {
+    node: String,
+    zone: Option<String>,
+    priority: i32,
+}
+
+impl NodeLabels {
+    fn apply(&self, backend: &mut Backend) {
+        if !self.node.is_empty() {
+            backend.ext.insert(NodeKey(self.node.clone()));
+        }
+        if let Some(zone) = &self.zone {
+            backend.ext.insert(NodeZone(zone.clone()));
+        }
+        if self.priority != 0 {
+            backend.ext.insert(NodePriority(self.priority));
+        }
+    }
 }
 
 /// DNS-based service discovery.
 ///
 /// Resolves DNS names to IP addresses and creates backends for each resolved IP.
 pub struct DnsDiscovery {
-    resolver: Arc<TokioResolver>,
+    resolver: Arc<DnsCache>,
     domain: String,
     port: u32,
     scheme: UpstreamScheme,
     weight: u32,
     client_cert_key: Option<Arc<CertKey>>,
+    labels: NodeLabels,
 }
 
 impl DnsDiscovery {
@@ -89,7 +202,7 @@ impl DnsDiscovery {
         port: u32,
         scheme: UpstreamScheme,
         weight: u32,
-        resolver: Arc<TokioResolver>,
+        resolver: Arc<DnsCache>,
         client_cert_key: Option<Arc<CertKey>>,
     ) -> Self {
         Self {
@@ -99,8 +212,15 @@ impl DnsDiscovery {
             scheme,
             weight,
             client_cert_key,
+            labels: NodeLabels::default(),
         }
     }
+
+    /// Label every resolved backend with the node's configured zone and priority.
+    pub(crate) fn with_labels(mut self, labels: NodeLabels) -> Self {
+        self.labels = labels;
+        self
+    }
 }
 
 #[async_trait]
@@ -116,51 +236,175 @@ impl ServiceDiscovery for DnsDiscovery {
             .await
             .map_err(|e| {
                 log::warn!("DNS discovery failed for domain: {domain}: {e}");
-                Error::because(
+                Error::explain(
                     InternalError,
                     format!("DNS discovery failed for domain: {domain}: {e}"),
-                    e,
                 )
             })?
             .iter()
             .filter_map(|ip| {
-                let addr = SocketAddr::new(ip, self.port as _).to_string();
-
-                // Creating backend
-                let mut backend = match Backend::new_with_weight(&addr, self.weight as _) {
-                    Ok(b) => b,
-                    Err(e) => {
-                        log::error!("Failed to create backend for {addr}: {e}");
-                        return None;
-                    }
-                };
+                dns_backend(
+                    SocketAddr::new(*ip, self.port as _),
+                    self.weight,
+                    self.scheme,
+                    &self.domain,
+                    self.client_cert_key.as_ref(),
+                    &self.labels,
+                )
+            })
+            .collect();
 
-                // Determine if TLS is needed
-                let tls = matches!(self.scheme, UpstreamScheme::HTTPS | UpstreamScheme::GRPCS);
+        // Return backends and an empty HashMap for now
+        Ok((backends, HashMap::new()))
+    }
+}
 
-                // Create HttpPeer
-                let mut peer = HttpPeer::new(&addr, tls, self.domain.clone());
-                if matches!(self.scheme, UpstreamScheme::GRPC | UpstreamScheme::GRPCS) {
-                    peer.options.alpn = ALPN::H2;
-                }
+/// HTTP versions offered to backends of `scheme`: gRPC and `http2` speak HTTP/2 only
+/// (h2c when plaintext), `https2` prefers HTTP/2 and falls back to HTTP/1.1 by ALPN.
+fn scheme_alpn(scheme: UpstreamScheme) -> ALPN {
+    match scheme {
+        UpstreamScheme::HTTP2 | UpstreamScheme::GRPC | UpstreamScheme::GRPCS => ALPN::H2,
+        UpstreamScheme::HTTPS2 => ALPN::H2H1,
+        UpstreamScheme::HTTP | UpstreamScheme::HTTPS => ALPN::H1,
+    }
+}
 
-                // Set client certificate if configured
-                if let Some(ref cert_key) = self.client_cert_key {
-                    peer.client_cert_key = Some(cert_key.clone());
-                }
+/// Build a backend carrying its `HttpPeer` for a resolved address.
+fn dns_backend(
+    addr: SocketAddr,
+    weight: u32,
+    scheme: UpstreamScheme,
+    sni: &str,
+    client_cert_key: Option<&Arc<CertKey>>,
+    labels: &NodeLabels,
+) -> Option<Backend> {
+    let addr = addr.to_string();
+
+    // Creating backend
+    let mut backend = match Backend::new_with_weight(&addr, weight as _) {
+        Ok(b) => b,
+        Err(e) => {
+            log::error!("Failed to create backend for {addr}: {e}");
+            return None;
+        }
+    };
 
-                // Insert HttpPeer into the backend. Must not live only inside
-                // `debug_assert!` — that expression is elided in release builds.
-                assert!(
-                    backend.ext.insert::<HttpPeer>(peer).is_none(),
-                    "backend already had HttpPeer metadata"
-                );
+    // Create HttpPeer
+    let mut peer = HttpPeer::new(&addr, scheme.is_tls(), sni.to_string());
+    peer.options.alpn = scheme_alpn(scheme);
 
-                Some(backend)
-            })
+    // Set client certificate if configured
+    if let Some(cert_key) = client_cert_key {
+        peer.client_cert_key = Some(cert_key.clone());
+    }
+
+    // Insert HttpPeer into the backend. Must not live only inside
+    // `debug_assert!` — that expression is elided in release builds.
+    assert!(
+        backend.ext.insert::<HttpPeer>(peer).is_none(),
+        "backend already had HttpPeer metadata"
+    );
+    labels.apply(&mut backend);
+
+    Some(backend)
+}
+
+/// DNS SRV service discovery.
+///
+/// Resolves an SRV name and creates a backend for every address of every target in the
+/// most preferred (lowest) priority, using the record's port and weight.
+pub struct SrvDiscovery {
+    resolver: Arc<DnsCache>,
+    name: String,
+    scheme: UpstreamScheme,
+    client_cert_key: Option<Arc<CertKey>>,
+    labels: NodeLabels,
+}
+
+impl SrvDiscovery {
+    /// Creates a new `SrvDiscovery` instance.
+    pub fn new(
+        name: String,
+        scheme: UpstreamScheme,
+        resolver: Arc<DnsCache>,
+        client_cert_key: Option<Arc<CertKey>>,
+    ) -> Self {
+        Self {
+            resolver,
+            name,
+            scheme,
+            client_cert_key,
+            labels: NodeLabels::default(),
+        }
+    }
+
+    /// Label every resolved backend with the node's configured zone and priority.
+    pub(crate) fn with_labels(mut self, labels: NodeLabels) -> Self {
+        self.labels = labels;
+        self
+    }
+}
+
+/// Targets of the lowest priority; lower-priority records are only for failover.
+fn preferred_srv_targets(mut records: Vec<SrvTarget>) -> Vec<SrvTarget> {
+    let Some(priority) = records.iter().map(|r| r.priority).min() else {
+        return records;
+    };
+    records.retain(|r| r.priority == priority);
+    records
+}
+
+#[async_trait]
+impl ServiceDiscovery for SrvDiscovery {
+    /// Discovers backends from the SRV records of the name and their targets' addresses.
+    async fn discover(&self) -> Result<(BTreeSet<Backend>, HashMap<u64, bool>)> {
+        let name = self.name.as_str();
+        log::debug!("Resolving SRV records for: {name}");
+
+        let records = self.resolver.lookup_srv(name).await.map_err(|e| {
+            log::warn!("SRV discovery failed for {name}: {e}");
+            Error::explain(
+                InternalError,
+                format!("SRV discovery failed for {name}: {e}"),
+            )
+        })?;
+
+        let targets = preferred_srv_targets(records.to_vec());
+        let names: Vec<String> = targets
+            .iter()
+            .map(|record| record.target.to_utf8())
             .collect();
+        let lookups = names.iter().map(|target| self.resolver.lookup_ip(target));
+        let mut backends = BTreeSet::new();
+        for ((record, target), result) in targets.iter().zip(&names).zip(join_all(lookups).await) {
+            let target = target.trim_end_matches('.');
+            let ips = match result {
+                Ok(ips) => ips,
+                Err(e) => {
+                    log::warn!("SRV target {target} of {name} did not resolve: {e}");
+                    continue;
+                }
+            };
+            // A zero SRV weight means "rarely"; selection weights must be positive.
+            let weight = u32::from(record.weight.max(1));
+            backends.extend(ips.iter().filter_map(|ip| {
+                dns_backend(
+                    SocketAddr::new(*ip, record.port),
+                    weight,
+                    self.scheme,
+                    target,
+                    self.client_cert_key.as_ref(),
+                    &self.labels,
+                )
+            }));
+        }
 
-        // Return backends and an empty HashMap for now
+        if backends.is_empty() {
+            return Error::e_explain(
+                InternalError,
+                format!("SRV discovery for {name} found no reachable targets"),
+            );
+        }
         Ok((backends, HashMap::new()))
     }
 }
@@ -227,18 +471,28 @@ pub(crate) async fn prepare_upstream(upstream: &Upstream) -> ProxyResult<Prepare
 
 /// Returns prepared backends once, then delegates subsequent refreshes to the
 /// normal discovery implementation.
+///
+/// Clones share their state, so a handle kept outside the load balancer can
+/// [`reseed`](Self::reseed) it with another node set.
+#[derive(Clone)]
 pub(crate) struct SeededDiscovery {
-    initial: Mutex<Option<PreparedUpstream>>,
-    refresh: HybridDiscovery,
+    initial: Arc<Mutex<Option<PreparedUpstream>>>,
+    refresh: Arc<ArcSwap<HybridDiscovery>>,
 }
 
 impl SeededDiscovery {
     pub(crate) fn new(initial: PreparedUpstream, refresh: HybridDiscovery) -> Self {
         Self {
-            initial: Mutex::new(Some(initial)),
-            refresh,
+            initial: Arc::new(Mutex::new(Some(initial))),
+            refresh: Arc::new(ArcSwap::from_pointee(refresh)),
         }
     }
+
+    /// Return `prepared` on the next discovery and refresh through `refresh` after.
+    pub(crate) fn reseed(&self, prepared: PreparedUpstream, refresh: HybridDiscovery) {
+        self.refresh.store(Arc::new(refresh));
+        *self.initial.lock().unwrap_or_else(|e| e.into_inner()) = Some(prepared);
+    }
 }
 
 #[async_trait]
@@ -252,7 +506,7 @@ impl ServiceDiscovery for SeededDiscovery {
         {
             return Ok((prepared.backends, prepared.health_checks));
         }
-        self.refresh.discover().await
+        self.refresh.load_full().discover().await
     }
 }
 
@@ -261,7 +515,16 @@ impl ServiceDiscovery for SeededDiscovery {
 /// Combines static and DNS-based service discovery.
 #[derive(Default)]
 pub struct HybridDiscovery {
+    upstream_id: String,
     discoveries: Vec<Box<dyn ServiceDiscovery + Send + Sync>>,
+    resolves_dns: bool,
+}
+
+impl HybridDiscovery {
+    /// Whether any node is a domain name, i.e. refreshing can change the backends.
+    pub fn resolves_dns(&self) -> bool {
+        self.resolves_dns
+    }
 }
 
 #[async_trait]
@@ -308,6 +571,7 @@ impl ServiceDiscovery for HybridDiscovery {
             }
         }
 
+        health_checks.extend(drain::enablement(&self.upstream_id, &backends));
         Ok((backends, health_checks))
     }
 }
@@ -316,23 +580,35 @@ impl TryFrom<Upstream> for HybridDiscovery {
     type Error = ProxyError;
 
     fn try_from(upstream: Upstream) -> ProxyResult<Self> {
-        let mut this = Self::default();
+        let mut this = Self {
+            upstream_id: upstream.id.clone(),
+            ..Self::default()
+        };
         let mut backends = BTreeSet::new();
 
         // Load client certificate if configured
-        let client_cert_key = if let Some(ref tls) = upstream.tls {
-            Some(load_client_cert_key(tls)?)
-        } else {
-            None
+        let client_cert_key = match &upstream.tls {
+            Some(tls) => load_client_cert_key(tls)?,
+            None => None,
         };
 
         // Process each node in upstream
         for (addr, weight) in upstream.nodes.iter() {
+            if upstream.discovery_type == Some(UpstreamDiscoveryType::DnsSrv) {
+                let discovery = SrvDiscovery::new(
+                    addr.clone(),
+                    upstream.scheme,
+                    get_dns_cache()?,
+                    client_cert_key.clone(),
+                )
+                .with_labels(node_labels(&upstream, addr));
+                this.discoveries.push(Box::new(discovery));
+                this.resolves_dns = true;
+                continue;
+            }
+
             let (host, port) = parse_host_and_port(addr)?;
-            let port = port.unwrap_or(match upstream.scheme {
-                UpstreamScheme::HTTPS | UpstreamScheme::GRPCS => 443,
-                _ => 80,
-            });
+            let port = port.unwrap_or(if upstream.scheme.is_tls() { 443 } else { 80 });
 
             // Strip brackets from IPv6 for parsing, then add them back for SocketAddr
             let host_for_parse = if host.starts_with('[') && host.ends_with(']') {
@@ -356,10 +632,6 @@ impl TryFrom<Upstream> for HybridDiscovery {
                         ))
                     })?;
 
-                let tls = matches!(
-                    upstream.scheme,
-                    UpstreamScheme::HTTPS | UpstreamScheme::GRPCS
-                );
                 let sni = if upstream.pass_host == UpstreamPassHost::REWRITE {
                     upstream
                         .upstream_host
@@ -369,13 +641,8 @@ impl TryFrom<Upstream> for HybridDiscovery {
                     host.to_string()
                 };
 
-                let mut peer = HttpPeer::new(&addr_str, tls, sni);
-                if matches!(
-                    upstream.scheme,
-                    UpstreamScheme::GRPC | UpstreamScheme::GRPCS
-                ) {
-                    peer.options.alpn = ALPN::H2;
-                }
+                let mut peer = HttpPeer::new(&addr_str, upstream.scheme.is_tls(), sni);
+                peer.options.alpn = scheme_alpn(upstream.scheme);
 
                 // Set client certificate if configured
                 if let Some(ref cert_key) = client_cert_key {
@@ -387,12 +654,13 @@ impl TryFrom<Upstream> for HybridDiscovery {
                     backend.ext.insert::<HttpPeer>(peer).is_none(),
                     "backend already had HttpPeer metadata"
                 );
+                node_labels(&upstream, addr).apply(&mut backend);
 
                 backends.insert(backend);
             } else {
                 // It's a domain name
                 // Handle DNS discovery for domain names
-                let resolver = get_global_resolver()?;
+                let resolver = get_dns_cache()?;
                 let discovery = DnsDiscovery::new(
                     host,
                     port,
@@ -400,8 +668,10 @@ impl TryFrom<Upstream> for HybridDiscovery {
                     *weight,
                     resolver,
                     client_cert_key.clone(),
-                );
+                )
+                .with_labels(node_labels(&upstream, addr));
                 this.discoveries.push(Box::new(discovery));
+                this.resolves_dns = true;
             }
         }
 
@@ -413,6 +683,20 @@ impl TryFrom<Upstream> for HybridDiscovery {
     }
 }
 
+/// Configured labels for a node key: the key itself, its zone, when the upstream is zone-aware,
+/// and its failover priority.
+fn node_labels(upstream: &Upstream, node: &str) -> NodeLabels {
+    NodeLabels {
+        node: node.to_string(),
+        zone: upstream
+            .zone_aware
+            .as_ref()
+            .and_then(|zone_aware| zone_aware.zones.get(node))
+            .cloned(),
+        priority: upstream.priorities.get(node).copied().unwrap_or_default(),
+    }
+}
+
 /// Regular expression for parsing host and port from an address string.
 static HOST_PORT_REGEX: once_cell::sync::Lazy<Regex> = once_cell::sync::Lazy::new(|| {
     Regex::new(r"^(?:\[(.+?)\]|([^:]+))(?::(\d+))?$").expect("Invalid HOST_PORT_REGEX pattern")
@@ -451,6 +735,8 @@ fn parse_host_and_port(addr: &str) -> ProxyResult<(String, Option<u32>)> {
 
 #[cfg(test)]
 mod tests {
+    use hickory_resolver::Name;
+
     use super::parse_host_and_port;
     use super::*;
 
@@ -499,7 +785,10 @@ mod tests {
             .into_iter()
             .map(|d| Box::new(d) as Box<dyn ServiceDiscovery + Send + Sync>)
             .collect();
-        HybridDiscovery { discoveries }
+        HybridDiscovery {
+            discoveries,
+            ..HybridDiscovery::default()
+        }
     }
 
     #[tokio::test]
@@ -538,6 +827,72 @@ mod tests {
         assert_eq!(backends.len(), 2);
     }
 
+    #[tokio::test]
+    async fn test_build_resolver_with_custom_nameservers() {
+        let dns = config::Dns {
+            nameservers: vec!["127.0.0.1:5353".into()],
+            timeout: Some(1),
+            min_ttl: Some(5),
+            max_ttl: Some(30),
+            ip_preference: Some(DnsIpPreference::Ipv6ThenIpv4),
+            refresh_interval: None,
+            negative_ttl: None,
+            stale_ttl: None,
+        };
+        let resolver = build_resolver(Some(&dns)).unwrap();
+        let servers = resolver.config().name_servers();
+        assert_eq!(servers.len(), 2);
+        assert!(servers
+            .iter()
+            .all(|ns| ns.socket_addr == "127.0.0.1:5353".parse().unwrap()));
+        let options = resolver.options();
+        assert_eq!(options.timeout, Duration::from_secs(1));
+        assert_eq!(options.positive_max_ttl, Some(Duration::from_secs(30)));
+        assert_eq!(options.ip_strategy, LookupIpStrategy::Ipv6thenIpv4);
+    }
+
+    #[test]
+    fn test_preferred_srv_targets_keep_lowest_priority() {
+        let record = |priority, target: &str| SrvTarget {
+            priority,
+            weight: 10,
+            port: 8080,
+            target: Name::from_ascii(target).unwrap(),
+        };
+        let targets = preferred_srv_targets(vec![
+            record(20, "backup.example.com."),
+            record(10, "a.example.com."),
+            record(10, "b.example.com."),
+        ]);
+        assert_eq!(
+            targets,
+            vec![record(10, "a.example.com."), record(10, "b.example.com.")]
+        );
+        assert!(preferred_srv_targets(vec![]).is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_http2_schemes_negotiate_h2() {
+        for (scheme, alpn, tls, port) in [
+            ("http", ALPN::H1, false, 80),
+            ("http2", ALPN::H2, false, 80),
+            ("https2", ALPN::H2H1, true, 443),
+            ("grpcs", ALPN::H2, true, 443),
+        ] {
+            let upstream: Upstream = serde_yml::from_str(&format!(
+                "id: u1\nscheme: {scheme}\nnodes:\n  \"127.0.0.1\": 1\n"
+            ))
+            .unwrap();
+            let discovery = HybridDiscovery::try_from(upstream).unwrap();
+            let (backends, _) = discovery.discover().await.unwrap();
+            let backend = backends.first().unwrap();
+            let peer = backend.ext.get::<HttpPeer>().unwrap();
+            assert_eq!(peer.options.alpn, alpn, "{scheme}");
+            assert_eq!(peer.is_tls(), tls, "{scheme}");
+            assert_eq!(backend.addr.to_string(), format!("127.0.0.1:{port}"));
+        }
+    }
+
     #[test]
     fn test_parse_upstream_node() {
         let test_cases = [
@@ -0,0 +1,465 @@
+//! Shared DNS cache of service discovery.
+//!
+//! Every DNS and SRV lookup of discovery goes through one cache in front of the resolver:
+//! - answers are served until their TTL (bounded by `min_ttl`/`max_ttl`) expires;
+//! - failures are cached for `negative_ttl`, so a broken name is not queried on every
+//!   refresh of every upstream using it;
+//! - an expired answer is still served for `stale_ttl` while it is refreshed in the
+//!   background, and is kept when that refresh fails.
+//!
+//! Resolution latency, failures and cache outcomes are exported as Prometheus metrics.
+
+use std::{
+    net::IpAddr,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use async_trait::async_trait;
+use dashmap::DashMap;
+use hickory_resolver::{Name, TokioResolver};
+use once_cell::sync::Lazy;
+use prometheus::{register_histogram_vec, register_int_counter_vec, HistogramVec, IntCounterVec};
+
+use crate::{
+    config,
+    core::memory::{MemoryConsumer, ENTRY_OVERHEAD},
+};
+
+pub const DEFAULT_NEGATIVE_TTL: u64 = 5;
+pub const DEFAULT_STALE_TTL: u64 = 30;
+
+static DNS_LOOKUPS: Lazy<IntCounterVec> = Lazy::new(|| {
+    register_int_counter_vec!(
+        "pingsix_dns_lookups_total",
+        "Discovery DNS lookups by record type and cache outcome (hit, stale, negative, miss)",
+        &["type", "result"]
+    )
+    .expect("DNS lookup metric registration must succeed")
+});
+
+static DNS_RESOLUTION_DURATION: Lazy<HistogramVec> = Lazy::new(|| {
+    register_histogram_vec!(
+        "pingsix_dns_resolution_duration_seconds",
+        "Time to resolve discovery DNS names that were not answered from the cache",
+        &["type"],
+        vec![0.001, 0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0]
+    )
+    .expect("DNS resolution duration metric registration must succeed")
+});
+
+static DNS_RESOLUTION_FAILURES: Lazy<IntCounterVec> = Lazy::new(|| {
+    register_int_counter_vec!(
+        "pingsix_dns_resolution_failures_total",
+        "Failed discovery DNS resolutions by record type and name",
+        &["type", "name"]
+    )
+    .expect("DNS resolution failure metric registration must succeed")
+});
+
+/// An SRV answer reduced to what a backend needs.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) struct SrvTarget {
+    pub priority: u16,
+    pub weight: u16,
+    pub port: u16,
+    pub target: Name,
+}
+
+/// Records of a successful resolution and until when they may be used.
+pub(crate) struct Answer<T> {
+    pub records: Vec<T>,
+    pub valid_until: Instant,
+}
+
+/// The resolver behind the cache.
+#[async_trait]
+pub(crate) trait Resolve: Send + Sync + 'static {
+    async fn lookup_ip(&self, name: &str) -> Result<Answer<IpAddr>, String>;
+
+    async fn lookup_srv(&self, name: &str) -> Result<Answer<SrvTarget>, String>;
+}
+
+#[async_trait]
+impl Resolve for TokioResolver {
+    async fn lookup_ip(&self, name: &str) -> Result<Answer<IpAddr>, String> {
+        let lookup = TokioResolver::lookup_ip(self, name)
+            .await
+            .map_err(|e| e.to_string())?;
+        Ok(Answer {
+            records: lookup.iter().collect(),
+            valid_until: lookup.valid_until(),
+        })
+    }
+
+    async fn lookup_srv(&self, name: &str) -> Result<Answer<SrvTarget>, String> {
+        let lookup = self.srv_lookup(name).await.map_err(|e| e.to_string())?;
+        Ok(Answer {
+            records: lookup
+                .iter()
+                .map(|srv| SrvTarget {
+                    priority: srv.priority(),
+                    weight: srv.weight(),
+                    port: srv.port(),
+                    target: srv.target().clone(),
+                })
+                .collect(),
+            valid_until: lookup.as_lookup().valid_until(),
+        })
+    }
+}
+
+/// A record type the cache holds.
+pub(crate) trait Record: Clone + Send + Sync + 'static {
+    /// Metric label of the record type.
+    const TYPE: &'static str;
+
+    fn table(cache: &DnsCache) -> &DashMap<String, Entry<Self>>;
+
+    fn resolve<'a>(
+        resolver: &'a dyn Resolve,
+        name: &'a str,
+    ) -> futures::future::BoxFuture<'a, Result<Answer<Self>, String>>;
+}
+
+impl Record for IpAddr {
+    const TYPE: &'static str = "A";
+
+    fn table(cache: &DnsCache) -> &DashMap<String, Entry<Self>> {
+        &cache.ips
+    }
+
+    fn resolve<'a>(
+        resolver: &'a dyn Resolve,
+        name: &'a str,
+    ) -> futures::future::BoxFuture<'a, Result<Answer<Self>, String>> {
+        resolver.lookup_ip(name)
+    }
+}
+
+impl Record for SrvTarget {
+    const TYPE: &'static str = "SRV";
+
+    fn table(cache: &DnsCache) -> &DashMap<String, Entry<Self>> {
+        &cache.srvs
+    }
+
+    fn resolve<'a>(
+        resolver: &'a dyn Resolve,
+        name: &'a str,
+    ) -> futures::future::BoxFuture<'a, Result<Answer<Self>, String>> {
+        resolver.lookup_srv(name)
+    }
+}
+
+/// A cached answer or failure.
+pub(crate) struct Entry<T> {
+    answer: Result<Arc<[T]>, String>,
+    expires: Instant,
+    /// A background refresh of this stale answer is running.
+    refreshing: bool,
+}
+
+impl<T> Entry<T> {
+    /// Estimated bytes held for the entry of `name`.
+    fn size(&self, name: &str) -> usize {
+        ENTRY_OVERHEAD
+            + name.len()
+            + match &self.answer {
+                Ok(records) => std::mem::size_of_val(&**records),
+                Err(e) => e.len(),
+            }
+    }
+
+    /// Whether the entry can still be served, fresh, stale or as a failure.
+    fn servable(&self, now: Instant, stale_ttl: Duration) -> bool {
+        match self.answer {
+            Ok(_) => now < self.expires + stale_ttl,
+            Err(_) => now < self.expires,
+        }
+    }
+}
+
+/// Cache of DNS answers and failures in front of a resolver.
+pub struct DnsCache {
+    resolver: Arc<dyn Resolve>,
+    negative_ttl: Duration,
+    stale_ttl: Duration,
+    ips: DashMap<String, Entry<IpAddr>>,
+    srvs: DashMap<String, Entry<SrvTarget>>,
+}
+
+impl DnsCache {
+    pub(crate) fn new(resolver: Arc<dyn Resolve>, dns: Option<&config::Dns>) -> Self {
+        let negative_ttl = dns
+            .and_then(|dns| dns.negative_ttl)
+            .unwrap_or(DEFAULT_NEGATIVE_TTL);
+        let stale_ttl = dns
+            .and_then(|dns| dns.stale_ttl)
+            .unwrap_or(DEFAULT_STALE_TTL);
+        Self {
+            resolver,
+            negative_ttl: Duration::from_secs(negative_ttl),
+            stale_ttl: Duration::from_secs(stale_ttl),
+            ips: DashMap::new(),
+            srvs: DashMap::new(),
+        }
+    }
+
+    /// Addresses of `name`.
+    pub(crate) async fn lookup_ip(self: &Arc<Self>, name: &str) -> Result<Arc<[IpAddr]>, String> {
+        self.lookup(name).await
+    }
+
+    /// SRV records of `name`.
+    pub(crate) async fn lookup_srv(
+        self: &Arc<Self>,
+        name: &str,
+    ) -> Result<Arc<[SrvTarget]>, String> {
+        self.lookup(name).await
+    }
+
+    async fn lookup<R: Record>(self: &Arc<Self>, name: &str) -> Result<Arc<[R]>, String> {
+        let now = Instant::now();
+        if let Some(mut entry) = R::table(self).get_mut(name) {
+            if now < entry.expires {
+                let result = if entry.answer.is_ok() {
+                    "hit"
+                } else {
+                    "negative"
+                };
+                DNS_LOOKUPS.with_label_values(&[R::TYPE, result]).inc();
+                return entry.answer.clone();
+            }
+            if let Ok(records) = &entry.answer {
+                if now < entry.expires + self.stale_ttl {
+                    DNS_LOOKUPS.with_label_values(&[R::TYPE, "stale"]).inc();
+                    let records = records.clone();
+                    if !entry.refreshing {
+                        entry.refreshing = self.spawn_refresh::<R>(name);
+                    }
+                    return Ok(records);
+                }
+            }
+        }
+        DNS_LOOKUPS.with_label_values(&[R::TYPE, "miss"]).inc();
+        self.resolve(name).await
+    }
+
+    /// Refresh `name` in the background; false without a runtime to run it on.
+    fn spawn_refresh<R: Record>(self: &Arc<Self>, name: &str) -> bool {
+        let Ok(handle) = tokio::runtime::Handle::try_current() else {
+            return false;
+        };
+        let cache = self.clone();
+        let name = name.to_string();
+        handle.spawn(async move {
+            let _ = cache.resolve::<R>(&name).await;
+        });
+        true
+    }
+
+    /// Query the resolver and cache the outcome. A failure does not replace an answer
+    /// that may still be served stale.
+    async fn resolve<R: Record>(&self, name: &str) -> Result<Arc<[R]>, String> {
+        let started = Instant::now();
+        let result = R::resolve(self.resolver.as_ref(), name).await;
+        let now = Instant::now();
+        DNS_RESOLUTION_DURATION
+            .with_label_values(&[R::TYPE])
+            .observe(now.duration_since(started).as_secs_f64());
+
+        let table = R::table(self);
+        match result {
+            Ok(answer) => {
+                let records: Arc<[R]> = answer.records.into();
+                table.insert(
+                    name.to_string(),
+                    Entry {
+                        answer: Ok(records.clone()),
+                        expires: answer.valid_until.max(now),
+                        refreshing: false,
+                    },
+                );
+                Ok(records)
+            }
+            Err(e) => {
+                log::warn!("DNS {} lookup of {name} failed: {e}", R::TYPE);
+                DNS_RESOLUTION_FAILURES
+                    .with_label_values(&[R::TYPE, name])
+                    .inc();
+                let mut entry = table.entry(name.to_string()).or_insert_with(|| Entry {
+                    answer: Err(String::new()),
+                    expires: now,
+                    refreshing: false,
+                });
+                if entry.answer.is_ok() && now < entry.expires + self.stale_ttl {
+                    entry.refreshing = false;
+                } else {
+                    *entry = Entry {
+                        answer: Err(e.clone()),
+                        expires: now + self.negative_ttl,
+                        refreshing: false,
+                    };
+                }
+                Err(e)
+            }
+        }
+    }
+}
+
+impl DnsCache {
+    fn usage_of<R: Record>(&self) -> usize {
+        R::table(self)
+            .iter()
+            .map(|entry| entry.value().size(entry.key()))
+            .sum()
+    }
+
+    /// `(expires, name, size)` of every entry, after dropping those no longer servable.
+    fn trim<R: Record>(&self, now: Instant) -> Vec<(Instant, String, usize)> {
+        let table = R::table(self);
+        table.retain(|_, entry| entry.servable(now, self.stale_ttl));
+        table
+            .iter()
+            .map(|entry| (entry.expires, entry.key().clone(), entry.size(entry.key())))
+            .collect()
+    }
+}
+
+#[async_trait]
+impl MemoryConsumer for DnsCache {
+    fn usage(&self) -> usize {
+        self.usage_of::<IpAddr>() + self.usage_of::<SrvTarget>()
+    }
+
+    /// Drops entries that can no longer be served, then those expiring soonest; they
+    /// are resolved again on their next lookup.
+    async fn shrink(&self, target: usize) {
+        let now = Instant::now();
+        let mut entries: Vec<_> = self
+            .trim::<IpAddr>(now)
+            .into_iter()
+            .map(|(expires, name, size)| (expires, true, name, size))
+            .chain(
+                self.trim::<SrvTarget>(now)
+                    .into_iter()
+                    .map(|(expires, name, size)| (expires, false, name, size)),
+            )
+            .collect();
+        let mut usage: usize = entries.iter().map(|entry| entry.3).sum();
+        entries.sort_by_key(|entry| entry.0);
+        for (_, ip, name, size) in entries {
+            if usage <= target {
+                break;
+            }
+            if ip {
+                self.ips.remove(&name);
+            } else {
+                self.srvs.remove(&name);
+            }
+            usage -= size;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use super::*;
+
+    /// Answers `10.0.0.1` for names starting with `ok`, with a TTL of `ttl`; fails
+    /// otherwise.
+    struct StubResolver {
+        queries: AtomicUsize,
+        ttl: Duration,
+    }
+
+    #[async_trait]
+    impl Resolve for StubResolver {
+        async fn lookup_ip(&self, name: &str) -> Result<Answer<IpAddr>, String> {
+            self.queries.fetch_add(1, Ordering::SeqCst);
+            if !name.starts_with("ok") {
+                return Err(format!("no record found for {name}"));
+            }
+            Ok(Answer {
+                records: vec!["10.0.0.1".parse().unwrap()],
+                valid_until: Instant::now() + self.ttl,
+            })
+        }
+
+        async fn lookup_srv(&self, name: &str) -> Result<Answer<SrvTarget>, String> {
+            Err(format!("no record found for {name}"))
+        }
+    }
+
+    fn cache(ttl: Duration, stale_ttl: u64) -> (Arc<DnsCache>, Arc<StubResolver>) {
+        let resolver = Arc::new(StubResolver {
+            queries: AtomicUsize::new(0),
+            ttl,
+        });
+        let dns = config::Dns {
+            negative_ttl: Some(60),
+            stale_ttl: Some(stale_ttl),
+            ..Default::default()
+        };
+        (
+            Arc::new(DnsCache::new(resolver.clone(), Some(&dns))),
+            resolver,
+        )
+    }
+
+    #[tokio::test]
+    async fn answers_and_failures_are_cached() {
+        let (cache, resolver) = cache(Duration::from_secs(60), 0);
+        assert_eq!(cache.lookup_ip("ok.example").await.unwrap().len(), 1);
+        assert_eq!(cache.lookup_ip("ok.example").await.unwrap().len(), 1);
+        assert!(cache.lookup_ip("missing.example").await.is_err());
+        assert!(cache.lookup_ip("missing.example").await.is_err());
+        assert_eq!(resolver.queries.load(Ordering::SeqCst), 2);
+    }
+
+    #[tokio::test]
+    async fn expired_answers_are_served_while_refreshing() {
+        let (cache, resolver) = cache(Duration::ZERO, 60);
+        cache.lookup_ip("ok.example").await.unwrap();
+        assert_eq!(resolver.queries.load(Ordering::SeqCst), 1);
+
+        // Expired at once: served stale, refreshed in the background.
+        assert_eq!(cache.lookup_ip("ok.example").await.unwrap().len(), 1);
+        tokio::task::yield_now().await;
+        assert_eq!(resolver.queries.load(Ordering::SeqCst), 2);
+
+        // A failed refresh keeps the stale answer.
+        cache.ips.insert(
+            "gone.example".to_string(),
+            Entry {
+                answer: Ok(Arc::from(vec!["10.0.0.2".parse::<IpAddr>().unwrap()])),
+                expires: Instant::now(),
+                refreshing: true,
+            },
+        );
+        cache.resolve::<IpAddr>("gone.example").await.unwrap_err();
+        let entry = cache.ips.get("gone.example").unwrap();
+        assert!(entry.answer.is_ok() && !entry.refreshing);
+    }
+
+    #[tokio::test]
+    async fn shrinking_drops_soonest_expiring_entries() {
+        let (cache, _) = cache(Duration::from_secs(60), 0);
+        cache.lookup_ip("ok-1.example").await.unwrap();
+        cache.lookup_ip("ok-2.example").await.unwrap();
+        cache.lookup_ip("missing.example").await.unwrap_err();
+        let usage = cache.usage();
+        assert!(usage > 0);
+
+        // Every entry lives 60s, so the first one cached expires first.
+        cache.shrink(usage - 1).await;
+        assert_eq!(cache.ips.len(), 2);
+        assert!(!cache.ips.contains_key("ok-1.example"));
+        cache.shrink(0).await;
+        assert!(cache.ips.is_empty());
+        assert_eq!(cache.usage(), 0);
+    }
+}
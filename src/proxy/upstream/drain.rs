@@ -0,0 +1,84 @@
+//! Runtime drain state of upstream nodes, set through the Admin API.
+//!
+//! A drained node stays in its upstream's configuration, but every backend it resolves
+//! to is disabled in the load balancer until the node is re-enabled. The state belongs
+//! to this process: it is neither stored in etcd nor kept across restarts, and it
+//! outlives rebuilds of the upstream, but not its deletion.
+
+use std::{
+    collections::{hash_map::DefaultHasher, BTreeSet, HashMap},
+    hash::{Hash, Hasher},
+};
+
+use dashmap::DashMap;
+use once_cell::sync::Lazy;
+use pingora_load_balancing::{Backend, Backends};
+
+use super::discovery::NodeKey;
+
+/// Drained node keys by upstream id.
+static DRAINED: Lazy<DashMap<String, BTreeSet<String>>> = Lazy::new(DashMap::new);
+
+/// Drain or re-enable `node` of `upstream_id`. Returns whether the state changed.
+pub fn set_drained(upstream_id: &str, node: &str, drained: bool) -> bool {
+    if drained {
+        return DRAINED
+            .entry(upstream_id.to_string())
+            .or_default()
+            .insert(node.to_string());
+    }
+    let changed = DRAINED
+        .get_mut(upstream_id)
+        .is_some_and(|mut nodes| nodes.remove(node));
+    DRAINED.remove_if(upstream_id, |_, nodes| nodes.is_empty());
+    changed
+}
+
+/// Forget the drained nodes of `upstream_id`, once the upstream is deleted, so an
+/// upstream later created with the same id starts with every node enabled.
+pub fn clear(upstream_id: &str) {
+    DRAINED.remove(upstream_id);
+}
+
+pub fn is_drained(upstream_id: &str, node: &str) -> bool {
+    DRAINED
+        .get(upstream_id)
+        .is_some_and(|nodes| nodes.contains(node))
+}
+
+/// Whether `backend` was resolved from a drained node of `upstream_id`.
+pub(crate) fn backend_drained(upstream_id: &str, backend: &Backend) -> bool {
+    backend
+        .ext
+        .get::<NodeKey>()
+        .is_some_and(|node| is_drained(upstream_id, &node.0))
+}
+
+/// Enablement of `backends` for a discovery result, so refreshed backends of a drained
+/// node stay out of rotation.
+pub(crate) fn enablement<'a>(
+    upstream_id: &str,
+    backends: impl IntoIterator<Item = &'a Backend>,
+) -> HashMap<u64, bool> {
+    backends
+        .into_iter()
+        .filter(|backend| backend.ext.get::<NodeKey>().is_some())
+        .map(|backend| (hash_key(backend), !backend_drained(upstream_id, backend)))
+        .collect()
+}
+
+/// The key `Backends` tracks health and enablement by; `Backend::hash_key` is private.
+fn hash_key(backend: &Backend) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    backend.hash(&mut hasher);
+    hasher.finish()
+}
+
+/// Enable or disable the current backends of a load balancer to match the drain state.
+pub(crate) fn apply(upstream_id: &str, backends: &Backends) {
+    for backend in backends.get_backend().iter() {
+        if backend.ext.get::<NodeKey>().is_some() {
+            backends.set_enable(backend, !backend_drained(upstream_id, backend));
+        }
+    }
+}
@@ -1,18 +1,32 @@
 use std::{
+    cell::RefCell,
+    collections::HashMap,
     sync::{
         atomic::{AtomicU64, Ordering},
         Arc,
     },
-    time::Duration,
+    time::{Duration, SystemTime, UNIX_EPOCH},
 };
 
 use async_trait::async_trait;
+use bytes::{BufMut, Bytes, BytesMut};
 use dashmap::DashMap;
+use futures::FutureExt;
+use http::header;
 use once_cell::sync::Lazy;
+use pingora::protocols::ALPN;
 use pingora_core::{
+    connectors::http::Connector as HttpConnector,
+    protocols::http::client::HttpSession,
     server::ShutdownWatch,
     services::{background::BackgroundService, Service},
+    upstreams::peer::HttpPeer,
 };
+use pingora_error::{Error, ErrorType};
+use pingora_http::RequestHeader;
+use pingora_load_balancing::{health_check::HealthCheck as HealthCheckTrait, Backend, Backends};
+use prost::Message;
+use serde::Serialize;
 use tokio::sync::{broadcast, watch};
 
 /// Registry update event types. Generations prevent delayed events from affecting replacements.
@@ -459,6 +473,408 @@ impl Service for SharedHealthCheckService {
     }
 }
 
+/// Outcome of the active probes against one backend, for `GET /status/upstreams`.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct BackendProbe {
+    /// Milliseconds since the UNIX epoch.
+    pub last_probe: Option<u64>,
+    pub last_error: Option<String>,
+    pub consecutive_failures: u64,
+    pub failures: u64,
+    pub successes: u64,
+}
+
+/// Health of a backend as the load balancer tracks it: whether it is healthy and how
+/// many consecutive probes disagreed since.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct HealthState {
+    pub healthy: bool,
+    pub counter: usize,
+}
+
+impl Default for HealthState {
+    fn default() -> Self {
+        Self {
+            healthy: true,
+            counter: 0,
+        }
+    }
+}
+
+impl HealthState {
+    /// Same transition as the load balancer's: `threshold` disagreeing probes in a row
+    /// flip the state.
+    fn observe(&mut self, success: bool, threshold: usize) {
+        if self.healthy == success {
+            self.counter = 0;
+            return;
+        }
+        self.counter += 1;
+        if self.counter >= threshold {
+            self.healthy = success;
+            self.counter = 0;
+        }
+    }
+}
+
+/// Probe results of one upstream, keyed by backend address. Shared between the health
+/// check and the upstream that reports it.
+#[derive(Debug, Default)]
+pub struct ProbeLog {
+    backends: DashMap<String, BackendProbe>,
+    /// Mirror of the load balancer's health per backend, which it does not expose.
+    states: DashMap<String, HealthState>,
+}
+
+impl ProbeLog {
+    fn record(&self, addr: String, result: &pingora_error::Result<()>, threshold: usize) {
+        self.states
+            .entry(addr.clone())
+            .or_default()
+            .observe(result.is_ok(), threshold);
+        let mut probe = self.backends.entry(addr).or_default();
+        probe.last_probe = Some(
+            SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_millis() as u64,
+        );
+        match result {
+            Ok(()) => {
+                probe.last_error = None;
+                probe.consecutive_failures = 0;
+                probe.successes += 1;
+            }
+            Err(e) => {
+                probe.last_error = Some(e.to_string());
+                probe.consecutive_failures += 1;
+                probe.failures += 1;
+            }
+        }
+    }
+
+    pub fn get(&self, addr: &str) -> Option<BackendProbe> {
+        self.backends.get(addr).map(|probe| probe.clone())
+    }
+
+    pub fn state(&self, addr: &str) -> HealthState {
+        self.states
+            .get(addr)
+            .map(|state| *state)
+            .unwrap_or_default()
+    }
+
+    /// Drop backends that discovery no longer returns.
+    pub fn retain(&self, keep: impl Fn(&str) -> bool) {
+        self.backends.retain(|addr, _| keep(addr));
+        self.states.retain(|addr, _| keep(addr));
+    }
+}
+
+/// Scripted probe results, by backend address, for a replay on this thread.
+struct Replay {
+    results: HashMap<String, bool>,
+    threshold: usize,
+}
+
+thread_local! {
+    static REPLAY: RefCell<Option<Replay>> = const { RefCell::new(None) };
+}
+
+/// Bring every backend of `backends` to the health `previous` tracked for its address,
+/// and carry its probe results over to `log`, the log of `backends`' health check.
+/// `previous` may be `log` itself, for backends the load balancer replaced because
+/// their weight changed.
+///
+/// The load balancer's health can only change through its health check, so scripted
+/// probes are replayed through it: one round flips the backends that should be
+/// unhealthy, further rounds restore the counters. The script is only visible to this
+/// thread, so probes of running health checks are unaffected.
+pub fn inherit_health(backends: &Backends, log: &ProbeLog, previous: &ProbeLog) {
+    let targets: Vec<(String, HealthState)> = backends
+        .get_backend()
+        .iter()
+        .map(|backend| {
+            let addr = backend.addr.to_string();
+            let state = previous.state(&addr);
+            (addr, state)
+        })
+        .collect();
+    if targets
+        .iter()
+        .all(|(_, state)| *state == HealthState::default())
+    {
+        return;
+    }
+
+    let rounds = targets
+        .iter()
+        .map(|(_, state)| state.counter)
+        .max()
+        .unwrap_or(0);
+    let mut scripts = vec![Replay {
+        results: targets
+            .iter()
+            .map(|(addr, state)| (addr.clone(), state.healthy))
+            .collect(),
+        threshold: 1,
+    }];
+    // A probe agreeing with the state resets the counter, so disagreeing ones come last.
+    scripts.extend((0..rounds).map(|round| {
+        Replay {
+            results: targets
+                .iter()
+                .map(|(addr, state)| {
+                    (
+                        addr.clone(),
+                        state.healthy ^ (round >= rounds - state.counter),
+                    )
+                })
+                .collect(),
+            threshold: usize::MAX,
+        }
+    }));
+    for script in scripts {
+        REPLAY.with(|replay| *replay.borrow_mut() = Some(script));
+        let replayed = backends.run_health_check(false).now_or_never().is_some();
+        REPLAY.with(|replay| *replay.borrow_mut() = None);
+        if !replayed {
+            log::warn!("Health check state replay did not complete; probes will catch up");
+            break;
+        }
+    }
+
+    if std::ptr::eq(log, previous) {
+        return;
+    }
+    for (addr, state) in targets {
+        log.states.insert(addr.clone(), state);
+        if let Some(probe) = previous.get(&addr) {
+            log.backends.insert(addr, probe);
+        }
+    }
+}
+
+/// Health check wrapper that records every probe in a [`ProbeLog`].
+pub struct RecordedHealthCheck {
+    inner: Box<dyn HealthCheckTrait + Send + Sync>,
+    log: Arc<ProbeLog>,
+}
+
+impl RecordedHealthCheck {
+    pub fn new(inner: Box<dyn HealthCheckTrait + Send + Sync>, log: Arc<ProbeLog>) -> Self {
+        Self { inner, log }
+    }
+}
+
+#[async_trait]
+impl HealthCheckTrait for RecordedHealthCheck {
+    async fn check(&self, target: &Backend) -> pingora_error::Result<()> {
+        let addr = target.addr.to_string();
+        let scripted = REPLAY.with(|replay| {
+            replay
+                .borrow()
+                .as_ref()
+                .map(|replay| replay.results.get(&addr).copied().unwrap_or(true))
+        });
+        match scripted {
+            Some(true) => return Ok(()),
+            Some(false) => {
+                return Error::e_explain(
+                    ErrorType::HTTPStatus(503),
+                    "unhealthy before the upstream was replaced",
+                )
+            }
+            None => {}
+        }
+        let result = self.inner.check(target).await;
+        let threshold = self.inner.health_threshold(result.is_ok());
+        self.log.record(addr, &result, threshold);
+        result
+    }
+
+    async fn health_status_change(&self, target: &Backend, healthy: bool) {
+        self.inner.health_status_change(target, healthy).await
+    }
+
+    fn backend_summary(&self, target: &Backend) -> String {
+        self.inner.backend_summary(target)
+    }
+
+    fn health_threshold(&self, success: bool) -> usize {
+        REPLAY
+            .with(|replay| replay.borrow().as_ref().map(|replay| replay.threshold))
+            .unwrap_or_else(|| self.inner.health_threshold(success))
+    }
+}
+
+const GRPC_HEALTH_PATH: &str = "/grpc.health.v1.Health/Check";
+
+/// Largest health check response body read before the check fails.
+const MAX_GRPC_HEALTH_RESPONSE: usize = 4096;
+
+/// Messages of the standard `grpc.health.v1` health checking protocol.
+mod proto {
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct HealthCheckRequest {
+        #[prost(string, tag = "1")]
+        pub service: String,
+    }
+
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct HealthCheckResponse {
+        #[prost(int32, tag = "1")]
+        pub status: i32,
+    }
+
+    pub const SERVING: i32 = 1;
+}
+
+/// Health check calling `grpc.health.v1.Health/Check` on each backend over HTTP/2;
+/// a backend is healthy when it answers `SERVING`.
+pub struct GrpcHealthCheck {
+    /// Number of successful checks to flip from unhealthy to healthy.
+    pub consecutive_success: usize,
+    /// Number of failed checks to flip from healthy to unhealthy.
+    pub consecutive_failure: usize,
+    /// Connection settings; the address is replaced by the backend's.
+    pub peer_template: HttpPeer,
+    /// Check this port of the backend IP instead of the backend's own.
+    pub port_override: Option<u16>,
+    /// `:authority` of the request; the backend address when empty.
+    pub authority: String,
+    /// Service whose status is asked for; empty asks about the server as a whole.
+    pub service: String,
+    connector: HttpConnector,
+}
+
+impl GrpcHealthCheck {
+    /// A check over h2c, or over TLS with `authority` as SNI when `tls` is set, with one
+    /// second timeouts and single-check thresholds.
+    pub fn new(authority: &str, tls: bool) -> Self {
+        let sni = if tls { authority } else { "" };
+        let mut peer_template = HttpPeer::new("0.0.0.0:1", tls, sni.to_string());
+        peer_template.options.alpn = ALPN::H2;
+        peer_template.options.connection_timeout = Some(Duration::from_secs(1));
+        peer_template.options.read_timeout = Some(Duration::from_secs(1));
+        Self {
+            consecutive_success: 1,
+            consecutive_failure: 1,
+            peer_template,
+            port_override: None,
+            authority: authority.to_string(),
+            service: String::new(),
+            connector: HttpConnector::new(None),
+        }
+    }
+
+    fn request_body(&self) -> Bytes {
+        let message = proto::HealthCheckRequest {
+            service: self.service.clone(),
+        }
+        .encode_to_vec();
+        let mut frame = BytesMut::with_capacity(5 + message.len());
+        frame.put_u8(0);
+        frame.put_u32(message.len() as u32);
+        frame.put_slice(&message);
+        frame.freeze()
+    }
+}
+
+fn grpc_health_error(context: impl Into<String>) -> Box<Error> {
+    Error::explain(
+        ErrorType::Custom("grpc health check failed"),
+        context.into(),
+    )
+}
+
+/// Status of the `HealthCheckResponse` in the first gRPC message of `body`.
+fn serving_status(body: &[u8]) -> pingora_error::Result<i32> {
+    let Some((&[0, a, b, c, d], rest)) = body.split_first_chunk::<5>() else {
+        return Err(grpc_health_error("missing or compressed response message"));
+    };
+    let len = u32::from_be_bytes([a, b, c, d]) as usize;
+    let message = rest
+        .get(..len)
+        .ok_or_else(|| grpc_health_error("truncated response message"))?;
+    proto::HealthCheckResponse::decode(message)
+        .map(|response| response.status)
+        .map_err(|e| grpc_health_error(format!("invalid response message: {e}")))
+}
+
+#[async_trait]
+impl HealthCheckTrait for GrpcHealthCheck {
+    async fn check(&self, target: &Backend) -> pingora_error::Result<()> {
+        let mut peer = self.peer_template.clone();
+        peer._address = target.addr.clone();
+        if let Some(port) = self.port_override {
+            peer._address.set_port(port);
+        }
+        let HttpSession::H2(mut session) = self.connector.get_http_session(&peer).await?.0 else {
+            return Err(grpc_health_error("backend did not negotiate HTTP/2"));
+        };
+        session.read_timeout = peer.options.read_timeout;
+
+        let authority = if self.authority.is_empty() {
+            peer._address.to_string()
+        } else {
+            self.authority.clone()
+        };
+        let mut req = RequestHeader::build("POST", GRPC_HEALTH_PATH.as_bytes(), None)?;
+        req.insert_header(header::HOST, authority)?;
+        req.insert_header(header::CONTENT_TYPE, "application/grpc")?;
+        req.insert_header(header::TE, "trailers")?;
+        session.write_request_header(Box::new(req), false)?;
+        session
+            .write_request_body(self.request_body(), true)
+            .await?;
+
+        session.read_response_header().await?;
+        let resp = session.response_header().expect("just read");
+        if resp.status != 200 {
+            return Error::e_explain(
+                ErrorType::CustomCode("non 200 code", resp.status.as_u16()),
+                "during grpc healthcheck",
+            );
+        }
+        // A trailers-only response carries the status in its headers.
+        let mut grpc_status = resp.headers.get("grpc-status").cloned();
+        let mut body = BytesMut::new();
+        while let Some(chunk) = session.read_response_body().await? {
+            body.extend_from_slice(&chunk);
+            if body.len() > MAX_GRPC_HEALTH_RESPONSE {
+                return Err(grpc_health_error("response too large"));
+            }
+        }
+        if let Some(trailers) = session.read_trailers().await? {
+            grpc_status = trailers.get("grpc-status").cloned().or(grpc_status);
+        }
+        match grpc_status.as_ref().map(|status| status.as_bytes()) {
+            Some(b"0") => {}
+            Some(status) => {
+                return Err(grpc_health_error(format!(
+                    "grpc-status {}",
+                    String::from_utf8_lossy(status)
+                )))
+            }
+            None => return Err(grpc_health_error("missing grpc-status")),
+        }
+
+        match serving_status(&body)? {
+            proto::SERVING => Ok(()),
+            status => Err(grpc_health_error(format!("serving status {status}"))),
+        }
+    }
+
+    fn health_threshold(&self, success: bool) -> usize {
+        if success {
+            self.consecutive_success
+        } else {
+            self.consecutive_failure
+        }
+    }
+}
+
 pub static SHARED_HEALTH_CHECK_SERVICE: Lazy<SharedHealthCheckService> =
     Lazy::new(SharedHealthCheckService::new);
 
@@ -544,4 +960,109 @@ mod tests {
             running.handle.abort();
         }
     }
+
+    #[test]
+    fn probe_log_tracks_failures_until_success() {
+        let log = ProbeLog::default();
+        let err =
+            || pingora_error::Error::e_explain(pingora_error::ErrorType::ConnectRefused, "down");
+        log.record("10.0.0.1:80".into(), &err(), 1);
+        log.record("10.0.0.1:80".into(), &err(), 1);
+        let probe = log.get("10.0.0.1:80").unwrap();
+        assert_eq!((probe.consecutive_failures, probe.failures), (2, 2));
+        assert!(probe.last_error.is_some() && probe.last_probe.is_some());
+
+        log.record("10.0.0.1:80".into(), &Ok(()), 1);
+        let probe = log.get("10.0.0.1:80").unwrap();
+        assert_eq!(
+            (probe.consecutive_failures, probe.failures, probe.successes),
+            (0, 2, 1)
+        );
+        assert!(probe.last_error.is_none());
+
+        log.retain(|addr| addr != "10.0.0.1:80");
+        assert!(log.get("10.0.0.1:80").is_none());
+    }
+
+    struct SwitchedCheck(Arc<std::sync::atomic::AtomicBool>);
+
+    #[async_trait]
+    impl HealthCheckTrait for SwitchedCheck {
+        async fn check(&self, _target: &Backend) -> pingora_error::Result<()> {
+            if self.0.load(Ordering::Relaxed) {
+                Ok(())
+            } else {
+                Error::e_explain(ErrorType::ConnectRefused, "down")
+            }
+        }
+
+        fn health_threshold(&self, _success: bool) -> usize {
+            2
+        }
+    }
+
+    async fn probed_backends(up: &Arc<std::sync::atomic::AtomicBool>) -> (Backends, Arc<ProbeLog>) {
+        let log = Arc::new(ProbeLog::default());
+        let mut backends = Backends::new(
+            pingora_load_balancing::discovery::Static::try_from_iter(["10.0.0.1:80"]).unwrap(),
+        );
+        backends.set_health_check(Box::new(RecordedHealthCheck::new(
+            Box::new(SwitchedCheck(up.clone())),
+            log.clone(),
+        )));
+        backends.update(|_| {}).await.unwrap();
+        (backends, log)
+    }
+
+    #[tokio::test]
+    async fn replacement_inherits_health_and_counter() {
+        let up = Arc::new(std::sync::atomic::AtomicBool::new(false));
+        let (old, old_log) = probed_backends(&up).await;
+        old.run_health_check(false).await;
+        old.run_health_check(false).await;
+        up.store(true, Ordering::Relaxed);
+        old.run_health_check(false).await;
+        let backend = Backend::new("10.0.0.1:80").unwrap();
+        assert!(!old.ready(&backend));
+        let inherited = HealthState {
+            healthy: false,
+            counter: 1,
+        };
+        assert_eq!(old_log.state("10.0.0.1:80"), inherited);
+
+        let (new, log) = probed_backends(&up).await;
+        inherit_health(&new, &log, &old_log);
+        assert!(!new.ready(&backend), "unhealthy state carries over");
+        assert_eq!(log.state("10.0.0.1:80"), inherited);
+        assert_eq!(log.get("10.0.0.1:80").unwrap().failures, 2);
+
+        // The carried-over counter completes the threshold with one more success.
+        new.run_health_check(false).await;
+        assert!(new.ready(&backend));
+        assert_eq!(log.state("10.0.0.1:80"), HealthState::default());
+    }
+
+    #[test]
+    fn grpc_health_messages_are_framed() {
+        let mut check = GrpcHealthCheck::new("", false);
+        assert_eq!(&check.request_body()[..], &[0, 0, 0, 0, 0]);
+        check.service = "echo".to_string();
+        assert_eq!(
+            &check.request_body()[..],
+            &[0, 0, 0, 0, 6, 0x0a, 4, b'e', b'c', b'h', b'o']
+        );
+
+        let serving = [0, 0, 0, 0, 2, 0x08, proto::SERVING as u8];
+        assert_eq!(serving_status(&serving).unwrap(), proto::SERVING);
+        assert_eq!(serving_status(&[0, 0, 0, 0, 2, 0x08, 2]).unwrap(), 2);
+        assert!(serving_status(&[]).is_err());
+        assert!(
+            serving_status(&[1, 0, 0, 0, 2, 0x08, 1]).is_err(),
+            "compressed"
+        );
+        assert!(
+            serving_status(&[0, 0, 0, 0, 9, 0x08, 1]).is_err(),
+            "truncated"
+        );
+    }
 }
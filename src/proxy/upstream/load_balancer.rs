@@ -1,9 +1,22 @@
-use std::{sync::Arc, time::Duration};
+use std::{
+    collections::{BTreeSet, HashMap},
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc, Mutex,
+    },
+    time::{Duration, Instant},
+};
 
+use arc_swap::ArcSwap;
+use dashmap::DashMap;
 use futures::FutureExt;
 use http::Uri;
+use once_cell::sync::Lazy;
 use pingora::services::background::background_service;
-use pingora_core::upstreams::peer::HttpPeer;
+use pingora_core::{
+    protocols::l4::socket::SocketAddr,
+    upstreams::peer::{HttpPeer, Peer},
+};
 use pingora_error::Error;
 use pingora_http::{RequestHeader, ResponseHeader};
 use pingora_load_balancing::{
@@ -14,16 +27,33 @@ use pingora_load_balancing::{
     Backend, Backends, LoadBalancer,
 };
 use pingora_proxy::Session;
+use prometheus::{register_int_counter_vec, IntCounterVec};
+use serde::Serialize;
+use sha2::{Digest, Sha256};
 
 use crate::{
     config::{self, Identifiable},
-    core::{ProxyError, ProxyResult, UpstreamSelector},
-    utils::request::request_selector_key,
+    core::{expand_route_params, ProxyError, ProxyResult, UpstreamSelector},
+    utils::request::{get_cookie_value, request_selector_key},
 };
 
 #[cfg(test)]
 use super::discovery::prepare_static_upstream;
-use super::discovery::{HybridDiscovery, PreparedUpstream, SeededDiscovery};
+use super::discovery::{
+    HybridDiscovery, NodePriority, NodeZone, PreparedUpstream, SeededDiscovery,
+};
+use super::drain;
+use super::health_check::{self, BackendProbe, GrpcHealthCheck, ProbeLog, RecordedHealthCheck};
+
+/// Zone-aware selections by locality of the chosen backend relative to the gateway.
+static ZONE_SELECTIONS: Lazy<IntCounterVec> = Lazy::new(|| {
+    register_int_counter_vec!(
+        "pingsix_upstream_zone_selections_total",
+        "Zone-aware upstream selections by locality",
+        &["locality"]
+    )
+    .expect("zone selection metric registration must succeed")
+});
 
 /// Runs a closure over the inner LB for any SelectionLB variant, eliminating repetitive match arms.
 macro_rules! with_lb {
@@ -33,6 +63,7 @@ macro_rules! with_lb {
             SelectionLB::Random($lb_var) => $body,
             SelectionLB::Fnv($lb_var) => $body,
             SelectionLB::Ketama($lb_var) => $body,
+            SelectionLB::Loaded($lb_var, _) => $body,
         }
     };
 }
@@ -45,6 +76,29 @@ pub struct ProxyUpstream {
     lb: SelectionLB,
     /// Stable fingerprint of origin-identity fields used for cache namespacing.
     cache_origin_fingerprint: u64,
+    /// Gateway zone and spill-over threshold when zone-aware selection applies.
+    zone_policy: Option<ZonePolicy>,
+    /// Whether nodes carry failover priorities.
+    prioritized: bool,
+    /// Nodes to install into the shared load balancer once this upstream is published.
+    staged_nodes: Mutex<Option<(HybridDiscovery, PreparedUpstream)>>,
+}
+
+/// One backend in `GET /status/upstreams`.
+#[derive(Debug, Serialize)]
+pub struct BackendHealth {
+    pub address: String,
+    pub weight: usize,
+    /// Selectable: passing health checks and not drained.
+    pub healthy: bool,
+    pub drained: bool,
+    #[serde(flatten)]
+    pub probe: BackendProbe,
+}
+
+struct ZonePolicy {
+    local_zone: String,
+    min_healthy_percent: u8,
 }
 
 /// Fingerprint of every upstream field that can change which origin is contacted
@@ -78,8 +132,12 @@ pub(crate) fn cache_origin_fingerprint(upstream: &config::Upstream) -> u64 {
     if let Some(tls) = &upstream.tls {
         // Digest PEM material so client identity changes invalidate cache without
         // embedding secrets in the key.
-        crate::core::secret_digest(&tls.client_cert).hash(&mut hasher);
-        crate::core::secret_digest(&tls.client_key).hash(&mut hasher);
+        let digest = |pem: &Option<String>| pem.as_deref().map(crate::core::secret_digest);
+        digest(&tls.client_cert).hash(&mut hasher);
+        digest(&tls.client_key).hash(&mut hasher);
+        tls.sni.hash(&mut hasher);
+        tls.verify.hash(&mut hasher);
+        tls.server_name.hash(&mut hasher);
     } else {
         0u8.hash(&mut hasher);
     }
@@ -117,16 +175,130 @@ impl ProxyUpstream {
             log::debug!("Generated ID for inline upstream: {}", upstream.id);
         }
 
-        let cache_origin_fingerprint = cache_origin_fingerprint(&upstream);
         let lb = SelectionLB::from_prepared(upstream.clone(), prepared).map_err(|e| {
             ProxyError::Configuration(format!("Failed to create load balancer: {e}"))
         })?;
 
-        Ok(ProxyUpstream {
+        Ok(Self::with_lb(upstream, lb, None))
+    }
+
+    fn with_lb(
+        upstream: config::Upstream,
+        lb: SelectionLB,
+        staged_nodes: Option<(HybridDiscovery, PreparedUpstream)>,
+    ) -> Self {
+        let zone_policy = upstream
+            .zone_aware
+            .as_ref()
+            .zip(config::gateway_zone())
+            .map(|(zone_aware, local_zone)| ZonePolicy {
+                local_zone: local_zone.to_string(),
+                min_healthy_percent: zone_aware.min_healthy_percent,
+            });
+
+        ProxyUpstream {
+            cache_origin_fingerprint: cache_origin_fingerprint(&upstream),
+            prioritized: !upstream.priorities.is_empty(),
             inner: upstream,
             lb,
-            cache_origin_fingerprint,
-        })
+            zone_policy,
+            staged_nodes: Mutex::new(staged_nodes),
+        }
+    }
+
+    /// Build the replacement of this upstream from prepared material.
+    ///
+    /// When `upstream` only changes nodes, the replacement shares this upstream's load
+    /// balancer, so health, drain and load state of the nodes it keeps carry over. The
+    /// new nodes are staged and only installed by [`Self::commit_nodes`] when the
+    /// replacement is published; until then both serve the current nodes. Otherwise a
+    /// new load balancer starts from the health this one's probes tracked.
+    pub(crate) fn rebuild(
+        &self,
+        upstream: config::Upstream,
+        prepared: PreparedUpstream,
+    ) -> ProxyResult<Self> {
+        if !self.differs_only_in_nodes(&upstream) {
+            return self.replace(upstream, prepared);
+        }
+        let refresh: HybridDiscovery = upstream.clone().try_into()?;
+        // DNS refresh is scheduled when the load balancer is created.
+        let refreshes = refresh.resolves_dns() && config::dns_refresh_interval().is_some();
+        if refreshes != with_lb!(&self.lb, |lb| lb.upstreams.update_frequency.is_some()) {
+            return self.replace(upstream, prepared);
+        }
+        Ok(Self::with_lb(
+            upstream,
+            self.lb.clone(),
+            Some((refresh, prepared)),
+        ))
+    }
+
+    fn replace(&self, upstream: config::Upstream, prepared: PreparedUpstream) -> ProxyResult<Self> {
+        let replacement = Self::build(upstream, prepared)?;
+        if let Some(previous) = with_lb!(&self.lb, |lb| lb.probes.clone()) {
+            with_lb!(&replacement.lb, |lb| lb.inherit_health(&previous));
+        }
+        Ok(replacement)
+    }
+
+    /// Whether `upstream` equals this one apart from its nodes. Labels of nodes in both
+    /// must not change: the load balancer keeps backends it already knows as they are.
+    fn differs_only_in_nodes(&self, upstream: &config::Upstream) -> bool {
+        let kept = |node: &String| {
+            self.inner.nodes.contains_key(node) && upstream.nodes.contains_key(node)
+        };
+        let without_nodes = |upstream: &config::Upstream| {
+            let mut upstream = upstream.clone();
+            upstream.nodes.clear();
+            upstream.priorities.retain(|node, _| kept(node));
+            if let Some(zone_aware) = upstream.zone_aware.as_mut() {
+                zone_aware.zones.retain(|node, _| kept(node));
+            }
+            upstream
+        };
+        without_nodes(&self.inner) == without_nodes(upstream)
+    }
+
+    /// Install the nodes staged by [`Self::rebuild`] into the shared load balancer.
+    pub(crate) fn commit_nodes(&self) {
+        let staged = self
+            .staged_nodes
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .take();
+        let Some((refresh, prepared)) = staged else {
+            return;
+        };
+        if let Err(e) = with_lb!(&self.lb, |lb| lb.reseed(&self.inner.id, prepared, refresh)) {
+            log::error!("{e}");
+        }
+    }
+
+    /// Current backends with the health state the load balancer selects by.
+    pub(crate) fn backend_health(&self) -> Vec<BackendHealth> {
+        with_lb!(&self.lb, |lb| lb.backend_health(&self.inner.id))
+    }
+
+    /// Addresses of the backends the nodes currently resolve to, sorted.
+    pub(crate) fn backend_addresses(&self) -> Vec<String> {
+        let mut addresses: Vec<String> = with_lb!(&self.lb, |lb| lb
+            .upstreams
+            .backends()
+            .get_backend()
+            .iter()
+            .map(|backend| backend.addr.to_string())
+            .collect());
+        addresses.sort();
+        addresses
+    }
+
+    /// Re-apply the runtime drain state after a node was drained or re-enabled.
+    pub(crate) fn apply_drains(&self) {
+        with_lb!(&self.lb, |lb| drain::apply(
+            &self.inner.id,
+            lb.upstreams.backends()
+        ))
     }
 
     pub(crate) fn health_check_service(
@@ -138,21 +310,139 @@ impl ProxyUpstream {
     /// Test helper: select a backend without a full proxy session.
     #[cfg(test)]
     pub(crate) fn select_backend_for_test(&self) -> Option<Backend> {
-        let mut backend = with_lb!(&self.lb, |lb| lb.upstreams.select(b"", 256));
+        let mut backend = match &self.lb {
+            SelectionLB::Loaded(lb, loads) => {
+                self.select_zoned(&lb.upstreams, |accept| loads.acquire(&lb.upstreams, accept))
+            }
+            lb => with_lb!(lb, |lb| self.select_from(&lb.upstreams, b"")),
+        };
         if let Some(backend) = backend.as_mut() {
             if let Some(peer) = backend.ext.get_mut::<HttpPeer>() {
                 self.set_timeout(peer);
+                self.set_tls(peer);
             }
         }
         backend
     }
 
+    /// The backend the request's affinity cookie pins it to, while that backend is
+    /// ready; otherwise normal selection applies and a new cookie is issued.
+    ///
+    /// A pinned backend is used even when node priorities or the local zone would
+    /// prefer another one: affinity outranks both.
+    fn pinned_backend(&self, req: &RequestHeader) -> Option<Backend> {
+        let sticky = self.inner.sticky.as_ref()?;
+        let token = get_cookie_value(req, &sticky.cookie)?;
+        let backend = with_lb!(&self.lb, |lb| {
+            let backends = lb.upstreams.backends();
+            lb.affinity_index()
+                .backends
+                .get(token)
+                .filter(|backend| backends.ready(backend))
+                .cloned()
+        })?;
+        self.count_in_flight(&backend);
+        Some(backend)
+    }
+
+    /// Count a backend picked outside the balancer as in flight, so `release_backend`
+    /// balances it like any other selection.
+    fn count_in_flight(&self, backend: &Backend) {
+        if let SelectionLB::Loaded(_, loads) = &self.lb {
+            loads
+                .loads
+                .entry(backend.addr.clone())
+                .or_default()
+                .in_flight += 1;
+        }
+    }
+
+    /// Select a backend, preferring the gateway's own zone when zone-aware.
+    fn select_from<BS>(&self, lb: &LoadBalancer<BS>, key: &[u8]) -> Option<Backend>
+    where
+        BS: BackendSelection + 'static,
+        BS::Iter: BackendIter,
+    {
+        self.select_zoned(lb, |accept| {
+            lb.select_with(key, 256, |backend, healthy| healthy && accept(backend))
+        })
+    }
+
+    /// Run `pick` (which returns a healthy backend passing the filter) within the active
+    /// priority tier, with zone preference.
+    ///
+    /// With node priorities, only the highest priority that still has a healthy backend
+    /// is eligible. Local healthy backends win while the local zone meets
+    /// `min_healthy_percent`; otherwise selection falls back to every healthy backend
+    /// across zones.
+    fn select_zoned<BS>(
+        &self,
+        lb: &LoadBalancer<BS>,
+        pick: impl Fn(&dyn Fn(&Backend) -> bool) -> Option<Backend>,
+    ) -> Option<Backend>
+    where
+        BS: BackendSelection + 'static,
+        BS::Iter: BackendIter,
+    {
+        let tier = if self.prioritized {
+            active_priority(lb)
+        } else {
+            None
+        };
+        let in_tier = |backend: &Backend| tier.is_none_or(|tier| node_priority(backend) == tier);
+        // Hash selection walks a bounded number of candidates; when none of them is in
+        // the tier, any healthy backend beats failing the request.
+        let pick_in_tier =
+            || pick(&in_tier).or_else(|| tier.and_then(|_| pick(&|_: &Backend| true)));
+
+        let Some(policy) = &self.zone_policy else {
+            return pick_in_tier();
+        };
+        let is_local = |backend: &Backend| {
+            in_tier(backend)
+                && backend
+                    .ext
+                    .get::<NodeZone>()
+                    .is_some_and(|zone| zone.0 == policy.local_zone)
+        };
+
+        if local_zone_has_capacity(lb, policy.min_healthy_percent, is_local) {
+            if let Some(backend) = pick(&is_local) {
+                ZONE_SELECTIONS.with_label_values(&["local"]).inc();
+                return Some(backend);
+            }
+        }
+
+        let backend = pick_in_tier()?;
+        let locality = if is_local(&backend) {
+            "local"
+        } else {
+            "cross_zone"
+        };
+        ZONE_SELECTIONS.with_label_values(&[locality]).inc();
+        Some(backend)
+    }
+
+    /// Applies the `tls` SNI and verification overrides to an `HttpPeer`.
+    fn set_tls(&self, p: &mut HttpPeer) {
+        let Some(tls) = &self.inner.tls else {
+            return;
+        };
+        if let Some(sni) = &tls.sni {
+            p.sni.clone_from(sni);
+        }
+        p.options.verify_cert = tls.verify;
+        p.options.verify_hostname = tls.verify;
+        p.options.alternative_cn.clone_from(&tls.server_name);
+    }
+
     /// Sets the finite upstream/global/built-in timeout for an `HttpPeer`.
     fn set_timeout(&self, p: &mut HttpPeer) {
         let config::Timeout {
             connect,
             read,
             send,
+            ..
         } = config::resolve_upstream_timeout(
             self.inner.timeout.clone(),
             config::default_upstream_timeout(),
@@ -166,32 +456,64 @@ impl ProxyUpstream {
 // Implementation of UpstreamSelector trait for decoupling from core module
 impl UpstreamSelector for ProxyUpstream {
     fn select_backend<'a>(&'a self, session: &'a mut Session) -> Option<Backend> {
-        let mut backend = match &self.lb {
-            SelectionLB::RoundRobin(lb) => lb.upstreams.select(b"", 256),
-            SelectionLB::Random(lb) => lb.upstreams.select(b"", 256),
-            SelectionLB::Fnv(lb) => {
-                let key =
-                    request_selector_key(session, &self.inner.hash_on, self.inner.key.as_str());
-                log::debug!("proxy lb key: {key}");
-                lb.upstreams.select(key.as_bytes(), 256)
-            }
-            SelectionLB::Ketama(lb) => {
-                let key =
-                    request_selector_key(session, &self.inner.hash_on, self.inner.key.as_str());
-                log::debug!("proxy lb key: {key}");
-                lb.upstreams.select(key.as_bytes(), 256)
-            }
-        };
+        let mut backend = self
+            .pinned_backend(session.req_header())
+            .or_else(|| match &self.lb {
+                SelectionLB::RoundRobin(lb) => self.select_from(&lb.upstreams, b""),
+                SelectionLB::Random(lb) => self.select_from(&lb.upstreams, b""),
+                SelectionLB::Fnv(lb) => {
+                    let key =
+                        request_selector_key(session, &self.inner.hash_on, self.inner.key.as_str());
+                    log::debug!("proxy lb key: {key}");
+                    self.select_from(&lb.upstreams, key.as_bytes())
+                }
+                SelectionLB::Ketama(lb) => {
+                    let key =
+                        request_selector_key(session, &self.inner.hash_on, self.inner.key.as_str());
+                    log::debug!("proxy lb key: {key}");
+                    self.select_from(&lb.upstreams, key.as_bytes())
+                }
+                SelectionLB::Loaded(lb, loads) => {
+                    self.select_zoned(&lb.upstreams, |accept| loads.acquire(&lb.upstreams, accept))
+                }
+            });
 
         if let Some(backend) = backend.as_mut() {
             if let Some(peer) = backend.ext.get_mut::<HttpPeer>() {
                 self.set_timeout(peer);
+                self.set_tls(peer);
             }
         }
 
         backend
     }
 
+    fn upstream_id(&self) -> &str {
+        &self.inner.id
+    }
+
+    fn select_node(&self, node: &str) -> Option<Backend> {
+        let mut backend = with_lb!(&self.lb, |lb| lb
+            .upstreams
+            .backends()
+            .get_backend()
+            .iter()
+            .find(|backend| backend.addr.to_string() == node)
+            .cloned())?;
+        self.count_in_flight(&backend);
+        if let Some(peer) = backend.ext.get_mut::<HttpPeer>() {
+            self.set_timeout(peer);
+            self.set_tls(peer);
+        }
+        Some(backend)
+    }
+
+    fn release_backend(&self, peer: &HttpPeer, response_time: Option<Duration>) {
+        if let SelectionLB::Loaded(_, loads) = &self.lb {
+            loads.release(peer.address(), response_time);
+        }
+    }
+
     fn get_retries(&self) -> Option<usize> {
         self.inner.retries.map(|r| r as _)
     }
@@ -204,10 +526,15 @@ impl UpstreamSelector for ProxyUpstream {
         &self.inner.pass_host
     }
 
-    fn upstream_host_rewrite(&self, upstream_request: &mut RequestHeader) {
+    fn upstream_host_rewrite(
+        &self,
+        upstream_request: &mut RequestHeader,
+        route_params: Option<&[(String, String)]>,
+    ) {
         if self.inner.pass_host == config::UpstreamPassHost::REWRITE {
             if let Some(host) = &self.inner.upstream_host {
-                if let Err(e) = upstream_request.insert_header(http::header::HOST, host) {
+                let host = expand_route_params(host, route_params);
+                if let Err(e) = upstream_request.insert_header(http::header::HOST, host.as_ref()) {
                     log::error!("Failed to rewrite upstream host header: {e}");
                 }
             }
@@ -217,6 +544,237 @@ impl UpstreamSelector for ProxyUpstream {
     fn cache_isolation_key(&self) -> String {
         format!("{:x}", self.cache_origin_fingerprint)
     }
+
+    fn affinity_cookie(&self, req: &RequestHeader, peer: &HttpPeer) -> Option<String> {
+        let sticky = self.inner.sticky.as_ref()?;
+        let token = with_lb!(&self.lb, |lb| lb
+            .affinity_index()
+            .tokens
+            .get(peer.address())
+            .cloned())
+        .unwrap_or_else(|| affinity_token(peer.address()));
+        if get_cookie_value(req, &sticky.cookie) == Some(token.as_str()) {
+            return None;
+        }
+        let mut cookie = format!("{}={token}; Path=/; HttpOnly", sticky.cookie);
+        if sticky.ttl > 0 {
+            cookie.push_str(&format!("; Max-Age={}", sticky.ttl));
+        }
+        Some(cookie)
+    }
+}
+
+/// Opaque affinity cookie value of a backend address, stable across gateway instances.
+fn affinity_token(addr: &SocketAddr) -> String {
+    let digest = Sha256::digest(addr.to_string().as_bytes());
+    hex::encode(&digest[..8])
+}
+
+/// Affinity tokens of one backend set, computed once per set rather than per request.
+struct AffinityIndex {
+    /// The set the tokens were computed for.
+    source: Arc<BTreeSet<Backend>>,
+    backends: HashMap<String, Backend>,
+    tokens: HashMap<SocketAddr, String>,
+}
+
+impl AffinityIndex {
+    fn new(source: Arc<BTreeSet<Backend>>) -> Self {
+        let mut backends = HashMap::with_capacity(source.len());
+        let mut tokens = HashMap::with_capacity(source.len());
+        for backend in source.iter() {
+            let token = affinity_token(&backend.addr);
+            tokens.insert(backend.addr.clone(), token.clone());
+            backends.insert(token, backend.clone());
+        }
+        Self {
+            source,
+            backends,
+            tokens,
+        }
+    }
+}
+
+/// Highest priority among healthy backends.
+fn active_priority<BS>(lb: &LoadBalancer<BS>) -> Option<i32>
+where
+    BS: BackendSelection + 'static,
+    BS::Iter: BackendIter,
+{
+    lb.backends()
+        .get_backend()
+        .iter()
+        .filter(|backend| lb.backends().ready(backend))
+        .map(node_priority)
+        .max()
+}
+
+fn node_priority(backend: &Backend) -> i32 {
+    backend
+        .ext
+        .get::<NodePriority>()
+        .map_or(0, |priority| priority.0)
+}
+
+/// Whether enough local backends are healthy to keep traffic in the gateway's zone.
+fn local_zone_has_capacity<BS>(
+    lb: &LoadBalancer<BS>,
+    min_healthy_percent: u8,
+    is_local: impl Fn(&Backend) -> bool,
+) -> bool
+where
+    BS: BackendSelection + 'static,
+    BS::Iter: BackendIter,
+{
+    if min_healthy_percent == 0 {
+        return true;
+    }
+    let backends = lb.backends().get_backend();
+    let (total, healthy) = backends
+        .iter()
+        .filter(|backend| is_local(backend))
+        .fold((0usize, 0usize), |(total, healthy), backend| {
+            (total + 1, healthy + lb.backends().ready(backend) as usize)
+        });
+    total > 0 && healthy * 100 >= total * min_healthy_percent as usize
+}
+
+/// Time constant of the response-time average; a sample's influence fades over ~10s.
+const EWMA_DECAY: Duration = Duration::from_secs(10);
+/// Response time assumed for a backend without samples, so new nodes are tried
+/// without being flooded.
+const EWMA_DEFAULT_RTT: Duration = Duration::from_millis(30);
+
+/// What `BackendLoads` minimizes when selecting.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum LoadMetric {
+    /// In-flight requests per unit of weight (`least_conn`).
+    InFlight,
+    /// Peak-EWMA response time times in-flight requests per unit of weight (`latency`).
+    PeakEwma,
+}
+
+/// Load observed on one backend.
+struct BackendLoad {
+    in_flight: usize,
+    /// Peak-sensitive moving average of the response time, in nanoseconds.
+    ewma_nanos: f64,
+    updated: Instant,
+}
+
+impl Default for BackendLoad {
+    fn default() -> Self {
+        Self {
+            in_flight: 0,
+            ewma_nanos: EWMA_DEFAULT_RTT.as_nanos() as f64,
+            updated: Instant::now(),
+        }
+    }
+}
+
+impl BackendLoad {
+    /// Fold in a response time. Slower samples replace the average at once, faster
+    /// ones decay it by the time since the last sample, so a degrading backend is
+    /// avoided immediately and recovers gradually.
+    fn observe(&mut self, sample: Duration) {
+        let now = Instant::now();
+        let sample = sample.as_nanos() as f64;
+        if sample > self.ewma_nanos {
+            self.ewma_nanos = sample;
+        } else {
+            let elapsed = now.saturating_duration_since(self.updated).as_secs_f64();
+            let weight = (-elapsed / EWMA_DECAY.as_secs_f64()).exp();
+            self.ewma_nanos = self.ewma_nanos * weight + sample * (1.0 - weight);
+        }
+        self.updated = now;
+    }
+
+    fn cost(&self, metric: LoadMetric, weight: usize) -> f64 {
+        let pending = (self.in_flight + 1) as f64 / weight.max(1) as f64;
+        match metric {
+            LoadMetric::InFlight => pending,
+            LoadMetric::PeakEwma => self.ewma_nanos * pending,
+        }
+    }
+}
+
+/// Per-backend load for `least_conn` and `latency` selection.
+///
+/// Loads belong to one upstream build; requests still running on a replaced build
+/// release into the loads they were selected from.
+struct BackendLoads {
+    metric: LoadMetric,
+    loads: DashMap<SocketAddr, BackendLoad>,
+    /// Rotates the starting point so ties do not always land on the same backend.
+    cursor: AtomicUsize,
+}
+
+impl BackendLoads {
+    fn new(metric: LoadMetric) -> Self {
+        Self {
+            metric,
+            loads: DashMap::new(),
+            cursor: AtomicUsize::new(0),
+        }
+    }
+
+    /// Pick the healthy backend with the lowest cost and count the new request against it.
+    fn acquire(
+        &self,
+        lb: &LoadBalancer<RoundRobin>,
+        accept: &dyn Fn(&Backend) -> bool,
+    ) -> Option<Backend> {
+        let backends = lb.backends().get_backend();
+        let candidates: Vec<&Backend> = backends
+            .iter()
+            .filter(|backend| lb.backends().ready(backend) && accept(backend))
+            .collect();
+        let backend = self.least_loaded(&candidates)?.clone();
+        self.loads
+            .entry(backend.addr.clone())
+            .or_default()
+            .in_flight += 1;
+
+        // Response-time history outlives idle periods; drop entries of backends that
+        // discovery no longer returns.
+        if self.loads.len() > backends.len() * 2 {
+            self.loads.retain(|addr, load| {
+                load.in_flight > 0 || backends.iter().any(|backend| &backend.addr == addr)
+            });
+        }
+        Some(backend)
+    }
+
+    fn least_loaded<'a>(&self, candidates: &[&'a Backend]) -> Option<&'a Backend> {
+        if candidates.is_empty() {
+            return None;
+        }
+        let start = self.cursor.fetch_add(1, Ordering::Relaxed) % candidates.len();
+        let mut best: Option<(&Backend, f64)> = None;
+        for backend in candidates[start..].iter().chain(&candidates[..start]) {
+            let cost = match self.loads.get(&backend.addr) {
+                Some(load) => load.cost(self.metric, backend.weight),
+                None => BackendLoad::default().cost(self.metric, backend.weight),
+            };
+            if best.is_none_or(|(_, best_cost)| cost < best_cost) {
+                best = Some((backend, cost));
+            }
+        }
+        best.map(|(backend, _)| backend)
+    }
+
+    /// Release a request, folding in its response time when one was observed.
+    fn release(&self, addr: &SocketAddr, response_time: Option<Duration>) {
+        if let Some(mut load) = self.loads.get_mut(addr) {
+            load.in_flight = load.in_flight.saturating_sub(1);
+            if let (LoadMetric::PeakEwma, Some(sample)) = (self.metric, response_time) {
+                load.observe(sample);
+            }
+        }
+        if self.metric == LoadMetric::InFlight {
+            self.loads.remove_if(addr, |_, load| load.in_flight == 0);
+        }
+    }
 }
 
 enum SelectionLB {
@@ -224,6 +782,21 @@ enum SelectionLB {
     Random(LB<Random>),
     Fnv(LB<FVNHash>),
     Ketama(LB<KetamaHashing>),
+    /// `least_conn` and `latency`: round-robin keeps discovery and health state,
+    /// selection minimizes the tracked load.
+    Loaded(LB<RoundRobin>, Arc<BackendLoads>),
+}
+
+impl Clone for SelectionLB {
+    fn clone(&self) -> Self {
+        match self {
+            SelectionLB::RoundRobin(lb) => SelectionLB::RoundRobin(lb.clone()),
+            SelectionLB::Random(lb) => SelectionLB::Random(lb.clone()),
+            SelectionLB::Fnv(lb) => SelectionLB::Fnv(lb.clone()),
+            SelectionLB::Ketama(lb) => SelectionLB::Ketama(lb.clone()),
+            SelectionLB::Loaded(lb, loads) => SelectionLB::Loaded(lb.clone(), loads.clone()),
+        }
+    }
 }
 
 impl SelectionLB {
@@ -241,12 +814,37 @@ impl SelectionLB {
             config::SelectionType::Ketama => Ok(SelectionLB::Ketama(
                 LB::<KetamaHashing>::from_prepared(value, prepared)?,
             )),
+            config::SelectionType::LeastConn => Ok(SelectionLB::Loaded(
+                LB::<RoundRobin>::from_prepared(value, prepared)?,
+                Arc::new(BackendLoads::new(LoadMetric::InFlight)),
+            )),
+            config::SelectionType::Latency => Ok(SelectionLB::Loaded(
+                LB::<RoundRobin>::from_prepared(value, prepared)?,
+                Arc::new(BackendLoads::new(LoadMetric::PeakEwma)),
+            )),
         }
     }
 }
 
 struct LB<BS: BackendSelection> {
     upstreams: Arc<LoadBalancer<BS>>,
+    /// Active probe results, when health checks are configured.
+    probes: Option<Arc<ProbeLog>>,
+    /// Handle on the discovery installed in `upstreams`, to swap the node set.
+    discovery: SeededDiscovery,
+    /// Affinity tokens of the current backends.
+    affinity: Arc<ArcSwap<AffinityIndex>>,
+}
+
+impl<BS: BackendSelection> Clone for LB<BS> {
+    fn clone(&self) -> Self {
+        Self {
+            upstreams: self.upstreams.clone(),
+            probes: self.probes.clone(),
+            discovery: self.discovery.clone(),
+            affinity: self.affinity.clone(),
+        }
+    }
 }
 
 impl<BS> LB<BS>
@@ -256,13 +854,21 @@ where
 {
     fn from_prepared(upstream: config::Upstream, prepared: PreparedUpstream) -> ProxyResult<Self> {
         let refresh: HybridDiscovery = upstream.clone().try_into()?;
+        let resolves_dns = refresh.resolves_dns();
         let discovery = SeededDiscovery::new(prepared, refresh);
-        let mut upstreams = LoadBalancer::<BS>::from_backends(Backends::new(Box::new(discovery)));
+        let mut upstreams =
+            LoadBalancer::<BS>::from_backends(Backends::new(Box::new(discovery.clone())));
 
+        let mut probes = None;
         if let Some(check) = upstream.checks {
             let health_check: Box<dyn HealthCheckTrait + Send + Sync + 'static> =
                 check.clone().into();
-            upstreams.set_health_check(health_check);
+            let log = Arc::new(ProbeLog::default());
+            upstreams.set_health_check(Box::new(RecordedHealthCheck::new(
+                health_check,
+                log.clone(),
+            )));
+            probes = Some(log);
 
             let health_check_frequency = check
                 .active
@@ -273,7 +879,8 @@ where
             upstreams.health_check_frequency = Some(health_check_frequency);
         }
 
-        if let Some(interval) = config::dns_refresh_interval() {
+        // Static-only upstreams have nothing to re-resolve.
+        if let Some(interval) = config::dns_refresh_interval().filter(|_| resolves_dns) {
             upstreams.update_frequency = Some(Duration::from_secs(interval));
         }
 
@@ -286,23 +893,99 @@ where
 
         // The seeded discovery result is immediately ready and never performs
         // I/O, so populate selection before this LB can be published.
-        let update_result = upstreams.update().now_or_never().ok_or_else(|| {
-            ProxyError::Configuration(format!(
-                "Upstream '{}' seeded discovery was not immediately ready",
-                upstream.id
-            ))
-        })?;
-        update_result.map_err(|e| {
-            ProxyError::Configuration(format!(
-                "Upstream '{}' failed to install prepared backends: {e}",
-                upstream.id
-            ))
-        })?;
+        install_seed(&upstream.id, &upstreams)?;
+        let affinity = AffinityIndex::new(upstreams.backends().get_backend());
+
+        Ok(Self {
+            upstreams,
+            probes,
+            discovery,
+            affinity: Arc::new(ArcSwap::from_pointee(affinity)),
+        })
+    }
+
+    /// Affinity tokens of the current backends, recomputed when a reseed or DNS refresh
+    /// has replaced the backend set.
+    fn affinity_index(&self) -> Arc<AffinityIndex> {
+        let current = self.upstreams.backends().get_backend();
+        let index = self.affinity.load_full();
+        if Arc::ptr_eq(&index.source, &current) {
+            return index;
+        }
+        let index = Arc::new(AffinityIndex::new(current));
+        self.affinity.store(index.clone());
+        index
+    }
+
+    /// Replace the node set. Backends present before and after keep their health, as do
+    /// those the load balancer re-creates for a new weight.
+    fn reseed(
+        &self,
+        upstream_id: &str,
+        prepared: PreparedUpstream,
+        refresh: HybridDiscovery,
+    ) -> ProxyResult<()> {
+        self.discovery.reseed(prepared, refresh);
+        install_seed(upstream_id, &self.upstreams)?;
+        if let Some(probes) = &self.probes {
+            self.inherit_health(probes);
+        }
+        Ok(())
+    }
 
-        Ok(Self { upstreams })
+    /// Bring backends to the health `previous` tracked for their address.
+    fn inherit_health(&self, previous: &ProbeLog) {
+        if let Some(probes) = &self.probes {
+            health_check::inherit_health(self.upstreams.backends(), probes, previous);
+        }
+    }
+
+    fn backend_health(&self, upstream_id: &str) -> Vec<BackendHealth> {
+        let backends = self.upstreams.backends();
+        let current = backends.get_backend();
+        if let Some(probes) = &self.probes {
+            probes.retain(|addr| current.iter().any(|b| b.addr.to_string() == addr));
+        }
+        let mut health: Vec<BackendHealth> = current
+            .iter()
+            .map(|backend| {
+                let address = backend.addr.to_string();
+                let probe = self.probes.as_ref().and_then(|p| p.get(&address));
+                BackendHealth {
+                    healthy: backends.ready(backend),
+                    drained: drain::backend_drained(upstream_id, backend),
+                    weight: backend.weight,
+                    probe: probe.unwrap_or_default(),
+                    address,
+                }
+            })
+            .collect();
+        health.sort_by(|a, b| a.address.cmp(&b.address));
+        health
     }
 }
 
+/// Run the seeded discovery of `upstreams`, which is immediately ready.
+fn install_seed<BS>(upstream_id: &str, upstreams: &LoadBalancer<BS>) -> ProxyResult<()>
+where
+    BS: BackendSelection + Send + Sync + 'static,
+    BS::Iter: BackendIter,
+{
+    let update_result = upstreams.update().now_or_never().ok_or_else(|| {
+        ProxyError::Configuration(format!(
+            "Upstream '{upstream_id}' seeded discovery was not immediately ready"
+        ))
+    })?;
+    update_result.map_err(|e| {
+        ProxyError::Configuration(format!(
+            "Upstream '{upstream_id}' failed to install prepared backends: {e}"
+        ))
+    })?;
+    // Prepared material may predate a drain issued since.
+    drain::apply(upstream_id, upstreams.backends());
+    Ok(())
+}
+
 impl From<config::HealthCheck> for Box<dyn HealthCheckTrait + Send + Sync + 'static> {
     fn from(value: config::HealthCheck) -> Self {
         match value.active.r#type {
@@ -310,10 +993,35 @@ impl From<config::HealthCheck> for Box<dyn HealthCheckTrait + Send + Sync + 'sta
             config::ActiveCheckType::HTTP | config::ActiveCheckType::HTTPS => {
                 Into::<Box<HttpHealthCheck>>::into(value)
             }
+            config::ActiveCheckType::GRPC | config::ActiveCheckType::GRPCS => {
+                Into::<Box<GrpcHealthCheck>>::into(value)
+            }
         }
     }
 }
 
+impl From<config::HealthCheck> for Box<GrpcHealthCheck> {
+    fn from(value: config::HealthCheck) -> Self {
+        let host = value.active.host.unwrap_or_default();
+        let tls = value.active.r#type == config::ActiveCheckType::GRPCS;
+        let mut health_check = GrpcHealthCheck::new(host.as_str(), tls);
+        health_check.service = value.active.grpc_service;
+        health_check.peer_template.options.total_connection_timeout =
+            Some(Duration::from_secs(value.active.timeout as _));
+        health_check.peer_template.options.verify_cert = value.active.https_verify_certificate;
+        health_check.port_override = value.active.port.map(|port| port as _);
+
+        if let Some(healthy) = value.active.healthy {
+            health_check.consecutive_success = healthy.successes as _;
+        }
+        if let Some(unhealthy) = value.active.unhealthy {
+            health_check.consecutive_failure = unhealthy.http_failures as _;
+        }
+
+        Box::new(health_check)
+    }
+}
+
 impl From<config::HealthCheck> for Box<TcpHealthCheck> {
     fn from(value: config::HealthCheck) -> Self {
         let mut health_check = TcpHealthCheck::new();
@@ -426,15 +1134,308 @@ mod tests {
             pass_host: UpstreamPassHost::PASS,
             upstream_host: None,
             tls: None,
+            zone_aware: None,
+            discovery_type: None,
+            priorities: HashMap::new(),
+            sticky: None,
+            labels: Default::default(),
         }
     }
 
+    #[test]
+    fn zone_labels_are_attached_to_static_backends() {
+        let mut upstream = sample_upstream("zoned", None);
+        upstream.zone_aware = Some(config::UpstreamZoneAware {
+            zones: HashMap::from([("127.0.0.1:18080".to_string(), "az-1".to_string())]),
+            min_healthy_percent: 0,
+        });
+        let upstream = ProxyUpstream::build_static(upstream).unwrap();
+        let backend = upstream.select_backend_for_test().unwrap();
+        assert_eq!(
+            backend.ext.get::<NodeZone>(),
+            Some(&NodeZone("az-1".to_string()))
+        );
+    }
+
+    /// An upstream with two nodes in the gateway's zone `az-1` and one in `az-2`.
+    fn zoned_upstream(id: &str, min_healthy_percent: u8) -> ProxyUpstream {
+        config::init_gateway_zone(Some("az-1".to_string()));
+        let mut upstream = sample_upstream(id, None);
+        upstream.nodes.insert("127.0.0.2:18080".to_string(), 1);
+        upstream.nodes.insert("127.0.0.3:18080".to_string(), 1);
+        upstream.zone_aware = Some(config::UpstreamZoneAware {
+            zones: HashMap::from([
+                ("127.0.0.1:18080".to_string(), "az-1".to_string()),
+                ("127.0.0.2:18080".to_string(), "az-1".to_string()),
+                ("127.0.0.3:18080".to_string(), "az-2".to_string()),
+            ]),
+            min_healthy_percent,
+        });
+        ProxyUpstream::build_static(upstream).unwrap()
+    }
+
+    /// Zones of the backends picked by `rounds` selections.
+    fn selected_zones(upstream: &ProxyUpstream, rounds: usize) -> BTreeSet<String> {
+        (0..rounds)
+            .map(|_| {
+                let backend = upstream.select_backend_for_test().unwrap();
+                backend.ext.get::<NodeZone>().unwrap().0.clone()
+            })
+            .collect()
+    }
+
+    fn set_ready(upstream: &ProxyUpstream, addr: &str, ready: bool) {
+        let backends = with_lb!(&upstream.lb, |lb| lb.upstreams.backends());
+        let backend = backends
+            .get_backend()
+            .iter()
+            .find(|backend| backend.addr.to_string() == addr)
+            .cloned()
+            .unwrap();
+        backends.set_enable(&backend, ready);
+    }
+
+    #[test]
+    fn exhausted_local_zone_spills_over_and_recovers() {
+        let upstream = zoned_upstream("zone-spill", 50);
+        assert_eq!(
+            selected_zones(&upstream, 6),
+            BTreeSet::from(["az-1".into()])
+        );
+
+        // Half of the local zone is still enough.
+        set_ready(&upstream, "127.0.0.1:18080", false);
+        assert_eq!(
+            selected_zones(&upstream, 6),
+            BTreeSet::from(["az-1".into()])
+        );
+
+        set_ready(&upstream, "127.0.0.2:18080", false);
+        assert_eq!(
+            selected_zones(&upstream, 6),
+            BTreeSet::from(["az-2".into()])
+        );
+
+        // Traffic returns once the local zone recovers.
+        set_ready(&upstream, "127.0.0.1:18080", true);
+        assert_eq!(
+            selected_zones(&upstream, 6),
+            BTreeSet::from(["az-1".into()])
+        );
+    }
+
+    #[test]
+    fn local_zone_below_threshold_shares_traffic_across_zones() {
+        let upstream = zoned_upstream("zone-threshold", 60);
+        set_ready(&upstream, "127.0.0.1:18080", false);
+        assert_eq!(
+            selected_zones(&upstream, 6),
+            BTreeSet::from(["az-1".into(), "az-2".into()])
+        );
+
+        set_ready(&upstream, "127.0.0.1:18080", true);
+        assert_eq!(
+            selected_zones(&upstream, 6),
+            BTreeSet::from(["az-1".into()])
+        );
+    }
+
+    #[test]
+    fn least_conn_weighs_in_flight_requests() {
+        let mut upstream = sample_upstream("least-conn", None);
+        upstream.r#type = SelectionType::LeastConn;
+        upstream.nodes.insert("127.0.0.2:18080".to_string(), 3);
+        let upstream = ProxyUpstream::build_static(upstream).unwrap();
+        let heavy: SocketAddr = "127.0.0.2:18080".parse().unwrap();
+
+        // Weight 3 absorbs two requests before the weight-1 node is as loaded.
+        let first = upstream.select_backend_for_test().unwrap();
+        let second = upstream.select_backend_for_test().unwrap();
+        assert_eq!((&first.addr, &second.addr), (&heavy, &heavy));
+
+        for backend in [first, second] {
+            upstream.release_backend(backend.ext.get::<HttpPeer>().unwrap(), None);
+        }
+        let SelectionLB::Loaded(_, loads) = &upstream.lb else {
+            panic!("expected least_conn selection");
+        };
+        assert!(loads.loads.is_empty());
+    }
+
+    #[test]
+    fn latency_prefers_faster_backend() {
+        let loads = BackendLoads::new(LoadMetric::PeakEwma);
+        let fast = Backend::new_with_weight("127.0.0.1:18080", 1).unwrap();
+        let slow = Backend::new_with_weight("127.0.0.2:18080", 1).unwrap();
+        for (backend, rtt) in [(&fast, 5), (&slow, 200)] {
+            loads
+                .loads
+                .entry(backend.addr.clone())
+                .or_default()
+                .in_flight += 1;
+            loads.release(&backend.addr, Some(Duration::from_millis(rtt)));
+        }
+
+        for _ in 0..4 {
+            assert_eq!(loads.least_loaded(&[&slow, &fast]), Some(&fast));
+        }
+
+        // A slow sample on the fast backend takes effect immediately (peak).
+        loads.loads.get_mut(&fast.addr).unwrap().in_flight += 1;
+        loads.release(&fast.addr, Some(Duration::from_millis(500)));
+        assert_eq!(loads.least_loaded(&[&slow, &fast]), Some(&slow));
+    }
+
+    #[test]
+    fn priority_tiers_fail_over_to_lower_priority() {
+        let mut upstream = sample_upstream("failover", None);
+        upstream.nodes.insert("127.0.0.2:18080".to_string(), 1);
+        upstream.priorities = HashMap::from([("127.0.0.1:18080".to_string(), 10)]);
+        let upstream = ProxyUpstream::build_static(upstream).unwrap();
+        let primary: SocketAddr = "127.0.0.1:18080".parse().unwrap();
+        for _ in 0..4 {
+            assert_eq!(upstream.select_backend_for_test().unwrap().addr, primary);
+        }
+
+        let backends = with_lb!(&upstream.lb, |lb| lb.upstreams.backends());
+        let primary_backend = backends
+            .get_backend()
+            .iter()
+            .find(|backend| backend.addr == primary)
+            .cloned()
+            .unwrap();
+        backends.set_enable(&primary_backend, false);
+        let fallback: SocketAddr = "127.0.0.2:18080".parse().unwrap();
+        assert_eq!(upstream.select_backend_for_test().unwrap().addr, fallback);
+    }
+
+    #[test]
+    fn drained_nodes_are_skipped_until_re_enabled() {
+        let mut upstream = sample_upstream("drain-test", None);
+        upstream.nodes.insert("127.0.0.2:18080".to_string(), 1);
+        let upstream = ProxyUpstream::build_static(upstream).unwrap();
+        let kept: SocketAddr = "127.0.0.2:18080".parse().unwrap();
+
+        assert!(drain::set_drained("drain-test", "127.0.0.1:18080", true));
+        upstream.apply_drains();
+        for _ in 0..4 {
+            assert_eq!(upstream.select_backend_for_test().unwrap().addr, kept);
+        }
+        let health = upstream.backend_health();
+        assert!(health[0].drained && !health[0].healthy);
+
+        // A rebuilt upstream starts drained too.
+        let rebuilt = ProxyUpstream::build_static(upstream.inner.clone()).unwrap();
+        assert!(rebuilt.backend_health()[0].drained);
+
+        assert!(drain::set_drained("drain-test", "127.0.0.1:18080", false));
+        upstream.apply_drains();
+        assert!(upstream.backend_health().iter().all(|b| b.healthy));
+    }
+
+    #[test]
+    fn affinity_cookie_pins_until_backend_is_unready() {
+        let mut upstream = sample_upstream("sticky", None);
+        upstream.nodes.insert("127.0.0.2:18080".to_string(), 1);
+        upstream.sticky = Some(config::UpstreamSticky {
+            r#type: config::StickyType::Cookie,
+            cookie: "affinity".into(),
+            ttl: 60,
+        });
+        let upstream = ProxyUpstream::build_static(upstream).unwrap();
+        let pinned: SocketAddr = "127.0.0.2:18080".parse().unwrap();
+        let mut req = RequestHeader::build("GET", b"/", None).unwrap();
+        req.insert_header(
+            "Cookie",
+            format!("a=1; affinity={}", affinity_token(&pinned)),
+        )
+        .unwrap();
+
+        let backend = upstream.pinned_backend(&req).unwrap();
+        assert_eq!(backend.addr, pinned);
+        let peer = backend.ext.get::<HttpPeer>().unwrap();
+        assert!(upstream.affinity_cookie(&req, peer).is_none());
+
+        let backends = with_lb!(&upstream.lb, |lb| lb.upstreams.backends());
+        backends.set_enable(&backend, false);
+        assert!(upstream.pinned_backend(&req).is_none());
+
+        let other = upstream.select_backend_for_test().unwrap();
+        let cookie = upstream
+            .affinity_cookie(&req, other.ext.get::<HttpPeer>().unwrap())
+            .unwrap();
+        assert_eq!(
+            cookie,
+            format!(
+                "affinity={}; Path=/; HttpOnly; Max-Age=60",
+                affinity_token(&other.addr)
+            )
+        );
+    }
+
+    #[test]
+    fn affinity_index_follows_node_changes() {
+        let mut config = sample_upstream("sticky-rebuild", None);
+        config.sticky = Some(config::UpstreamSticky {
+            r#type: config::StickyType::Cookie,
+            cookie: "affinity".into(),
+            ttl: 0,
+        });
+        let upstream = ProxyUpstream::build_static(config.clone()).unwrap();
+        let added: SocketAddr = "127.0.0.3:18080".parse().unwrap();
+        let mut req = RequestHeader::build("GET", b"/", None).unwrap();
+        req.insert_header("Cookie", format!("affinity={}", affinity_token(&added)))
+            .unwrap();
+        assert!(upstream.pinned_backend(&req).is_none());
+
+        config.nodes.insert(added.to_string(), 1);
+        let prepared = prepare_static_upstream(&config).unwrap();
+        let rebuilt = upstream.rebuild(config, prepared).unwrap();
+        rebuilt.commit_nodes();
+        assert_eq!(rebuilt.pinned_backend(&req).unwrap().addr, added);
+    }
+
+    #[test]
+    fn zone_labels_must_reference_configured_nodes() {
+        use validator::Validate;
+        let mut upstream = sample_upstream("zoned", None);
+        upstream.zone_aware = Some(config::UpstreamZoneAware {
+            zones: HashMap::from([("10.9.9.9:80".to_string(), "az-1".to_string())]),
+            min_healthy_percent: 0,
+        });
+        assert!(upstream.validate().is_err());
+    }
+
+    #[test]
+    fn tls_overrides_applied_to_peer() {
+        let mut upstream = sample_upstream("tls", None);
+        upstream.scheme = UpstreamScheme::HTTPS;
+        upstream.tls = Some(config::UpstreamTls {
+            client_cert: None,
+            client_key: None,
+            sni: Some("api.example.com".into()),
+            verify: false,
+            server_name: Some("internal.example.com".into()),
+        });
+        let upstream = ProxyUpstream::build_static(upstream).unwrap();
+        let backend = upstream.select_backend_for_test().unwrap();
+        let peer = backend.ext.get::<HttpPeer>().unwrap();
+        assert_eq!(peer.sni, "api.example.com");
+        assert!(!peer.options.verify_cert);
+        assert!(!peer.options.verify_hostname);
+        assert_eq!(
+            peer.options.alternative_cn.as_deref(),
+            Some("internal.example.com")
+        );
+    }
+
     #[test]
     fn explicit_upstream_timeout_applied_to_peer() {
         init_default_upstream_timeout(Some(Timeout {
             connect: 5,
             send: 5,
             read: 5,
+            total: None,
         }));
         let upstream = ProxyUpstream::build_static(sample_upstream(
             "payments",
@@ -442,6 +1443,7 @@ mod tests {
                 connect: 30,
                 send: 30,
                 read: 30,
+                total: None,
             }),
         ))
         .unwrap();
@@ -461,6 +1463,7 @@ mod tests {
             connect: 5,
             send: 5,
             read: 5,
+            total: None,
         }));
         // First-wins OnceCell: if a prior test already set a different value,
         // resolve via whatever is currently configured.
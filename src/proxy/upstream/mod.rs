@@ -1,11 +1,15 @@
 //! Upstream management module.
 //!
 //! This module contains all the upstream-related functionality including:
-//! - Service discovery (DNS and static)
+//! - Service discovery (DNS and static) and its shared DNS cache
 //! - Load balancing and backend selection
 //! - Health checking and monitoring
+//! - Connection reuse statistics
 
+pub mod conn_stats;
 pub mod discovery;
+pub mod dns_cache;
+pub mod drain;
 pub mod health_check;
 pub mod load_balancer;
 
@@ -27,6 +31,10 @@ pub(crate) fn traffic_split_key(owner: &str, rule: usize, upstream: usize) -> St
     format!("traffic-split/{owner}/{rule}/{upstream}")
 }
 
+pub(crate) fn ai_proxy_key(owner: &str) -> String {
+    format!("ai-proxy/{owner}")
+}
+
 // Re-export commonly used items
 pub use health_check::SHARED_HEALTH_CHECK_SERVICE;
 pub use load_balancer::ProxyUpstream;
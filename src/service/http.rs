@@ -1,4 +1,11 @@
-use std::{collections::HashSet, sync::Arc, time::Duration};
+#[cfg(unix)]
+use std::os::fd::{AsRawFd, BorrowedFd, OwnedFd};
+use std::{
+    collections::HashSet,
+    net::SocketAddr,
+    sync::Arc,
+    time::{Duration, Instant},
+};
 
 use async_trait::async_trait;
 use bytes::Bytes;
@@ -8,31 +15,78 @@ use http::{
 };
 use once_cell::sync::{Lazy, OnceCell};
 use pingora::modules::http::{
+    compression::{ResponseCompression, ResponseCompressionBuilder},
     HttpModules,
-    {compression::ResponseCompressionBuilder, grpc_web::GrpcWeb},
 };
 use pingora_cache::{
     cache_control::{CacheControl, DirectiveMap, DirectiveValue},
-    eviction::simple_lru::Manager,
+    eviction::EvictionManager,
     filters::resp_cacheable,
     key::{CacheKey, HashBinary},
     lock::{CacheKeyLockImpl, CacheLock},
-    CacheMeta, CacheMetaDefaults, CachePhase, MemCache, NoCacheReason, RespCacheable,
-    VarianceBuilder,
+    trace::Span,
+    CacheMeta, CacheMetaDefaults, CachePhase, MemCache, NoCacheReason, PurgeType, RespCacheable,
+    Storage, VarianceBuilder,
 };
-use pingora_core::upstreams::peer::HttpPeer;
-use pingora_error::{Error, Result};
+use pingora_core::{protocols::Digest, upstreams::peer::HttpPeer};
+use pingora_error::{Error, ErrorSource, ErrorType, Result};
 use pingora_http::{RequestHeader, ResponseHeader};
-use pingora_proxy::{ProxyHttp, Session};
+use pingora_proxy::{FailToProxy, ProxyHttp, Session};
 use prometheus::{register_int_counter_vec, IntCounterVec};
 
 use crate::{
-    config::{self, CacheDefaults},
-    core::{ProxyContext, ProxyError, ProxyPlugin, ProxyPluginExecutor, RouteContext},
-    plugins::cache::{self, CacheSettings, CTX_KEY_CACHE_SETTINGS},
-    proxy::runtime::RUNTIME,
+    config::{self, CacheDefaults, Downstream, Listener},
+    core::{
+        eviction::{AdaptiveLru, ItemTag},
+        header_hygiene, maintenance,
+        memory::MemoryConsumer,
+        overload, slow_log, ProxyContext, ProxyError, ProxyPlugin, ProxyPluginExecutor,
+        RouteContext, UpstreamInfo, UpstreamSelector,
+    },
+    plugins::{
+        cache::{self, CacheSettings, PurgeTarget, CTX_KEY_CACHE_SETTINGS},
+        debug_routing, error_page, fallback,
+    },
+    proxy::{route::MatchKind, runtime::RUNTIME, upstream::conn_stats},
+    utils::{
+        compression,
+        grpc_bridge::GrpcBridgeBuilder,
+        request::{get_request_host, strip_path_prefix},
+        response::{grpc_status, is_streaming_response, ResponseBuilder},
+    },
 };
 
+/// Context key holding the request's overload protection slot.
+const CTX_KEY_OVERLOAD_SLOT: &str = "overload_slot";
+
+/// Context key holding a duplicate of the upstream socket, until the exchange is known to be
+/// HTTP/1 and its deadline watch is armed.
+#[cfg(unix)]
+const CTX_KEY_UPSTREAM_SOCKET: &str = "upstream_socket";
+
+/// Context key holding the [`DeadlineWatch`] of the current upstream attempt.
+#[cfg(unix)]
+const CTX_KEY_DEADLINE_WATCH: &str = "deadline_watch";
+
+/// Peer of a request pinned to one node by the debug-routing plugin. Falls back to
+/// normal balancing when the selected upstream has no such node.
+fn debug_routed_peer(ctx: &ProxyContext) -> Option<(Box<HttpPeer>, Arc<dyn UpstreamSelector>)> {
+    let node = ctx.get::<String>(debug_routing::CTX_KEY_DEBUG_NODE)?;
+    let upstream = ctx
+        .upstream_override
+        .clone()
+        .or_else(|| ctx.route.as_ref()?.resolve_upstream())?;
+    let Some(mut backend) = upstream.select_node(node) else {
+        log::warn!("Debug routing: node '{node}' is not in the upstream, balancing normally");
+        return None;
+    };
+    let mut peer = Box::new(backend.ext.get_mut::<HttpPeer>()?.clone());
+    if let Some(route) = ctx.route.as_ref() {
+        crate::proxy::route::apply_route_timeout(route.timeout(), &mut peer);
+    }
+    Some((peer, upstream))
+}
+
 /// Headers that imply credentials for shared-cache safety (checked before plugins mutate them).
 pub(crate) fn headers_indicate_shared_cache_credentials(headers: &http::HeaderMap) -> bool {
     headers.contains_key("authorization")
@@ -90,14 +144,186 @@ pub fn configured_max_memory_bytes() -> usize {
         .unwrap_or(FALLBACK_MAX_MEMORY_BYTES)
 }
 
-// 3. Eviction manager: sized from `pingsix.defaults.cache.max_memory_bytes`
-static EVICTION_MANAGER: Lazy<Manager> = Lazy::new(|| Manager::new(configured_max_memory_bytes()));
+// 3. Eviction manager: sized from `pingsix.defaults.cache.max_memory_bytes`, lowered
+//    by the memory tuner under `pingsix.memory_limit`
+static EVICTION_MANAGER: Lazy<AdaptiveLru> =
+    Lazy::new(|| AdaptiveLru::new(configured_max_memory_bytes()));
 
 // 4. Cache lock: Timeout should be slightly larger than upstream P99 response time
 static CACHE_LOCK: Lazy<Box<CacheKeyLockImpl>> =
     Lazy::new(|| CacheLock::new_boxed(Duration::from_secs(5)));
+
+/// The response cache as seen by the memory limit.
+struct CacheMemory;
+
+#[async_trait]
+impl MemoryConsumer for CacheMemory {
+    fn usage(&self) -> usize {
+        EVICTION_MANAGER.total_size()
+    }
+
+    async fn shrink(&self, target: usize) {
+        let evicted = EVICTION_MANAGER.set_limit(target.min(configured_max_memory_bytes()));
+        let span = Span::inactive();
+        for key in evicted {
+            if let Err(e) = CACHE_BACKEND
+                .purge(&key, PurgeType::Eviction, &span.handle())
+                .await
+            {
+                log::warn!("Failed to purge {key} while shrinking the cache: {e}");
+            }
+        }
+    }
+}
+
+/// Remove the responses `target` covers from this process's cache; returns how many
+/// entries were removed.
+pub async fn purge_cache(target: &PurgeTarget) -> usize {
+    let purged = EVICTION_MANAGER.take_matching(|tag| target.covers(&tag.route_id, &tag.key));
+    let span = Span::inactive();
+    for key in &purged {
+        if let Err(e) = CACHE_BACKEND
+            .purge(key, PurgeType::Invalidation, &span.handle())
+            .await
+        {
+            log::warn!("Failed to purge {key} from the cache: {e}");
+        }
+    }
+    purged.len()
+}
+
+/// Records what a response just stored in the cache was stored for, so Admin API purges
+/// can find it.
+fn tag_cached_response(session: &Session, ctx: &ProxyContext) {
+    if !matches!(
+        session.cache.phase(),
+        CachePhase::Miss | CachePhase::Expired
+    ) {
+        return;
+    }
+    let Some(key) = session.cache.maybe_cache_key() else {
+        return;
+    };
+    let req = session.req_header();
+    let host = req
+        .headers
+        .get(http::header::HOST)
+        .and_then(|v| v.to_str().ok())
+        .unwrap_or("");
+    let path_and_query = req.uri.path_and_query().map_or("/", |pq| pq.as_str());
+    EVICTION_MANAGER.tag(
+        &key.to_compact(),
+        ItemTag {
+            route_id: ctx.route.as_ref().map_or("", |r| r.id()).to_string(),
+            key: format!("{host}{path_and_query}"),
+        },
+    );
+}
+
+/// Handle through which `pingsix.memory_limit` sizes the response cache.
+pub fn cache_memory() -> Arc<dyn MemoryConsumer> {
+    Arc::new(CacheMemory)
+}
 // --- END: Global Cache Infrastructure ---
 
+/// Client connection settings from `pingsix.downstream`, set once at startup.
+static DOWNSTREAM: OnceCell<Downstream> = OnceCell::new();
+
+/// Applies `pingsix.downstream` to every client connection. Called once at startup;
+/// later calls are no-ops.
+pub fn init_downstream(downstream: Option<Downstream>) {
+    if let Some(downstream) = downstream {
+        let _ = DOWNSTREAM.set(downstream);
+    }
+}
+
+/// Listener addresses whose accepted connections turn Nagle's algorithm back on.
+static NAGLE_LISTENERS: OnceCell<Vec<SocketAddr>> = OnceCell::new();
+
+/// Records the listeners configured with `socket.tcp_nodelay: false`. Called once at
+/// startup; later calls are no-ops.
+pub fn init_listener_sockets(listeners: &[Listener]) {
+    let nagle: Vec<SocketAddr> = listeners
+        .iter()
+        .filter(|l| l.socket.as_ref().is_some_and(|s| !s.tcp_nodelay))
+        .map(|l| l.address)
+        .collect();
+    if !nagle.is_empty() {
+        let _ = NAGLE_LISTENERS.set(nagle);
+    }
+}
+
+/// Whether a connection accepted on `local` belongs to the listener bound to `listener`.
+fn is_listener_addr(listener: &SocketAddr, local: &SocketAddr) -> bool {
+    listener.port() == local.port()
+        && (listener.ip().is_unspecified() || listener.ip() == local.ip())
+}
+
+/// Pingora sets `TCP_NODELAY` on every accepted connection; clear it again for HTTP/1
+/// connections of listeners that asked for Nagle's algorithm.
+fn apply_listener_socket_settings(session: &Session) {
+    let Some(listeners) = NAGLE_LISTENERS.get() else {
+        return;
+    };
+    let Some(local) = session.server_addr().and_then(|a| a.as_inet()) else {
+        return;
+    };
+    if !listeners.iter().any(|l| is_listener_addr(l, local)) {
+        return;
+    }
+    #[cfg(unix)]
+    if let Some(stream) = session.as_downstream().stream() {
+        let off: libc::c_int = 0;
+        // SAFETY: the fd belongs to the live downstream stream and `off` outlives the call.
+        let rc = unsafe {
+            libc::setsockopt(
+                stream.id(),
+                libc::IPPROTO_TCP,
+                libc::TCP_NODELAY,
+                &off as *const libc::c_int as *const libc::c_void,
+                std::mem::size_of::<libc::c_int>() as libc::socklen_t,
+            )
+        };
+        if rc != 0 {
+            log::debug!(
+                "failed to clear TCP_NODELAY: {}",
+                std::io::Error::last_os_error()
+            );
+        }
+    }
+}
+
+/// Keepalive timer for the next request on an HTTP/1 connection whose current timer is
+/// `current` (`None` = closing, `Some(0)` = no limit).
+///
+/// Pingora reads the next request's headers under the same timer, so a header timeout
+/// caps the keepalive timeout.
+fn downstream_keepalive(config: &Downstream, current: Option<u64>) -> Option<u64> {
+    let idle = match config.keepalive_timeout {
+        Some(0) => return None,
+        Some(keepalive) => current.map(|_| keepalive)?,
+        None => current?,
+    };
+    match config.header_timeout {
+        Some(header) if idle == 0 || header < idle => Some(header),
+        _ => Some(idle),
+    }
+}
+
+/// Apply the configured body read timeout and keepalive timer to the client session.
+fn apply_downstream_settings(session: &mut Session) {
+    let Some(config) = DOWNSTREAM.get() else {
+        return;
+    };
+    if let Some(body_timeout) = config.body_timeout {
+        session.set_read_timeout(Some(Duration::from_secs(body_timeout)));
+    }
+    if config.keepalive_timeout.is_some() || config.header_timeout.is_some() {
+        let keepalive = downstream_keepalive(config, session.get_keepalive());
+        session.set_keepalive(keepalive);
+    }
+}
+
 /// Proxy service.
 ///
 /// Manages the proxying of requests to upstream servers.
@@ -161,6 +387,23 @@ pub async fn run_global_then_route_response_filter(
     route.response_filter(session, upstream_response, ctx).await
 }
 
+/// Run global-rule plugins then route/service plugins for `request_body_filter`.
+pub async fn run_global_then_route_request_body_filter(
+    global: Arc<ProxyPluginExecutor>,
+    route: Arc<ProxyPluginExecutor>,
+    session: &mut Session,
+    body: &mut Option<Bytes>,
+    end_of_stream: bool,
+    ctx: &mut ProxyContext,
+) -> Result<()> {
+    global
+        .request_body_filter(session, body, end_of_stream, ctx)
+        .await?;
+    route
+        .request_body_filter(session, body, end_of_stream, ctx)
+        .await
+}
+
 /// Run global-rule plugins then route/service plugins for `response_body_filter`.
 pub fn run_global_then_route_response_body_filter(
     global: Arc<ProxyPluginExecutor>,
@@ -174,6 +417,20 @@ pub fn run_global_then_route_response_body_filter(
     route.response_body_filter(session, body, end_of_stream, ctx)
 }
 
+/// Run global-rule plugins then route/service plugins for `response_trailer_filter`.
+pub async fn run_global_then_route_response_trailer_filter(
+    global: Arc<ProxyPluginExecutor>,
+    route: Arc<ProxyPluginExecutor>,
+    session: &mut Session,
+    trailers: &mut http::HeaderMap,
+    ctx: &mut ProxyContext,
+) -> Result<()> {
+    global
+        .response_trailer_filter(session, trailers, ctx)
+        .await?;
+    route.response_trailer_filter(session, trailers, ctx).await
+}
+
 /// Run global-rule plugins then route/service plugins for `logging`.
 pub async fn run_global_then_route_logging(
     global: Arc<ProxyPluginExecutor>,
@@ -197,17 +454,21 @@ impl ProxyHttp for HttpService {
 
     /// Set up downstream modules.
     ///
-    /// set up [ResponseCompressionBuilder] for gzip and brotli compression.
-    /// set up [GrpcWeb] for grpc-web protocol.
+    /// set up [ResponseCompressionBuilder] for gzip, brotli and zstd compression.
+    /// set up [GrpcBridgeBuilder] for the gRPC-Web and Connect protocols.
     fn init_downstream_modules(&self, modules: &mut HttpModules) {
         // Add disabled downstream compression module by default
         modules.add_module(ResponseCompressionBuilder::enable(0));
-        // Add the gRPC web module
-        modules.add_module(Box::new(GrpcWeb));
+        // Add the gRPC-Web and Connect bridge module
+        modules.add_module(Box::new(GrpcBridgeBuilder));
     }
 
     /// Handle the incoming request before any downstream module is executed.
     async fn early_request_filter(&self, session: &mut Session, ctx: &mut Self::CTX) -> Result<()> {
+        apply_downstream_settings(session);
+        apply_listener_socket_settings(session);
+        header_hygiene::sanitize_client_request(session);
+
         let original_headers = &session.req_header().headers;
         ctx.original_request_had_credentials =
             headers_indicate_shared_cache_credentials(original_headers);
@@ -217,18 +478,17 @@ impl ProxyHttp for HttpService {
 
         // Load one immutable runtime snapshot for all data-plane configuration used here.
         let runtime = RUNTIME.load();
-        ctx.global_plugin = runtime.global_plugins.clone();
-        let (route_match, is_fallback_preflight) =
-            match runtime.route_matcher.match_request(session) {
-                Some(route_match) => (Some(route_match), false),
-                None => (
-                    runtime
-                        .route_matcher
-                        .match_preflight(session, runtime.global_plugins.has_plugin("cors")),
-                    true,
-                ),
-            };
-        if let Some((route_params, route)) = route_match {
+        let matching = Instant::now();
+        let route_match = runtime
+            .route_matcher
+            .match_request(session, runtime.global_plugins.has_plugin("cors"));
+        ctx.timings.route_match = matching.elapsed();
+        ctx.global_plugin = runtime.global_plugins_for(
+            route_match.as_ref().map(|(_, _, route)| route.as_ref()),
+            session.req_header(),
+        );
+        if let Some((kind, route_params, route)) = route_match {
+            let is_fallback_preflight = kind == MatchKind::Preflight;
             // The preflight matcher itself filters fallback candidates to routes
             // whose effective route/service/global configuration contains CORS.
             let executor = route.build_plugin_executor();
@@ -238,36 +498,89 @@ impl ProxyHttp for HttpService {
                     || runtime.global_plugins.has_plugin("cors")
             );
             ctx.route_params = Some(route_params);
+            ctx.streaming = route.streaming();
+            ctx.deadline = route
+                .timeout()
+                .and_then(|timeout| timeout.total)
+                .map(|total| ctx.request_start + Duration::from_secs(total));
+            if let Some(group) = route.api_group() {
+                ctx.set("api_group", group.to_string());
+            }
             ctx.plugin = executor;
             ctx.route = Some(route);
         }
 
         // Execute global rule plugins, then route/service plugins.
-        run_global_then_route_early_request_filter(
+        let started = Instant::now();
+        let result = run_global_then_route_early_request_filter(
             ctx.global_plugin.clone(),
             ctx.plugin.clone(),
             session,
             ctx,
         )
-        .await
+        .await;
+        ctx.timings.early_request_filter += started.elapsed();
+        result?;
+
+        // Runs before the compression module reads Accept-Encoding.
+        compression::negotiate(session, ctx)
     }
 
     /// Filters incoming requests
     async fn request_filter(&self, session: &mut Session, ctx: &mut Self::CTX) -> Result<bool> {
-        if ctx.route.is_none() {
-            session
-                .respond_error(StatusCode::NOT_FOUND.as_u16())
-                .await?;
+        let route_id = ctx.route.as_ref().map(|route| route.id());
+        if let Some(maintenance) = maintenance::check(route_id) {
+            let host = get_request_host(session.req_header()).unwrap_or_default();
+            let body = maintenance::render_body(&maintenance, route_id, host);
+            let retry_after = maintenance.retry_after.map(|secs| secs.to_string());
+            let mut headers = vec![("Content-Type", maintenance.content_type.as_str())];
+            if let Some(retry_after) = retry_after.as_deref() {
+                headers.push(("Retry-After", retry_after));
+            }
+            let status = StatusCode::from_u16(maintenance.status_code)
+                .unwrap_or(StatusCode::SERVICE_UNAVAILABLE);
+            ResponseBuilder::send_proxy_error(session, status, Some(&body), Some(&headers)).await?;
             return Ok(true);
         }
 
-        run_global_then_route_request_filter(
+        let Some(route) = ctx.route.as_ref() else {
+            error_page::respond_error(session, ctx, StatusCode::NOT_FOUND.as_u16()).await?;
+            return Ok(true);
+        };
+
+        match overload::admit(route.shed_priority()) {
+            Ok(Some(slot)) => ctx.set(CTX_KEY_OVERLOAD_SLOT, slot),
+            Ok(None) => {}
+            Err(retry_after) => {
+                session.set_keepalive(None);
+                ResponseBuilder::send_proxy_error(
+                    session,
+                    StatusCode::SERVICE_UNAVAILABLE,
+                    None,
+                    Some(&[("Retry-After", &retry_after.to_string())]),
+                )
+                .await?;
+                return Ok(true);
+            }
+        }
+
+        let started = Instant::now();
+        let result = run_global_then_route_request_filter(
             ctx.global_plugin.clone(),
             ctx.plugin.clone(),
             session,
             ctx,
         )
-        .await
+        .await;
+        ctx.timings.request_filter += started.elapsed();
+        // Plugins that picked an upstream (traffic-split, fallback, ...) take
+        // precedence over the service's blue/green switch.
+        if matches!(result, Ok(false)) && ctx.upstream_override.is_none() {
+            if let Some(route) = ctx.route.as_ref() {
+                ctx.upstream_override = route.request_upstream(session.req_header());
+            }
+        }
+        result
     }
 
     /// Selects an upstream peer for the request
@@ -276,9 +589,17 @@ impl ProxyHttp for HttpService {
         session: &mut Session,
         ctx: &mut Self::CTX,
     ) -> Result<Box<HttpPeer>> {
-        let (peer, selected_upstream) = if let Some(upstream) = ctx.upstream_override.clone() {
+        // A retry replaces the previous selection.
+        release_selected_backend(ctx);
+        disarm_deadline(ctx);
+
+        let (mut peer, selected_upstream) = if let Some((peer, upstream)) = debug_routed_peer(ctx) {
+            (peer, Some(upstream))
+        } else if let Some(upstream) = ctx.upstream_override.clone() {
             let mut backend = upstream.select_backend(session).ok_or_else(|| {
-                ProxyError::UpstreamSelection("Traffic-split selected no backend".to_string())
+                ProxyError::UpstreamSelection(
+                    "Selected upstream has no available backend".to_string(),
+                )
             })?;
             let mut peer = backend
                 .ext
@@ -296,12 +617,46 @@ impl ProxyHttp for HttpService {
                 .ok_or_else(|| ProxyError::Internal("Route not found".into()))?;
             (route.select_http_peer(session)?, route.resolve_upstream())
         };
+        apply_deadline(ctx, &mut peer)?;
 
         ctx.selected_upstream = selected_upstream;
         ctx.peer = Some(peer.clone());
+        ctx.upstream_info = UpstreamInfo {
+            peer_selected_at: Some(Instant::now()),
+            ..Default::default()
+        };
         Ok(peer)
     }
 
+    /// Records whether the upstream connection was reused from the keepalive pool
+    async fn connected_to_upstream(
+        &self,
+        _session: &mut Session,
+        reused: bool,
+        peer: &HttpPeer,
+        #[cfg(unix)] fd: std::os::unix::io::RawFd,
+        #[cfg(windows)] _sock: std::os::windows::io::RawSocket,
+        _digest: Option<&Digest>,
+        ctx: &mut Self::CTX,
+    ) -> Result<()> {
+        #[cfg(unix)]
+        if ctx.deadline.is_some() {
+            // SAFETY: Pingora passes the fd of the connection it just established or reused.
+            match unsafe { BorrowedFd::borrow_raw(fd) }.try_clone_to_owned() {
+                Ok(socket) => ctx.set(CTX_KEY_UPSTREAM_SOCKET, socket),
+                Err(e) => log::warn!("failed to watch the upstream connection deadline: {e}"),
+            }
+        }
+        let upstream = match ctx.selected_upstream.as_ref().map(|u| u.upstream_id()) {
+            Some(id) if !id.is_empty() => id.to_string(),
+            // Inline upstreams are reported under their route.
+            _ => format!("route/{}", ctx.route.as_ref().map_or("-", |r| r.id())),
+        };
+        let connect = ctx.upstream_info.peer_selected_at.map(|at| at.elapsed());
+        conn_stats::record(&upstream, &peer.sni, reused, connect);
+        Ok(())
+    }
+
     /// Modify the request before it is sent to the upstream
     async fn upstream_request_filter(
         &self,
@@ -309,14 +664,31 @@ impl ProxyHttp for HttpService {
         upstream_request: &mut RequestHeader,
         ctx: &mut Self::CTX,
     ) -> Result<()> {
-        run_global_then_route_upstream_request_filter(
+        // Pingora calls this once the upstream connection is established.
+        ctx.upstream_info.connected_at = Some(Instant::now());
+        compression::restore_accept_encoding(upstream_request, ctx)?;
+        // Before plugins, so a proxy-rewrite `uri` still replaces the whole path.
+        if let Some(prefix) = ctx
+            .route
+            .as_ref()
+            .and_then(|route| route.strip_path_prefix())
+        {
+            strip_path_prefix(upstream_request, prefix)
+                .map_err(|e| Error::because(ErrorType::InternalError, "strip_path_prefix", e))?;
+        }
+
+        let started = Instant::now();
+        let result = run_global_then_route_upstream_request_filter(
             ctx.global_plugin.clone(),
             ctx.plugin.clone(),
             session,
             upstream_request,
             ctx,
         )
-        .await?;
+        .await;
+        ctx.timings.upstream_request_filter += started.elapsed();
+        result?;
+        header_hygiene::sanitize_upstream_request(upstream_request);
 
         // Rewrite host header
         // Priority: upstream_override > route upstream
@@ -326,7 +698,7 @@ impl ProxyHttp for HttpService {
                     // Do nothing, preserve original host
                 }
                 config::UpstreamPassHost::REWRITE => {
-                    upstream.upstream_host_rewrite(upstream_request);
+                    upstream.upstream_host_rewrite(upstream_request, ctx.route_params.as_deref());
                 }
                 config::UpstreamPassHost::NODE => {
                     if let Some(peer) = ctx.peer.as_ref() {
@@ -339,7 +711,8 @@ impl ProxyHttp for HttpService {
                 }
             }
         }
-        Ok(())
+        // Last, so no plugin can turn the `Connection: close` it may add back.
+        arm_deadline(ctx, upstream_request)
     }
 
     async fn response_filter(
@@ -348,6 +721,37 @@ impl ProxyHttp for HttpService {
         upstream_response: &mut ResponseHeader,
         ctx: &mut Self::CTX,
     ) -> Result<()> {
+        // Only responses that crossed an upstream connection carry upstream details.
+        if ctx.upstream_info.connected_at.is_some() && ctx.upstream_info.status.is_none() {
+            ctx.upstream_info.responded_at = Some(Instant::now());
+            ctx.upstream_info.status = Some(upstream_response.status.as_u16());
+        }
+
+        header_hygiene::sanitize_response(upstream_response);
+        // Set for trailers-only gRPC responses; trailers may still override it.
+        ctx.grpc_status = grpc_status(&upstream_response.headers);
+
+        if let (Some(upstream), Some(peer)) = (ctx.selected_upstream.as_ref(), ctx.peer.as_ref()) {
+            if let Some(cookie) = upstream.affinity_cookie(session.req_header(), peer) {
+                upstream_response.append_header(http::header::SET_COOKIE, cookie)?;
+            }
+        }
+
+        // Streaming responses are flushed as they arrive: the compression encoder would
+        // hold chunks back, and plugins check `ctx.streaming` before buffering the body.
+        if !ctx.streaming && is_streaming_response(upstream_response) {
+            ctx.streaming = true;
+        }
+        if ctx.streaming {
+            if let Some(compression) = session
+                .downstream_modules_ctx
+                .get_mut::<ResponseCompression>()
+            {
+                compression.adjust_level(0);
+                compression.adjust_decompression(false);
+            }
+        }
+
         // Add X-Cache-Status header logic
         if let Some(settings) = ctx.get::<Arc<CacheSettings>>(CTX_KEY_CACHE_SETTINGS) {
             let cache_phase = session.cache.phase();
@@ -362,20 +766,48 @@ impl ProxyHttp for HttpService {
             CACHE_REQUESTS
                 .with_label_values(&[&status_str.to_ascii_lowercase(), "local"])
                 .inc();
+            cache::record_route_outcome(ctx, cache::route_outcome(&cache_phase));
             if !settings.hide_cache_headers {
                 upstream_response.insert_header("X-Cache-Status", status_str)?;
                 upstream_response.insert_header("X-Cache-Scope", "local")?;
             }
         }
 
-        run_global_then_route_response_filter(
+        let started = Instant::now();
+        let result = run_global_then_route_response_filter(
             ctx.global_plugin.clone(),
             ctx.plugin.clone(),
             session,
             upstream_response,
             ctx,
         )
-        .await
+        .await;
+        ctx.timings.response_filter += started.elapsed();
+        result
+    }
+
+    async fn request_body_filter(
+        &self,
+        session: &mut Session,
+        body: &mut Option<Bytes>,
+        end_of_stream: bool,
+        ctx: &mut Self::CTX,
+    ) -> Result<()> {
+        if ctx.deadline_passed() {
+            return Err(deadline_exceeded());
+        }
+        let started = Instant::now();
+        let result = run_global_then_route_request_body_filter(
+            ctx.global_plugin.clone(),
+            ctx.plugin.clone(),
+            session,
+            body,
+            end_of_stream,
+            ctx,
+        )
+        .await;
+        ctx.timings.request_body_filter += started.elapsed();
+        result
     }
 
     fn response_body_filter(
@@ -385,18 +817,53 @@ impl ProxyHttp for HttpService {
         end_of_stream: bool,
         ctx: &mut Self::CTX,
     ) -> Result<Option<Duration>> {
-        run_global_then_route_response_body_filter(
+        // Ends a response still streaming at the deadline; the client sees it cut short.
+        if ctx.deadline_passed() {
+            return Err(deadline_exceeded());
+        }
+        // The exchange is over before its connection goes back to the pool.
+        if end_of_stream {
+            disarm_deadline(ctx);
+        }
+        let started = Instant::now();
+        let result = run_global_then_route_response_body_filter(
             ctx.global_plugin.clone(),
             ctx.plugin.clone(),
             session,
             body,
             end_of_stream,
             ctx,
-        )?;
+        );
+        ctx.timings.response_body_filter += started.elapsed();
+        result?;
+        Ok(ctx.response_body_delay.take())
+    }
+
+    async fn response_trailer_filter(
+        &self,
+        session: &mut Session,
+        upstream_trailers: &mut http::HeaderMap,
+        ctx: &mut Self::CTX,
+    ) -> Result<Option<Bytes>> {
+        ctx.grpc_status = grpc_status(upstream_trailers).or(ctx.grpc_status);
+        run_global_then_route_response_trailer_filter(
+            ctx.global_plugin.clone(),
+            ctx.plugin.clone(),
+            session,
+            upstream_trailers,
+            ctx,
+        )
+        .await?;
+        // Trailers are passed on as trailers, never folded into the body.
         Ok(None)
     }
 
     fn request_cache_filter(&self, session: &mut Session, ctx: &mut Self::CTX) -> Result<()> {
+        if ctx.streaming {
+            log::debug!("Skipping cache for streaming route");
+            return Ok(());
+        }
+
         // Check for cache bypass headers (optimized to avoid repeated map lookups)
         let headers = &session.req_header().headers;
 
@@ -563,6 +1030,10 @@ impl ProxyHttp for HttpService {
             return Ok(RespCacheable::Uncacheable(NoCacheReason::OriginNotCache));
         }
 
+        if is_streaming_response(resp) {
+            return Ok(RespCacheable::Uncacheable(NoCacheReason::OriginNotCache));
+        }
+
         if !settings.statuses.contains(&resp.status.as_u16()) {
             return Ok(RespCacheable::Uncacheable(NoCacheReason::OriginNotCache));
         }
@@ -576,7 +1047,10 @@ impl ProxyHttp for HttpService {
         }
 
         let cc = CacheControl::from_resp_headers(resp);
-        let final_cc = ensure_max_age(cc, settings);
+        let mut final_cc = ensure_max_age(cc, settings);
+        if let (Some(cc), Some(window)) = (final_cc.as_mut(), settings.stale_if_error) {
+            cap_stale_if_error(cc, window);
+        }
 
         // Only treat the request as authorized when credentials were actually
         // present; the previous hard-coded `true` made every response require
@@ -592,6 +1066,43 @@ impl ProxyHttp for HttpService {
         ))
     }
 
+    async fn upstream_response_filter(
+        &self,
+        session: &mut Session,
+        upstream_response: &mut ResponseHeader,
+        ctx: &mut Self::CTX,
+    ) -> Result<()> {
+        // Fail a 5xx so Pingora's stale-if-error handling answers from the cache instead.
+        if upstream_response.status.is_server_error()
+            && session.cache.can_serve_stale_error()
+            && ctx
+                .get::<Arc<CacheSettings>>(CTX_KEY_CACHE_SETTINGS)
+                .is_some_and(|settings| settings.serve_stale_on_5xx)
+        {
+            log::debug!(
+                "Upstream returned {}, serving stale cached response",
+                upstream_response.status
+            );
+            return Err(Error::create(
+                ErrorType::HTTPStatus(upstream_response.status.as_u16()),
+                ErrorSource::Upstream,
+                Some("upstream 5xx with stale cache available".into()),
+                None,
+            ));
+        }
+        // Fail a configured 5xx so the fallback upstream or response answers instead.
+        let status = upstream_response.status.as_u16();
+        if fallback::intercepts(ctx, status) {
+            return Err(Error::create(
+                ErrorType::HTTPStatus(status),
+                ErrorSource::Upstream,
+                Some("upstream status handled by fallback".into()),
+                None,
+            ));
+        }
+        Ok(())
+    }
+
     async fn logging(&self, session: &mut Session, e: Option<&Error>, ctx: &mut Self::CTX) {
         run_global_then_route_logging(
             ctx.global_plugin.clone(),
@@ -601,12 +1112,96 @@ impl ProxyHttp for HttpService {
             ctx,
         )
         .await;
+        slow_log::record(session, ctx);
+        tag_cached_response(session, ctx);
+        release_selected_backend(ctx);
+        disarm_deadline(ctx);
+        if let Some(vars) = ctx.vars.as_mut() {
+            vars.remove(CTX_KEY_OVERLOAD_SLOT);
+        }
+    }
+
+    /// Responds to a request that failed before a response reached the client, using the
+    /// request's `error-page` configuration when present.
+    async fn fail_to_proxy(
+        &self,
+        session: &mut Session,
+        e: &Error,
+        ctx: &mut Self::CTX,
+    ) -> FailToProxy
+    where
+        Self::CTX: Send,
+    {
+        // Same status mapping as Pingora's default handler.
+        let code = match e.etype() {
+            ErrorType::HTTPStatus(code) => *code,
+            _ => match e.esource() {
+                ErrorSource::Upstream => 502,
+                ErrorSource::Downstream => match e.etype() {
+                    ErrorType::WriteError | ErrorType::ReadError | ErrorType::ConnectionClosed => 0,
+                    _ => 400,
+                },
+                ErrorSource::Internal | ErrorSource::Unset => 500,
+            },
+        };
+        // Upstream timeouts are capped at the deadline; report them as such.
+        let code = if code >= 500 && ctx.deadline_passed() {
+            504
+        } else {
+            code
+        };
+        if code >= 500 {
+            match fallback::respond(session, ctx).await {
+                Ok(Some(status)) => {
+                    return FailToProxy {
+                        error_code: status,
+                        can_reuse_downstream: false,
+                    }
+                }
+                Ok(None) => {}
+                Err(e) => log::error!("failed to send fallback response to downstream: {e}"),
+            }
+        }
+        if code > 0 {
+            if let Err(e) = error_page::respond_error(session, ctx, code).await {
+                log::error!("failed to send error response to downstream: {e}");
+            }
+        }
+        FailToProxy {
+            error_code: code,
+            can_reuse_downstream: false,
+        }
+    }
+
+    /// Errors after the connection was established, including upstream timeouts and
+    /// statuses failed by `upstream_response_filter`, may move the request to the
+    /// `fallback` upstream.
+    fn error_while_proxy(
+        &self,
+        peer: &HttpPeer,
+        session: &mut Session,
+        e: Box<Error>,
+        ctx: &mut Self::CTX,
+        client_reused: bool,
+    ) -> Box<Error> {
+        let mut e = e.more_context(format!("Peer: {peer}"));
+        disarm_deadline(ctx);
+        if ctx.deadline_passed() {
+            e.set_retry(false);
+            return e;
+        }
+        e.retry
+            .decide_reuse(client_reused && !session.retry_buffer_truncated());
+        if !e.retry() && e.esource() == &ErrorSource::Upstream && fallback::reroute(session, ctx) {
+            e.set_retry(true);
+        }
+        e
     }
 
     /// This filter is called when there is an error in the process of establishing a connection to the upstream.
     fn fail_to_connect(
         &self,
-        _session: &mut Session,
+        session: &mut Session,
         _peer: &HttpPeer,
         ctx: &mut Self::CTX,
         mut e: Box<Error>,
@@ -618,17 +1213,144 @@ impl ProxyHttp for HttpService {
                         Some(timeout) => ctx.elapsed_ms() <= (timeout * 1000) as u128,
                         None => true,
                     };
-                    if within_timeout {
+                    if within_timeout && !ctx.deadline_passed() {
                         ctx.tries += 1;
                         e.set_retry(true);
                     }
                 }
             }
         }
+        if !e.retry() && !ctx.deadline_passed() && fallback::reroute(session, ctx) {
+            e.set_retry(true);
+        }
         e
     }
 }
 
+/// Cap the peer's timeouts at the time left before the route's `timeout.total`
+/// deadline, so connecting, sending or waiting on the upstream cannot outlive it.
+/// Pingora selects a new peer for every attempt, so retries are capped again with
+/// whatever time is left.
+fn apply_deadline(ctx: &ProxyContext, peer: &mut HttpPeer) -> Result<()> {
+    let Some(left) = ctx.remaining() else {
+        return Ok(());
+    };
+    if left.is_zero() {
+        return Err(deadline_exceeded());
+    }
+    let cap = |timeout: Option<Duration>| Some(timeout.map_or(left, |t| t.min(left)));
+    let options = &mut peer.options;
+    options.connection_timeout = cap(options.connection_timeout);
+    options.total_connection_timeout = cap(options.total_connection_timeout);
+    options.read_timeout = cap(options.read_timeout);
+    options.write_timeout = cap(options.write_timeout);
+    Ok(())
+}
+
+/// Shuts an upstream connection down once the route's deadline passes, failing the
+/// read or write pending on it. Per-operation timeouts restart with every chunk, so
+/// they alone cannot bound an upstream that trickles its response. Aborted on drop.
+#[cfg(unix)]
+struct DeadlineWatch(tokio::task::AbortHandle);
+
+#[cfg(unix)]
+impl DeadlineWatch {
+    fn spawn(socket: OwnedFd, deadline: Instant) -> Self {
+        let task = tokio::spawn(async move {
+            tokio::time::sleep_until(deadline.into()).await;
+            // SAFETY: `socket` is a descriptor owned by this task.
+            unsafe { libc::shutdown(socket.as_raw_fd(), libc::SHUT_RDWR) };
+        });
+        Self(task.abort_handle())
+    }
+}
+
+#[cfg(unix)]
+impl Drop for DeadlineWatch {
+    fn drop(&mut self) {
+        self.0.abort();
+    }
+}
+
+/// Arms the deadline watch on the socket saved by `connected_to_upstream` and asks for
+/// the connection to be closed after the exchange. A watched socket therefore never goes
+/// back to the keepalive pool: a watch that outlives its request can only shut down a
+/// connection no other request uses. HTTP/2 connections carry other requests' streams,
+/// so they rely on the capped peer timeouts.
+fn arm_deadline(ctx: &mut ProxyContext, upstream_request: &mut RequestHeader) -> Result<()> {
+    #[cfg(unix)]
+    {
+        let socket = ctx
+            .vars
+            .as_mut()
+            .and_then(|vars| vars.remove(CTX_KEY_UPSTREAM_SOCKET))
+            .and_then(|socket| socket.downcast::<OwnedFd>().ok());
+        let (Some(socket), Some(deadline)) = (socket, ctx.deadline) else {
+            return Ok(());
+        };
+        if upstream_request.version == http::Version::HTTP_2 {
+            return Ok(());
+        }
+        let upgrade = upstream_request
+            .headers
+            .get(http::header::CONNECTION)
+            .and_then(|v| v.to_str().ok())
+            .is_some_and(|v| {
+                v.split(',')
+                    .any(|token| token.trim().eq_ignore_ascii_case("upgrade"))
+            });
+        upstream_request.insert_header(
+            http::header::CONNECTION,
+            if upgrade { "Upgrade, close" } else { "close" },
+        )?;
+        ctx.set(
+            CTX_KEY_DEADLINE_WATCH,
+            DeadlineWatch::spawn(*socket, deadline),
+        );
+    }
+    #[cfg(not(unix))]
+    let _ = (ctx, upstream_request);
+    Ok(())
+}
+
+/// Stops the current attempt's deadline watch once its exchange is over or has failed.
+fn disarm_deadline(ctx: &mut ProxyContext) {
+    #[cfg(unix)]
+    if let Some(vars) = ctx.vars.as_mut() {
+        vars.remove(CTX_KEY_DEADLINE_WATCH);
+        vars.remove(CTX_KEY_UPSTREAM_SOCKET);
+    }
+    #[cfg(not(unix))]
+    let _ = ctx;
+}
+
+fn deadline_exceeded() -> Box<Error> {
+    Error::explain(ErrorType::HTTPStatus(504), "route total timeout exceeded")
+}
+
+/// Hand the selected backend back to its upstream. Clears the selection, so each
+/// selection is released exactly once.
+fn release_selected_backend(ctx: &mut ProxyContext) {
+    if let (Some(upstream), Some(peer)) = (ctx.selected_upstream.take(), ctx.peer.take()) {
+        upstream.release_backend(&peer, ctx.upstream_info.response_time());
+    }
+}
+
+/// Sets `stale-if-error` to the configured window, capping a larger origin value.
+fn cap_stale_if_error(cc: &mut CacheControl, window: Duration) {
+    let origin_secs = cc
+        .directives
+        .get("stale-if-error")
+        .and_then(|value| value.as_ref())
+        .and_then(|value| std::str::from_utf8(&value.0).ok())
+        .and_then(|value| value.trim_matches('"').parse::<u64>().ok());
+    let secs = origin_secs.map_or(window.as_secs(), |origin| origin.min(window.as_secs()));
+    cc.directives.insert(
+        "stale-if-error".to_string(),
+        Some(DirectiveValue(secs.to_string().into_bytes())),
+    );
+}
+
 /// Ensures CacheControl has max-age set, adding default TTL if missing.
 /// Also handles s-maxage and stale-while-revalidate directives based on settings.
 fn ensure_max_age(cc: Option<CacheControl>, settings: &CacheSettings) -> Option<CacheControl> {
@@ -713,6 +1435,49 @@ fn ensure_max_age(cc: Option<CacheControl>, settings: &CacheSettings) -> Option<
 mod tests {
     use super::*;
 
+    #[test]
+    fn wildcard_listener_matches_any_local_ip_on_its_port() {
+        let any: SocketAddr = "0.0.0.0:8080".parse().unwrap();
+        let local: SocketAddr = "10.0.0.5:8080".parse().unwrap();
+        assert!(is_listener_addr(&any, &local));
+        assert!(!is_listener_addr(&any, &"10.0.0.5:8443".parse().unwrap()));
+
+        let bound: SocketAddr = "127.0.0.1:8080".parse().unwrap();
+        assert!(!is_listener_addr(&bound, &local));
+        assert!(is_listener_addr(&bound, &"127.0.0.1:8080".parse().unwrap()));
+    }
+
+    #[test]
+    fn downstream_keepalive_is_capped_by_the_header_timeout() {
+        let config = |keepalive, header| Downstream {
+            keepalive_timeout: keepalive,
+            header_timeout: header,
+            ..Default::default()
+        };
+        assert_eq!(
+            downstream_keepalive(&config(Some(30), None), Some(0)),
+            Some(30)
+        );
+        assert_eq!(
+            downstream_keepalive(&config(Some(30), None), None),
+            None,
+            "connection: close is kept"
+        );
+        assert_eq!(downstream_keepalive(&config(Some(0), None), Some(0)), None);
+        assert_eq!(
+            downstream_keepalive(&config(Some(30), Some(10)), Some(0)),
+            Some(10)
+        );
+        assert_eq!(
+            downstream_keepalive(&config(None, Some(10)), Some(0)),
+            Some(10)
+        );
+        assert_eq!(
+            downstream_keepalive(&config(None, Some(10)), Some(5)),
+            Some(5)
+        );
+    }
+
     #[test]
     fn vary_star_is_detected_across_all_header_lines() {
         let mut headers = http::HeaderMap::new();
@@ -741,6 +1506,131 @@ mod tests {
         assert!(headers_indicate_shared_cache_credentials(&headers));
     }
 
+    #[test]
+    fn stale_if_error_is_capped_at_configured_window() {
+        let stale_if_error = |header: &str, window: u64| {
+            let mut resp = ResponseHeader::build(200, None).unwrap();
+            resp.insert_header("cache-control", header).unwrap();
+            let mut cc = CacheControl::from_resp_headers(&resp).unwrap();
+            cap_stale_if_error(&mut cc, Duration::from_secs(window));
+            cc.stale_if_error().unwrap().unwrap().to_string()
+        };
+        assert_eq!(stale_if_error("max-age=60, stale-if-error=900", 300), "300");
+        assert_eq!(stale_if_error("max-age=60, stale-if-error=30", 300), "30");
+        assert_eq!(stale_if_error("max-age=60", 300), "300");
+    }
+
+    #[test]
+    fn peer_timeouts_are_capped_at_the_deadline() {
+        let mut peer = HttpPeer::new("127.0.0.1:80", false, String::new());
+        peer.options.read_timeout = Some(Duration::from_secs(60));
+        let mut ctx = ProxyContext::default();
+        apply_deadline(&ctx, &mut peer).unwrap();
+        assert_eq!(peer.options.read_timeout, Some(Duration::from_secs(60)));
+        assert_eq!(peer.options.connection_timeout, None);
+
+        ctx.deadline = Some(Instant::now() + Duration::from_secs(10));
+        apply_deadline(&ctx, &mut peer).unwrap();
+        assert!(peer.options.read_timeout.unwrap() <= Duration::from_secs(10));
+        assert!(peer.options.connection_timeout.unwrap() <= Duration::from_secs(10));
+
+        ctx.deadline = Some(Instant::now());
+        let err = apply_deadline(&ctx, &mut peer).unwrap_err();
+        assert_eq!(err.etype(), &ErrorType::HTTPStatus(504));
+    }
+
+    #[test]
+    fn retries_are_capped_at_the_time_left() {
+        let ctx = ProxyContext {
+            deadline: Some(Instant::now() + Duration::from_millis(300)),
+            ..Default::default()
+        };
+        let mut first = HttpPeer::new("127.0.0.1:80", false, String::new());
+        first.options.read_timeout = Some(Duration::from_secs(60));
+        let mut retry = first.clone();
+        apply_deadline(&ctx, &mut first).unwrap();
+        std::thread::sleep(Duration::from_millis(100));
+        apply_deadline(&ctx, &mut retry).unwrap();
+        assert!(retry.options.read_timeout < first.options.read_timeout);
+        assert!(retry.options.write_timeout.unwrap() <= Duration::from_millis(200));
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn slow_upstream_is_cut_off_at_the_deadline() {
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let mut client = tokio::net::TcpStream::connect(listener.local_addr().unwrap())
+            .await
+            .unwrap();
+        // The upstream accepts the request and never answers.
+        let (_upstream, _) = listener.accept().await.unwrap();
+        client.write_all(b"GET / HTTP/1.1\r\n\r\n").await.unwrap();
+
+        let mut ctx = ProxyContext {
+            deadline: Some(Instant::now() + Duration::from_millis(100)),
+            ..Default::default()
+        };
+        let socket = unsafe { BorrowedFd::borrow_raw(client.as_raw_fd()) }
+            .try_clone_to_owned()
+            .unwrap();
+        ctx.set(CTX_KEY_UPSTREAM_SOCKET, socket);
+        let mut request = RequestHeader::build("GET", b"/", None).unwrap();
+        arm_deadline(&mut ctx, &mut request).unwrap();
+        // A watched connection is never handed back to the keepalive pool.
+        assert_eq!(request.headers["connection"], "close");
+
+        let mut buf = [0u8; 16];
+        let read = tokio::time::timeout(Duration::from_secs(5), client.read(&mut buf)).await;
+        assert!(matches!(read, Ok(Ok(0)) | Ok(Err(_))), "{read:?}");
+        assert!(ctx.deadline_passed());
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn disarmed_or_http2_connections_are_left_open() {
+        use tokio::io::AsyncReadExt;
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let mut ctx = ProxyContext {
+            deadline: Some(Instant::now() + Duration::from_millis(50)),
+            ..Default::default()
+        };
+        let (mut clients, mut upstreams) = (Vec::new(), Vec::new());
+        for version in [http::Version::HTTP_11, http::Version::HTTP_2] {
+            let client = tokio::net::TcpStream::connect(listener.local_addr().unwrap())
+                .await
+                .unwrap();
+            upstreams.push(listener.accept().await.unwrap().0);
+            let socket = unsafe { BorrowedFd::borrow_raw(client.as_raw_fd()) }
+                .try_clone_to_owned()
+                .unwrap();
+            ctx.set(CTX_KEY_UPSTREAM_SOCKET, socket);
+            let mut request = RequestHeader::build("GET", b"/", None).unwrap();
+            request.set_version(version);
+            request.insert_header("connection", "Upgrade").unwrap();
+            arm_deadline(&mut ctx, &mut request).unwrap();
+            let expected = if version == http::Version::HTTP_2 {
+                "Upgrade"
+            } else {
+                "Upgrade, close"
+            };
+            assert_eq!(request.headers["connection"], expected);
+            if version == http::Version::HTTP_11 {
+                disarm_deadline(&mut ctx);
+            }
+            clients.push(client);
+        }
+
+        tokio::time::sleep(Duration::from_millis(150)).await;
+        for client in &mut clients {
+            let mut buf = [0u8; 16];
+            let read = tokio::time::timeout(Duration::from_millis(50), client.read(&mut buf)).await;
+            assert!(read.is_err(), "connection was shut down: {read:?}");
+        }
+    }
+
     #[test]
     fn eviction_manager_uses_configured_memory() {
         // init_cache_defaults is idempotent (first call wins); in a fresh test binary this
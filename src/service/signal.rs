@@ -0,0 +1,223 @@
+//! Operator signal handling.
+//!
+//! - `SIGUSR1` asks the logging service to reopen its file and reopens Pingora's
+//!   `error_log` (logrotate `postrotate`).
+//! - `SIGHUP` re-reads the YAML file and republishes static resources. When etcd is the
+//!   configuration source the signal is ignored: etcd remains authoritative.
+//! - `SIGQUIT` is handled by Pingora (listener hand-over to an upgraded process); this
+//!   service only follows the server execution phase to fail readiness while draining.
+//! - `SIGTERM` goes through [`DrainingShutdownSignal`], which fails readiness before
+//!   Pingora closes the listeners.
+
+use std::time::Duration;
+
+use async_trait::async_trait;
+use pingora::{
+    server::{ExecutionPhase, ListenFds, ShutdownSignal, ShutdownSignalWatch, ShutdownWatch},
+    services::Service,
+};
+use tokio::sync::broadcast;
+
+use crate::{
+    config::Config, core::status, logging, proxy::control_plane::reload_static_configurations,
+};
+
+/// Background service that turns process signals into log reopen and config reload requests.
+pub struct SignalService {
+    /// YAML path re-read on `SIGHUP`; `None` disables static reload (etcd mode).
+    config_path: Option<String>,
+    /// Server execution phases (`Server::watch_execution_phase`).
+    execution_phase: Option<broadcast::Receiver<ExecutionPhase>>,
+    /// Pingora `error_log` that stderr was redirected to on daemonization.
+    error_log: Option<String>,
+}
+
+impl SignalService {
+    pub fn new(config_path: Option<String>) -> Self {
+        Self {
+            config_path,
+            execution_phase: None,
+            error_log: None,
+        }
+    }
+
+    /// Reopen `path` as stderr on `SIGUSR1`.
+    pub fn with_error_log(mut self, path: Option<String>) -> Self {
+        self.error_log = path;
+        self
+    }
+
+    /// Follow the server execution phase to drain during a graceful upgrade.
+    pub fn with_execution_phase(mut self, phases: broadcast::Receiver<ExecutionPhase>) -> Self {
+        self.execution_phase = Some(phases);
+        self
+    }
+
+    async fn reload(&self) {
+        let Some(path) = self.config_path.as_deref() else {
+            log::info!("SIGHUP ignored: configuration is managed by etcd");
+            return;
+        };
+        let config = match Config::load_from_yaml(path) {
+            Ok(config) => config,
+            Err(e) => {
+                log::error!("SIGHUP reload rejected, keeping current configuration: {e}");
+                return;
+            }
+        };
+        match reload_static_configurations(&config).await {
+            Ok(snapshot) => log::info!(
+                "SIGHUP reload published {} routes from {path}",
+                snapshot.routes.len()
+            ),
+            Err(e) => {
+                log::error!("SIGHUP reload failed, keeping current configuration: {e}")
+            }
+        }
+    }
+}
+
+#[async_trait]
+impl Service for SignalService {
+    #[cfg(unix)]
+    async fn start_service(
+        &mut self,
+        _fds: Option<ListenFds>,
+        mut shutdown: ShutdownWatch,
+        _listeners_per_fd: usize,
+    ) {
+        use tokio::signal::unix::{signal, SignalKind};
+
+        let (mut sighup, mut sigusr1) = match (
+            signal(SignalKind::hangup()),
+            signal(SignalKind::user_defined1()),
+        ) {
+            (Ok(sighup), Ok(sigusr1)) => (sighup, sigusr1),
+            (Err(e), _) | (_, Err(e)) => {
+                log::error!("Failed to install SIGHUP/SIGUSR1 handlers: {e}");
+                return;
+            }
+        };
+
+        let mut phases = self.execution_phase.take();
+        loop {
+            tokio::select! {
+                biased;
+                _ = shutdown.changed() => {
+                    if *shutdown.borrow() {
+                        return;
+                    }
+                }
+                phase = next_phase(&mut phases) => match phase {
+                    Some(ExecutionPhase::GracefulUpgradeTransferringFds) => {
+                        log::info!("SIGQUIT received, handing listeners over to the upgraded process");
+                        status::mark_draining();
+                    }
+                    Some(_) => {}
+                    None => phases = None,
+                },
+                Some(()) = sigusr1.recv() => {
+                    log::info!("SIGUSR1 received, reopening log files");
+                    logging::request_reopen();
+                    if let Some(path) = self.error_log.as_deref() {
+                        if let Err(e) = logging::reopen_error_log(path) {
+                            log::error!("Failed to reopen error log '{path}': {e}");
+                        }
+                    }
+                }
+                Some(()) = sighup.recv() => {
+                    log::info!("SIGHUP received, reloading configuration");
+                    self.reload().await;
+                }
+            }
+        }
+    }
+
+    #[cfg(not(unix))]
+    async fn start_service(
+        &mut self,
+        _fds: Option<ListenFds>,
+        _shutdown: ShutdownWatch,
+        _listeners_per_fd: usize,
+    ) {
+    }
+
+    fn name(&self) -> &'static str {
+        "Signal handler"
+    }
+
+    fn threads(&self) -> Option<usize> {
+        Some(1)
+    }
+}
+
+/// Next execution phase; pends forever once the server stops publishing phases.
+#[cfg(unix)]
+async fn next_phase(
+    phases: &mut Option<broadcast::Receiver<ExecutionPhase>>,
+) -> Option<ExecutionPhase> {
+    let Some(receiver) = phases else {
+        return std::future::pending().await;
+    };
+    loop {
+        match receiver.recv().await {
+            Ok(phase) => return Some(phase),
+            Err(broadcast::error::RecvError::Lagged(_)) => continue,
+            Err(broadcast::error::RecvError::Closed) => return None,
+        }
+    }
+}
+
+/// Shutdown signal watch replacing Pingora's default one.
+///
+/// `SIGTERM` flips readiness to `draining` and keeps the listeners accepting for
+/// `readiness_delay`, so load balancers stop routing here before connections are
+/// refused. `SIGINT` still exits immediately, even during the delay.
+pub struct DrainingShutdownSignal {
+    readiness_delay: Duration,
+}
+
+impl DrainingShutdownSignal {
+    pub fn new(readiness_delay: Duration) -> Self {
+        Self { readiness_delay }
+    }
+}
+
+#[cfg(unix)]
+#[async_trait]
+impl ShutdownSignalWatch for DrainingShutdownSignal {
+    async fn recv(&self) -> ShutdownSignal {
+        use tokio::signal::unix::{signal, SignalKind};
+
+        let (mut sigquit, mut sigterm, mut sigint) = match (
+            signal(SignalKind::quit()),
+            signal(SignalKind::terminate()),
+            signal(SignalKind::interrupt()),
+        ) {
+            (Ok(sigquit), Ok(sigterm), Ok(sigint)) => (sigquit, sigterm, sigint),
+            (Err(e), _, _) | (_, Err(e), _) | (_, _, Err(e)) => {
+                log::error!("Failed to install shutdown signal handlers: {e}");
+                return std::future::pending().await;
+            }
+        };
+
+        tokio::select! {
+            _ = sigquit.recv() => ShutdownSignal::GracefulUpgrade,
+            _ = sigint.recv() => ShutdownSignal::FastShutdown,
+            _ = sigterm.recv() => {
+                status::mark_draining();
+                if self.readiness_delay.is_zero() {
+                    return ShutdownSignal::GracefulTerminate;
+                }
+                log::info!(
+                    "SIGTERM received, failing readiness for {}s before closing listeners",
+                    self.readiness_delay.as_secs()
+                );
+                tokio::select! {
+                    _ = tokio::time::sleep(self.readiness_delay) => ShutdownSignal::GracefulTerminate,
+                    _ = sigint.recv() => ShutdownSignal::FastShutdown,
+                }
+            }
+        }
+    }
+}
@@ -7,7 +7,14 @@ use serde::Serialize;
 
 use crate::{
     config::Status,
-    core::{constant_time_eq, status},
+    core::{constant_time_eq, metrics, status},
+    proxy::{
+        runtime::{RuntimeSnapshot, RUNTIME},
+        upstream::{
+            conn_stats::{self, ConnReuse},
+            load_balancer::BackendHealth,
+        },
+    },
 };
 
 #[derive(Serialize)]
@@ -22,6 +29,63 @@ struct ReadyResponse {
     reason: Option<&'static str>,
 }
 
+#[derive(Serialize)]
+struct UpstreamHealth {
+    key: String,
+    health_check: bool,
+    backends: Vec<BackendHealth>,
+}
+
+#[derive(Serialize)]
+struct UpstreamsResponse {
+    revision: i64,
+    upstreams: Vec<UpstreamHealth>,
+    connections: Vec<ConnReuse>,
+}
+
+#[derive(Serialize)]
+struct RouteState {
+    id: String,
+    uris: Vec<String>,
+    hosts: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    upstream_id: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    service_id: Option<String>,
+}
+
+#[derive(Serialize)]
+struct ServiceState {
+    id: String,
+    hosts: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    upstream_id: Option<String>,
+}
+
+#[derive(Serialize)]
+struct UpstreamState {
+    id: String,
+    backends: Vec<String>,
+}
+
+#[derive(Serialize)]
+struct SslState {
+    id: String,
+    snis: Vec<String>,
+}
+
+/// The published runtime snapshot reduced to what can be compared with the config store.
+#[derive(Serialize)]
+struct StateResponse {
+    revision: i64,
+    routes: Vec<RouteState>,
+    services: Vec<ServiceState>,
+    upstreams: Vec<UpstreamState>,
+    ssls: Vec<SslState>,
+    global_rules: Vec<String>,
+    ip_lists: Vec<String>,
+}
+
 /// HTTP application for serving public probes and protected diagnostics.
 pub struct StatusHttpApp {
     config: Status,
@@ -69,6 +133,14 @@ impl ServeHttp for StatusHttpApp {
         match http_session.req_header().uri.path() {
             "/status/live" => handle_live_endpoint(),
             "/status/ready" => handle_ready_endpoint(),
+            "/metrics" if self.config.public_metrics => handle_metrics_endpoint(),
+            "/metrics" if self.config.diagnostics_enabled() => {
+                if self.diagnostics_authorized(http_session) {
+                    handle_metrics_endpoint()
+                } else {
+                    forbidden_response()
+                }
+            }
             "/status/config" if self.config.diagnostics_enabled() => {
                 if self.diagnostics_authorized(http_session) {
                     handle_config_endpoint()
@@ -76,6 +148,20 @@ impl ServeHttp for StatusHttpApp {
                     forbidden_response()
                 }
             }
+            "/status/upstreams" if self.config.diagnostics_enabled() => {
+                if self.diagnostics_authorized(http_session) {
+                    handle_upstreams_endpoint()
+                } else {
+                    forbidden_response()
+                }
+            }
+            "/status/state" if self.config.diagnostics_enabled() => {
+                if self.diagnostics_authorized(http_session) {
+                    handle_state_endpoint()
+                } else {
+                    forbidden_response()
+                }
+            }
             _ => not_found_response(),
         }
     }
@@ -101,10 +187,131 @@ fn handle_ready_endpoint() -> Response<Vec<u8>> {
     )
 }
 
+fn handle_metrics_endpoint() -> Response<Vec<u8>> {
+    Response::builder()
+        .status(StatusCode::OK)
+        .header("Content-Type", "text/plain; version=0.0.4")
+        .body(metrics::encode_text())
+        .unwrap_or_else(|e| {
+            log::error!("Failed to build metrics HTTP response: {e}");
+            let mut resp = Response::new(b"Internal Server Error".to_vec());
+            *resp.status_mut() = StatusCode::INTERNAL_SERVER_ERROR;
+            resp
+        })
+}
+
 fn handle_config_endpoint() -> Response<Vec<u8>> {
     json_response(StatusCode::OK, &status::status_view())
 }
 
+/// Health of every upstream the runtime snapshot checks, keyed like the shared health
+/// check service: `upstream/{id}`, `route/{id}/inline` and `service/{id}/inline`.
+fn handle_upstreams_endpoint() -> Response<Vec<u8>> {
+    let runtime = RUNTIME.load();
+    let named = runtime
+        .upstreams
+        .iter()
+        .map(|(id, upstream)| (format!("upstream/{id}"), upstream));
+    let services = runtime.services.iter().filter_map(|(id, service)| {
+        let upstream = service.inline_upstream.as_ref()?;
+        Some((format!("service/{id}/inline"), upstream))
+    });
+    let routes = runtime.routes.iter().filter_map(|(id, route)| {
+        let upstream = route.inline_upstream.as_ref()?;
+        Some((format!("route/{id}/inline"), upstream))
+    });
+
+    let mut upstreams: Vec<UpstreamHealth> = named
+        .chain(services)
+        .chain(routes)
+        .map(|(key, upstream)| UpstreamHealth {
+            key,
+            health_check: upstream.inner.checks.is_some(),
+            backends: upstream.backend_health(),
+        })
+        .collect();
+    upstreams.sort_by(|a, b| a.key.cmp(&b.key));
+    json_response(
+        StatusCode::OK,
+        &UpstreamsResponse {
+            revision: runtime.revision,
+            upstreams,
+            connections: conn_stats::snapshot(),
+        },
+    )
+}
+
+/// The in-memory resource view, sorted by id so it can be diffed against the store.
+fn handle_state_endpoint() -> Response<Vec<u8>> {
+    json_response(StatusCode::OK, &state_view(&RUNTIME.load()))
+}
+
+fn state_view(runtime: &RuntimeSnapshot) -> StateResponse {
+    let mut routes: Vec<RouteState> = runtime
+        .routes
+        .values()
+        .map(|route| RouteState {
+            id: route.inner.id.clone(),
+            uris: route
+                .inner
+                .get_uris()
+                .into_iter()
+                .map(String::from)
+                .collect(),
+            hosts: route.effective_hosts().to_vec(),
+            upstream_id: route.inner.upstream_id.clone(),
+            service_id: route.inner.service_id.clone(),
+        })
+        .collect();
+    routes.sort_by(|a, b| a.id.cmp(&b.id));
+
+    let mut services: Vec<ServiceState> = runtime
+        .services
+        .values()
+        .map(|service| ServiceState {
+            id: service.inner.id.clone(),
+            hosts: service.inner.hosts.clone(),
+            upstream_id: service.inner.upstream_id.clone(),
+        })
+        .collect();
+    services.sort_by(|a, b| a.id.cmp(&b.id));
+
+    let mut upstreams: Vec<UpstreamState> = runtime
+        .upstreams
+        .iter()
+        .map(|(id, upstream)| UpstreamState {
+            id: id.clone(),
+            backends: upstream.backend_addresses(),
+        })
+        .collect();
+    upstreams.sort_by(|a, b| a.id.cmp(&b.id));
+
+    let mut ssls: Vec<SslState> = runtime
+        .ssls
+        .iter()
+        .map(|(id, ssl)| SslState {
+            id: id.clone(),
+            snis: ssl.inner.snis.clone(),
+        })
+        .collect();
+    ssls.sort_by(|a, b| a.id.cmp(&b.id));
+
+    let sorted_ids = |ids: Vec<&String>| {
+        let mut ids: Vec<String> = ids.into_iter().cloned().collect();
+        ids.sort();
+        ids
+    };
+    StateResponse {
+        revision: runtime.revision,
+        routes,
+        services,
+        upstreams,
+        ssls,
+        global_rules: sorted_ids(runtime.global_rules.keys().collect()),
+        ip_lists: sorted_ids(runtime.ip_lists.keys().collect()),
+    }
+}
+
 fn json_response<T: Serialize>(status: StatusCode, body: &T) -> Response<Vec<u8>> {
     let json_body = serde_json::to_vec(body).unwrap_or_else(|e| {
         log::error!("Failed to serialize status response: {e}");
@@ -133,3 +340,65 @@ fn not_found_response() -> Response<Vec<u8>> {
     *resp.status_mut() = StatusCode::NOT_FOUND;
     resp
 }
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+    use crate::proxy::{
+        control_plane::{CandidateSnapshot, ResourceConfigSet},
+        runtime::RUNTIME_TEST_LOCK,
+    };
+
+    #[test]
+    fn state_view_lists_resources_sorted_by_id() {
+        // Building a candidate reuses upstreams of the published snapshot.
+        let _guard = RUNTIME_TEST_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+        let mut set = ResourceConfigSet::default();
+        set.upstreams.insert(
+            "u-state".into(),
+            serde_json::from_value(
+                json!({"id": "u-state", "nodes": {"127.0.0.2:80": 1, "127.0.0.1:80": 1}}),
+            )
+            .unwrap(),
+        );
+        set.services.insert(
+            "s1".into(),
+            serde_json::from_value(
+                json!({"id": "s1", "hosts": ["svc.example.com"], "upstream_id": "u-state"}),
+            )
+            .unwrap(),
+        );
+        for route in [
+            json!({"id": "r2", "uris": ["/b", "/c"], "host": "api.example.com", "upstream_id": "u-state"}),
+            json!({"id": "r1", "uri": "/a", "service_id": "s1"}),
+        ] {
+            let id = route["id"].as_str().unwrap().to_string();
+            set.routes
+                .insert(id, serde_json::from_value(route).unwrap());
+        }
+        set.global_rules.insert(
+            "g1".into(),
+            serde_json::from_value(json!({"id": "g1", "plugins": {"request-id": {}}})).unwrap(),
+        );
+        let runtime = RuntimeSnapshot::compile(CandidateSnapshot::build(set).unwrap(), 7).unwrap();
+
+        let body = serde_json::to_value(state_view(&runtime)).unwrap();
+        assert_eq!(
+            body,
+            json!({
+                "revision": 7,
+                "routes": [
+                    {"id": "r1", "uris": ["/a"], "hosts": ["svc.example.com"], "service_id": "s1"},
+                    {"id": "r2", "uris": ["/b", "/c"], "hosts": ["api.example.com"], "upstream_id": "u-state"},
+                ],
+                "services": [{"id": "s1", "hosts": ["svc.example.com"], "upstream_id": "u-state"}],
+                "upstreams": [{"id": "u-state", "backends": ["127.0.0.1:80", "127.0.0.2:80"]}],
+                "ssls": [],
+                "global_rules": ["g1"],
+                "ip_lists": [],
+            })
+        );
+    }
+}
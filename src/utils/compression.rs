@@ -0,0 +1,331 @@
+//! Per-route response compression shared by the `zstd`, `brotli` and `gzip` plugins.
+//!
+//! The plugins raise their algorithm's level on the downstream compression module in
+//! `early_request_filter` and register the codec. The service then [`negotiate`]s one
+//! codec for the request, and [`CompressionRules`] decide per response, once its headers
+//! are known, whether it stays enabled.
+
+use http::header::{ACCEPT_ENCODING, CONTENT_ENCODING, CONTENT_LENGTH, CONTENT_TYPE, VARY};
+use pingora::{
+    modules::http::compression::ResponseCompression, protocols::http::compression::Algorithm,
+};
+use pingora_error::Result;
+use pingora_http::{RequestHeader, ResponseHeader};
+use pingora_proxy::Session;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Map, Value as JsonValue};
+use validator::{Validate, ValidationError};
+
+use crate::core::ProxyContext;
+
+/// Context key listing the codecs enabled for the request.
+const CODECS_CTX_KEY: &str = "compression_codecs";
+
+/// Context key holding the client's `Accept-Encoding` while it is narrowed to the
+/// negotiated codec.
+const ACCEPT_ENCODING_CTX_KEY: &str = "compression_accept_encoding";
+
+/// Codec preference among those the client weighs equally.
+const CODEC_PREFERENCE: [Algorithm; 3] = [Algorithm::Zstd, Algorithm::Brotli, Algorithm::Gzip];
+
+/// Enable `algorithm` at `level` for this request.
+pub fn enable_codec(
+    session: &mut Session,
+    ctx: &mut ProxyContext,
+    algorithm: Algorithm,
+    level: u32,
+) {
+    let Some(compression) = session
+        .downstream_modules_ctx
+        .get_mut::<ResponseCompression>()
+    else {
+        return;
+    };
+    compression.adjust_algorithm_level(algorithm, level);
+    if level == 0 {
+        return;
+    }
+    match ctx.get_mut::<Vec<Algorithm>>(CODECS_CTX_KEY) {
+        Some(codecs) if !codecs.contains(&algorithm) => codecs.push(algorithm),
+        Some(_) => {}
+        None => ctx.set(CODECS_CTX_KEY, vec![algorithm]),
+    }
+}
+
+/// Pick the enabled codec the client prefers: highest `q` value first, then
+/// zstd, brotli, gzip. `None` when the client accepts none of them.
+fn preferred_codec(accept_encoding: &str, codecs: &[Algorithm]) -> Option<Algorithm> {
+    let mut weights: Vec<(&str, f32)> = Vec::new();
+    for item in accept_encoding.split(',') {
+        let mut parts = item.split(';');
+        let coding = parts.next().unwrap_or("").trim();
+        if coding.is_empty() {
+            continue;
+        }
+        let q = parts
+            .filter_map(|param| param.trim().strip_prefix("q="))
+            .find_map(|q| q.trim().parse::<f32>().ok())
+            .unwrap_or(1.0);
+        weights.push((coding, q));
+    }
+    let weight = |algorithm: Algorithm| {
+        let lookup = |name: &str| {
+            weights
+                .iter()
+                .find(|(coding, _)| coding.eq_ignore_ascii_case(name))
+                .map(|(_, q)| *q)
+        };
+        lookup(algorithm.as_str())
+            .or_else(|| lookup("*"))
+            .unwrap_or(0.0)
+    };
+
+    let mut best: Option<(Algorithm, f32)> = None;
+    for algorithm in CODEC_PREFERENCE {
+        if !codecs.contains(&algorithm) {
+            continue;
+        }
+        let q = weight(algorithm);
+        if q > 0.0 && best.is_none_or(|(_, best_q)| q > best_q) {
+            best = Some((algorithm, q));
+        }
+    }
+    best.map(|(algorithm, _)| algorithm)
+}
+
+/// Narrow the request's `Accept-Encoding` to the negotiated codec.
+///
+/// The compression module compresses with the first codec the client lists, whatever
+/// the route enabled; listing only the negotiated one makes it honor the preference.
+/// The client's header is restored on the upstream request by
+/// [`restore_accept_encoding`].
+pub fn negotiate(session: &mut Session, ctx: &mut ProxyContext) -> Result<()> {
+    let Some(codecs) = ctx.get::<Vec<Algorithm>>(CODECS_CTX_KEY) else {
+        return Ok(());
+    };
+    let Some(accept_encoding) = session
+        .req_header()
+        .headers
+        .get(ACCEPT_ENCODING)
+        .and_then(|v| v.to_str().ok())
+    else {
+        return Ok(());
+    };
+    let Some(codec) = preferred_codec(accept_encoding, codecs) else {
+        return Ok(());
+    };
+    if accept_encoding.trim().eq_ignore_ascii_case(codec.as_str()) {
+        return Ok(());
+    }
+    let original = accept_encoding.to_string();
+    session
+        .req_header_mut()
+        .insert_header(ACCEPT_ENCODING, codec.as_str())?;
+    ctx.set(ACCEPT_ENCODING_CTX_KEY, original);
+    Ok(())
+}
+
+/// Forward the client's own `Accept-Encoding` upstream.
+pub fn restore_accept_encoding(
+    upstream_request: &mut RequestHeader,
+    ctx: &ProxyContext,
+) -> Result<()> {
+    if let Some(original) = ctx.get::<String>(ACCEPT_ENCODING_CTX_KEY) {
+        upstream_request.insert_header(ACCEPT_ENCODING, original.as_str())?;
+    }
+    Ok(())
+}
+
+/// Which responses a compression plugin compresses.
+#[derive(Debug, Clone, Serialize, Deserialize, Validate)]
+pub struct CompressionRules {
+    /// Minimum `Content-Length` in bytes; smaller responses are sent as is. Responses
+    /// without a length (chunked) are always eligible.
+    #[serde(default)]
+    pub min_length: u64,
+
+    /// MIME types to compress, e.g. `text/html` or `text/*`. Empty compresses every type.
+    #[serde(default)]
+    #[validate(custom(function = "validate_types"))]
+    pub types: Vec<String>,
+
+    /// Add `Vary: Accept-Encoding` to eligible responses (default: true), so shared
+    /// caches keep compressed and identity variants apart.
+    #[serde(default = "CompressionRules::default_vary")]
+    pub vary: bool,
+}
+
+impl Default for CompressionRules {
+    fn default() -> Self {
+        Self {
+            min_length: 0,
+            types: Vec::new(),
+            vary: Self::default_vary(),
+        }
+    }
+}
+
+impl CompressionRules {
+    fn default_vary() -> bool {
+        true
+    }
+
+    /// JSON Schema properties of the rules, which the plugins flatten into their config.
+    pub fn schema_properties() -> Map<String, JsonValue> {
+        let mut properties = Map::new();
+        properties.insert(
+            "min_length".into(),
+            json!({"type": "integer", "minimum": 0, "default": 0}),
+        );
+        properties.insert(
+            "types".into(),
+            json!({"type": "array", "items": {"type": "string"}}),
+        );
+        properties.insert("vary".into(), json!({"type": "boolean", "default": true}));
+        properties
+    }
+
+    /// Whether a response with these headers may be compressed.
+    pub fn eligible(&self, resp: &ResponseHeader) -> bool {
+        if resp.headers.contains_key(CONTENT_ENCODING) {
+            return false;
+        }
+        let length = resp
+            .headers
+            .get(CONTENT_LENGTH)
+            .and_then(|v| v.to_str().ok())
+            .and_then(|v| v.parse::<u64>().ok());
+        if length.is_some_and(|length| length < self.min_length) {
+            return false;
+        }
+        if self.types.is_empty() {
+            return true;
+        }
+        let Some(mime) = resp
+            .headers
+            .get(CONTENT_TYPE)
+            .and_then(|v| v.to_str().ok())
+            .and_then(|v| v.split(';').next())
+            .map(str::trim)
+        else {
+            return false;
+        };
+        self.types.iter().any(|pattern| mime_matches(pattern, mime))
+    }
+
+    /// Disable `algorithm` for an ineligible response, or guarantee `Vary` on an
+    /// eligible one.
+    pub fn apply(
+        &self,
+        session: &mut Session,
+        resp: &mut ResponseHeader,
+        algorithm: Algorithm,
+    ) -> Result<()> {
+        if !self.eligible(resp) {
+            if let Some(compression) = session
+                .downstream_modules_ctx
+                .get_mut::<ResponseCompression>()
+            {
+                compression.adjust_algorithm_level(algorithm, 0);
+            }
+            return Ok(());
+        }
+        if self.vary && !varies_by_accept_encoding(resp) {
+            resp.append_header(VARY, "Accept-Encoding")?;
+        }
+        Ok(())
+    }
+}
+
+fn mime_matches(pattern: &str, mime: &str) -> bool {
+    match pattern.strip_suffix("/*") {
+        Some(kind) => mime
+            .split_once('/')
+            .is_some_and(|(mime_kind, _)| mime_kind.eq_ignore_ascii_case(kind)),
+        None => pattern.eq_ignore_ascii_case(mime),
+    }
+}
+
+fn varies_by_accept_encoding(resp: &ResponseHeader) -> bool {
+    resp.headers
+        .get_all(VARY)
+        .iter()
+        .filter_map(|v| v.to_str().ok())
+        .flat_map(|v| v.split(','))
+        .map(str::trim)
+        .any(|token| token == "*" || token.eq_ignore_ascii_case("accept-encoding"))
+}
+
+/// Each `types` entry must be `type/subtype` or `type/*`.
+fn validate_types(types: &[String]) -> Result<(), ValidationError> {
+    let valid = |t: &String| {
+        t.split_once('/')
+            .is_some_and(|(kind, sub)| !kind.is_empty() && !sub.is_empty() && kind != "*")
+    };
+    if types.iter().all(valid) {
+        Ok(())
+    } else {
+        Err(ValidationError::new("invalid_mime_type"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn response(headers: &[(&str, &str)]) -> ResponseHeader {
+        let mut resp = ResponseHeader::build(200, None).unwrap();
+        for (name, value) in headers {
+            resp.append_header(name.to_string(), *value).unwrap();
+        }
+        resp
+    }
+
+    #[test]
+    fn eligibility_follows_length_and_types() {
+        let rules = CompressionRules {
+            min_length: 100,
+            types: vec!["text/*".into(), "application/json".into()],
+            vary: true,
+        };
+        let html = response(&[("content-type", "text/html; charset=utf-8")]);
+        assert!(rules.eligible(&html));
+        let json = response(&[
+            ("content-type", "application/json"),
+            ("content-length", "500"),
+        ]);
+        assert!(rules.eligible(&json));
+        let small = response(&[("content-type", "text/css"), ("content-length", "50")]);
+        assert!(!rules.eligible(&small));
+        let image = response(&[("content-type", "image/png")]);
+        assert!(!rules.eligible(&image));
+        let encoded = response(&[("content-type", "text/html"), ("content-encoding", "gzip")]);
+        assert!(!rules.eligible(&encoded));
+        assert!(CompressionRules::default().eligible(&image));
+    }
+
+    #[test]
+    fn negotiation_prefers_client_weight_then_codec_order() {
+        use Algorithm::{Brotli, Gzip, Zstd};
+        let all = [Gzip, Brotli, Zstd];
+        assert_eq!(preferred_codec("gzip, br, zstd", &all), Some(Zstd));
+        assert_eq!(preferred_codec("gzip, br", &all), Some(Brotli));
+        assert_eq!(preferred_codec("gzip, br;q=0.5", &all), Some(Gzip));
+        assert_eq!(preferred_codec("zstd;q=0, *", &all), Some(Brotli));
+        assert_eq!(preferred_codec("gzip, br", &[Gzip]), Some(Gzip));
+        assert_eq!(preferred_codec("identity", &all), None);
+    }
+
+    #[test]
+    fn vary_detection_and_type_validation() {
+        assert!(varies_by_accept_encoding(&response(&[(
+            "vary",
+            "Origin, accept-encoding"
+        )])));
+        assert!(!varies_by_accept_encoding(&response(&[("vary", "Origin")])));
+        let types = |list: &[&str]| list.iter().map(|t| t.to_string()).collect::<Vec<_>>();
+        assert!(validate_types(&types(&["text/*", "application/json"])).is_ok());
+        assert!(validate_types(&types(&["*/*"])).is_err());
+        assert!(validate_types(&types(&["html"])).is_err());
+    }
+}
@@ -0,0 +1,621 @@
+//! Browser gRPC protocols bridged to gRPC upstreams.
+//!
+//! When the `grpc-web` plugin [`init`](GrpcBridge::init)s it for a request, [`GrpcBridge`]
+//! translates gRPC-Web (binary and base64 `-text`) and Connect streaming requests into
+//! gRPC, and the responses back. All three protocols frame messages in gRPC's 5-byte
+//! length-prefixed envelope, so messages pass through as they arrive and server streams
+//! stay streams; only the trailers move into a final body frame.
+//!
+//! Connect unary calls are rejected: their response status and trailers belong in the
+//! response header, which would mean holding it back until the upstream's trailers.
+
+use std::any::Any;
+
+use async_trait::async_trait;
+use base64::{engine::general_purpose::STANDARD, Engine as _};
+use bytes::{BufMut, Bytes, BytesMut};
+use http::{
+    header::{CONTENT_LENGTH, CONTENT_TYPE, TRANSFER_ENCODING},
+    HeaderMap, StatusCode,
+};
+use pingora::modules::http::{HttpModule, HttpModuleBuilder, Module};
+use pingora_error::{Error, ErrorType::HTTPStatus, OrErr, Result};
+use pingora_http::{RequestHeader, ResponseHeader};
+use serde_json::{json, Map, Value as JsonValue};
+
+use super::response::grpc_status;
+
+const GRPC: &str = "application/grpc";
+const GRPC_WEB: &str = "application/grpc-web";
+const GRPC_WEB_TEXT: &str = "application/grpc-web-text";
+const CONNECT: &str = "application/connect";
+
+/// Envelope flag of the gRPC-Web trailers frame.
+const GRPC_WEB_TRAILERS: u8 = 0x80;
+/// Envelope flag of the Connect end-of-stream message.
+const CONNECT_END_STREAM: u8 = 0x02;
+
+/// Trailers that Connect carries in the end-of-stream error rather than as metadata.
+const STATUS_TRAILERS: [&str; 3] = ["grpc-status", "grpc-message", "grpc-status-details-bin"];
+
+/// Connect names of the gRPC status codes, indexed by code.
+const CONNECT_CODES: [&str; 17] = [
+    "ok",
+    "canceled",
+    "unknown",
+    "invalid_argument",
+    "deadline_exceeded",
+    "not_found",
+    "already_exists",
+    "permission_denied",
+    "resource_exhausted",
+    "failed_precondition",
+    "aborted",
+    "out_of_range",
+    "unimplemented",
+    "internal",
+    "unavailable",
+    "data_loss",
+    "unauthenticated",
+];
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Protocol {
+    GrpcWeb,
+    GrpcWebText,
+    Connect,
+}
+
+impl Protocol {
+    /// The protocol of a request content type, with the codec suffix (e.g. `+proto`).
+    fn detect(content_type: &str) -> Option<(Self, &str)> {
+        let lower = content_type.to_ascii_lowercase();
+        let (protocol, prefix) = if lower.starts_with(GRPC_WEB_TEXT) {
+            (Self::GrpcWebText, GRPC_WEB_TEXT)
+        } else if lower.starts_with(GRPC_WEB) {
+            (Self::GrpcWeb, GRPC_WEB)
+        } else if lower.starts_with(CONNECT) && lower[CONNECT.len()..].starts_with('+') {
+            (Self::Connect, CONNECT)
+        } else {
+            return None;
+        };
+        Some((protocol, &content_type[prefix.len()..]))
+    }
+
+    fn content_type(self, codec: &str) -> String {
+        match self {
+            Self::GrpcWeb => format!("{GRPC_WEB}{codec}"),
+            Self::GrpcWebText => format!("{GRPC_WEB_TEXT}{codec}"),
+            // Connect requires the codec; plain gRPC implies protobuf.
+            Self::Connect if codec.is_empty() => format!("{CONNECT}+proto"),
+            Self::Connect => format!("{CONNECT}{codec}"),
+        }
+    }
+}
+
+#[derive(Debug, Default, PartialEq)]
+enum Stage {
+    /// Not enabled for this request, or not a bridged request.
+    #[default]
+    Off,
+    /// Enabled by the plugin, waiting for the request header.
+    Init,
+    /// Request translated, waiting for the response header.
+    Upgraded,
+    /// Translating the response body.
+    Body,
+    Done,
+}
+
+/// Per-request bridge state, registered as a downstream module by [`GrpcBridgeBuilder`].
+#[derive(Default)]
+pub struct GrpcBridge {
+    stage: Stage,
+    protocol: Option<Protocol>,
+    /// Request base64 not yet decoded, less than one quantum.
+    request_text: BytesMut,
+    /// Response bytes not yet base64 encoded, less than three.
+    response_text: BytesMut,
+}
+
+impl GrpcBridge {
+    /// Bridge this request if it turns out to use one of the browser protocols.
+    pub fn init(&mut self) {
+        self.stage = Stage::Init;
+    }
+
+    fn text(&self) -> bool {
+        self.protocol == Some(Protocol::GrpcWebText)
+    }
+
+    fn upgrade_request(&mut self, req: &mut RequestHeader) -> Result<()> {
+        let content_type = req
+            .headers
+            .get(CONTENT_TYPE)
+            .and_then(|value| value.to_str().ok())
+            .unwrap_or_default();
+        let Some((protocol, codec)) = Protocol::detect(content_type) else {
+            self.stage = Stage::Off;
+            if is_connect_unary(req) {
+                return Error::e_explain(
+                    HTTPStatus(415),
+                    "Connect unary calls are not supported, use the gRPC-Web transport",
+                );
+            }
+            return Ok(());
+        };
+        req.insert_header(
+            CONTENT_TYPE,
+            format!("{GRPC}{}", codec.to_ascii_lowercase()),
+        )?;
+        // Required by gRPC over HTTP/2 to detect proxies that cannot forward trailers.
+        req.insert_header("te", "trailers")?;
+        // The request stream must end with a DATA frame, even an empty one.
+        req.set_send_end_stream(false);
+        match protocol {
+            Protocol::GrpcWebText => {
+                req.remove_header(&CONTENT_LENGTH);
+            }
+            Protocol::Connect => translate_connect_request(req)?,
+            Protocol::GrpcWeb => {}
+        }
+        self.protocol = Some(protocol);
+        self.stage = Stage::Upgraded;
+        Ok(())
+    }
+
+    fn downgrade_response(&mut self, resp: &mut ResponseHeader, end_of_stream: bool) -> Result<()> {
+        let Some(protocol) = self.protocol else {
+            return Ok(());
+        };
+        let content_type = resp
+            .headers
+            .get(CONTENT_TYPE)
+            .and_then(|value| value.to_str().ok())
+            .unwrap_or_default();
+        let Some(codec) = content_type.strip_prefix(GRPC) else {
+            // Not gRPC, e.g. an error page of the gateway: pass it through untouched.
+            self.stage = Stage::Off;
+            return Ok(());
+        };
+        resp.insert_header(CONTENT_TYPE, protocol.content_type(codec))?;
+        resp.remove_header(&CONTENT_LENGTH);
+        resp.insert_header(TRANSFER_ENCODING, "chunked")?;
+
+        if protocol == Protocol::Connect {
+            for (grpc, connect) in [
+                ("grpc-encoding", "connect-content-encoding"),
+                ("grpc-accept-encoding", "connect-accept-encoding"),
+            ] {
+                if let Some(value) = resp.remove_header(grpc) {
+                    resp.insert_header(connect, value)?;
+                }
+            }
+            // A trailers-only response leaves no body to carry the end-of-stream message,
+            // so a failure is reported the other way Connect allows: by HTTP status.
+            if end_of_stream {
+                if let Some(status) = grpc_status(&resp.headers).filter(|status| *status != 0) {
+                    resp.set_status(connect_http_status(status))?;
+                }
+            }
+        }
+        self.stage = if end_of_stream {
+            Stage::Done
+        } else {
+            Stage::Body
+        };
+        Ok(())
+    }
+
+    /// The final body frame carrying `trailers`.
+    fn trailers_frame(&mut self, trailers: &HeaderMap) -> Bytes {
+        let frame = match self.protocol {
+            Some(Protocol::Connect) => connect_end_stream(trailers),
+            _ => grpc_web_trailers(trailers),
+        };
+        if !self.text() {
+            return frame;
+        }
+        self.response_text.extend_from_slice(&frame);
+        encode_text(&mut self.response_text, true)
+    }
+}
+
+#[async_trait]
+impl HttpModule for GrpcBridge {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+
+    async fn request_header_filter(&mut self, req: &mut RequestHeader) -> Result<()> {
+        if self.stage != Stage::Init {
+            return Ok(());
+        }
+        self.upgrade_request(req)
+    }
+
+    async fn request_body_filter(
+        &mut self,
+        body: &mut Option<Bytes>,
+        end_of_stream: bool,
+    ) -> Result<()> {
+        // The request may still stream after the response has started.
+        if !self.text() {
+            return Ok(());
+        }
+        if let Some(chunk) = body.take() {
+            self.request_text.extend_from_slice(&chunk);
+            *body = Some(decode_text(&mut self.request_text)?);
+        }
+        if end_of_stream && !self.request_text.is_empty() {
+            return Error::e_explain(HTTPStatus(400), "truncated gRPC-Web text request");
+        }
+        Ok(())
+    }
+
+    async fn response_header_filter(
+        &mut self,
+        resp: &mut ResponseHeader,
+        end_of_stream: bool,
+    ) -> Result<()> {
+        if self.stage != Stage::Upgraded || resp.status.is_informational() {
+            return Ok(());
+        }
+        self.downgrade_response(resp, end_of_stream)
+    }
+
+    fn response_body_filter(
+        &mut self,
+        body: &mut Option<Bytes>,
+        end_of_stream: bool,
+    ) -> Result<()> {
+        if self.stage != Stage::Body || !self.text() {
+            return Ok(());
+        }
+        if let Some(chunk) = body.take() {
+            self.response_text.extend_from_slice(&chunk);
+        }
+        let encoded = encode_text(&mut self.response_text, end_of_stream);
+        *body = (!encoded.is_empty()).then_some(encoded);
+        if end_of_stream {
+            self.stage = Stage::Done;
+        }
+        Ok(())
+    }
+
+    fn response_trailer_filter(
+        &mut self,
+        trailers: &mut Option<Box<HeaderMap>>,
+    ) -> Result<Option<Bytes>> {
+        if self.stage != Stage::Body {
+            return Ok(None);
+        }
+        let Some(trailers) = trailers else {
+            return Ok(None);
+        };
+        self.stage = Stage::Done;
+        Ok(Some(self.trailers_frame(trailers)))
+    }
+
+    fn response_done_filter(&mut self) -> Result<Option<Bytes>> {
+        if self.stage != Stage::Body {
+            return Ok(None);
+        }
+        self.stage = Stage::Done;
+        let rest = encode_text(&mut self.response_text, true);
+        Ok((!rest.is_empty()).then_some(rest))
+    }
+}
+
+/// Builds the disabled [`GrpcBridge`] of every request.
+pub struct GrpcBridgeBuilder;
+
+impl HttpModuleBuilder for GrpcBridgeBuilder {
+    fn init(&self) -> Module {
+        Box::new(GrpcBridge::default())
+    }
+}
+
+/// Whether `req` is a Connect unary call: a POST announcing `connect-protocol-version`
+/// with an unenveloped body, or a GET with `connect=v1` in the query.
+fn is_connect_unary(req: &RequestHeader) -> bool {
+    req.headers.contains_key("connect-protocol-version")
+        || req
+            .uri
+            .query()
+            .is_some_and(|query| query.split('&').any(|pair| pair == "connect=v1"))
+}
+
+/// Map the Connect request headers onto their gRPC counterparts.
+fn translate_connect_request(req: &mut RequestHeader) -> Result<()> {
+    if let Some(timeout) = req.remove_header("connect-timeout-ms") {
+        let millis: u64 = timeout
+            .to_str()
+            .ok()
+            .and_then(|value| value.parse().ok())
+            .ok_or_else(|| Error::explain(HTTPStatus(400), "invalid connect-timeout-ms"))?;
+        // grpc-timeout allows at most 8 digits.
+        let timeout = if millis < 100_000_000 {
+            format!("{millis}m")
+        } else {
+            format!("{}S", millis / 1000)
+        };
+        req.insert_header("grpc-timeout", timeout)?;
+    }
+    for (connect, grpc) in [
+        ("connect-content-encoding", "grpc-encoding"),
+        ("connect-accept-encoding", "grpc-accept-encoding"),
+    ] {
+        if let Some(value) = req.remove_header(connect) {
+            req.insert_header(grpc, value)?;
+        }
+    }
+    Ok(())
+}
+
+/// HTTP status Connect uses for a gRPC status code.
+fn connect_http_status(code: u32) -> StatusCode {
+    let status = match code {
+        1 => 499,
+        3 | 9 | 11 => 400,
+        4 => 504,
+        5 => 404,
+        6 | 10 => 409,
+        7 => 403,
+        8 => 429,
+        12 => 501,
+        14 => 503,
+        16 => 401,
+        _ => 500,
+    };
+    StatusCode::from_u16(status).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR)
+}
+
+fn envelope(flags: u8, payload: &[u8]) -> Bytes {
+    let mut frame = BytesMut::with_capacity(5 + payload.len());
+    frame.put_u8(flags);
+    frame.put_u32(payload.len() as u32);
+    frame.put_slice(payload);
+    frame.freeze()
+}
+
+/// gRPC-Web trailers frame: the trailers as HTTP/1 header lines.
+fn grpc_web_trailers(trailers: &HeaderMap) -> Bytes {
+    let mut lines = Vec::new();
+    for (name, value) in trailers {
+        lines.extend_from_slice(name.as_str().as_bytes());
+        lines.push(b':');
+        lines.extend_from_slice(value.as_bytes());
+        lines.extend_from_slice(b"\r\n");
+    }
+    envelope(GRPC_WEB_TRAILERS, &lines)
+}
+
+/// Connect end-of-stream message: the status as `error` and other trailers as
+/// `metadata`. Trailers without `grpc-status` end the call as `unknown`.
+fn connect_end_stream(trailers: &HeaderMap) -> Bytes {
+    let mut end = Map::new();
+    let code = grpc_status(trailers).unwrap_or(2);
+    if code != 0 {
+        let mut error = Map::new();
+        let name = CONNECT_CODES
+            .get(code as usize)
+            .copied()
+            .unwrap_or("unknown");
+        error.insert("code".to_string(), json!(name));
+        if let Some(message) = trailers
+            .get("grpc-message")
+            .map(|value| percent_decode(value.as_bytes()))
+        {
+            error.insert("message".to_string(), json!(message));
+        }
+        end.insert("error".to_string(), JsonValue::Object(error));
+    }
+    let mut metadata = Map::new();
+    for (name, value) in trailers {
+        if STATUS_TRAILERS.contains(&name.as_str()) {
+            continue;
+        }
+        let Ok(value) = value.to_str() else {
+            continue;
+        };
+        if let JsonValue::Array(values) = metadata
+            .entry(name.as_str())
+            .or_insert_with(|| JsonValue::Array(Vec::new()))
+        {
+            values.push(json!(value));
+        }
+    }
+    if !metadata.is_empty() {
+        end.insert("metadata".to_string(), JsonValue::Object(metadata));
+    }
+    envelope(
+        CONNECT_END_STREAM,
+        JsonValue::Object(end).to_string().as_bytes(),
+    )
+}
+
+/// Decode `grpc-message`, which is percent-encoded.
+fn percent_decode(value: &[u8]) -> String {
+    let mut decoded = Vec::with_capacity(value.len());
+    let mut i = 0;
+    while i < value.len() {
+        let hex = value
+            .get(i + 1..i + 3)
+            .and_then(|hex| std::str::from_utf8(hex).ok())
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+        match (value[i], hex) {
+            (b'%', Some(byte)) => {
+                decoded.push(byte);
+                i += 3;
+            }
+            (byte, _) => {
+                decoded.push(byte);
+                i += 1;
+            }
+        }
+    }
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+/// Decode the complete base64 quanta of `pending`, leaving a partial one for the next
+/// chunk. Clients may encode each message on its own, so padding can end a quantum in
+/// the middle of the body.
+fn decode_text(pending: &mut BytesMut) -> Result<Bytes> {
+    let quanta = pending.split_to(pending.len() / 4 * 4);
+    let mut decoded = Vec::with_capacity(quanta.len() / 4 * 3);
+    let mut start = 0;
+    for end in (4..=quanta.len()).step_by(4) {
+        if quanta[end - 1] == b'=' || end == quanta.len() {
+            STANDARD
+                .decode_vec(&quanta[start..end], &mut decoded)
+                .or_err(HTTPStatus(400), "invalid base64 in gRPC-Web text request")?;
+            start = end;
+        }
+    }
+    Ok(decoded.into())
+}
+
+/// Base64 encode `pending`, keeping back bytes that do not fill a quantum unless
+/// `flush`ing.
+fn encode_text(pending: &mut BytesMut, flush: bool) -> Bytes {
+    let len = if flush {
+        pending.len()
+    } else {
+        pending.len() / 3 * 3
+    };
+    let bytes = pending.split_to(len);
+    STANDARD.encode(bytes).into()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn bridge_request(content_type: &str) -> (GrpcBridge, RequestHeader) {
+        let mut req = RequestHeader::build("POST", b"/pkg.Svc/Watch", None).unwrap();
+        req.insert_header(CONTENT_TYPE, content_type).unwrap();
+        req.insert_header(CONTENT_LENGTH, "8").unwrap();
+        req.insert_header("connect-timeout-ms", "1500").unwrap();
+        let mut bridge = GrpcBridge::default();
+        bridge.init();
+        bridge.upgrade_request(&mut req).unwrap();
+        (bridge, req)
+    }
+
+    fn grpc_response(end_of_stream: bool, bridge: &mut GrpcBridge) -> ResponseHeader {
+        let mut resp = ResponseHeader::build(200, None).unwrap();
+        resp.insert_header(CONTENT_TYPE, "application/grpc+proto")
+            .unwrap();
+        if end_of_stream {
+            resp.insert_header("grpc-status", "5").unwrap();
+        }
+        bridge.downgrade_response(&mut resp, end_of_stream).unwrap();
+        resp
+    }
+
+    #[test]
+    fn requests_are_upgraded_by_content_type() {
+        let (bridge, req) = bridge_request("application/grpc-web-text+proto");
+        assert_eq!(bridge.protocol, Some(Protocol::GrpcWebText));
+        assert_eq!(req.headers[CONTENT_TYPE], "application/grpc+proto");
+        assert_eq!(req.headers["te"], "trailers");
+        assert!(req.headers.get(CONTENT_LENGTH).is_none());
+
+        let (bridge, req) = bridge_request("application/connect+json");
+        assert_eq!(bridge.protocol, Some(Protocol::Connect));
+        assert_eq!(req.headers[CONTENT_TYPE], "application/grpc+json");
+        assert_eq!(req.headers["grpc-timeout"], "1500m");
+
+        let (bridge, req) = bridge_request("application/json");
+        assert_eq!(bridge.stage, Stage::Off);
+        assert_eq!(req.headers[CONTENT_TYPE], "application/json");
+    }
+
+    #[test]
+    fn connect_unary_calls_are_rejected() {
+        let mut req = RequestHeader::build("POST", b"/pkg.Svc/Get", None).unwrap();
+        req.insert_header(CONTENT_TYPE, "application/proto")
+            .unwrap();
+        req.insert_header("connect-protocol-version", "1").unwrap();
+        let mut bridge = GrpcBridge::default();
+        bridge.init();
+        let err = bridge.upgrade_request(&mut req).unwrap_err();
+        assert_eq!(err.etype(), &HTTPStatus(415));
+
+        let mut req =
+            RequestHeader::build("GET", b"/pkg.Svc/Get?connect=v1&encoding=json", None).unwrap();
+        let mut bridge = GrpcBridge::default();
+        bridge.init();
+        assert!(bridge.upgrade_request(&mut req).is_err());
+    }
+
+    #[tokio::test]
+    async fn text_bodies_are_translated_across_chunks() {
+        let (mut bridge, _) = bridge_request("application/grpc-web-text");
+        // Two messages encoded separately, split mid-quantum.
+        let encoded = format!(
+            "{}{}",
+            STANDARD.encode(b"\0\0\0\0\x01a"),
+            STANDARD.encode(b"b")
+        );
+        let mut decoded = Vec::new();
+        for chunk in [&encoded[..5], &encoded[5..]] {
+            let mut body = Some(Bytes::copy_from_slice(chunk.as_bytes()));
+            bridge.request_body_filter(&mut body, false).await.unwrap();
+            decoded.extend_from_slice(&body.unwrap());
+        }
+        assert_eq!(decoded, b"\0\0\0\0\x01ab");
+
+        let resp = grpc_response(false, &mut bridge);
+        assert_eq!(
+            resp.headers[CONTENT_TYPE],
+            "application/grpc-web-text+proto"
+        );
+        let mut body = Some(Bytes::from_static(b"\0\0\0\0\x02hi"));
+        bridge.response_body_filter(&mut body, false).unwrap();
+        let mut trailers = HeaderMap::new();
+        trailers.insert("grpc-status", "0".parse().unwrap());
+        let rest = bridge
+            .response_trailer_filter(&mut Some(Box::new(trailers)))
+            .unwrap()
+            .unwrap();
+        let response = STANDARD.decode([body.unwrap(), rest].concat()).unwrap();
+        assert_eq!(&response[..7], b"\0\0\0\0\x02hi");
+        assert_eq!(&response[7..], b"\x80\0\0\0\x0fgrpc-status:0\r\n");
+    }
+
+    #[test]
+    fn connect_streams_end_with_a_status_message() {
+        let (mut bridge, _) = bridge_request("application/connect+proto");
+        let resp = grpc_response(false, &mut bridge);
+        assert_eq!(resp.headers[CONTENT_TYPE], "application/connect+proto");
+
+        let mut trailers = HeaderMap::new();
+        trailers.insert("grpc-status", "5".parse().unwrap());
+        trailers.insert("grpc-message", "no%20such%20key".parse().unwrap());
+        trailers.insert("x-shard", "7".parse().unwrap());
+        let frame = bridge
+            .response_trailer_filter(&mut Some(Box::new(trailers)))
+            .unwrap()
+            .unwrap();
+        assert_eq!(frame[0], CONNECT_END_STREAM);
+        let end: JsonValue = serde_json::from_slice(&frame[5..]).unwrap();
+        assert_eq!(
+            end,
+            json!({
+                "error": {"code": "not_found", "message": "no such key"},
+                "metadata": {"x-shard": ["7"]}
+            })
+        );
+
+        // Trailers-only failures map onto the HTTP status.
+        let (mut bridge, _) = bridge_request("application/connect+proto");
+        let resp = grpc_response(true, &mut bridge);
+        assert_eq!(resp.status, StatusCode::NOT_FOUND);
+    }
+}
@@ -0,0 +1,123 @@
+//! MD5-crypt (`$1$`) and Apache's variant (`$apr1$`) password verification.
+
+use md5::{Digest, Md5};
+use subtle::ConstantTimeEq;
+
+/// crypt(3) base64 alphabet.
+const ITOA64: &[u8; 64] = b"./0123456789ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz";
+const MAX_SALT_LEN: usize = 8;
+
+/// A parsed `$1$salt$hash` or `$apr1$salt$hash` string.
+#[derive(Debug, Clone)]
+pub struct Md5CryptHash {
+    magic: &'static str,
+    salt: String,
+    hash: String,
+}
+
+impl Md5CryptHash {
+    /// Parses an MD5-crypt string; `None` when it is not well-formed.
+    pub fn parse(s: &str) -> Option<Self> {
+        let (magic, rest) = ["$apr1$", "$1$"]
+            .into_iter()
+            .find_map(|magic| s.strip_prefix(magic).map(|rest| (magic, rest)))?;
+        let (salt, hash) = rest.split_once('$')?;
+        if salt.len() > MAX_SALT_LEN || hash.len() != 22 || salt.contains('$') {
+            return None;
+        }
+        Some(Self {
+            magic,
+            salt: salt.to_string(),
+            hash: hash.to_string(),
+        })
+    }
+
+    /// Checks `password` against the hash in constant time.
+    pub fn verify(&self, password: &[u8]) -> bool {
+        let computed = md5_crypt(password, self.magic, self.salt.as_bytes());
+        computed.as_bytes().ct_eq(self.hash.as_bytes()).into()
+    }
+}
+
+/// Returns the 22-character hash part of the MD5-crypt string.
+fn md5_crypt(password: &[u8], magic: &str, salt: &[u8]) -> String {
+    let alternate = md5(&[password, salt, password].concat());
+
+    let mut buf = [password, magic.as_bytes(), salt].concat();
+    let mut remaining = password.len();
+    while remaining > 0 {
+        let n = remaining.min(16);
+        buf.extend_from_slice(&alternate[..n]);
+        remaining -= n;
+    }
+    let mut i = password.len();
+    while i > 0 {
+        buf.push(if i & 1 == 1 {
+            0
+        } else {
+            password.first().copied().unwrap_or(0)
+        });
+        i >>= 1;
+    }
+    let mut digest = md5(&buf);
+
+    for round in 0..1000 {
+        buf.clear();
+        if round & 1 == 1 {
+            buf.extend_from_slice(password);
+        } else {
+            buf.extend_from_slice(&digest);
+        }
+        if round % 3 != 0 {
+            buf.extend_from_slice(salt);
+        }
+        if round % 7 != 0 {
+            buf.extend_from_slice(password);
+        }
+        if round & 1 == 1 {
+            buf.extend_from_slice(&digest);
+        } else {
+            buf.extend_from_slice(password);
+        }
+        digest = md5(&buf);
+    }
+
+    let d = digest.map(u32::from);
+    let mut out = String::with_capacity(22);
+    for (value, chars) in [
+        ((d[0] << 16) | (d[6] << 8) | d[12], 4),
+        ((d[1] << 16) | (d[7] << 8) | d[13], 4),
+        ((d[2] << 16) | (d[8] << 8) | d[14], 4),
+        ((d[3] << 16) | (d[9] << 8) | d[15], 4),
+        ((d[4] << 16) | (d[10] << 8) | d[5], 4),
+        (d[11], 2),
+    ] {
+        let mut value = value;
+        for _ in 0..chars {
+            out.push(ITOA64[(value & 0x3f) as usize] as char);
+            value >>= 6;
+        }
+    }
+    out
+}
+
+fn md5(data: &[u8]) -> [u8; 16] {
+    Md5::digest(data).into()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn verifies_reference_hashes() {
+        for hash in [
+            "$1$saltsalt$qjXMvbEw8oaL.CzflDtaK/",
+            "$apr1$saltsalt$yAAkm4libquA.ZWLHbSBq/",
+        ] {
+            let parsed = Md5CryptHash::parse(hash).unwrap();
+            assert!(parsed.verify(b"password"), "{hash}");
+            assert!(!parsed.verify(b"Password"), "{hash}");
+        }
+    }
+}
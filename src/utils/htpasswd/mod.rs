@@ -0,0 +1,375 @@
+//! htpasswd files with bcrypt and MD5-crypt entries.
+//!
+//! Verifying a password is deliberately slow, so [`Htpasswd::verify`] must run on a
+//! blocking thread, and bcrypt entries above [`MAX_BCRYPT_COST`] are rejected at load.
+//! [`Htpasswd::verify_blocking`] does so for at most [`MAX_CONCURRENT_VERIFICATIONS`]
+//! passwords at a time, so a burst of logins cannot take over the blocking pool.
+//! Files are re-read without blocking when their modification time or size changes,
+//! checked at most once per [`RELOAD_CHECK_INTERVAL`]. A file that no longer parses
+//! keeps the previous entries in service.
+
+mod md5_crypt;
+
+use std::{
+    collections::{HashMap, HashSet},
+    fs::{self, Metadata},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use arc_swap::ArcSwap;
+use once_cell::sync::Lazy;
+use tokio::sync::Semaphore;
+
+use crate::core::{secret_digest, ProxyError, ProxyResult};
+
+use self::md5_crypt::Md5CryptHash;
+
+/// Minimum time between two modification checks of an htpasswd file.
+pub const RELOAD_CHECK_INTERVAL: Duration = Duration::from_secs(5);
+/// Highest accepted bcrypt cost; each step doubles the work of one login attempt.
+pub const MAX_BCRYPT_COST: u32 = 12;
+/// Passwords hashed at the same time, across all htpasswd files.
+pub const MAX_CONCURRENT_VERIFICATIONS: usize = 16;
+/// Successful logins remembered so bcrypt runs once per credential, not per request.
+const VERIFIED_CACHE_SIZE: usize = 4096;
+/// Salt and checksum of the hash unknown users are checked against.
+const DUMMY_BCRYPT: &str = "abcdefghijklmnopqrstuuWG29KuyeAicPCJODk1zjyGvyQUU2awu";
+const DUMMY_MD5_CRYPT: &str = "$apr1$saltsalt$yAAkm4libquA.ZWLHbSBq/";
+
+static VERIFICATIONS: Lazy<Arc<Semaphore>> =
+    Lazy::new(|| Arc::new(Semaphore::new(MAX_CONCURRENT_VERIFICATIONS)));
+
+/// Every verification slot is taken; the login should be retried later.
+#[derive(Debug, PartialEq, Eq)]
+pub struct VerifierBusy;
+
+#[derive(Debug, Clone)]
+enum PasswordHash {
+    Bcrypt { hash: String, cost: u32 },
+    Md5Crypt(Md5CryptHash),
+}
+
+impl PasswordHash {
+    fn parse(hash: &str) -> Result<Self, String> {
+        if hash.starts_with("$2") {
+            let cost = hash
+                .parse::<bcrypt::HashParts>()
+                .map_err(|e| format!("invalid bcrypt hash: {e}"))?
+                .get_cost();
+            if cost > MAX_BCRYPT_COST {
+                return Err(format!(
+                    "bcrypt cost {cost} exceeds the maximum of {MAX_BCRYPT_COST}"
+                ));
+            }
+            Ok(Self::Bcrypt {
+                hash: hash.to_string(),
+                cost,
+            })
+        } else {
+            Md5CryptHash::parse(hash)
+                .map(Self::Md5Crypt)
+                .ok_or_else(|| {
+                    "unsupported password hash (use bcrypt or MD5-crypt, e.g. `htpasswd -B`)".into()
+                })
+        }
+    }
+
+    /// A hash no password matches, as expensive to check as the costliest of `hashes`.
+    fn dummy<'a>(hashes: impl Iterator<Item = &'a PasswordHash>) -> Self {
+        let cost = hashes
+            .filter_map(|hash| match hash {
+                Self::Bcrypt { cost, .. } => Some(*cost),
+                Self::Md5Crypt(_) => None,
+            })
+            .max();
+        match cost {
+            Some(cost) => Self::Bcrypt {
+                hash: format!("$2y${cost:02}${DUMMY_BCRYPT}"),
+                cost,
+            },
+            None => Self::Md5Crypt(
+                Md5CryptHash::parse(DUMMY_MD5_CRYPT).expect("dummy MD5-crypt hash is valid"),
+            ),
+        }
+    }
+
+    fn verify(&self, password: &str) -> bool {
+        match self {
+            Self::Bcrypt { hash, .. } => bcrypt::verify(password, hash).unwrap_or(false),
+            Self::Md5Crypt(hash) => hash.verify(password.as_bytes()),
+        }
+    }
+}
+
+/// Parsed `user:hash` entries.
+#[derive(Debug)]
+pub struct Htpasswd {
+    users: HashMap<String, PasswordHash>,
+    /// Checked for unknown users, so the response time does not reveal who exists.
+    dummy: PasswordHash,
+    /// Digests of `user:password` pairs that already verified against these entries.
+    verified: Mutex<HashSet<[u8; 32]>>,
+}
+
+impl Htpasswd {
+    /// Parses htpasswd content. Blank lines and `#` comments are skipped; any other
+    /// line must be `user:hash` with a bcrypt (cost at most [`MAX_BCRYPT_COST`]) or
+    /// MD5-crypt hash.
+    pub fn parse(content: &str, source: &str) -> ProxyResult<Self> {
+        let mut users = HashMap::new();
+        for (lineno, line) in content.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let (user, hash) = line.split_once(':').ok_or_else(|| {
+                ProxyError::Configuration(format!("{source}:{}: expected `user:hash`", lineno + 1))
+            })?;
+            let hash = PasswordHash::parse(hash).map_err(|e| {
+                ProxyError::Configuration(format!("{source}:{}: '{user}': {e}", lineno + 1))
+            })?;
+            users.insert(user.to_string(), hash);
+        }
+        Ok(Self {
+            dummy: PasswordHash::dummy(users.values()),
+            users,
+            verified: Mutex::default(),
+        })
+    }
+
+    /// Whether `user` exists and `password` matches its hash. This hashes the password
+    /// unless the pair verified before, so call it from a blocking thread.
+    pub fn verify(&self, user: &str, password: &str) -> bool {
+        let key = secret_digest(&format!("{user}:{password}"));
+        if self.lock_verified().contains(&key) {
+            return true;
+        }
+        let (hash, known) = match self.users.get(user) {
+            Some(hash) => (hash, true),
+            None => (&self.dummy, false),
+        };
+        if !(hash.verify(password) && known) {
+            return false;
+        }
+        let mut verified = self.lock_verified();
+        if verified.len() >= VERIFIED_CACHE_SIZE {
+            verified.clear();
+        }
+        verified.insert(key);
+        true
+    }
+
+    /// [`Htpasswd::verify`] on a blocking thread. Pairs that verified before are
+    /// accepted right away; others fail with [`VerifierBusy`] when
+    /// [`MAX_CONCURRENT_VERIFICATIONS`] passwords are already being hashed.
+    pub async fn verify_blocking(
+        self: Arc<Self>,
+        user: String,
+        password: String,
+    ) -> Result<bool, VerifierBusy> {
+        self.verify_with(&VERIFICATIONS, user, password).await
+    }
+
+    async fn verify_with(
+        self: Arc<Self>,
+        permits: &Arc<Semaphore>,
+        user: String,
+        password: String,
+    ) -> Result<bool, VerifierBusy> {
+        let key = secret_digest(&format!("{user}:{password}"));
+        if self.lock_verified().contains(&key) {
+            return Ok(true);
+        }
+        let permit = permits
+            .clone()
+            .try_acquire_owned()
+            .map_err(|_| VerifierBusy)?;
+        // The permit moves along, so an abandoned request still holds it until hashing ends.
+        Ok(tokio::task::spawn_blocking(move || {
+            let _permit = permit;
+            self.verify(&user, &password)
+        })
+        .await
+        .unwrap_or(false))
+    }
+
+    fn lock_verified(&self) -> std::sync::MutexGuard<'_, HashSet<[u8; 32]>> {
+        self.verified.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    pub fn len(&self) -> usize {
+        self.users.len()
+    }
+}
+
+/// Modification time and size identifying one version of a file.
+type FileStamp = (Option<SystemTime>, u64);
+
+/// An htpasswd file that picks up changes while the gateway runs.
+pub struct HtpasswdFile {
+    path: String,
+    entries: ArcSwap<Htpasswd>,
+    stamp: Mutex<FileStamp>,
+    /// Unix seconds of the last modification check.
+    checked_at: AtomicU64,
+}
+
+impl HtpasswdFile {
+    /// Loads `path`, failing if it cannot be read or parsed.
+    pub fn load(path: &str) -> ProxyResult<Self> {
+        let (entries, stamp) = read_file(path)?;
+        Ok(Self {
+            path: path.to_string(),
+            entries: ArcSwap::from_pointee(entries),
+            stamp: Mutex::new(stamp),
+            checked_at: AtomicU64::new(unix_now()),
+        })
+    }
+
+    /// Current entries, reloading the file first if it changed.
+    pub async fn entries(&self) -> Arc<Htpasswd> {
+        self.reload_if_changed().await;
+        self.entries.load_full()
+    }
+
+    async fn reload_if_changed(&self) {
+        let now = unix_now();
+        let checked = self.checked_at.load(Ordering::Relaxed);
+        if now.saturating_sub(checked) < RELOAD_CHECK_INTERVAL.as_secs()
+            || self
+                .checked_at
+                .compare_exchange(checked, now, Ordering::Relaxed, Ordering::Relaxed)
+                .is_err()
+        {
+            return;
+        }
+
+        let Ok(meta) = tokio::fs::metadata(&self.path).await else {
+            log::warn!(
+                "htpasswd file '{}' is unreadable, keeping current entries",
+                self.path
+            );
+            return;
+        };
+        let stamp = file_stamp(&meta);
+        if *self.lock_stamp() == stamp {
+            return;
+        }
+        let loaded = match tokio::fs::read_to_string(&self.path).await {
+            Ok(content) => Htpasswd::parse(&content, &self.path),
+            Err(e) => Err(read_error(&self.path, e)),
+        };
+        match loaded {
+            Ok(entries) => {
+                log::info!(
+                    "Reloaded htpasswd file '{}' ({} users)",
+                    self.path,
+                    entries.len()
+                );
+                self.entries.store(Arc::new(entries));
+            }
+            Err(e) => {
+                log::warn!("Failed to reload htpasswd file, keeping current entries: {e}");
+            }
+        }
+        *self.lock_stamp() = stamp;
+    }
+
+    fn lock_stamp(&self) -> std::sync::MutexGuard<'_, FileStamp> {
+        self.stamp.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+fn file_stamp(meta: &Metadata) -> FileStamp {
+    (meta.modified().ok(), meta.len())
+}
+
+fn read_error(path: &str, e: std::io::Error) -> ProxyError {
+    ProxyError::Configuration(format!("Failed to read htpasswd file '{path}': {e}"))
+}
+
+fn read_file(path: &str) -> ProxyResult<(Htpasswd, FileStamp)> {
+    let stamp = fs::metadata(path)
+        .map(|meta| file_stamp(&meta))
+        .map_err(|e| read_error(path, e))?;
+    let content = fs::read_to_string(path).map_err(|e| read_error(path, e))?;
+    Ok((Htpasswd::parse(&content, path)?, stamp))
+}
+
+fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_secs())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_entries_and_rejects_unsupported_hashes() {
+        let htpasswd = Htpasswd::parse(
+            "# users\n\
+             alice:$apr1$saltsalt$yAAkm4libquA.ZWLHbSBq/\n\
+             \n\
+             bob:$2y$05$abcdefghijklmnopqrstuuWG29KuyeAicPCJODk1zjyGvyQUU2awu\n",
+            "test",
+        )
+        .unwrap();
+        assert_eq!(htpasswd.len(), 2);
+        assert!(htpasswd.verify("alice", "password"));
+        assert!(htpasswd.verify("bob", "password"));
+        assert!(!htpasswd.verify("bob", "nope"));
+        assert!(!htpasswd.verify("carol", "password"));
+
+        let err = Htpasswd::parse("carol:{SHA}W6ph5Mm5Pz8GgiULbPgzG37mj9g=", "test").unwrap_err();
+        assert!(err.to_string().contains("test:1"), "{err}");
+    }
+
+    #[test]
+    fn rejects_expensive_bcrypt_and_checks_unknown_users_at_the_highest_cost() {
+        let err = Htpasswd::parse(&format!("dave:$2y$13${DUMMY_BCRYPT}"), "test").unwrap_err();
+        assert!(err.to_string().contains("cost 13"), "{err}");
+
+        let htpasswd = Htpasswd::parse(
+            "alice:$apr1$saltsalt$yAAkm4libquA.ZWLHbSBq/\n\
+             bob:$2y$05$abcdefghijklmnopqrstuuWG29KuyeAicPCJODk1zjyGvyQUU2awu\n",
+            "test",
+        )
+        .unwrap();
+        assert!(matches!(
+            htpasswd.dummy,
+            PasswordHash::Bcrypt { cost: 5, .. }
+        ));
+        // The dummy shares bob's salt and checksum, yet never authenticates anyone.
+        assert!(!htpasswd.verify("mallory", "password"));
+    }
+
+    #[tokio::test]
+    async fn verification_is_refused_while_all_slots_are_taken() {
+        let htpasswd = Arc::new(
+            Htpasswd::parse("alice:$apr1$saltsalt$yAAkm4libquA.ZWLHbSBq/", "test").unwrap(),
+        );
+        let permits = Arc::new(Semaphore::new(1));
+        let verify = |password: &str| {
+            htpasswd
+                .clone()
+                .verify_with(&permits, "alice".into(), password.into())
+        };
+
+        let taken = permits.clone().try_acquire_owned().unwrap();
+        assert_eq!(verify("password").await, Err(VerifierBusy));
+        drop(taken);
+        assert_eq!(verify("password").await, Ok(true));
+        assert_eq!(verify("nope").await, Ok(false));
+
+        // Logins that verified before skip hashing, so they need no slot.
+        let _taken = permits.clone().try_acquire_owned().unwrap();
+        assert_eq!(verify("password").await, Ok(true));
+        assert_eq!(verify("nope").await, Err(VerifierBusy));
+    }
+}
@@ -0,0 +1,201 @@
+//! Prefix-trie CIDR sets used for IP access control.
+//!
+//! Networks are stored in one binary trie per address family, so a lookup costs at
+//! most 32 (IPv4) or 128 (IPv6) steps however many networks the set holds. Networks
+//! covered by a shorter prefix are folded into it on insert. IPv4-mapped IPv6
+//! addresses (`::ffff:a.b.c.d`) are matched against the IPv4 networks.
+
+use std::net::IpAddr;
+
+use ipnetwork::IpNetwork;
+
+/// Sentinel child index for "no child"; the root occupies index 0 and is never a child.
+const NONE: u32 = 0;
+
+#[derive(Clone, Copy, Default)]
+struct Node {
+    children: [u32; 2],
+    /// A network ends here, so every address below is contained.
+    terminal: bool,
+}
+
+/// Binary trie over the bits of one address family.
+#[derive(Clone)]
+struct Trie {
+    nodes: Vec<Node>,
+}
+
+impl Default for Trie {
+    fn default() -> Self {
+        Self {
+            nodes: vec![Node::default()],
+        }
+    }
+}
+
+impl Trie {
+    /// Insert the first `len` bits of `bits` (most significant first), `width` bits wide.
+    fn insert(&mut self, bits: u128, len: u8, width: u8) {
+        let mut index = 0usize;
+        for depth in 0..len {
+            if self.nodes[index].terminal {
+                // Already covered by a shorter prefix.
+                return;
+            }
+            let bit = ((bits >> (width - 1 - depth)) & 1) as usize;
+            let child = self.nodes[index].children[bit];
+            index = if child == NONE {
+                self.nodes.push(Node::default());
+                let new = (self.nodes.len() - 1) as u32;
+                self.nodes[index].children[bit] = new;
+                new as usize
+            } else {
+                child as usize
+            };
+        }
+        let node = &mut self.nodes[index];
+        node.terminal = true;
+        // Longer prefixes below are now redundant.
+        node.children = [NONE; 2];
+    }
+
+    fn contains(&self, bits: u128, width: u8) -> bool {
+        let mut index = 0usize;
+        for depth in 0..width {
+            let node = &self.nodes[index];
+            if node.terminal {
+                return true;
+            }
+            let bit = ((bits >> (width - 1 - depth)) & 1) as usize;
+            match node.children[bit] {
+                NONE => return false,
+                child => index = child as usize,
+            }
+        }
+        self.nodes[index].terminal
+    }
+}
+
+/// A set of IPv4 and IPv6 networks with prefix-trie membership tests.
+#[derive(Clone, Default)]
+pub struct IpSet {
+    v4: Trie,
+    v6: Trie,
+    len: usize,
+}
+
+impl IpSet {
+    /// Add a network. Host bits beyond the prefix are ignored.
+    pub fn insert(&mut self, network: IpNetwork) {
+        match network {
+            IpNetwork::V4(net) => {
+                self.v4
+                    .insert(u32::from(net.network()) as u128, net.prefix(), 32)
+            }
+            IpNetwork::V6(net) => self.v6.insert(u128::from(net.network()), net.prefix(), 128),
+        }
+        self.len += 1;
+    }
+
+    /// Whether any network in the set contains `ip`.
+    pub fn contains(&self, ip: IpAddr) -> bool {
+        match ip {
+            IpAddr::V4(v4) => self.v4.contains(u32::from(v4) as u128, 32),
+            IpAddr::V6(v6) => match v6.to_ipv4_mapped() {
+                Some(v4) => self.v4.contains(u32::from(v4) as u128, 32),
+                None => self.v6.contains(u128::from(v6), 128),
+            },
+        }
+    }
+
+    /// Number of networks inserted, including those folded into shorter prefixes.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+}
+
+impl FromIterator<IpNetwork> for IpSet {
+    fn from_iter<I: IntoIterator<Item = IpNetwork>>(iter: I) -> Self {
+        let mut set = Self::default();
+        for network in iter {
+            set.insert(network);
+        }
+        set
+    }
+}
+
+impl std::fmt::Debug for IpSet {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("IpSet").field("len", &self.len).finish()
+    }
+}
+
+/// Parse CIDR strings (or bare addresses) into a set; `label` names the list in errors.
+pub fn parse_ip_set<'a>(
+    label: &str,
+    entries: impl IntoIterator<Item = &'a String>,
+) -> Result<IpSet, String> {
+    entries
+        .into_iter()
+        .map(|entry| {
+            entry
+                .trim()
+                .parse::<IpNetwork>()
+                .map_err(|e| format!("Invalid {label} IP network '{entry}': {e}"))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn set(entries: &[&str]) -> IpSet {
+        let entries: Vec<String> = entries.iter().map(|e| e.to_string()).collect();
+        parse_ip_set("test", &entries).unwrap()
+    }
+
+    fn ip(addr: &str) -> IpAddr {
+        addr.parse().unwrap()
+    }
+
+    #[test]
+    fn matches_ipv4_and_ipv6_prefixes() {
+        let set = set(&["10.0.0.0/8", "192.168.1.7", "2001:db8::/32", "::1/128"]);
+        assert!(set.contains(ip("10.200.3.4")));
+        assert!(set.contains(ip("192.168.1.7")));
+        assert!(!set.contains(ip("192.168.1.8")));
+        assert!(!set.contains(ip("11.0.0.1")));
+        assert!(set.contains(ip("2001:db8:ffff::1")));
+        assert!(!set.contains(ip("2001:db9::1")));
+        assert!(set.contains(ip("::1")));
+        assert!(set.contains(ip("::ffff:10.1.2.3")));
+        assert!(!IpSet::default().contains(ip("10.0.0.1")));
+    }
+
+    #[test]
+    fn shorter_prefixes_absorb_longer_ones() {
+        let set = set(&["10.1.2.0/24", "10.0.0.0/8", "10.9.9.9/32", "0.0.0.0/0"]);
+        assert_eq!(set.len(), 4);
+        assert!(set.v4.nodes[0].terminal);
+        assert!(set.contains(ip("203.0.113.1")));
+        assert!(!set.contains(ip("2001:db8::1")));
+    }
+
+    #[test]
+    fn large_sets_stay_exact() {
+        let mut set = IpSet::default();
+        for i in 0..20_000u32 {
+            let addr = std::net::Ipv4Addr::from(0x0a00_0000 | (i << 8));
+            set.insert(IpNetwork::new(IpAddr::V4(addr), 24).unwrap());
+        }
+        assert!(set.contains(ip("10.0.5.9")));
+        assert!(set.contains(ip("10.78.31.200")));
+        assert!(!set.contains(ip("10.78.32.1")));
+        assert!(parse_ip_set("test", &["not-a-cidr".to_string()]).is_err());
+    }
+}
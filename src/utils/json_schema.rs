@@ -0,0 +1,230 @@
+//! Validation of JSON values against the subset of JSON Schema used by plugin schemas.
+//!
+//! Supported keywords are `type`, `enum`, `minimum`, `maximum`, `minLength`,
+//! `maxLength`, `pattern`, `items`, `minItems`, `maxItems`, `properties`, `required`,
+//! `additionalProperties` and `anyOf`. Annotations such as `description` and `default`
+//! are ignored.
+
+use regex::Regex;
+use serde_json::Value as JsonValue;
+
+/// Check `value` against `schema`. The error names the first offending location as a
+/// JSON pointer, e.g. `/rules/0/weight: must be at least 0`.
+pub fn validate(schema: &JsonValue, value: &JsonValue) -> Result<(), String> {
+    check(schema, value, &mut String::new())
+}
+
+fn fail(path: &str, message: impl std::fmt::Display) -> Result<(), String> {
+    let path = if path.is_empty() { "/" } else { path };
+    Err(format!("{path}: {message}"))
+}
+
+fn check(schema: &JsonValue, value: &JsonValue, path: &mut String) -> Result<(), String> {
+    if let Some(types) = schema.get("type") {
+        let allowed: Vec<&str> = match types {
+            JsonValue::String(name) => vec![name.as_str()],
+            JsonValue::Array(names) => names.iter().filter_map(JsonValue::as_str).collect(),
+            _ => Vec::new(),
+        };
+        if !allowed.iter().any(|name| has_type(value, name)) {
+            return fail(path, format_args!("expected {}", allowed.join(" or ")));
+        }
+    }
+
+    if let Some(JsonValue::Array(options)) = schema.get("enum") {
+        if !options.contains(value) {
+            let options: Vec<String> = options.iter().map(JsonValue::to_string).collect();
+            return fail(path, format_args!("must be one of {}", options.join(", ")));
+        }
+    }
+
+    if let Some(alternatives) = schema.get("anyOf").and_then(JsonValue::as_array) {
+        let matched = alternatives
+            .iter()
+            .any(|alternative| check(alternative, value, &mut path.clone()).is_ok());
+        if !matched {
+            return fail(path, "does not match any allowed form");
+        }
+    }
+
+    match value {
+        JsonValue::Number(number) => check_number(schema, number.as_f64().unwrap_or(0.0), path),
+        JsonValue::String(text) => check_string(schema, text, path),
+        JsonValue::Array(items) => check_array(schema, items, path),
+        JsonValue::Object(object) => check_object(schema, object, path),
+        _ => Ok(()),
+    }
+}
+
+fn has_type(value: &JsonValue, name: &str) -> bool {
+    match name {
+        "null" => value.is_null(),
+        "boolean" => value.is_boolean(),
+        // Integers must be written without a fraction, as serde expects.
+        "integer" => value.is_i64() || value.is_u64(),
+        "number" => value.is_number(),
+        "string" => value.is_string(),
+        "array" => value.is_array(),
+        "object" => value.is_object(),
+        _ => false,
+    }
+}
+
+fn check_number(schema: &JsonValue, number: f64, path: &str) -> Result<(), String> {
+    if let Some(minimum) = schema.get("minimum").and_then(JsonValue::as_f64) {
+        if number < minimum {
+            return fail(path, format_args!("must be at least {minimum}"));
+        }
+    }
+    if let Some(maximum) = schema.get("maximum").and_then(JsonValue::as_f64) {
+        if number > maximum {
+            return fail(path, format_args!("must be at most {maximum}"));
+        }
+    }
+    Ok(())
+}
+
+fn check_string(schema: &JsonValue, text: &str, path: &str) -> Result<(), String> {
+    let length = text.chars().count() as u64;
+    if let Some(min) = schema.get("minLength").and_then(JsonValue::as_u64) {
+        if length < min {
+            return fail(path, format_args!("must be at least {min} characters long"));
+        }
+    }
+    if let Some(max) = schema.get("maxLength").and_then(JsonValue::as_u64) {
+        if length > max {
+            return fail(path, format_args!("must be at most {max} characters long"));
+        }
+    }
+    if let Some(pattern) = schema.get("pattern").and_then(JsonValue::as_str) {
+        let regex = Regex::new(pattern)
+            .map_err(|e| format!("{path}: invalid schema pattern '{pattern}': {e}"))?;
+        if !regex.is_match(text) {
+            return fail(path, format_args!("must match '{pattern}'"));
+        }
+    }
+    Ok(())
+}
+
+fn check_array(schema: &JsonValue, items: &[JsonValue], path: &mut String) -> Result<(), String> {
+    let count = items.len() as u64;
+    if let Some(min) = schema.get("minItems").and_then(JsonValue::as_u64) {
+        if count < min {
+            return fail(path, format_args!("must have at least {min} items"));
+        }
+    }
+    if let Some(max) = schema.get("maxItems").and_then(JsonValue::as_u64) {
+        if count > max {
+            return fail(path, format_args!("must have at most {max} items"));
+        }
+    }
+    if let Some(item_schema) = schema.get("items") {
+        for (index, item) in items.iter().enumerate() {
+            let len = path.len();
+            path.push_str(&format!("/{index}"));
+            check(item_schema, item, path)?;
+            path.truncate(len);
+        }
+    }
+    Ok(())
+}
+
+fn check_object(
+    schema: &JsonValue,
+    object: &serde_json::Map<String, JsonValue>,
+    path: &mut String,
+) -> Result<(), String> {
+    if let Some(required) = schema.get("required").and_then(JsonValue::as_array) {
+        if let Some(missing) = required
+            .iter()
+            .filter_map(JsonValue::as_str)
+            .find(|name| !object.contains_key(*name))
+        {
+            return fail(path, format_args!("missing required field '{missing}'"));
+        }
+    }
+
+    let properties = schema.get("properties").and_then(JsonValue::as_object);
+    for (name, value) in object {
+        let field_schema = match properties.and_then(|properties| properties.get(name)) {
+            Some(field_schema) => field_schema,
+            None => match schema.get("additionalProperties") {
+                Some(JsonValue::Bool(false)) => {
+                    return fail(path, format_args!("unknown field '{name}'"));
+                }
+                Some(extra) if extra.is_object() => extra,
+                _ => continue,
+            },
+        };
+        let len = path.len();
+        path.push('/');
+        path.push_str(&name.replace('~', "~0").replace('/', "~1"));
+        check(field_schema, value, path)?;
+        path.truncate(len);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn errors_name_the_offending_field() {
+        let schema = json!({
+            "type": "object",
+            "required": ["rules"],
+            "properties": {
+                "mode": {"type": "string", "enum": ["block", "detect"]},
+                "rules": {
+                    "type": "array",
+                    "minItems": 1,
+                    "items": {
+                        "type": "object",
+                        "properties": {"weight": {"type": "integer", "minimum": 0}},
+                        "additionalProperties": false
+                    }
+                }
+            }
+        });
+
+        assert!(validate(&schema, &json!({"rules": [{"weight": 1}]})).is_ok());
+        assert_eq!(
+            validate(&schema, &json!({})).unwrap_err(),
+            "/: missing required field 'rules'"
+        );
+        assert_eq!(
+            validate(&schema, &json!({"rules": [{"weight": -1}]})).unwrap_err(),
+            "/rules/0/weight: must be at least 0"
+        );
+        assert_eq!(
+            validate(&schema, &json!({"rules": [{"weight": 1.5}]})).unwrap_err(),
+            "/rules/0/weight: expected integer"
+        );
+        assert_eq!(
+            validate(&schema, &json!({"rules": [{"wieght": 1}]})).unwrap_err(),
+            "/rules/0: unknown field 'wieght'"
+        );
+        assert!(validate(&schema, &json!({"rules": [], "mode": "block"}))
+            .unwrap_err()
+            .starts_with("/rules: must have at least 1"));
+        assert!(validate(&schema, &json!({"rules": [{}], "mode": "log"})).is_err());
+    }
+
+    #[test]
+    fn any_of_accepts_each_alternative() {
+        let schema = json!({
+            "type": "array",
+            "items": {"anyOf": [
+                {"type": "string", "minLength": 1},
+                {"type": "object", "required": ["key"]}
+            ]}
+        });
+        assert!(validate(&schema, &json!(["a", {"key": "b"}])).is_ok());
+        assert_eq!(
+            validate(&schema, &json!([{"expires_at": 1}])).unwrap_err(),
+            "/0: does not match any allowed form"
+        );
+    }
+}
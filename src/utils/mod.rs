@@ -1,2 +1,9 @@
+pub mod compression;
+pub mod grpc_bridge;
+pub mod htpasswd;
+pub mod ip_set;
+pub mod json_schema;
 pub mod request;
 pub mod response;
+pub mod secret;
+pub mod signed_token;
@@ -1,5 +1,6 @@
 use std::{borrow::Cow, net::IpAddr};
 
+use once_cell::sync::Lazy;
 use pingora_http::RequestHeader;
 use pingora_proxy::Session;
 
@@ -45,13 +46,8 @@ fn handle_vars<'a>(session: &'a mut Session, key: &str) -> Cow<'a, str> {
                 .map_or_else(|| session.req_header().uri.path(), |pq| pq.as_str()),
         ),
         "query_string" => Cow::Borrowed(session.req_header().uri.query().unwrap_or_default()),
-        "remote_addr" => session
-            .client_addr()
-            .and_then(|addr| addr.as_inet())
-            .map_or_else(
-                || Cow::Borrowed(""),
-                |inet| Cow::Owned(inet.ip().to_string()),
-            ),
+        "remote_addr" => get_direct_client_ip(session)
+            .map_or_else(|| Cow::Borrowed(""), |ip| Cow::Owned(ip.to_string())),
         "remote_port" => session
             .client_addr()
             .and_then(|s| s.as_inet())
@@ -128,6 +124,29 @@ pub fn remove_query_from_header(
     Ok(())
 }
 
+/// Removes a leading path `prefix` from the request URI, keeping the query string.
+///
+/// `/api` turns `/api/users` into `/users` and `/api` into `/`; paths outside the
+/// prefix, such as `/apiv2`, are left unchanged.
+pub fn strip_path_prefix(
+    req_header: &mut RequestHeader,
+    prefix: &str,
+) -> Result<(), http::uri::InvalidUri> {
+    let path = req_header.uri.path();
+    let rest = match path.strip_prefix(prefix) {
+        Some("") => "/",
+        Some(rest) if rest.starts_with('/') => rest,
+        _ => return Ok(()),
+    };
+    let new_path = match req_header.uri.query() {
+        Some(query) => format!("{rest}?{query}"),
+        None => rest.to_string(),
+    };
+    new_path
+        .parse::<http::Uri>()
+        .map(|uri| req_header.set_uri(uri))
+}
+
 /// Retrieves the value of a specific header from the request.
 ///
 /// Returns `None` if the header is not present or its value is not valid UTF-8.
@@ -138,6 +157,27 @@ pub fn get_req_header_value<'a>(req_header: &'a RequestHeader, key: &str) -> Opt
         .and_then(|value| value.to_str().ok())
 }
 
+/// Whether the request carries a body: chunked, or with a non-zero `Content-Length`.
+pub fn request_has_body(req_header: &RequestHeader) -> bool {
+    req_header
+        .headers
+        .contains_key(http::header::TRANSFER_ENCODING)
+        || get_req_header_value(req_header, "content-length")
+            .and_then(|len| len.trim().parse::<usize>().ok())
+            .is_some_and(|len| len > 0)
+}
+
+/// Reframe an upstream request whose body length changes: drops `Content-Length` and,
+/// for HTTP/1.x upstreams, adds `Transfer-Encoding: chunked`. HTTP/2 frames the body
+/// itself and does not allow `Transfer-Encoding`.
+pub fn set_chunked_body(upstream_request: &mut RequestHeader) -> pingora_error::Result<()> {
+    upstream_request.remove_header(&http::header::CONTENT_LENGTH);
+    if upstream_request.version != http::Version::HTTP_2 {
+        upstream_request.insert_header(http::header::TRANSFER_ENCODING, "chunked")?;
+    }
+    Ok(())
+}
+
 /// Retrieves the value of a specific cookie from the `Cookie` header.
 ///
 /// Parses the `Cookie` header string manually. This is sufficient for simple
@@ -239,18 +279,150 @@ pub fn get_request_host(header: &RequestHeader) -> Option<&str> {
     None
 }
 
-/// Returns the peer address without formatting it as a string.
+/// Retrieves the port the request was addressed to.
+///
+/// Prefers the port from the URI, then the `Host` header port, then the local port
+/// of the listener that accepted the connection.
+pub fn get_request_port(session: &Session) -> Option<u16> {
+    let header = session.req_header();
+    header
+        .uri
+        .port_u16()
+        .or_else(|| host_header_port(header))
+        .or_else(|| {
+            session
+                .server_addr()
+                .and_then(|addr| addr.as_inet())
+                .map(|inet| inet.port())
+        })
+}
+
+/// Port of the `Host` header, handling bracketed IPv6 hosts (`[::1]:8080`).
+fn host_header_port(header: &RequestHeader) -> Option<u16> {
+    let host = header.headers.get(http::header::HOST)?.to_str().ok()?;
+    let port = match host.rfind(']') {
+        Some(bracket_end) => host[bracket_end + 1..].strip_prefix(':')?,
+        None => host.rsplit_once(':')?.1,
+    };
+    port.parse().ok()
+}
+
+/// Carries the original client address on requests the gateway sends to itself, such
+/// as batch sub-requests. Removed before requests are forwarded upstream.
+pub const GATEWAY_CLIENT_HEADER: &str = "x-pingsix-client-addr";
+
+/// Proves a [`GATEWAY_CLIENT_HEADER`] value was set by this process.
+static GATEWAY_TOKEN: Lazy<String> = Lazy::new(|| uuid::Uuid::new_v4().simple().to_string());
+
+/// The [`GATEWAY_CLIENT_HEADER`] value for a request made on behalf of `client`.
+pub fn gateway_client_value(client: IpAddr) -> String {
+    format!("{client}; {}", *GATEWAY_TOKEN)
+}
+
+/// Returns the client address without formatting it as a string.
+///
+/// This is the peer address, except on connections the gateway opened to itself, where
+/// the address in [`GATEWAY_CLIENT_HEADER`] is used instead.
 pub fn get_direct_client_ip(session: &Session) -> Option<IpAddr> {
-    session
-        .client_addr()
+    let peer = session.client_addr()?.as_inet()?.ip();
+    let local = session
+        .server_addr()
         .and_then(|addr| addr.as_inet())
-        .map(|inet| inet.ip())
+        .map(|inet| inet.ip());
+    Some(gateway_client_ip(peer, local, session.req_header()).unwrap_or(peer))
+}
+
+/// The address in [`GATEWAY_CLIENT_HEADER`] when `peer` is the gateway's own host, i.e. a
+/// loopback address or the address of the listener it connected to, and the value
+/// carries this process's token.
+fn gateway_client_ip(peer: IpAddr, local: Option<IpAddr>, req: &RequestHeader) -> Option<IpAddr> {
+    if !peer.is_loopback() && Some(peer) != local {
+        return None;
+    }
+    let (client, token) = get_req_header_value(req, GATEWAY_CLIENT_HEADER)?.split_once(';')?;
+    if token.trim() != GATEWAY_TOKEN.as_str() {
+        return None;
+    }
+    client.trim().parse().ok()
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    #[test]
+    fn chunked_framing_only_for_http1_upstreams() {
+        for (version, chunked) in [
+            (http::Version::HTTP_11, true),
+            (http::Version::HTTP_2, false),
+        ] {
+            let mut req = RequestHeader::build("POST", b"/", None).unwrap();
+            req.set_version(version);
+            req.insert_header("Content-Length", "12").unwrap();
+            set_chunked_body(&mut req).unwrap();
+            assert!(req.headers.get("content-length").is_none());
+            assert_eq!(
+                req.headers.contains_key("transfer-encoding"),
+                chunked,
+                "{version:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn parses_host_header_port() {
+        let mut req = RequestHeader::build("GET", b"/", None).unwrap();
+        for (host, port) in [
+            ("example.com:8443", Some(8443)),
+            ("example.com", None),
+            ("[::1]:8080", Some(8080)),
+            ("[::1]", None),
+        ] {
+            req.insert_header("Host", host).unwrap();
+            assert_eq!(host_header_port(&req), port, "{host}");
+        }
+    }
+
+    #[test]
+    fn gateway_client_header_is_honoured_only_from_the_gateway_itself() {
+        let client: IpAddr = "203.0.113.9".parse().unwrap();
+        let listener: IpAddr = "10.0.0.5".parse().unwrap();
+        let loopback = "127.0.0.1".parse().unwrap();
+        let mut req = RequestHeader::build("GET", b"/", None).unwrap();
+        req.insert_header(GATEWAY_CLIENT_HEADER, gateway_client_value(client))
+            .unwrap();
+
+        assert_eq!(gateway_client_ip(loopback, None, &req), Some(client));
+        assert_eq!(
+            gateway_client_ip(listener, Some(listener), &req),
+            Some(client)
+        );
+        let remote = "198.51.100.1".parse().unwrap();
+        assert_eq!(gateway_client_ip(remote, Some(listener), &req), None);
+
+        req.insert_header(GATEWAY_CLIENT_HEADER, "203.0.113.9; forged")
+            .unwrap();
+        assert_eq!(gateway_client_ip(loopback, None, &req), None);
+        req.insert_header(GATEWAY_CLIENT_HEADER, "203.0.113.9")
+            .unwrap();
+        assert_eq!(gateway_client_ip(loopback, None, &req), None);
+    }
+
+    #[test]
+    fn strips_path_prefix_on_segment_boundary() {
+        for (uri, expected) in [
+            ("/api/users?page=2", "/users?page=2"),
+            ("/api", "/"),
+            ("/api?x=1", "/?x=1"),
+            ("/apiv2/users", "/apiv2/users"),
+            ("/other", "/other"),
+        ] {
+            let mut req = RequestHeader::build("GET", uri.as_bytes(), None).unwrap();
+            strip_path_prefix(&mut req, "/api").unwrap();
+            assert_eq!(req.uri.to_string(), expected, "{uri}");
+        }
+    }
+
     #[test]
     fn removes_named_cookie_from_every_cookie_header() {
         let mut req = RequestHeader::build("GET", b"/", None).unwrap();
@@ -16,6 +16,43 @@ pub mod content_type {
     pub const APPLICATION_JSON: &str = "application/json";
 }
 
+/// Content types of long-lived streaming responses (server-sent events and NDJSON).
+const STREAMING_CONTENT_TYPES: &[&str] = &["text/event-stream", "application/x-ndjson"];
+
+/// Whether a response header carries a streaming content type. gRPC responses count
+/// too: their length-prefixed messages must be relayed as they arrive.
+pub fn is_streaming_response(resp: &ResponseHeader) -> bool {
+    resp.headers
+        .get(header::CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.split(';').next())
+        .is_some_and(|mime| {
+            let mime = mime.trim();
+            is_grpc_content_type(mime)
+                || STREAMING_CONTENT_TYPES
+                    .iter()
+                    .any(|t| mime.eq_ignore_ascii_case(t))
+        })
+}
+
+/// `application/grpc` and its `+proto`/`+json` variants.
+fn is_grpc_content_type(mime: &str) -> bool {
+    let mime = mime.to_ascii_lowercase();
+    mime == "application/grpc" || mime.starts_with("application/grpc+")
+}
+
+/// The `grpc-status` of a gRPC response's trailers, or of its headers for a
+/// trailers-only response.
+pub fn grpc_status(headers: &http::HeaderMap) -> Option<u32> {
+    headers
+        .get("grpc-status")?
+        .to_str()
+        .ok()?
+        .trim()
+        .parse()
+        .ok()
+}
+
 /// Unified response builder for different response types
 pub struct ResponseBuilder;
 
@@ -159,6 +196,31 @@ mod tests {
         assert_eq!(response.body(), expected.as_bytes());
     }
 
+    #[test]
+    fn test_streaming_response_detection() {
+        let mut resp = ResponseHeader::build(200, None).unwrap();
+        assert!(!is_streaming_response(&resp));
+        resp.insert_header(header::CONTENT_TYPE, "Text/Event-Stream; charset=utf-8")
+            .unwrap();
+        assert!(is_streaming_response(&resp));
+        resp.insert_header(header::CONTENT_TYPE, "text/html")
+            .unwrap();
+        assert!(!is_streaming_response(&resp));
+        resp.insert_header(header::CONTENT_TYPE, "application/grpc+proto")
+            .unwrap();
+        assert!(is_streaming_response(&resp));
+    }
+
+    #[test]
+    fn test_grpc_status() {
+        let mut trailers = http::HeaderMap::new();
+        assert_eq!(grpc_status(&trailers), None);
+        trailers.insert("grpc-status", HeaderValue::from_static("14"));
+        assert_eq!(grpc_status(&trailers), Some(14));
+        trailers.insert("grpc-status", HeaderValue::from_static("unavailable"));
+        assert_eq!(grpc_status(&trailers), None);
+    }
+
     #[test]
     fn test_common_errors() {
         let response = CommonErrors::bad_request("Missing parameter");
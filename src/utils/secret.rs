@@ -0,0 +1,15 @@
+//! Secret references in configuration values.
+
+/// Prefix of a value read from the environment variable named after it.
+pub const ENV_PREFIX: &str = "$env://";
+
+/// Replace `$env://NAME` with the value of the environment variable `NAME`; other
+/// values are returned unchanged.
+pub fn resolve(value: &str) -> Result<String, String> {
+    match value.strip_prefix(ENV_PREFIX) {
+        Some(name) => {
+            std::env::var(name).map_err(|_| format!("secret {ENV_PREFIX}{name} is not set"))
+        }
+        None => Ok(value.to_string()),
+    }
+}
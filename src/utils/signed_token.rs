@@ -0,0 +1,77 @@
+//! Expiring tokens signed with HMAC-SHA256, used to unlock debugging features for
+//! single requests.
+//!
+//! A token is `<expires>.<hex HMAC-SHA256(secret, message)>`, where `expires` is in UNIX
+//! seconds and `message` is `expires`, or `<subject>.<expires>` for a token bound to a
+//! subject such as an upstream node.
+
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+
+type HmacSha256 = Hmac<Sha256>;
+
+fn mac(secret: &str, subject: Option<&str>, expires: &str) -> Option<HmacSha256> {
+    let mut mac = HmacSha256::new_from_slice(secret.as_bytes()).ok()?;
+    if let Some(subject) = subject {
+        mac.update(subject.as_bytes());
+        mac.update(b".");
+    }
+    mac.update(expires.as_bytes());
+    Some(mac)
+}
+
+/// Token for `subject` valid until `expires`.
+#[cfg(test)]
+pub fn sign(secret: &str, subject: Option<&str>, expires: u64) -> String {
+    let expires = expires.to_string();
+    let signature = mac(secret, subject, &expires)
+        .map(|mac| hex::encode(mac.finalize().into_bytes()))
+        .unwrap_or_default();
+    format!("{expires}.{signature}")
+}
+
+/// Whether `token` was signed with `secret` for `subject` and is still valid at `now`.
+pub fn verify(secret: &str, subject: Option<&str>, token: &str, now: u64) -> bool {
+    let Some((expires, signature)) = token.trim().split_once('.') else {
+        return false;
+    };
+    if !expires.parse::<u64>().is_ok_and(|expires| expires > now) {
+        return false;
+    }
+    let Ok(signature) = hex::decode(signature) else {
+        return false;
+    };
+    mac(secret, subject, expires).is_some_and(|mac| mac.verify_slice(&signature).is_ok())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SECRET: &str = "0123456789abcdef";
+
+    #[test]
+    fn tokens_must_be_signed_and_unexpired() {
+        assert!(verify(SECRET, None, &sign(SECRET, None, 2_000), 1_000));
+        assert!(!verify(SECRET, None, &sign(SECRET, None, 1_000), 1_000));
+        assert!(!verify(
+            SECRET,
+            None,
+            &sign("another-secret-value", None, 2_000),
+            1_000
+        ));
+        let forged = sign(SECRET, None, 2_000).replacen("2000", "9000", 1);
+        assert!(!verify(SECRET, None, &forged, 1_000));
+        assert!(!verify(SECRET, None, "2000", 1_000));
+        assert!(!verify(SECRET, None, "2000.zz", 1_000));
+    }
+
+    #[test]
+    fn tokens_are_bound_to_their_subject() {
+        let node = Some("10.0.0.5:8080");
+        let token = sign(SECRET, node, 2_000);
+        assert!(verify(SECRET, node, &token, 1_000));
+        assert!(!verify(SECRET, Some("10.0.0.6:8080"), &token, 1_000));
+        assert!(!verify(SECRET, None, &token, 1_000));
+    }
+}
@@ -0,0 +1,185 @@
+//! Route `timeout.total` against a keepalive upstream (static YAML, no etcd).
+
+mod common;
+
+use std::io::{BufRead, BufReader, Write};
+use std::net::TcpListener;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use common::*;
+
+/// Request seen by [`KeepaliveUpstream`]: connection number, path and `Connection` header.
+type Seen = (usize, String, String);
+
+/// HTTP/1.1 upstream that keeps connections open unless asked to close them, and
+/// never answers `/deadline/slow`.
+struct KeepaliveUpstream {
+    port: u16,
+    seen: Arc<Mutex<Vec<Seen>>>,
+}
+
+impl KeepaliveUpstream {
+    fn start() -> Self {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let seen = Arc::new(Mutex::new(Vec::new()));
+        let seen_c = seen.clone();
+        thread::spawn(move || {
+            for (conn, stream) in listener.incoming().enumerate() {
+                let Ok(stream) = stream else { break };
+                let seen = seen_c.clone();
+                thread::spawn(move || serve(conn, stream, &seen));
+            }
+        });
+        Self { port, seen }
+    }
+
+    fn seen(&self) -> Vec<Seen> {
+        self.seen.lock().unwrap().clone()
+    }
+}
+
+fn serve(conn: usize, stream: std::net::TcpStream, seen: &Mutex<Vec<Seen>>) {
+    let mut writer = stream.try_clone().unwrap();
+    let mut reader = BufReader::new(stream);
+    loop {
+        let mut request_line = String::new();
+        if reader.read_line(&mut request_line).unwrap_or(0) == 0 {
+            return;
+        }
+        let path = request_line
+            .split_whitespace()
+            .nth(1)
+            .unwrap_or_default()
+            .to_string();
+        let mut connection = String::new();
+        loop {
+            let mut line = String::new();
+            if reader.read_line(&mut line).unwrap_or(0) == 0 {
+                return;
+            }
+            if line == "\r\n" {
+                break;
+            }
+            if let Some((name, value)) = line.split_once(':') {
+                if name.eq_ignore_ascii_case("connection") {
+                    connection = value.trim().to_ascii_lowercase();
+                }
+            }
+        }
+        seen.lock()
+            .unwrap()
+            .push((conn, path.clone(), connection.clone()));
+        if path == "/deadline/slow" {
+            thread::sleep(Duration::from_secs(5));
+            return;
+        }
+        let resp = "HTTP/1.1 200 OK\r\nContent-Length: 2\r\n\r\nok";
+        if writer.write_all(resp.as_bytes()).is_err() || connection.contains("close") {
+            return;
+        }
+    }
+}
+
+/// Status of a GET sent without `Connection: close`, which Pingora would forward and
+/// which would keep the upstream connection out of the pool on its own.
+fn get(addr: &str, path: &str) -> Option<u16> {
+    let mut stream = std::net::TcpStream::connect(addr).ok()?;
+    stream.set_read_timeout(Some(Duration::from_secs(3))).ok()?;
+    write!(stream, "GET {path} HTTP/1.1\r\nHost: localhost\r\n\r\n").ok()?;
+    let mut reader = BufReader::new(stream);
+    let mut status_line = String::new();
+    reader.read_line(&mut status_line).ok()?;
+    status_line.split_whitespace().nth(1)?.parse().ok()
+}
+
+fn spawn(upstream: &KeepaliveUpstream) -> PingsixGuard {
+    let listen_port = random_port();
+    let status_port = random_port();
+    let yaml = format!(
+        r#"{}
+pingsix:
+  listeners:
+    - address: "127.0.0.1:{listen_port}"
+  status:
+    address: "127.0.0.1:{status_port}"
+
+routes:
+  - id: "plain"
+    uri: /plain
+    upstream:
+      nodes:
+        "127.0.0.1:{up}": 1
+      type: roundrobin
+  - id: "deadline"
+    uri: /deadline/{{*rest}}
+    timeout:
+      connect: 5
+      send: 5
+      read: 5
+      total: 1
+    upstream:
+      nodes:
+        "127.0.0.1:{up}": 1
+      type: roundrobin
+"#,
+        pingora_header(listen_port),
+        up = upstream.port
+    );
+    let config_path = write_config(listen_port, &yaml);
+    let guard = PingsixGuard::new(
+        listen_port,
+        config_path.clone(),
+        spawn_pingsix(&config_path),
+    );
+    assert!(
+        wait_until_ready(status_port, Duration::from_secs(15)),
+        "static config should become ready"
+    );
+    guard
+}
+
+#[test]
+fn deadline_connections_are_not_pooled_for_later_requests() {
+    let upstream = KeepaliveUpstream::start();
+    let guard = spawn(&upstream);
+    let addr = format!("127.0.0.1:{}", guard.listen_port());
+
+    // Pools a connection, which the deadline request then picks up.
+    assert_eq!(get(&addr, "/plain"), Some(200));
+    assert_eq!(get(&addr, "/deadline/fast"), Some(200));
+    // Past the deadline a leftover watch would have fired on the pooled socket.
+    thread::sleep(Duration::from_millis(1500));
+    assert_eq!(get(&addr, "/plain"), Some(200));
+    assert_eq!(get(&addr, "/plain"), Some(200));
+
+    let seen = upstream.seen();
+    let paths: Vec<&str> = seen.iter().map(|(_, path, _)| path.as_str()).collect();
+    assert_eq!(paths, ["/plain", "/deadline/fast", "/plain", "/plain"]);
+    let conns: Vec<usize> = seen.iter().map(|(conn, _, _)| *conn).collect();
+    assert_eq!(conns[0], conns[1], "the deadline request reused the pool");
+    assert_eq!(
+        seen[1].2, "close",
+        "the deadline request closes its connection"
+    );
+    assert_ne!(conns[2], conns[1], "a closed connection is not reused");
+    assert_eq!(conns[3], conns[2], "plain connections stay pooled");
+}
+
+#[test]
+fn stalled_upstream_gets_504_at_the_deadline() {
+    let upstream = KeepaliveUpstream::start();
+    let guard = spawn(&upstream);
+    let addr = format!("127.0.0.1:{}", guard.listen_port());
+
+    let started = Instant::now();
+    assert_eq!(get(&addr, "/deadline/slow"), Some(504));
+    assert!(
+        started.elapsed() < Duration::from_secs(3),
+        "{:?}",
+        started.elapsed()
+    );
+    assert_eq!(get(&addr, "/plain"), Some(200));
+}
@@ -0,0 +1,73 @@
+//! `SIGHUP` re-reads the static YAML file and republishes its routes.
+
+mod common;
+
+use std::time::Duration;
+
+use common::*;
+
+fn config_yaml(listen_port: u16, status_port: u16, upstream: u16, uris: &[&str]) -> String {
+    let mut yaml = format!(
+        r#"{}
+pingsix:
+  listeners:
+    - address: "127.0.0.1:{listen_port}"
+  status:
+    address: "127.0.0.1:{status_port}"
+
+routes:
+"#,
+        pingora_header(listen_port)
+    );
+    for (i, uri) in uris.iter().enumerate() {
+        yaml.push_str(&format!(
+            r#"  - id: "r{i}"
+    uri: {uri}
+    upstream:
+      nodes:
+        "127.0.0.1:{upstream}": 1
+      type: roundrobin
+"#
+        ));
+    }
+    yaml
+}
+
+#[test]
+fn sighup_publishes_routes_from_the_rewritten_file() {
+    let upstream = MockUpstream::start(MockUpstreamConfig::default());
+    let listen_port = random_port();
+    let status_port = random_port();
+    let config_path = write_config(
+        listen_port,
+        &config_yaml(listen_port, status_port, upstream.port, &["/a"]),
+    );
+    let mut guard = PingsixGuard::new(
+        listen_port,
+        config_path.clone(),
+        spawn_pingsix(&config_path),
+    );
+    assert!(
+        wait_until_ready(status_port, Duration::from_secs(15)),
+        "static config should become ready"
+    );
+    let addr = format!("127.0.0.1:{listen_port}");
+    assert_eq!(http_status(&addr, "/a"), Some(200));
+    assert_eq!(http_status(&addr, "/b"), Some(404));
+
+    std::fs::write(
+        &config_path,
+        config_yaml(listen_port, status_port, upstream.port, &["/a", "/b"]),
+    )
+    .unwrap();
+    unsafe {
+        libc::kill(guard.child_mut().id() as i32, libc::SIGHUP);
+    }
+
+    assert!(
+        wait_until_proxy_body(listen_port, "/b", "upstream-ok", Duration::from_secs(10)),
+        "SIGHUP should publish the new route\n{}",
+        guard.child_logs()
+    );
+    assert_eq!(http_status(&addr, "/a"), Some(200));
+}
@@ -26,6 +26,7 @@ fn static_cache_default_applies_before_plugin_build() {
             connect: 5,
             send: 5,
             read: 5,
+            total: None,
         }),
         dns_resolution_timeout: 3,
         dns_refresh_interval: Some(7),
@@ -51,6 +52,7 @@ fn static_cache_default_applies_before_plugin_build() {
             connect: 5,
             send: 5,
             read: 5,
+            total: None,
         })
     );
 